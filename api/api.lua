@@ -17,7 +17,8 @@ export type BlockDefinition = {
   name: string,
   description: string,
   textures: Array<string>,
-  drawtype: number
+  drawtype: number,
+  mod_origin: string?
 }
 
 export type ItemDefinition = {
@@ -25,20 +26,57 @@ export type ItemDefinition = {
   description: string,
   readable_name: string,
   textures: Array<string>,
-  drawtype: number
+  drawtype: number,
+  mod_origin: string?
 }
 
 -- A fancy closure.
 export type OnTick = (delta: number) -> nil
 
--- Singleton instances of raw data.
-_G.blocks  = _G.blocks  or {}
-_G.items   = _G.items   or {}
-_G.on_tick = _G.on_tick or {}
+-- The closure and who registered it, so errors can be blamed on the right mod.
+export type RegisteredOnTick = {
+  mod_name: string,
+  on_tick: OnTick
+}
+
+-- Runs when a new map area is made. minp and maxp are vectors, seed is the area's seed.
+export type OnGenerated = (minp: any, maxp: any, seed: number) -> nil
 
-local blocks:  {[string] : BlockDefinition} = _G.blocks
-local items:   {[string] : ItemDefinition}  = _G.items
-local on_tick: Array<OnTick>                = _G.on_tick
+export type RegisteredOnGenerated = {
+  mod_name: string,
+  on_generated: OnGenerated
+}
+
+-- Runs when a maintenance task like clear_entities stops. cancelled is if an admin stopped it early.
+export type OnMaintenanceDone = (name: string, cancelled: boolean) -> nil
+
+export type RegisteredOnMaintenanceDone = {
+  mod_name: string,
+  on_maintenance_done: OnMaintenanceDone
+}
+
+-- Runs once when the server shuts down cleanly, the place to save what a mod needs to keep.
+export type OnShutdown = () -> nil
+
+export type RegisteredOnShutdown = {
+  mod_name: string,
+  on_shutdown: OnShutdown
+}
+
+-- Singleton instances of raw data.
+_G.blocks       = _G.blocks       or {}
+_G.items        = _G.items        or {}
+_G.on_tick      = _G.on_tick      or {}
+_G.on_generated = _G.on_generated or {}
+_G.on_maintenance_done = _G.on_maintenance_done or {}
+_G.on_shutdown  = _G.on_shutdown  or {}
+
+local blocks:       {[string] : BlockDefinition}  = _G.blocks
+local items:        {[string] : ItemDefinition}   = _G.items
+local on_tick:      Array<RegisteredOnTick>       = _G.on_tick
+local on_generated: Array<RegisteredOnGenerated>  = _G.on_generated
+local on_maintenance_done: Array<RegisteredOnMaintenanceDone> = _G.on_maintenance_done
+local on_shutdown:  Array<RegisteredOnShutdown>   = _G.on_shutdown
 
 ----------
 -- Now we can ship the rest of the codebase back to the mod as a module.
@@ -52,6 +90,11 @@ local minetest = {}
 minetest = _G.minetest or {}
 _G.minetest = minetest
 
+-- The engine implements get_current_modname, it is nil outside of mod loading.
+local function current_mod_name(): string
+  return minetest.get_current_modname() or "unknown"
+end
+
 minetest.draw_type = {
   air       = 0,
   regular   = 1,
@@ -63,6 +106,7 @@ function minetest.register_block(definition: BlockDefinition)
   if (blocks[definition.name] ~= nil) then
     error(definition.name .. " is already a registered block.")
   end
+  definition.mod_origin = current_mod_name()
   blocks[definition.name] = definition
   print("minetest: registered block [" .. definition.name .. "]")
 end
@@ -74,7 +118,35 @@ function minetest.register_item(definition: ItemDefinition)
 end
 
 function minetest.register_on_tick(tick_closure: OnTick)
-  insert(on_tick, tick_closure)
+  insert(on_tick, {
+    mod_name = current_mod_name(),
+    on_tick = tick_closure
+  })
+end
+
+-- The same world seed always hands the same area the same seed.
+function minetest.register_on_generated(generated_closure: OnGenerated)
+  insert(on_generated, {
+    mod_name = current_mod_name(),
+    on_generated = generated_closure
+  })
+end
+
+
+-- Start one with minetest.clear_objects(), stop it with minetest.cancel_maintenance().
+function minetest.register_on_maintenance_done(done_closure: OnMaintenanceDone)
+  insert(on_maintenance_done, {
+    mod_name = current_mod_name(),
+    on_maintenance_done = done_closure
+  })
+end
+
+-- Ctrl+C, kill, and /shutdown all run these, a crash doesn't.
+function minetest.register_on_shutdown(shutdown_closure: OnShutdown)
+  insert(on_shutdown, {
+    mod_name = current_mod_name(),
+    on_shutdown = shutdown_closure
+  })
 end
 
 
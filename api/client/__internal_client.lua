@@ -43,11 +43,14 @@ local minetest = require("api/api")
 
 local old_time_stamp: number = clock()
 
-local on_tick: minetest.Array<minetest.OnTick> = _G.on_tick
+local on_tick: minetest.Array<minetest.RegisteredOnTick> = _G.on_tick
 
 local function do_on_tick(delta: number)
-  for _,func in ipairs(on_tick) do
-    func(delta)
+  for _,registered in ipairs(on_tick) do
+    local success, err = pcall(registered.on_tick, delta)
+    if (not success) then
+      error("minetest: mod [" .. registered.mod_name .. "] on_tick failed: " .. tostring(err))
+    end
   end
 end
 
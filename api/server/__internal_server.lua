@@ -43,11 +43,15 @@ local minetest = require("api/api")
 
 local old_time_stamp: number = clock()
 
-local on_tick: minetest.Array<minetest.OnTick> = _G.on_tick
+local on_tick: minetest.Array<minetest.RegisteredOnTick> = _G.on_tick
 
+-- Like on_generated, one broken mod doesn't stop everyone else's tick.
 local function do_on_tick(delta: number)
-  for _,func in ipairs(on_tick) do
-    func(delta)
+  for _,registered in ipairs(on_tick) do
+    local success, err = pcall(registered.on_tick, delta)
+    if (not success) then
+      print("minetest: mod [" .. registered.mod_name .. "] on_tick failed: " .. tostring(err))
+    end
   end
 end
 
@@ -61,4 +65,52 @@ _G.engine_on_tick_function = function(delta: number)
   do_on_tick(delta)
 
   old_time_stamp = time_stamp
-end
\ No newline at end of file
+end
+
+
+----------
+-- Map generation.
+
+local on_generated: minetest.Array<minetest.RegisteredOnGenerated> = _G.on_generated
+
+-- One broken mod shouldn't leave a hole in the map for everyone else.
+-- So errors are printed and the rest of the callbacks still run.
+_G.engine_on_generated_function = function(minp: any, maxp: any, seed: number)
+  for _,registered in ipairs(on_generated) do
+    local success, err = pcall(registered.on_generated, minp, maxp, seed)
+    if (not success) then
+      print("minetest: mod [" .. registered.mod_name .. "] on_generated failed: " .. tostring(err))
+    end
+  end
+end
+
+
+----------
+-- Maintenance.
+
+local on_maintenance_done: minetest.Array<minetest.RegisteredOnMaintenanceDone> = _G.on_maintenance_done
+
+_G.engine_on_maintenance_done_function = function(name: string, cancelled: boolean)
+  for _,registered in ipairs(on_maintenance_done) do
+    local success, err = pcall(registered.on_maintenance_done, name, cancelled)
+    if (not success) then
+      print("minetest: mod [" .. registered.mod_name .. "] on_maintenance_done failed: " .. tostring(err))
+    end
+  end
+end
+
+
+----------
+-- Shutdown.
+
+local on_shutdown: minetest.Array<minetest.RegisteredOnShutdown> = _G.on_shutdown
+
+-- A mod that fails to save shouldn't stop the others from saving.
+_G.engine_on_shutdown_function = function()
+  for _,registered in ipairs(on_shutdown) do
+    local success, err = pcall(registered.on_shutdown)
+    if (not success) then
+      print("minetest: mod [" .. registered.mod_name .. "] on_shutdown failed: " .. tostring(err))
+    end
+  end
+end
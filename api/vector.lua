@@ -18,8 +18,15 @@ export type Vec3 = {
 ----------
 -- Implementation.
 
+-- The engine creates the glam backed vector functions:
+-- vector.new, vector.add, vector.subtract, vector.length,
+-- vector.normalize, vector.distance
+-- These are extended here.
 local vector = {}
 
+vector = _G.vector or {}
+_G.vector = vector
+
 function vector.vec2(x: number, y: number): Vec2
   return {
     x = x,
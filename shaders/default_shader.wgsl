@@ -73,7 +73,26 @@ var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
 
+// ! This must match ALPHA_CUTOUT_THRESHOLD in alpha_mode.rs !
+const ALPHA_CUTOUT_THRESHOLD: f32 = 0.5;
+
+fn shade(in: VertexOutput) -> vec4<f32> {
+    // The vertex color tints the texel. White vertices leave it unchanged.
+    return textureSample(t_diffuse, s_diffuse, in.texture_coordinates) * colorBuffer.rgb * vec4<f32>(in.color, 1.0);
+}
+
+// AlphaMode::Opaque and AlphaMode::Blend.
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.texture_coordinates) * colorBuffer.rgb; //* vec4<f32>(in.color, 1.0);
+    return shade(in);
+}
+
+// AlphaMode::Cutout.
+@fragment
+fn fs_cutout(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = shade(in);
+    if color.a < ALPHA_CUTOUT_THRESHOLD {
+        discard;
+    }
+    return vec4<f32>(color.rgb, 1.0);
 }
\ No newline at end of file
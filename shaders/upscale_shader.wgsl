@@ -0,0 +1,26 @@
+// Stretches the dynamic resolution texture over the whole window.
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) texture_coordinates: vec2<f32>,
+};
+
+// One triangle that covers the screen, no vertex buffer needed.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+  var out: VertexOutput;
+  let texture_coordinates = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+  out.texture_coordinates = texture_coordinates;
+  out.clip_position = vec4<f32>(texture_coordinates * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+  return out;
+}
+
+@group(0) @binding(0)
+var t_scaled: texture_2d<f32>;
+@group(0) @binding(1)
+var s_scaled: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  return textureSample(t_scaled, s_scaled, in.texture_coordinates);
+}
@@ -1,5 +1,27 @@
 use clap::Parser;
 
+///
+/// Where a bare minetest connects, and a server listens.
+///
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1";
+
+///
+/// The port a bare minetest connects to, and a server listens on.
+///
+pub const DEFAULT_PORT: i32 = 30_001;
+
+///
+/// What --help shows under the options.
+///
+const USAGE_EXAMPLES: &str = "With no options minetest starts a client, as a generated Guest name,
+and connects to a server on 127.0.0.1:30001.
+
+Examples:
+  minetest                                  Join a local server as a guest.
+  minetest -c sam -a 10.0.0.5               Join another machine's server as sam.
+  minetest --server                         Host the minetest game in ./worlds/world.
+  minetest --server -g mygame -w creative   Host mygame in ./worlds/creative.";
+
 ///
 /// This is the CLI struct.
 ///
@@ -15,25 +37,177 @@ use clap::Parser;
 #[derive(Parser, Debug)]
 #[command(about = "Welcome to the minetest help section.
 Please see below for the list of available options.")]
-#[command(author, version, long_about = None)]
+#[command(author, version, long_about = None, after_help = USAGE_EXAMPLES)]
 pub struct CommandLineInterface {
   /// Run minetest as a server.
   #[arg(short, long, default_value_t = false)]
   pub server: bool,
 
-  /// Start server with a specific game.
+  /// Start server with a specific game from ./games. (an existing world keeps the game in it's world.mt)
   #[arg(short, long, default_value_t = String::from("minetest"))]
   pub game: String,
 
-  /// Start the server on a specific address.
-  #[arg(short, long, default_value_t = String::from("127.0.0.1"))]
+  /// Start the server with a specific world. (./worlds/<world>)
+  #[arg(short, long, default_value_t = String::from("world"))]
+  pub world: String,
+
+  /// The address a server listens on, or a client connects to.
+  #[arg(short, long, default_value_t = String::from(DEFAULT_ADDRESS))]
   pub address: String,
 
-  /// Start server on a specific port.
-  #[arg(short, long, default_value_t = 30_001)]
+  /// The port a server listens on, or a client connects to. (0 lets a server pick one)
+  #[arg(short, long, default_value_t = DEFAULT_PORT)]
   pub port: i32,
 
-  /// The default name for your player. (this is a placholder)
-  #[arg(short, long, default_value_t = String::from("singleplayer"))]
-  pub client_name: String,
+  /// The name for your player. [default: a generated Guest name]
+  #[arg(short, long)]
+  pub client_name: Option<String>,
+
+  /// Record every incoming packet into a file.
+  #[arg(long)]
+  pub record: Option<String>,
+
+  /// Replay a recording into a headless server, then exit. (implies --server)
+  #[arg(long)]
+  pub replay: Option<String>,
+
+  /// Replay as fast as the server keeps up, instead of at the original timing.
+  #[arg(long, default_value_t = false)]
+  pub replay_fast: bool,
+
+  /// Force the world seed, for reproducible runs. (overrides fixed_map_seed)
+  #[arg(long)]
+  pub seed: Option<u64>,
+
+  /// Give up connecting to the server after this many seconds. (overrides connect_timeout)
+  #[arg(long)]
+  pub connect_timeout: Option<f64>,
+
+  /// The password to log in with, on servers with auth. (overrides password)
+  #[arg(long)]
+  pub password: Option<String>,
+
+  /// Fail at startup on missing textures, shaders or broken mods, instead of carrying on.
+  #[arg(long, default_value_t = false)]
+  pub strict: bool,
+}
+
+impl CommandLineInterface {
+  ///
+  /// Check for options that don't make sense together.
+  ///
+  /// The error says what to do instead.
+  ///
+  pub fn validate(&self) -> Result<(), String> {
+    let is_server = self.server || self.replay.is_some();
+
+    if !(0..=65_535).contains(&self.port) {
+      return Err(format!(
+        "Port [{}] isn't a port, pick one from 0 to 65535.",
+        self.port
+      ));
+    }
+    if is_server && self.game.trim().is_empty() {
+      return Err(
+        "A server needs a game. Pass --game <name> with a game from ./games, like --game minetest."
+          .to_string(),
+      );
+    }
+    if is_server && self.world.trim().is_empty() {
+      return Err(
+        "A server needs a world. Pass --world <name>, it's kept in ./worlds/<name>.".to_string(),
+      );
+    }
+    if self.replay_fast && self.replay.is_none() {
+      return Err("--replay-fast only works with --replay <file>.".to_string());
+    }
+    if !is_server && self.seed.is_some() {
+      return Err("--seed only works on a server. Add --server, or leave it out.".to_string());
+    }
+    if let Some(connect_timeout) = self.connect_timeout {
+      if is_server {
+        return Err("--connect-timeout only works on a client. Leave out --server.".to_string());
+      }
+      if !connect_timeout.is_finite() || connect_timeout <= 0.0 {
+        return Err(format!(
+          "--connect-timeout [{}] has to be more than 0 seconds.",
+          connect_timeout
+        ));
+      }
+    }
+    if is_server && self.password.is_some() {
+      return Err("--password only works on a client. Leave out --server.".to_string());
+    }
+    if let Some(name) = &self.client_name {
+      if name.trim().is_empty() {
+        return Err("--client-name can't be empty, leave it out to play as a guest.".to_string());
+      }
+    }
+    Ok(())
+  }
+
+  ///
+  /// Get the player name, generating a Guest name if none was given.
+  ///
+  pub fn get_client_name(&self) -> String {
+    match &self.client_name {
+      Some(name) => name.clone(),
+      None => format!("Guest{}", rand::random::<u16>() % 10_000),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use clap::Parser;
+
+  use crate::command_line::{CommandLineInterface, DEFAULT_ADDRESS, DEFAULT_PORT};
+
+  #[test]
+  fn test_command_line_defaults_and_validation() {
+    println!("--- BEGIN COMMAND LINE DEFAULTS AND VALIDATION TEST ---");
+    let parse = |args: &[&str]| match CommandLineInterface::try_parse_from(args) {
+      Ok(cli) => cli,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+
+    // A bare invocation is a client joining localhost as a guest.
+    let cli = parse(&["minetest"]);
+    assert!(!cli.server);
+    assert_eq!(cli.address, DEFAULT_ADDRESS);
+    assert_eq!(cli.port, DEFAULT_PORT);
+    assert!(cli.get_client_name().starts_with("Guest"));
+    assert_eq!(cli.validate(), Ok(()));
+    assert_eq!(parse(&["minetest", "-c", "sam"]).get_client_name(), "sam");
+    assert_eq!(parse(&["minetest", "--server"]).validate(), Ok(()));
+
+    // Things that don't go together say what to do instead.
+    let error = |args: &[&str]| match parse(args).validate() {
+      Ok(_) => panic!("{:?} should not be valid.", args),
+      Err(e) => e,
+    };
+    assert!(error(&["minetest", "--server", "--game", ""]).contains("--game"));
+    assert!(error(&["minetest", "--server", "--world", " "]).contains("--world"));
+    assert!(error(&["minetest", "--replay-fast"]).contains("--replay"));
+    assert!(error(&["minetest", "--seed", "5"]).contains("--server"));
+    assert!(error(&["minetest", "--port", "70000"]).contains("65535"));
+    assert!(error(&["minetest", "-c", ""]).contains("guest"));
+    assert!(error(&["minetest", "--server", "--connect-timeout", "5"]).contains("client"));
+    assert!(error(&["minetest", "--connect-timeout", "0"]).contains("more than 0"));
+    assert_eq!(
+      parse(&["minetest", "--connect-timeout", "1.5"]).connect_timeout,
+      Some(1.5)
+    );
+    assert!(error(&["minetest", "--server", "--password", "hunter2"]).contains("client"));
+    assert_eq!(
+      parse(&["minetest", "--password", "hunter2"]).password,
+      Some("hunter2".to_string())
+    );
+
+    // --help has the examples.
+    match CommandLineInterface::try_parse_from(["minetest", "--help"]) {
+      Ok(_) => panic!("--help should stop parsing."),
+      Err(e) => assert!(e.to_string().contains("Examples:")),
+    }
+  }
 }
@@ -1,9 +1,18 @@
 use std::{
   fs::{self, File},
-  io::BufReader,
+  io::{self, BufReader, Read, Write},
   path::Path,
 };
 
+use flate2::read::MultiGzDecoder;
+
+use crate::game::minetest_error::MinetestError;
+
+///
+/// Every gzip file starts with these.
+///
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
 ///
 /// A micro helper function.
 /// Simply check if a directory exists.
@@ -21,41 +30,77 @@ pub fn file_exists(path: &str) -> bool {
   Path::new(path).is_file()
 }
 
+///
+/// Get the directory a client keeps it's own things in.
+///
+/// ~/.minetest on unix, %APPDATA%/Minetest on windows. If neither is set
+/// it's the current directory.
+///
+pub fn get_user_dir() -> String {
+  #[cfg(windows)]
+  let (variable, folder) = ("APPDATA", "Minetest");
+  #[cfg(not(windows))]
+  let (variable, folder) = ("HOME", ".minetest");
+
+  match std::env::var(variable) {
+    Ok(base) if !base.is_empty() => Path::new(&base).join(folder).to_string_lossy().to_string(),
+    _ => ".".to_string(),
+  }
+}
+
 ///
 /// Get a file name from the path provided.
 ///
-pub fn file_name_from_path(path: &str) -> Result<&str, &str> {
+pub fn file_name_from_path(path: &str) -> Result<&str, MinetestError> {
   let new_path = Path::new(path);
 
   if !new_path.exists() {
-    return Err("File name from file path. Path does not exist.");
+    return Err(MinetestError::Io(
+      io::ErrorKind::NotFound,
+      format!("File name from file path. [{}] does not exist.", path),
+    ));
   }
 
   match new_path.file_name() {
     Some(os_str) => match os_str.to_str() {
       Some(final_str) => Ok(final_str),
-      None => Err("File name from file path. Failed to convert OsStr to str."),
+      None => Err(MinetestError::Io(
+        io::ErrorKind::InvalidData,
+        "File name from file path. Failed to convert OsStr to str.".to_string(),
+      )),
     },
-    None => Err("File name from file path. Failed to parse OS Path str."),
+    None => Err(MinetestError::Io(
+      io::ErrorKind::InvalidInput,
+      "File name from file path. Failed to parse OS Path str.".to_string(),
+    )),
   }
 }
 
 ///
 /// Get a file extension from the path provided.
 ///
-pub fn file_extension_from_path(path: &str) -> Result<&str, &str> {
+pub fn file_extension_from_path(path: &str) -> Result<&str, MinetestError> {
   let new_path = Path::new(path);
 
   if !new_path.exists() {
-    return Err("Extension from file path. Path does not exist.");
+    return Err(MinetestError::Io(
+      io::ErrorKind::NotFound,
+      format!("Extension from file path. [{}] does not exist.", path),
+    ));
   }
 
   match new_path.extension() {
     Some(extension_os_str) => match extension_os_str.to_str() {
       Some(os_str) => Ok(os_str),
-      None => Err("Extension from file path. Failed to convert OsStr to str."),
+      None => Err(MinetestError::Io(
+        io::ErrorKind::InvalidData,
+        "Extension from file path. Failed to convert OsStr to str.".to_string(),
+      )),
     },
-    None => Err("Extension from file path. Failed to parse OS Path str."),
+    None => Err(MinetestError::Io(
+      io::ErrorKind::InvalidInput,
+      "Extension from file path. Failed to parse OS Path str.".to_string(),
+    )),
   }
 }
 
@@ -79,6 +124,38 @@ pub fn read_file_to_byte_vec(path: &str) -> Result<Vec<u8>, String> {
   }
 }
 
+///
+/// Automatically parse a file path into a String, gunzipping it if it's gzipped.
+///
+pub fn read_file_to_string_maybe_gzip(path: &str) -> Result<String, String> {
+  match String::from_utf8(read_file_to_byte_vec_maybe_gzip(path)?) {
+    Ok(data) => Ok(data),
+    Err(e) => Err(format!("Path to String read failure. {}", e)),
+  }
+}
+
+///
+/// Automatically parse a file path into a byte Vec, gunzipping it if it's gzipped.
+///
+/// Anything without the gzip magic bytes comes back as is.
+///
+pub fn read_file_to_byte_vec_maybe_gzip(path: &str) -> Result<Vec<u8>, String> {
+  let data = read_file_to_byte_vec(path)?;
+  if !data.starts_with(&GZIP_MAGIC) {
+    return Ok(data);
+  }
+
+  // Multi, so files that had more gzipped onto the end come out whole like with gunzip.
+  let mut decompressed = vec![];
+  match MultiGzDecoder::new(data.as_slice()).read_to_end(&mut decompressed) {
+    Ok(_) => Ok(decompressed),
+    Err(e) => Err(format!(
+      "Path to gzip byte Vec read failure. [{}] {}",
+      path, e
+    )),
+  }
+}
+
 ///
 /// Automatically parse a file path into a BufReader<File>.
 ///
@@ -88,3 +165,139 @@ pub fn read_path_to_buf_read(path: &str) -> Result<BufReader<File>, String> {
     Err(e) => Err(format!("Path to BufReader failure. {}", e)),
   }
 }
+
+///
+/// Automatically create a directory, and all of it's missing parents.
+///
+/// Does nothing if the directory already exists.
+///
+pub fn create_dir_all(path: &str) -> Result<(), String> {
+  match fs::create_dir_all(path) {
+    Ok(_) => Ok(()),
+    Err(e) => Err(format!("Create directory failure. {}", e)),
+  }
+}
+
+///
+/// Atomically write a byte slice into a file.
+///
+/// The data is written into a temporary file next to the target first,
+/// then renamed over the target. A crash halfway through a write will
+/// never leave behind a half written file.
+///
+pub fn write_file_atomic(path: &str, data: &[u8]) -> Result<(), String> {
+  let mut temporary_path = path.to_owned();
+  temporary_path.push_str(".tmp");
+
+  let mut temporary_file = match File::create(&temporary_path) {
+    Ok(file) => file,
+    Err(e) => return Err(format!("Atomic write temporary file failure. {}", e)),
+  };
+
+  if let Err(e) = temporary_file.write_all(data) {
+    return Err(format!("Atomic write data failure. {}", e));
+  }
+
+  // Make sure the data actually hit the disk before we swap it in.
+  if let Err(e) = temporary_file.sync_all() {
+    return Err(format!("Atomic write sync failure. {}", e));
+  }
+
+  match fs::rename(&temporary_path, path) {
+    Ok(_) => Ok(()),
+    Err(e) => Err(format!("Atomic write rename failure. {}", e)),
+  }
+}
+
+///
+/// Remove a file from the path provided.
+///
+pub fn remove_file(path: &str) -> Result<(), String> {
+  match fs::remove_file(path) {
+    Ok(_) => Ok(()),
+    Err(e) => Err(format!("Remove file failure. {}", e)),
+  }
+}
+
+///
+/// Get a directory for a test to work in, emptied out if an old run left it behind.
+///
+/// It's in the system temp dir, tests never write into the repo. It isn't
+/// created, and the test removes it again when it's done.
+///
+#[cfg(test)]
+pub fn test_dir(name: &str) -> String {
+  let path = std::env::temp_dir().join(format!("minetest_{}", name));
+  let _ = fs::remove_dir_all(&path);
+  match path.to_str() {
+    Some(path) => path.to_string(),
+    None => panic!("Unit test is broken. Temp dir is not valid UTF-8."),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs::{self, remove_dir_all},
+    io::Write,
+  };
+
+  use flate2::{write::GzEncoder, Compression};
+
+  use crate::file_utilities::{
+    read_file_to_byte_vec_maybe_gzip, read_file_to_string_maybe_gzip, test_dir,
+  };
+
+  #[test]
+  fn test_read_file_maybe_gzip() {
+    println!("--- BEGIN READ FILE MAYBE GZIP TEST ---");
+    let directory = test_dir("read_file_maybe_gzip");
+    if let Err(e) = fs::create_dir_all(&directory) {
+      panic!("Unit test is broken. {}", e);
+    }
+    let path = |name: &str| format!("{}/{}", directory, name);
+    let text = "max_users = 15\nmotd = hi\n";
+
+    // Plain files come back as is.
+    if let Err(e) = fs::write(path("plain.conf"), text) {
+      panic!("Unit test is broken. {}", e);
+    }
+    assert_eq!(
+      read_file_to_string_maybe_gzip(&path("plain.conf")),
+      Ok(text.to_string())
+    );
+
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    if let Err(e) = encoder.write_all(text.as_bytes()) {
+      panic!("Unit test is broken. {}", e);
+    }
+    let gzipped = match encoder.finish() {
+      Ok(gzipped) => gzipped,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    if let Err(e) = fs::write(path("gzipped.conf.gz"), &gzipped) {
+      panic!("Unit test is broken. {}", e);
+    }
+    assert_eq!(
+      read_file_to_string_maybe_gzip(&path("gzipped.conf.gz")),
+      Ok(text.to_string())
+    );
+    assert_eq!(
+      read_file_to_byte_vec_maybe_gzip(&path("gzipped.conf.gz")),
+      Ok(text.as_bytes().to_vec())
+    );
+
+    // Cut short, and garbage behind the magic bytes. Both are errors.
+    if let Err(e) = fs::write(path("truncated.gz"), &gzipped[..gzipped.len() / 2]) {
+      panic!("Unit test is broken. {}", e);
+    }
+    assert!(read_file_to_byte_vec_maybe_gzip(&path("truncated.gz")).is_err());
+    if let Err(e) = fs::write(path("corrupt.gz"), b"\x1f\x8bthis is not gzip at all") {
+      panic!("Unit test is broken. {}", e);
+    }
+    assert!(read_file_to_byte_vec_maybe_gzip(&path("corrupt.gz")).is_err());
+    assert!(read_file_to_string_maybe_gzip(&path("missing.gz")).is_err());
+
+    let _ = remove_dir_all(&directory);
+  }
+}
@@ -1,22 +1,75 @@
 mod client;
+mod connection_stats;
+mod crash_handler;
 mod delta_reporter;
+mod event_bus;
+mod frame_limiter;
+mod frame_pacing;
+mod game_command;
+mod game_config;
+mod game_init_error;
+mod log_file;
 mod lua_engine;
+#[cfg(feature = "metrics")]
+mod metrics_exporter;
+pub mod minetest_error;
+pub mod native_plugin;
+mod network_message;
+mod remote_console;
+mod replay;
+mod sequencer;
+mod serial;
 mod server;
+mod server_console;
+mod server_sleep;
+mod settings;
+mod startup_check;
+#[cfg(test)]
+mod test_client;
+mod time_of_day;
+mod time_step;
+mod transport_error;
+mod window_title;
 
-use core::panic;
 use std::{
-  ops::Deref,
-  sync::{Arc, RwLock},
-  time::Duration,
+  sync::mpsc::{channel, Receiver, Sender},
+  time::{Duration, Instant},
 };
 
+use serde::{Deserialize, Serialize};
 use spin_sleep_util::{interval, Interval, RateReporter};
 
-use crate::command_line::CommandLineInterface;
+use crate::{command_line::CommandLineInterface, file_utilities::get_user_dir};
 
-use self::{client::Client, delta_reporter::DeltaReporter, server::Server};
+#[cfg(feature = "metrics")]
+use self::metrics_exporter::{MetricsExporter, ServerMetrics};
+
+use self::{
+  client::{Client, ConnectionStatus},
+  delta_reporter::DeltaReporter,
+  event_bus::EventBus,
+  frame_limiter::FrameLimitStrategy,
+  frame_pacing::{FramePacing, PacingReport},
+  game_command::GameCommand,
+  game_config::GameConfig,
+  game_init_error::GameInitError,
+  log_file::StdioCapture,
+  minetest_error::MinetestError,
+  native_plugin::PluginRegistry,
+  remote_console::RemoteConsole,
+  replay::{Replay, ReplaySpeed, Replayer},
+  server::{Issuer, Server},
+  server_console::ServerConsole,
+  server_sleep::{ServerSleep, SleepMode},
+  settings::Settings,
+  startup_check::{StartupMode, StartupReport},
+  time_step::TimeStep,
+  window_title::WindowTitle,
+};
 
 // TODO get better name
+// There is only ever one of these, the size difference doesn't matter.
+#[allow(clippy::large_enum_variant)]
 enum ServerClient {
   Server(Server),
   Client(Client),
@@ -30,14 +83,27 @@ impl ServerClient {
   }
 }
 
-#[derive(PartialEq, Eq)]
-enum VSyncMode {
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VSyncMode {
+  #[default]
   Off,
   On,
   Double,
   Triple,
 }
 
+///
+/// What a tick_once() left the Game at.
+///
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum StepOutcome {
+  // Call tick_once() again when it's time.
+  Continue,
+  // The Game is shutting down, it won't tick anymore.
+  Closing,
+}
+
 ///
 /// The master container for the game.
 ///
@@ -58,7 +124,26 @@ enum VSyncMode {
 /// ! Do not create multiple instances of game. It's monolithic.
 ///
 pub struct Game {
-  should_close: Arc<RwLock<bool>>,
+  // Only the main loop touches this. Other threads (like the ctrlc handler)
+  // ask for a shutdown with GameCommand::Shutdown, so there's no lock to poison.
+  should_close: bool,
+
+  // Other threads talk to the Game through this. See GameCommand.
+  command_sender: Sender<GameCommand>,
+  command_receiver: Receiver<GameCommand>,
+
+  // Subsystems react to each other through this. See EngineEvent.
+  event_bus: EventBus,
+
+  // Only exists on a server.
+  console: Option<ServerConsole>,
+  // Only exists on a server, and only if minetest.conf turns it on.
+  remote_console: Option<RemoteConsole>,
+  // Only exists on a server, and only if minetest.conf turns it on.
+  #[cfg(feature = "metrics")]
+  metrics_exporter: Option<MetricsExporter>,
+  // Only exists when started with --replay. It plays into the Server.
+  replayer: Option<Replayer>,
 
   goal_frames_per_second: f64,
   goal_ticks_per_second: f64,
@@ -66,12 +151,25 @@ pub struct Game {
   // TODO also rename this
   serverclient: ServerClient,
 
+  config: GameConfig,
+  // The typed side of the config.
+  settings: Settings,
+
   interval: Interval,
+  server_sleep: ServerSleep,
   fps_reporter: RateReporter,
   delta_reporter: DeltaReporter,
+  frame_pacing: FramePacing,
 
-  delta: f64,
+  delta: TimeStep,
   current_fps: f64,
+  // When this is set, every tick gets exactly this delta. See set_fixed_step.
+  fixed_step: Option<TimeStep>,
+  // How many times tick_once() actually ticked.
+  tick_count: u64,
+
+  // The window title, see window_title.rs for the placeholders.
+  window_title: WindowTitle,
 
   // vsync can be:
   // off
@@ -79,71 +177,225 @@ pub struct Game {
   // double
   // triple
   vsync_mode: VSyncMode,
+
+  // Dropped last, so everything printed on the way out still makes it into the log file.
+  log_capture: Option<StdioCapture>,
 }
 
 impl Game {
-  pub fn new(cli: CommandLineInterface) -> Game {
+  ///
+  /// Create the Game, panicking if it can't start.
+  ///
+  /// Use try_new() to report that instead.
+  ///
+  pub fn new(cli: CommandLineInterface, plugins: PluginRegistry) -> Game {
+    match Self::try_new(cli, plugins) {
+      Ok(new_game) => new_game,
+      Err(e) => panic!("Minetest: {}", e),
+    }
+  }
+
+  ///
+  /// Create the Game.
+  ///
+  /// Fails if this is a server and it can't bind it's address,
+  /// or a client and it can't render.
+  ///
+  /// The native plugins run on the Server, a client has nothing to run them on.
+  ///
+  pub fn try_new(
+    cli: CommandLineInterface,
+    plugins: PluginRegistry,
+  ) -> Result<Game, GameInitError> {
     println!("Minetest initialized!");
 
-    // Set up the environment logger.
-    env_logger::init();
+    cli.validate().map_err(GameInitError::CommandLine)?;
 
-    // 60 FPS goal for the moment.
-    let goal_frames_per_second = 60.0;
+    let mut config = GameConfig::load("./minetest.conf");
+    if let Some(seed) = cli.seed {
+      config.set("fixed_map_seed", &seed.to_string());
+    }
+    if let Some(connect_timeout) = cli.connect_timeout {
+      config.set("connect_timeout", &connect_timeout.to_string());
+    }
+    if let Some(password) = &cli.password {
+      config.set("password", password);
+    }
+    // --strict reports every broken mod before refusing to start, instead of crashing on the first.
+    if cli.strict {
+      config.set("skip_broken_mods", "true");
+    }
+
+    // A replay needs something to replay into.
+    let is_server = cli.server || cli.replay.is_some();
 
-    // 20 Tick Per Second goal.
-    let goal_ticks_per_second = 20.0;
+    // Set up the environment logger, minetest.conf can send it into a file too.
+    // A server logs into it's world, a client into the user directory.
+    // This can only happen once per process, don't crash if it already did.
+    let log_dir = match is_server {
+      true => format!("./worlds/{}", cli.world),
+      false => get_user_dir(),
+    };
+    let log_capture = log_file::init_logger(&config, &log_dir);
 
-    let loop_helper_goal = match cli.server {
+    // Tests panic on purpose, those aren't crashes.
+    #[cfg(not(test))]
+    crash_handler::install(&config);
+    let settings = Settings::from_config(&config)?;
+
+    let goal_frames_per_second = settings.fps_max;
+    let goal_ticks_per_second = settings.get_ticks_per_second();
+
+    let loop_helper_goal = match is_server {
       true => goal_ticks_per_second,
       false => goal_frames_per_second,
     };
 
-    let interval = interval(Duration::from_secs_f64(1.0 / loop_helper_goal));
+    // Yield sleeping trades a little precision for a lot less CPU.
+    let interval = FrameLimitStrategy::from_config(&config, is_server)
+      .apply(interval(Duration::from_secs_f64(1.0 / loop_helper_goal)));
     let fps_reporter = RateReporter::new(Duration::from_secs(1));
     let delta_reporter = DeltaReporter::new();
 
-    //todo: make this happen!
-    println!("we need a minetest.conf parser for vsync!");
+    let (command_sender, command_receiver) = channel();
+
+    let server_sleep = ServerSleep::new(SleepMode::from_config(&config));
+
+    // --strict fails here with everything that's wrong, instead of mid-session.
+    let startup_mode = StartupMode::from_strict(cli.strict);
+    let mut startup_report = StartupReport::new();
+    if !is_server {
+      // The Client can't start without it's shaders, check before it tries.
+      startup_report.check_shaders();
+      startup_report.finish(startup_mode)?;
+    }
+
+    // Simply reverse these then we can plop in a server when
+    // the player enters singleplayer.
+    // We could parse the player's name instead from a file, or a first time ask. This is mutable after all.
+    // If this is a server we don't do any client things.
+    let mut serverclient = match is_server {
+      true => {
+        let mut server = Server::try_new(
+          cli.address,
+          cli.port,
+          cli.game,
+          &format!("./worlds/{}", cli.world),
+          &config,
+          goal_ticks_per_second,
+        )?;
+        server.set_network_broadcast_rate(settings.network_broadcast_rate);
+        ServerClient::Server(server)
+      }
+      false => ServerClient::Client(Client::try_new(
+        cli.get_client_name(),
+        cli.address.clone(),
+        cli.port,
+        &config,
+      )?),
+    };
+
+    if let ServerClient::Server(server) = &serverclient {
+      server.check_assets(&mut startup_report);
+      startup_report.finish(startup_mode)?;
+    }
 
-    let new_game = Game {
-      should_close: Arc::new(RwLock::new(false)),
+    match &mut serverclient {
+      ServerClient::Server(server) => server.load_plugins(plugins),
+      ServerClient::Client(_) if !plugins.is_empty() => println!(
+        "Minetest: native plugins only run on a server, ignoring [{}].",
+        plugins.len()
+      ),
+      ServerClient::Client(_) => (),
+    }
+
+    if let Some(path) = &cli.record {
+      let result = match &mut serverclient {
+        ServerClient::Server(server) => server.start_recording(path),
+        ServerClient::Client(client) => client.start_recording(path),
+      };
+      result.map_err(GameInitError::Replay)?;
+    }
+
+    let replayer = match (&cli.replay, &serverclient) {
+      (Some(path), ServerClient::Server(server)) => {
+        let speed = match cli.replay_fast {
+          true => ReplaySpeed::Fast,
+          false => ReplaySpeed::Original,
+        };
+        let replay = Replay::load(path).map_err(GameInitError::Replay)?;
+        println!(
+          "Minetest: replaying [{}] events from [{}].",
+          replay.get_entries().len(),
+          path
+        );
+        Some(Replayer::new(replay, server.get_real_address(), speed))
+      }
+      _ => None,
+    };
+
+    let mut new_game = Game {
+      should_close: false,
+
+      command_sender,
+      command_receiver,
+
+      event_bus: EventBus::new(),
+
+      console: None,
+      remote_console: None,
+      #[cfg(feature = "metrics")]
+      metrics_exporter: None,
+      replayer,
 
       goal_frames_per_second,
       goal_ticks_per_second,
 
-      // Simply reverse these then we can plop in a server when
-      // the player enters singleplayer.
-      // We could parse the player's name instead from a file, or a first time ask. This is mutable after all.
-      // If this is a server we don't do any client things.
-      serverclient: match cli.server {
-        true => ServerClient::Server(Server::new(cli.address, cli.port, cli.game)),
-        false => ServerClient::Client(Client::new(cli.client_name, cli.address.clone(), cli.port)),
-      },
+      serverclient,
 
       interval,
+      server_sleep,
       fps_reporter,
       delta_reporter,
+      frame_pacing: FramePacing::new(),
 
-      delta: 0.0,
+      delta: TimeStep::ZERO,
       current_fps: 0.0,
+      fixed_step: None,
+      tick_count: 0,
+
+      window_title: WindowTitle::from_config(&config),
+
+      vsync_mode: settings.vsync,
 
-      //todo: fix this when the minetest.conf parser is implemented
-      vsync_mode: VSyncMode::Off,
+      config,
+      settings,
+
+      log_capture,
     };
 
     // Automatically elegantly stops the game when CTRL+C is hit or user terminates the process.
 
-    let run_clone = new_game.should_close.clone();
-    let _ = ctrlc::set_handler(move || match run_clone.deref().write() {
-      Ok(mut rw_lock) => {
-        *rw_lock = true;
-        println!("Minetest: Termination signal received. Exiting elegantly.");
+    // The stdin console waits for start_console, see ServerConsole.
+    if new_game.serverclient.is_server() {
+      new_game.remote_console =
+        RemoteConsole::from_config(&new_game.config, new_game.command_sender());
+      #[cfg(feature = "metrics")]
+      {
+        new_game.metrics_exporter = MetricsExporter::from_config(&new_game.config);
+      }
+    }
+
+    let ctrlc_sender = new_game.command_sender();
+    let _ = ctrlc::set_handler(move || {
+      println!("Minetest: Termination signal received. Exiting elegantly.");
+      // The only way this fails is if the Game is already gone.
+      if let Err(e) = ctrlc_sender.send(GameCommand::Shutdown) {
+        println!("Minetest: Failed to exit process elegantly. {}", e)
       }
-      Err(e) => panic!("Minetest: Failed to exit process elegantly. {}", e),
     });
 
-    new_game
+    Ok(new_game)
   }
 
   ///
@@ -152,9 +404,13 @@ impl Game {
   /// A client, server, or singleplayer.
   ///
   fn update_target_framerate_goal(&mut self) {
-    let new_goal = match self.serverclient {
+    let new_goal = match &mut self.serverclient {
       ServerClient::Client(_) => self.goal_frames_per_second,
-      ServerClient::Server(_) => self.goal_ticks_per_second,
+      ServerClient::Server(server) => {
+        // The server warns when a tick goes over this budget.
+        server.set_goal_ticks_per_second(self.goal_ticks_per_second);
+        self.goal_ticks_per_second
+      }
     };
 
     self
@@ -165,10 +421,87 @@ impl Game {
   ///
   /// This simply returns the current delta time.
   ///
-  pub fn get_delta(&self) -> f64 {
+  /// This is the raw value, it can jitter a lot between frames.
+  ///
+  pub fn get_delta(&self) -> TimeStep {
     self.delta
   }
 
+  ///
+  /// Give every tick the same delta, no matter how much time actually went by.
+  ///
+  /// For a host that runs tick_once() itself, like an editor or a test
+  /// harness. None goes back to measuring it.
+  ///
+  pub fn set_fixed_step(&mut self, fixed_step: Option<TimeStep>) {
+    self.fixed_step = fixed_step;
+  }
+
+  ///
+  /// Get how many ticks have run so far.
+  ///
+  pub fn get_tick_count(&self) -> u64 {
+    self.tick_count
+  }
+
+  ///
+  /// Get the smoothed delta time.
+  ///
+  /// Better for anything a player looks at, like an FPS counter.
+  ///
+  pub fn get_smoothed_delta(&self) -> f64 {
+    self.delta_reporter.get_smoothed_delta()
+  }
+
+  ///
+  /// Get the average FPS, from the smoothed delta time.
+  ///
+  pub fn get_average_fps(&self) -> f64 {
+    let smoothed_delta = self.get_smoothed_delta();
+    if smoothed_delta <= 0.0 {
+      return 0.0;
+    }
+    1.0 / smoothed_delta
+  }
+
+  ///
+  /// Get how evenly frames are coming in compared to the interval's
+  /// target period.
+  ///
+  /// If it keeps missing deadlines, vsync is probably fighting the cap.
+  /// If it's work bound, the frames are too slow for the cap.
+  ///
+  pub fn pacing_report(&self) -> PacingReport {
+    self
+      .frame_pacing
+      .report(TimeStep::from(self.interval.period()))
+  }
+
+  ///
+  /// Get the typed settings from minetest.conf.
+  ///
+  pub fn get_settings(&self) -> &Settings {
+    &self.settings
+  }
+
+  ///
+  /// Change the window title format.
+  ///
+  /// Supports {fps}, {tps}, and {version}. It gets filled in once a second.
+  ///
+  pub fn set_title_format(&mut self, title_format: &str) {
+    self.window_title.set_format(title_format);
+  }
+
+  ///
+  /// Turn the FPS in the window title on or off, like show_fps_in_title.
+  ///
+  /// Off sets a plain title once and leaves it alone.
+  ///
+  pub fn set_show_fps_in_title(&mut self, show_fps: bool) {
+    self.window_title.set_show_fps(show_fps);
+  }
+
   ///
   /// Update the games' target FPS.
   /// ! Only has side effects if this is a client/singleplayer.
@@ -199,38 +532,137 @@ impl Game {
   /// ! This shouldn't be used for anything but testing!
   ///
   pub fn shutdown_game(&mut self) {
-    match self.should_close.deref().write() {
-      Ok(mut rw_lock) => {
-        *rw_lock = true;
-        println!("Minetest: Shutdown signal received.");
+    self.should_close = true;
+    println!("Minetest: Shutdown signal received.");
+    // While the connection and the VM are still up.
+    if let ServerClient::Server(server) = &mut self.serverclient {
+      server.on_shutdown();
+    }
+  }
+
+  ///
+  /// Get a Sender which can send GameCommands to the Game from any thread.
+  ///
+  pub fn command_sender(&self) -> Sender<GameCommand> {
+    self.command_sender.clone()
+  }
+
+  ///
+  /// Get the EventBus, to subscribe to EngineEvents.
+  ///
+  pub fn get_event_bus(&mut self) -> &mut EventBus {
+    &mut self.event_bus
+  }
+
+  ///
+  /// Apply every GameCommand that came in since the last frame.
+  ///
+  fn process_commands(&mut self) {
+    while let Ok(command) = self.command_receiver.try_recv() {
+      println!("Minetest: applying command {:?}", command);
+      match command {
+        GameCommand::Shutdown => self.shutdown_game(),
+        GameCommand::SetFpsTarget(new_frames_per_second_goal) => {
+          self.set_frame_rate_target(new_frames_per_second_goal)
+        }
+        // todo: the RenderEngine needs to switch it's present mode.
+        GameCommand::SetVSync(new_vsync_mode) => self.vsync_mode = new_vsync_mode,
+        GameCommand::Reload => match &mut self.serverclient {
+          ServerClient::Server(server) => server.reload(),
+          ServerClient::Client(client) => client.reset_lua_vm(),
+        },
+        GameCommand::ConsoleCommand(line) => match &mut self.serverclient {
+          ServerClient::Server(server) => {
+            println!("{}", server.run_chat_command(&Issuer::Console, &line))
+          }
+          ServerClient::Client(_) => println!("Minetest: console commands only work on a server."),
+        },
+        GameCommand::RemoteCommand(line, reply) => {
+          let output = match &mut self.serverclient {
+            ServerClient::Server(server) => server.run_chat_command(&Issuer::Console, &line),
+            ServerClient::Client(_) => "Console commands only work on a server.".to_string(),
+          };
+          // The remote client hung up, nobody to tell.
+          let _ = reply.0.send(output);
+        }
       }
-      Err(e) => panic!("Minetest: Failed to shutdown. {}", e),
     }
   }
 
   ///
-  /// The main loop of the game engine.
+  /// Run one tick of the game engine, then hand control back.
   ///
-  fn main(&mut self) {
-    //? Here is where the logic loop goes.
+  /// This never waits for the next tick, when to call it again is up to
+  /// whoever is calling it. enter_main_loop() is the one that waits.
+  ///
+  pub fn tick_once(&mut self) -> StepOutcome {
+    if self.should_close {
+      return StepOutcome::Closing;
+    }
+
+    // Everything up to the sleep is this frame's work.
+    let work_start = Instant::now();
 
-    self.delta = self.delta_reporter.report();
+    self.process_commands();
+
+    // This also updates the smoothed delta.
+    let measured_delta = self.delta_reporter.report();
+    self.frame_pacing.record(measured_delta);
+    self.delta = self.fixed_step.unwrap_or(measured_delta);
+    self.tick_count += 1;
 
     // * Uncomment this to see the exact delta time.
     // println!("delta: {:.32}", self.delta);
 
     //* Begin server/client on_tick()
 
+    let average_fps = self.get_average_fps();
+
     match &mut self.serverclient {
       ServerClient::Server(server) => {
+        if let Some(replayer) = &mut self.replayer {
+          replayer.step(Instant::now());
+        }
+
         server.on_tick(self.delta);
 
+        #[cfg(feature = "metrics")]
+        if let Some(metrics_exporter) = &self.metrics_exporter {
+          let (bytes_in_per_second, bytes_out_per_second) = server.get_bytes_per_second();
+          metrics_exporter.publish(ServerMetrics {
+            player_count: server.get_player_names().len() as u32,
+            ticks_per_second: average_fps,
+            tick_duration: server.get_last_tick_duration().as_secs_f64(),
+            lagged_ticks: server.get_lagged_ticks(),
+            bytes_in_per_second,
+            bytes_out_per_second,
+            receive_gaps: server.get_receive_gaps(),
+          });
+        }
+
+        for event in server.take_events() {
+          self.event_bus.publish(&event);
+        }
+
         if server.shutdown_is_approved() {
           self.shutdown_game()
         }
+
+        if let Some(replayer) = &self.replayer {
+          if replayer.is_finished(Instant::now()) {
+            println!("Minetest: replay finished.");
+            self.shutdown_game();
+          }
+        }
       }
       ServerClient::Client(client) => {
+        client.set_average_fps(average_fps);
         client.on_tick(self.delta);
+
+        for event in client.take_events() {
+          self.event_bus.publish(&event);
+        }
+
         if client.should_quit() {
           self.shutdown_game();
         }
@@ -243,42 +675,113 @@ impl Game {
     //todo: this can also be linked into the client struct to report
     //todo: the current framerate.
 
-    if let Some(fps) = self.fps_reporter.increment_and_report() {
+    let new_fps = self.fps_reporter.increment_and_report();
+    if let Some(fps) = new_fps {
       self.current_fps = fps;
       let time_measurement = match self.serverclient.is_client() {
         true => "FPS",
         false => "TPS",
       };
       // println!("Debug {}: {}", time_measurement, self.current_fps)
-      if let ServerClient::Client(client) = &mut self.serverclient {
-        let mut new_title = "minetest | ".to_string();
-        new_title.push_str(format!("{:.1}", fps).as_str());
-        new_title.push_str(" FPS");
+
+      // An idle server sleeps past it's deadline on purpose, only frames matter here.
+      let pacing_report = self.pacing_report();
+      if self.serverclient.is_client() && pacing_report.is_work_bound() {
+        println!(
+          "Minetest: frames take [{:.2}ms] of work, over the [{:.2}ms] target. The game is CPU or GPU bound.",
+          pacing_report.mean_work.as_millis(),
+          pacing_report.target_period.as_millis()
+        );
+      } else if self.serverclient.is_client() && pacing_report.is_consistently_missing() {
+        println!(
+          "Minetest: {:.1}% of frames are missing the [{:.2}ms] deadline. Try another vsync mode.",
+          pacing_report.missed_deadline_percent,
+          pacing_report.target_period.as_millis()
+        );
+      }
+    }
+
+    // Once a second with the FPS in it, or just once without.
+    if let ServerClient::Client(client) = &mut self.serverclient {
+      if let Some(new_title) = self
+        .window_title
+        .update(new_fps, self.goal_ticks_per_second)
+      {
         client.get_window_handler().set_title(&new_title);
       }
     }
 
-    if self.vsync_mode == VSyncMode::Off || self.serverclient.is_server() {
-      self.interval.tick();
+    self
+      .frame_pacing
+      .record_work(TimeStep::from(work_start.elapsed()));
+
+    match self.should_close {
+      true => StepOutcome::Closing,
+      false => StepOutcome::Continue,
     }
   }
 
   ///
-  /// This is the actual entry point for the game.
+  /// Wait until it's time for the next tick.
   ///
-  pub fn enter_main_loop(&mut self) {
-    loop {
-      self.main();
-      match self.should_close.deref().read() {
-        Ok(should_close) => {
-          if *should_close {
-            break;
-          }
+  fn wait_for_next_tick(&mut self) {
+    match &self.serverclient {
+      // An idle server doesn't need to spin the CPU waiting for the next tick.
+      ServerClient::Server(server) => self
+        .server_sleep
+        .sleep(&mut self.interval, server.is_idle()),
+      ServerClient::Client(_) => {
+        if self.vsync_mode == VSyncMode::Off {
+          self.interval.tick();
         }
-        Err(e) => panic!("Minetest: Failed to enter main loop. {}", e),
       }
     }
   }
+
+  ///
+  /// Give a dedicated server its admin console on stdin, see ServerConsole.
+  ///
+  /// Only main() calls this, a Game in a test never reads stdin.
+  ///
+  pub fn start_console(&mut self) {
+    if self.serverclient.is_server() && self.console.is_none() {
+      self.console = Some(ServerConsole::new(self.command_sender()));
+    }
+  }
+
+  ///
+  /// This is the actual entry point for the game.
+  ///
+  /// It's tick_once() over and over, keeping time in between, until the Game closes.
+  /// A console from start_console() is shut down on the way out.
+  ///
+  /// Fails if the Game closed because something went wrong, like a client
+  /// that lost its server. main() turns that into the exit code.
+  ///
+  pub fn enter_main_loop(&mut self) -> Result<(), MinetestError> {
+    while self.tick_once() == StepOutcome::Continue {
+      self.wait_for_next_tick();
+    }
+
+    if let Some(mut console) = self.console.take() {
+      console.shutdown();
+    }
+
+    self.get_exit_error()
+  }
+
+  ///
+  /// Get what went wrong, if the Game is closing because of a failure.
+  ///
+  fn get_exit_error(&self) -> Result<(), MinetestError> {
+    match &self.serverclient {
+      ServerClient::Client(client) => match client.get_connection_status() {
+        ConnectionStatus::Failed(reason) => Err(MinetestError::Network(reason.clone())),
+        _ => Ok(()),
+      },
+      ServerClient::Server(_) => Ok(()),
+    }
+  }
 }
 
 impl Drop for Game {
@@ -287,3 +790,315 @@ impl Drop for Game {
     println!("Minetest dropped!");
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    cell::RefCell,
+    fs::{create_dir_all, remove_dir_all},
+    net::UdpSocket,
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
+  };
+
+  use clap::Parser;
+  use glam::Vec3A;
+
+  use crate::{
+    command_line::CommandLineInterface,
+    file_utilities::test_dir,
+    game::{
+      event_bus::{EngineEvent, EngineEventType},
+      game_command::GameCommand,
+      native_plugin::PluginRegistry,
+      network_message::NetworkMessage,
+      server::{entities::PLAYER_ENTITY_KIND, Server},
+      test_client::TestClient,
+      time_step::TimeStep,
+      Game, ServerClient, StepOutcome,
+    },
+  };
+
+  ///
+  /// Get the Server out of a server Game.
+  ///
+  fn get_server(game: &Game) -> &Server {
+    match &game.serverclient {
+      ServerClient::Server(server) => server,
+      ServerClient::Client(_) => panic!("Unit test is broken. Game is not a server."),
+    }
+  }
+
+  ///
+  /// One tick and the wait after it, like enter_main_loop() does.
+  ///
+  fn run_frame(game: &mut Game) {
+    game.tick_once();
+    game.wait_for_next_tick();
+  }
+
+  #[test]
+  fn test_game_command_shutdown() {
+    println!("--- BEGIN GAME COMMAND SHUTDOWN TEST ---");
+    let mut game = Game::new(
+      CommandLineInterface::parse_from(["minetest", "--server", "--port", "0"]),
+      PluginRegistry::new(),
+    );
+
+    let sender = game.command_sender();
+    let sending_thread = thread::spawn(move || sender.send(GameCommand::Shutdown));
+
+    // This only returns if the command made it through.
+    assert_eq!(game.enter_main_loop(), Ok(()));
+
+    match sending_thread.join() {
+      Ok(result) => assert!(result.is_ok()),
+      Err(_) => panic!("Unit test is broken. Sending thread panicked."),
+    }
+  }
+
+  #[test]
+  fn test_game_tick_once() {
+    println!("--- BEGIN GAME TICK ONCE TEST ---");
+    let mut game = Game::new(
+      CommandLineInterface::parse_from(["minetest", "--server", "--port", "0"]),
+      PluginRegistry::new(),
+    );
+    let step = TimeStep::from_secs_f64(0.05);
+    game.set_fixed_step(Some(step));
+
+    // The host keeps the time, so one tick is one step however long it really took.
+    let start = get_server(&game).get_time_of_day();
+    assert_eq!(game.tick_once(), StepOutcome::Continue);
+    let one_tick = get_server(&game).get_time_of_day() - start;
+    assert!(one_tick > 0.0);
+    assert_eq!(game.get_delta(), step);
+
+    const TICKS: u64 = 25;
+    for _ in 0..TICKS {
+      assert_eq!(game.tick_once(), StepOutcome::Continue);
+    }
+    assert_eq!(game.get_tick_count(), TICKS + 1);
+    let advanced = get_server(&game).get_time_of_day() - start;
+    assert!((advanced - one_tick * (TICKS + 1) as f64).abs() < 1e-12);
+
+    // Once it's closing, there's no more ticking.
+    if let Err(e) = game.command_sender().send(GameCommand::Shutdown) {
+      panic!("Unit test is broken. {}", e);
+    }
+    assert_eq!(game.tick_once(), StepOutcome::Closing);
+    assert_eq!(game.tick_once(), StepOutcome::Closing);
+    assert_eq!(game.get_tick_count(), TICKS + 2);
+  }
+
+  #[test]
+  fn test_game_drop_releases_server() {
+    println!("--- BEGIN GAME DROP RELEASES SERVER TEST ---");
+    let game = Game::new(
+      CommandLineInterface::parse_from(["minetest", "--server", "--port", "0"]),
+      PluginRegistry::new(),
+    );
+
+    let address = get_server(&game).get_real_address();
+
+    // The Game owns the Server outright, nothing else keeps it alive.
+    // If the Server leaked, it's ServerConnection would still hold the port.
+    drop(game);
+    assert!(UdpSocket::bind(address).is_ok());
+  }
+
+  #[test]
+  fn test_game_join_flow() {
+    println!("--- BEGIN GAME JOIN FLOW TEST ---");
+    let world_path = "./worlds/test_game_join_flow";
+    let _ = remove_dir_all(world_path);
+    let mut game = Game::new(
+      CommandLineInterface::parse_from([
+        "minetest",
+        "--server",
+        "--port",
+        "0",
+        "--world",
+        "test_game_join_flow",
+      ]),
+      PluginRegistry::new(),
+    );
+    let address = get_server(&game).get_real_address();
+
+    let events = Rc::new(RefCell::new(vec![]));
+    for event_type in [EngineEventType::PlayerJoined, EngineEventType::ChatReceived] {
+      let events = events.clone();
+      game.get_event_bus().subscribe(event_type, move |event| {
+        events.borrow_mut().push(event.clone())
+      });
+    }
+
+    // * Phase 1: Connect and shake hands.
+    let mut alice = TestClient::new(address);
+    let mut bob = TestClient::new(address);
+    for (name, player) in [("alice", &mut alice), ("bob", &mut bob)] {
+      player.send_handshake(name);
+      assert_eq!(
+        player.wait_for_reply(|| run_frame(&mut game)),
+        Some(NetworkMessage::HandShakeConfirmed)
+      );
+    }
+    let mut players = get_server(&game).get_player_names();
+    players.sort();
+    assert_eq!(players, vec!["alice", "bob"]);
+
+    // Bob sees alice, who was already here, and alice sees bob show up.
+    let spawned = |id: u64| {
+      Some(NetworkMessage::AddEntity {
+        id,
+        kind: PLAYER_ENTITY_KIND.to_string(),
+        position: Vec3A::ZERO,
+      })
+    };
+    assert_eq!(bob.wait_for_reply(|| run_frame(&mut game)), spawned(1));
+    assert_eq!(alice.wait_for_reply(|| run_frame(&mut game)), spawned(2));
+    // Then where they are, every broadcast.
+    let positions = bob.wait_for_message(
+      || run_frame(&mut game),
+      |message| matches!(message, NetworkMessage::EntityPositions { .. }),
+    );
+    assert_eq!(
+      positions,
+      Some(NetworkMessage::EntityPositions {
+        positions: vec![(1, Vec3A::ZERO)],
+      })
+    );
+
+    // * Phase 2: Chat goes out to everyone.
+    alice.send(&NetworkMessage::ChatMessage("hello".to_string()));
+    let broadcast = Some(NetworkMessage::ChatMessage("<alice> hello".to_string()));
+    assert_eq!(bob.wait_for_reply(|| run_frame(&mut game)), broadcast);
+    assert_eq!(alice.wait_for_reply(|| run_frame(&mut game)), broadcast);
+    assert_eq!(
+      *events.borrow(),
+      vec![
+        EngineEvent::PlayerJoined("alice".to_string()),
+        EngineEvent::PlayerJoined("bob".to_string()),
+        EngineEvent::ChatReceived {
+          name: "alice".to_string(),
+          message: "hello".to_string(),
+        },
+      ]
+    );
+
+    // * Phase 3: Leaving cleans up the session.
+    alice.send(&NetworkMessage::Disconnect {
+      reason: "Client quit.".to_string(),
+    });
+    for _ in 0..40 {
+      if get_server(&game).get_player_names() == vec!["bob"] {
+        break;
+      }
+      run_frame(&mut game);
+    }
+    assert_eq!(get_server(&game).get_player_names(), vec!["bob"]);
+    assert_eq!(
+      bob.wait_for_reply(|| run_frame(&mut game)),
+      Some(NetworkMessage::RemoveEntity { id: 1 })
+    );
+
+    drop(game);
+    let _ = remove_dir_all(world_path);
+  }
+
+  #[test]
+  fn test_game_record_and_replay() {
+    println!("--- BEGIN GAME RECORD AND REPLAY TEST ---");
+    let recording_dir = test_dir("game_record_and_replay");
+    if let Err(e) = create_dir_all(&recording_dir) {
+      panic!("Unit test is broken. {}", e);
+    }
+    let recording = format!("{}/session.replay", recording_dir);
+    let world_paths = ["./worlds/test_game_record", "./worlds/test_game_replay"];
+    for world_path in world_paths {
+      let _ = remove_dir_all(world_path);
+    }
+
+    // * Phase 1: Record a short session.
+    let mut game = Game::new(
+      CommandLineInterface::parse_from([
+        "minetest",
+        "--server",
+        "--port",
+        "0",
+        "--world",
+        "test_game_record",
+        "--record",
+        &recording,
+      ]),
+      PluginRegistry::new(),
+    );
+    let address = get_server(&game).get_real_address();
+
+    let mut alice = TestClient::new(address);
+    let mut bob = TestClient::new(address);
+    for (name, player) in [("alice", &mut alice), ("bob", &mut bob)] {
+      player.send_handshake(name);
+      assert_eq!(
+        player.wait_for_reply(|| run_frame(&mut game)),
+        Some(NetworkMessage::HandShakeConfirmed)
+      );
+    }
+    let player_move = |x: f32| NetworkMessage::PlayerMove {
+      position: Vec3A::new(x, 0.0, 0.0),
+      rotation: Vec3A::ZERO,
+    };
+    alice.send(&player_move(1.0));
+    alice.send(&player_move(2.0));
+    bob.send(&player_move(5.0));
+    bob.send(&NetworkMessage::Disconnect {
+      reason: "Client quit.".to_string(),
+    });
+
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(2)
+      && (get_server(&game).get_player_names() != vec!["alice"]
+        || get_server(&game).get_player_position("alice").is_none())
+    {
+      run_frame(&mut game);
+    }
+    let recorded_players = get_server(&game).get_player_names();
+    let recorded_position = get_server(&game).get_player_position("alice");
+    assert_eq!(recorded_players, vec!["alice"]);
+    assert_eq!(
+      recorded_position,
+      Some((Vec3A::new(2.0, 0.0, 0.0), Vec3A::ZERO))
+    );
+    drop(game);
+
+    // * Phase 2: Replay it into a fresh server, it ends up the same.
+    let mut game = Game::new(
+      CommandLineInterface::parse_from([
+        "minetest",
+        "--port",
+        "0",
+        "--world",
+        "test_game_replay",
+        "--replay",
+        &recording,
+        "--replay-fast",
+      ]),
+      PluginRegistry::new(),
+    );
+    // This only returns once the replay shut the Game down.
+    assert_eq!(game.enter_main_loop(), Ok(()));
+    assert_eq!(get_server(&game).get_player_names(), recorded_players);
+    assert_eq!(
+      get_server(&game).get_player_position("alice"),
+      recorded_position
+    );
+    assert_eq!(get_server(&game).get_player_position("bob"), None);
+
+    drop(game);
+    let _ = remove_dir_all(&recording_dir);
+    for world_path in world_paths {
+      let _ = remove_dir_all(world_path);
+    }
+  }
+}
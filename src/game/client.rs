@@ -1,22 +1,59 @@
 mod client_connection;
+mod debug_overlay;
+mod entity_registry;
+pub mod input_events;
 mod keyboard;
+pub mod media_cache;
 mod mouse;
-mod render_engine;
+mod prediction;
+pub mod render_engine;
+mod snapshot;
+mod sound_manager;
+mod spectator;
 mod window_handler;
+mod zoom;
 
-use glam::{vec3a, vec4, Vec3A};
+use std::{cell::RefCell, rc::Rc};
+
+use glam::Vec3A;
+
+pub use self::client_connection::ConnectionStatus;
+
+use crate::media_hash::hash_bytes;
 
 use self::{
-  client_connection::ClientConnection,
+  client_connection::{ClientConnection, DEFAULT_CONNECT_TIMEOUT},
+  debug_overlay::{DebugOverlay, DebugOverlayStats, OVERLAY_POSITION, OVERLAY_SCALE},
+  entity_registry::EntityRegistry,
+  input_events::InputEventQueue,
   keyboard::KeyboardController,
+  media_cache::{MediaCache, DEFAULT_MEDIA_CACHE_SIZE_BYTES},
   mouse::MouseController,
-  render_engine::{instanced_render_matrix::InstanceMatrixRGBA, RenderEngine},
-  window_handler::WindowHandler,
+  prediction::MovePrediction,
+  render_engine::{render_init_error::RenderInitError, RenderEngine},
+  snapshot::{Snapshot, SnapshotBuffer},
+  sound_manager::SoundManager,
+  spectator::{Spectator, SPECTATOR_SPEED_MULTIPLIER},
+  window_handler::{window_settings::WindowSettings, WindowHandler},
+  zoom::Zoom,
 };
 
 const TESTING_LIMIT: usize = 100;
 
-use super::lua_engine::LuaEngine;
+///
+/// How often the player count is asked for while the debug overlay is up. [seconds]
+///
+const PLAYER_COUNT_POLL_SECONDS: f64 = 1.0;
+
+use super::{
+  event_bus::EngineEvent,
+  game_config::GameConfig,
+  lua_engine::LuaEngine,
+  replay::Recorder,
+  serial::get_max_message_bytes,
+  time_of_day::{TimeOfDay, DEFAULT_START_TIME, DEFAULT_TIME_SPEED},
+  time_step::TimeStep,
+};
 
 ///
 /// The Client component for the engine.
@@ -25,24 +62,55 @@ use super::lua_engine::LuaEngine;
 /// 1.) Hold a window.
 /// 2.) Hold the render engine.
 /// 3.) Hold a ClientConnection which handles talking to a server.
-/// * 4.) [in the future] Be the main handler for ClientAuthentication.
+/// ? 4.) Handle GameConfig as a component. This should be received from a server
+/// ? 4 - Marked with ? because it's still being thought out at the moment.
+/// * 5.) [in the future] Be the main handler for ClientAuthentication.
 /// *  - ClientAuthentication does exactly what you think it does.
 /// *  - Maintains a client auth for itself when talking to the server.
-/// ? 5.) Handle GameConfig as a component. This should be received from a server
-/// ? 5 - Marked with ? because it's still being thought out at the moment.
 ///
 pub struct Client {
-  window_handler: WindowHandler,
+  // ! Fields drop top to bottom. The RenderEngine's Surface was made from
+  // ! the window, so it has to go before the WindowHandler. See Drop.
   render_engine: RenderEngine,
+  window_handler: WindowHandler,
   client_name: String,
   connection: ClientConnection,
+  // What the EventBus was last told, see ConnectionStatusChanged.
+  connection_status: ConnectionStatus,
   lua_engine: LuaEngine,
+  media_cache: MediaCache,
+  sound_manager: SoundManager,
+  // What the server spawned, see AddEntity.
+  entities: EntityRegistry,
 
   mouse: MouseController,
   keyboard: KeyboardController,
+  // This frame's input, for client mods. See minetest.get_input_events.
+  input_events: Rc<RefCell<InputEventQueue>>,
+
+  // The simulation writes the back, rendering reads the front.
+  snapshots: SnapshotBuffer,
+
+  // Follows the server's clock, see TimeOfDay.
+  // todo: the sky needs to follow this once there is one.
+  time_of_day: TimeOfDay,
+
+  debug_overlay: DebugOverlay,
+  // The animated FOV, the Camera gets it every frame.
+  zoom: Zoom,
+  // Free flying, the key is keymap_noclip.
+  spectator: Spectator,
+  // Where the local player is, ahead of the server.
+  prediction: MovePrediction,
+  // Handed in by the Game, the Client doesn't keep track of it's own frames.
+  average_fps: f64,
+  player_count_poll_timer: f64,
 
   quit_received: bool,
 
+  // Queued up for the Game's EventBus.
+  events: Vec<EngineEvent>,
+
   // ! TESTING
   spin_test: f64,
 
@@ -50,35 +118,95 @@ pub struct Client {
 }
 
 impl Client {
-  pub fn new(client_name: String, address: String, port: i32) -> Self {
+  ///
+  /// Create a Client, panicking if it can't render.
+  ///
+  /// Use try_new() to handle that instead.
+  ///
+  pub fn new(client_name: String, address: String, port: i32, config: &GameConfig) -> Self {
+    match Self::try_new(client_name, address, port, config) {
+      Ok(new_client) => new_client,
+      Err(e) => panic!("Client: {}", e),
+    }
+  }
+
+  ///
+  /// Create a Client.
+  ///
+  /// Fails if no graphics adapter works on this machine.
+  ///
+  pub fn try_new(
+    client_name: String,
+    address: String,
+    port: i32,
+    config: &GameConfig,
+  ) -> Result<Self, RenderInitError> {
     // Input engines.
-    let mut mouse = MouseController::new();
+    let mut mouse = MouseController::from_config(config);
     let keyboard = KeyboardController::new();
 
     // Set up the window handler.
-    let window_handler = WindowHandler::new(&mut mouse);
+    let window_handler = WindowHandler::new(&mut mouse, &WindowSettings::from_config(config));
 
     // Set up the render engine.
-    let render_engine = RenderEngine::new(&window_handler);
+    let mut render_engine = RenderEngine::new(&window_handler, config)?;
+
+    // The first snapshot is wherever the Camera starts out.
+    let snapshots = SnapshotBuffer::new(Snapshot {
+      camera_position: *render_engine.get_camera().get_position(),
+      camera_rotation: *render_engine.get_camera().get_rotation(),
+      ..Default::default()
+    });
+    let prediction = MovePrediction::new(*render_engine.get_camera().get_position());
 
     // Set up a blank client connection.
-    let connection = ClientConnection::new(address, port);
+    let mut connection = ClientConnection::new(address, port);
+    connection.set_max_message_bytes(get_max_message_bytes(config));
+    connection.set_connect_timeout(config.get_parsed("connect_timeout", DEFAULT_CONNECT_TIMEOUT));
+    connection.set_password(&config.get_string("password", ""));
 
     // Finally create the Client-side luau virtual machine.
     let lua_engine = LuaEngine::new(false);
 
+    // Media from servers is cached on disk by content hash.
+    let media_cache = MediaCache::new(
+      "./cache/media",
+      config.get_parsed("media_cache_size_bytes", DEFAULT_MEDIA_CACHE_SIZE_BYTES),
+    );
+
+    // Sounds play on their own thread, the Client only queues them up.
+    let sound_manager = SoundManager::from_config(config);
+
     let mut new_client = Client {
-      window_handler,
       render_engine,
+      window_handler,
       client_name,
       connection,
+      connection_status: ConnectionStatus::Disconnected,
       lua_engine,
+      media_cache,
+      sound_manager,
+      entities: EntityRegistry::from_config(config),
 
       mouse,
       keyboard,
+      input_events: Rc::new(RefCell::new(InputEventQueue::new())),
+
+      snapshots,
+
+      time_of_day: TimeOfDay::new(DEFAULT_START_TIME, DEFAULT_TIME_SPEED),
+
+      debug_overlay: DebugOverlay::new(config),
+      zoom: Zoom::new(config),
+      spectator: Spectator::new(config),
+      prediction,
+      average_fps: 0.0,
+      player_count_poll_timer: 0.0,
 
       quit_received: false,
 
+      events: vec![],
+
       // ! TESTING
       spin_test: 0.0,
       color_fun: 0.0,
@@ -88,7 +216,9 @@ impl Client {
 
     println!("Client: player name is: {}", &new_client.client_name);
 
-    new_client
+    new_client.connect();
+
+    Ok(new_client)
   }
 
   ///
@@ -106,12 +236,135 @@ impl Client {
     self.client_name.clone()
   }
 
+  ///
+  /// Start connecting to the server, as this client's name.
+  ///
+  /// This returns right away, poll get_connection_status() or listen for
+  /// ConnectionStatusChanged to see how it goes.
+  ///
+  pub fn connect(&mut self) {
+    let name = self.client_name.clone();
+    self.connection.connect(&name);
+    self.update_connection_status();
+  }
+
+  ///
+  /// Leave the server, and connect to a different one.
+  ///
+  pub fn connect_to(&mut self, address: String, port: i32) {
+    self.disconnect();
+    self.connection.set_address(address);
+    self.connection.set_port(port);
+    self.connect();
+  }
+
+  ///
+  /// Leave the server. Everything it spawned goes away.
+  ///
+  pub fn disconnect(&mut self) {
+    self.connection.disconnect();
+    self.entities.clear();
+    self.update_connection_status();
+  }
+
+  ///
+  /// Get where the connection to the server is at.
+  ///
+  pub fn get_connection_status(&self) -> &ConnectionStatus {
+    self.connection.get_status()
+  }
+
+  ///
+  /// Tell the EventBus if the connection moved along since last time.
+  ///
+  fn update_connection_status(&mut self) {
+    if self.connection.get_status() != &self.connection_status {
+      self.connection_status = self.connection.get_status().clone();
+      self.events.push(EngineEvent::ConnectionStatusChanged(
+        self.connection_status.clone(),
+      ));
+    }
+  }
+
   ///
   /// Wipe the memory of the lua VM.
   /// Automatically regenerates a blank client VM.
   ///
   pub fn reset_lua_vm(&mut self) {
     self.lua_engine = LuaEngine::new(false);
+    self.lua_engine.set_input_events(self.input_events.clone());
+  }
+
+  ///
+  /// Get a piece of media by it's SHA1 hash.
+  ///
+  /// The MediaCache is always consulted first, so media only needs to be
+  /// requested from the server when it's not already on disk.
+  ///
+  /// On a miss this asks the server for it and returns None. Once it
+  /// arrives it's in the MediaCache, and the next call gets it.
+  ///
+  pub fn get_media(&mut self, hash: &str) -> Option<Vec<u8>> {
+    let cached = self.media_cache.get_cached(hash);
+    if cached.is_none() {
+      self.connection.request_media(hash);
+    }
+    cached
+  }
+
+  ///
+  /// Put the media the server sent into the MediaCache.
+  ///
+  /// Anything that doesn't hash to what was asked for is thrown out.
+  ///
+  fn store_received_media(&mut self) {
+    for (hash, data) in self.connection.take_media() {
+      if hash_bytes(&data) != hash {
+        println!("Client: media from the server doesn't match [{}].", hash);
+        continue;
+      }
+      if let Err(e) = self.media_cache.store(&data) {
+        println!("Client: {}", e);
+      }
+    }
+  }
+
+  ///
+  /// Store media received from a server into the MediaCache.
+  ///
+  /// Returns the SHA1 hash it was stored under.
+  ///
+  pub fn store_media(&mut self, data: &[u8]) -> Result<String, String> {
+    self.media_cache.store(data)
+  }
+
+  ///
+  /// Start recording incoming packets into a file, for Replay.
+  ///
+  pub fn start_recording(&mut self, path: &str) -> Result<(), String> {
+    self.connection.set_recorder(Recorder::create(path)?);
+    Ok(())
+  }
+
+  ///
+  /// Tell the Client the FPS, for the debug overlay.
+  ///
+  pub fn set_average_fps(&mut self, average_fps: f64) {
+    self.average_fps = average_fps;
+  }
+
+  ///
+  /// Change the mouse look sensitivity, live. [radians per pixel]
+  ///
+  pub fn set_mouse_sensitivity(&mut self, new_sensitivity: f32) {
+    self.mouse.set_sensitivity(new_sensitivity);
+  }
+
+  ///
+  /// Change if moving the mouse up looks down, live.
+  ///
+  pub fn set_invert_mouse_y(&mut self, invert_y: bool) {
+    self.mouse.set_invert_y(invert_y);
   }
 
   ///
@@ -121,6 +374,20 @@ impl Client {
     self.quit_received = true;
   }
 
+  ///
+  /// Get the time of day, as the Client sees it. [0.0 - 1.0]
+  ///
+  pub fn get_time_of_day(&self) -> f64 {
+    self.time_of_day.get()
+  }
+
+  ///
+  /// Hand over the EngineEvents from this tick.
+  ///
+  pub fn take_events(&mut self) -> Vec<EngineEvent> {
+    std::mem::take(&mut self.events)
+  }
+
   ///
   /// Retrieve if the client wants to quit.
   ///
@@ -143,84 +410,170 @@ impl Client {
   ///
   /// This is referred to as on_step in C++ minetest.
   ///
-  pub fn on_tick(&mut self, delta: f64) {
+  pub fn on_tick(&mut self, delta: TimeStep) {
     // This is for the Mouse' Camera controls.
     self.mouse.reset_mouse_relative_position();
 
     // Update the SDL2 context.
+    let size_before = *self.window_handler.get_size();
     self
       .window_handler
-      .update(delta, &mut self.mouse, &mut self.keyboard);
+      .update(*delta, &mut self.mouse, &mut self.keyboard);
+    let key_changes = self.keyboard.take_changes();
+    {
+      let mut input_events = self.input_events.borrow_mut();
+      input_events.push_key_changes(&key_changes);
+      input_events.push_mouse_delta(*self.mouse.get_relative_position());
+    }
+    if *self.window_handler.get_size() != size_before {
+      self
+        .events
+        .push(EngineEvent::WindowResized(*self.window_handler.get_size()));
+    }
 
     // Poll any incoming network traffic. (non blocking)
-    if self.connection.is_connected() {
-      self.connection.receive(delta);
+    self.connection.receive(*delta);
+    self.update_connection_status();
+
+    // Run the clock, nudged toward the server whenever it says something.
+    if let Some((time_of_day, time_speed)) = self.connection.take_time_of_day() {
+      self.time_of_day.sync(time_of_day, time_speed);
+    }
+    self.time_of_day.advance(delta);
+
+    for (name, gain, pitch) in self.connection.take_sounds() {
+      self.sound_manager.play(&name, gain, pitch);
+    }
+
+    self.store_received_media();
+
+    self.entities.advance(*delta);
+    for message in self.connection.take_entity_messages() {
+      self.entities.apply(&message);
+    }
+
+    self.debug_overlay.update(&self.keyboard);
+
+    // Only bother the server while someone is looking.
+    if self.debug_overlay.is_visible() && self.connection.is_connected() {
+      self.player_count_poll_timer += *delta;
+      if self.player_count_poll_timer >= PLAYER_COUNT_POLL_SECONDS {
+        self.player_count_poll_timer = 0.0;
+        self.connection.request_status();
+      }
     }
 
     //todo: probably should do user input here
 
     self.lua_engine.on_tick(delta);
+    // The mods had their chance to look.
+    self.input_events.borrow_mut().clear();
 
     //todo: should probably do side effects from lua here
 
-    let camera = self.render_engine.get_camera();
+    // On a server it's theirs to allow, alone there's nobody to ask.
+    let spectate_toggle = self.spectator.update(&self.keyboard);
+    let spectating = match self.connection.is_connected() {
+      true => {
+        if let Some(enabled) = spectate_toggle {
+          self.connection.request_spectate(enabled);
+        }
+        self.connection.take_spectating()
+      }
+      false => spectate_toggle,
+    };
+
+    // The server has the final say, like at spawn or at the border.
+    if let Some((position, acknowledged)) = self.connection.take_moved_to() {
+      self.prediction.reconcile(position, acknowledged);
+    }
 
-    let mut camera_pos = *camera.get_position();
+    match spectating {
+      Some(true) => self.spectator.enter(self.prediction.get_position()),
+      Some(false) => {
+        if let Some(position) = self.spectator.exit() {
+          self.prediction.reset(position);
+        }
+      }
+      None => (),
+    }
 
-    let move_speed = delta as f32 * 10.0;
+    // * Simulation. This only writes into the back snapshot.
+    let simulation = self.snapshots.get_back_mut();
+
+    let move_speed = match self.spectator.is_active() {
+      true => *delta as f32 * 10.0 * SPECTATOR_SPEED_MULTIPLIER,
+      false => *delta as f32 * 10.0,
+    };
 
     // * A very simple test to check the buffer in the shader.
+    let mut displacement = Vec3A::ZERO;
     if self.keyboard.is_key_down("A") {
-      camera_pos.x += move_speed;
+      displacement.x += move_speed;
     }
     if self.keyboard.is_key_down("D") {
-      camera_pos.x -= move_speed;
+      displacement.x -= move_speed;
     }
 
     if self.keyboard.is_key_down("W") {
-      camera_pos.z += move_speed;
+      displacement.z += move_speed;
     }
     if self.keyboard.is_key_down("S") {
-      camera_pos.z -= move_speed;
+      displacement.z -= move_speed;
     }
 
     if self.keyboard.is_key_down("Left Shift") {
-      camera_pos.y += move_speed;
+      displacement.y += move_speed;
     }
     if self.keyboard.is_key_down("Space") {
-      camera_pos.y -= move_speed;
+      displacement.y -= move_speed;
     }
 
     // println!("camera pos {:?}", camera_pos);
     let mouse_relative = self.mouse.get_relative_position();
     if mouse_relative.length_squared() != 0 {
       // println!("Mouse is moved!");
-      let camera = self.render_engine.get_camera();
-      let mut camera_rotation = *camera.get_rotation();
+      let look_delta = self.mouse.get_look_delta();
+      simulation.camera_rotation.y += look_delta.x;
+      simulation.camera_rotation.x += look_delta.y;
+    }
 
-      camera_rotation.y += mouse_relative.x as f32 * self.mouse.get_sensitivity();
-      camera_rotation.x += mouse_relative.y as f32 * self.mouse.get_sensitivity();
+    // Move right away, the server corrects it later if it disagrees.
+    let sequence = match displacement == Vec3A::ZERO {
+      true => None,
+      false => self.connection.send_player_move(
+        self.prediction.get_position() + displacement,
+        simulation.camera_rotation,
+      ),
+    };
+    self.prediction.apply_input(displacement, sequence);
+    self.prediction.advance(*delta);
 
-      camera.set_rotation(&camera_rotation);
+    simulation.camera_position = self.prediction.get_display_position();
 
-      println!("{:?}", camera.get_rotation());
-    }
+    // The tick is done, hand it to rendering all at once.
+    self.snapshots.publish();
 
-    self.render_engine.get_camera().set_position(&camera_pos);
+    // * Rendering. This only reads the front snapshot.
+    let frame = self.snapshots.get_front();
+    let camera = self.render_engine.get_camera();
+    camera.set_position(&frame.camera_position);
+    camera.set_rotation(&frame.camera_rotation);
+    camera.set_fov(self.zoom.update(&self.keyboard, *delta).to_radians());
+    // Smooth the Camera toward the snapshot, if camera_smoothing is on.
+    camera.update(*delta);
 
     // Update the RenderEngine with the WindowHandler.
-    self.render_engine.update(&self.window_handler, delta);
+    self.render_engine.update(&self.window_handler, *delta);
 
     // Now render everything.
 
-    self.spin_test += delta;
+    self.spin_test += *delta;
 
     // println!("spin  {}", self.spin_test);
 
     // Update the camera's projection matrix.
-    self
-      .render_engine
-      .update_camera_matrix(&self.window_handler);
+    self.render_engine.update_camera_matrix();
 
     // Now create the framebuffer.
     self.render_engine.generate_frame_buffer();
@@ -299,6 +652,21 @@ impl Client {
       Vec3A::new(1.0, 1.0, 1.0),
     );
 
+    // Everything the server spawned. Kinds without a model aren't drawn.
+    for (_, entity) in self.entities.iter() {
+      let (model, texture) = match EntityRegistry::get_model_for_kind(&entity.kind) {
+        Some(model_and_texture) => model_and_texture,
+        None => continue,
+      };
+      self.render_engine.render_model(
+        self.render_engine.get_model_id(model),
+        vec![self.render_engine.get_texture_id(texture)],
+        self.entities.get_render_position(entity),
+        Vec3A::new(0.0, 0.0, 0.0),
+        Vec3A::new(1.0, 1.0, 1.0),
+      );
+    }
+
     self.render_engine.process_not_instanced_render_calls();
 
     // * Begin instanced.
@@ -358,6 +726,22 @@ impl Client {
 
     // ? End rendering calls.
 
+    let frame = self.snapshots.get_front();
+    let debug_overlay_stats = DebugOverlayStats {
+      fps: self.average_fps,
+      delta: *delta,
+      // The Camera's position is the view translation, the world position is the inverse of it.
+      camera_position: -frame.camera_position,
+      camera_rotation: frame.camera_rotation,
+      player_count: self.connection.get_player_count(),
+    };
+    if let Some(debug_text) = self.debug_overlay.get_text(&debug_overlay_stats) {
+      let (x, y) = OVERLAY_POSITION;
+      self
+        .render_engine
+        .draw_text(&debug_text, x, y, OVERLAY_SCALE);
+    }
+
     self.render_engine.show_and_destroy_frame_buffer();
 
     // This will need to run a close event for the client engine and send out a close event to the internal server.
@@ -366,3 +750,12 @@ impl Client {
     }
   }
 }
+
+impl Drop for Client {
+  fn drop(&mut self) {
+    // The field order gets this right too, this doesn't count on it.
+    // The window is only destroyed once this is done, when the fields drop.
+    self.render_engine.release_window();
+    println!("Client dropped!");
+  }
+}
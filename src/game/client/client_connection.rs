@@ -1,11 +1,75 @@
-use std::time::Duration;
+use std::{
+  net::{SocketAddr, ToSocketAddrs},
+  time::{Duration, Instant},
+};
 
+use ahash::AHashMap;
+use glam::Vec3A;
 use message_io::{
   events::EventReceiver,
-  network::{Endpoint, ToRemoteAddr, Transport},
+  network::{Endpoint, Transport},
   node::{self, NodeHandler, NodeTask, StoredNetEvent, StoredNodeEvent},
 };
 
+use crate::game::{
+  connection_stats::{ConnectionStats, ConnectionTracker},
+  network_message::NetworkMessage,
+  replay::Recorder,
+  serial::{deserialize_bounded, serialize_into, DEFAULT_MAX_MESSAGE_BYTES},
+  transport_error::TransportError,
+};
+
+///
+/// How long the server gets to answer a handshake, unless minetest.conf says connect_timeout. [seconds]
+///
+pub const DEFAULT_CONNECT_TIMEOUT: f64 = 3.0;
+
+///
+/// How often the handshake goes out again while the server hasn't answered. [seconds]
+///
+/// The first one can go out before the socket is ready, and UDP drops things.
+///
+const HANDSHAKE_RESEND_DELTA: f64 = 0.5;
+
+///
+/// How long a MediaRequest goes unanswered before it can go out again.
+///
+/// The request or the answer could've been dropped, and the server says
+/// nothing at all about media it doesn't have.
+///
+const MEDIA_REQUEST_RESEND: Duration = Duration::from_secs(2);
+
+///
+/// The biggest piece of media a server can send, it's thrown out past this. 64 MB
+///
+const MAX_MEDIA_BYTES: usize = 64 * 1024 * 1024;
+
+///
+/// A piece of media coming in chunk by chunk.
+///
+#[derive(Default)]
+struct MediaDownload {
+  // The chunks so far, in order.
+  data: Vec<u8>,
+  next_chunk: u32,
+  // When next_chunk was last asked for, None if it never was.
+  requested_at: Option<Instant>,
+}
+
+///
+/// Where the connection to the server is at, for the UI to show.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionStatus {
+  // Never connected, or disconnect() was called.
+  Disconnected,
+  // The handshake went out, the server hasn't answered yet.
+  Connecting,
+  Connected,
+  // Why it didn't work, or why it stopped working.
+  Failed(String),
+}
+
 ///
 /// ClientConnection and Client can be considered 1 entity.
 ///
@@ -15,86 +79,395 @@ pub struct ClientConnection {
   address: String,
   port: i32,
 
-  connected: bool,
+  status: ConnectionStatus,
 
   handshake_timeout: f64,
+  handshake_resend_delta: f64,
+  // Giving up once handshake_timeout gets here, see set_connect_timeout.
+  connect_timeout: f64,
+  // Who we're connecting as, for resending the handshake.
+  name: String,
+  password: String,
 
   ping_resend_delta: f64,
   ping_waiting_receive: bool,
   ping_timeout: f64,
 
-  lost_connection: bool,
+  // From the last StatusResponse, if there was one.
+  player_count: Option<u32>,
+  // The newest TimeOfDay the Client hasn't picked up yet, (time_of_day, time_speed).
+  time_of_day: Option<(f64, f64)>,
+  // The newest MovePlayer the Client hasn't picked up yet, (position, acknowledged).
+  moved_to: Option<(Vec3A, u32)>,
+  // The newest Spectate the Client hasn't picked up yet.
+  spectating: Option<bool>,
+  // Sounds the server wants played that the Client hasn't picked up yet, (name, gain, pitch).
+  sounds: Vec<(String, f32, f32)>,
+  // AddEntity and RemoveEntity the Client hasn't picked up yet, in order.
+  entity_messages: Vec<NetworkMessage>,
+  // Media asked for and not all here yet, hash -> MediaDownload.
+  requested_media: AHashMap<String, MediaDownload>,
+  // Media the server sent that the Client hasn't picked up yet, (hash, data).
+  media: Vec<(String, Vec<u8>)>,
+
+  // Anything bigger is dropped before it's parsed, see max_message_bytes.
+  max_message_bytes: usize,
+  // Everything that goes out is serialized into this, so sending doesn't allocate.
+  send_buffer: Vec<u8>,
+
+  // Ping, packet loss, and throughput against the server.
+  tracker: ConnectionTracker,
 
-  end_point: Endpoint,
+  // Every packet that comes in is written into this, if it's recording.
+  recorder: Option<Recorder>,
+
+  // Where connect() resolved the server to.
+  remote_address: Option<SocketAddr>,
+  // None while there's no server to talk to.
+  end_point: Option<Endpoint>,
   task: NodeTask,
   handler: NodeHandler<()>,
   event_receiver: EventReceiver<StoredNodeEvent<()>>,
 }
 
 impl ClientConnection {
+  ///
+  /// Set up the network handler, without talking to anyone yet. See connect().
+  ///
   pub fn new(address: String, port: i32) -> Self {
-    let remote_address = match Self::get_socket(&address, port).to_remote_addr() {
-      Ok(address) => address,
-      Err(e) => panic!("ClientConnection: Socket get failure. {}", e),
-    };
-
-    let transport_protocol = Transport::Udp;
-
-    // todo: will need to be initialized by the gui component.
-
     let (handler, listener) = node::split();
-
-    // todo: fixme: this is udp, why are we doing a match here?
-    // todo: If this fails, the user probably doesn't have a network
-    // todo: adapter!
-    let (server_id, local_address) = match handler
-      .network()
-      .connect(transport_protocol, remote_address)
-    {
-      Ok((end_point, local_address)) => {
-        // UDP is connectionless, but it's still good to know it's working.
-        println!(
-          "ClientConnection: established connection to server at id [{}], local address [{}]",
-          end_point, local_address
-        );
-        (end_point, local_address)
-      }
-      Err(e) => panic!("{}", e),
-    };
-
     let (task, event_receiver) = listener.enqueue();
-    let end_point = server_id;
-
-    // Can possibly be used as a handshake
-    // ! Note: this literally is the handshake right now
-    // self.send_data(self.end_point.un no >:(  wrap(), "MINETEST_HAND_SHAKE");
 
     ClientConnection {
       address,
       port,
 
-      connected: false,
+      status: ConnectionStatus::Disconnected,
 
       handshake_timeout: 0.0,
+      handshake_resend_delta: 0.0,
+      connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+      name: String::new(),
+      password: String::new(),
 
       ping_resend_delta: 0.0,
       ping_waiting_receive: false,
       ping_timeout: 0.0,
 
-      lost_connection: false,
+      player_count: None,
+      time_of_day: None,
+      moved_to: None,
+      spectating: None,
+      sounds: vec![],
+      entity_messages: vec![],
+      requested_media: AHashMap::new(),
+      media: vec![],
 
-      end_point,
+      max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+      send_buffer: vec![],
+
+      tracker: ConnectionTracker::new(Instant::now()),
+
+      recorder: None,
+
+      remote_address: None,
+      end_point: None,
       task,
       handler,
       event_receiver,
     }
   }
 
+  ///
+  /// Start connecting to the server at the address & port, as a player.
+  ///
+  /// This returns right away, poll get_status() to see how it's going.
+  /// Already being connected somewhere disconnects from there first.
+  ///
+  pub fn connect(&mut self, name: &str) {
+    if self.end_point.is_some() {
+      self.disconnect();
+    }
+    self.reset_session();
+
+    // message_io can only send UDP to an address that's already resolved.
+    let socket = Self::get_socket(&self.address, self.port);
+    let remote_address = match socket.to_socket_addrs().map(|mut found| found.next()) {
+      Ok(Some(remote_address)) => remote_address,
+      Ok(None) => {
+        self.fail(format!("[{}] is not a valid address.", socket));
+        return;
+      }
+      Err(e) => {
+        self.fail(format!("[{}] is not a valid address. {}", socket, e));
+        return;
+      }
+    };
+
+    self.remote_address = Some(remote_address);
+    self.status = ConnectionStatus::Connecting;
+    self.name = name.to_string();
+    self.open_end_point();
+    self.send_handshake();
+  }
+
+  ///
+  /// Open the socket to the server.
+  ///
+  /// If the OS is only busy, the next handshake resend tries again.
+  ///
+  fn open_end_point(&mut self) {
+    let remote_address = match self.remote_address {
+      Some(remote_address) => remote_address,
+      None => return,
+    };
+
+    // UDP is connectionless, this only fails without a network adapter.
+    match self
+      .handler
+      .network()
+      .connect(Transport::Udp, remote_address)
+    {
+      Ok((end_point, local_address)) => {
+        println!(
+          "ClientConnection: connecting to server at id [{}], local address [{}]",
+          end_point, local_address
+        );
+        self.end_point = Some(end_point);
+      }
+      Err(e) => match TransportError::from_io_error(&e) {
+        e @ TransportError::Transient(_) => {
+          println!("ClientConnection: reaching [{}]. {}", remote_address, e)
+        }
+        e => self.fail(format!("Failed to reach [{}]. {}", remote_address, e)),
+      },
+    }
+  }
+
+  fn send_handshake(&mut self) {
+    self.send_to_server(&NetworkMessage::HandShake {
+      name: self.name.clone(),
+      password: self.password.clone(),
+    });
+  }
+
+  ///
+  /// Leave the server, telling it so it can free up our slot.
+  ///
+  /// The network handler stays up, connect() can go somewhere else after.
+  ///
+  pub fn disconnect(&mut self) {
+    if self.status == ConnectionStatus::Connected {
+      self.send_to_server(&NetworkMessage::Disconnect {
+        reason: "Client quit.".to_string(),
+      });
+    }
+    if let Some(end_point) = self.end_point.take() {
+      self.handler.network().remove(end_point.resource_id());
+    }
+    self.status = ConnectionStatus::Disconnected;
+  }
+
+  ///
+  /// Forget everything about the last server.
+  ///
+  fn reset_session(&mut self) {
+    self.handshake_timeout = 0.0;
+    self.handshake_resend_delta = 0.0;
+    self.ping_resend_delta = 0.0;
+    self.ping_waiting_receive = false;
+    self.ping_timeout = 0.0;
+    self.player_count = None;
+    self.time_of_day = None;
+    self.moved_to = None;
+    self.spectating = None;
+    self.sounds.clear();
+    self.entity_messages.clear();
+    self.requested_media.clear();
+    self.media.clear();
+    self.tracker = ConnectionTracker::new(Instant::now());
+  }
+
+  ///
+  /// Give up on the server.
+  ///
+  fn fail(&mut self, reason: String) {
+    println!("ClientConnection: {}", reason);
+    if let Some(end_point) = self.end_point.take() {
+      self.handler.network().remove(end_point.resource_id());
+    }
+    self.status = ConnectionStatus::Failed(reason);
+  }
+
+  ///
+  /// Get where the connection to the server is at.
+  ///
+  pub fn get_status(&self) -> &ConnectionStatus {
+    &self.status
+  }
+
   ///
   /// Get if the Client is connected to a server.
   ///
   pub fn is_connected(&self) -> bool {
-    self.connected
+    self.status == ConnectionStatus::Connected
+  }
+
+  ///
+  /// Ask the server how it's doing. The answer fills in get_player_count.
+  ///
+  pub fn request_status(&mut self) {
+    self.send_to_server(&NetworkMessage::StatusRequest);
+  }
+
+  ///
+  /// Get how many players the server said are connected.
+  ///
+  /// None until the server answers request_status.
+  ///
+  pub fn get_player_count(&self) -> Option<u32> {
+    self.player_count
+  }
+
+  ///
+  /// Take the newest time of day from the server, (time_of_day, time_speed).
+  ///
+  /// None if nothing new came in since the last take.
+  ///
+  pub fn take_time_of_day(&mut self) -> Option<(f64, f64)> {
+    self.time_of_day.take()
+  }
+
+  ///
+  /// Take where the server last put the player, like at spawn, (position, acknowledged).
+  ///
+  /// None if it hasn't moved them since the last take.
+  ///
+  pub fn take_moved_to(&mut self) -> Option<(Vec3A, u32)> {
+    self.moved_to.take()
+  }
+
+  ///
+  /// Tell the server where the player is.
+  ///
+  /// Returns the sequence it went out with, for MovePrediction. None if not connected.
+  ///
+  pub fn send_player_move(&mut self, position: Vec3A, rotation: Vec3A) -> Option<u32> {
+    if !self.is_connected() {
+      return None;
+    }
+    self.send_to_server(&NetworkMessage::PlayerMove { position, rotation })
+  }
+
+  ///
+  /// Ask the server to start or stop spectating. The answer comes in take_spectating.
+  ///
+  pub fn request_spectate(&mut self, enabled: bool) {
+    self.send_to_server(&NetworkMessage::Spectate { enabled });
+  }
+
+  ///
+  /// Take the spectating mode the server last put the player in, if it said since the last take.
+  ///
+  pub fn take_spectating(&mut self) -> Option<bool> {
+    self.spectating.take()
+  }
+
+  ///
+  /// Take every sound the server asked for since the last take, (name, gain, pitch).
+  ///
+  pub fn take_sounds(&mut self) -> Vec<(String, f32, f32)> {
+    std::mem::take(&mut self.sounds)
+  }
+
+  ///
+  /// Take every AddEntity, RemoveEntity and EntityPositions since the last take, see EntityRegistry.
+  ///
+  pub fn take_entity_messages(&mut self) -> Vec<NetworkMessage> {
+    std::mem::take(&mut self.entity_messages)
+  }
+
+  ///
+  /// Ask the server for a piece of media by it's SHA1 hash. The answer comes in take_media.
+  ///
+  /// Asking again before MEDIA_REQUEST_RESEND is up does nothing, so this
+  /// can be called every time the media is wanted. Past that, the chunk
+  /// the download is stuck on is asked for again.
+  ///
+  pub fn request_media(&mut self, hash: &str) {
+    if !self.is_connected() {
+      return;
+    }
+    let now = Instant::now();
+    let download = self.requested_media.entry(hash.to_string()).or_default();
+    if let Some(requested_at) = download.requested_at {
+      if now.duration_since(requested_at) < MEDIA_REQUEST_RESEND {
+        return;
+      }
+    }
+    download.requested_at = Some(now);
+    let chunk = download.next_chunk;
+    self.send_to_server(&NetworkMessage::MediaRequest {
+      hash: hash.to_string(),
+      chunk,
+    });
+  }
+
+  ///
+  /// Add a chunk of media to it's download, and ask for the next one.
+  ///
+  /// Media nobody asked for and chunks out of turn are dropped.
+  ///
+  fn receive_media_chunk(&mut self, hash: String, chunk: u32, chunk_count: u32, data: Vec<u8>) {
+    let download = match self.requested_media.get_mut(&hash) {
+      Some(download) if download.next_chunk == chunk => download,
+      _ => return,
+    };
+
+    if download.data.len() + data.len() > MAX_MEDIA_BYTES {
+      println!(
+        "ClientConnection: media [{}] is over [{}] bytes, giving up on it.",
+        hash, MAX_MEDIA_BYTES
+      );
+      self.requested_media.remove(&hash);
+      return;
+    }
+    download.data.extend(data);
+    download.next_chunk += 1;
+
+    if download.next_chunk >= chunk_count {
+      if let Some(download) = self.requested_media.remove(&hash) {
+        self.media.push((hash, download.data));
+      }
+      return;
+    }
+
+    // Straight on to the next chunk, no need to wait for the Client to ask.
+    download.requested_at = Some(Instant::now());
+    let chunk = download.next_chunk;
+    self.send_to_server(&NetworkMessage::MediaRequest { hash, chunk });
+  }
+
+  ///
+  /// Take every piece of media the server sent since the last take, (hash, data).
+  ///
+  pub fn take_media(&mut self) -> Vec<(String, Vec<u8>)> {
+    std::mem::take(&mut self.media)
+  }
+
+  ///
+  /// Get how the connection to the server is doing.
+  ///
+  pub fn get_connection_stats(&self) -> ConnectionStats {
+    self.tracker.get_stats(Instant::now())
+  }
+
+  ///
+  /// Ping the server right now. The round trip shows up in get_connection_stats.
+  ///
+  pub fn send_ping(&mut self) {
+    let id = self.tracker.start_ping(Instant::now());
+    self.send_to_server(&NetworkMessage::PingRequest { id });
   }
 
   ///
@@ -123,10 +496,35 @@ impl ClientConnection {
   }
 
   ///
-  /// Send raw data to the EndPoint (ServerConnection).
+  /// Send a NetworkMessage to the EndPoint (ServerConnection).
+  ///
+  fn send_data(&mut self, end_point: Endpoint, message: &NetworkMessage) -> u32 {
+    let sequence = self.tracker.get_next_sequence();
+    serialize_into(&mut self.send_buffer, sequence, message);
+    self
+      .tracker
+      .record_sent(self.send_buffer.len(), Instant::now());
+    let status = self.handler.network().send(end_point, &self.send_buffer);
+    match TransportError::check_send(status) {
+      // UDP might drop it anyway, whatever needs to get there gets resent.
+      Ok(()) | Err(TransportError::Transient(_)) => (),
+      Err(e @ TransportError::Message(_)) => println!("ClientConnection: {}", e),
+      // While connecting that's a server that isn't up yet, the handshake timeout decides.
+      Err(e @ TransportError::Fatal(_)) => {
+        if self.status != ConnectionStatus::Connecting {
+          self.fail(e.to_string());
+        }
+      }
+    }
+    sequence
+  }
+
+  ///
+  /// Send a NetworkMessage to the server, if there is one.
   ///
-  fn send_data(&self, end_point: Endpoint, data: &str) {
-    self.handler.network().send(end_point, data.as_bytes());
+  fn send_to_server(&mut self, message: &NetworkMessage) -> Option<u32> {
+    let end_point = self.end_point?;
+    Some(self.send_data(end_point, message))
   }
 
   ///
@@ -135,37 +533,85 @@ impl ClientConnection {
   pub fn event_reaction(&mut self, event: StoredNetEvent) {
     // We don't need to match, we're using UDP which is connectionless.
     if let StoredNetEvent::Message(end_point, raw_message) = event {
-      // todo: use https://github.com/serde-rs/bytes
-      let receieved_string = match String::from_utf8(raw_message) {
-        Ok(new_string) => new_string,
-        Err(_) => {
-          println!("ClientConnection: message buffer attack detected, bailing on deserialization!");
-          "".to_string()
+      // Stragglers from a server we already left.
+      if Some(end_point) != self.end_point {
+        return;
+      }
+      let packet = match deserialize_bounded(&raw_message, self.max_message_bytes) {
+        Ok(packet) => packet,
+        Err(e) => {
+          println!("ClientConnection: bad message from server. {}", e);
+          return;
         }
       };
+      if let Some(recorder) = &self.recorder {
+        recorder.record_packet(end_point.addr(), &packet, Instant::now());
+      }
+      let messages = self
+        .tracker
+        .receive(packet, raw_message.len(), Instant::now());
+      for message in messages {
+        self.handle_message(end_point, message);
+      }
+    }
+  }
 
-      match receieved_string.as_str() {
-        "hi" => println!("ClientConnection: The server says hi."),
-        "MINETEST_HAND_SHAKE_CONFIRMED" => {
-          // Received handshake with the server.
-          if !self.connected {
-            self.connected = true;
-            self.handshake_timeout = 0.0;
-            println!("ClientConnection: ClientConnection received handshake from ServerConnection.");
-          }
-
-          // ! Do not enable this unless you want the server to
-          // ! shutdown as soon as you connect.
-          // self.send_data(end_point, "MINETEST_SHUT_DOWN_REQUEST");
-        }
-        "MINETEST_PING_CONFIRMATION" => {
-          println!("ClientConnection: ClientConnection ping received from ServerConnection.");
-          self.ping_timeout = 0.0;
-          self.ping_waiting_receive = false;
-          self.ping_resend_delta = 0.0;
-        }
-        _ => (),
+  ///
+  /// Act on one message, after the ConnectionTracker let it through.
+  ///
+  fn handle_message(&mut self, end_point: Endpoint, message: NetworkMessage) {
+    match message {
+      NetworkMessage::HiThere => println!("ClientConnection: The server says hi."),
+      // todo: this needs to go into a chat window.
+      NetworkMessage::ChatMessage(chat_message) => println!("{}", chat_message),
+      // Received handshake with the server.
+      NetworkMessage::HandShakeConfirmed if self.status == ConnectionStatus::Connecting => {
+        self.status = ConnectionStatus::Connected;
+        self.handshake_timeout = 0.0;
+        println!("ClientConnection: ClientConnection received handshake from ServerConnection.");
+
+        // ! Do not enable this unless you want the server to
+        // ! shutdown as soon as you connect.
+        // self.send_data(end_point, &NetworkMessage::ShutDownRequest);
+      }
+      NetworkMessage::HandShakeRejected { reason } => {
+        self.fail(format!("Server rejected the connection. {}", reason))
+      }
+      NetworkMessage::Disconnect { reason } => {
+        self.fail(format!("Disconnected by the server. {}", reason))
       }
+      // The server keeps it's own round trip to us.
+      NetworkMessage::PingRequest { id } => {
+        self.send_data(end_point, &NetworkMessage::PingConfirmation { id });
+      }
+      NetworkMessage::PingConfirmation { id } => {
+        println!("ClientConnection: ClientConnection ping received from ServerConnection.");
+        self.tracker.finish_ping(id, Instant::now());
+        self.ping_timeout = 0.0;
+        self.ping_waiting_receive = false;
+        self.ping_resend_delta = 0.0;
+      }
+      NetworkMessage::StatusResponse { players, .. } => self.player_count = Some(players),
+      NetworkMessage::TimeOfDay {
+        time_of_day,
+        time_speed,
+      } => self.time_of_day = Some((time_of_day, time_speed)),
+      NetworkMessage::MovePlayer {
+        position,
+        acknowledged,
+      } => self.moved_to = Some((position, acknowledged)),
+      NetworkMessage::Spectate { enabled } => self.spectating = Some(enabled),
+      NetworkMessage::PlaySound { name, gain, pitch } => self.sounds.push((name, gain, pitch)),
+      NetworkMessage::Media {
+        hash,
+        chunk,
+        chunk_count,
+        data,
+      } => self.receive_media_chunk(hash, chunk, chunk_count, data),
+      message @ (NetworkMessage::AddEntity { .. }
+      | NetworkMessage::RemoveEntity { .. }
+      | NetworkMessage::EntityPositions { .. }) => self.entity_messages.push(message),
+      _ => (),
     }
   }
 
@@ -175,13 +621,24 @@ impl ClientConnection {
   ///
   fn check_handshake(&mut self, delta: f64) {
     // Handshake timeout, aka server connection timeout
-    if !self.connected {
+    if self.status == ConnectionStatus::Connecting {
       self.handshake_timeout += delta;
 
-      // 3 second timeout.
-      // todo: make this not a panic.
-      if self.handshake_timeout >= 3.0 {
-        panic!("ClientConnection: ClientConnection attempt to connect to server timed out.")
+      if self.handshake_timeout >= self.connect_timeout {
+        self.fail(format!(
+          "Could not reach the server at {}:{}, connecting timed out after {} seconds.",
+          self.address, self.port, self.connect_timeout
+        ));
+        return;
+      }
+
+      self.handshake_resend_delta += delta;
+      if self.handshake_resend_delta >= HANDSHAKE_RESEND_DELTA {
+        self.handshake_resend_delta = 0.0;
+        if self.end_point.is_none() {
+          self.open_end_point();
+        }
+        self.send_handshake();
       }
     }
   }
@@ -191,28 +648,69 @@ impl ClientConnection {
   ///
   fn do_ping_timeout_logic(&mut self, delta: f64) {
     // If we're not connected, don't attempt to do this.
-    if self.connected {
-      if self.ping_waiting_receive {
-        // We're waiting for the server to respond.
-        self.ping_timeout += delta;
-
-        // 3 second timeout.
-        // todo: make this not a panic.
-        if self.ping_timeout >= 3.0 {
-          panic!("ClientConnection: ClientConnection connection to server timed out.")
-        }
-      } else {
-        // Wait 3 seconds before pinging the server again.
-        self.ping_resend_delta += delta;
+    if !self.is_connected() {
+      return;
+    }
 
-        if self.ping_resend_delta >= 3.0 {
-          self.ping_waiting_receive = true;
-          self.send_data(self.end_point, "MINETEST_PING_REQUEST");
-        }
+    if self.ping_waiting_receive {
+      // We're waiting for the server to respond.
+      self.ping_timeout += delta;
+
+      // 3 second timeout.
+      // todo: make this not a panic.
+      if self.ping_timeout >= 3.0 {
+        self.fail("Connection to the server timed out.".to_string());
+      }
+    } else {
+      // Wait 3 seconds before pinging the server again.
+      self.ping_resend_delta += delta;
+
+      if self.ping_resend_delta >= 3.0 {
+        self.ping_waiting_receive = true;
+        self.send_ping();
       }
     }
   }
 
+  ///
+  /// Change how long connect() waits for the handshake before giving up. [seconds]
+  ///
+  /// Anything that isn't a positive number keeps the default.
+  ///
+  pub fn set_connect_timeout(&mut self, connect_timeout: f64) {
+    self.connect_timeout = match connect_timeout.is_finite() && connect_timeout > 0.0 {
+      true => connect_timeout,
+      false => DEFAULT_CONNECT_TIMEOUT,
+    };
+  }
+
+  pub fn get_connect_timeout(&self) -> f64 {
+    self.connect_timeout
+  }
+
+  ///
+  /// Set the password sent with the handshake.
+  ///
+  /// Servers without auth take anything, the default is empty.
+  ///
+  pub fn set_password(&mut self, password: &str) {
+    self.password = password.to_string();
+  }
+
+  ///
+  /// Change the biggest message taken from the server, see get_max_message_bytes.
+  ///
+  pub fn set_max_message_bytes(&mut self, max_message_bytes: usize) {
+    self.max_message_bytes = max_message_bytes;
+  }
+
+  ///
+  /// Start writing every incoming packet into a Recorder.
+  ///
+  pub fn set_recorder(&mut self, recorder: Recorder) {
+    self.recorder = Some(recorder);
+  }
+
   ///
   /// Non-blocking event receiver for network events.
   ///
@@ -220,13 +718,24 @@ impl ClientConnection {
     if let Some(event) = self.event_receiver.receive_timeout(Duration::new(0, 0)) {
       match event {
         StoredNodeEvent::Network(new_event) => self.event_reaction(new_event),
-        // todo: figure out what a signal is!
-        StoredNodeEvent::Signal(_) => todo!(),
+        // Nothing sends signals, the handler is only used for the network.
+        StoredNodeEvent::Signal(_) => (),
+      }
+    }
+
+    // Chat that waited on a lost packet long enough.
+    if let Some(end_point) = self.end_point {
+      for message in self.tracker.take_ready(Instant::now()) {
+        self.handle_message(end_point, message);
       }
     }
 
     self.check_handshake(delta);
     self.do_ping_timeout_logic(delta);
+
+    if let Some(recorder) = &self.recorder {
+      recorder.flush();
+    }
   }
 }
 
@@ -235,7 +744,289 @@ impl Drop for ClientConnection {
     // ClientConnection must stop the handler entity or the Client
     // will not shut down.
     println!("Clientconnection: Shutting down network handler.");
+    // Let the server free up our slot instead of waiting on a timeout.
+    self.disconnect();
     NodeHandler::stop(&self.handler);
     println!("ClientConnection dropped!")
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs::remove_dir_all,
+    net::UdpSocket,
+    time::{Duration, Instant},
+  };
+
+  use crate::{
+    file_utilities::{create_dir_all, test_dir, write_file_atomic},
+    game::{
+      client::client_connection::{ClientConnection, ConnectionStatus, DEFAULT_CONNECT_TIMEOUT},
+      game_config::GameConfig,
+      lua_engine::lua_file_helpers::ModDirectory,
+      network_message::{NetworkMessage, MEDIA_CHUNK_BYTES},
+      server::{auth::PasswordAuth, media_index::MediaIndex, server_connection::ServerConnection},
+      test_client::TestClient,
+    },
+    media_hash::hash_bytes,
+  };
+
+  #[test]
+  fn test_client_connection_round_trip_time() {
+    println!("--- BEGIN CLIENT CONNECTION ROUND TRIP TIME TEST ---");
+    let world = test_dir("client_connection_round_trip_time");
+    let mut server =
+      match ServerConnection::new("127.0.0.1".to_string(), 0, &GameConfig::new(), &world) {
+        Ok(server) => server,
+        Err(e) => panic!("Unit test is broken. {}", e),
+      };
+
+    let mut client = ClientConnection::new(
+      "127.0.0.1".to_string(),
+      server.get_real_address().port() as i32,
+    );
+    client.connect("pinger");
+    assert_eq!(client.get_connection_stats().round_trip_time, None);
+
+    // Loopback is fast, but the first ping can go out before the socket is ready.
+    let start = Instant::now();
+    let mut last_ping: Option<Instant> = None;
+    while client.get_connection_stats().round_trip_time.is_none()
+      && start.elapsed() < Duration::from_secs(2)
+    {
+      if last_ping.map_or(true, |last_ping| {
+        last_ping.elapsed() > Duration::from_millis(100)
+      }) {
+        client.send_ping();
+        last_ping = Some(Instant::now());
+      }
+      server.receive();
+      client.receive(0.0);
+    }
+
+    let stats = client.get_connection_stats();
+    let round_trip_time = match stats.round_trip_time {
+      Some(round_trip_time) => round_trip_time,
+      None => panic!("Unit test is broken. The ping never came back."),
+    };
+    assert!(round_trip_time > Duration::ZERO);
+    assert!(round_trip_time < Duration::from_secs(2));
+    assert!(stats.bytes_out_per_second > 0.0);
+    assert!(stats.bytes_in_per_second > 0.0);
+    assert_eq!(stats.packet_loss, 0.0);
+
+    // Only players with a session get tracked on the server.
+    let mut player = TestClient::new(server.get_real_address());
+    player.send_handshake("tracked");
+    assert_eq!(
+      player.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeConfirmed)
+    );
+    let end_point = match server.clients.keys().next() {
+      Some(end_point) => *end_point,
+      None => panic!("Unit test is broken. The player has no session."),
+    };
+    let player_stats = match server.get_connection_stats(end_point) {
+      Some(player_stats) => player_stats,
+      None => panic!("Unit test is broken. The player is not tracked."),
+    };
+    assert!(player_stats.bytes_out_per_second > 0.0);
+
+    drop(client);
+    let _ = remove_dir_all(&world);
+  }
+
+  #[test]
+  fn test_client_connection_media_download() {
+    println!("--- BEGIN CLIENT CONNECTION MEDIA DOWNLOAD TEST ---");
+    let world = test_dir("client_connection_media_download");
+    // Over three chunks, so it takes a few round trips.
+    let model: Vec<u8> = (0..MEDIA_CHUNK_BYTES * 3 + 10)
+      .map(|index| (index % 251) as u8)
+      .collect();
+    let hash = hash_bytes(&model);
+    if let Err(e) = create_dir_all(&format!("{}/rocks/models", world)) {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = write_file_atomic(&format!("{}/rocks/models/rock.obj", world), &model) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let mut server =
+      match ServerConnection::new("127.0.0.1".to_string(), 0, &GameConfig::new(), &world) {
+        Ok(server) => server,
+        Err(e) => panic!("Unit test is broken. {}", e),
+      };
+    server.set_media_index(MediaIndex::from_mods(&[ModDirectory {
+      mod_name: "rocks".to_string(),
+      mod_path: format!("{}/rocks", world),
+      depends: vec![],
+    }]));
+
+    let mut client = ClientConnection::new(
+      "127.0.0.1".to_string(),
+      server.get_real_address().port() as i32,
+    );
+    client.connect("downloader");
+    // Real time, so the handshake gets resent if the first one is lost.
+    let mut last = Instant::now();
+    while client.get_status() == &ConnectionStatus::Connecting {
+      server.receive();
+      client.receive(last.elapsed().as_secs_f64());
+      last = Instant::now();
+    }
+    assert_eq!(client.get_status(), &ConnectionStatus::Connected);
+
+    // The Client asks every frame it wants the media, like it would for a texture.
+    let mut media = vec![];
+    let start = Instant::now();
+    while media.is_empty() && start.elapsed() < Duration::from_secs(5) {
+      client.request_media(&hash);
+      server.receive();
+      client.receive(0.0);
+      media = client.take_media();
+    }
+    assert_eq!(media, vec![(hash, model)]);
+
+    drop(client);
+    drop(server);
+    let _ = remove_dir_all(&world);
+  }
+
+  #[test]
+  fn test_client_connection_status() {
+    println!("--- BEGIN CLIENT CONNECTION STATUS TEST ---");
+    let world = test_dir("client_connection_status");
+    let start_server =
+      || match ServerConnection::new("127.0.0.1".to_string(), 0, &GameConfig::new(), &world) {
+        Ok(server) => server,
+        Err(e) => panic!("Unit test is broken. {}", e),
+      };
+    let connect = |client: &mut ClientConnection, server: &mut ServerConnection| {
+      client.connect("alice");
+      assert_eq!(client.get_status(), &ConnectionStatus::Connecting);
+      // Real time, so the handshake gets resent if the first one is lost.
+      let mut last = Instant::now();
+      while client.get_status() == &ConnectionStatus::Connecting {
+        server.receive();
+        client.receive(last.elapsed().as_secs_f64());
+        last = Instant::now();
+      }
+    };
+    let mut first = start_server();
+    let mut second = start_server();
+
+    let mut client = ClientConnection::new(
+      "127.0.0.1".to_string(),
+      first.get_real_address().port() as i32,
+    );
+    assert_eq!(client.get_status(), &ConnectionStatus::Disconnected);
+    connect(&mut client, &mut first);
+    assert_eq!(client.get_status(), &ConnectionStatus::Connected);
+    assert!(client.is_connected());
+
+    // Going somewhere else leaves the first server.
+    client.disconnect();
+    assert_eq!(client.get_status(), &ConnectionStatus::Disconnected);
+    client.set_port(second.get_real_address().port() as i32);
+    connect(&mut client, &mut second);
+    assert_eq!(client.get_status(), &ConnectionStatus::Connected);
+    let start = Instant::now();
+    while !first.clients.is_empty() && start.elapsed() < Duration::from_secs(2) {
+      first.receive();
+    }
+    assert!(first.clients.is_empty());
+    assert_eq!(second.clients.len(), 1);
+
+    // An address that doesn't parse fails right away.
+    client.set_address("".to_string());
+    client.connect("alice");
+    match client.get_status() {
+      ConnectionStatus::Failed(reason) => assert!(reason.contains("not a valid address")),
+      status => panic!("Bad address was accepted. {:?}", status),
+    }
+
+    // Nobody answering fails once the handshake times out.
+    let nobody = match UdpSocket::bind("127.0.0.1:0").and_then(|socket| socket.local_addr()) {
+      Ok(nobody) => nobody,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    client.set_address("127.0.0.1".to_string());
+    client.set_port(nobody.port() as i32);
+    client.connect("alice");
+    assert_eq!(client.get_status(), &ConnectionStatus::Connecting);
+    for _ in 0..3 {
+      client.receive(1.0);
+    }
+    match client.get_status() {
+      ConnectionStatus::Failed(reason) => assert!(reason.contains("timed out")),
+      status => panic!("Connecting to nobody didn't fail. {:?}", status),
+    }
+
+    // The password goes along with the handshake.
+    let mut third = start_server();
+    third.set_auth_provider(Box::new(PasswordAuth::load(&world, true)));
+    client.set_port(third.get_real_address().port() as i32);
+    connect(&mut client, &mut third);
+    match client.get_status() {
+      ConnectionStatus::Failed(reason) => assert!(reason.contains("password")),
+      status => panic!("Registered without a password. {:?}", status),
+    }
+    client.set_password("hunter2");
+    connect(&mut client, &mut third);
+    assert_eq!(client.get_status(), &ConnectionStatus::Connected);
+
+    drop(client);
+    let _ = remove_dir_all(&world);
+  }
+
+  #[test]
+  fn test_client_connection_connect_timeout() {
+    println!("--- BEGIN CLIENT CONNECTION CONNECT TIMEOUT TEST ---");
+    // A socket that's there, but never answers. Like a firewall dropping everything.
+    let black_hole = match UdpSocket::bind("127.0.0.1:0") {
+      Ok(black_hole) => black_hole,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let port = match black_hole.local_addr() {
+      Ok(address) => address.port() as i32,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+
+    let mut client = ClientConnection::new("127.0.0.1".to_string(), port);
+    client.set_connect_timeout(-1.0);
+    assert_eq!(client.get_connect_timeout(), DEFAULT_CONNECT_TIMEOUT);
+    client.set_connect_timeout(f64::NAN);
+    assert_eq!(client.get_connect_timeout(), DEFAULT_CONNECT_TIMEOUT);
+    client.set_connect_timeout(0.5);
+    assert_eq!(client.get_connect_timeout(), 0.5);
+
+    client.connect("alice");
+    client.receive(0.4);
+    assert_eq!(client.get_status(), &ConnectionStatus::Connecting);
+
+    // Real time from here, it gives up once the configured time is up.
+    let start = Instant::now();
+    let mut last = Instant::now();
+    while client.get_status() == &ConnectionStatus::Connecting
+      && start.elapsed() < Duration::from_secs(2)
+    {
+      let delta = last.elapsed().as_secs_f64();
+      last = Instant::now();
+      client.receive(delta);
+    }
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    match client.get_status() {
+      ConnectionStatus::Failed(reason) => {
+        assert!(reason.contains("Could not reach the server"));
+        assert!(reason.contains("0.5 seconds"));
+      }
+      status => panic!("Connecting to a black hole didn't fail. {:?}", status),
+    }
+
+    drop(black_hole);
+  }
+}
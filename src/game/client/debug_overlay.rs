@@ -0,0 +1,164 @@
+use glam::Vec3A;
+
+use crate::game::game_config::GameConfig;
+
+use super::keyboard::KeyboardController;
+
+///
+/// The key that shows and hides the overlay, if minetest.conf doesn't say.
+///
+/// This is an SDL2 scancode name.
+///
+const DEFAULT_TOGGLE_KEY: &str = "F3";
+
+///
+/// Where the overlay goes on screen. [pixels]
+///
+pub const OVERLAY_POSITION: (f32, f32) = (4.0, 4.0);
+pub const OVERLAY_SCALE: f32 = 1.0;
+
+///
+/// Everything the DebugOverlay shows, gathered up once per frame.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DebugOverlayStats {
+  pub fps: f64,
+  pub delta: f64,
+  pub camera_position: Vec3A,
+  pub camera_rotation: Vec3A,
+  // None until the server answers a status request.
+  pub player_count: Option<u32>,
+}
+
+///
+/// The F3 debug overlay.
+///
+/// Off by default. The key is keymap_toggle_debug in minetest.conf.
+///
+/// This only decides what to show, it never touches the simulation.
+///
+pub struct DebugOverlay {
+  visible: bool,
+  toggle_key: String,
+  toggle_key_was_down: bool,
+}
+
+impl DebugOverlay {
+  pub fn new(config: &GameConfig) -> Self {
+    DebugOverlay {
+      visible: false,
+      toggle_key: config.get_string("keymap_toggle_debug", DEFAULT_TOGGLE_KEY),
+      toggle_key_was_down: false,
+    }
+  }
+
+  ///
+  /// Flip the overlay on or off.
+  ///
+  pub fn toggle(&mut self) {
+    self.visible = !self.visible;
+  }
+
+  ///
+  /// Get if the overlay is showing.
+  ///
+  pub fn is_visible(&self) -> bool {
+    self.visible
+  }
+
+  ///
+  /// Toggle when the key goes down. Holding it doesn't flicker.
+  ///
+  pub fn update(&mut self, keyboard: &KeyboardController) {
+    let toggle_key_down = keyboard.is_key_down(&self.toggle_key);
+    if toggle_key_down && !self.toggle_key_was_down {
+      self.toggle();
+    }
+    self.toggle_key_was_down = toggle_key_down;
+  }
+
+  ///
+  /// Get the text to draw this frame. None if the overlay is hidden.
+  ///
+  pub fn get_text(&self, stats: &DebugOverlayStats) -> Option<String> {
+    if !self.visible {
+      return None;
+    }
+
+    let player_count = match stats.player_count {
+      Some(player_count) => player_count.to_string(),
+      None => "?".to_string(),
+    };
+
+    Some(format!(
+      "FPS: {:.0}\nDelta: {:.2}ms\nPosition: {:.1}, {:.1}, {:.1}\nRotation: {:.2}, {:.2}, {:.2}\nPlayers: {}",
+      stats.fps,
+      stats.delta * 1000.0,
+      stats.camera_position.x,
+      stats.camera_position.y,
+      stats.camera_position.z,
+      stats.camera_rotation.x,
+      stats.camera_rotation.y,
+      stats.camera_rotation.z,
+      player_count
+    ))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::Vec3A;
+
+  use crate::game::{
+    client::{
+      debug_overlay::{DebugOverlay, DebugOverlayStats},
+      keyboard::KeyboardController,
+    },
+    game_config::GameConfig,
+  };
+
+  #[test]
+  fn test_debug_overlay_toggle() {
+    println!("--- BEGIN DEBUG OVERLAY TOGGLE TEST ---");
+    let stats = DebugOverlayStats {
+      fps: 59.6,
+      delta: 0.0167,
+      camera_position: Vec3A::new(1.0, 2.0, 3.0),
+      camera_rotation: Vec3A::ZERO,
+      player_count: Some(3),
+    };
+
+    let mut config = GameConfig::new();
+    config.set("keymap_toggle_debug", "F4");
+    let mut debug_overlay = DebugOverlay::new(&config);
+    let mut keyboard = KeyboardController::new();
+
+    // Off by default, nothing gets drawn.
+    assert!(!debug_overlay.is_visible());
+    assert_eq!(debug_overlay.get_text(&stats), None);
+
+    // The default key is not the configured one.
+    keyboard.set_key("F3", true);
+    debug_overlay.update(&keyboard);
+    assert_eq!(debug_overlay.get_text(&stats), None);
+
+    // Holding the key only toggles once.
+    keyboard.set_key("F4", true);
+    debug_overlay.update(&keyboard);
+    debug_overlay.update(&keyboard);
+    let text = match debug_overlay.get_text(&stats) {
+      Some(text) => text,
+      None => panic!("Unit test is broken. The overlay should be showing."),
+    };
+    assert!(text.contains("FPS: 60"));
+    assert!(text.contains("Position: 1.0, 2.0, 3.0"));
+    assert!(text.contains("Players: 3"));
+
+    // And back off.
+    keyboard.set_key("F4", false);
+    debug_overlay.update(&keyboard);
+    keyboard.set_key("F4", true);
+    debug_overlay.update(&keyboard);
+    assert_eq!(debug_overlay.get_text(&stats), None);
+  }
+}
@@ -0,0 +1,404 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use glam::Vec3A;
+
+use crate::game::{
+  game_config::GameConfig, network_message::NetworkMessage, server::entities::PLAYER_ENTITY_KIND,
+};
+
+///
+/// How many positions an InterpolationBuffer keeps.
+///
+const INTERPOLATION_BUFFER_SIZE: usize = 8;
+
+///
+/// How far behind the newest position entities are drawn, unless
+/// minetest.conf says interpolation_delay. [seconds]
+///
+/// Positions come in with every EntityPositions broadcast. This is about two
+/// broadcasts at the usual rate, so there's something to blend toward.
+///
+pub const DEFAULT_INTERPOLATION_DELAY: f64 = 0.1;
+
+///
+/// The longest interpolation_delay allowed. [seconds]
+///
+/// Any longer and remote players feel like a replay.
+///
+pub const MAX_INTERPOLATION_DELAY: f64 = 0.5;
+
+///
+/// The last few positions of an entity, with when they arrived.
+///
+/// The renderer samples this a little in the past, so it can blend between
+/// two known positions instead of snapping from one update to the next.
+///
+pub struct InterpolationBuffer {
+  // (time, position), oldest first. [seconds]
+  samples: VecDeque<(f64, Vec3A)>,
+}
+
+impl InterpolationBuffer {
+  pub fn new(time: f64, position: Vec3A) -> Self {
+    let mut samples = VecDeque::with_capacity(INTERPOLATION_BUFFER_SIZE);
+    samples.push_back((time, position));
+    InterpolationBuffer { samples }
+  }
+
+  ///
+  /// Add a position. Anything older than the newest one is ignored.
+  ///
+  pub fn push(&mut self, time: f64, position: Vec3A) {
+    if let Some((newest_time, _)) = self.samples.back() {
+      if time < *newest_time {
+        return;
+      }
+    }
+    if self.samples.len() >= INTERPOLATION_BUFFER_SIZE {
+      self.samples.pop_front();
+    }
+    self.samples.push_back((time, position));
+  }
+
+  ///
+  /// Get where the entity was at a time, blending between the samples around it.
+  ///
+  /// Before the oldest sample is the oldest, after the newest is the newest.
+  ///
+  pub fn sample(&self, time: f64) -> Vec3A {
+    let mut previous = match self.samples.front() {
+      Some(oldest) => *oldest,
+      None => return Vec3A::ZERO,
+    };
+    for (sample_time, position) in self.samples.iter().copied() {
+      if sample_time >= time {
+        let span = sample_time - previous.0;
+        if span <= 0.0 {
+          return position;
+        }
+        let alpha = ((time - previous.0) / span) as f32;
+        return previous.1.lerp(position, alpha);
+      }
+      previous = (sample_time, position);
+    }
+    previous.1
+  }
+
+  ///
+  /// Forget the samples nothing will be sampled between anymore.
+  ///
+  /// The newest one at or before the time stays, the blend starts from it.
+  ///
+  pub fn discard_before(&mut self, time: f64) {
+    while self.samples.len() > 1 {
+      match self.samples.get(1) {
+        Some((second_time, _)) if *second_time <= time => {
+          self.samples.pop_front();
+        }
+        _ => break,
+      }
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.samples.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.samples.is_empty()
+  }
+
+  pub fn get_newest(&self) -> Vec3A {
+    match self.samples.back() {
+      Some((_, position)) => *position,
+      None => Vec3A::ZERO,
+    }
+  }
+}
+
+///
+/// Something the server told the client about, like another player.
+///
+pub struct ClientEntity {
+  pub kind: String,
+  pub positions: InterpolationBuffer,
+}
+
+///
+/// Every entity the server has spawned for this client, by id.
+///
+/// Filled by AddEntity and emptied by RemoveEntity. EntityPositions moves them.
+///
+pub struct EntityRegistry {
+  entities: BTreeMap<u64, ClientEntity>,
+  // How long the registry has been running, positions are timestamped with it. [seconds]
+  time: f64,
+  // Entities are drawn this far in the past, jitter shorter than this is hidden. [seconds]
+  interpolation_delay: f64,
+}
+
+impl EntityRegistry {
+  pub fn new() -> Self {
+    EntityRegistry {
+      entities: BTreeMap::new(),
+      time: 0.0,
+      interpolation_delay: DEFAULT_INTERPOLATION_DELAY,
+    }
+  }
+
+  pub fn from_config(config: &GameConfig) -> Self {
+    let mut registry = Self::new();
+    registry.set_interpolation_delay(
+      config.get_parsed("interpolation_delay", DEFAULT_INTERPOLATION_DELAY),
+    );
+    registry
+  }
+
+  ///
+  /// Set how far in the past entities are drawn. [seconds]
+  ///
+  /// Clamped to [0.0 - MAX_INTERPOLATION_DELAY], 0.0 draws the newest position.
+  ///
+  pub fn set_interpolation_delay(&mut self, interpolation_delay: f64) {
+    if !(0.0..=MAX_INTERPOLATION_DELAY).contains(&interpolation_delay) {
+      println!(
+        "EntityRegistry: interpolation_delay [{}] is out of range, clamping it.",
+        interpolation_delay
+      );
+    }
+    self.interpolation_delay = match interpolation_delay.is_nan() {
+      true => DEFAULT_INTERPOLATION_DELAY,
+      false => interpolation_delay.clamp(0.0, MAX_INTERPOLATION_DELAY),
+    };
+  }
+
+  pub fn get_interpolation_delay(&self) -> f64 {
+    self.interpolation_delay
+  }
+
+  ///
+  /// Get the time entities are drawn at right now. [seconds]
+  ///
+  pub fn get_render_time(&self) -> f64 {
+    self.time - self.interpolation_delay
+  }
+
+  ///
+  /// Run the clock forward. [seconds]
+  ///
+  /// Samples from before the render time that aren't needed anymore are dropped.
+  ///
+  pub fn advance(&mut self, delta: f64) {
+    self.time += delta;
+    let render_time = self.get_render_time();
+    for entity in self.entities.values_mut() {
+      entity.positions.discard_before(render_time);
+    }
+  }
+
+  ///
+  /// Apply an AddEntity, RemoveEntity or EntityPositions.
+  ///
+  /// Returns if it was one of those. Removing an id that isn't known is
+  /// only warned about, the server may have spawned it before we joined.
+  /// Positions for unknown ids are skipped, the AddEntity can still be on the way.
+  ///
+  pub fn apply(&mut self, message: &NetworkMessage) -> bool {
+    match message {
+      NetworkMessage::AddEntity { id, kind, position } => {
+        let entity = ClientEntity {
+          kind: kind.clone(),
+          positions: InterpolationBuffer::new(self.time, *position),
+        };
+        if self.entities.insert(*id, entity).is_some() {
+          println!("EntityRegistry: entity [{}] was added twice.", id);
+        }
+        true
+      }
+      NetworkMessage::RemoveEntity { id } => {
+        if self.entities.remove(id).is_none() {
+          println!(
+            "EntityRegistry: tried to remove entity [{}], it was never added.",
+            id
+          );
+        }
+        true
+      }
+      NetworkMessage::EntityPositions { positions } => {
+        for (id, position) in positions {
+          if let Some(entity) = self.entities.get_mut(id) {
+            entity.positions.push(self.time, *position);
+          }
+        }
+        true
+      }
+      _ => false,
+    }
+  }
+
+  pub fn get(&self, id: u64) -> Option<&ClientEntity> {
+    self.entities.get(&id)
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (&u64, &ClientEntity)> {
+    self.entities.iter()
+  }
+
+  ///
+  /// Get where an entity should be drawn right now.
+  ///
+  pub fn get_render_position(&self, entity: &ClientEntity) -> Vec3A {
+    entity.positions.sample(self.get_render_time())
+  }
+
+  ///
+  /// Get the model and texture an entity kind is drawn with.
+  ///
+  /// None if the client doesn't know how to draw it.
+  ///
+  pub fn get_model_for_kind(kind: &str) -> Option<(&'static str, &'static str)> {
+    match kind {
+      PLAYER_ENTITY_KIND => Some(("minetest_sam.gltf", "minetest_sam.png")),
+      _ => None,
+    }
+  }
+
+  ///
+  /// Forget everything, like when the connection drops.
+  ///
+  pub fn clear(&mut self) {
+    self.entities.clear();
+  }
+
+  pub fn len(&self) -> usize {
+    self.entities.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entities.is_empty()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::Vec3A;
+
+  use crate::game::{
+    client::entity_registry::{EntityRegistry, MAX_INTERPOLATION_DELAY},
+    game_config::GameConfig,
+    network_message::NetworkMessage,
+  };
+
+  #[test]
+  fn test_entity_registry_spawn_despawn() {
+    println!("--- BEGIN ENTITY REGISTRY SPAWN DESPAWN TEST ---");
+    let mut registry = EntityRegistry::new();
+
+    let spawn = NetworkMessage::AddEntity {
+      id: 7,
+      kind: "player".to_string(),
+      position: Vec3A::new(1.0, 2.0, 3.0),
+    };
+    assert!(registry.apply(&spawn));
+    match registry.get(7) {
+      Some(entity) => {
+        assert_eq!(entity.kind, "player");
+        assert_eq!(entity.positions.get_newest(), Vec3A::new(1.0, 2.0, 3.0));
+      }
+      None => panic!("Spawned entity is missing."),
+    }
+
+    // An id nobody spawned doesn't break anything.
+    assert!(registry.apply(&NetworkMessage::RemoveEntity { id: 99 }));
+    assert_eq!(registry.len(), 1);
+
+    // Position updates go into the buffer.
+    registry.advance(0.1);
+    assert!(registry.apply(&NetworkMessage::EntityPositions {
+      positions: vec![(7, Vec3A::new(4.0, 2.0, 3.0)), (99, Vec3A::ONE)],
+    }));
+    match registry.get(7) {
+      Some(entity) => {
+        assert_eq!(entity.positions.len(), 2);
+        assert_eq!(entity.positions.get_newest(), Vec3A::new(4.0, 2.0, 3.0));
+      }
+      None => panic!("Spawned entity is missing."),
+    }
+    assert!(registry.get(99).is_none());
+
+    assert!(registry.apply(&NetworkMessage::RemoveEntity { id: 7 }));
+    assert!(registry.is_empty());
+
+    // Anything else isn't the registry's business.
+    assert!(!registry.apply(&NetworkMessage::ShutDownRequest));
+  }
+
+  #[test]
+  fn test_interpolation_buffer_sample() {
+    println!("--- BEGIN INTERPOLATION BUFFER SAMPLE TEST ---");
+    let mut registry = EntityRegistry::new();
+    let spawn = NetworkMessage::AddEntity {
+      id: 1,
+      kind: "mob".to_string(),
+      position: Vec3A::ZERO,
+    };
+    registry.advance(1.0);
+    registry.apply(&spawn);
+    let positions = &mut match registry.entities.get_mut(&1) {
+      Some(entity) => entity,
+      None => panic!("Spawned entity is missing."),
+    }
+    .positions;
+
+    positions.push(2.0, Vec3A::new(10.0, 0.0, 0.0));
+    // Out of order is dropped.
+    positions.push(1.5, Vec3A::new(-100.0, 0.0, 0.0));
+
+    assert_eq!(positions.sample(0.0), Vec3A::ZERO);
+    assert_eq!(positions.sample(1.5), Vec3A::new(5.0, 0.0, 0.0));
+    assert_eq!(positions.sample(9.0), Vec3A::new(10.0, 0.0, 0.0));
+  }
+
+  #[test]
+  fn test_entity_registry_interpolation_delay() {
+    println!("--- BEGIN ENTITY REGISTRY INTERPOLATION DELAY TEST ---");
+    let config = GameConfig::parse("interpolation_delay = 0.2");
+    let mut registry = EntityRegistry::from_config(&config);
+    assert_eq!(registry.get_interpolation_delay(), 0.2);
+    registry.apply(&NetworkMessage::AddEntity {
+      id: 1,
+      kind: "mob".to_string(),
+      position: Vec3A::ZERO,
+    });
+
+    // Moving 1 node every 0.1 seconds, but the broadcasts show up jittery.
+    let arrivals = [0.13, 0.19, 0.32, 0.41, 0.48, 0.63];
+    let mut time = 0.0;
+    for (i, arrival) in arrivals.iter().enumerate() {
+      registry.advance(arrival - time);
+      time = *arrival;
+      registry.apply(&NetworkMessage::EntityPositions {
+        positions: vec![(1, Vec3A::new((i + 1) as f32, 0.0, 0.0))],
+      });
+    }
+    registry.advance(0.7 - time);
+
+    // Drawn 0.2 seconds back, between the samples from 0.48 and 0.63.
+    let entity = match registry.get(1) {
+      Some(entity) => entity,
+      None => panic!("Spawned entity is missing."),
+    };
+    let expected = Vec3A::new(5.0, 0.0, 0.0).lerp(Vec3A::new(6.0, 0.0, 0.0), 0.02 / 0.15);
+    assert!(registry
+      .get_render_position(entity)
+      .abs_diff_eq(expected, 1e-4));
+    // Only the two around the render time are left.
+    assert_eq!(entity.positions.len(), 2);
+
+    // Too long is clamped.
+    registry.set_interpolation_delay(5.0);
+    assert_eq!(registry.get_interpolation_delay(), MAX_INTERPOLATION_DELAY);
+    registry.set_interpolation_delay(-1.0);
+    assert_eq!(registry.get_interpolation_delay(), 0.0);
+  }
+}
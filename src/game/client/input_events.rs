@@ -0,0 +1,106 @@
+use glam::IVec2;
+
+///
+/// Something the player did this frame, for client mods.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputEvent {
+  KeyDown(String),
+  KeyUp(String),
+  // How far the mouse moved this frame, in relative mode. [pixels]
+  MouseMove(IVec2),
+}
+
+///
+/// This frame's InputEvents, in the order they happened.
+///
+/// The Client fills this before Lua's on_tick and clears it after, so
+/// every mod sees the same events once. See minetest.get_input_events.
+///
+pub struct InputEventQueue {
+  events: Vec<InputEvent>,
+}
+
+impl InputEventQueue {
+  pub fn new() -> Self {
+    InputEventQueue { events: vec![] }
+  }
+
+  ///
+  /// Add the key changes from KeyboardController::take_changes. (key, pressed)
+  ///
+  pub fn push_key_changes(&mut self, key_changes: &[(String, bool)]) {
+    for (key, pressed) in key_changes {
+      self.events.push(match pressed {
+        true => InputEvent::KeyDown(key.clone()),
+        false => InputEvent::KeyUp(key.clone()),
+      });
+    }
+  }
+
+  ///
+  /// Add how far the mouse moved. Not moving at all isn't an event.
+  ///
+  pub fn push_mouse_delta(&mut self, delta: IVec2) {
+    if delta != IVec2::ZERO {
+      self.events.push(InputEvent::MouseMove(delta));
+    }
+  }
+
+  pub fn get_events(&self) -> &[InputEvent] {
+    &self.events
+  }
+
+  ///
+  /// Throw this frame's events away, they've been delivered.
+  ///
+  pub fn clear(&mut self) {
+    self.events.clear();
+  }
+
+  pub fn len(&self) -> usize {
+    self.events.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.events.is_empty()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::IVec2;
+
+  use crate::game::client::{
+    input_events::{InputEvent, InputEventQueue},
+    keyboard::KeyboardController,
+  };
+
+  #[test]
+  fn test_input_event_queue_keys() {
+    println!("--- BEGIN INPUT EVENT QUEUE KEYS TEST ---");
+    let mut keyboard = KeyboardController::new();
+    let mut queue = InputEventQueue::new();
+
+    keyboard.set_key("W", true);
+    keyboard.set_key("Space", true);
+    keyboard.set_key("W", false);
+    queue.push_key_changes(&keyboard.take_changes());
+    queue.push_mouse_delta(IVec2::ZERO);
+    queue.push_mouse_delta(IVec2::new(3, -1));
+
+    assert_eq!(
+      queue.get_events(),
+      &[
+        InputEvent::KeyDown("W".to_string()),
+        InputEvent::KeyDown("Space".to_string()),
+        InputEvent::KeyUp("W".to_string()),
+        InputEvent::MouseMove(IVec2::new(3, -1)),
+      ]
+    );
+
+    // The next frame starts empty.
+    queue.clear();
+    assert!(queue.is_empty());
+  }
+}
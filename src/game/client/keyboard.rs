@@ -2,12 +2,15 @@ use ahash::AHashMap;
 
 pub struct KeyboardController {
   keys: AHashMap<String, bool>,
+  // Every set_key since the last take_changes, in order. (key, pressed)
+  changes: Vec<(String, bool)>,
 }
 
 impl KeyboardController {
   pub fn new() -> Self {
     KeyboardController {
       keys: AHashMap::new(),
+      changes: vec![],
     }
   }
 
@@ -16,6 +19,7 @@ impl KeyboardController {
   ///
   pub fn set_key(&mut self, key_name: &str, pressed: bool) {
     self.keys.insert(key_name.to_owned(), pressed);
+    self.changes.push((key_name.to_owned(), pressed));
 
     println!("{} is pressed? {}", key_name, pressed);
   }
@@ -30,5 +34,12 @@ impl KeyboardController {
     }
   }
 
+  ///
+  /// Get every key that went up or down since the last time this was asked.
+  ///
+  pub fn take_changes(&mut self) -> Vec<(String, bool)> {
+    std::mem::take(&mut self.changes)
+  }
+
   // * future note: this can poll for key pressed. Simply store memory with an update.
 }
@@ -0,0 +1,354 @@
+use std::{fs::read_dir, time::SystemTime};
+
+use ahash::AHashMap;
+
+use crate::{
+  file_utilities::{
+    create_dir_all, file_exists, read_file_to_byte_vec, remove_file, write_file_atomic,
+  },
+  media_hash::hash_bytes,
+};
+
+///
+/// The default size limit of the MediaCache. 512 MB.
+///
+pub const DEFAULT_MEDIA_CACHE_SIZE_BYTES: u64 = 512 * 1024 * 1024;
+
+///
+/// The bookkeeping for a single file in the MediaCache.
+///
+struct MediaCacheEntry {
+  size_bytes: u64,
+  last_access: u64,
+}
+
+///
+/// A content addressed cache for media on the client.
+///
+/// Every file is stored under it's SHA1 hash in the cache directory.
+/// Because of this, identical assets across different servers only
+/// ever get downloaded once.
+///
+/// The cache has a size limit. When it's exceeded, the least recently
+/// used files are evicted until it fits again.
+///
+pub struct MediaCache {
+  cache_dir: String,
+  max_size_bytes: u64,
+  current_size_bytes: u64,
+  entries: AHashMap<String, MediaCacheEntry>,
+
+  // A monotonic counter which acts as the LRU clock.
+  access_counter: u64,
+}
+
+impl MediaCache {
+  pub fn new(cache_dir: &str, max_size_bytes: u64) -> Self {
+    if let Err(e) = create_dir_all(cache_dir) {
+      panic!("MediaCache: {}", e);
+    }
+
+    let mut new_media_cache = MediaCache {
+      cache_dir: cache_dir.to_owned(),
+      max_size_bytes,
+      current_size_bytes: 0,
+      entries: AHashMap::new(),
+
+      access_counter: 0,
+    };
+
+    new_media_cache.scan_cache_dir();
+
+    println!(
+      "MediaCache: [{}] file(s) cached. [{}] bytes in use.",
+      new_media_cache.entries.len(),
+      new_media_cache.current_size_bytes
+    );
+
+    new_media_cache
+  }
+
+  ///
+  /// Pick up whatever is already sitting in the cache directory.
+  ///
+  /// Modification time is used to seed the LRU order, so the oldest
+  /// files from the last session are the first to go.
+  ///
+  fn scan_cache_dir(&mut self) {
+    let raw_files = match read_dir(&self.cache_dir) {
+      Ok(raw_files) => raw_files,
+      Err(e) => panic!("MediaCache: Failed to read cache directory. {}", e),
+    };
+
+    let mut found_files: Vec<(String, u64, SystemTime)> = vec![];
+
+    for file_result in raw_files {
+      let file = match file_result {
+        Ok(file) => file,
+        Err(e) => {
+          println!("MediaCache: Failed to get directory entry. {}", e);
+          continue;
+        }
+      };
+
+      let hash = match file.file_name().to_str() {
+        Some(file_name) => file_name.to_string(),
+        None => continue,
+      };
+
+      // Leftover temporary files and foreign files are ignored.
+      if !Self::is_valid_hash(&hash) {
+        continue;
+      }
+
+      let metadata = match file.metadata() {
+        Ok(metadata) => metadata,
+        Err(_) => continue,
+      };
+
+      if !metadata.is_file() {
+        continue;
+      }
+
+      let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+
+      found_files.push((hash, metadata.len(), modified));
+    }
+
+    found_files.sort_by_key(|(_, _, modified)| *modified);
+
+    for (hash, size_bytes, _) in found_files {
+      self.insert_entry(hash, size_bytes);
+    }
+
+    self.evict(None);
+  }
+
+  ///
+  /// Check if a string is shaped like a SHA1 hash.
+  ///
+  /// This also stops a malicious hash from walking out of the cache directory.
+  ///
+  fn is_valid_hash(hash: &str) -> bool {
+    hash.len() == 40 && hash.chars().all(|c| c.is_ascii_hexdigit())
+  }
+
+  ///
+  /// Get the file path of a hash in the cache directory.
+  ///
+  fn get_path(&self, hash: &str) -> String {
+    let mut path = self.cache_dir.clone();
+    path.push('/');
+    path.push_str(hash);
+    path
+  }
+
+  ///
+  /// Track a new file in the cache.
+  ///
+  fn insert_entry(&mut self, hash: String, size_bytes: u64) {
+    self.access_counter += 1;
+
+    if let Some(old_entry) = self.entries.insert(
+      hash,
+      MediaCacheEntry {
+        size_bytes,
+        last_access: self.access_counter,
+      },
+    ) {
+      self.current_size_bytes -= old_entry.size_bytes;
+    }
+
+    self.current_size_bytes += size_bytes;
+  }
+
+  ///
+  /// Stop tracking a file in the cache and delete it from the disk.
+  ///
+  fn remove_entry(&mut self, hash: &str) {
+    if let Some(entry) = self.entries.remove(hash) {
+      self.current_size_bytes -= entry.size_bytes;
+    }
+
+    let path = self.get_path(hash);
+    if file_exists(&path) {
+      if let Err(e) = remove_file(&path) {
+        println!("MediaCache: {}", e);
+      }
+    }
+  }
+
+  ///
+  /// Evict the least recently used files until the cache fits in the size limit.
+  ///
+  /// The protected hash is never evicted. This is so a freshly stored file
+  /// doesn't immediately evict itself.
+  ///
+  fn evict(&mut self, protected_hash: Option<&str>) {
+    while self.current_size_bytes > self.max_size_bytes {
+      let oldest_hash = self
+        .entries
+        .iter()
+        .filter(|(hash, _)| Some(hash.as_str()) != protected_hash)
+        .min_by_key(|(_, entry)| entry.last_access)
+        .map(|(hash, _)| hash.clone());
+
+      match oldest_hash {
+        Some(hash) => {
+          println!("MediaCache: evicting [{}].", hash);
+          self.remove_entry(&hash);
+        }
+        // Only the protected file is left, there's nothing more we can do.
+        None => break,
+      }
+    }
+  }
+
+  ///
+  /// Check if a hash is in the cache without touching it's LRU position.
+  ///
+  pub fn contains(&self, hash: &str) -> bool {
+    self.entries.contains_key(hash)
+  }
+
+  ///
+  /// Get the bytes of a cached file by it's SHA1 hash.
+  ///
+  /// Returns None if the file is not in the cache.
+  ///
+  pub fn get_cached(&mut self, hash: &str) -> Option<Vec<u8>> {
+    if !self.contains(hash) {
+      return None;
+    }
+
+    match read_file_to_byte_vec(&self.get_path(hash)) {
+      Ok(data) => {
+        self.access_counter += 1;
+        if let Some(entry) = self.entries.get_mut(hash) {
+          entry.last_access = self.access_counter;
+        }
+        Some(data)
+      }
+      Err(e) => {
+        // Somebody deleted it out from under us. Forget it.
+        println!("MediaCache: cached file [{}] went missing. {}", hash, e);
+        self.remove_entry(hash);
+        None
+      }
+    }
+  }
+
+  ///
+  /// Store data in the cache.
+  ///
+  /// Returns the SHA1 hash the data is stored under.
+  ///
+  pub fn store(&mut self, data: &[u8]) -> Result<String, String> {
+    let hash = hash_bytes(data);
+
+    if !self.contains(&hash) {
+      if let Err(e) = write_file_atomic(&self.get_path(&hash), data) {
+        return Err(format!("MediaCache: {}", e));
+      }
+    }
+
+    self.insert_entry(hash.clone(), data.len() as u64);
+    self.evict(Some(&hash));
+
+    Ok(hash)
+  }
+
+  ///
+  /// Get the amount of bytes the cache is currently using.
+  ///
+  pub fn get_size_bytes(&self) -> u64 {
+    self.current_size_bytes
+  }
+
+  ///
+  /// Get the size limit of the cache in bytes.
+  ///
+  pub fn get_max_size_bytes(&self) -> u64 {
+    self.max_size_bytes
+  }
+
+  ///
+  /// Change the size limit of the cache.
+  ///
+  /// Shrinking the limit will immediately evict files.
+  ///
+  pub fn set_max_size_bytes(&mut self, new_max_size_bytes: u64) {
+    self.max_size_bytes = new_max_size_bytes;
+    self.evict(None);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::remove_dir_all;
+
+  use crate::{
+    file_utilities::test_dir, game::client::media_cache::MediaCache, media_hash::hash_bytes,
+  };
+
+  #[test]
+  fn test_media_cache_hit_and_miss() {
+    println!("--- BEGIN MEDIA CACHE HIT AND MISS TEST ---");
+    let cache_dir = test_dir("media_cache_hit_and_miss");
+    let mut media_cache = MediaCache::new(&cache_dir, 1024);
+
+    let data = b"minetest media".to_vec();
+    let hash = hash_bytes(&data);
+
+    // Nothing has been stored yet.
+    assert!(media_cache.get_cached(&hash).is_none());
+
+    let stored_hash = match media_cache.store(&data) {
+      Ok(stored_hash) => stored_hash,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    assert_eq!(hash, stored_hash);
+
+    // Writing then reading must give back identical bytes.
+    assert_eq!(media_cache.get_cached(&hash), Some(data));
+
+    // A new cache on the same directory picks the file back up.
+    let mut reopened_media_cache = MediaCache::new(&cache_dir, 1024);
+    assert!(reopened_media_cache.get_cached(&hash).is_some());
+
+    let _ = remove_dir_all(&cache_dir);
+  }
+
+  #[test]
+  fn test_media_cache_lru_eviction() {
+    println!("--- BEGIN MEDIA CACHE LRU EVICTION TEST ---");
+    let cache_dir = test_dir("media_cache_lru_eviction");
+
+    // Room for two 4 byte files.
+    let mut media_cache = MediaCache::new(&cache_dir, 8);
+
+    let first = match media_cache.store(b"aaaa") {
+      Ok(hash) => hash,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let second = match media_cache.store(b"bbbb") {
+      Ok(hash) => hash,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+
+    // Touch the first file so the second one is the least recently used.
+    assert!(media_cache.get_cached(&first).is_some());
+
+    let third = match media_cache.store(b"cccc") {
+      Ok(hash) => hash,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+
+    assert!(media_cache.contains(&first));
+    assert!(!media_cache.contains(&second));
+    assert!(media_cache.contains(&third));
+    assert_eq!(media_cache.get_size_bytes(), 8);
+
+    let _ = remove_dir_all(&cache_dir);
+  }
+}
@@ -1,10 +1,26 @@
-use glam::IVec2;
+use std::ops::RangeInclusive;
+
+use glam::{IVec2, Vec2};
+
+use crate::game::game_config::GameConfig;
+
+///
+/// Radians of turn per pixel of mouse movement.
+///
+const DEFAULT_SENSITIVITY: f32 = 0.01;
+
+///
+/// Zero or negative would freeze or flip the camera, way past 1.0 is a full
+/// turn in a few pixels.
+///
+const SENSITIVITY_RANGE: RangeInclusive<f32> = 0.0001..=1.0;
 
 pub struct MouseController {
   position: IVec2,
   relative_position: IVec2,
   relative_mode: bool,
   sensitivity: f32,
+  invert_y: bool,
 }
 
 impl MouseController {
@@ -13,10 +29,24 @@ impl MouseController {
       position: IVec2::new(0, 0),
       relative_position: IVec2::new(0, 0),
       relative_mode: false,
-      sensitivity: 0.01,
+      sensitivity: DEFAULT_SENSITIVITY,
+      invert_y: false,
     }
   }
 
+  ///
+  /// Create a MouseController with the look settings from minetest.conf.
+  ///
+  /// mouse_sensitivity = radians per pixel (default 0.01)
+  /// invert_mouse_y = true | false (default false)
+  ///
+  pub fn from_config(config: &GameConfig) -> Self {
+    let mut new_mouse = MouseController::new();
+    new_mouse.set_sensitivity(config.get_parsed("mouse_sensitivity", DEFAULT_SENSITIVITY));
+    new_mouse.set_invert_y(config.get_bool("invert_mouse_y", false));
+    new_mouse
+  }
+
   /// ! This should only be run by the Client!
   ///
   /// Resets the Mouse' relative position for Camera controls.
@@ -96,8 +126,18 @@ impl MouseController {
   ///
   /// Set the Mouse' sensitivity.
   ///
+  /// Clamped into SENSITIVITY_RANGE.
+  ///
   pub fn set_sensitivity(&mut self, new_sensitivity: f32) {
-    self.sensitivity = new_sensitivity;
+    if new_sensitivity.is_nan() {
+      println!(
+        "MouseController: sensitivity can't be NaN. Using default [{}].",
+        DEFAULT_SENSITIVITY
+      );
+      self.sensitivity = DEFAULT_SENSITIVITY;
+      return;
+    }
+    self.sensitivity = new_sensitivity.clamp(*SENSITIVITY_RANGE.start(), *SENSITIVITY_RANGE.end());
   }
 
   ///
@@ -106,4 +146,65 @@ impl MouseController {
   pub fn get_sensitivity(&self) -> f32 {
     self.sensitivity
   }
+
+  ///
+  /// Set if moving the Mouse up looks down.
+  ///
+  pub fn set_invert_y(&mut self, invert_y: bool) {
+    self.invert_y = invert_y;
+  }
+
+  ///
+  /// Get if moving the Mouse up looks down.
+  ///
+  pub fn is_invert_y(&self) -> bool {
+    self.invert_y
+  }
+
+  ///
+  /// Get how far the Camera should turn this frame. [radians]
+  ///
+  /// x is the yaw, y is the pitch.
+  ///
+  pub fn get_look_delta(&self) -> Vec2 {
+    let mut look_delta = self.relative_position.as_vec2() * self.sensitivity;
+    if self.invert_y {
+      look_delta.y = -look_delta.y;
+    }
+    look_delta
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::game::{client::mouse::MouseController, game_config::GameConfig};
+
+  #[test]
+  fn test_mouse_invert_y() {
+    println!("--- BEGIN MOUSE INVERT Y TEST ---");
+    let mut mouse = MouseController::from_config(&GameConfig::parse(
+      "mouse_sensitivity = 0.5
+      invert_mouse_y = false",
+    ));
+    mouse.toggle_relative_mode();
+    mouse.set_relative_position(4, -6);
+
+    let look_delta = mouse.get_look_delta();
+    assert_eq!(look_delta.x, 2.0);
+    assert_eq!(look_delta.y, -3.0);
+
+    // Only the pitch flips.
+    mouse.set_invert_y(true);
+    let inverted = mouse.get_look_delta();
+    assert_eq!(inverted.x, look_delta.x);
+    assert_eq!(inverted.y, -look_delta.y);
+
+    // Sensitivity stays positive.
+    mouse.set_sensitivity(-5.0);
+    assert!(mouse.get_sensitivity() > 0.0);
+    mouse.set_sensitivity(f32::NAN);
+    assert_eq!(mouse.get_sensitivity(), 0.01);
+    mouse.set_sensitivity(100.0);
+    assert_eq!(mouse.get_sensitivity(), 1.0);
+  }
 }
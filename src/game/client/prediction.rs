@@ -0,0 +1,242 @@
+use std::collections::VecDeque;
+
+use glam::Vec3A;
+
+///
+/// A correction further than this from the prediction jumps there, instead of
+/// being smoothed out. [nodes]
+///
+pub const SNAP_DISTANCE: f32 = 4.0;
+
+///
+/// A correction this close to the prediction is the prediction. [nodes]
+///
+const MATCH_DISTANCE: f32 = 0.01;
+
+///
+/// How fast a smoothed correction fades. About this many times the rest of it per second.
+///
+const CORRECTION_DECAY: f32 = 10.0;
+
+///
+/// The most moves kept waiting on the server. Past this the oldest are
+/// forgotten, a correction for one of those is taken as is.
+///
+const MAX_HISTORY: usize = 256;
+
+///
+/// A move sent to the server that it hasn't answered for yet.
+///
+#[derive(Debug, Clone, Copy)]
+struct PredictedMove {
+  // The packet sequence it went out with.
+  sequence: u32,
+  displacement: Vec3A,
+  // Where the player was predicted to be after it.
+  position: Vec3A,
+}
+
+///
+/// What a correction from the server did.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reconciliation {
+  // The server agreed, nothing moved.
+  Matched,
+  // A small miss. The player is where the server says, the Camera gets there smoothly.
+  Smoothed,
+  // A big miss, or a teleport. Straight there.
+  Snapped,
+}
+
+///
+/// Client side prediction of the local player.
+///
+/// Input moves the player right away, without waiting on the server. Every
+/// move sent is kept until the server's MovePlayer acknowledges it. When a
+/// correction comes in, the moves it acknowledged are dropped and the ones
+/// after it are replayed on top of where the server put the player.
+///
+/// The position is what the server should end up agreeing with, the display
+/// position is where the Camera goes. Those are only apart for a moment
+/// after a small correction.
+///
+pub struct MovePrediction {
+  position: Vec3A,
+  // What's left of the last smoothed correction, display - position.
+  correction: Vec3A,
+  history: VecDeque<PredictedMove>,
+}
+
+impl MovePrediction {
+  pub fn new(position: Vec3A) -> Self {
+    MovePrediction {
+      position,
+      correction: Vec3A::ZERO,
+      history: VecDeque::new(),
+    }
+  }
+
+  ///
+  /// Move the player. sequence is what the move went to the server with, None if it didn't.
+  ///
+  /// Returns where the player is now.
+  ///
+  pub fn apply_input(&mut self, displacement: Vec3A, sequence: Option<u32>) -> Vec3A {
+    self.position += displacement;
+    if let Some(sequence) = sequence {
+      self.history.push_back(PredictedMove {
+        sequence,
+        displacement,
+        position: self.position,
+      });
+      while self.history.len() > MAX_HISTORY {
+        self.history.pop_front();
+      }
+    }
+    self.position
+  }
+
+  ///
+  /// The server says the player was at position, after the move with the acknowledged sequence.
+  ///
+  pub fn reconcile(&mut self, position: Vec3A, acknowledged: u32) -> Reconciliation {
+    // The moves it took are done waiting.
+    let mut predicted = None;
+    while let Some(predicted_move) = self.history.front() {
+      if predicted_move.sequence > acknowledged {
+        break;
+      }
+      if predicted_move.sequence == acknowledged {
+        predicted = Some(predicted_move.position);
+      }
+      self.history.pop_front();
+    }
+
+    if let Some(predicted) = predicted {
+      if predicted.distance(position) <= MATCH_DISTANCE {
+        return Reconciliation::Matched;
+      }
+    }
+
+    // Rewind to where the server put the player, and replay what it hasn't seen yet.
+    let mut replayed = position;
+    for predicted_move in self.history.iter_mut() {
+      replayed += predicted_move.displacement;
+      predicted_move.position = replayed;
+    }
+
+    let display = self.get_display_position();
+    self.position = replayed;
+    match predicted.is_some() && display.distance(replayed) <= SNAP_DISTANCE {
+      true => {
+        self.correction = display - replayed;
+        Reconciliation::Smoothed
+      }
+      false => {
+        self.correction = Vec3A::ZERO;
+        Reconciliation::Snapped
+      }
+    }
+  }
+
+  ///
+  /// Put the player somewhere, forgetting everything that was waiting on the server.
+  ///
+  pub fn reset(&mut self, position: Vec3A) {
+    self.position = position;
+    self.correction = Vec3A::ZERO;
+    self.history.clear();
+  }
+
+  ///
+  /// Fade out what's left of a smoothed correction. Call this once a frame.
+  ///
+  pub fn advance(&mut self, delta: f64) {
+    self.correction *= (-CORRECTION_DECAY * delta as f32).exp();
+    if self.correction.length() < MATCH_DISTANCE {
+      self.correction = Vec3A::ZERO;
+    }
+  }
+
+  pub fn get_position(&self) -> Vec3A {
+    self.position
+  }
+
+  ///
+  /// Get where the Camera goes, the position with the rest of a correction still on it.
+  ///
+  pub fn get_display_position(&self) -> Vec3A {
+    self.position + self.correction
+  }
+
+  ///
+  /// Get how many moves are waiting on the server.
+  ///
+  pub fn get_pending(&self) -> usize {
+    self.history.len()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::Vec3A;
+
+  use crate::game::client::prediction::{MovePrediction, Reconciliation};
+
+  #[test]
+  fn test_move_prediction_reconcile() {
+    println!("--- BEGIN MOVE PREDICTION RECONCILE TEST ---");
+    let step = Vec3A::new(1.0, 0.0, 0.0);
+    let mut prediction = MovePrediction::new(Vec3A::ZERO);
+
+    // Input moves right away.
+    for sequence in 1..=5 {
+      prediction.apply_input(step, Some(sequence));
+    }
+    assert_eq!(prediction.get_position(), Vec3A::new(5.0, 0.0, 0.0));
+    assert_eq!(prediction.get_pending(), 5);
+
+    // The server agrees with move 3, nothing on screen moves.
+    let before = prediction.get_display_position();
+    assert_eq!(
+      prediction.reconcile(Vec3A::new(3.0, 0.0, 0.0), 3),
+      Reconciliation::Matched
+    );
+    assert_eq!(prediction.get_display_position(), before);
+    assert_eq!(prediction.get_pending(), 2);
+
+    // The server had move 4 a bit short. Moves after it get replayed from there,
+    // and the Camera doesn't jump, it eases over.
+    assert_eq!(
+      prediction.reconcile(Vec3A::new(3.5, 0.0, 0.0), 4),
+      Reconciliation::Smoothed
+    );
+    assert_eq!(prediction.get_position(), Vec3A::new(4.5, 0.0, 0.0));
+    assert_eq!(prediction.get_display_position(), before);
+    assert_eq!(prediction.get_pending(), 1);
+    for _ in 0..120 {
+      prediction.advance(1.0 / 60.0);
+    }
+    assert_eq!(prediction.get_display_position(), Vec3A::new(4.5, 0.0, 0.0));
+
+    // Way off, like the border or a teleport. Straight there, plus the move after.
+    prediction.apply_input(step, Some(6));
+    assert_eq!(
+      prediction.reconcile(Vec3A::new(100.0, 0.0, 0.0), 5),
+      Reconciliation::Snapped
+    );
+    assert_eq!(prediction.get_position(), Vec3A::new(101.0, 0.0, 0.0));
+    assert_eq!(
+      prediction.get_display_position(),
+      Vec3A::new(101.0, 0.0, 0.0)
+    );
+
+    // Nothing acknowledged, like at spawn, is taken as is.
+    assert_eq!(
+      prediction.reconcile(Vec3A::new(0.0, 10.0, 0.0), 0),
+      Reconciliation::Snapped
+    );
+    assert_eq!(prediction.get_position(), Vec3A::new(1.0, 10.0, 0.0));
+  }
+}
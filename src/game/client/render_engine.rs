@@ -1,17 +1,31 @@
+mod alpha_mode;
+mod asset_loader;
 mod camera;
 mod color_uniform;
 mod depth_buffer;
+mod dynamic_resolution;
+mod hud_text;
 mod instance_trigger;
 pub mod instanced_render_matrix;
 mod mesh;
 mod mesh_trs_uniform;
 mod model;
 mod model_loader;
+mod msaa_buffer;
 mod render_call;
+pub mod render_init_error;
+mod render_target;
 mod texture;
+mod texture_watcher;
 mod trs_projection_data;
+mod upscale_blit;
 
-use std::{collections::VecDeque, iter, mem::swap};
+use std::{
+  collections::VecDeque,
+  iter,
+  mem::{swap, take},
+  path::Path,
+};
 
 use ahash::AHashMap;
 use glam::{UVec2, Vec3A, Vec4};
@@ -22,12 +36,21 @@ use wgpu::{util::DeviceExt, CommandEncoder, SurfaceTexture, TextureView};
 use wgpu_sdl_linker::link_wgpu_to_sdl2;
 
 use crate::{
-  file_utilities::read_file_to_string,
-  game::client::render_engine::{
-    instance_trigger::InstanceTrigger,
-    mesh::{Mesh, Vertex},
-    model_loader::ModelLoader,
-    texture::Texture,
+  file_utilities::{file_name_from_path, read_file_to_string},
+  game::{
+    client::render_engine::{
+      alpha_mode::{AlphaMode, AlphaModePipelines},
+      asset_loader::{AssetHandle, AssetLoader, LoadingProgress},
+      instance_trigger::InstanceTrigger,
+      mesh::{Mesh, Vertex},
+      model_loader::ModelLoader,
+      msaa_buffer::MsaaBuffer,
+      render_init_error::RenderInitError,
+      render_target::RenderTarget,
+      texture::Texture,
+      texture_watcher::TextureWatcher,
+    },
+    game_config::GameConfig,
   },
 };
 
@@ -35,12 +58,15 @@ use self::{
   camera::Camera,
   color_uniform::ColorUniform,
   depth_buffer::DepthBuffer,
+  dynamic_resolution::DynamicResolution,
+  hud_text::{build_text_mesh, layout_text, HudProjection, HudTextCall, FONT_ATLAS_PATH},
   instanced_render_matrix::{
     InstanceMatrixRGBA, InstancedMeshRenderData, InstancedModelRenderData,
   },
   mesh_trs_uniform::MeshTRSUniform,
   model::Model,
   render_call::{MeshRenderCall, ModelRenderCall},
+  upscale_blit::{ScaledTarget, UpscaleBlit, UPSCALE_SHADER_PATH},
 };
 
 use super::window_handler::WindowHandler;
@@ -58,7 +84,7 @@ pub struct RenderEngine {
 
   // General implementation.
   instance: wgpu::Instance,
-  surface: wgpu::Surface,
+  target: RenderTarget,
   adapter: wgpu::Adapter,
   device: wgpu::Device,
   queue: wgpu::Queue,
@@ -66,7 +92,7 @@ pub struct RenderEngine {
   shader: wgpu::ShaderModule,
 
   render_pipeline_layout: wgpu::PipelineLayout,
-  render_pipeline: wgpu::RenderPipeline,
+  render_pipelines: AlphaModePipelines,
   surface_format: wgpu::TextureFormat,
 
   // Render state memory.
@@ -76,6 +102,22 @@ pub struct RenderEngine {
   depth_buffer: Option<DepthBuffer>,
   render_command_count: u64,
 
+  // Multisampling. The MSAA buffer lives across frames, it only changes on resize.
+  sample_count: u32,
+  msaa_buffer: Option<MsaaBuffer>,
+
+  // Anisotropic filtering level every Texture gets sampled with.
+  anisotropy: u16,
+
+  // Dynamic resolution. While scaled down, the world is drawn into the
+  // scaled target and upscaled into the surface view at the end of the frame.
+  // The HUD goes on after that, at the window size, with its own MSAA buffer.
+  dynamic_resolution: DynamicResolution,
+  upscale_blit: UpscaleBlit,
+  scaled_target: Option<ScaledTarget>,
+  surface_view: Option<TextureView>,
+  hud_msaa_buffer: Option<MsaaBuffer>,
+
   // General variables.
   config: wgpu::SurfaceConfiguration,
   size: UVec2,
@@ -91,6 +133,17 @@ pub struct RenderEngine {
   instance_buffer: Option<wgpu::Buffer>,
   instance_trigger: InstanceTrigger,
 
+  // Blended render calls get held back until everything opaque is drawn.
+  blended_mesh_render_queue: Vec<MeshRenderCall>,
+  blended_model_render_queue: Vec<ModelRenderCall>,
+  blended_instanced_mesh_render_queue: Vec<(u64, InstancedMeshRenderData)>,
+  blended_instanced_model_render_queue: Vec<(u64, InstancedModelRenderData)>,
+
+  // HUD text, drawn over everything in screen space.
+  hud_text_queue: Vec<HudTextCall>,
+  hud_projection: HudProjection,
+  font_texture_id: u64,
+
   // ID dispatcher for wgpu. Acts like the OpenGL ID dispatcher.
   id_dispatcher: Unique64,
 
@@ -100,6 +153,12 @@ pub struct RenderEngine {
 
   texture_name_to_id: AHashMap<String, u64>,
   textures: AHashMap<u64, Texture>,
+  // Texture name -> the color that's keyed out of it when it loads.
+  texture_color_keys: AHashMap<String, [u8; 3]>,
+  // Decodes textures off the main thread, see load_texture_async().
+  asset_loader: AssetLoader,
+  // Only there when minetest.conf says texture_hot_reload.
+  texture_watcher: Option<TextureWatcher>,
   model_name_to_id: AHashMap<String, u64>,
   models: AHashMap<u64, Model>,
 
@@ -112,39 +171,271 @@ pub struct RenderEngine {
   // ! END TESTING VARIABLES
 }
 
+///
+/// Where the main shader is loaded from.
+///
+pub const DEFAULT_SHADER_PATH: &str = "shaders/default_shader.wgsl";
+
+///
+/// Every shader file the RenderEngine loads, so they can be checked up front.
+///
+pub const SHADER_FILES: [&str; 2] = [DEFAULT_SHADER_PATH, UPSCALE_SHADER_PATH];
+
+///
+/// The backends the RenderEngine tries, best first.
+///
+/// Only the ones the platform supports can work, the rest are skipped.
+///
+const BACKEND_ORDER: [(wgpu::Backends, &str); 4] = [
+  (wgpu::Backends::VULKAN, "Vulkan"),
+  (wgpu::Backends::DX12, "DX12"),
+  (wgpu::Backends::METAL, "Metal"),
+  (wgpu::Backends::GL, "GL"),
+];
+
 impl RenderEngine {
-  pub fn new(window_handler: &WindowHandler) -> Self {
-    // This is written verbosely so you can read what's going on easier.
+  pub fn new(
+    window_handler: &WindowHandler,
+    game_config: &GameConfig,
+  ) -> Result<Self, RenderInitError> {
+    let window = window_handler.borrow_window();
+
+    let (instance, surface, adapter) = Self::try_backends(|backends| {
+      let instance = Self::create_instance(backends);
+      let surface = match link_wgpu_to_sdl2(&instance, window) {
+        Ok(new_surface) => new_surface,
+        Err(e) => return Err(e.to_string()),
+      };
+      Ok(
+        Self::request_adapter(&instance, Some(&surface))
+          .map(|adapter| (instance, Some(surface), adapter)),
+      )
+    })?;
+
+    // Need to get the window size to configure the surface.
+    let (width, height) = window.size();
+
+    let mut new_render_engine = Self::create(
+      instance,
+      surface,
+      adapter,
+      UVec2::new(width, height),
+      game_config,
+    )?;
+
+    // ! THIS IS TEMPORARY MESH DEBUGGING !
+    {
+      let mut new_mesh = Mesh::new("debug");
+      new_mesh.push_vertex_vec(&mut vec![
+        Vertex {
+          position: [-0.0868241, 0.49240386, 0.0],
+          texture_coordinates: [0.4131759, 0.00759614],
+          color: [1.0, 0.0, 0.0],
+        }, // A
+        Vertex {
+          position: [-0.49513406, 0.06958647, 0.0],
+          texture_coordinates: [0.0048659444, 0.43041354],
+          color: [0.0, 1.0, 0.0],
+        }, // B
+        Vertex {
+          position: [-0.21918549, -0.44939706, 0.0],
+          texture_coordinates: [0.28081453, 0.949397],
+          color: [0.0, 0.0, 1.0],
+        }, // C
+        Vertex {
+          position: [0.35966998, -0.3473291, 0.0],
+          texture_coordinates: [0.85967, 0.84732914],
+          color: [1.0, 1.0, 0.0],
+        }, // D
+        Vertex {
+          position: [0.44147372, 0.2347359, 0.0],
+          texture_coordinates: [0.9414737, 0.2652641],
+          color: [1.0, 0.0, 1.0],
+        }, // E
+      ]);
+
+      new_mesh.push_index_vec(&mut vec![0, 1, 4, 1, 2, 4, 2, 3, 4]);
+
+      // * Passes in the entire device as a mutable ref to finalize the Mesh.
+      new_mesh.generate_wgpu_buffers(&mut new_render_engine.device);
+
+      // * Then we store the newly generated Mesh into our render engine.
+      // * It's now owned by the render engine.
+      new_render_engine.store_mesh(&new_mesh.get_name().clone(), new_mesh);
+
+      new_render_engine.create_texture("./prototype_textures/tf.png");
+
+      // ? BEGIN DEBUGGING MODEL LOADER ?
+
+      // ! CHAIR - OBJ
+
+      let chair_model = match ModelLoader::load_model(
+        "./prototype_models/chair.obj",
+        &new_render_engine.device,
+        &new_render_engine.queue,
+      ) {
+        Ok(chair) => chair,
+        Err(e) => panic!("RenderEngine: {}", e),
+      };
+
+      new_render_engine.store_model(&chair_model.name.clone(), chair_model);
+
+      new_render_engine.create_texture("./prototype_textures/chair.png");
+
+      // ! SNOWMAN - OBJ
+
+      let snowman = match ModelLoader::load_model(
+        "./prototype_models/snowman.obj",
+        &new_render_engine.device,
+        &new_render_engine.queue,
+      ) {
+        Ok(snowman) => snowman,
+        Err(e) => panic!("RenderEngine: {}", e),
+      };
+
+      new_render_engine.store_model(&snowman.name.clone(), snowman);
+
+      new_render_engine.create_texture("./prototype_textures/snowman.png");
+
+      // ! MINETEST SAM - GLTF
+
+      let minetest_sam = match ModelLoader::load_model(
+        "./prototype_models/minetest_sam.gltf",
+        &new_render_engine.device,
+        &new_render_engine.queue,
+      ) {
+        Ok(sam) => sam,
+        Err(e) => panic!("RenderEngine: {}", e),
+      };
+
+      new_render_engine.store_model(&minetest_sam.name.clone(), minetest_sam);
+
+      new_render_engine.create_texture("./prototype_textures/minetest_sam.png");
+
+      // ! SNOWMAN - GLTF
+
+      let snowman_gltf = match ModelLoader::load_model(
+        "./prototype_models/snowman.gltf",
+        &new_render_engine.device,
+        &new_render_engine.queue,
+      ) {
+        Ok(snowman_gltf) => snowman_gltf,
+        Err(e) => panic!("RenderEngine: {}", e),
+      };
+
+      new_render_engine.store_model(&snowman_gltf.name.clone(), snowman_gltf);
+
+      // ! SIMPLE_SKIN - GLTF
+
+      let simple_skin = match ModelLoader::load_model(
+        "./prototype_models/simple_skin.gltf",
+        &new_render_engine.device,
+        &new_render_engine.queue,
+      ) {
+        Ok(simple) => simple,
+        Err(e) => panic!("RenderEngine: {}", e),
+      };
+
+      new_render_engine.store_model(&simple_skin.name.clone(), simple_skin);
+
+      // ? END DEBUGGING MODEL LOADER ?
+    }
+    // ! END TEMPORARY MESH DEBUGGING !
+
+    Ok(new_render_engine)
+  }
+
+  ///
+  /// Create a RenderEngine that renders into an offscreen texture instead of a window.
+  ///
+  /// The frame can be read back with read_pixels. This allows testing
+  /// the render pipeline without a display.
+  ///
+  pub fn new_headless(size: UVec2, game_config: &GameConfig) -> Result<Self, RenderInitError> {
+    let (instance, surface, adapter) = Self::try_backends(|backends| {
+      let instance = Self::create_instance(backends);
+      Ok(Self::request_adapter(&instance, None).map(|adapter| (instance, None, adapter)))
+    })?;
+    Self::create(instance, surface, adapter, size, game_config)
+  }
+
+  ///
+  /// Find the first backend that works, in BACKEND_ORDER.
+  ///
+  /// try_backend returns None when the backend has no adapter, and an
+  /// error when it couldn't even be tried. Either way the next one is
+  /// tried, and if none work every reason gets reported.
+  ///
+  fn try_backends<T>(
+    mut try_backend: impl FnMut(wgpu::Backends) -> Result<Option<T>, String>,
+  ) -> Result<T, RenderInitError> {
+    let mut attempts = vec![];
+    for (backends, backend_name) in BACKEND_ORDER {
+      match try_backend(backends) {
+        Ok(Some(found)) => {
+          println!("RenderEngine: using the [{}] backend.", backend_name);
+          return Ok(found);
+        }
+        Ok(None) => attempts.push((backend_name.to_string(), "No adapter.".to_string())),
+        Err(e) => attempts.push((backend_name.to_string(), e)),
+      }
+    }
+    Err(RenderInitError::NoAdapter(attempts))
+  }
 
-    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-      // This is the portion where you can select the backend.
+  ///
+  /// Create the wgpu instance for a backend.
+  ///
+  fn create_instance(backends: wgpu::Backends) -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
       // Vulkan, OpenGL, Metal, DX11, DX12, and WebGPU.
-      // It automatically selects based on your hardware for now.
-      backends: wgpu::Backends::PRIMARY,
+      // See try_backends for how this gets picked.
+      backends,
       flags: wgpu::InstanceFlags::debugging(),
       dx12_shader_compiler: wgpu::Dx12Compiler::default(),
       gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
-    });
-
-    let surface = match link_wgpu_to_sdl2(&instance, window_handler.borrow_window()) {
-      Ok(new_surface) => new_surface,
-      Err(e) => panic!("{}", e),
-    };
+    })
+  }
 
+  ///
+  /// Ask an instance for a GPU. None if it doesn't have one.
+  ///
+  fn request_adapter(
+    instance: &wgpu::Instance,
+    surface: Option<&wgpu::Surface>,
+  ) -> Option<wgpu::Adapter> {
     // We must block the main thread while this completes or things can go crazy.
     // This is waiting for a future.
-    let adapter_option =
-      // ! Block on might cause a crash in WASM !
-      pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-        power_preference: wgpu::PowerPreference::HighPerformance,
-        force_fallback_adapter: false,
-        compatible_surface: Some(&surface),
-      }));
-
-    let adapter = match adapter_option {
-      Some(new_adapter) => new_adapter,
-      None => panic!("RenderEngine: no graphics adapter found!"),
-    };
+    // ! Block on might cause a crash in WASM !
+    pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+      power_preference: wgpu::PowerPreference::HighPerformance,
+      force_fallback_adapter: false,
+      compatible_surface: surface,
+    }))
+  }
+
+  ///
+  /// The shared part of new and new_headless.
+  ///
+  /// Without a surface, the RenderEngine renders into an offscreen texture.
+  ///
+  fn create(
+    instance: wgpu::Instance,
+    surface: Option<wgpu::Surface>,
+    adapter: wgpu::Adapter,
+    size: UVec2,
+    game_config: &GameConfig,
+  ) -> Result<Self, RenderInitError> {
+    // This is written verbosely so you can read what's going on easier.
+
+    // Without this feature, WebGPU only guarantees 1x and 4x multisampling.
+    let adapter_specific_format_features = adapter
+      .features()
+      .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+    let mut device_features = wgpu::Features::DEPTH_CLIP_CONTROL;
+    if adapter_specific_format_features {
+      device_features |= wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+    }
 
     // We must block the main thread while this completes or things can go crazy.
     // This is waiting for a future.
@@ -154,16 +445,16 @@ impl RenderEngine {
         limits: wgpu::Limits::default(),
         label: Some("minetest_gpu"),
         // * this was: wgpu::Features::default()
-        features: /*wgpu::Features::POLYGON_MODE_LINE | */ wgpu::Features::DEPTH_CLIP_CONTROL,
+        features: /*wgpu::Features::POLYGON_MODE_LINE | */ device_features,
       },
       None,
     )) {
       Ok(device_and_queue) => device_and_queue,
-      Err(e) => panic!("{}", e),
+      Err(e) => return Err(RenderInitError::Device(e.to_string())),
     };
 
     // Load up the default shader source code.
-    let shader_code = match read_file_to_string("shaders/default_shader.wgsl") {
+    let shader_code = match read_file_to_string(DEFAULT_SHADER_PATH) {
       Ok(shader_code) => shader_code,
       Err(e) => panic!("RenderEngine: {}", e),
     };
@@ -187,23 +478,49 @@ impl RenderEngine {
       push_constant_ranges: &[],
     });
 
-    // Surface capabilities.
-    let surface_caps = surface.get_capabilities(&adapter);
-
-    // And the surface format.
-    let surface_format = match surface_caps
-      .formats
-      .iter()
-      .copied()
-      // This may not be thorough enough to get the format we want.
-      .find(|f| f.is_srgb())
-    {
-      Some(found_surface) => found_surface,
-      None => surface_caps.formats[0],
+    let surface_format = match surface.as_ref() {
+      Some(surface) => {
+        // Surface capabilities.
+        let surface_caps = surface.get_capabilities(&adapter);
+
+        // And the surface format.
+        match surface_caps
+          .formats
+          .iter()
+          .copied()
+          // This may not be thorough enough to get the format we want.
+          .find(|f| f.is_srgb())
+        {
+          Some(found_surface) => found_surface,
+          None => surface_caps.formats[0],
+        }
+      }
+      None => RenderTarget::OFFSCREEN_FORMAT,
     };
 
-    // Need to get the window size to configure the surface.
-    let (width, height) = window_handler.borrow_window().size();
+    // The color target and the depth buffer both have to support the sample count.
+    let sample_count = MsaaBuffer::choose_sample_count(
+      MsaaBuffer::get_requested_sample_count(game_config),
+      |count| {
+        (adapter_specific_format_features
+          && adapter
+            .get_texture_format_features(surface_format)
+            .flags
+            .sample_count_supported(count)
+          && adapter
+            .get_texture_format_features(DepthBuffer::DEPTH_FORMAT)
+            .flags
+            .sample_count_supported(count))
+          || count == 4
+      },
+    );
+
+    let anisotropy = Texture::choose_anisotropy(
+      Texture::get_requested_anisotropy(game_config),
+      Texture::get_max_anisotropy(&adapter),
+    );
+
+    let (width, height) = (size.x, size.y);
 
     let config = wgpu::SurfaceConfiguration {
       usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -215,61 +532,28 @@ impl RenderEngine {
       view_formats: Vec::default(),
     };
 
-    // And the pipeline, very important!.
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-      layout: Some(&render_pipeline_layout),
-      vertex: wgpu::VertexState {
-        buffers: &[
-          Mesh::get_wgpu_descriptor(),
-          InstanceMatrixRGBA::get_wgpu_descriptor(),
-        ],
-        module: &shader,
-        entry_point: "vs_main",
-      },
-      fragment: Some(wgpu::FragmentState {
-        targets: &[Some(wgpu::ColorTargetState {
-          format: config.format,
-          blend: Some(wgpu::BlendState {
-            color: wgpu::BlendComponent {
-              src_factor: wgpu::BlendFactor::SrcAlpha,
-              dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-              operation: wgpu::BlendOperation::Add,
-            },
-            alpha: wgpu::BlendComponent::OVER,
-          }),
-          write_mask: wgpu::ColorWrites::ALL,
-        })],
-        module: &shader,
-        entry_point: "fs_main",
-      }),
-      primitive: wgpu::PrimitiveState {
-        topology: wgpu::PrimitiveTopology::TriangleList,
-        strip_index_format: None,
-        front_face: wgpu::FrontFace::Ccw,
-        // Backface culling.
-        cull_mode: None, //Some(wgpu::Face::Back),
-        unclipped_depth: false,
-        polygon_mode: wgpu::PolygonMode::Fill,
-        conservative: false,
-      },
-      depth_stencil: Some(wgpu::DepthStencilState {
-        format: DepthBuffer::DEPTH_FORMAT,
-        depth_write_enabled: true,
-        depth_compare: wgpu::CompareFunction::Less,
-        stencil: wgpu::StencilState::default(),
-        bias: wgpu::DepthBiasState::default(),
-      }),
-      label: None,
-      multisample: wgpu::MultisampleState {
-        count: 1,
-        mask: !0,
-        alpha_to_coverage_enabled: false,
-      },
-      multiview: None,
+    // And the pipelines, very important!. One for each AlphaMode.
+    let render_pipelines = AlphaModePipelines::new(|alpha_mode| {
+      Self::create_render_pipeline(
+        &device,
+        &render_pipeline_layout,
+        &shader,
+        config.format,
+        sample_count,
+        alpha_mode,
+      )
     });
 
-    // Then actually configure the surface with the config.
-    surface.configure(&device, &config);
+    // Then actually configure the render target with the config.
+    let target = match surface {
+      Some(surface) => {
+        surface.configure(&device, &config);
+        RenderTarget::Window(surface)
+      }
+      None => RenderTarget::Offscreen(RenderTarget::create_offscreen_texture(&device, &config)),
+    };
+
+    let msaa_buffer = Self::create_msaa_buffer(&device, &config, sample_count);
 
     let clear_color = wgpu::Color {
       r: 0.1,
@@ -294,31 +578,42 @@ impl RenderEngine {
     // Initial creation and updating of the Camera.
     let mut camera = Camera::new(
       Vec3A::new(0.0, 0.0, -2.0),
-      65.0,
+      65.0f32.to_radians(),
+      &device,
+      &size,
+      mesh_trs_uniform.get_buffer(),
+      instance_trigger.get_buffer(),
+    );
+    camera.set_damping(Camera::get_requested_damping(game_config));
+    camera.build_view_projection_matrix(&device, &size, &queue);
+
+    let hud_projection = HudProjection::new(
       &device,
-      window_handler,
       mesh_trs_uniform.get_buffer(),
       instance_trigger.get_buffer(),
     );
-    camera.build_view_projection_matrix(&device, window_handler, &queue);
+
+    let upscale_blit = UpscaleBlit::new(&device, surface_format);
 
     // ! TESTING
     let color_uniform = ColorUniform::new(1.0, 1.0, 1.0, &device);
     // ! END TESTING
 
+    let max_texture_dimension = device.limits().max_texture_dimension_2d;
+
     let mut new_render_engine = RenderEngine {
       camera,
 
       // General implementation.
       instance,
-      surface,
+      target,
       adapter,
       device,
       queue,
       shader,
 
       render_pipeline_layout,
-      render_pipeline,
+      render_pipelines,
       surface_format,
 
       // Render state memory.
@@ -328,6 +623,19 @@ impl RenderEngine {
       depth_buffer: None,
       render_command_count: 0,
 
+      // Multisampling.
+      sample_count,
+      msaa_buffer,
+
+      anisotropy,
+
+      // Dynamic resolution.
+      dynamic_resolution: DynamicResolution::from_config(game_config),
+      upscale_blit,
+      scaled_target: None,
+      surface_view: None,
+      hud_msaa_buffer: None,
+
       // General variables.
       config,
       size: UVec2::new(width, height),
@@ -343,6 +651,18 @@ impl RenderEngine {
       instance_buffer: None,
       instance_trigger,
 
+      // Blended render calls.
+      blended_mesh_render_queue: vec![],
+      blended_model_render_queue: vec![],
+      blended_instanced_mesh_render_queue: vec![],
+      blended_instanced_model_render_queue: vec![],
+
+      // HUD text.
+      hud_text_queue: vec![],
+      hud_projection,
+      // Loaded in right below, it has to go through store_texture.
+      font_texture_id: 0,
+
       // ID dispatcher for wgpu. Acts like the OpenGL ID dispatcher.
       id_dispatcher: Unique64::new(),
 
@@ -352,6 +672,14 @@ impl RenderEngine {
 
       texture_name_to_id: AHashMap::new(),
       textures: AHashMap::new(),
+      texture_color_keys: AHashMap::new(),
+      asset_loader: AssetLoader::with_default_threads(max_texture_dimension),
+      texture_watcher: if game_config.get_bool("texture_hot_reload", false) {
+        println!("RenderEngine: texture_hot_reload is on, watching texture files.");
+        Some(TextureWatcher::new())
+      } else {
+        None
+      },
       model_name_to_id: AHashMap::new(),
       models: AHashMap::new(),
 
@@ -364,158 +692,224 @@ impl RenderEngine {
       // ! END TESTING VARIABLES
     };
 
-    // ! THIS IS TEMPORARY MESH DEBUGGING !
-    {
-      let mut new_mesh = Mesh::new("debug");
-      new_mesh.push_vertex_vec(&mut vec![
-        Vertex {
-          position: [-0.0868241, 0.49240386, 0.0],
-          texture_coordinates: [0.4131759, 0.00759614],
-          color: [1.0, 0.0, 0.0],
-        }, // A
-        Vertex {
-          position: [-0.49513406, 0.06958647, 0.0],
-          texture_coordinates: [0.0048659444, 0.43041354],
-          color: [0.0, 1.0, 0.0],
-        }, // B
-        Vertex {
-          position: [-0.21918549, -0.44939706, 0.0],
-          texture_coordinates: [0.28081453, 0.949397],
-          color: [0.0, 0.0, 1.0],
-        }, // C
-        Vertex {
-          position: [0.35966998, -0.3473291, 0.0],
-          texture_coordinates: [0.85967, 0.84732914],
-          color: [1.0, 1.0, 0.0],
-        }, // D
-        Vertex {
-          position: [0.44147372, 0.2347359, 0.0],
-          texture_coordinates: [0.9414737, 0.2652641],
-          color: [1.0, 0.0, 1.0],
-        }, // E
-      ]);
-
-      new_mesh.push_index_vec(&mut vec![0, 1, 4, 1, 2, 4, 2, 3, 4]);
+    // The glyphs are anti-aliased, they need to blend.
+    new_render_engine.font_texture_id = new_render_engine.create_texture(FONT_ATLAS_PATH);
+    new_render_engine.set_texture_alpha_mode(new_render_engine.font_texture_id, AlphaMode::Blend);
 
-      // * Passes in the entire device as a mutable ref to finalize the Mesh.
-      new_mesh.generate_wgpu_buffers(&mut new_render_engine.device);
+    // minetest.conf might have turned dynamic resolution on.
+    new_render_engine.update_render_targets();
 
-      // * Then we store the newly generated Mesh into our render engine.
-      // * It's now owned by the render engine.
-      new_render_engine.store_mesh(&new_mesh.get_name().clone(), new_mesh);
-
-      new_render_engine.create_texture("./prototype_textures/tf.png");
-
-      // ? BEGIN DEBUGGING MODEL LOADER ?
-
-      // ! CHAIR - OBJ
-
-      let chair_model = match ModelLoader::load_model(
-        "./prototype_models/chair.obj",
-        &new_render_engine.device,
-        &new_render_engine.queue,
-      ) {
-        Ok(chair) => chair,
-        Err(e) => panic!("RenderEngine: {}", e),
-      };
-
-      new_render_engine.store_model(&chair_model.name.clone(), chair_model);
-
-      new_render_engine.create_texture("./prototype_textures/chair.png");
-
-      // ! SNOWMAN - OBJ
+    Ok(new_render_engine)
+  }
 
-      let snowman = match ModelLoader::load_model(
-        "./prototype_models/snowman.obj",
-        &new_render_engine.device,
-        &new_render_engine.queue,
-      ) {
-        Ok(snowman) => snowman,
-        Err(e) => panic!("RenderEngine: {}", e),
-      };
+  ///
+  /// Automatically resizes the surface from the passed through UVec2.
+  ///
+  /// We simply check if the size is different, then update the surface
+  /// config and update the surface with it.
+  ///
+  fn update_size(&mut self, new_size: &UVec2) {
+    if self.size != *new_size {
+      // Update internal size
+      self.size = *new_size;
 
-      new_render_engine.store_model(&snowman.name.clone(), snowman);
+      // println!("RenderEngine: SURFACE UPDATE! {:?}", self.size);
 
-      new_render_engine.create_texture("./prototype_textures/snowman.png");
+      // Now update the config.
+      self.config.width = self.size.x;
+      self.config.height = self.size.y;
 
-      // ! MINETEST SAM - GLTF
+      // Finally, reconfigure the surface with the config.
+      self.target.configure(&self.device, &self.config);
 
-      let minetest_sam = match ModelLoader::load_model(
-        "./prototype_models/minetest_sam.gltf",
-        &new_render_engine.device,
-        &new_render_engine.queue,
-      ) {
-        Ok(sam) => sam,
-        Err(e) => panic!("RenderEngine: {}", e),
-      };
+      // The MSAA buffer has to match the surface size.
+      self.msaa_buffer = Self::create_msaa_buffer(&self.device, &self.config, self.sample_count);
 
-      new_render_engine.store_model(&minetest_sam.name.clone(), minetest_sam);
+      // And the scaled target has to follow the window.
+      self.scaled_target = None;
+      self.hud_msaa_buffer = None;
+      self.update_render_targets();
+    }
+  }
 
-      new_render_engine.create_texture("./prototype_textures/minetest_sam.png");
+  ///
+  /// Get the surface config, at the size the world is actually drawn at.
+  ///
+  /// This is the window size, unless dynamic resolution has scaled it down.
+  ///
+  fn get_render_config(&self) -> wgpu::SurfaceConfiguration {
+    let mut render_config = self.config.clone();
+    if let Some(scaled_target) = &self.scaled_target {
+      render_config.width = scaled_target.get_size().x;
+      render_config.height = scaled_target.get_size().y;
+    }
+    render_config
+  }
 
-      // ! SNOWMAN - GLTF
+  ///
+  /// Make the scaled target and MSAA buffer match the dynamic resolution scale.
+  ///
+  /// Nothing gets reallocated if the render size didn't change.
+  ///
+  fn update_render_targets(&mut self) {
+    let render_size = self.dynamic_resolution.get_render_size(&self.size);
+    let current_size = match &self.scaled_target {
+      Some(scaled_target) => *scaled_target.get_size(),
+      None => self.size,
+    };
+    if render_size == current_size {
+      return;
+    }
 
-      let snowman_gltf = match ModelLoader::load_model(
-        "./prototype_models/snowman.gltf",
-        &new_render_engine.device,
-        &new_render_engine.queue,
-      ) {
-        Ok(snowman_gltf) => snowman_gltf,
-        Err(e) => panic!("RenderEngine: {}", e),
-      };
+    self.scaled_target = match render_size == self.size {
+      true => None,
+      false => {
+        let mut scaled_config = self.config.clone();
+        scaled_config.width = render_size.x;
+        scaled_config.height = render_size.y;
+        Some(ScaledTarget::new(
+          &self.device,
+          &scaled_config,
+          self.upscale_blit.get_sampler(),
+        ))
+      }
+    };
 
-      new_render_engine.store_model(&snowman_gltf.name.clone(), snowman_gltf);
+    self.msaa_buffer =
+      Self::create_msaa_buffer(&self.device, &self.get_render_config(), self.sample_count);
 
-      // ! SIMPLE_SKIN - GLTF
+    // The HUD is drawn after the upscale, so it needs one at the window size.
+    self.hud_msaa_buffer = match self.scaled_target.is_some() {
+      true => Self::create_msaa_buffer(&self.device, &self.config, self.sample_count),
+      false => None,
+    };
+  }
 
-      let simple_skin = match ModelLoader::load_model(
-        "./prototype_models/simple_skin.gltf",
-        &new_render_engine.device,
-        &new_render_engine.queue,
-      ) {
-        Ok(simple) => simple,
-        Err(e) => panic!("RenderEngine: {}", e),
-      };
+  ///
+  /// Turn dynamic resolution on or off, and set how far it can scale. [0.25 - 1.0]
+  ///
+  /// When on, the world is drawn smaller when frames go over the fps_max budget,
+  /// and upscaled to the window.
+  ///
+  pub fn set_dynamic_resolution(&mut self, enabled: bool, min_scale: f32, max_scale: f32) {
+    self.dynamic_resolution.set(enabled, min_scale, max_scale);
+    self.update_render_targets();
+  }
 
-      new_render_engine.store_model(&simple_skin.name.clone(), simple_skin);
+  ///
+  /// Get the current dynamic resolution scale. 1.0 is native.
+  ///
+  pub fn get_render_scale(&self) -> f32 {
+    self.dynamic_resolution.get_scale()
+  }
 
-      // ? END DEBUGGING MODEL LOADER ?
+  ///
+  /// Get the size the world is drawn at this frame. [pixels]
+  ///
+  pub fn get_render_size(&self) -> UVec2 {
+    match &self.scaled_target {
+      Some(scaled_target) => *scaled_target.get_size(),
+      None => self.size,
     }
-    // ! END TEMPORARY MESH DEBUGGING !
-
-    new_render_engine
   }
 
   ///
-  /// Automatically resizes the surface from the passed through UVec2.
+  /// Feed the last frame time into dynamic resolution. [seconds]
   ///
-  /// We simply check if the size is different, then update the surface
-  /// config and update the surface with it.
+  /// This has to happen between frames, the render targets might change.
   ///
-  fn update_size(&mut self, new_size: &UVec2) {
-    if self.size != *new_size {
-      // Update internal size
-      self.size = *new_size;
+  pub fn report_frame_time(&mut self, frame_time: f64) {
+    if self.dynamic_resolution.report_frame_time(frame_time) {
+      self.update_render_targets();
+    }
+  }
 
-      // println!("RenderEngine: SURFACE UPDATE! {:?}", self.size);
+  ///
+  /// Create the render pipeline for an AlphaMode.
+  ///
+  fn create_render_pipeline(
+    device: &wgpu::Device,
+    render_pipeline_layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    alpha_mode: AlphaMode,
+  ) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      layout: Some(render_pipeline_layout),
+      vertex: wgpu::VertexState {
+        buffers: &[
+          Mesh::get_wgpu_descriptor(),
+          InstanceMatrixRGBA::get_wgpu_descriptor(),
+        ],
+        module: shader,
+        entry_point: "vs_main",
+      },
+      fragment: Some(wgpu::FragmentState {
+        targets: &[Some(alpha_mode.get_color_target_state(format))],
+        module: shader,
+        entry_point: alpha_mode.get_fragment_entry_point(),
+      }),
+      primitive: wgpu::PrimitiveState {
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        strip_index_format: None,
+        front_face: wgpu::FrontFace::Ccw,
+        // Backface culling.
+        cull_mode: None, //Some(wgpu::Face::Back),
+        unclipped_depth: false,
+        polygon_mode: wgpu::PolygonMode::Fill,
+        conservative: false,
+      },
+      depth_stencil: Some(alpha_mode.get_depth_stencil_state()),
+      label: None,
+      multisample: wgpu::MultisampleState {
+        count: sample_count,
+        mask: !0,
+        alpha_to_coverage_enabled: false,
+      },
+      multiview: None,
+    })
+  }
 
-      // Now update the config.
-      self.config.width = self.size.x;
-      self.config.height = self.size.y;
+  ///
+  /// Create the MSAA buffer, if multisampling is enabled.
+  ///
+  fn create_msaa_buffer(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    sample_count: u32,
+  ) -> Option<MsaaBuffer> {
+    if sample_count > 1 {
+      Some(MsaaBuffer::new(device, config, sample_count, "msaa_buffer"))
+    } else {
+      None
+    }
+  }
 
-      // Finally, reconfigure the surface with the config.
-      self.surface.configure(&self.device, &self.config);
+  ///
+  /// Get the view to render color into, and the view to resolve it to.
+  ///
+  /// With MSAA on, everything is drawn into the MSAA buffer and resolved into
+  /// the surface texture. With it off, it's drawn straight into the surface texture.
+  ///
+  fn get_color_target<'a>(
+    texture_view: &'a TextureView,
+    msaa_buffer: Option<&'a MsaaBuffer>,
+  ) -> (&'a TextureView, Option<&'a TextureView>) {
+    match msaa_buffer {
+      Some(msaa_buffer) => (msaa_buffer.get_view(), Some(texture_view)),
+      None => (texture_view, None),
     }
   }
 
   ///
   /// This simply updates the Camera's uniform projection matrix.
   ///
-  pub fn update_camera_matrix(&mut self, window_handler: &WindowHandler) {
+  pub fn update_camera_matrix(&mut self) {
     // First update the camera in cpu and wgu memory.
     self
       .camera
-      .build_view_projection_matrix(&self.device, window_handler, &self.queue);
+      .build_view_projection_matrix(&self.device, &self.size, &self.queue);
 
     // Next we will write the color buffer into memory.
     // ! TODO: this might be needed in the uninstanced/instanced loop. Test this.
@@ -528,25 +922,24 @@ impl RenderEngine {
   /// Aka, the framebuffer.
   ///
   pub fn generate_frame_buffer(&mut self) {
-    match self.surface.get_current_texture() {
-      Ok(texture) => self.output = Some(texture),
-      Err(e) => panic!("RenderEngine: Surface texture error. {}", e),
-    }
-
-    match self.output.as_mut() {
-      Some(output) => {
-        self.texture_view = Some(
-          output
-            .texture
-            // ? If this comes up as an error in vscode, you need to switch
-            // ? to a rust-analyzer pre-release version.
-            .create_view(&wgpu::TextureViewDescriptor::default()),
-        );
+    let (output, texture_view) = self.target.get_current_texture();
+    self.output = output;
+
+    // Scaled down, everything draws into the scaled target until the upscale.
+    match &self.scaled_target {
+      Some(scaled_target) => {
+        self.texture_view = Some(scaled_target.create_view());
+        self.surface_view = Some(texture_view);
       }
-      None => panic!("RenderEngine: Tried to generate a framebuffer with no output."),
+      None => self.texture_view = Some(texture_view),
     }
 
-    self.depth_buffer = Some(DepthBuffer::new(&self.device, &self.config, "depth_buffer"));
+    self.depth_buffer = Some(DepthBuffer::new(
+      &self.device,
+      &self.get_render_config(),
+      self.sample_count,
+      "depth_buffer",
+    ));
   }
 
   ///
@@ -596,14 +989,17 @@ impl RenderEngine {
       wgpu::LoadOp::Load
     };
 
+    let (color_view, resolve_target) =
+      Self::get_color_target(texture_view, self.msaa_buffer.as_ref());
+
     let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
       // The label of this render pass.
       label: Some("minetest_clear_buffers_render_pass"),
 
       // color attachments is a array of pipeline render pass color attachments.
       color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-        view: texture_view,
-        resolve_target: None,
+        view: color_view,
+        resolve_target,
         ops: wgpu::Operations {
           load: clear_color,
           store: wgpu::StoreOp::Store,
@@ -622,7 +1018,7 @@ impl RenderEngine {
       timestamp_writes: None,
     });
 
-    render_pass.set_pipeline(&self.render_pipeline);
+    render_pass.set_pipeline(self.render_pipelines.get(AlphaMode::Opaque));
   }
 
   ///
@@ -664,14 +1060,17 @@ impl RenderEngine {
 
     // * Begin not instanced render calls. [MESH]
     // Begin a wgpu render pass
+    let (color_view, resolve_target) =
+      Self::get_color_target(texture_view, self.msaa_buffer.as_ref());
+
     let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
       // The label of this render pass.
       label: Some("minetest_not_instanced_mesh_render_pass"),
 
       // color attachments is a array of pipeline render pass color attachments.
       color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-        view: texture_view,
-        resolve_target: None,
+        view: color_view,
+        resolve_target,
         ops: wgpu::Operations {
           load: wgpu::LoadOp::Load,
           store: wgpu::StoreOp::Store,
@@ -690,7 +1089,7 @@ impl RenderEngine {
       timestamp_writes: None,
     });
 
-    render_pass.set_pipeline(&self.render_pipeline);
+    render_pass.set_pipeline(self.render_pipelines.get(AlphaMode::Opaque));
 
     // Activate the camera's bind group.
     render_pass.set_bind_group(1, self.camera.get_bind_group(), &[]);
@@ -720,6 +1119,9 @@ impl RenderEngine {
 
         match self.textures.get(&texture_id) {
           Some(texture) => {
+            // The texture decides how alpha is handled.
+            render_pass.set_pipeline(self.render_pipelines.get(texture.get_alpha_mode()));
+
             // Now activate the used texture's bind group.
             render_pass.set_bind_group(0, texture.get_wgpu_diffuse_bind_group(), &[]);
 
@@ -772,6 +1174,12 @@ impl RenderEngine {
   ///
   fn process_not_instanced_mesh_render_calls(&mut self) {
     while let Some(not_instanced_mesh_render_call) = self.mesh_render_queue.pop_front() {
+      if self.is_texture_blended(not_instanced_mesh_render_call.get_texture_id()) {
+        self
+          .blended_mesh_render_queue
+          .push(not_instanced_mesh_render_call);
+        continue;
+      }
       self.initialize_render();
       self.process_not_instanced_mesh_render_call(not_instanced_mesh_render_call);
       self.submit_render();
@@ -810,14 +1218,17 @@ impl RenderEngine {
     };
 
     // Begin a wgpu render pass
+    let (color_view, resolve_target) =
+      Self::get_color_target(texture_view, self.msaa_buffer.as_ref());
+
     let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
       // The label of this render pass.
       label: Some("minetest_not_instanced_model_render_pass"),
 
       // color attachments is a array of pipeline render pass color attachments.
       color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-        view: texture_view,
-        resolve_target: None,
+        view: color_view,
+        resolve_target,
         ops: wgpu::Operations {
           load: wgpu::LoadOp::Load,
           store: wgpu::StoreOp::Store,
@@ -836,7 +1247,7 @@ impl RenderEngine {
       timestamp_writes: None,
     });
 
-    render_pass.set_pipeline(&self.render_pipeline);
+    render_pass.set_pipeline(self.render_pipelines.get(AlphaMode::Opaque));
 
     // Activate the camera's bind group.
     render_pass.set_bind_group(1, self.camera.get_bind_group(), &[]);
@@ -878,6 +1289,9 @@ impl RenderEngine {
         for (mesh, texture_id) in meshes.iter().zip(texture_ids) {
           match self.textures.get(texture_id) {
             Some(texture) => {
+              // The texture decides how alpha is handled.
+              render_pass.set_pipeline(self.render_pipelines.get(texture.get_alpha_mode()));
+
               // Now activate the used texture's bind group.
               render_pass.set_bind_group(0, texture.get_wgpu_diffuse_bind_group(), &[]);
 
@@ -958,6 +1372,12 @@ impl RenderEngine {
   ///
   fn process_not_instanced_model_render_calls(&mut self) {
     while let Some(model_not_instanced_render_call) = self.model_render_queue.pop_front() {
+      if self.is_any_texture_blended(model_not_instanced_render_call.get_texture_ids()) {
+        self
+          .blended_model_render_queue
+          .push(model_not_instanced_render_call);
+        continue;
+      }
       self.initialize_render();
       self.process_not_instanced_model_render_call(model_not_instanced_render_call);
       self.submit_render();
@@ -1013,14 +1433,17 @@ impl RenderEngine {
     };
 
     // Begin a wgpu render pass
+    let (color_view, resolve_target) =
+      Self::get_color_target(texture_view, self.msaa_buffer.as_ref());
+
     let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
       // The label of this render pass.
       label: Some("minetest_instanced_mesh_render_pass"),
 
       // color attachments is a array of pipeline render pass color attachments.
       color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-        view: texture_view,
-        resolve_target: None,
+        view: color_view,
+        resolve_target,
         ops: wgpu::Operations {
           load: wgpu::LoadOp::Load,
           store: wgpu::StoreOp::Store,
@@ -1039,7 +1462,7 @@ impl RenderEngine {
       timestamp_writes: None,
     });
 
-    render_pass.set_pipeline(&self.render_pipeline);
+    render_pass.set_pipeline(self.render_pipelines.get(AlphaMode::Opaque));
 
     // Activate the camera's bind group.
     render_pass.set_bind_group(1, self.camera.get_bind_group(), &[]);
@@ -1055,6 +1478,9 @@ impl RenderEngine {
       Some(mesh) => {
         match self.textures.get(&texture_id) {
           Some(texture) => {
+            // The texture decides how alpha is handled.
+            render_pass.set_pipeline(self.render_pipelines.get(texture.get_alpha_mode()));
+
             // Now activate the used texture's bind group.
             render_pass.set_bind_group(0, texture.get_wgpu_diffuse_bind_group(), &[]);
 
@@ -1127,6 +1553,12 @@ impl RenderEngine {
 
     // Iterate through all the instanced data.
     for (mesh_name, instance_data) in instanced_key_value_set {
+      if self.is_texture_blended(instance_data.get_texture_id()) {
+        self
+          .blended_instanced_mesh_render_queue
+          .push((mesh_name, instance_data));
+        continue;
+      }
       self.initialize_render();
       self.process_instanced_mesh_render_call(
         mesh_name,
@@ -1171,14 +1603,17 @@ impl RenderEngine {
     };
 
     // Begin a wgpu render pass
+    let (color_view, resolve_target) =
+      Self::get_color_target(texture_view, self.msaa_buffer.as_ref());
+
     let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
       // The label of this render pass.
       label: Some("minetest_instanced_model_render_pass"),
 
       // color attachments is a array of pipeline render pass color attachments.
       color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-        view: texture_view,
-        resolve_target: None,
+        view: color_view,
+        resolve_target,
         ops: wgpu::Operations {
           load: wgpu::LoadOp::Load,
           store: wgpu::StoreOp::Store,
@@ -1197,7 +1632,7 @@ impl RenderEngine {
       timestamp_writes: None,
     });
 
-    render_pass.set_pipeline(&self.render_pipeline);
+    render_pass.set_pipeline(self.render_pipelines.get(AlphaMode::Opaque));
 
     // Activate the camera's bind group.
     render_pass.set_bind_group(1, self.camera.get_bind_group(), &[]);
@@ -1238,77 +1673,290 @@ impl RenderEngine {
         for (mesh, texture_id) in meshes.iter().zip(texture_ids) {
           match self.textures.get(texture_id) {
             Some(texture) => {
+              // The texture decides how alpha is handled.
+              render_pass.set_pipeline(self.render_pipelines.get(texture.get_alpha_mode()));
+
               // Now activate the used texture's bind group.
               render_pass.set_bind_group(0, texture.get_wgpu_diffuse_bind_group(), &[]);
 
               // Now we're going to bind the pipeline to the Mesh and draw it.
 
-              self
-                .mesh_trs_uniform
-                .build_mesh_projection_matrix(&self.device, &self.queue);
+              self
+                .mesh_trs_uniform
+                .build_mesh_projection_matrix(&self.device, &self.queue);
+
+              render_pass.set_vertex_buffer(0, mesh.get_wgpu_vertex_buffer().slice(..));
+
+              let instance_buffer = match self.instance_buffer.as_ref() {
+                Some(buffer) => buffer,
+                None => panic!("RenderEngine: Attempted to render Model with no instance buffer."),
+              };
+
+              render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+
+              render_pass.set_index_buffer(
+                mesh.get_wgpu_index_buffer().slice(..),
+                wgpu::IndexFormat::Uint32,
+              );
+
+              render_pass.draw_indexed(
+                0..mesh.get_number_of_indices(),
+                0,
+                0..(instance_data.len() as u32),
+              );
+            }
+            None => {
+              error!(
+                "render_engine: ID {} is not a stored Texture. [instanced]",
+                texture_id
+              );
+            }
+          }
+        }
+      }
+      None => {
+        error!(
+          "render_engine: ID {} is not a stored Model. [instanced]",
+          model_id
+        );
+      }
+    }
+  }
+
+  ///
+  /// Completely wipes out the instanced Model render queue and returns the current data to you.
+  ///
+  fn take_model_instanced_data(&mut self) -> AHashMap<u64, InstancedModelRenderData> {
+    let mut temporary = AHashMap::new();
+    swap(&mut self.instanced_model_render_queue, &mut temporary);
+    temporary
+  }
+
+  ///
+  /// Process and submit all the instanced Model render calls.
+  ///
+  pub fn process_instanced_model_render_calls(&mut self) {
+    // ! This is an absolute brute force method. Perhaps there's a more elegant way?
+    let instanced_key_value_set = self.take_model_instanced_data();
+
+    // Iterate through all the instanced data.
+    for (mesh_id, instance_data) in instanced_key_value_set {
+      if self.is_any_texture_blended(instance_data.borrow_texture_names()) {
+        self
+          .blended_instanced_model_render_queue
+          .push((mesh_id, instance_data));
+        continue;
+      }
+      self.initialize_render();
+      self.process_instanced_model_render_call(
+        mesh_id,
+        instance_data.borrow_texture_names(),
+        instance_data.borrow_data(),
+      );
+      self.submit_render();
+    }
+  }
+
+  ///
+  /// Get if a Texture uses AlphaMode::Blend.
+  ///
+  fn is_texture_blended(&self, texture_id: u64) -> bool {
+    self
+      .textures
+      .get(&texture_id)
+      .is_some_and(|texture| texture.get_alpha_mode().is_blended())
+  }
+
+  ///
+  /// Get if any of the Textures use AlphaMode::Blend.
+  ///
+  fn is_any_texture_blended(&self, texture_ids: &[u64]) -> bool {
+    texture_ids
+      .iter()
+      .any(|texture_id| self.is_texture_blended(*texture_id))
+  }
+
+  ///
+  /// Process all the blended render calls that were held back.
+  ///
+  /// This runs after everything opaque has been drawn, so the blended
+  /// geometry has something to blend with.
+  ///
+  fn process_blended_render_calls(&mut self) {
+    // The Camera's position is the view translation, the world position is the inverse of it.
+    let camera_position = -*self.camera.get_position();
+
+    // Back to front.
+    let mut blended_meshes = take(&mut self.blended_mesh_render_queue);
+    blended_meshes.sort_by(|a, b| {
+      let a_distance = a.get_translation().distance_squared(camera_position);
+      let b_distance = b.get_translation().distance_squared(camera_position);
+      b_distance.total_cmp(&a_distance)
+    });
+    for blended_mesh in blended_meshes {
+      self.initialize_render();
+      self.process_not_instanced_mesh_render_call(blended_mesh);
+      self.submit_render();
+    }
+
+    let mut blended_models = take(&mut self.blended_model_render_queue);
+    blended_models.sort_by(|a, b| {
+      let a_distance = a.get_translation().distance_squared(camera_position);
+      let b_distance = b.get_translation().distance_squared(camera_position);
+      b_distance.total_cmp(&a_distance)
+    });
+    for blended_model in blended_models {
+      self.initialize_render();
+      self.process_not_instanced_model_render_call(blended_model);
+      self.submit_render();
+    }
+
+    // todo: instances are not sorted back to front yet.
+    for (mesh_id, instance_data) in take(&mut self.blended_instanced_mesh_render_queue) {
+      self.initialize_render();
+      self.process_instanced_mesh_render_call(
+        mesh_id,
+        instance_data.get_texture_id(),
+        instance_data.borrow_data(),
+      );
+      self.submit_render();
+    }
+
+    for (model_id, instance_data) in take(&mut self.blended_instanced_model_render_queue) {
+      self.initialize_render();
+      self.process_instanced_model_render_call(
+        model_id,
+        instance_data.borrow_texture_names(),
+        instance_data.borrow_data(),
+      );
+      self.submit_render();
+    }
+  }
+
+  ///
+  /// Draw one queued HUD text call.
+  ///
+  /// upscaled is if the frame was already stretched over the window,
+  /// then it draws at the window size instead of the render size.
+  ///
+  fn process_hud_render_call(&mut self, hud_text_call: HudTextCall, upscaled: bool) {
+    let mut text_mesh = build_text_mesh(hud_text_call.get_quads());
+    text_mesh.generate_wgpu_buffers(&mut self.device);
+
+    let command_encoder = match self.command_encoder.as_mut() {
+      Some(encoder) => encoder,
+      None => panic!("RenderEngine: Attempted to process HUD render call without command encoder."),
+    };
+
+    let texture_view = match self.texture_view.as_ref() {
+      Some(view) => view,
+      None => panic!("RenderEngine: Attempted to process HUD render call without texture view."),
+    };
+
+    let depth_buffer = match self.depth_buffer.as_ref() {
+      Some(buffer) => buffer,
+      None => panic!("RenderEngine: Attempted to process HUD render call without depth buffer."),
+    };
+
+    let font_texture = match self.textures.get(&self.font_texture_id) {
+      Some(texture) => texture,
+      None => panic!("RenderEngine: The font atlas is missing."),
+    };
+
+    let msaa_buffer = match upscaled {
+      true => self.hud_msaa_buffer.as_ref(),
+      false => self.msaa_buffer.as_ref(),
+    };
+    let (color_view, resolve_target) = Self::get_color_target(texture_view, msaa_buffer);
+
+    let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      // The label of this render pass.
+      label: Some("minetest_hud_render_pass"),
+
+      color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+        view: color_view,
+        resolve_target,
+        ops: wgpu::Operations {
+          load: wgpu::LoadOp::Load,
+          store: wgpu::StoreOp::Store,
+        },
+      })],
+
+      // The world's depth means nothing in screen space, wipe it.
+      depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+        view: depth_buffer.get_view(),
+        depth_ops: Some(wgpu::Operations {
+          load: wgpu::LoadOp::Clear(1.0),
+          store: wgpu::StoreOp::Store,
+        }),
+        stencil_ops: None,
+      }),
+      occlusion_query_set: None,
+      timestamp_writes: None,
+    });
+
+    render_pass.set_pipeline(self.render_pipelines.get(font_texture.get_alpha_mode()));
+
+    render_pass.set_bind_group(0, font_texture.get_wgpu_diffuse_bind_group(), &[]);
+
+    // The orthographic projection instead of the Camera.
+    render_pass.set_bind_group(1, self.hud_projection.get_bind_group(), &[]);
+
+    render_pass.set_bind_group(2, self.color_uniform.get_bind_group(), &[]);
+
+    let blank_data = InstanceMatrixRGBA::get_blank_data();
+    self.instance_buffer = Some(self.device.create_buffer_init(
+      &wgpu::util::BufferInitDescriptor {
+        label: Some("instance_buffer"),
+        contents: bytemuck::cast_slice(&blank_data),
+        usage: wgpu::BufferUsages::VERTEX,
+      },
+    ));
+
+    self.instance_trigger.trigger_off(&self.queue);
 
-              render_pass.set_vertex_buffer(0, mesh.get_wgpu_vertex_buffer().slice(..));
+    // The glyphs are already in screen pixels.
+    self.mesh_trs_uniform.set_translation(&Vec3A::ZERO);
+    self.mesh_trs_uniform.set_rotation(&Vec3A::ZERO);
+    self.mesh_trs_uniform.set_scale(&Vec3A::ONE);
+    self
+      .mesh_trs_uniform
+      .build_mesh_projection_matrix(&self.device, &self.queue);
 
-              let instance_buffer = match self.instance_buffer.as_ref() {
-                Some(buffer) => buffer,
-                None => panic!("RenderEngine: Attempted to render Model with no instance buffer."),
-              };
+    render_pass.set_vertex_buffer(0, text_mesh.get_wgpu_vertex_buffer().slice(..));
 
-              render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+    let instance_buffer = match self.instance_buffer.as_ref() {
+      Some(buffer) => buffer,
+      None => panic!("RenderEngine: Attempted to render HUD text with no instance buffer."),
+    };
+    render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
 
-              render_pass.set_index_buffer(
-                mesh.get_wgpu_index_buffer().slice(..),
-                wgpu::IndexFormat::Uint32,
-              );
+    render_pass.set_index_buffer(
+      text_mesh.get_wgpu_index_buffer().slice(..),
+      wgpu::IndexFormat::Uint32,
+    );
 
-              render_pass.draw_indexed(
-                0..mesh.get_number_of_indices(),
-                0,
-                0..(instance_data.len() as u32),
-              );
-            }
-            None => {
-              error!(
-                "render_engine: ID {} is not a stored Texture. [instanced]",
-                texture_id
-              );
-            }
-          }
-        }
-      }
-      None => {
-        error!(
-          "render_engine: ID {} is not a stored Model. [instanced]",
-          model_id
-        );
-      }
-    }
+    render_pass.draw_indexed(0..text_mesh.get_number_of_indices(), 0, 0..1);
   }
 
   ///
-  /// Completely wipes out the instanced Model render queue and returns the current data to you.
-  ///
-  fn take_model_instanced_data(&mut self) -> AHashMap<u64, InstancedModelRenderData> {
-    let mut temporary = AHashMap::new();
-    swap(&mut self.instanced_model_render_queue, &mut temporary);
-    temporary
-  }
-
+  /// Draw all the HUD text queued up this frame.
   ///
-  /// Process and submit all the instanced Model render calls.
+  /// This goes after the upscale, so text stays sharp at any render scale.
   ///
-  pub fn process_instanced_model_render_calls(&mut self) {
-    // ! This is an absolute brute force method. Perhaps there's a more elegant way?
-    let instanced_key_value_set = self.take_model_instanced_data();
+  fn process_hud_render_calls(&mut self, upscaled: bool) {
+    let hud_text_calls = take(&mut self.hud_text_queue);
+    if hud_text_calls.is_empty() {
+      return;
+    }
 
-    // Iterate through all the instanced data.
-    for (mesh_id, instance_data) in instanced_key_value_set {
+    self
+      .hud_projection
+      .build_projection_matrix(&self.size, &self.queue);
+
+    for hud_text_call in hud_text_calls {
       self.initialize_render();
-      self.process_instanced_model_render_call(
-        mesh_id,
-        instance_data.borrow_texture_names(),
-        instance_data.borrow_data(),
-      );
+      self.process_hud_render_call(hud_text_call, upscaled);
       self.submit_render();
     }
   }
@@ -1339,18 +1987,30 @@ impl RenderEngine {
   /// Destroys the old context.
   ///
   pub fn show_and_destroy_frame_buffer(&mut self) {
+    // Blended geometry always goes last.
+    self.process_blended_render_calls();
+
+    // Then a scaled down frame gets stretched over the window.
+    let upscaled = self.process_upscale();
+
+    // The HUD goes over all of it, at the window's resolution.
+    self.process_hud_render_calls(upscaled);
+
     // Next we simply swap the surface out into a local variable. We've just flushed the surface out into None.
 
     let mut final_output_option: Option<SurfaceTexture> = None;
 
     swap(&mut final_output_option, &mut self.output);
 
-    let final_output = match final_output_option {
-      Some(output) => output,
-      None => panic!("RenderEngine: Attempted to show a framebuffer that doesn't exist."),
-    };
-
-    final_output.present();
+    match final_output_option {
+      Some(output) => output.present(),
+      // Offscreen targets have nothing to present, the frame stays in the texture.
+      None => {
+        if !self.target.is_offscreen() || self.texture_view.is_none() {
+          panic!("RenderEngine: Attempted to show a framebuffer that doesn't exist.")
+        }
+      }
+    }
 
     // Destroy the depth buffer.
     self.depth_buffer = None;
@@ -1363,6 +2023,87 @@ impl RenderEngine {
     assert!(self.output.is_none());
     assert!(self.texture_view.is_none());
     assert!(self.depth_buffer.is_none());
+    assert!(self.surface_view.is_none());
+  }
+
+  ///
+  /// Upscale the scaled target into the surface, if dynamic resolution is scaled down.
+  ///
+  /// After this the surface is what gets drawn into, with a window sized
+  /// depth buffer. Returns if it upscaled.
+  ///
+  fn process_upscale(&mut self) -> bool {
+    let surface_view = match take(&mut self.surface_view) {
+      Some(surface_view) => surface_view,
+      None => return false,
+    };
+    let scaled_target = match self.scaled_target.as_ref() {
+      Some(scaled_target) => scaled_target,
+      None => panic!("RenderEngine: Attempted to upscale without a scaled target."),
+    };
+
+    let mut command_encoder = self
+      .device
+      .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("minetest_upscale_command_encoder"),
+      });
+    self
+      .upscale_blit
+      .blit(&mut command_encoder, scaled_target, &surface_view);
+    self.queue.submit(iter::once(command_encoder.finish()));
+
+    self.texture_view = Some(surface_view);
+    self.depth_buffer = Some(DepthBuffer::new(
+      &self.device,
+      &self.config,
+      self.sample_count,
+      "hud_depth_buffer",
+    ));
+    true
+  }
+
+  ///
+  /// Let go of the window, so it can be destroyed after this.
+  ///
+  /// The Surface was made from the SDL2 window, and it can't outlive it.
+  /// A frame that was never shown goes first, it holds the Surface's
+  /// texture. From then on the RenderEngine renders offscreen.
+  ///
+  pub fn release_window(&mut self) {
+    self.command_encoder = None;
+    self.surface_view = None;
+    self.texture_view = None;
+    self.depth_buffer = None;
+    self.output = None;
+
+    if !self.target.is_offscreen() {
+      self.target = RenderTarget::Offscreen(RenderTarget::create_offscreen_texture(
+        &self.device,
+        &self.config,
+      ));
+      println!("RenderEngine: released the window.");
+    }
+
+    // Whatever the GPU was still doing with the Surface is done after this.
+    self.device.poll(wgpu::Maintain::Wait);
+  }
+
+  ///
+  /// Get if this renders into an offscreen texture instead of a window.
+  ///
+  pub fn is_offscreen(&self) -> bool {
+    self.target.is_offscreen()
+  }
+
+  ///
+  /// Read the last shown frame back from an offscreen RenderEngine.
+  ///
+  /// Returns tightly packed RGBA8 rows, top to bottom.
+  ///
+  pub fn read_pixels(&self) -> Result<Vec<u8>, String> {
+    self
+      .target
+      .read_pixels(&self.device, &self.queue, &self.size)
   }
 
   ///
@@ -1377,6 +2118,31 @@ impl RenderEngine {
     new_id
   }
 
+  ///
+  /// Load an OBJ file into a stored Mesh.
+  ///
+  /// The texture it's material wants is reused if it's already loaded.
+  ///
+  /// Returns the Mesh ID, and the Texture ID if the material had one.
+  ///
+  pub fn load_obj_mesh(&mut self, path: &str) -> Result<(u64, Option<u64>), String> {
+    let (mesh, texture_path) = Mesh::from_obj(path, &mut self.device)?;
+
+    let texture_id = match texture_path {
+      Some(texture_path) => {
+        let texture_name = file_name_from_path(&texture_path)?;
+        match self.texture_name_to_id.get(texture_name) {
+          Some(texture_id) => Some(*texture_id),
+          None => Some(self.create_texture(&texture_path)),
+        }
+      }
+      None => None,
+    };
+
+    let name = mesh.get_name().clone();
+    Ok((self.store_mesh(&name, mesh), texture_id))
+  }
+
   ///
   /// Store a Model into the render engine for usage.
   ///
@@ -1395,7 +2161,164 @@ impl RenderEngine {
   /// Returns the Texture ID.
   ///
   pub fn create_texture(&mut self, path: &str) -> u64 {
-    self.store_texture(Texture::new(path, &self.device, &self.queue))
+    let texture_id = self.store_texture(Texture::new(
+      path,
+      self.get_texture_color_key(path),
+      &self.device,
+      &self.queue,
+      self.anisotropy,
+    ));
+    if let Some(texture_watcher) = &mut self.texture_watcher {
+      texture_watcher.watch(path, texture_id);
+    }
+    texture_id
+  }
+
+  ///
+  /// Start loading a texture from a path without stalling the frame.
+  ///
+  /// It gets decoded on the AssetLoader's threads. Call
+  /// poll_loaded_textures() every frame to upload and collect it.
+  ///
+  pub fn load_texture_async(&mut self, path: &str) -> AssetHandle {
+    let handle = self
+      .asset_loader
+      .load_texture(path, self.get_texture_color_key(path));
+    if let Some(texture_watcher) = &mut self.texture_watcher {
+      texture_watcher.watch_when_loaded(handle, path);
+    }
+    handle
+  }
+
+  ///
+  /// Upload every texture that finished decoding and store it.
+  ///
+  /// Returns the Texture ID of each one, or why it failed to load.
+  ///
+  pub fn poll_loaded_textures(&mut self) -> Vec<(AssetHandle, Result<u64, String>)> {
+    let mut loaded = vec![];
+    for (handle, result) in self.asset_loader.poll() {
+      let result = result.and_then(|decoded| {
+        Texture::from_decoded(&decoded, &self.device, &self.queue, self.anisotropy)
+      });
+      let result = result.map(|texture| self.store_texture(texture));
+      if let Some(texture_watcher) = &mut self.texture_watcher {
+        match &result {
+          Ok(texture_id) => texture_watcher.watch_loaded(handle, *texture_id),
+          Err(_) => texture_watcher.forget_pending(handle),
+        }
+      }
+      loaded.push((handle, result));
+    }
+    loaded
+  }
+
+  ///
+  /// Reload every texture whose file changed, if texture_hot_reload is on.
+  ///
+  /// The new Texture takes the old one's ID, so nothing that points at it
+  /// has to change. It keeps the old alpha mode, and gets the current color key.
+  /// A file that fails to decode leaves the old texture alone.
+  ///
+  /// New files next to the loaded textures get loaded too, unless a texture
+  /// already has their name.
+  ///
+  /// This swaps bind groups, so it only runs between frames, see update().
+  ///
+  fn reload_changed_textures(&mut self) {
+    let changes = match &mut self.texture_watcher {
+      Some(texture_watcher) => texture_watcher.poll(),
+      None => return,
+    };
+
+    for path in changes.added {
+      let is_known = match file_name_from_path(&path) {
+        Ok(name) => self.texture_name_to_id.contains_key(name),
+        Err(_) => true,
+      };
+      if !is_known {
+        println!("RenderEngine: picked up new texture [{}].", path);
+        self.create_texture(&path);
+      }
+    }
+
+    let max_dimension = self.device.limits().max_texture_dimension_2d;
+    for (path, texture_id) in changes.changed {
+      let alpha_mode = match self.textures.get(&texture_id) {
+        Some(old_texture) => old_texture.get_alpha_mode(),
+        None => continue,
+      };
+
+      let color_key = self.get_texture_color_key(&path);
+      let result = Texture::decode_file(&path, max_dimension, color_key).and_then(|decoded| {
+        Texture::from_decoded(&decoded, &self.device, &self.queue, self.anisotropy)
+      });
+      let mut texture = match result {
+        Ok(texture) => texture,
+        Err(e) => {
+          error!("RenderEngine: failed to reload [{}]. {}", path, e);
+          continue;
+        }
+      };
+      // Unless the color key just cut holes into an Opaque one.
+      if alpha_mode != AlphaMode::Opaque {
+        texture.set_alpha_mode(alpha_mode);
+      }
+
+      println!("RenderEngine: reloaded [{}].", path);
+      self.textures.insert(texture_id, texture);
+    }
+  }
+
+  ///
+  /// Get how many of the async textures are done, for a loading screen.
+  ///
+  pub fn get_loading_progress(&self) -> LoadingProgress {
+    self.asset_loader.get_progress()
+  }
+
+  ///
+  /// Set how a Texture's alpha channel is used.
+  ///
+  pub fn set_texture_alpha_mode(&mut self, texture_id: u64, alpha_mode: AlphaMode) {
+    match self.textures.get_mut(&texture_id) {
+      Some(texture) => texture.set_alpha_mode(alpha_mode),
+      None => error!(
+        "RenderEngine: Tried to set alpha mode of non-existent texture ID [{}].",
+        texture_id
+      ),
+    }
+  }
+
+  ///
+  /// Treat a color as fully transparent in a texture, for textures from
+  /// before alpha channels. (magenta is the usual one) None turns it back off.
+  ///
+  /// The pixels are keyed once while loading and not kept around after,
+  /// so this goes by name and has to be set before the texture loads.
+  /// With texture_hot_reload on, the next reload picks a change up.
+  ///
+  pub fn set_texture_color_key(&mut self, texture_name: &str, color_key: Option<[u8; 3]>) {
+    match color_key {
+      Some(color_key) => self
+        .texture_color_keys
+        .insert(texture_name.to_string(), color_key),
+      None => self.texture_color_keys.remove(texture_name),
+    };
+    if self.texture_name_to_id.contains_key(texture_name) {
+      println!(
+        "RenderEngine: [{}] is already loaded, the color key applies from the next load.",
+        texture_name
+      );
+    }
+  }
+
+  ///
+  /// Get the color key for the texture at a path, see set_texture_color_key.
+  ///
+  fn get_texture_color_key(&self, path: &str) -> Option<[u8; 3]> {
+    let name = Path::new(path).file_name()?.to_str()?;
+    self.texture_color_keys.get(name).copied()
   }
 
   ///
@@ -1485,6 +2408,26 @@ impl RenderEngine {
     ))
   }
 
+  ///
+  /// Draw text on the HUD, over the world.
+  ///
+  /// x and y are in window pixels from the top left. At scale 1.0 a
+  /// glyph is GLYPH_SIZE pixels. ASCII only.
+  ///
+  /// Returns how many glyph quads got queued.
+  ///
+  pub fn draw_text(&mut self, text: &str, x: f32, y: f32, scale: f32) -> usize {
+    let quads = layout_text(text, x, y, scale);
+    let quad_count = quads.len();
+
+    // All whitespace, there's nothing to draw.
+    if quad_count > 0 {
+      self.hud_text_queue.push(HudTextCall::new(quads));
+    }
+
+    quad_count
+  }
+
   ///
   /// Push one instance call into the Mesh instance queue.
   ///
@@ -1593,7 +2536,288 @@ impl RenderEngine {
   ///
   pub fn update(&mut self, window_handler: &WindowHandler, delta: f64) {
     self.update_size(window_handler.get_size());
+    self.report_frame_time(delta);
+    // Before the frame buffer is made, nothing is using the old bind groups yet.
+    self.reload_changed_textures();
     // self.trollface_rave(delta);
     // self.test_implementation(window_handler);
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::f32::consts::FRAC_PI_2;
+
+  use glam::{UVec2, Vec3A};
+
+  use crate::game::{
+    client::render_engine::{
+      mesh::{Mesh, Vertex},
+      render_init_error::RenderInitError,
+      texture::Texture,
+      RenderEngine,
+    },
+    game_config::GameConfig,
+  };
+
+  ///
+  /// How far off a channel can be before the pixel counts as different.
+  ///
+  const CHANNEL_TOLERANCE: u8 = 8;
+
+  ///
+  /// How many pixels can be different before the frame doesn't match. [0.0 - 1.0]
+  ///
+  /// Leaves room for rasterization differences on the edges between drivers.
+  ///
+  const PIXEL_TOLERANCE: f64 = 0.01;
+
+  ///
+  /// Compare RGBA8 pixels against a golden image on disk.
+  ///
+  /// Returns the share of pixels that are different.
+  ///
+  fn compare_to_golden_image(pixels: &[u8], size: UVec2, path: &str) -> f64 {
+    let golden = match image::open(path) {
+      Ok(golden) => golden.to_rgba8(),
+      Err(e) => panic!("Unit test is broken. Failed to open [{}]. {}", path, e),
+    };
+    assert_eq!(golden.dimensions(), (size.x, size.y));
+
+    let different_pixels = pixels
+      .chunks(4)
+      .zip(golden.as_raw().chunks(4))
+      .filter(|(pixel, golden_pixel)| {
+        pixel
+          .iter()
+          .zip(golden_pixel.iter())
+          .any(|(channel, golden_channel)| channel.abs_diff(*golden_channel) > CHANNEL_TOLERANCE)
+      })
+      .count();
+
+    different_pixels as f64 / (size.x * size.y) as f64
+  }
+
+  ///
+  /// Draw the red golden triangle, and read the frame back.
+  ///
+  fn render_golden_triangle(render_engine: &mut RenderEngine) -> Vec<u8> {
+    // 90 degrees with the camera 2 units away maps the triangle to half of the frame.
+    render_engine.get_camera().set_fov(FRAC_PI_2);
+
+    let mut triangle = Mesh::new("golden_triangle");
+    triangle.push_vertex_vec(&mut vec![
+      Vertex {
+        position: [-1.0, -1.0, 0.0],
+        texture_coordinates: [0.0, 1.0],
+        color: [1.0, 0.0, 0.0],
+      },
+      Vertex {
+        position: [1.0, -1.0, 0.0],
+        texture_coordinates: [1.0, 1.0],
+        color: [1.0, 0.0, 0.0],
+      },
+      Vertex {
+        position: [0.0, 1.0, 0.0],
+        texture_coordinates: [0.5, 0.0],
+        color: [1.0, 0.0, 0.0],
+      },
+    ]);
+    triangle.push_index_vec(&mut vec![0, 1, 2]);
+    triangle.generate_wgpu_buffers(&mut render_engine.device);
+    let mesh_id = render_engine.store_mesh("golden_triangle", triangle);
+
+    let red = match Texture::from_rgba(
+      "red",
+      UVec2::new(1, 1),
+      &[255, 0, 0, 255],
+      &render_engine.device,
+      &render_engine.queue,
+      render_engine.anisotropy,
+    ) {
+      Ok(red) => red,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let texture_id = render_engine.store_texture(red);
+
+    render_engine.update_camera_matrix();
+    render_engine.generate_frame_buffer();
+    render_engine.clear_buffers(true, true);
+    render_engine.render_mesh(mesh_id, texture_id, Vec3A::ZERO, Vec3A::ZERO, Vec3A::ONE);
+    render_engine.process_not_instanced_render_calls();
+    render_engine.show_and_destroy_frame_buffer();
+
+    match render_engine.read_pixels() {
+      Ok(pixels) => pixels,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+  }
+
+  ///
+  /// Start a headless RenderEngine for the tests that need a GPU.
+  ///
+  /// They're #[ignore]d, so this only runs when someone asked for it.
+  /// No GPU is a failure then, not a silent pass.
+  ///
+  fn start_headless(size: UVec2) -> RenderEngine {
+    match RenderEngine::new_headless(size, &GameConfig::new()) {
+      Ok(render_engine) => render_engine,
+      Err(e) => panic!("Unit test is broken. No GPU available. {}", e),
+    }
+  }
+
+  ///
+  /// Needs a GPU. Run with: cargo test render_engine -- --ignored
+  ///
+  #[test]
+  #[ignore]
+  fn test_render_engine_golden_triangle() {
+    println!("--- BEGIN RENDER ENGINE GOLDEN TRIANGLE TEST ---");
+    let size = UVec2::new(64, 64);
+
+    let mut render_engine = start_headless(size);
+
+    let pixels = render_golden_triangle(&mut render_engine);
+
+    let difference = compare_to_golden_image(&pixels, size, "./golden_images/colored_triangle.png");
+    assert!(
+      difference <= PIXEL_TOLERANCE,
+      "[{:.2}]% of the frame does not match the golden image.",
+      difference * 100.0
+    );
+  }
+
+  ///
+  /// Needs a GPU. Run with: cargo test render_engine -- --ignored
+  ///
+  #[test]
+  #[ignore]
+  fn test_render_engine_release_window() {
+    println!("--- BEGIN RENDER ENGINE RELEASE WINDOW TEST ---");
+    let size = UVec2::new(64, 64);
+    let mut render_engine = start_headless(size);
+
+    // Mid frame, like a window closing during a draw.
+    render_engine.update_camera_matrix();
+    render_engine.generate_frame_buffer();
+    render_engine.clear_buffers(true, true);
+    render_engine.release_window();
+    render_engine.release_window();
+    assert!(render_engine.is_offscreen());
+
+    // Nothing of that frame is left over, the next one starts clean.
+    let pixels = render_golden_triangle(&mut render_engine);
+    let difference = compare_to_golden_image(&pixels, size, "./golden_images/colored_triangle.png");
+    assert!(difference <= PIXEL_TOLERANCE);
+  }
+
+  ///
+  /// Needs a GPU. Run with: cargo test render_engine -- --ignored
+  ///
+  #[test]
+  #[ignore]
+  fn test_render_engine_dynamic_resolution() {
+    println!("--- BEGIN RENDER ENGINE DYNAMIC RESOLUTION TEST ---");
+    let size = UVec2::new(64, 64);
+
+    let mut render_engine = start_headless(size);
+
+    // Off by default.
+    assert_eq!(render_engine.get_render_size(), size);
+
+    // Sustained slow frames shrink the world, down to the floor.
+    render_engine.set_dynamic_resolution(true, 0.5, 1.0);
+    for _ in 0..600 {
+      render_engine.report_frame_time(1.0 / 20.0);
+    }
+    assert_eq!(render_engine.get_render_scale(), 0.5);
+    assert_eq!(render_engine.get_render_size(), UVec2::new(32, 32));
+
+    // It gets upscaled to the whole frame. Only the blurred edges are different.
+    let pixels = render_golden_triangle(&mut render_engine);
+    assert_eq!(pixels.len(), (size.x * size.y * 4) as usize);
+    let difference = compare_to_golden_image(&pixels, size, "./golden_images/colored_triangle.png");
+    assert!(
+      difference <= 0.1,
+      "[{:.2}]% of the upscaled frame does not match the golden image.",
+      difference * 100.0
+    );
+
+    // Fast frames bring native resolution back.
+    for _ in 0..600 {
+      render_engine.report_frame_time(1.0 / 240.0);
+    }
+    assert_eq!(render_engine.get_render_scale(), 1.0);
+    assert_eq!(render_engine.get_render_size(), size);
+  }
+
+  ///
+  /// Needs a GPU. Run with: cargo test render_engine -- --ignored
+  ///
+  #[test]
+  #[ignore]
+  fn test_render_engine_draw_text() {
+    println!("--- BEGIN RENDER ENGINE DRAW TEXT TEST ---");
+    let size = UVec2::new(64, 64);
+
+    let mut render_engine = start_headless(size);
+
+    // One quad per glyph, none for the space.
+    assert_eq!(render_engine.draw_text("Hi there", 0.0, 0.0, 2.0), 7);
+    assert_eq!(render_engine.draw_text("   ", 0.0, 40.0, 1.0), 0);
+    assert_eq!(render_engine.hud_text_queue.len(), 1);
+
+    render_engine.update_camera_matrix();
+    render_engine.generate_frame_buffer();
+    render_engine.clear_buffers(true, true);
+    render_engine.show_and_destroy_frame_buffer();
+    assert!(render_engine.hud_text_queue.is_empty());
+
+    let pixels = match render_engine.read_pixels() {
+      Ok(pixels) => pixels,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+
+    // The glyphs are white, the clear color is dark gray.
+    let bright = |row: std::ops::Range<usize>| {
+      pixels[row.start * 64 * 4..row.end * 64 * 4]
+        .chunks(4)
+        .any(|pixel| pixel[0] > 200)
+    };
+    assert!(bright(0..32));
+    assert!(!bright(32..64));
+  }
+
+  #[test]
+  fn test_render_engine_no_adapter() {
+    println!("--- BEGIN RENDER ENGINE NO ADAPTER TEST ---");
+
+    // Pretend this machine has no working GPU at all.
+    let result = RenderEngine::try_backends::<()>(|backends| match backends {
+      wgpu::Backends::GL => Err("Driver crashed.".to_string()),
+      _ => Ok(None),
+    });
+
+    let attempts = match result {
+      Err(RenderInitError::NoAdapter(attempts)) => attempts,
+      _ => panic!("Unit test is broken. Expected no adapter."),
+    };
+    let tried: Vec<&str> = attempts
+      .iter()
+      .map(|(backend, _)| backend.as_str())
+      .collect();
+    assert_eq!(tried, vec!["Vulkan", "DX12", "Metal", "GL"]);
+    assert_eq!(attempts[3].1, "Driver crashed.");
+
+    let message = RenderInitError::NoAdapter(attempts).to_string();
+    assert!(message.contains("[GL] Driver crashed."));
+    assert!(message.contains("--server"));
+
+    // The first backend that works wins.
+    let found = RenderEngine::try_backends(|backends| match backends {
+      wgpu::Backends::VULKAN => Ok(None),
+      _ => Ok(Some(backends)),
+    });
+    assert_eq!(found, Ok(wgpu::Backends::DX12));
+  }
+}
@@ -0,0 +1,152 @@
+use super::depth_buffer::DepthBuffer;
+
+///
+/// Fragments with alpha under this get discarded in Cutout mode.
+///
+/// ! This must match ALPHA_CUTOUT_THRESHOLD in default_shader.wgsl !
+///
+pub const ALPHA_CUTOUT_THRESHOLD: f32 = 0.5;
+
+///
+/// How a Texture's alpha channel gets used.
+///
+/// Opaque: Alpha is ignored. Stone, dirt, etc.
+///
+/// Cutout: Fragments under ALPHA_CUTOUT_THRESHOLD are discarded, the rest
+/// are opaque. Leaves, grass, etc.
+///
+/// Blend: Standard src-alpha blending. These get drawn after everything
+/// else, back to front, and don't write depth. Glass, water, etc.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AlphaMode {
+  #[default]
+  Opaque,
+  Cutout,
+  Blend,
+}
+
+impl AlphaMode {
+  ///
+  /// Get if this needs to go through the blended pass.
+  ///
+  pub fn is_blended(&self) -> bool {
+    matches!(self, AlphaMode::Blend)
+  }
+
+  ///
+  /// Get the fragment shader entry point for this mode.
+  ///
+  pub fn get_fragment_entry_point(&self) -> &'static str {
+    match self {
+      AlphaMode::Cutout => "fs_cutout",
+      AlphaMode::Opaque | AlphaMode::Blend => "fs_main",
+    }
+  }
+
+  ///
+  /// Get the pipeline color target for this mode.
+  ///
+  pub fn get_color_target_state(&self, format: wgpu::TextureFormat) -> wgpu::ColorTargetState {
+    let blend = match self {
+      AlphaMode::Opaque | AlphaMode::Cutout => None,
+      AlphaMode::Blend => Some(wgpu::BlendState {
+        color: wgpu::BlendComponent {
+          src_factor: wgpu::BlendFactor::SrcAlpha,
+          dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+          operation: wgpu::BlendOperation::Add,
+        },
+        alpha: wgpu::BlendComponent::OVER,
+      }),
+    };
+
+    wgpu::ColorTargetState {
+      format,
+      blend,
+      write_mask: wgpu::ColorWrites::ALL,
+    }
+  }
+
+  ///
+  /// Get the pipeline depth state for this mode.
+  ///
+  /// Blended geometry still gets depth tested, but it can't write depth
+  /// or it would hide the blended geometry behind it.
+  ///
+  pub fn get_depth_stencil_state(&self) -> wgpu::DepthStencilState {
+    wgpu::DepthStencilState {
+      format: DepthBuffer::DEPTH_FORMAT,
+      depth_write_enabled: !self.is_blended(),
+      depth_compare: wgpu::CompareFunction::Less,
+      stencil: wgpu::StencilState::default(),
+      bias: wgpu::DepthBiasState::default(),
+    }
+  }
+}
+
+///
+/// One render pipeline per AlphaMode.
+///
+pub struct AlphaModePipelines {
+  opaque: wgpu::RenderPipeline,
+  cutout: wgpu::RenderPipeline,
+  blend: wgpu::RenderPipeline,
+}
+
+impl AlphaModePipelines {
+  pub fn new(mut create_pipeline: impl FnMut(AlphaMode) -> wgpu::RenderPipeline) -> Self {
+    AlphaModePipelines {
+      opaque: create_pipeline(AlphaMode::Opaque),
+      cutout: create_pipeline(AlphaMode::Cutout),
+      blend: create_pipeline(AlphaMode::Blend),
+    }
+  }
+
+  ///
+  /// Get the render pipeline for an AlphaMode.
+  ///
+  pub fn get(&self, alpha_mode: AlphaMode) -> &wgpu::RenderPipeline {
+    match alpha_mode {
+      AlphaMode::Opaque => &self.opaque,
+      AlphaMode::Cutout => &self.cutout,
+      AlphaMode::Blend => &self.blend,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::game::client::render_engine::alpha_mode::AlphaMode;
+
+  #[test]
+  fn test_alpha_mode_pipeline_state() {
+    println!("--- BEGIN ALPHA MODE PIPELINE STATE TEST ---");
+    let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    let opaque = AlphaMode::Opaque;
+    assert_eq!(opaque.get_color_target_state(format).blend, None);
+    assert!(opaque.get_depth_stencil_state().depth_write_enabled);
+    assert_eq!(opaque.get_fragment_entry_point(), "fs_main");
+
+    // Cutout is opaque, it just discards.
+    let cutout = AlphaMode::Cutout;
+    assert_eq!(cutout.get_color_target_state(format).blend, None);
+    assert!(cutout.get_depth_stencil_state().depth_write_enabled);
+    assert_eq!(cutout.get_fragment_entry_point(), "fs_cutout");
+
+    let blend = AlphaMode::Blend;
+    let blend_state = match blend.get_color_target_state(format).blend {
+      Some(blend_state) => blend_state,
+      None => panic!("Blend mode has no blend state."),
+    };
+    assert_eq!(blend_state.color.src_factor, wgpu::BlendFactor::SrcAlpha);
+    assert_eq!(
+      blend_state.color.dst_factor,
+      wgpu::BlendFactor::OneMinusSrcAlpha
+    );
+    assert!(!blend.get_depth_stencil_state().depth_write_enabled);
+    assert!(blend.is_blended());
+
+    assert_eq!(AlphaMode::default(), AlphaMode::Opaque);
+  }
+}
@@ -0,0 +1,279 @@
+use std::{
+  sync::{
+    mpsc::{channel, Receiver, Sender},
+    Arc, Mutex,
+  },
+  thread::{self, JoinHandle},
+};
+
+use super::texture::{DecodedTexture, Texture};
+
+///
+/// The most decoding threads an AssetLoader starts.
+///
+/// Decoding is bound by memory bandwidth long before it runs out of cores.
+///
+pub const MAX_ASSET_LOADER_THREADS: usize = 4;
+
+///
+/// Points at an asset that was asked for, until it's done loading.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssetHandle(u64);
+
+///
+/// How far along loading is, for a loading screen.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadingProgress {
+  pub finished: usize,
+  pub total: usize,
+}
+
+impl LoadingProgress {
+  pub fn is_done(&self) -> bool {
+    self.finished >= self.total
+  }
+
+  ///
+  /// Get how much is done. [0.0 - 1.0]
+  ///
+  pub fn get_fraction(&self) -> f32 {
+    match self.total {
+      0 => 1.0,
+      total => self.finished as f32 / total as f32,
+    }
+  }
+}
+
+///
+/// A texture waiting for a decoding thread.
+///
+struct DecodeJob {
+  handle: AssetHandle,
+  path: String,
+  color_key: Option<[u8; 3]>,
+}
+
+///
+/// Decodes textures on a small pool of threads.
+///
+/// The main thread queues paths up, then polls for the decoded pixels
+/// every frame and uploads them itself. wgpu uploads have to stay on
+/// the render thread, decoding doesn't.
+///
+pub struct AssetLoader {
+  // None once it's shutting down, that's what stops the workers.
+  job_sender: Option<Sender<DecodeJob>>,
+  result_receiver: Receiver<(AssetHandle, Result<DecodedTexture, String>)>,
+  workers: Vec<JoinHandle<()>>,
+
+  next_handle: u64,
+  progress: LoadingProgress,
+}
+
+impl AssetLoader {
+  ///
+  /// Start the decoding threads.
+  ///
+  /// Anything bigger than max_dimension gets downscaled, see Texture::decode_bytes.
+  ///
+  pub fn new(thread_count: usize, max_dimension: u32) -> Self {
+    let (job_sender, job_receiver) = channel::<DecodeJob>();
+    let (result_sender, result_receiver) = channel();
+    let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+    let mut workers = vec![];
+    for index in 0..thread_count.clamp(1, MAX_ASSET_LOADER_THREADS) {
+      let job_receiver = job_receiver.clone();
+      let result_sender = result_sender.clone();
+      let spawn_result = thread::Builder::new()
+        .name(format!("asset_loader_{}", index))
+        .spawn(move || Self::decode_jobs(job_receiver, result_sender, max_dimension));
+      match spawn_result {
+        Ok(worker) => workers.push(worker),
+        Err(e) => println!("AssetLoader: Failed to start decoding thread. {}", e),
+      }
+    }
+
+    AssetLoader {
+      job_sender: Some(job_sender),
+      result_receiver,
+      workers,
+
+      next_handle: 0,
+      progress: LoadingProgress {
+        finished: 0,
+        total: 0,
+      },
+    }
+  }
+
+  ///
+  /// Start as many threads as the machine has cores, up to MAX_ASSET_LOADER_THREADS.
+  ///
+  pub fn with_default_threads(max_dimension: u32) -> Self {
+    let thread_count = match thread::available_parallelism() {
+      Ok(cores) => cores.get(),
+      Err(_) => 1,
+    };
+    Self::new(thread_count, max_dimension)
+  }
+
+  ///
+  /// Decode whatever comes in until the AssetLoader drops.
+  ///
+  fn decode_jobs(
+    job_receiver: Arc<Mutex<Receiver<DecodeJob>>>,
+    result_sender: Sender<(AssetHandle, Result<DecodedTexture, String>)>,
+    max_dimension: u32,
+  ) {
+    loop {
+      // Only hold the lock while waiting, so the others can decode meanwhile.
+      let job = match job_receiver.lock() {
+        Ok(job_receiver) => job_receiver.recv(),
+        Err(_) => return,
+      };
+      let job = match job {
+        Ok(job) => job,
+        // The AssetLoader is gone.
+        Err(_) => return,
+      };
+
+      let result = Texture::decode_file(&job.path, max_dimension, job.color_key);
+      if result_sender.send((job.handle, result)).is_err() {
+        return;
+      }
+    }
+  }
+
+  ///
+  /// Queue a texture up for decoding. This never blocks.
+  ///
+  /// The color key gets applied on the decoding thread, see Texture::decode_bytes.
+  ///
+  pub fn load_texture(&mut self, path: &str, color_key: Option<[u8; 3]>) -> AssetHandle {
+    let handle = AssetHandle(self.next_handle);
+    self.next_handle += 1;
+    self.progress.total += 1;
+
+    let job = DecodeJob {
+      handle,
+      path: path.to_string(),
+      color_key,
+    };
+    let sent = match &self.job_sender {
+      Some(job_sender) => job_sender.send(job).is_ok(),
+      None => false,
+    };
+    if !sent {
+      // Nothing will ever pick it up, don't let the loading screen hang on it.
+      println!("AssetLoader: no decoding threads left for [{}].", path);
+      self.progress.finished += 1;
+    }
+
+    handle
+  }
+
+  ///
+  /// Collect every texture that got decoded since the last poll. This never blocks.
+  ///
+  /// Failed ones come back as an Err, they still count as finished.
+  ///
+  pub fn poll(&mut self) -> Vec<(AssetHandle, Result<DecodedTexture, String>)> {
+    let finished: Vec<(AssetHandle, Result<DecodedTexture, String>)> =
+      self.result_receiver.try_iter().collect();
+    self.progress.finished += finished.len();
+    finished
+  }
+
+  pub fn get_progress(&self) -> LoadingProgress {
+    self.progress
+  }
+}
+
+impl Drop for AssetLoader {
+  fn drop(&mut self) {
+    // Hanging up wakes the workers, they finish their current job and stop.
+    self.job_sender = None;
+    for worker in self.workers.drain(..) {
+      let _ = worker.join();
+    }
+    println!("AssetLoader dropped!");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs::{create_dir_all, remove_dir_all},
+    thread,
+    time::{Duration, Instant},
+  };
+
+  use image::{ImageFormat, Rgba, RgbaImage};
+
+  use crate::{file_utilities::test_dir, game::client::render_engine::asset_loader::AssetLoader};
+
+  #[test]
+  fn test_asset_loader_decodes_off_thread() {
+    println!("--- BEGIN ASSET LOADER DECODES OFF THREAD TEST ---");
+    let asset_dir = test_dir("asset_loader");
+    if let Err(e) = create_dir_all(&asset_dir) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let mut paths = vec![];
+    for index in 0..8u8 {
+      let path = format!("{}/texture_{}.png", asset_dir, index);
+      let image = RgbaImage::from_pixel(4, 2, Rgba([index, 0, 255, 255]));
+      if let Err(e) = image.save_with_format(&path, ImageFormat::Png) {
+        panic!("Unit test is broken. {}", e);
+      }
+      paths.push(path);
+    }
+    let missing = format!("{}/missing.png", asset_dir);
+
+    let mut loader = AssetLoader::new(2, 8192);
+    let handles: Vec<_> = paths
+      .iter()
+      .map(|path| loader.load_texture(path, None))
+      .collect();
+    let missing_handle = loader.load_texture(&missing, None);
+    assert_eq!(loader.get_progress().total, 9);
+
+    // Collect everything on this thread, like a frame loop would.
+    let mut decoded = vec![];
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !loader.get_progress().is_done() {
+      assert!(Instant::now() < deadline, "Decoding never finished.");
+      decoded.extend(loader.poll());
+      thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(decoded.len(), 9);
+    assert_eq!(loader.get_progress().get_fraction(), 1.0);
+
+    for (handle, result) in decoded {
+      if handle == missing_handle {
+        assert!(result.is_err());
+        continue;
+      }
+      let texture = match result {
+        Ok(texture) => texture,
+        Err(e) => panic!("Failed to decode. {}", e),
+      };
+      let index = match handles.iter().position(|other| *other == handle) {
+        Some(index) => index,
+        None => panic!("Got back a handle that was never handed out."),
+      };
+      assert_eq!(texture.name, format!("texture_{}.png", index));
+      assert_eq!(texture.dimensions.x, 4);
+      assert_eq!(texture.dimensions.y, 2);
+      assert_eq!(&texture.diffuse_rgba[0..4], &[index as u8, 0, 255, 255]);
+      assert_ne!(texture.decoded_on, thread::current().id());
+    }
+
+    drop(loader);
+    let _ = remove_dir_all(&asset_dir);
+  }
+}
@@ -1,17 +1,129 @@
-use glam::{Mat4, Vec3, Vec3A};
+use std::f32::consts::{PI, TAU};
+
+use glam::{Mat4, UVec2, Vec3, Vec3A};
 
 use wgpu::util::DeviceExt;
 
-use crate::game::client::window_handler::WindowHandler;
+use crate::game::game_config::GameConfig;
 
 use super::trs_projection_data::TRSProjectionData;
 
+///
+/// The highest camera_smoothing allowed. At 1.0 the Camera would never move.
+///
+pub const MAX_CAMERA_SMOOTHING: f32 = 0.99;
+
+///
+/// The aspect ratios a Camera can have, anything past these is squished to fit.
+///
+/// Far wider than a triple monitor setup, far taller than a phone.
+///
+pub const MIN_ASPECT_RATIO: f32 = 0.1;
+pub const MAX_ASPECT_RATIO: f32 = 10.0;
+
+///
+/// What a Camera starts at when the window is too broken to tell.
+///
+const DEFAULT_ASPECT_RATIO: f32 = 16.0 / 9.0;
+
+///
+/// Get a usable aspect ratio out of whatever the window says.
+///
+/// 0, negative, and non finite come from minimized or mid resize windows,
+/// those keep the last good one. The rest is clamped to
+/// [MIN_ASPECT_RATIO - MAX_ASPECT_RATIO].
+///
+pub fn sanitize_aspect_ratio(aspect_ratio: f32, last_good: f32) -> f32 {
+  if !aspect_ratio.is_finite() || aspect_ratio <= 0.0 {
+    return last_good;
+  }
+  aspect_ratio.clamp(MIN_ASPECT_RATIO, MAX_ASPECT_RATIO)
+}
+
+///
+/// camera_smoothing is how much of the distance is left after a frame at this rate.
+///
+/// That keeps the feel the same at any framerate.
+///
+const SMOOTHING_REFERENCE_FPS: f64 = 60.0;
+
+///
+/// Where a Camera is and which way it's facing.
+///
+/// The view projection matrix is built from the eye and the rotation.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CameraTransform {
+  pub eye: Vec3A,
+  pub rotation: Vec3A,
+}
+
+impl CameraTransform {
+  ///
+  /// Get the view projection matrix for this transform.
+  ///
+  pub fn get_view_projection_matrix(&self, fov_y: f32, aspect_ratio: f32, z_near: f32) -> Mat4 {
+    let rotation = Mat4::from_euler(
+      glam::EulerRot::XYZ,
+      self.rotation.x,
+      self.rotation.y,
+      self.rotation.z,
+    );
+
+    let translation = Mat4::from_translation(Vec3::from(self.eye));
+
+    let projection = Mat4::perspective_infinite_rh(fov_y, aspect_ratio, z_near);
+
+    projection * rotation * translation
+  }
+
+  ///
+  /// Move this transform part of the way to the goal.
+  ///
+  /// Exponential smoothing, damping is how much of the way is left after
+  /// a 60 FPS frame. [0.0 - MAX_CAMERA_SMOOTHING] 0.0 jumps straight there.
+  ///
+  /// The rotation turns the short way around. Looking from just under PI
+  /// to just over -PI is a small turn, not a spin all the way around.
+  ///
+  pub fn approach(&self, goal: &CameraTransform, damping: f32, delta: f64) -> CameraTransform {
+    if damping <= 0.0 {
+      return *goal;
+    }
+
+    let remaining = damping
+      .min(MAX_CAMERA_SMOOTHING)
+      .powf((delta * SMOOTHING_REFERENCE_FPS) as f32);
+    let blend = 1.0 - remaining;
+
+    CameraTransform {
+      eye: self.eye.lerp(goal.eye, blend),
+      rotation: self.rotation + get_shortest_turn(self.rotation, goal.rotation) * blend,
+    }
+  }
+}
+
+///
+/// Get the smallest turn from one rotation to another, per axis. [-PI - PI radians]
+///
+fn get_shortest_turn(from: Vec3A, to: Vec3A) -> Vec3A {
+  let difference = to - from;
+  Vec3A::new(
+    (difference.x + PI).rem_euclid(TAU) - PI,
+    (difference.y + PI).rem_euclid(TAU) - PI,
+    (difference.z + PI).rem_euclid(TAU) - PI,
+  )
+}
+
 pub struct Camera {
-  eye: Vec3A,
-  target: Vec3A,
-  rotation: Vec3A,
-  up: Vec3A,
+  // Where the Camera actually is.
+  transform: CameraTransform,
+  // Where the setters put it, the transform catches up in update().
+  goal: CameraTransform,
+  // See CameraTransform::approach.
+  damping: f32,
   aspect_ratio: f32,
+  // [radians]
   fov_y: f32,
   z_near: f32,
   z_far: f32,
@@ -25,11 +137,14 @@ pub struct Camera {
 }
 
 impl Camera {
+  ///
+  /// Create a Camera. fov_y is in radians.
+  ///
   pub fn new(
     position: Vec3A,
     fov_y: f32,
     device: &wgpu::Device,
-    window_handler: &WindowHandler,
+    size: &UVec2,
     mesh_buffer: &wgpu::Buffer,
     instance_trigger_buffer: &wgpu::Buffer,
   ) -> Self {
@@ -63,14 +178,18 @@ impl Camera {
       label: Some("camera_bind_group"),
     });
 
+    let transform = CameraTransform {
+      eye: position,
+      ..Default::default()
+    };
+
     // Now you have a new camera.
     Camera {
-      eye: position,
-      target: Vec3A::new(0.0, 0.0, 0.0),
-      rotation: Vec3A::new(0.0, 0.0, 0.0),
-      up: glam::Vec3A::Y,
-      aspect_ratio: window_handler.get_width() as f32 / window_handler.get_height() as f32,
-      fov_y: 45.0,
+      transform,
+      goal: transform,
+      damping: 0.0,
+      aspect_ratio: sanitize_aspect_ratio(size.x as f32 / size.y as f32, DEFAULT_ASPECT_RATIO),
+      fov_y,
       z_near: 0.1,
       z_far: 100.0,
 
@@ -84,38 +203,133 @@ impl Camera {
   }
 
   ///
-  /// Set the FOV of the Camera.
+  /// Set the vertical FOV of the Camera. [radians]
   ///
   pub fn set_fov(&mut self, new_fov: f32) {
     self.fov_y = new_fov;
   }
 
+  ///
+  /// Set the aspect ratio, width / height.
+  ///
+  /// Broken ones are ignored, see sanitize_aspect_ratio.
+  ///
+  pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+    self.aspect_ratio = sanitize_aspect_ratio(aspect_ratio, self.aspect_ratio);
+  }
+
+  pub fn get_aspect_ratio(&self) -> f32 {
+    self.aspect_ratio
+  }
+
+  ///
+  /// Set how smoothly the Camera follows it's setters, see CameraTransform::approach.
+  ///
+  /// 0.0 is instant.
+  ///
+  pub fn set_damping(&mut self, damping: f32) {
+    self.damping = damping.clamp(0.0, MAX_CAMERA_SMOOTHING);
+    if self.damping == 0.0 {
+      self.transform = self.goal;
+    }
+  }
+
+  pub fn get_damping(&self) -> f32 {
+    self.damping
+  }
+
+  ///
+  /// Get the camera_smoothing from minetest.conf.
+  ///
+  pub fn get_requested_damping(config: &GameConfig) -> f32 {
+    config
+      .get_parsed("camera_smoothing", 0.0f32)
+      .clamp(0.0, MAX_CAMERA_SMOOTHING)
+  }
+
+  ///
+  /// Move the Camera toward where the setters put it.
+  ///
+  /// Call this once a frame, before build_view_projection_matrix().
+  ///
+  pub fn update(&mut self, delta: f64) {
+    self.transform = self.transform.approach(&self.goal, self.damping, delta);
+  }
+
+  ///
+  /// Changing the goal, without smoothing it's also where the Camera is.
+  ///
+  fn set_goal(&mut self, goal: CameraTransform) {
+    self.goal = goal;
+    if self.damping == 0.0 {
+      self.transform = goal;
+    }
+  }
+
   ///
   /// Set the position of the Camera.
   ///
   pub fn set_position(&mut self, new_position: &Vec3A) {
-    self.eye = new_position.to_owned();
+    self.set_eye(*new_position);
   }
 
   ///
   /// Get the Camera's position.
   ///
   pub fn get_position(&self) -> &Vec3A {
-    &self.eye
+    &self.transform.eye
   }
 
   ///
   /// Set the Camera's rotation.
   ///
   pub fn set_rotation(&mut self, new_rotation: &Vec3A) {
-    self.rotation = new_rotation.to_owned();
+    self.set_goal(CameraTransform {
+      rotation: *new_rotation,
+      ..self.goal
+    });
   }
 
   ///
   /// Get the Camera's rotation.
   ///
   pub fn get_rotation(&self) -> &Vec3A {
-    &self.rotation
+    &self.transform.rotation
+  }
+
+  ///
+  /// Get the Camera's eye, the same as its position.
+  ///
+  pub fn get_eye(&self) -> Vec3A {
+    self.transform.eye
+  }
+
+  ///
+  /// Set the Camera's eye, the same as its position.
+  ///
+  /// Like every setter here, it shows up on the next build_view_projection_matrix().
+  /// With damping, the Camera only gets there over a few update()s.
+  ///
+  pub fn set_eye(&mut self, eye: Vec3A) {
+    self.set_goal(CameraTransform { eye, ..self.goal });
+  }
+
+  ///
+  /// Get everything about where the Camera is, all at once.
+  ///
+  pub fn get_transform(&self) -> &CameraTransform {
+    &self.transform
+  }
+
+  pub fn set_transform(&mut self, transform: CameraTransform) {
+    self.set_goal(transform);
+  }
+
+  ///
+  /// Get where the Camera is headed, where it is with damping off.
+  ///
+  pub fn get_goal(&self) -> &CameraTransform {
+    &self.goal
   }
 
   ///
@@ -123,7 +337,7 @@ impl Camera {
   ///
   /// Passes back a new view projection matrix.
   ///
-  /// This also updates the aspect ratio, so WindowHandler is required.
+  /// This also updates the aspect ratio, so the render size is required.
   ///
   /// On top of this, it will also update the wgpu matrix uniform automatically.
   /// So the queue is required.
@@ -131,23 +345,15 @@ impl Camera {
   pub fn build_view_projection_matrix(
     &mut self,
     device: &wgpu::Device,
-    window_handler: &WindowHandler,
+    size: &UVec2,
     queue: &wgpu::Queue,
   ) {
-    self.aspect_ratio = window_handler.get_width() as f32 / window_handler.get_height() as f32;
+    self.set_aspect_ratio(size.x as f32 / size.y as f32);
 
-    let rotation = Mat4::from_euler(
-      glam::EulerRot::XYZ,
-      self.rotation.x,
-      self.rotation.y,
-      self.rotation.z,
-    );
-
-    let translation = Mat4::from_translation(Vec3::from(self.eye));
-
-    let projection = Mat4::perspective_infinite_rh(self.fov_y, self.aspect_ratio, self.z_near);
-
-    self.camera_uniform.projection = (projection * rotation * translation).to_cols_array_2d();
+    self.camera_uniform.projection = self
+      .transform
+      .get_view_projection_matrix(self.fov_y, self.aspect_ratio, self.z_near)
+      .to_cols_array_2d();
 
     // Automatically write the data into the queue.
     queue.write_buffer(self.get_buffer(), 0, self.get_wgpu_raw_matrix());
@@ -215,3 +421,115 @@ impl Camera {
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use glam::{Mat4, Vec3A, Vec4};
+
+  use crate::game::client::render_engine::camera::{
+    get_shortest_turn, sanitize_aspect_ratio, CameraTransform, MAX_ASPECT_RATIO, MIN_ASPECT_RATIO,
+  };
+
+  #[test]
+  fn test_camera_transform_matrix() {
+    println!("--- BEGIN CAMERA TRANSFORM MATRIX TEST ---");
+    let (fov_y, aspect_ratio, z_near) = (1.0, 16.0 / 9.0, 0.1);
+    let mut transform = CameraTransform::default();
+    let before = transform.get_view_projection_matrix(fov_y, aspect_ratio, z_near);
+
+    transform.eye = Vec3A::new(1.0, 2.0, 3.0);
+    let after = transform.get_view_projection_matrix(fov_y, aspect_ratio, z_near);
+    assert_ne!(before, after);
+
+    // With no rotation, the eye ends up in the translation column.
+    let projection = Mat4::perspective_infinite_rh(fov_y, aspect_ratio, z_near);
+    assert!(after
+      .w_axis
+      .abs_diff_eq(projection * Vec4::new(1.0, 2.0, 3.0, 1.0), 1e-6));
+
+    // Turning does move the view.
+    transform.rotation = Vec3A::new(0.0, 1.0, 0.0);
+    assert_ne!(
+      transform.get_view_projection_matrix(fov_y, aspect_ratio, z_near),
+      after
+    );
+  }
+
+  #[test]
+  fn test_camera_extreme_aspect_ratios() {
+    println!("--- BEGIN CAMERA EXTREME ASPECT RATIOS TEST ---");
+    let transform = CameraTransform::default();
+    let mut aspect_ratio = 16.0 / 9.0;
+
+    // A 0 pixel tall window, minimized, and mid resize garbage keep the last one.
+    for broken in [0.0, -1.5, f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+      aspect_ratio = sanitize_aspect_ratio(broken, aspect_ratio);
+      assert_eq!(aspect_ratio, 16.0 / 9.0);
+    }
+
+    // Silly but real ones are squished to fit.
+    aspect_ratio = sanitize_aspect_ratio(100_000.0, aspect_ratio);
+    assert_eq!(aspect_ratio, MAX_ASPECT_RATIO);
+    aspect_ratio = sanitize_aspect_ratio(1.0 / 1080.0, aspect_ratio);
+    assert_eq!(aspect_ratio, MIN_ASPECT_RATIO);
+    assert_eq!(sanitize_aspect_ratio(21.0 / 9.0, aspect_ratio), 21.0 / 9.0);
+
+    // Whatever happened, the projection stays usable.
+    for aspect_ratio in [MIN_ASPECT_RATIO, MAX_ASPECT_RATIO] {
+      let matrix = transform.get_view_projection_matrix(1.0, aspect_ratio, 0.1);
+      assert!(matrix.is_finite());
+    }
+  }
+
+  #[test]
+  fn test_camera_transform_damping() {
+    println!("--- BEGIN CAMERA TRANSFORM DAMPING TEST ---");
+    let start = CameraTransform::default();
+    let goal = CameraTransform {
+      eye: Vec3A::new(10.0, 0.0, 0.0),
+      rotation: Vec3A::new(0.0, 1.0, 0.0),
+    };
+    let frame = 1.0 / 60.0;
+
+    // No damping is the old behavior, straight there.
+    assert_eq!(start.approach(&goal, 0.0, frame), goal);
+
+    // With damping it gets closer, but not all the way.
+    let moved = start.approach(&goal, 0.5, frame);
+    assert!((moved.eye.x - 5.0).abs() < 1e-4);
+    assert!((moved.rotation.y - 0.5).abs() < 1e-4);
+    assert_ne!(moved, goal);
+
+    // Two half frames land in the same spot as one whole frame.
+    let halves = start
+      .approach(&goal, 0.5, frame / 2.0)
+      .approach(&goal, 0.5, frame / 2.0);
+    assert!(halves.eye.abs_diff_eq(moved.eye, 1e-4));
+
+    // It does get there eventually.
+    let mut settled = start;
+    for _ in 0..200 {
+      settled = settled.approach(&goal, 0.5, frame);
+    }
+    assert!(settled.eye.abs_diff_eq(goal.eye, 1e-4));
+
+    // Across the wrap it takes the short way, through PI instead of through 0.
+    let start = CameraTransform {
+      eye: Vec3A::ZERO,
+      rotation: Vec3A::new(0.0, 3.0, 0.0),
+    };
+    let goal = CameraTransform {
+      eye: Vec3A::ZERO,
+      rotation: Vec3A::new(0.0, -3.0, 0.0),
+    };
+    let moved = start.approach(&goal, 0.5, frame);
+    let short_way = std::f32::consts::TAU - 6.0;
+    assert!((moved.rotation.y - (3.0 + short_way / 2.0)).abs() < 1e-4);
+    let mut settled = start;
+    for _ in 0..200 {
+      settled = settled.approach(&goal, 0.5, frame);
+    }
+    // It ends up facing the same way, even if the number is a turn off.
+    assert!(get_shortest_turn(settled.rotation, goal.rotation).y.abs() < 1e-3);
+  }
+}
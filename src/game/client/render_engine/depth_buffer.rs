@@ -12,7 +12,12 @@ pub struct DepthBuffer {
 impl DepthBuffer {
   pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
-  pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+  pub fn new(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    sample_count: u32,
+    label: &str,
+  ) -> Self {
     let size = wgpu::Extent3d {
       width: config.width,
       height: config.height,
@@ -22,7 +27,8 @@ impl DepthBuffer {
       label: Some(label),
       size,
       mip_level_count: 1,
-      sample_count: 1,
+      // Must match the color target's sample count.
+      sample_count,
       dimension: wgpu::TextureDimension::D2,
       format: Self::DEPTH_FORMAT,
       usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
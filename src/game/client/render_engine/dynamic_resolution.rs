@@ -0,0 +1,262 @@
+use glam::UVec2;
+
+use crate::game::game_config::GameConfig;
+
+///
+/// The bounds the render scale can be set to. [0.0 - 1.0]
+///
+/// Below a quarter of the window it's just a blur.
+///
+pub const SCALE_LIMITS: (f32, f32) = (0.25, 1.0);
+
+///
+/// The bounds dynamic_resolution_min/max fall back to.
+///
+const DEFAULT_MIN_SCALE: f32 = 0.5;
+const DEFAULT_MAX_SCALE: f32 = 1.0;
+
+///
+/// How much each new frame time pulls the average. [0.0 - 1.0]
+///
+const FRAME_TIME_SMOOTHING: f64 = 0.1;
+
+///
+/// The scale only gets a chance to move once every this many frames.
+///
+/// Every change reallocates the render textures, and the average
+/// needs a moment to catch up with the last change.
+///
+const ADJUST_INTERVAL_FRAMES: u32 = 15;
+
+///
+/// How far the scale moves in one adjustment.
+///
+const SCALE_STEP: f32 = 0.05;
+
+///
+/// How far over the frame budget the average can be before the scale drops.
+/// 0.15 = 15% over.
+///
+/// Growing only needs the budget to be met within GROW_TOLERANCE. The gap
+/// between the two is what keeps the scale from bouncing back and forth.
+///
+const SHRINK_TOLERANCE: f64 = 0.15;
+const GROW_TOLERANCE: f64 = 0.05;
+
+///
+/// Scales the resolution the world is drawn at by how long frames take.
+///
+/// Over the frame budget, it shrinks. Under it, it grows back. The
+/// RenderEngine draws into a texture this size and upscales it to the window.
+///
+/// Off by default. Turn it on with dynamic_resolution in minetest.conf,
+/// bounded by dynamic_resolution_min and dynamic_resolution_max.
+///
+pub struct DynamicResolution {
+  enabled: bool,
+  min_scale: f32,
+  max_scale: f32,
+  scale: f32,
+
+  // The frame time goal, from fps_max. [seconds]
+  frame_budget: f64,
+  average_frame_time: Option<f64>,
+  frames_since_adjust: u32,
+}
+
+impl DynamicResolution {
+  pub fn new(target_fps: f64) -> Self {
+    DynamicResolution {
+      enabled: false,
+      min_scale: DEFAULT_MIN_SCALE,
+      max_scale: DEFAULT_MAX_SCALE,
+      scale: DEFAULT_MAX_SCALE,
+
+      frame_budget: 1.0 / target_fps.max(1.0),
+      average_frame_time: None,
+      frames_since_adjust: 0,
+    }
+  }
+
+  ///
+  /// Read dynamic_resolution, dynamic_resolution_min, dynamic_resolution_max,
+  /// and the fps_max budget out of minetest.conf.
+  ///
+  pub fn from_config(config: &GameConfig) -> Self {
+    let mut dynamic_resolution = DynamicResolution::new(config.get_parsed("fps_max", 60.0));
+    dynamic_resolution.set(
+      config.get_bool("dynamic_resolution", false),
+      config.get_parsed("dynamic_resolution_min", DEFAULT_MIN_SCALE),
+      config.get_parsed("dynamic_resolution_max", DEFAULT_MAX_SCALE),
+    );
+    dynamic_resolution
+  }
+
+  ///
+  /// Turn it on or off, and set the bounds of the scale.
+  ///
+  /// The bounds are clamped into SCALE_LIMITS, and swapped if backwards.
+  /// Turning it off goes straight back to native resolution.
+  ///
+  pub fn set(&mut self, enabled: bool, min_scale: f32, max_scale: f32) {
+    let clamp = |scale: f32| match scale.is_nan() {
+      true => SCALE_LIMITS.1,
+      false => scale.clamp(SCALE_LIMITS.0, SCALE_LIMITS.1),
+    };
+    let (min_scale, max_scale) = (clamp(min_scale), clamp(max_scale));
+
+    self.enabled = enabled;
+    self.min_scale = min_scale.min(max_scale);
+    self.max_scale = min_scale.max(max_scale);
+    self.scale = match enabled {
+      true => self.scale.clamp(self.min_scale, self.max_scale),
+      false => 1.0,
+    };
+    self.frames_since_adjust = 0;
+  }
+
+  ///
+  /// Get if dynamic resolution is on.
+  ///
+  pub fn is_enabled(&self) -> bool {
+    self.enabled
+  }
+
+  ///
+  /// Get the current render scale. [min_scale - max_scale]
+  ///
+  pub fn get_scale(&self) -> f32 {
+    self.scale
+  }
+
+  ///
+  /// Get the smoothed frame time. [seconds]
+  ///
+  pub fn get_average_frame_time(&self) -> Option<f64> {
+    self.average_frame_time
+  }
+
+  ///
+  /// Feed in how long the last frame took. [seconds]
+  ///
+  /// Returns if the scale changed.
+  ///
+  pub fn report_frame_time(&mut self, frame_time: f64) -> bool {
+    if !frame_time.is_finite() || frame_time < 0.0 {
+      return false;
+    }
+
+    let average_frame_time = match self.average_frame_time {
+      Some(average) => average + (frame_time - average) * FRAME_TIME_SMOOTHING,
+      None => frame_time,
+    };
+    self.average_frame_time = Some(average_frame_time);
+
+    if !self.enabled {
+      return false;
+    }
+
+    self.frames_since_adjust += 1;
+    if self.frames_since_adjust < ADJUST_INTERVAL_FRAMES {
+      return false;
+    }
+    self.frames_since_adjust = 0;
+
+    let old_scale = self.scale;
+    if average_frame_time > self.frame_budget * (1.0 + SHRINK_TOLERANCE) {
+      self.scale = (self.scale - SCALE_STEP).max(self.min_scale);
+    } else if average_frame_time <= self.frame_budget * (1.0 + GROW_TOLERANCE) {
+      self.scale = (self.scale + SCALE_STEP).min(self.max_scale);
+    }
+    self.scale != old_scale
+  }
+
+  ///
+  /// Get the size to draw the world at, for a window this size.
+  ///
+  /// Never smaller than 1x1.
+  ///
+  pub fn get_render_size(&self, window_size: &UVec2) -> UVec2 {
+    if !self.enabled {
+      return *window_size;
+    }
+    let scale = |length: u32| ((length as f32 * self.scale).round() as u32).clamp(1, length.max(1));
+    UVec2::new(scale(window_size.x), scale(window_size.y))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::UVec2;
+
+  use crate::game::client::render_engine::dynamic_resolution::DynamicResolution;
+
+  #[test]
+  fn test_dynamic_resolution_follows_frame_time() {
+    println!("--- BEGIN DYNAMIC RESOLUTION FOLLOWS FRAME TIME TEST ---");
+    let budget = 1.0 / 60.0;
+    let mut dynamic_resolution = DynamicResolution::new(60.0);
+    let window_size = UVec2::new(1920, 1080);
+
+    // Off, slow frames don't matter.
+    for _ in 0..600 {
+      dynamic_resolution.report_frame_time(budget * 2.0);
+    }
+    assert_eq!(dynamic_resolution.get_scale(), 1.0);
+    assert_eq!(
+      dynamic_resolution.get_render_size(&window_size),
+      window_size
+    );
+
+    dynamic_resolution.set(true, 0.5, 1.0);
+
+    // A single hitch doesn't drop anything.
+    dynamic_resolution.report_frame_time(budget * 10.0);
+    assert_eq!(dynamic_resolution.get_scale(), 1.0);
+
+    // Sustained slow frames walk it down, one step at a time, to the floor.
+    let mut last_scale = dynamic_resolution.get_scale();
+    for _ in 0..600 {
+      dynamic_resolution.report_frame_time(budget * 2.0);
+      let scale = dynamic_resolution.get_scale();
+      assert!(last_scale - scale <= 0.05 + f32::EPSILON);
+      last_scale = scale;
+    }
+    assert_eq!(dynamic_resolution.get_scale(), 0.5);
+    assert_eq!(
+      dynamic_resolution.get_render_size(&window_size),
+      UVec2::new(960, 540)
+    );
+
+    // Frames a little over budget sit in the gap, the scale holds.
+    for _ in 0..600 {
+      dynamic_resolution.report_frame_time(budget * 1.1);
+    }
+    assert_eq!(dynamic_resolution.get_scale(), 0.5);
+
+    // Fast frames bring it back up to native.
+    for _ in 0..600 {
+      dynamic_resolution.report_frame_time(budget * 0.5);
+    }
+    assert_eq!(dynamic_resolution.get_scale(), 1.0);
+    assert_eq!(
+      dynamic_resolution.get_render_size(&window_size),
+      window_size
+    );
+
+    // Backwards and out of range bounds get fixed up.
+    dynamic_resolution.set(true, 2.0, 0.1);
+    for _ in 0..600 {
+      dynamic_resolution.report_frame_time(budget * 2.0);
+    }
+    assert_eq!(dynamic_resolution.get_scale(), 0.25);
+    assert_eq!(
+      dynamic_resolution.get_render_size(&UVec2::new(2, 2)),
+      UVec2::new(1, 1)
+    );
+
+    // Off is native again.
+    dynamic_resolution.set(false, 0.5, 1.0);
+    assert_eq!(dynamic_resolution.get_scale(), 1.0);
+  }
+}
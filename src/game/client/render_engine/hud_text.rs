@@ -0,0 +1,275 @@
+use glam::{Mat4, UVec2, Vec2};
+use wgpu::util::DeviceExt;
+
+use super::{
+  camera::Camera,
+  mesh::{Mesh, Vertex},
+  trs_projection_data::TRSProjectionData,
+};
+
+///
+/// The bitmap font atlas. White glyphs on a transparent background.
+///
+pub const FONT_ATLAS_PATH: &str = "./prototype_textures/font.png";
+
+///
+/// The atlas is a grid of ASCII, laid out by character code.
+///
+/// Cell 0 is the top left, cell 127 is the bottom right.
+///
+const FONT_ATLAS_COLUMNS: u32 = 16;
+const FONT_ATLAS_ROWS: u32 = 8;
+
+///
+/// How big one glyph is on screen at scale 1.0. [pixels]
+///
+pub const GLYPH_SIZE: f32 = 16.0;
+
+///
+/// What gets drawn instead of characters the atlas doesn't have.
+///
+const REPLACEMENT_CHARACTER: u8 = b'?';
+
+///
+/// One glyph, in screen pixels. The origin is the top left of the window.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphQuad {
+  pub min: Vec2,
+  pub max: Vec2,
+  pub uv_min: Vec2,
+  pub uv_max: Vec2,
+}
+
+///
+/// Lay out a line (or lines) of text into glyph quads.
+///
+/// ASCII only, anything else comes out as a ?. Whitespace takes up room
+/// but has no quad.
+///
+pub fn layout_text(text: &str, x: f32, y: f32, scale: f32) -> Vec<GlyphQuad> {
+  let glyph_size = GLYPH_SIZE * scale;
+  let mut quads = vec![];
+  let mut cursor = Vec2::new(x, y);
+
+  for character in text.chars() {
+    match character {
+      '\n' => {
+        cursor.x = x;
+        cursor.y += glyph_size;
+        continue;
+      }
+      ' ' | '\t' => {
+        cursor.x += glyph_size;
+        continue;
+      }
+      _ => (),
+    }
+
+    let code = match character {
+      character if character.is_ascii_graphic() => character as u8,
+      _ => REPLACEMENT_CHARACTER,
+    };
+
+    let cell = UVec2::new(
+      code as u32 % FONT_ATLAS_COLUMNS,
+      code as u32 / FONT_ATLAS_COLUMNS,
+    );
+    let cell_size = Vec2::new(
+      1.0 / FONT_ATLAS_COLUMNS as f32,
+      1.0 / FONT_ATLAS_ROWS as f32,
+    );
+    let uv_min = cell.as_vec2() * cell_size;
+
+    quads.push(GlyphQuad {
+      min: cursor,
+      max: cursor + Vec2::splat(glyph_size),
+      uv_min,
+      uv_max: uv_min + cell_size,
+    });
+
+    cursor.x += glyph_size;
+  }
+
+  quads
+}
+
+///
+/// Turn glyph quads into a Mesh, two triangles each.
+///
+pub fn build_text_mesh(quads: &[GlyphQuad]) -> Mesh {
+  let mut vertices = Vec::with_capacity(quads.len() * 4);
+  let mut indices = Vec::with_capacity(quads.len() * 6);
+
+  for quad in quads {
+    let first = vertices.len() as u32;
+    vertices.push(Vertex::new_untinted(
+      [quad.min.x, quad.min.y, 0.0],
+      [quad.uv_min.x, quad.uv_min.y],
+    ));
+    vertices.push(Vertex::new_untinted(
+      [quad.min.x, quad.max.y, 0.0],
+      [quad.uv_min.x, quad.uv_max.y],
+    ));
+    vertices.push(Vertex::new_untinted(
+      [quad.max.x, quad.max.y, 0.0],
+      [quad.uv_max.x, quad.uv_max.y],
+    ));
+    vertices.push(Vertex::new_untinted(
+      [quad.max.x, quad.min.y, 0.0],
+      [quad.uv_max.x, quad.uv_min.y],
+    ));
+    indices.append(&mut vec![
+      first,
+      first + 1,
+      first + 2,
+      first,
+      first + 2,
+      first + 3,
+    ]);
+  }
+
+  let mut mesh = Mesh::new("hud_text");
+  mesh.push_vertex_vec(&mut vertices);
+  mesh.push_index_vec(&mut indices);
+  mesh
+}
+
+///
+/// A queued draw_text call.
+///
+pub struct HudTextCall {
+  quads: Vec<GlyphQuad>,
+}
+
+impl HudTextCall {
+  pub fn new(quads: Vec<GlyphQuad>) -> Self {
+    HudTextCall { quads }
+  }
+
+  ///
+  /// Get the glyph quads of this HudTextCall.
+  ///
+  pub fn get_quads(&self) -> &Vec<GlyphQuad> {
+    &self.quads
+  }
+}
+
+///
+/// The HUD's stand in for the Camera.
+///
+/// An orthographic projection in window pixels, y pointing down. It uses
+/// the Camera's bind group layout so it runs through the same shader.
+///
+pub struct HudProjection {
+  projection_uniform: TRSProjectionData,
+  projection_buffer: wgpu::Buffer,
+  projection_bind_group: wgpu::BindGroup,
+}
+
+impl HudProjection {
+  pub fn new(
+    device: &wgpu::Device,
+    mesh_buffer: &wgpu::Buffer,
+    instance_trigger_buffer: &wgpu::Buffer,
+  ) -> Self {
+    let projection_uniform = TRSProjectionData::new();
+
+    let projection_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("hud_projection_buffer"),
+      contents: bytemuck::cast_slice(&projection_uniform.projection),
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+
+    let projection_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      layout: &Camera::get_wgpu_bind_group_layout(device),
+      entries: &[
+        wgpu::BindGroupEntry {
+          binding: 0,
+          resource: projection_buffer.as_entire_binding(),
+        },
+        wgpu::BindGroupEntry {
+          binding: 1,
+          resource: mesh_buffer.as_entire_binding(),
+        },
+        wgpu::BindGroupEntry {
+          binding: 2,
+          resource: instance_trigger_buffer.as_entire_binding(),
+        },
+      ],
+      label: Some("hud_projection_bind_group"),
+    });
+
+    HudProjection {
+      projection_uniform,
+      projection_buffer,
+      projection_bind_group,
+    }
+  }
+
+  ///
+  /// Get the orthographic matrix for a window size.
+  ///
+  pub fn get_projection_matrix(size: &UVec2) -> Mat4 {
+    Mat4::orthographic_rh(0.0, size.x as f32, size.y as f32, 0.0, -1.0, 1.0)
+  }
+
+  ///
+  /// Rebuild the projection for the window size and write it into the queue.
+  ///
+  pub fn build_projection_matrix(&mut self, size: &UVec2, queue: &wgpu::Queue) {
+    self.projection_uniform.projection = Self::get_projection_matrix(size).to_cols_array_2d();
+    queue.write_buffer(
+      &self.projection_buffer,
+      0,
+      bytemuck::cast_slice(&self.projection_uniform.projection),
+    );
+  }
+
+  ///
+  /// Get the HudProjection's wgpu bind group for rendering.
+  ///
+  pub fn get_bind_group(&self) -> &wgpu::BindGroup {
+    &self.projection_bind_group
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::{UVec2, Vec2, Vec3};
+
+  use crate::game::client::render_engine::hud_text::{layout_text, HudProjection, GLYPH_SIZE};
+
+  #[test]
+  fn test_hud_text_layout() {
+    println!("--- BEGIN HUD TEXT LAYOUT TEST ---");
+
+    // Spaces take room, but don't get a quad.
+    let quads = layout_text("FPS: 60", 10.0, 20.0, 2.0);
+    assert_eq!(quads.len(), 6);
+    assert_eq!(quads[0].min, Vec2::new(10.0, 20.0));
+    assert_eq!(quads[0].max, Vec2::new(10.0, 20.0) + GLYPH_SIZE * 2.0);
+    assert_eq!(quads[4].min.x, 10.0 + GLYPH_SIZE * 2.0 * 5.0);
+
+    // 'A' is 65, row 4 column 1 of the atlas.
+    let quads = layout_text("A", 0.0, 0.0, 1.0);
+    assert_eq!(quads[0].uv_min, Vec2::new(1.0 / 16.0, 4.0 / 8.0));
+    assert_eq!(quads[0].uv_max, Vec2::new(2.0 / 16.0, 5.0 / 8.0));
+
+    // Newlines go back to the start, one line down.
+    let quads = layout_text("ab\ncd", 5.0, 0.0, 1.0);
+    assert_eq!(quads.len(), 4);
+    assert_eq!(quads[2].min, Vec2::new(5.0, GLYPH_SIZE));
+
+    // Non ASCII comes out as ?.
+    let quads = layout_text("é", 0.0, 0.0, 1.0);
+    assert_eq!(quads, layout_text("?", 0.0, 0.0, 1.0));
+
+    // The top left pixel is the top left corner of clip space.
+    let projection = HudProjection::get_projection_matrix(&UVec2::new(800, 600));
+    let top_left = projection.project_point3(Vec3::ZERO);
+    let bottom_right = projection.project_point3(Vec3::new(800.0, 600.0, 0.0));
+    assert!((top_left - Vec3::new(-1.0, 1.0, 0.5)).length() < 1e-6);
+    assert!((bottom_right - Vec3::new(1.0, -1.0, 0.5)).length() < 1e-6);
+  }
+}
@@ -1,6 +1,10 @@
 use std::mem::size_of;
 use wgpu::util::DeviceExt;
 
+use crate::file_utilities::file_name_from_path;
+
+use super::model_loader::obj_mesh::ObjMeshParser;
+
 ///
 /// The root sizes of the Vertex components.
 ///
@@ -21,6 +25,14 @@ const COLOR_COMPONENTS: usize = 3;
 ///
 /// Vertex is simply a data container, this is why everything is public.
 ///
+/// The color multiplies the sampled texel in the fragment shader, this is
+/// how biome tints and light levels get applied. White leaves the texture as is.
+///
+/// The WGSL VertexInput is expected to match this layout:
+/// @location(0) position: vec3<f32>
+/// @location(1) texture_coordinates: vec2<f32>
+/// @location(2) color: vec3<f32>
+///
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
@@ -30,6 +42,11 @@ pub struct Vertex {
 }
 
 impl Vertex {
+  ///
+  /// The color of untinted geometry.
+  ///
+  pub const DEFAULT_COLOR: [f32; COLOR_COMPONENTS] = [1.0, 1.0, 1.0];
+
   pub fn new(
     position: [f32; POSITION_COMPONENTS],
     texture_coordinates: [f32; TEXTURE_COORDINATE_COMPONENTS],
@@ -41,6 +58,16 @@ impl Vertex {
       color,
     }
   }
+
+  ///
+  /// Create a Vertex with the default (white) color.
+  ///
+  pub fn new_untinted(
+    position: [f32; POSITION_COMPONENTS],
+    texture_coordinates: [f32; TEXTURE_COORDINATE_COMPONENTS],
+  ) -> Self {
+    Vertex::new(position, texture_coordinates, Vertex::DEFAULT_COLOR)
+  }
 }
 
 ///
@@ -96,6 +123,24 @@ impl Mesh {
     }
   }
 
+  ///
+  /// Load a Mesh straight out of an OBJ file, buffers generated.
+  ///
+  /// Returns the Mesh and the path of the texture it's material wants, if any.
+  /// The RenderEngine resolves that into a Texture ID, see load_obj_mesh.
+  ///
+  pub fn from_obj(path: &str, device: &mut wgpu::Device) -> Result<(Mesh, Option<String>), String> {
+    let name = file_name_from_path(path)?;
+    let mut data = ObjMeshParser::load(path)?;
+
+    let mut mesh = Mesh::new(name);
+    mesh.push_vertex_vec(&mut data.vertices);
+    mesh.push_index_vec(&mut data.indices);
+    mesh.generate_wgpu_buffers(device);
+
+    Ok((mesh, data.texture_path))
+  }
+
   ///
   /// Get the Mesh's name.
   ///
@@ -245,13 +290,15 @@ impl Mesh {
         },
         // Texture coordinates.
         wgpu::VertexAttribute {
-          offset: size_of::<[f32; 3]>() as wgpu::BufferAddress,
+          offset: size_of::<[f32; POSITION_COMPONENTS]>() as wgpu::BufferAddress,
           shader_location: 1,
           format: wgpu::VertexFormat::Float32x2,
         },
         // Colors.
         wgpu::VertexAttribute {
-          offset: (size_of::<[f32; 3]>() + size_of::<[f32; 2]>()) as wgpu::BufferAddress,
+          offset: (size_of::<[f32; POSITION_COMPONENTS]>()
+            + size_of::<[f32; TEXTURE_COORDINATE_COMPONENTS]>())
+            as wgpu::BufferAddress,
           shader_location: 2,
           format: wgpu::VertexFormat::Float32x3,
         },
@@ -362,7 +409,7 @@ pub fn generate_mesh(
 
 #[cfg(test)]
 mod tests {
-  use crate::game::client::render_engine::mesh::generate_mesh;
+  use crate::game::client::render_engine::mesh::{generate_mesh, Mesh, Vertex};
 
   // Mesh does not test indices. This is basically untestable.
   // There can be variable number of indices per mesh.
@@ -370,6 +417,31 @@ mod tests {
   // Each test is one or two vertex positions.
   // They simply ensure that the required data will not cause issues.
 
+  #[test]
+  fn test_vertex_color_layout() {
+    println!("--- BEGIN VERTEX COLOR LAYOUT TEST ---");
+    let descriptor = Mesh::get_wgpu_descriptor();
+
+    // 3 position + 2 texture coordinate + 3 color floats.
+    assert_eq!(descriptor.array_stride, 32);
+
+    let offsets: Vec<(u64, u32)> = descriptor
+      .attributes
+      .iter()
+      .map(|attribute| (attribute.offset, attribute.shader_location))
+      .collect();
+    assert_eq!(offsets, vec![(0, 0), (12, 1), (20, 2)]);
+
+    // The color lands where the descriptor says it does.
+    let vertices = [
+      Vertex::new([0.0, 1.0, 2.0], [0.25, 0.75], [0.5, 0.6, 0.7]),
+      Vertex::new_untinted([3.0, 4.0, 5.0], [1.0, 0.0]),
+    ];
+    let raw: &[f32] = bytemuck::cast_slice(&vertices);
+    assert_eq!(&raw[5..8], &[0.5, 0.6, 0.7]);
+    assert_eq!(&raw[8 + 5..8 + 8], &Vertex::DEFAULT_COLOR);
+  }
+
   #[test]
   fn test_procedural_mesh_creation() {
     // Good Meshes.
@@ -1,5 +1,6 @@
 mod gltf_loader;
 mod obj_loader;
+pub mod obj_mesh;
 
 use crate::{
   file_utilities::{file_extension_from_path, file_name_from_path},
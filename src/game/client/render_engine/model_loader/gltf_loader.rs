@@ -49,7 +49,7 @@ impl GLTFLoader {
         let new_vertex = Vertex {
           position: vertex.position.into(),
           texture_coordinates: vertex.tex_coords.into(),
-          color: [1.0, 1.0, 1.0],
+          color: Vertex::DEFAULT_COLOR,
         };
 
         vertices.push(new_vertex);
@@ -76,7 +76,7 @@ impl ObjLoader {
             1.0 - model.mesh.texcoords[index * 2 + 1],
           ],
 
-          color: [1.0, 1.0, 1.0],
+          color: Vertex::DEFAULT_COLOR,
         };
 
         // ? note: meshes can also have normals from obj models.
@@ -0,0 +1,458 @@
+use std::path::Path;
+
+use ahash::AHashMap;
+use glam::Vec3;
+
+use crate::{
+  file_utilities::{file_exists, read_file_to_string},
+  game::client::render_engine::mesh::Vertex,
+};
+
+///
+/// The raw data of an OBJ file, flattened into one Mesh worth of data.
+///
+/// ! Vertex has no normal yet, the shader doesn't light anything.
+/// ! The normals are kept next to the vertices until it does.
+///
+#[derive(Debug, Clone)]
+pub struct ObjMeshData {
+  pub vertices: Vec<Vertex>,
+  // One per vertex.
+  pub normals: Vec<[f32; 3]>,
+  pub indices: Vec<u32>,
+  // The map_Kd of the first material, relative to the working directory.
+  pub texture_path: Option<String>,
+}
+
+///
+/// One corner of a face. (position, texture coordinates, normal)
+///
+/// These are the indices into the OBJ lists, 0 based.
+///
+type FaceCorner = (usize, Option<usize>, Option<usize>);
+
+///
+/// A small Wavefront OBJ parser.
+///
+/// The Model ObjLoader goes through tobj, which can't tell you where a
+/// file broke. This one reports the line, so modders can fix their models.
+///
+/// Supports v, vt, vn, f (any polygon, fan triangulated), mtllib and usemtl.
+/// Everything else (o, g, s, ...) is skipped.
+///
+pub struct ObjMeshParser {
+  file_name: String,
+  positions: Vec<Vec3>,
+  texture_coordinates: Vec<[f32; 2]>,
+  normals: Vec<Vec3>,
+
+  corner_to_index: AHashMap<FaceCorner, u32>,
+  corners: Vec<FaceCorner>,
+  indices: Vec<u32>,
+
+  material_library: Option<String>,
+  material: Option<String>,
+}
+
+impl ObjMeshParser {
+  ///
+  /// Load an OBJ file, and it's material library if it has one.
+  ///
+  pub fn load(path: &str) -> Result<ObjMeshData, String> {
+    let source = read_file_to_string(path)?;
+    let mut parser = ObjMeshParser::new(path);
+    parser.parse(&source)?;
+
+    // The texture comes out of the material library, next to the OBJ.
+    let directory = match Path::new(path).parent() {
+      Some(directory) => directory.to_path_buf(),
+      None => Path::new(".").to_path_buf(),
+    };
+    let texture_path = match &parser.material_library {
+      Some(material_library) => {
+        let material_library_path = directory.join(material_library);
+        let material_library_path = material_library_path.to_string_lossy();
+        if file_exists(&material_library_path) {
+          let raw_materials = read_file_to_string(&material_library_path)?;
+          Self::find_texture(&raw_materials, parser.material.as_deref())
+            .map(|texture| directory.join(texture).to_string_lossy().to_string())
+        } else {
+          println!(
+            "ObjMeshParser: [{}] wants [{}], but it does not exist. No texture.",
+            path, material_library_path
+          );
+          None
+        }
+      }
+      None => None,
+    };
+
+    let mut data = parser.finish()?;
+    data.texture_path = texture_path;
+    Ok(data)
+  }
+
+  pub fn new(file_name: &str) -> Self {
+    ObjMeshParser {
+      file_name: file_name.to_owned(),
+      positions: vec![],
+      texture_coordinates: vec![],
+      normals: vec![],
+
+      corner_to_index: AHashMap::new(),
+      corners: vec![],
+      indices: vec![],
+
+      material_library: None,
+      material: None,
+    }
+  }
+
+  ///
+  /// Build an error that says where it happened.
+  ///
+  fn error(&self, line_number: usize, line: &str, reason: &str) -> String {
+    format!(
+      "ObjMeshParser: [{}] line [{}]: {} `{}`",
+      self.file_name, line_number, reason, line
+    )
+  }
+
+  ///
+  /// Parse exactly N floats off of a line.
+  ///
+  fn parse_floats<const N: usize>(
+    &self,
+    line_number: usize,
+    line: &str,
+    arguments: &[&str],
+  ) -> Result<[f32; N], String> {
+    // Some exporters write an optional w, it gets ignored.
+    if arguments.len() < N {
+      return Err(self.error(
+        line_number,
+        line,
+        &format!("Expected [{}] numbers, got [{}].", N, arguments.len()),
+      ));
+    }
+    let mut floats = [0.0; N];
+    for (float, argument) in floats.iter_mut().zip(arguments) {
+      *float = match argument.parse() {
+        Ok(parsed) => parsed,
+        Err(_) => {
+          return Err(self.error(
+            line_number,
+            line,
+            &format!("[{}] is not a number.", argument),
+          ))
+        }
+      };
+    }
+    Ok(floats)
+  }
+
+  ///
+  /// Turn an OBJ index (1 based, negative is from the end) into a 0 based one.
+  ///
+  fn resolve_index(
+    &self,
+    line_number: usize,
+    line: &str,
+    raw_index: &str,
+    length: usize,
+  ) -> Result<usize, String> {
+    let index = match raw_index.parse::<i64>() {
+      Ok(index) => index,
+      Err(_) => {
+        return Err(self.error(
+          line_number,
+          line,
+          &format!("[{}] is not an index.", raw_index),
+        ))
+      }
+    };
+    let resolved = match index {
+      index if index > 0 => index - 1,
+      index if index < 0 => length as i64 + index,
+      _ => -1,
+    };
+    if resolved < 0 || resolved >= length as i64 {
+      return Err(self.error(
+        line_number,
+        line,
+        &format!(
+          "Index [{}] is out of bounds, there are [{}].",
+          raw_index, length
+        ),
+      ));
+    }
+    Ok(resolved as usize)
+  }
+
+  ///
+  /// Parse one v/vt/vn corner of a face.
+  ///
+  fn parse_corner(
+    &self,
+    line_number: usize,
+    line: &str,
+    raw_corner: &str,
+  ) -> Result<FaceCorner, String> {
+    let mut parts = raw_corner.split('/');
+    let position = match parts.next() {
+      Some(position) => self.resolve_index(line_number, line, position, self.positions.len())?,
+      None => return Err(self.error(line_number, line, "Empty face corner.")),
+    };
+    let texture_coordinates = match parts.next() {
+      Some("") | None => None,
+      Some(raw) => {
+        Some(self.resolve_index(line_number, line, raw, self.texture_coordinates.len())?)
+      }
+    };
+    let normal = match parts.next() {
+      Some("") | None => None,
+      Some(raw) => Some(self.resolve_index(line_number, line, raw, self.normals.len())?),
+    };
+    Ok((position, texture_coordinates, normal))
+  }
+
+  ///
+  /// Get the vertex index of a corner, sharing it if it's been seen before.
+  ///
+  fn get_corner_index(&mut self, corner: FaceCorner) -> u32 {
+    match self.corner_to_index.get(&corner) {
+      Some(index) => *index,
+      None => {
+        let index = self.corners.len() as u32;
+        self.corners.push(corner);
+        self.corner_to_index.insert(corner, index);
+        index
+      }
+    }
+  }
+
+  ///
+  /// Parse the text of an OBJ file.
+  ///
+  pub fn parse(&mut self, source: &str) -> Result<(), String> {
+    for (line_index, raw_line) in source.lines().enumerate() {
+      let line_number = line_index + 1;
+      let line = raw_line.trim();
+      let mut words = line.split_whitespace();
+      let keyword = match words.next() {
+        Some(keyword) => keyword,
+        None => continue,
+      };
+      let arguments: Vec<&str> = words.collect();
+
+      match keyword {
+        _ if keyword.starts_with('#') => (),
+        "v" => {
+          let position = self.parse_floats::<3>(line_number, line, &arguments)?;
+          self.positions.push(Vec3::from_array(position));
+        }
+        "vt" => {
+          // Only u is required.
+          let mut texture_coordinates = [0.0; 2];
+          let parsed = self.parse_floats::<1>(line_number, line, &arguments)?;
+          texture_coordinates[0] = parsed[0];
+          if arguments.len() > 1 {
+            texture_coordinates = self.parse_floats::<2>(line_number, line, &arguments)?;
+          }
+          self.texture_coordinates.push(texture_coordinates);
+        }
+        "vn" => {
+          let normal = self.parse_floats::<3>(line_number, line, &arguments)?;
+          self.normals.push(Vec3::from_array(normal));
+        }
+        "f" => {
+          if arguments.len() < 3 {
+            return Err(self.error(line_number, line, "A face needs at least 3 corners."));
+          }
+          let mut face = Vec::with_capacity(arguments.len());
+          for raw_corner in &arguments {
+            let corner = self.parse_corner(line_number, line, raw_corner)?;
+            face.push(self.get_corner_index(corner));
+          }
+          // Fan triangulation. Fine for the convex quads modelers export.
+          for i in 1..face.len() - 1 {
+            self
+              .indices
+              .extend_from_slice(&[face[0], face[i], face[i + 1]]);
+          }
+        }
+        "mtllib" => self.material_library = arguments.first().map(|name| name.to_string()),
+        // Only one Texture per Mesh, the first material wins.
+        "usemtl" if self.material.is_none() => {
+          self.material = arguments.first().map(|name| name.to_string())
+        }
+        _ => (),
+      }
+    }
+    Ok(())
+  }
+
+  ///
+  /// Find the map_Kd texture of a material in a material library.
+  ///
+  /// If no material was asked for, the first one with a texture is used.
+  ///
+  pub fn find_texture(raw_materials: &str, material: Option<&str>) -> Option<String> {
+    let mut current_material: Option<&str> = None;
+    for line in raw_materials.lines() {
+      let mut words = line.split_whitespace();
+      match words.next() {
+        Some("newmtl") => current_material = words.next(),
+        Some("map_Kd") => {
+          let wanted = match material {
+            Some(material) => current_material == Some(material),
+            None => true,
+          };
+          if wanted {
+            // The file name is always last, options come before it.
+            return words.last().map(|texture| texture.to_string());
+          }
+        }
+        _ => (),
+      }
+    }
+    None
+  }
+
+  ///
+  /// Turn everything parsed into Vertex data.
+  ///
+  /// Corners without a normal get a smooth one computed from the faces
+  /// around them.
+  ///
+  pub fn finish(self) -> Result<ObjMeshData, String> {
+    if self.indices.is_empty() {
+      return Err(format!("ObjMeshParser: [{}] has no faces.", self.file_name));
+    }
+
+    // Area weighted face normals, summed per vertex.
+    let mut computed_normals = vec![Vec3::ZERO; self.corners.len()];
+    for triangle in self.indices.chunks(3) {
+      let [a, b, c] = [triangle[0], triangle[1], triangle[2]]
+        .map(|index| self.positions[self.corners[index as usize].0]);
+      let face_normal = (b - a).cross(c - a);
+      for index in triangle {
+        computed_normals[*index as usize] += face_normal;
+      }
+    }
+
+    let mut vertices = Vec::with_capacity(self.corners.len());
+    let mut normals = Vec::with_capacity(self.corners.len());
+    for (corner_index, (position, texture_coordinates, normal)) in self.corners.iter().enumerate() {
+      let texture_coordinates = match texture_coordinates {
+        Some(index) => self.texture_coordinates[*index],
+        None => [0.0, 0.0],
+      };
+      vertices.push(Vertex::new_untinted(
+        self.positions[*position].to_array(),
+        // This flips the texture coordinates right side up.
+        [texture_coordinates[0], 1.0 - texture_coordinates[1]],
+      ));
+
+      let normal = match normal {
+        Some(index) => self.normals[*index],
+        None => computed_normals[corner_index],
+      };
+      normals.push(normal.normalize_or_zero().to_array());
+    }
+
+    Ok(ObjMeshData {
+      vertices,
+      normals,
+      indices: self.indices,
+      texture_path: None,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::remove_dir_all;
+
+  use crate::{
+    file_utilities::{create_dir_all, test_dir, write_file_atomic},
+    game::client::render_engine::model_loader::obj_mesh::ObjMeshParser,
+  };
+
+  #[test]
+  fn test_obj_mesh_parser() {
+    println!("--- BEGIN OBJ MESH PARSER TEST ---");
+    let directory = test_dir("obj_mesh_parser");
+    if let Err(e) = create_dir_all(&directory) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    // A quad and a triangle. No normals, so they get computed.
+    let obj_path = format!("{}/sign.obj", directory);
+    let obj = "# A sign.
+mtllib sign.mtl
+o sign
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 2 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+usemtl wood
+f 1/1 2/2 3/3 4/4
+f 4/4 3/3 5/1
+";
+    let mtl = "newmtl stone
+map_Kd stone.png
+newmtl wood
+Kd 1 1 1
+map_Kd -bm 1 wood.png
+";
+    for (path, contents) in [
+      (obj_path.clone(), obj),
+      (format!("{}/sign.mtl", directory), mtl),
+    ] {
+      if let Err(e) = write_file_atomic(&path, contents.as_bytes()) {
+        panic!("Unit test is broken. {}", e);
+      }
+    }
+
+    let data = match ObjMeshParser::load(&obj_path) {
+      Ok(data) => data,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    // 4 shared corners from the quad, and 5/1 is a new one.
+    assert_eq!(data.vertices.len(), 5);
+    assert_eq!(data.normals.len(), 5);
+    // The quad was split into 2 triangles.
+    assert_eq!(data.indices.len(), 9);
+    assert_eq!(data.indices[..6], [0, 1, 2, 0, 2, 3]);
+    // Flat in XY, so the normal points down Z.
+    assert_eq!(data.normals[0], [0.0, 0.0, 1.0]);
+    assert_eq!(data.texture_path, Some(format!("{}/wood.png", directory)));
+
+    // Broken files say where.
+    let mut parser = ObjMeshParser::new("broken.obj");
+    let error = match parser.parse("v 0 0 0\nv 1 0 0\nv 1 one 0\n") {
+      Err(e) => e,
+      Ok(_) => panic!("Unit test is broken. Bad number was accepted."),
+    };
+    assert!(error.contains("line [3]"));
+    assert!(error.contains("[one] is not a number."));
+
+    let mut parser = ObjMeshParser::new("broken.obj");
+    let error = match parser.parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 7\n") {
+      Err(e) => e,
+      Ok(_) => panic!("Unit test is broken. Bad index was accepted."),
+    };
+    assert!(error.contains("line [4]"));
+    assert!(error.contains("out of bounds"));
+
+    assert!(ObjMeshParser::new("empty.obj").finish().is_err());
+
+    let _ = remove_dir_all(&directory);
+  }
+}
@@ -0,0 +1,125 @@
+use crate::game::game_config::GameConfig;
+
+///
+/// The sample counts minetest.conf is allowed to ask for.
+///
+pub const MSAA_SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+///
+/// The MSAA Buffer is the multisampled color target.
+///
+/// Everything gets rendered into this, then it gets resolved
+/// into the surface texture at the end of each render pass.
+///
+/// It only exists when the sample count is above 1.
+///
+pub struct MsaaBuffer {
+  texture: wgpu::Texture,
+  view: wgpu::TextureView,
+}
+
+impl MsaaBuffer {
+  pub fn new(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    sample_count: u32,
+    label: &str,
+  ) -> Self {
+    let size = wgpu::Extent3d {
+      width: config.width,
+      height: config.height,
+      depth_or_array_layers: 1,
+    };
+    let desc = wgpu::TextureDescriptor {
+      label: Some(label),
+      size,
+      mip_level_count: 1,
+      sample_count,
+      dimension: wgpu::TextureDimension::D2,
+      format: config.format,
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+      view_formats: &[],
+    };
+    let texture = device.create_texture(&desc);
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    Self { texture, view }
+  }
+
+  pub fn get_view(&self) -> &wgpu::TextureView {
+    &self.view
+  }
+
+  ///
+  /// Read the requested sample count out of minetest.conf. (msaa)
+  ///
+  /// Anything that isn't 1, 2, 4, or 8 turns MSAA off.
+  ///
+  pub fn get_requested_sample_count(config: &GameConfig) -> u32 {
+    let requested = config.get_parsed::<u32>("msaa", 1);
+    if MSAA_SAMPLE_COUNTS.contains(&requested) {
+      requested
+    } else {
+      println!(
+        "MsaaBuffer: msaa must be one of {:?}, got [{}]. Disabling MSAA.",
+        MSAA_SAMPLE_COUNTS, requested
+      );
+      1
+    }
+  }
+
+  ///
+  /// Pick the sample count to actually use.
+  ///
+  /// If the adapter can't do the requested count, this falls back to the
+  /// highest one it can do that's still below it.
+  ///
+  pub fn choose_sample_count(requested: u32, is_supported: impl Fn(u32) -> bool) -> u32 {
+    let chosen = MSAA_SAMPLE_COUNTS
+      .iter()
+      .copied()
+      .filter(|count| *count <= requested && (*count == 1 || is_supported(*count)))
+      .max()
+      .unwrap_or(1);
+
+    if chosen != requested {
+      println!(
+        "MsaaBuffer: adapter does not support [{}]x MSAA. Falling back to [{}]x.",
+        requested, chosen
+      );
+    }
+
+    chosen
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::game::{client::render_engine::msaa_buffer::MsaaBuffer, game_config::GameConfig};
+
+  #[test]
+  fn test_msaa_sample_count_fallback() {
+    println!("--- BEGIN MSAA SAMPLE COUNT FALLBACK TEST ---");
+    assert_eq!(
+      MsaaBuffer::get_requested_sample_count(&GameConfig::parse("msaa = 4\n")),
+      4
+    );
+    assert_eq!(
+      MsaaBuffer::get_requested_sample_count(&GameConfig::parse("msaa = 3\n")),
+      1
+    );
+    assert_eq!(
+      MsaaBuffer::get_requested_sample_count(&GameConfig::new()),
+      1
+    );
+
+    // Everything supported.
+    assert_eq!(MsaaBuffer::choose_sample_count(8, |_| true), 8);
+    // Only the WebGPU guaranteed 4x.
+    assert_eq!(MsaaBuffer::choose_sample_count(8, |count| count == 4), 4);
+    assert_eq!(MsaaBuffer::choose_sample_count(2, |count| count == 4), 1);
+    // Nothing supported.
+    assert_eq!(MsaaBuffer::choose_sample_count(4, |_| false), 1);
+  }
+}
@@ -0,0 +1,34 @@
+use std::fmt;
+
+///
+/// Why a RenderEngine couldn't be created.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenderInitError {
+  // Every backend was tried, (backend, why it failed) in the order tried.
+  NoAdapter(Vec<(String, String)>),
+  // An adapter was found, but it refused to give a device.
+  Device(String),
+}
+
+impl fmt::Display for RenderInitError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RenderInitError::NoAdapter(attempts) => {
+        write!(f, "No graphics adapter found.")?;
+        for (backend, reason) in attempts {
+          write!(f, " [{}] {}", backend, reason)?;
+        }
+        write!(
+          f,
+          " Check your graphics drivers, or run a dedicated server with --server."
+        )
+      }
+      RenderInitError::Device(reason) => write!(
+        f,
+        "The graphics adapter failed to create a device. {} Check your graphics drivers.",
+        reason
+      ),
+    }
+  }
+}
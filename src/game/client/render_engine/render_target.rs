@@ -0,0 +1,170 @@
+use std::{iter, sync::mpsc::channel};
+
+use glam::UVec2;
+use wgpu::{SurfaceTexture, TextureView};
+
+///
+/// Where the RenderEngine draws to.
+///
+/// Window: The SDL2 window's surface, presented every frame.
+///
+/// Offscreen: A plain texture, the pixels can be read back.
+/// This lets the RenderEngine run without a window. (Unit tests, CI)
+///
+pub enum RenderTarget {
+  Window(wgpu::Surface),
+  Offscreen(wgpu::Texture),
+}
+
+impl RenderTarget {
+  pub const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+  ///
+  /// Create the texture an Offscreen target renders into.
+  ///
+  pub fn create_offscreen_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+  ) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("offscreen_render_target"),
+      size: wgpu::Extent3d {
+        width: config.width,
+        height: config.height,
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: config.format,
+      // COPY_SRC so the pixels can be read back.
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+      view_formats: &[],
+    })
+  }
+
+  ///
+  /// Apply a new config. (size)
+  ///
+  pub fn configure(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+    match self {
+      RenderTarget::Window(surface) => surface.configure(device, config),
+      RenderTarget::Offscreen(texture) => *texture = Self::create_offscreen_texture(device, config),
+    }
+  }
+
+  ///
+  /// Get if this is an Offscreen target.
+  ///
+  pub fn is_offscreen(&self) -> bool {
+    matches!(self, RenderTarget::Offscreen(_))
+  }
+
+  ///
+  /// Get the texture to render this frame into.
+  ///
+  /// Only a Window target has a SurfaceTexture that needs presenting.
+  ///
+  pub fn get_current_texture(&self) -> (Option<SurfaceTexture>, TextureView) {
+    match self {
+      RenderTarget::Window(surface) => {
+        let output = match surface.get_current_texture() {
+          Ok(texture) => texture,
+          Err(e) => panic!("RenderEngine: Surface texture error. {}", e),
+        };
+        let view = output
+          .texture
+          // ? If this comes up as an error in vscode, you need to switch
+          // ? to a rust-analyzer pre-release version.
+          .create_view(&wgpu::TextureViewDescriptor::default());
+        (Some(output), view)
+      }
+      RenderTarget::Offscreen(texture) => (
+        None,
+        texture.create_view(&wgpu::TextureViewDescriptor::default()),
+      ),
+    }
+  }
+
+  ///
+  /// Copy the pixels out of an Offscreen target.
+  ///
+  /// Returns tightly packed RGBA8 rows, top to bottom.
+  ///
+  pub fn read_pixels(
+    &self,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    size: &UVec2,
+  ) -> Result<Vec<u8>, String> {
+    let texture = match self {
+      RenderTarget::Window(_) => {
+        return Err("RenderTarget: Can only read pixels from an offscreen target.".to_string())
+      }
+      RenderTarget::Offscreen(texture) => texture,
+    };
+
+    // wgpu requires each row of the copy to be aligned.
+    let unpadded_bytes_per_row = 4 * size.x;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+      * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("offscreen_readback_buffer"),
+      size: padded_bytes_per_row as u64 * size.y as u64,
+      usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+      mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+      label: Some("offscreen_readback_encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+      texture.as_image_copy(),
+      wgpu::ImageCopyBuffer {
+        buffer: &buffer,
+        layout: wgpu::ImageDataLayout {
+          offset: 0,
+          bytes_per_row: Some(padded_bytes_per_row),
+          rows_per_image: Some(size.y),
+        },
+      },
+      wgpu::Extent3d {
+        width: size.x,
+        height: size.y,
+        depth_or_array_layers: 1,
+      },
+    );
+    queue.submit(iter::once(encoder.finish()));
+
+    // Block until the GPU is done with the copy.
+    let buffer_slice = buffer.slice(..);
+    let (sender, receiver) = channel();
+    buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+      let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+
+    match receiver.recv() {
+      Ok(Ok(())) => (),
+      Ok(Err(e)) => {
+        return Err(format!(
+          "RenderTarget: Failed to map readback buffer. {}",
+          e
+        ))
+      }
+      Err(e) => return Err(format!("RenderTarget: Readback never finished. {}", e)),
+    }
+
+    let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * size.y) as usize);
+    {
+      let mapped = buffer_slice.get_mapped_range();
+      for row in mapped.chunks(padded_bytes_per_row as usize) {
+        pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+      }
+    }
+    buffer.unmap();
+
+    Ok(pixels)
+  }
+}
@@ -1,11 +1,67 @@
+use std::{
+  path::Path,
+  thread::{self, ThreadId},
+};
+
 use glam::UVec2;
-use image::{GenericImageView, ImageBuffer, Rgba};
+use image::{imageops::FilterType, GenericImageView, ImageBuffer, ImageFormat, Rgba};
+use log::debug;
+
+use crate::{
+  file_utilities::{file_name_from_path, read_file_to_byte_vec},
+  game::game_config::GameConfig,
+};
+
+use super::alpha_mode::AlphaMode;
+
+///
+/// How far off the color key a channel can be and still get keyed.
+///
+/// Legacy textures went through enough tools that magenta isn't
+/// always exactly 255, 0, 255.
+///
+pub const COLOR_KEY_TOLERANCE: u8 = 2;
 
-use crate::file_utilities::{file_name_from_path, read_file_to_byte_vec};
+///
+/// The anisotropic filtering levels minetest.conf is allowed to ask for.
+///
+pub const ANISOTROPY_LEVELS: [u16; 5] = [1, 2, 4, 8, 16];
+
+///
+/// The image formats textures can be in.
+///
+/// Anything else gets a clear error instead of a generic decode failure.
+///
+pub const SUPPORTED_FORMATS: [ImageFormat; 5] = [
+  ImageFormat::Png,
+  ImageFormat::Jpeg,
+  ImageFormat::Bmp,
+  ImageFormat::Tga,
+  ImageFormat::WebP,
+];
+
+///
+/// The pixels of a Texture, decoded but not uploaded to the GPU yet.
+///
+pub struct DecodedTexture {
+  pub name: String,
+  pub dimensions: UVec2,
+  // RGBA8, with the color key already applied.
+  pub diffuse_rgba: Vec<u8>,
+  pub color_key: Option<[u8; 3]>,
+  // How many pixels the color key made transparent.
+  pub keyed_pixels: usize,
+  // Which thread did the decoding.
+  pub decoded_on: ThreadId,
+}
 
 pub struct Texture {
   name: String,
   dimensions: UVec2,
+  alpha_mode: AlphaMode,
+
+  // What was keyed out at load. The pixels aren't kept, it can't change after.
+  color_key: Option<[u8; 3]>,
 
   diffuse_bind_group: wgpu::BindGroup,
 
@@ -15,28 +71,333 @@ pub struct Texture {
 }
 
 impl Texture {
-  pub fn new(path: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+  pub fn new(
+    path: &str,
+    color_key: Option<[u8; 3]>,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    anisotropy: u16,
+  ) -> Self {
+    let max_dimension = device.limits().max_texture_dimension_2d;
+    let decoded = match Self::decode_file(path, max_dimension, color_key) {
+      Ok(decoded) => decoded,
+      Err(e) => panic!("{}", e),
+    };
+
+    match Self::from_decoded(&decoded, device, queue, anisotropy) {
+      Ok(texture) => texture,
+      Err(e) => panic!("{}", e),
+    }
+  }
+
+  ///
+  /// Read and decode an image file, without touching the GPU.
+  ///
+  /// This is safe to run off the render thread, see AssetLoader.
+  ///
+  pub fn decode_file(
+    path: &str,
+    max_dimension: u32,
+    color_key: Option<[u8; 3]>,
+  ) -> Result<DecodedTexture, String> {
     let name = match file_name_from_path(path) {
       Ok(name) => name.to_string(),
-      Err(e) => panic!("Texture: {}", e),
+      Err(e) => return Err(format!("Texture: {}", e)),
     };
 
     let diffuse_bytes = match read_file_to_byte_vec(path) {
       Ok(diffuse_bytes) => diffuse_bytes,
-      Err(e) => panic!("Texture: {}", e),
+      Err(e) => return Err(format!("Texture: {}", e)),
     };
-    let diffuse_image = match image::load_from_memory(diffuse_bytes.as_slice()) {
+
+    Self::decode_bytes(&name, &diffuse_bytes, max_dimension, color_key)
+  }
+
+  ///
+  /// Figure out what format an encoded image is in, from it's bytes.
+  ///
+  /// TGA has no signature, so that comes from the name's extension.
+  /// Warns when the extension says something else than the bytes do.
+  ///
+  pub fn detect_format(name: &str, diffuse_bytes: &[u8]) -> Result<ImageFormat, String> {
+    let declared_format = Path::new(name)
+      .extension()
+      .and_then(|extension| extension.to_str())
+      .and_then(ImageFormat::from_extension);
+
+    let format = match (image::guess_format(diffuse_bytes), declared_format) {
+      (Ok(format), _) => format,
+      (Err(_), Some(ImageFormat::Tga)) => ImageFormat::Tga,
+      (Err(_), _) => {
+        return Err(format!(
+          "Texture: [{}] is not in a known image format.",
+          name
+        ))
+      }
+    };
+
+    if !SUPPORTED_FORMATS.contains(&format) || !format.reading_enabled() {
+      return Err(format!(
+        "Texture: [{}] is [{:?}], which is not supported. Use one of {:?}.",
+        name, format, SUPPORTED_FORMATS
+      ));
+    }
+
+    if let Some(declared_format) = declared_format {
+      if declared_format != format {
+        println!(
+          "Texture: [{}] is named like [{:?}], but it's actually [{:?}].",
+          name, declared_format, format
+        );
+      }
+    }
+
+    debug!("Texture: [{}] is [{:?}].", name, format);
+    Ok(format)
+  }
+
+  ///
+  /// Decode an encoded image into RGBA8 pixels, see SUPPORTED_FORMATS.
+  ///
+  /// Anything bigger than max_dimension gets downscaled to fit, instead
+  /// of letting wgpu fail on it.
+  ///
+  /// The color key is applied here, once, see apply_color_key.
+  ///
+  pub fn decode_bytes(
+    name: &str,
+    diffuse_bytes: &[u8],
+    max_dimension: u32,
+    color_key: Option<[u8; 3]>,
+  ) -> Result<DecodedTexture, String> {
+    let format = Self::detect_format(name, diffuse_bytes)?;
+
+    let mut diffuse_image = match image::load_from_memory_with_format(diffuse_bytes, format) {
       Ok(image) => image,
-      Err(e) => panic!("Texture: Failed to load image from memory. {}", e),
+      Err(e) => {
+        return Err(format!(
+          "Texture: Failed to load image [{}] from memory. {}",
+          name, e
+        ))
+      }
     };
+
+    let (width, height) = diffuse_image.dimensions();
+    let fitted_dimensions = Self::fit_dimensions(UVec2::new(width, height), max_dimension);
+    if fitted_dimensions != UVec2::new(width, height) {
+      println!(
+        "Texture: [{}] is [{}x{}], but this GPU only supports up to [{}x{}]. Downscaling to [{}x{}].",
+        name, width, height, max_dimension, max_dimension, fitted_dimensions.x, fitted_dimensions.y
+      );
+      diffuse_image = diffuse_image.resize_exact(
+        fitted_dimensions.x,
+        fitted_dimensions.y,
+        FilterType::Triangle,
+      );
+    }
+
+    if !width.is_power_of_two() || !height.is_power_of_two() {
+      println!(
+        "Texture: [{}] is [{}x{}], which is not a power of two. It might not tile cleanly.",
+        name, width, height
+      );
+    }
+
     let diffuse_rgba: ImageBuffer<Rgba<u8>, Vec<u8>> = diffuse_image.to_rgba8();
-    let dimensions = diffuse_image.dimensions();
+    let (width, height) = diffuse_image.dimensions();
 
-    let texture_size = wgpu::Extent3d {
-      width: dimensions.0,
-      height: dimensions.1,
-      depth_or_array_layers: 1,
+    let mut diffuse_rgba = diffuse_rgba.into_raw();
+    let keyed_pixels = match color_key {
+      Some(color_key) => Self::apply_color_key(&mut diffuse_rgba, color_key),
+      None => 0,
+    };
+
+    Ok(DecodedTexture {
+      name: name.to_string(),
+      dimensions: UVec2::new(width, height),
+      diffuse_rgba,
+      color_key,
+      keyed_pixels,
+      decoded_on: thread::current().id(),
+    })
+  }
+
+  ///
+  /// Upload a DecodedTexture to the GPU. This has to happen on the render thread.
+  ///
+  /// A texture the color key cut holes into is Cutout, or the keyed pixels would still show.
+  ///
+  pub fn from_decoded(
+    decoded: &DecodedTexture,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    anisotropy: u16,
+  ) -> Result<Self, String> {
+    let mut texture = Self::from_rgba(
+      &decoded.name,
+      decoded.dimensions,
+      &decoded.diffuse_rgba,
+      device,
+      queue,
+      anisotropy,
+    )?;
+    texture.color_key = decoded.color_key;
+    if decoded.keyed_pixels > 0 {
+      texture.alpha_mode = AlphaMode::Cutout;
+    }
+    Ok(texture)
+  }
+
+  ///
+  /// Check that a Texture of these dimensions can be uploaded to the GPU.
+  ///
+  pub fn validate_dimensions(
+    name: &str,
+    dimensions: UVec2,
+    max_dimension: u32,
+  ) -> Result<(), String> {
+    if dimensions.x == 0 || dimensions.y == 0 {
+      return Err(format!(
+        "Texture: [{}] has an empty dimension [{}x{}].",
+        name, dimensions.x, dimensions.y
+      ));
+    }
+    if dimensions.x > max_dimension || dimensions.y > max_dimension {
+      return Err(format!(
+        "Texture: [{}] is [{}x{}], but this GPU only supports up to [{}x{}].",
+        name, dimensions.x, dimensions.y, max_dimension, max_dimension
+      ));
+    }
+    Ok(())
+  }
+
+  ///
+  /// Shrink dimensions to fit max_dimension, keeping the aspect ratio.
+  ///
+  pub fn fit_dimensions(dimensions: UVec2, max_dimension: u32) -> UVec2 {
+    let largest = dimensions.max_element();
+    if largest <= max_dimension {
+      return dimensions;
+    }
+
+    let scale = max_dimension as f64 / largest as f64;
+    UVec2::new(
+      ((dimensions.x as f64 * scale) as u32).clamp(1, max_dimension),
+      ((dimensions.y as f64 * scale) as u32).clamp(1, max_dimension),
+    )
+  }
+
+  ///
+  /// Read the requested anisotropic filtering level out of minetest.conf. (anisotropy)
+  ///
+  /// Anything that isn't 1, 2, 4, 8, or 16 turns it off.
+  ///
+  pub fn get_requested_anisotropy(config: &GameConfig) -> u16 {
+    let requested = config.get_parsed::<u16>("anisotropy", 1);
+    if ANISOTROPY_LEVELS.contains(&requested) {
+      requested
+    } else {
+      println!(
+        "Texture: anisotropy must be one of {:?}, got [{}]. Disabling anisotropic filtering.",
+        ANISOTROPY_LEVELS, requested
+      );
+      1
+    }
+  }
+
+  ///
+  /// Pick the anisotropic filtering level to actually use.
+  ///
+  /// Anything above what the device supports falls back to the highest
+  /// level it does support.
+  ///
+  pub fn choose_anisotropy(requested: u16, max_supported: u16) -> u16 {
+    let chosen = ANISOTROPY_LEVELS
+      .iter()
+      .copied()
+      .filter(|level| *level <= requested && *level <= max_supported)
+      .max()
+      .unwrap_or(1);
+
+    if chosen != requested {
+      println!(
+        "Texture: device does not support [{}]x anisotropic filtering. Falling back to [{}]x.",
+        requested, chosen
+      );
+    }
+
+    chosen
+  }
+
+  ///
+  /// Get the highest anisotropic filtering level an adapter can do.
+  ///
+  /// wgpu only has it as a downlevel flag, it's either 16x or nothing.
+  ///
+  pub fn get_max_anisotropy(adapter: &wgpu::Adapter) -> u16 {
+    match adapter
+      .get_downlevel_capabilities()
+      .flags
+      .contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING)
+    {
+      true => 16,
+      false => 1,
+    }
+  }
+
+  ///
+  /// Get how every Texture gets sampled.
+  ///
+  /// Without anisotropic filtering it's nearest neighbor, crisp pixels.
+  /// wgpu only allows anisotropy with linear filtering, so turning it
+  /// on smooths the textures out up close too.
+  ///
+  pub fn get_sampler_descriptor(anisotropy: u16) -> wgpu::SamplerDescriptor<'static> {
+    let filter = match anisotropy {
+      1 => wgpu::FilterMode::Nearest,
+      _ => wgpu::FilterMode::Linear,
     };
+    wgpu::SamplerDescriptor {
+      address_mode_u: wgpu::AddressMode::ClampToEdge,
+      address_mode_v: wgpu::AddressMode::ClampToEdge,
+      address_mode_w: wgpu::AddressMode::ClampToEdge,
+      mag_filter: filter,
+      min_filter: filter,
+      mipmap_filter: filter,
+      anisotropy_clamp: anisotropy,
+      ..Default::default()
+    }
+  }
+
+  ///
+  /// Create a Texture straight from RGBA8 pixel data.
+  ///
+  /// Errors if the GPU can't hold a texture this big.
+  ///
+  pub fn from_rgba(
+    name: &str,
+    dimensions: UVec2,
+    diffuse_rgba: &[u8],
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    anisotropy: u16,
+  ) -> Result<Self, String> {
+    Self::validate_dimensions(name, dimensions, device.limits().max_texture_dimension_2d)?;
+
+    let expected_length = 4 * dimensions.x as usize * dimensions.y as usize;
+    if diffuse_rgba.len() != expected_length {
+      return Err(format!(
+        "Texture: [{}] needs [{}] bytes of RGBA data, got [{}].",
+        name,
+        expected_length,
+        diffuse_rgba.len()
+      ));
+    }
+
+    let name = name.to_string();
+    let dimensions = (dimensions.x, dimensions.y);
+
+    let texture_size = Self::get_extent(UVec2::new(dimensions.0, dimensions.1));
 
     // * Keep these comments in here, they're very helpful.
 
@@ -65,38 +426,18 @@ impl Texture {
     });
 
     // And now we upload it into the queue for usage.
-    queue.write_texture(
-      // Tells wgpu where to copy the pixel data
-      wgpu::ImageCopyTexture {
-        texture: &texture,
-        mip_level: 0,
-        origin: wgpu::Origin3d::ZERO,
-        aspect: wgpu::TextureAspect::All,
-      },
-      // The actual pixel data
-      &diffuse_rgba,
-      // The layout of the texture
-      wgpu::ImageDataLayout {
-        offset: 0,
-        bytes_per_row: Some(4 * dimensions.0),
-        rows_per_image: Some(dimensions.1),
-      },
-      texture_size,
+    Self::upload(
+      &texture,
+      UVec2::new(dimensions.0, dimensions.1),
+      diffuse_rgba,
+      queue,
     );
 
     // We don't need to configure the texture view much, so let's
     // let wgpu define it.
     let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-      address_mode_u: wgpu::AddressMode::ClampToEdge,
-      address_mode_v: wgpu::AddressMode::ClampToEdge,
-      address_mode_w: wgpu::AddressMode::ClampToEdge,
-      mag_filter: wgpu::FilterMode::Nearest,
-      min_filter: wgpu::FilterMode::Nearest,
-      mipmap_filter: wgpu::FilterMode::Nearest,
-      ..Default::default()
-    });
+    let sampler = device.create_sampler(&Self::get_sampler_descriptor(anisotropy));
 
     let mut diffuse_bind_group_name = name.clone();
     diffuse_bind_group_name.push_str("_diffuse_bind_group");
@@ -116,18 +457,101 @@ impl Texture {
       label: Some(&diffuse_bind_group_name),
     });
 
-    Texture {
+    Ok(Texture {
       name,
       dimensions: UVec2::new(dimensions.0, dimensions.1),
+      alpha_mode: AlphaMode::default(),
+
+      color_key: None,
 
       diffuse_bind_group,
 
       texture,
       view,
       sampler,
+    })
+  }
+
+  fn get_extent(dimensions: UVec2) -> wgpu::Extent3d {
+    wgpu::Extent3d {
+      width: dimensions.x,
+      height: dimensions.y,
+      depth_or_array_layers: 1,
     }
   }
 
+  ///
+  /// Copy RGBA8 pixel data into a wgpu texture.
+  ///
+  fn upload(texture: &wgpu::Texture, dimensions: UVec2, diffuse_rgba: &[u8], queue: &wgpu::Queue) {
+    queue.write_texture(
+      // Tells wgpu where to copy the pixel data
+      wgpu::ImageCopyTexture {
+        texture,
+        mip_level: 0,
+        origin: wgpu::Origin3d::ZERO,
+        aspect: wgpu::TextureAspect::All,
+      },
+      // The actual pixel data
+      diffuse_rgba,
+      // The layout of the texture
+      wgpu::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: Some(4 * dimensions.x),
+        rows_per_image: Some(dimensions.y),
+      },
+      Self::get_extent(dimensions),
+    );
+  }
+
+  ///
+  /// Make every pixel within COLOR_KEY_TOLERANCE of the color key fully transparent.
+  ///
+  /// Returns how many pixels were keyed.
+  ///
+  pub fn apply_color_key(diffuse_rgba: &mut [u8], color_key: [u8; 3]) -> usize {
+    let mut keyed = 0;
+    for pixel in diffuse_rgba.chunks_exact_mut(4) {
+      let matches = pixel
+        .iter()
+        .zip(color_key.iter())
+        .all(|(channel, key)| channel.abs_diff(*key) <= COLOR_KEY_TOLERANCE);
+      if matches {
+        pixel[3] = 0;
+        keyed += 1;
+      }
+    }
+    keyed
+  }
+
+  ///
+  /// Get the color that was treated as transparent when this was loaded, if any.
+  ///
+  pub fn get_color_key(&self) -> Option<[u8; 3]> {
+    self.color_key
+  }
+
+  ///
+  /// Get how the Texture's alpha channel is used.
+  ///
+  pub fn get_alpha_mode(&self) -> AlphaMode {
+    self.alpha_mode
+  }
+
+  ///
+  /// Set how the Texture's alpha channel is used.
+  ///
+  pub fn set_alpha_mode(&mut self, alpha_mode: AlphaMode) {
+    self.alpha_mode = alpha_mode;
+  }
+
+  ///
+  /// Get the Texture's width and height in pixels.
+  ///
+  pub fn get_dimensions(&self) -> &UVec2 {
+    &self.dimensions
+  }
+
   ///
   /// Get the Texture's name.
   ///
@@ -152,6 +576,7 @@ impl Texture {
           binding: 0,
           visibility: wgpu::ShaderStages::FRAGMENT,
           ty: wgpu::BindingType::Texture {
+            // Only render targets are multisampled (MSAA), sampled textures never are.
             multisampled: false,
             view_dimension: wgpu::TextureViewDimension::D2,
             sample_type: wgpu::TextureSampleType::Float { filterable: true },
@@ -171,3 +596,166 @@ impl Texture {
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use glam::UVec2;
+  use image::{ImageFormat, Rgba, RgbaImage};
+
+  use crate::game::{client::render_engine::texture::Texture, game_config::GameConfig};
+
+  #[test]
+  fn test_texture_color_key() {
+    println!("--- BEGIN TEXTURE COLOR KEY TEST ---");
+    let magenta = [255, 0, 255];
+    let mut diffuse_rgba = vec![
+      255, 0, 255, 255, // Magenta.
+      254, 1, 253, 255, // Close enough to magenta.
+      250, 0, 255, 255, // Too far off.
+      10, 200, 30, 128, // Green.
+    ];
+
+    assert_eq!(Texture::apply_color_key(&mut diffuse_rgba, magenta), 2);
+    assert_eq!(
+      diffuse_rgba,
+      vec![255, 0, 255, 0, 254, 1, 253, 0, 250, 0, 255, 255, 10, 200, 30, 128]
+    );
+
+    // Decoding applies it once, right away.
+    let mut image = RgbaImage::from_pixel(2, 1, Rgba([10, 200, 30, 255]));
+    image.put_pixel(0, 0, Rgba([255, 0, 255, 255]));
+    let mut png = Cursor::new(vec![]);
+    if let Err(e) = image.write_to(&mut png, ImageFormat::Png) {
+      panic!("Unit test is broken. {}", e);
+    }
+    match Texture::decode_bytes("legacy.png", png.get_ref(), 8192, Some(magenta)) {
+      Ok(decoded) => {
+        assert_eq!(decoded.keyed_pixels, 1);
+        assert_eq!(decoded.color_key, Some(magenta));
+        assert_eq!(decoded.diffuse_rgba, vec![255, 0, 255, 0, 10, 200, 30, 255]);
+      }
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+    match Texture::decode_bytes("legacy.png", png.get_ref(), 8192, None) {
+      Ok(decoded) => assert_eq!(decoded.keyed_pixels, 0),
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+  }
+
+  #[test]
+  fn test_texture_anisotropy() {
+    println!("--- BEGIN TEXTURE ANISOTROPY TEST ---");
+    let requested =
+      |raw_config: &str| Texture::get_requested_anisotropy(&GameConfig::parse(raw_config));
+    assert_eq!(requested(""), 1);
+    assert_eq!(requested("anisotropy = 8"), 8);
+    assert_eq!(requested("anisotropy = 3"), 1);
+
+    // Off is the old nearest neighbor sampler.
+    let off = Texture::get_sampler_descriptor(1);
+    assert_eq!(off.anisotropy_clamp, 1);
+    assert_eq!(off.mag_filter, wgpu::FilterMode::Nearest);
+    assert_eq!(off.min_filter, wgpu::FilterMode::Nearest);
+
+    let on = Texture::get_sampler_descriptor(Texture::choose_anisotropy(8, 16));
+    assert_eq!(on.anisotropy_clamp, 8);
+    assert_eq!(on.mag_filter, wgpu::FilterMode::Linear);
+    assert_eq!(on.min_filter, wgpu::FilterMode::Linear);
+    assert_eq!(on.mipmap_filter, wgpu::FilterMode::Linear);
+
+    // Asking for more than the device does falls back.
+    assert_eq!(Texture::choose_anisotropy(16, 1), 1);
+    assert_eq!(Texture::choose_anisotropy(16, 8), 8);
+  }
+
+  #[test]
+  fn test_texture_format_detection() {
+    println!("--- BEGIN TEXTURE FORMAT DETECTION TEST ---");
+    let encode = |format: ImageFormat| {
+      let mut bytes = Cursor::new(vec![]);
+      let image = RgbaImage::from_pixel(2, 2, Rgba([10, 20, 30, 255]));
+      if let Err(e) = image.write_to(&mut bytes, format) {
+        panic!("Unit test is broken. {}", e);
+      }
+      bytes.into_inner()
+    };
+
+    let png = encode(ImageFormat::Png);
+    assert_eq!(
+      Texture::detect_format("dirt.png", &png),
+      Ok(ImageFormat::Png)
+    );
+    match Texture::decode_bytes("dirt.png", &png, 8192, None) {
+      Ok(decoded) => {
+        assert_eq!(decoded.dimensions, UVec2::new(2, 2));
+        assert_eq!(&decoded.diffuse_rgba[0..4], &[10, 20, 30, 255]);
+      }
+      Err(e) => panic!("Failed to decode a valid png. {}", e),
+    }
+
+    // TGA comes from the extension, a wrong extension is only a warning.
+    let tga = encode(ImageFormat::Tga);
+    assert_eq!(
+      Texture::detect_format("old.tga", &tga),
+      Ok(ImageFormat::Tga)
+    );
+    let bmp = encode(ImageFormat::Bmp);
+    assert_eq!(
+      Texture::detect_format("liar.png", &bmp),
+      Ok(ImageFormat::Bmp)
+    );
+    assert!(Texture::decode_bytes("liar.png", &bmp, 8192, None).is_ok());
+
+    // A png that breaks off halfway fails to decode, it doesn't panic.
+    let corrupt = &png[0..png.len() / 2];
+    match Texture::decode_bytes("corrupt.png", corrupt, 8192, None) {
+      Ok(_) => panic!("Corrupt png was accepted."),
+      Err(e) => assert!(e.contains("corrupt.png")),
+    }
+
+    // Known formats that aren't supported, and garbage.
+    match Texture::detect_format("anim.gif", b"GIF89a and the rest") {
+      Ok(_) => panic!("Gif was accepted."),
+      Err(e) => assert!(e.contains("not supported")),
+    }
+    assert!(Texture::detect_format("noise.png", &[1, 2, 3, 4, 5, 6, 7, 8]).is_err());
+  }
+
+  #[test]
+  fn test_texture_oversized() {
+    println!("--- BEGIN TEXTURE OVERSIZED TEST ---");
+    // wgpu's default limit.
+    let max_dimension = 8192;
+
+    assert!(Texture::validate_dimensions("fine.png", UVec2::new(16, 16), max_dimension).is_ok());
+    assert!(
+      Texture::validate_dimensions("edge.png", UVec2::new(8192, 8192), max_dimension).is_ok()
+    );
+
+    match Texture::validate_dimensions("huge.png", UVec2::new(16384, 16), max_dimension) {
+      Ok(_) => panic!("Oversized texture was accepted."),
+      Err(e) => {
+        assert!(e.contains("huge.png"));
+        assert!(e.contains("16384x16"));
+        assert!(e.contains("8192x8192"));
+      }
+    }
+    assert!(Texture::validate_dimensions("empty.png", UVec2::new(0, 16), max_dimension).is_err());
+
+    // Downscaling keeps the aspect ratio.
+    assert_eq!(
+      Texture::fit_dimensions(UVec2::new(16384, 4096), max_dimension),
+      UVec2::new(8192, 2048)
+    );
+    assert_eq!(
+      Texture::fit_dimensions(UVec2::new(16384, 1), max_dimension),
+      UVec2::new(8192, 1)
+    );
+    assert_eq!(
+      Texture::fit_dimensions(UVec2::new(64, 32), max_dimension),
+      UVec2::new(64, 32)
+    );
+  }
+}
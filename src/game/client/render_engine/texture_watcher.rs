@@ -0,0 +1,290 @@
+use std::{
+  fs,
+  path::Path,
+  time::{Duration, Instant, SystemTime},
+};
+
+use ahash::{AHashMap, AHashSet};
+
+use super::asset_loader::AssetHandle;
+
+///
+/// How often the files get checked.
+///
+/// Saving a texture and looking at it takes longer than this anyway.
+///
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+///
+/// Files with these extensions in a watched directory are picked up as new textures.
+///
+/// Every format the image crate is built with.
+///
+const TEXTURE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "bmp", "tga", "webp"];
+
+///
+/// A texture file being watched, and what it looked like last time.
+///
+struct WatchedTexture {
+  texture_id: u64,
+  modified: Option<SystemTime>,
+}
+
+///
+/// What changed on disk since the last poll.
+///
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TextureChanges {
+  // Watched files that were written to, with their Texture ID.
+  pub changed: Vec<(String, u64)>,
+  // Files that showed up in a directory a texture was loaded from.
+  pub added: Vec<String>,
+}
+
+///
+/// Notices when texture files change on disk, so they can be reloaded
+/// without restarting. This is a dev tool, see texture_hot_reload.
+///
+/// It polls modification times about once a second instead of asking the OS
+/// to notify it. The directories textures came from get listed too, so a
+/// texture that's dropped into the pack shows up.
+///
+pub struct TextureWatcher {
+  // Path -> the texture loaded from it.
+  watched: AHashMap<String, WatchedTexture>,
+  // Async loads that aren't done yet, they get watched once they have an id.
+  pending: AHashMap<AssetHandle, String>,
+  // Directory -> every texture file seen in it so far.
+  directories: AHashMap<String, AHashSet<String>>,
+  last_poll: Option<Instant>,
+}
+
+impl TextureWatcher {
+  pub fn new() -> Self {
+    TextureWatcher {
+      watched: AHashMap::new(),
+      pending: AHashMap::new(),
+      directories: AHashMap::new(),
+      last_poll: None,
+    }
+  }
+
+  ///
+  /// Get every texture file in a directory. A directory that can't be read has none.
+  ///
+  fn list_textures(directory: &str) -> Vec<String> {
+    let entries = match fs::read_dir(directory) {
+      Ok(entries) => entries,
+      Err(_) => return vec![],
+    };
+    entries
+      .filter_map(|entry| entry.ok())
+      .map(|entry| entry.path())
+      .filter(|path| {
+        path
+          .extension()
+          .and_then(|extension| extension.to_str())
+          .is_some_and(|extension| {
+            TEXTURE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+          })
+      })
+      .filter_map(|path| path.to_str().map(|path| path.to_string()))
+      .collect()
+  }
+
+  fn get_directory(path: &str) -> String {
+    match Path::new(path).parent().and_then(|parent| parent.to_str()) {
+      Some(directory) => directory.to_string(),
+      None => ".".to_string(),
+    }
+  }
+
+  fn get_modified(path: &str) -> Option<SystemTime> {
+    match fs::metadata(path) {
+      Ok(metadata) => metadata.modified().ok(),
+      Err(_) => None,
+    }
+  }
+
+  ///
+  /// Start watching the file a texture was loaded from.
+  ///
+  /// The first texture from a directory starts watching it for new files.
+  /// What's already in it doesn't count as new.
+  ///
+  pub fn watch(&mut self, path: &str, texture_id: u64) {
+    self.watched.insert(
+      path.to_string(),
+      WatchedTexture {
+        texture_id,
+        modified: Self::get_modified(path),
+      },
+    );
+    let directory = Self::get_directory(path);
+    self
+      .directories
+      .entry(directory.clone())
+      .or_insert_with(|| Self::list_textures(&directory).into_iter().collect())
+      .insert(path.to_string());
+  }
+
+  ///
+  /// Remember the path of an async load, see watch_loaded.
+  ///
+  pub fn watch_when_loaded(&mut self, handle: AssetHandle, path: &str) {
+    self.pending.insert(handle, path.to_string());
+  }
+
+  ///
+  /// Start watching an async load now that it has a Texture ID.
+  ///
+  pub fn watch_loaded(&mut self, handle: AssetHandle, texture_id: u64) {
+    if let Some(path) = self.pending.remove(&handle) {
+      self.watch(&path, texture_id);
+    }
+  }
+
+  ///
+  /// Forget an async load that failed.
+  ///
+  pub fn forget_pending(&mut self, handle: AssetHandle) {
+    self.pending.remove(&handle);
+  }
+
+  ///
+  /// Get every file that changed or showed up since the last poll.
+  ///
+  /// Only looks once every POLL_INTERVAL, in between nothing changed.
+  /// A file that went missing isn't reported, the old texture stays
+  /// until it comes back.
+  ///
+  pub fn poll(&mut self) -> TextureChanges {
+    self.poll_at(Instant::now())
+  }
+
+  ///
+  /// poll() but you supply the time. Makes the watcher testable.
+  ///
+  pub fn poll_at(&mut self, now: Instant) -> TextureChanges {
+    let mut changes = TextureChanges::default();
+    if let Some(last_poll) = self.last_poll {
+      if now.saturating_duration_since(last_poll) < POLL_INTERVAL {
+        return changes;
+      }
+    }
+    self.last_poll = Some(now);
+
+    for (path, watched) in self.watched.iter_mut() {
+      let modified = match Self::get_modified(path) {
+        Some(modified) => modified,
+        None => continue,
+      };
+      if watched.modified != Some(modified) {
+        watched.modified = Some(modified);
+        changes.changed.push((path.clone(), watched.texture_id));
+      }
+    }
+
+    for (directory, seen) in self.directories.iter_mut() {
+      for path in Self::list_textures(directory) {
+        if seen.insert(path.clone()) {
+          changes.added.push(path);
+        }
+      }
+    }
+    changes.added.sort();
+    changes
+  }
+
+  pub fn len(&self) -> usize {
+    self.watched.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.watched.is_empty()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs::{create_dir_all, remove_dir_all, File},
+    time::{Duration, Instant, SystemTime},
+  };
+
+  use image::{Rgba, RgbaImage};
+
+  use crate::{
+    file_utilities::test_dir,
+    game::client::render_engine::{
+      texture::Texture,
+      texture_watcher::{TextureWatcher, POLL_INTERVAL},
+    },
+  };
+
+  #[test]
+  fn test_texture_watcher_reload() {
+    println!("--- BEGIN TEXTURE WATCHER RELOAD TEST ---");
+    let texture_dir = test_dir("texture_watcher");
+    if let Err(e) = create_dir_all(&texture_dir) {
+      panic!("Unit test is broken. {}", e);
+    }
+    let get_path = |name: &str| format!("{}/{}", texture_dir, name);
+    let dirt = get_path("dirt.png");
+    let stone = get_path("stone.png");
+
+    // The file system might only keep whole seconds, so the time is set by hand.
+    let write = |path: &str, color: [u8; 4], modified: SystemTime| {
+      if let Err(e) = RgbaImage::from_pixel(2, 2, Rgba(color)).save(path) {
+        panic!("Unit test is broken. {}", e);
+      }
+      let set = File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(modified));
+      if let Err(e) = set {
+        panic!("Unit test is broken. {}", e);
+      }
+    };
+    let start = SystemTime::now() - Duration::from_secs(60);
+    write(&dirt, [10, 20, 30, 255], start);
+    write(&stone, [90, 90, 90, 255], start);
+
+    let mut watcher = TextureWatcher::new();
+    watcher.watch(&dirt, 1);
+    watcher.watch(&stone, 2);
+    assert_eq!(watcher.len(), 2);
+    let mut now = Instant::now();
+    assert_eq!(watcher.poll_at(now), Default::default());
+
+    // Only the one that changed comes back, once. Not before the next poll is due.
+    write(&dirt, [200, 100, 50, 255], start + Duration::from_secs(5));
+    assert!(watcher.poll_at(now).changed.is_empty());
+    now += POLL_INTERVAL;
+    assert_eq!(watcher.poll_at(now).changed, vec![(dirt.clone(), 1)]);
+    now += POLL_INTERVAL;
+    assert!(watcher.poll_at(now).changed.is_empty());
+
+    // A texture dropped into the same directory is new, once.
+    let grass = get_path("grass.png");
+    write(&grass, [0, 200, 0, 255], start);
+    now += POLL_INTERVAL;
+    assert_eq!(watcher.poll_at(now).added, vec![grass]);
+    now += POLL_INTERVAL;
+    assert!(watcher.poll_at(now).added.is_empty());
+
+    // And decoding it again picks up the new pixels.
+    match Texture::decode_file(&dirt, 8192, None) {
+      Ok(decoded) => {
+        assert_eq!(decoded.name, "dirt.png");
+        assert_eq!(&decoded.diffuse_rgba[0..4], &[200, 100, 50, 255]);
+      }
+      Err(e) => panic!("Failed to decode the changed texture. {}", e),
+    }
+
+    // A deleted file keeps the old texture.
+    let _ = remove_dir_all(&texture_dir);
+    now += POLL_INTERVAL;
+    assert_eq!(watcher.poll_at(now), Default::default());
+  }
+}
@@ -0,0 +1,183 @@
+use glam::UVec2;
+
+use crate::file_utilities::read_file_to_string;
+
+use super::texture::Texture;
+
+///
+/// The texture the world gets drawn into when dynamic resolution is scaled down.
+///
+/// It has the surface's format, so the pipelines don't change.
+///
+pub struct ScaledTarget {
+  size: UVec2,
+  texture: wgpu::Texture,
+  bind_group: wgpu::BindGroup,
+}
+
+impl ScaledTarget {
+  pub fn new(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    sampler: &wgpu::Sampler,
+  ) -> Self {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("scaled_render_target"),
+      size: wgpu::Extent3d {
+        width: config.width,
+        height: config.height,
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: config.format,
+      // Drawn into, then sampled by the upscale.
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+      view_formats: &[],
+    });
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      layout: &Texture::get_wgpu_bind_group_layout(device),
+      entries: &[
+        wgpu::BindGroupEntry {
+          binding: 0,
+          resource: wgpu::BindingResource::TextureView(&view),
+        },
+        wgpu::BindGroupEntry {
+          binding: 1,
+          resource: wgpu::BindingResource::Sampler(sampler),
+        },
+      ],
+      label: Some("scaled_render_target_bind_group"),
+    });
+
+    ScaledTarget {
+      size: UVec2::new(config.width, config.height),
+      texture,
+      bind_group,
+    }
+  }
+
+  ///
+  /// Get the size the world is drawn at. [pixels]
+  ///
+  pub fn get_size(&self) -> &UVec2 {
+    &self.size
+  }
+
+  ///
+  /// Get a view to render this frame into.
+  ///
+  pub fn create_view(&self) -> wgpu::TextureView {
+    self
+      .texture
+      .create_view(&wgpu::TextureViewDescriptor::default())
+  }
+}
+
+///
+/// Where the upscale shader is loaded from.
+///
+pub const UPSCALE_SHADER_PATH: &str = "shaders/upscale_shader.wgsl";
+
+///
+/// Stretches a ScaledTarget over the whole surface.
+///
+/// This is the last thing drawn in a frame, when dynamic resolution is scaled down.
+///
+pub struct UpscaleBlit {
+  pipeline: wgpu::RenderPipeline,
+  // Linear, nearest would make the scaled down frame look blocky.
+  sampler: wgpu::Sampler,
+}
+
+impl UpscaleBlit {
+  pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+    let shader_code = match read_file_to_string(UPSCALE_SHADER_PATH) {
+      Ok(shader_code) => shader_code,
+      Err(e) => panic!("UpscaleBlit: {}", e),
+    };
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("upscale_shader"),
+      source: wgpu::ShaderSource::Wgsl(shader_code.into()),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("upscale_pipeline_layout"),
+      bind_group_layouts: &[&Texture::get_wgpu_bind_group_layout(device)],
+      push_constant_ranges: &[],
+    });
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      layout: Some(&pipeline_layout),
+      vertex: wgpu::VertexState {
+        buffers: &[],
+        module: &shader,
+        entry_point: "vs_main",
+      },
+      fragment: Some(wgpu::FragmentState {
+        targets: &[Some(wgpu::ColorTargetState {
+          format,
+          blend: None,
+          write_mask: wgpu::ColorWrites::ALL,
+        })],
+        module: &shader,
+        entry_point: "fs_main",
+      }),
+      primitive: wgpu::PrimitiveState::default(),
+      // The world has already been depth tested.
+      depth_stencil: None,
+      label: Some("upscale_pipeline"),
+      multisample: wgpu::MultisampleState::default(),
+      multiview: None,
+    });
+
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+      address_mode_u: wgpu::AddressMode::ClampToEdge,
+      address_mode_v: wgpu::AddressMode::ClampToEdge,
+      address_mode_w: wgpu::AddressMode::ClampToEdge,
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      mipmap_filter: wgpu::FilterMode::Nearest,
+      ..Default::default()
+    });
+
+    UpscaleBlit { pipeline, sampler }
+  }
+
+  pub fn get_sampler(&self) -> &wgpu::Sampler {
+    &self.sampler
+  }
+
+  ///
+  /// Draw the scaled target over the whole output view.
+  ///
+  pub fn blit(
+    &self,
+    command_encoder: &mut wgpu::CommandEncoder,
+    scaled_target: &ScaledTarget,
+    output_view: &wgpu::TextureView,
+  ) {
+    let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("minetest_upscale_render_pass"),
+      color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+        view: output_view,
+        resolve_target: None,
+        ops: wgpu::Operations {
+          // Every pixel gets written.
+          load: wgpu::LoadOp::Load,
+          store: wgpu::StoreOp::Store,
+        },
+      })],
+      depth_stencil_attachment: None,
+      occlusion_query_set: None,
+      timestamp_writes: None,
+    });
+
+    render_pass.set_pipeline(&self.pipeline);
+    render_pass.set_bind_group(0, &scaled_target.bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+  }
+}
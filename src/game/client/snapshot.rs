@@ -0,0 +1,145 @@
+use glam::Vec3A;
+
+///
+/// Everything the renderer needs from one simulation tick.
+///
+/// Positions are in the same space as the Camera's, see Camera::get_position.
+/// Other players are entities, the EntityRegistry interpolates those itself.
+///
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Snapshot {
+  // Which tick this came from, 0 is before the first tick.
+  pub tick: u64,
+  pub camera_position: Vec3A,
+  pub camera_rotation: Vec3A,
+}
+
+impl Snapshot {
+  ///
+  /// Blend the camera between this snapshot and the next one.
+  ///
+  /// alpha 0.0 is this snapshot, 1.0 is the next.
+  ///
+  pub fn interpolate(&self, next: &Snapshot, alpha: f32) -> Snapshot {
+    Snapshot {
+      tick: next.tick,
+      camera_position: self.camera_position.lerp(next.camera_position, alpha),
+      camera_rotation: self.camera_rotation.lerp(next.camera_rotation, alpha),
+    }
+  }
+}
+
+///
+/// Double buffers the simulation state for the renderer.
+///
+/// The simulation only writes into the back buffer. publish() turns it
+/// into the front buffer all at once, so the renderer never sees a tick
+/// that is still being written. The last front buffer is kept for
+/// interpolating between the two.
+///
+pub struct SnapshotBuffer {
+  back: Snapshot,
+  front: Snapshot,
+  previous: Snapshot,
+}
+
+impl SnapshotBuffer {
+  pub fn new(initial: Snapshot) -> Self {
+    SnapshotBuffer {
+      back: initial.clone(),
+      front: initial.clone(),
+      previous: initial,
+    }
+  }
+
+  ///
+  /// Borrow the back buffer to write this tick into.
+  ///
+  /// It starts out as a copy of the last published snapshot.
+  ///
+  pub fn get_back_mut(&mut self) -> &mut Snapshot {
+    &mut self.back
+  }
+
+  ///
+  /// Swap the finished back buffer to the front.
+  ///
+  pub fn publish(&mut self) {
+    self.back.tick = self.front.tick + 1;
+    std::mem::swap(&mut self.previous, &mut self.front);
+    std::mem::swap(&mut self.front, &mut self.back);
+    // The next tick carries on from this one.
+    self.back.clone_from(&self.front);
+  }
+
+  ///
+  /// Get the newest complete snapshot.
+  ///
+  pub fn get_front(&self) -> &Snapshot {
+    &self.front
+  }
+
+  ///
+  /// Get the snapshot published before the front one.
+  ///
+  pub fn get_previous(&self) -> &Snapshot {
+    &self.previous
+  }
+
+  ///
+  /// Get a snapshot between the previous and front ones. [0.0 - 1.0]
+  ///
+  pub fn get_interpolated(&self, alpha: f32) -> Snapshot {
+    self
+      .previous
+      .interpolate(&self.front, alpha.clamp(0.0, 1.0))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::Vec3A;
+
+  use crate::game::client::snapshot::{Snapshot, SnapshotBuffer};
+
+  #[test]
+  fn test_snapshot_buffer_never_half_updated() {
+    println!("--- BEGIN SNAPSHOT BUFFER NEVER HALF UPDATED TEST ---");
+    let mut snapshots = SnapshotBuffer::new(Snapshot::default());
+
+    // Every field of a tick gets the tick number, so a mix of two ticks shows up.
+    let write_tick = |snapshot: &mut Snapshot, value: f32, stop_after: usize| {
+      for field in 0..stop_after {
+        match field {
+          0 => snapshot.camera_position = Vec3A::splat(value),
+          _ => snapshot.camera_rotation = Vec3A::splat(value),
+        }
+      }
+    };
+    let is_consistent = |snapshot: &Snapshot| snapshot.camera_rotation == snapshot.camera_position;
+
+    for tick in 1..=10 {
+      // The renderer looks in the middle of every write.
+      for stop_after in 0..2 {
+        write_tick(snapshots.get_back_mut(), tick as f32, stop_after);
+        assert!(is_consistent(snapshots.get_front()));
+        assert_eq!(snapshots.get_front().tick, tick - 1);
+      }
+      write_tick(snapshots.get_back_mut(), tick as f32, 2);
+      snapshots.publish();
+
+      assert!(is_consistent(snapshots.get_front()));
+      assert!(is_consistent(snapshots.get_previous()));
+      assert_eq!(snapshots.get_front().tick, tick);
+      assert_eq!(
+        snapshots.get_front().camera_position,
+        Vec3A::splat(tick as f32)
+      );
+    }
+
+    // Halfway between tick 9 and 10.
+    let halfway = snapshots.get_interpolated(0.5);
+    assert_eq!(halfway.camera_position, Vec3A::splat(9.5));
+    assert_eq!(snapshots.get_interpolated(7.0), *snapshots.get_front());
+  }
+}
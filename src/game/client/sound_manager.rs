@@ -0,0 +1,422 @@
+use std::sync::Arc;
+
+use ahash::AHashMap;
+use sdl2::{
+  audio::{AudioCallback, AudioDevice, AudioSpecDesired},
+  Sdl,
+};
+
+use crate::{file_utilities::read_file_to_byte_vec, game::game_config::GameConfig};
+
+///
+/// Where the Client looks for sounds, as <name>.wav.
+///
+pub const DEFAULT_SOUND_DIR: &str = "./sounds";
+
+///
+/// Everything gets converted to this before it's mixed. [hz]
+///
+pub const SAMPLE_RATE: i32 = 44100;
+
+///
+/// The most sounds that play at once. Anything past this gets dropped.
+///
+const MAX_VOICES: usize = 32;
+
+///
+/// Slower than this and a sound would take forever to finish.
+///
+const MIN_PITCH: f32 = 0.1;
+
+///
+/// Faster than this is just a click.
+///
+const MAX_PITCH: f32 = 10.0;
+
+///
+/// The loudest a single sound gets. Louder would only clip.
+///
+const MAX_GAIN: f32 = 4.0;
+
+///
+/// Check that a sound name is just a file name in the sound dir.
+///
+/// The server picks the names, it doesn't get to pick files elsewhere.
+///
+fn is_valid_sound_name(name: &str) -> bool {
+  !name.is_empty()
+    && !name.contains("..")
+    && name
+      .chars()
+      .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '.' | '-'))
+}
+
+///
+/// Clamp a number from the server into a range. NaN and infinity get the default.
+///
+fn clamp_finite(value: f32, min: f32, max: f32, default: f32) -> f32 {
+  match value.is_finite() {
+    true => value.clamp(min, max),
+    false => default,
+  }
+}
+
+///
+/// One sound that's playing.
+///
+struct Voice {
+  samples: Arc<Vec<f32>>,
+  // Fractional so pitch can step through it at any speed. [samples]
+  position: f64,
+  gain: f32,
+  pitch: f32,
+}
+
+///
+/// Mixes every playing Voice into the audio device's buffer.
+///
+/// This runs on SDL's audio thread.
+///
+struct Mixer {
+  voices: Vec<Voice>,
+  volume: f32,
+}
+
+impl AudioCallback for Mixer {
+  type Channel = f32;
+
+  fn callback(&mut self, out: &mut [f32]) {
+    out.fill(0.0);
+
+    for voice in &mut self.voices {
+      for sample in out.iter_mut() {
+        let index = voice.position as usize;
+        if index >= voice.samples.len() {
+          break;
+        }
+        *sample += voice.samples[index] * voice.gain;
+        voice.position += voice.pitch as f64;
+      }
+    }
+    self
+      .voices
+      .retain(|voice| (voice.position as usize) < voice.samples.len());
+
+    for sample in out.iter_mut() {
+      *sample = (*sample * self.volume).clamp(-1.0, 1.0);
+    }
+  }
+}
+
+///
+/// Plays the sounds the server asks for.
+///
+/// Sounds are loaded from the sound dir the first time they're played, then cached.
+/// Only .wav is supported for now.
+///
+/// If there's no audio device the SoundManager just stays quiet,
+/// a missing sound card shouldn't keep anyone from playing.
+///
+pub struct SoundManager {
+  sound_dir: String,
+  // None means the file is missing or broken, so it's only complained about once.
+  cache: AHashMap<String, Option<Arc<Vec<f32>>>>,
+
+  device: Option<AudioDevice<Mixer>>,
+  // The device stops working once SDL shuts down, so hold on to it.
+  _sdl_context: Option<Sdl>,
+}
+
+impl SoundManager {
+  pub fn new(sound_dir: &str, enabled: bool, volume: f32) -> Self {
+    let mut new_sound_manager = SoundManager {
+      sound_dir: sound_dir.to_string(),
+      cache: AHashMap::new(),
+
+      device: None,
+      _sdl_context: None,
+    };
+
+    if !enabled {
+      return new_sound_manager;
+    }
+
+    match Self::open_device(volume) {
+      Ok((sdl_context, device)) => {
+        new_sound_manager.device = Some(device);
+        new_sound_manager._sdl_context = Some(sdl_context);
+      }
+      Err(e) => println!("SoundManager: no audio, sounds are off. {}", e),
+    }
+
+    new_sound_manager
+  }
+
+  pub fn from_config(config: &GameConfig) -> Self {
+    Self::new(
+      DEFAULT_SOUND_DIR,
+      config.get_bool("enable_sound", true),
+      config.get_parsed("sound_volume", 1.0f32).clamp(0.0, 1.0),
+    )
+  }
+
+  ///
+  /// Open the default audio device, mono at SAMPLE_RATE.
+  ///
+  fn open_device(volume: f32) -> Result<(Sdl, AudioDevice<Mixer>), String> {
+    // SDL is refcounted, this is the same context the window uses.
+    let sdl_context = sdl2::init()?;
+    let audio_subsystem = sdl_context.audio()?;
+
+    let desired_spec = AudioSpecDesired {
+      freq: Some(SAMPLE_RATE),
+      channels: Some(1),
+      samples: None,
+    };
+    let device = audio_subsystem.open_playback(None, &desired_spec, |_| Mixer {
+      voices: vec![],
+      volume,
+    })?;
+    device.resume();
+
+    Ok((sdl_context, device))
+  }
+
+  ///
+  /// Decode a wav file into mono f32 samples at SAMPLE_RATE.
+  ///
+  /// Takes 8 bit, 16 bit and float PCM, any channel count and any rate.
+  ///
+  pub fn decode_wav(bytes: &[u8]) -> Result<Vec<f32>, String> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+      return Err("Not a wav file.".to_string());
+    }
+
+    // (format tag, channels, sample rate, bits per sample)
+    let mut format = None;
+    let mut data = None;
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+      let id = &bytes[offset..offset + 4];
+      let size = u32::from_le_bytes([
+        bytes[offset + 4],
+        bytes[offset + 5],
+        bytes[offset + 6],
+        bytes[offset + 7],
+      ]) as usize;
+      let body = &bytes[offset + 8..(offset + 8 + size).min(bytes.len())];
+
+      if id == b"fmt " && body.len() >= 16 {
+        format = Some((
+          u16::from_le_bytes([body[0], body[1]]),
+          u16::from_le_bytes([body[2], body[3]]),
+          u32::from_le_bytes([body[4], body[5], body[6], body[7]]),
+          u16::from_le_bytes([body[14], body[15]]),
+        ));
+      } else if id == b"data" {
+        data = Some(body);
+      }
+
+      // Chunks are padded to an even size.
+      offset += 8 + size + (size % 2);
+    }
+
+    let (format_tag, channels, sample_rate, bits) = match format {
+      Some(format) => format,
+      None => return Err("Wav file has no fmt chunk.".to_string()),
+    };
+    let data = match data {
+      Some(data) => data,
+      None => return Err("Wav file has no data chunk.".to_string()),
+    };
+    if channels == 0 || sample_rate == 0 {
+      return Err("Wav file has no channels.".to_string());
+    }
+
+    let samples: Vec<f32> = match (format_tag, bits) {
+      (1, 8) => data.iter().map(|s| (*s as f32 - 128.0) / 128.0).collect(),
+      (1, 16) => data
+        .chunks_exact(2)
+        .map(|s| i16::from_le_bytes([s[0], s[1]]) as f32 / 32768.0)
+        .collect(),
+      (3, 32) => data
+        .chunks_exact(4)
+        .map(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+        .collect(),
+      _ => {
+        return Err(format!(
+          "Wav format [{}] at [{}] bits is not supported.",
+          format_tag, bits
+        ))
+      }
+    };
+
+    // Down to mono.
+    let mono: Vec<f32> = samples
+      .chunks_exact(channels as usize)
+      .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+      .collect();
+
+    if sample_rate == SAMPLE_RATE as u32 || mono.is_empty() {
+      return Ok(mono);
+    }
+
+    // Linear resampling is plenty for sound effects.
+    let step = sample_rate as f64 / SAMPLE_RATE as f64;
+    let length = (mono.len() as f64 / step) as usize;
+    Ok(
+      (0..length)
+        .map(|index| {
+          let position = index as f64 * step;
+          let before = position as usize;
+          let after = (before + 1).min(mono.len() - 1);
+          let blend = (position - before as f64) as f32;
+          mono[before] * (1.0 - blend) + mono[after] * blend
+        })
+        .collect(),
+    )
+  }
+
+  ///
+  /// Get a sound's samples, loading it if this is the first time.
+  ///
+  fn get_samples(&mut self, name: &str) -> Option<Arc<Vec<f32>>> {
+    if let Some(cached) = self.cache.get(name) {
+      return cached.clone();
+    }
+
+    // Not cached, so a server can't fill up the cache with junk names.
+    if !is_valid_sound_name(name) {
+      println!(
+        "SoundManager: not playing [{}], that isn't a sound name.",
+        name.escape_debug()
+      );
+      return None;
+    }
+
+    let path = format!("{}/{}.wav", self.sound_dir, name);
+    let samples = match read_file_to_byte_vec(&path).and_then(|bytes| Self::decode_wav(&bytes)) {
+      Ok(samples) => Some(Arc::new(samples)),
+      Err(e) => {
+        println!("SoundManager: can't play [{}]. {}", name, e);
+        None
+      }
+    };
+    self.cache.insert(name.to_string(), samples.clone());
+    samples
+  }
+
+  ///
+  /// Start playing a sound. Unknown sounds are ignored.
+  ///
+  pub fn play(&mut self, name: &str, gain: f32, pitch: f32) {
+    // Don't bother loading anything nobody will hear.
+    if self.device.is_none() {
+      return;
+    }
+
+    let samples = match self.get_samples(name) {
+      Some(samples) => samples,
+      None => return,
+    };
+
+    if let Some(device) = &mut self.device {
+      let mut mixer = device.lock();
+      if mixer.voices.len() >= MAX_VOICES {
+        return;
+      }
+      mixer.voices.push(Voice {
+        samples,
+        position: 0.0,
+        gain: clamp_finite(gain, 0.0, MAX_GAIN, 1.0),
+        pitch: clamp_finite(pitch, MIN_PITCH, MAX_PITCH, 1.0),
+      });
+    }
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.device.is_some()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::game::client::sound_manager::{
+    clamp_finite, is_valid_sound_name, SoundManager, MAX_GAIN, SAMPLE_RATE,
+  };
+
+  ///
+  /// A 16 bit mono wav, built by hand.
+  ///
+  fn make_wav(samples: &[i16]) -> Vec<u8> {
+    let data_size = (samples.len() * 2) as u32;
+    let mut wav = vec![];
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, mono.
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE as u32).to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE as u32 * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+      wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+  }
+
+  #[test]
+  fn test_sound_manager_decode_wav() {
+    println!("--- BEGIN SOUND MANAGER DECODE WAV TEST ---");
+    let samples = match SoundManager::decode_wav(&make_wav(&[0, i16::MAX, i16::MIN, 0])) {
+      Ok(samples) => samples,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    assert_eq!(samples.len(), 4);
+    assert_eq!(samples[0], 0.0);
+    assert!(samples[1] > 0.99);
+    assert!(samples[2] < -0.99);
+
+    assert!(SoundManager::decode_wav(b"not a wav").is_err());
+
+    // Half the rate comes out twice as long.
+    let mut slow_wav = make_wav(&[0; 100]);
+    slow_wav[24..28].copy_from_slice(&(SAMPLE_RATE as u32 / 2).to_le_bytes());
+    match SoundManager::decode_wav(&slow_wav) {
+      Ok(samples) => assert_eq!(samples.len(), 200),
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+
+    // Without a device nothing plays, and that's fine.
+    let mut sound_manager = SoundManager::new("./sounds_that_do_not_exist", false, 1.0);
+    assert!(!sound_manager.is_enabled());
+    sound_manager.play("default_dig", 1.0, 1.0);
+    assert!(sound_manager.get_samples("default_dig").is_none());
+
+    // The server can only name sounds, not paths.
+    assert!(is_valid_sound_name("default_dig.1-b"));
+    for name in [
+      "",
+      "../../.ssh/id_rsa",
+      "/etc/passwd",
+      "sub/sound",
+      "..",
+      "Loud",
+    ] {
+      assert!(!is_valid_sound_name(name), "[{}] got through.", name);
+    }
+    assert!(sound_manager.get_samples("../secret").is_none());
+    assert!(!sound_manager.cache.contains_key("../secret"));
+
+    // And nothing it sends is infinitely loud.
+    assert_eq!(clamp_finite(f32::NAN, 0.0, MAX_GAIN, 1.0), 1.0);
+    assert_eq!(clamp_finite(f32::INFINITY, 0.0, MAX_GAIN, 1.0), 1.0);
+    assert_eq!(clamp_finite(1000.0, 0.0, MAX_GAIN, 1.0), MAX_GAIN);
+    assert_eq!(clamp_finite(-1.0, 0.0, MAX_GAIN, 1.0), 0.0);
+  }
+}
@@ -0,0 +1,122 @@
+use glam::Vec3A;
+
+use crate::game::game_config::GameConfig;
+
+use super::keyboard::KeyboardController;
+
+///
+/// The key that toggles spectating, if minetest.conf doesn't say.
+///
+/// This is an SDL2 scancode name.
+///
+const DEFAULT_TOGGLE_KEY: &str = "H";
+
+///
+/// How much faster the Camera flies while spectating.
+///
+pub const SPECTATOR_SPEED_MULTIPLIER: f32 = 3.0;
+
+///
+/// The free flying spectator camera.
+///
+/// The key is keymap_noclip in minetest.conf. On a server it only asks,
+/// the server says if it's allowed with the noclip privilege. Without a
+/// server there's nobody to ask, it just toggles.
+///
+/// While spectating the Camera leaves the player where they were, and
+/// goes back there when it stops.
+///
+pub struct Spectator {
+  active: bool,
+  toggle_key: String,
+  toggle_key_was_down: bool,
+  // Where the player was when spectating started.
+  return_position: Option<Vec3A>,
+}
+
+impl Spectator {
+  pub fn new(config: &GameConfig) -> Self {
+    Spectator {
+      active: false,
+      toggle_key: config.get_string("keymap_noclip", DEFAULT_TOGGLE_KEY),
+      toggle_key_was_down: false,
+      return_position: None,
+    }
+  }
+
+  ///
+  /// Watch the toggle key.
+  ///
+  /// Returns the mode it wants to switch to, when the key goes down.
+  ///
+  pub fn update(&mut self, keyboard: &KeyboardController) -> Option<bool> {
+    let toggle_key_down = keyboard.is_key_down(&self.toggle_key);
+    let pressed = toggle_key_down && !self.toggle_key_was_down;
+    self.toggle_key_was_down = toggle_key_down;
+    match pressed {
+      true => Some(!self.active),
+      false => None,
+    }
+  }
+
+  ///
+  /// Start spectating, remembering where the player is.
+  ///
+  pub fn enter(&mut self, player_position: Vec3A) {
+    if !self.active {
+      self.active = true;
+      self.return_position = Some(player_position);
+    }
+  }
+
+  ///
+  /// Stop spectating. Returns where the Camera goes back to, None if it wasn't spectating.
+  ///
+  pub fn exit(&mut self) -> Option<Vec3A> {
+    self.active = false;
+    self.return_position.take()
+  }
+
+  pub fn is_active(&self) -> bool {
+    self.active
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::Vec3A;
+
+  use crate::game::{
+    client::{keyboard::KeyboardController, spectator::Spectator},
+    game_config::GameConfig,
+  };
+
+  #[test]
+  fn test_spectator_toggle() {
+    println!("--- BEGIN SPECTATOR TOGGLE TEST ---");
+    let mut spectator = Spectator::new(&GameConfig::parse("keymap_noclip = N"));
+    let mut keyboard = KeyboardController::new();
+    assert_eq!(spectator.update(&keyboard), None);
+
+    // Holding the key only asks once.
+    keyboard.set_key("N", true);
+    assert_eq!(spectator.update(&keyboard), Some(true));
+    assert_eq!(spectator.update(&keyboard), None);
+    assert!(!spectator.is_active());
+
+    // Entering twice keeps the first position.
+    spectator.enter(Vec3A::new(1.0, 2.0, 3.0));
+    spectator.enter(Vec3A::new(50.0, 2.0, 3.0));
+    assert!(spectator.is_active());
+
+    keyboard.set_key("N", false);
+    spectator.update(&keyboard);
+    keyboard.set_key("N", true);
+    assert_eq!(spectator.update(&keyboard), Some(false));
+
+    // Leaving goes back to the player.
+    assert_eq!(spectator.exit(), Some(Vec3A::new(1.0, 2.0, 3.0)));
+    assert!(!spectator.is_active());
+    assert_eq!(spectator.exit(), None);
+  }
+}
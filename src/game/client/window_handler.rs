@@ -1,4 +1,5 @@
 mod key_event_enum;
+pub mod window_settings;
 
 use glam::UVec2;
 use sdl2::{
@@ -12,7 +13,7 @@ use sdl2::{
 
 use log::error;
 
-use self::key_event_enum::KeyEvent;
+use self::{key_event_enum::KeyEvent, window_settings::WindowSettings};
 
 use super::{keyboard::KeyboardController, mouse::MouseController};
 
@@ -42,7 +43,7 @@ pub struct WindowHandler {
 }
 
 impl WindowHandler {
-  pub fn new(mouse: &mut MouseController) -> Self {
+  pub fn new(mouse: &mut MouseController, settings: &WindowSettings) -> Self {
     // We're going to do this line by line,
     // in case any of this fails.
 
@@ -59,16 +60,32 @@ impl WindowHandler {
       Err(e) => panic!("WindowHandler: Failed to initialize video subsystem. {}", e),
     };
 
-    let size = UVec2::new(512, 512);
+    // Make sure the window actually fits on the screen it's going to be centered on.
+    let settings = match video_subsystem.display_bounds(0) {
+      Ok(bounds) => settings.fit_to_display(UVec2::new(bounds.width(), bounds.height())),
+      Err(e) => {
+        println!("WindowHandler: Failed to get display bounds. {}", e);
+        *settings
+      }
+    };
 
-    let window = match video_subsystem
-      .window("minetest", size.x, size.y)
+    let size = settings.size;
+
+    let mut window_builder = video_subsystem.window("minetest", size.x, size.y);
+    window_builder
       .resizable()
       .position_centered()
       .allow_highdpi()
-      .metal_view()
-      .build()
-    {
+      .metal_view();
+
+    if settings.maximized {
+      window_builder.maximized();
+    }
+    if settings.fullscreen {
+      window_builder.fullscreen_desktop();
+    }
+
+    let window = match window_builder.build() {
       Ok(window) => window,
       Err(e) => panic!("WindowBuilder: Failed to initialize window. {}", e),
     };
@@ -82,7 +99,7 @@ impl WindowHandler {
       visible: false,
       size,
 
-      maximized: false,
+      maximized: settings.maximized,
     };
 
     new_window_handler.show();
@@ -0,0 +1,117 @@
+use glam::UVec2;
+
+use crate::game::game_config::GameConfig;
+
+///
+/// The window size used when minetest.conf doesn't have a usable one.
+///
+pub const DEFAULT_WINDOW_SIZE: UVec2 = UVec2::new(1024, 600);
+
+///
+/// How the window should look when it's first created.
+///
+/// Read from minetest.conf:
+/// screen_w, screen_h, window_maximized, fullscreen
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowSettings {
+  pub size: UVec2,
+  pub maximized: bool,
+  pub fullscreen: bool,
+}
+
+impl WindowSettings {
+  pub fn from_config(config: &GameConfig) -> Self {
+    let width = config.get_parsed::<u32>("screen_w", DEFAULT_WINDOW_SIZE.x);
+    let height = config.get_parsed::<u32>("screen_h", DEFAULT_WINDOW_SIZE.y);
+
+    // A 0 sized window can't be rendered to.
+    let size = if width == 0 || height == 0 {
+      println!(
+        "WindowSettings: invalid window size [{}x{}]. Using default [{}x{}].",
+        width, height, DEFAULT_WINDOW_SIZE.x, DEFAULT_WINDOW_SIZE.y
+      );
+      DEFAULT_WINDOW_SIZE
+    } else {
+      UVec2::new(width, height)
+    };
+
+    WindowSettings {
+      size,
+      maximized: config.get_bool("window_maximized", false),
+      fullscreen: config.get_bool("fullscreen", false),
+    }
+  }
+
+  ///
+  /// Shrink the window size so it fits on a display of display_size.
+  ///
+  /// This keeps the window from being created partially off-screen.
+  ///
+  pub fn fit_to_display(&self, display_size: UVec2) -> Self {
+    // Can't trust a display that claims to be 0 sized.
+    if display_size.x == 0 || display_size.y == 0 {
+      return *self;
+    }
+
+    let fitted_size = self.size.min(display_size);
+    if fitted_size != self.size {
+      println!(
+        "WindowSettings: window size [{}x{}] is bigger than the display [{}x{}]. Shrinking.",
+        self.size.x, self.size.y, display_size.x, display_size.y
+      );
+    }
+
+    WindowSettings {
+      size: fitted_size,
+      ..*self
+    }
+  }
+}
+
+impl Default for WindowSettings {
+  fn default() -> Self {
+    WindowSettings {
+      size: DEFAULT_WINDOW_SIZE,
+      maximized: false,
+      fullscreen: false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::UVec2;
+
+  use crate::game::{
+    client::window_handler::window_settings::{WindowSettings, DEFAULT_WINDOW_SIZE},
+    game_config::GameConfig,
+  };
+
+  #[test]
+  fn test_window_settings_from_config() {
+    println!("--- BEGIN WINDOW SETTINGS FROM CONFIG TEST ---");
+    let config = GameConfig::parse("screen_w = 1920\nscreen_h = 1080\nfullscreen = true\n");
+    let settings = WindowSettings::from_config(&config);
+    assert_eq!(settings.size, UVec2::new(1920, 1080));
+    assert!(settings.fullscreen);
+    assert!(!settings.maximized);
+
+    // Zero and garbage sizes fall back to the default.
+    let config = GameConfig::parse("screen_w = 0\nscreen_h = 720\n");
+    assert_eq!(
+      WindowSettings::from_config(&config).size,
+      DEFAULT_WINDOW_SIZE
+    );
+    let config = GameConfig::parse("screen_w = huge\nscreen_h = -5\n");
+    assert_eq!(
+      WindowSettings::from_config(&config).size,
+      DEFAULT_WINDOW_SIZE
+    );
+
+    // Too big for the display gets shrunk, one axis at a time.
+    let config = GameConfig::parse("screen_w = 4000\nscreen_h = 600\n");
+    let fitted = WindowSettings::from_config(&config).fit_to_display(UVec2::new(1920, 1080));
+    assert_eq!(fitted.size, UVec2::new(1920, 600));
+  }
+}
@@ -0,0 +1,176 @@
+use crate::game::game_config::GameConfig;
+
+use super::keyboard::KeyboardController;
+
+///
+/// The key that zooms while it's held, if minetest.conf doesn't say.
+///
+/// This is an SDL2 scancode name.
+///
+const DEFAULT_ZOOM_KEY: &str = "Z";
+
+///
+/// The FOV without zooming, unless minetest.conf says fov. [degrees]
+///
+pub const DEFAULT_FOV: f32 = 72.0;
+
+///
+/// The FOV while zooming, unless minetest.conf says zoom_fov. [degrees]
+///
+pub const DEFAULT_ZOOM_FOV: f32 = 15.0;
+
+///
+/// How fast the FOV moves to where it's going, unless minetest.conf says zoom_speed.
+///
+/// Exponential, about this many times the remaining distance per second.
+/// 0.0 jumps straight there.
+///
+pub const DEFAULT_ZOOM_SPEED: f32 = 10.0;
+
+///
+/// The FOV range minetest.conf can pick from. [degrees]
+///
+const MIN_FOV: f32 = 7.0;
+const MAX_FOV: f32 = 160.0;
+
+///
+/// Close enough to the target FOV to just be there. [degrees]
+///
+const FOV_SNAP: f32 = 0.01;
+
+///
+/// Zooms the Camera in while the zoom key is held, and back out when it's let go.
+///
+/// The base FOV is what the settings say, the current FOV is what the
+/// Camera actually gets this frame. Changing the base mid zoom doesn't
+/// jump the Camera, it just changes where it goes back to.
+///
+pub struct Zoom {
+  key: String,
+  base_fov: f32,
+  zoom_fov: f32,
+  speed: f32,
+  fov: f32,
+}
+
+impl Zoom {
+  pub fn new(config: &GameConfig) -> Self {
+    let base_fov = config
+      .get_parsed("fov", DEFAULT_FOV)
+      .clamp(MIN_FOV, MAX_FOV);
+    Zoom {
+      key: config.get_string("keymap_zoom", DEFAULT_ZOOM_KEY),
+      base_fov,
+      zoom_fov: config
+        .get_parsed("zoom_fov", DEFAULT_ZOOM_FOV)
+        .clamp(MIN_FOV, MAX_FOV),
+      speed: config.get_parsed("zoom_speed", DEFAULT_ZOOM_SPEED).max(0.0),
+      fov: base_fov,
+    }
+  }
+
+  ///
+  /// Set the FOV for when not zooming, like from the settings. [degrees]
+  ///
+  /// When not zooming the Camera goes there right away.
+  ///
+  pub fn set_base_fov(&mut self, base_fov: f32, keyboard: &KeyboardController) {
+    self.base_fov = base_fov.clamp(MIN_FOV, MAX_FOV);
+    if !self.is_zooming(keyboard) {
+      self.fov = self.base_fov;
+    }
+  }
+
+  pub fn get_base_fov(&self) -> f32 {
+    self.base_fov
+  }
+
+  ///
+  /// Get the FOV the Camera has right now. [degrees]
+  ///
+  pub fn get_fov(&self) -> f32 {
+    self.fov
+  }
+
+  pub fn is_zooming(&self, keyboard: &KeyboardController) -> bool {
+    keyboard.is_key_down(&self.key)
+  }
+
+  ///
+  /// Move the FOV toward the zoom or the base, depending on the key.
+  ///
+  /// Returns the FOV for the Camera this frame. [degrees]
+  ///
+  pub fn update(&mut self, keyboard: &KeyboardController, delta: f64) -> f32 {
+    let target = match self.is_zooming(keyboard) {
+      true => self.zoom_fov,
+      false => self.base_fov,
+    };
+
+    if self.speed <= 0.0 {
+      self.fov = target;
+      return self.fov;
+    }
+
+    let blend = 1.0 - (-self.speed * delta as f32).exp();
+    self.fov += (target - self.fov) * blend;
+    if (target - self.fov).abs() < FOV_SNAP {
+      self.fov = target;
+    }
+    self.fov
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::game::{
+    client::{keyboard::KeyboardController, zoom::Zoom},
+    game_config::GameConfig,
+  };
+
+  #[test]
+  fn test_zoom_fov() {
+    println!("--- BEGIN ZOOM FOV TEST ---");
+    let mut config = GameConfig::new();
+    config.set("fov", "90");
+    config.set("zoom_fov", "30");
+    config.set("keymap_zoom", "C");
+    let mut zoom = Zoom::new(&config);
+    let mut keyboard = KeyboardController::new();
+    let frame = 1.0 / 60.0;
+
+    // Not zooming, it stays put.
+    assert_eq!(zoom.update(&keyboard, frame), 90.0);
+
+    // Holding the key closes in on the zoom, a little more every frame.
+    keyboard.set_key("C", true);
+    let mut last = zoom.get_fov();
+    for _ in 0..10 {
+      let fov = zoom.update(&keyboard, frame);
+      assert!(fov < last);
+      assert!(fov > 30.0);
+      last = fov;
+    }
+    for _ in 0..600 {
+      zoom.update(&keyboard, frame);
+    }
+    assert_eq!(zoom.get_fov(), 30.0);
+
+    // The settings changing mid zoom is only where it goes back to.
+    zoom.set_base_fov(100.0, &keyboard);
+    assert_eq!(zoom.get_fov(), 30.0);
+    assert_eq!(zoom.get_base_fov(), 100.0);
+
+    // Letting go heads back out.
+    keyboard.set_key("C", false);
+    assert!(zoom.update(&keyboard, frame) > 30.0);
+    for _ in 0..600 {
+      zoom.update(&keyboard, frame);
+    }
+    assert_eq!(zoom.get_fov(), 100.0);
+
+    // And without zooming, the settings apply right away.
+    zoom.set_base_fov(80.0, &keyboard);
+    assert_eq!(zoom.get_fov(), 80.0);
+  }
+}
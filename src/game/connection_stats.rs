@@ -0,0 +1,469 @@
+use std::{
+  collections::VecDeque,
+  time::{Duration, Instant},
+};
+
+use ahash::AHashMap;
+
+use super::{
+  network_message::{NetworkMessage, Packet},
+  sequencer::Sequencer,
+};
+
+///
+/// How often a peer gets pinged.
+///
+pub const PING_INTERVAL: Duration = Duration::from_secs(3);
+
+///
+/// A ping that hasn't come back in this long is forgotten.
+///
+const PING_EXPIRY: Duration = Duration::from_secs(10);
+
+///
+/// How much each new round trip pulls the smoothed one. [0.0 - 1.0]
+///
+/// 1/8 is what TCP uses.
+///
+const ROUND_TRIP_SMOOTHING: f64 = 0.125;
+
+///
+/// How many sequence numbers the packet loss is estimated over.
+///
+/// The estimate covers the last one to two windows worth of packets.
+///
+const LOSS_WINDOW: u32 = 128;
+
+///
+/// The throughput is averaged over this long.
+///
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+///
+/// Where the receive gap buckets end, the last bucket takes the rest. [seconds]
+///
+/// A peer sending every tick lands around 0.05, bursts and stalls show up
+/// on either side of that.
+///
+pub const RECEIVE_GAP_BUCKETS: [f64; 8] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+///
+/// How long the gaps between packets from a peer were, bucketed.
+///
+/// This is the arrival jitter, the spread of it is what matters. Each
+/// bucket only counts the gaps that didn't fit in the one before it.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReceiveHistogram {
+  // One more than RECEIVE_GAP_BUCKETS, for the gaps longer than all of them.
+  pub counts: [u64; RECEIVE_GAP_BUCKETS.len() + 1],
+  // All the gaps added up. [seconds]
+  pub sum: f64,
+}
+
+impl ReceiveHistogram {
+  ///
+  /// Count one gap between packets.
+  ///
+  pub fn record(&mut self, gap: Duration) {
+    let gap = gap.as_secs_f64();
+    let bucket = RECEIVE_GAP_BUCKETS
+      .iter()
+      .position(|bound| gap <= *bound)
+      .unwrap_or(RECEIVE_GAP_BUCKETS.len());
+    self.counts[bucket] += 1;
+    self.sum += gap;
+  }
+
+  ///
+  /// Add another histogram's gaps to this one.
+  ///
+  pub fn merge(&mut self, other: &ReceiveHistogram) {
+    for (count, other_count) in self.counts.iter_mut().zip(other.counts) {
+      *count += other_count;
+    }
+    self.sum += other.sum;
+  }
+
+  ///
+  /// Get how many gaps were counted.
+  ///
+  pub fn get_total(&self) -> u64 {
+    self.counts.iter().sum()
+  }
+}
+
+///
+/// A snapshot of how a connection to one peer is doing.
+///
+/// This is the data behind the net graph.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConnectionStats {
+  // Smoothed ping. None until the first ping comes back.
+  pub round_trip_time: Option<Duration>,
+  // Share of the peer's packets that never showed up. [0.0 - 1.0]
+  pub packet_loss: f64,
+  pub bytes_in_per_second: f64,
+  pub bytes_out_per_second: f64,
+  // Time between packets from the peer, since the session started.
+  pub receive_gaps: ReceiveHistogram,
+}
+
+///
+/// Counts how many sequence numbers went missing.
+///
+#[derive(Debug, Clone, Copy, Default)]
+struct LossWindow {
+  first_sequence: u32,
+  highest_sequence: u32,
+  received: u32,
+}
+
+impl LossWindow {
+  fn get_expected(&self) -> u32 {
+    match self.received {
+      0 => 0,
+      _ => self.highest_sequence.wrapping_sub(self.first_sequence) + 1,
+    }
+  }
+}
+
+///
+/// Bytes over the last RATE_WINDOW.
+///
+#[derive(Debug, Default)]
+struct ByteRate {
+  samples: VecDeque<(Instant, usize)>,
+}
+
+impl ByteRate {
+  fn record(&mut self, bytes: usize, now: Instant) {
+    self.samples.push_back((now, bytes));
+    while let Some((time, _)) = self.samples.front() {
+      match now.duration_since(*time) > RATE_WINDOW {
+        true => self.samples.pop_front(),
+        false => break,
+      };
+    }
+  }
+
+  fn get_per_second(&self, now: Instant) -> f64 {
+    let bytes: usize = self
+      .samples
+      .iter()
+      .filter(|(time, _)| now.duration_since(*time) <= RATE_WINDOW)
+      .map(|(_, bytes)| bytes)
+      .sum();
+    bytes as f64 / RATE_WINDOW.as_secs_f64()
+  }
+}
+
+///
+/// Keeps the ConnectionStats of one peer up to date.
+///
+/// Every packet either way goes through here. Outgoing packets get their
+/// sequence number from it, incoming ones get checked for gaps and run
+/// through the Sequencer.
+///
+pub struct ConnectionTracker {
+  next_sequence: u32,
+  sequencer: Sequencer,
+
+  next_ping_id: u32,
+  pings_in_flight: AHashMap<u32, Instant>,
+  last_ping: Instant,
+  round_trip_time: Option<Duration>,
+
+  previous_loss: LossWindow,
+  current_loss: LossWindow,
+
+  bytes_in: ByteRate,
+  bytes_out: ByteRate,
+
+  last_receive: Option<Instant>,
+  receive_gaps: ReceiveHistogram,
+}
+
+impl ConnectionTracker {
+  ///
+  /// Start tracking a peer. The first ping goes out one PING_INTERVAL from now.
+  ///
+  pub fn new(now: Instant) -> Self {
+    ConnectionTracker {
+      next_sequence: 1,
+      sequencer: Sequencer::new(),
+
+      next_ping_id: 0,
+      pings_in_flight: AHashMap::new(),
+      last_ping: now,
+      round_trip_time: None,
+
+      previous_loss: LossWindow::default(),
+      current_loss: LossWindow::default(),
+
+      bytes_in: ByteRate::default(),
+      bytes_out: ByteRate::default(),
+
+      last_receive: None,
+      receive_gaps: ReceiveHistogram::default(),
+    }
+  }
+
+  ///
+  /// Get the sequence number for the next outgoing packet.
+  ///
+  pub fn get_next_sequence(&mut self) -> u32 {
+    let sequence = self.next_sequence;
+    // 0 is for packets sent without a session.
+    self.next_sequence = match self.next_sequence.wrapping_add(1) {
+      0 => 1,
+      next => next,
+    };
+    sequence
+  }
+
+  ///
+  /// Count an outgoing packet.
+  ///
+  pub fn record_sent(&mut self, bytes: usize, now: Instant) {
+    self.bytes_out.record(bytes, now);
+  }
+
+  ///
+  /// Count an incoming packet, and look for gaps in the sequence.
+  ///
+  /// Late packets still count as received. Duplicates are ignored by the
+  /// estimate clamping at 0% loss.
+  ///
+  pub fn record_received(&mut self, sequence: u32, bytes: usize, now: Instant) {
+    self.record_arrival(bytes, now);
+
+    // Sessionless, it's not part of the sequence.
+    if sequence == 0 {
+      return;
+    }
+
+    let window = &mut self.current_loss;
+    if window.received == 0 {
+      window.first_sequence = sequence;
+      window.highest_sequence = sequence;
+    } else if (sequence.wrapping_sub(window.highest_sequence) as i32) > 0 {
+      window.highest_sequence = sequence;
+    }
+    window.received += 1;
+
+    if window.get_expected() >= LOSS_WINDOW {
+      self.previous_loss = self.current_loss;
+      self.current_loss = LossWindow::default();
+    }
+  }
+
+  ///
+  /// Count the bytes of an incoming packet, and the gap since the last one.
+  ///
+  fn record_arrival(&mut self, bytes: usize, now: Instant) {
+    self.bytes_in.record(bytes, now);
+    if let Some(last_receive) = self.last_receive {
+      self
+        .receive_gaps
+        .record(now.saturating_duration_since(last_receive));
+    }
+    self.last_receive = Some(now);
+  }
+
+  ///
+  /// Take in a packet from the peer.
+  ///
+  /// Returns the messages that are ready to be handled. Duplicates are
+  /// dropped, Ordered messages may be held back for take_ready.
+  ///
+  pub fn receive(&mut self, packet: Packet, bytes: usize, now: Instant) -> Vec<NetworkMessage> {
+    let sequence = packet.sequence;
+    match self.sequencer.receive(sequence, packet.message, now) {
+      Some(ready) => {
+        self.record_received(sequence, bytes, now);
+        ready
+      }
+      // Still traffic, but it says nothing about loss.
+      None => {
+        self.record_arrival(bytes, now);
+        vec![]
+      }
+    }
+  }
+
+  ///
+  /// Get the held back messages that are done waiting. Call this every tick.
+  ///
+  pub fn take_ready(&mut self, now: Instant) -> Vec<NetworkMessage> {
+    self.sequencer.take_ready(now)
+  }
+
+  ///
+  /// Get if it's time to ping the peer again.
+  ///
+  pub fn should_ping(&self, now: Instant) -> bool {
+    now.duration_since(self.last_ping) >= PING_INTERVAL
+  }
+
+  ///
+  /// Start timing a ping. Returns the ID to send with the PingRequest.
+  ///
+  pub fn start_ping(&mut self, now: Instant) -> u32 {
+    self
+      .pings_in_flight
+      .retain(|_, sent| now.duration_since(*sent) < PING_EXPIRY);
+
+    let id = self.next_ping_id;
+    self.next_ping_id = self.next_ping_id.wrapping_add(1);
+    self.pings_in_flight.insert(id, now);
+    self.last_ping = now;
+    id
+  }
+
+  ///
+  /// A PingConfirmation came back.
+  ///
+  /// Returns this round trip, None if the ID was never sent or expired.
+  ///
+  pub fn finish_ping(&mut self, id: u32, now: Instant) -> Option<Duration> {
+    let sent = self.pings_in_flight.remove(&id)?;
+    let round_trip_time = now.duration_since(sent);
+
+    self.round_trip_time = Some(match self.round_trip_time {
+      Some(smoothed) => {
+        let smoothed = smoothed.as_secs_f64();
+        Duration::from_secs_f64(
+          smoothed + (round_trip_time.as_secs_f64() - smoothed) * ROUND_TRIP_SMOOTHING,
+        )
+      }
+      None => round_trip_time,
+    });
+
+    Some(round_trip_time)
+  }
+
+  ///
+  /// Get the ConnectionStats as of now.
+  ///
+  pub fn get_stats(&self, now: Instant) -> ConnectionStats {
+    let expected = self.previous_loss.get_expected() + self.current_loss.get_expected();
+    let received = self.previous_loss.received + self.current_loss.received;
+    let packet_loss = match expected {
+      0 => 0.0,
+      _ => (1.0 - received as f64 / expected as f64).max(0.0),
+    };
+
+    ConnectionStats {
+      round_trip_time: self.round_trip_time,
+      packet_loss,
+      bytes_in_per_second: self.bytes_in.get_per_second(now),
+      bytes_out_per_second: self.bytes_out.get_per_second(now),
+      receive_gaps: self.receive_gaps,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::{Duration, Instant};
+
+  use crate::game::connection_stats::{
+    ConnectionTracker, ReceiveHistogram, PING_INTERVAL, RECEIVE_GAP_BUCKETS,
+  };
+
+  #[test]
+  fn test_connection_tracker() {
+    println!("--- BEGIN CONNECTION TRACKER TEST ---");
+    let start = Instant::now();
+    let mut tracker = ConnectionTracker::new(start);
+
+    // Nothing yet.
+    let stats = tracker.get_stats(start);
+    assert_eq!(stats.round_trip_time, None);
+    assert_eq!(stats.packet_loss, 0.0);
+
+    // Sequence numbers start at 1, 0 is sessionless.
+    assert_eq!(tracker.get_next_sequence(), 1);
+    assert_eq!(tracker.get_next_sequence(), 2);
+
+    // Every 4th packet goes missing.
+    for sequence in 1..=100 {
+      if sequence % 4 != 0 {
+        tracker.record_received(sequence, 10, start);
+      }
+    }
+    // Late and sessionless packets don't count as gaps.
+    tracker.record_received(0, 10, start);
+    let stats = tracker.get_stats(start);
+    assert!((stats.packet_loss - 0.25).abs() < 0.01);
+    assert_eq!(stats.bytes_in_per_second, 760.0);
+
+    // The rate only covers the last second.
+    tracker.record_sent(500, start);
+    let later = start + Duration::from_millis(1500);
+    tracker.record_sent(100, later);
+    let stats = tracker.get_stats(later);
+    assert_eq!(stats.bytes_in_per_second, 0.0);
+    assert_eq!(stats.bytes_out_per_second, 100.0);
+
+    // Round trips are smoothed, unknown pongs are ignored.
+    assert!(!tracker.should_ping(start));
+    assert!(tracker.should_ping(start + PING_INTERVAL));
+    let id = tracker.start_ping(start);
+    assert_eq!(tracker.finish_ping(id + 1, start), None);
+    assert_eq!(
+      tracker.finish_ping(id, start + Duration::from_millis(80)),
+      Some(Duration::from_millis(80))
+    );
+    assert_eq!(tracker.finish_ping(id, start), None);
+    let id = tracker.start_ping(start);
+    tracker.finish_ping(id, start + Duration::from_millis(160));
+    assert_eq!(
+      tracker.get_stats(start).round_trip_time,
+      Some(Duration::from_millis(90))
+    );
+  }
+
+  #[test]
+  fn test_connection_tracker_receive_histogram() {
+    println!("--- BEGIN CONNECTION TRACKER RECEIVE HISTOGRAM TEST ---");
+    let start = Instant::now();
+    let mut tracker = ConnectionTracker::new(start);
+
+    // The first packet has nothing to be a gap from.
+    tracker.record_received(1, 10, start);
+    assert_eq!(tracker.get_stats(start).receive_gaps.get_total(), 0);
+
+    // A steady 50ms, a burst, and a stall.
+    let mut now = start;
+    let mut sequence = 2;
+    let mut arrive = |gap_ms: u64| {
+      now += Duration::from_millis(gap_ms);
+      tracker.record_received(sequence, 10, now);
+      sequence += 1;
+    };
+    for _ in 0..10 {
+      arrive(50);
+    }
+    for _ in 0..3 {
+      arrive(1);
+    }
+    arrive(300);
+    arrive(2000);
+
+    let gaps = tracker.get_stats(now).receive_gaps;
+    assert_eq!(gaps.get_total(), 15);
+    assert_eq!(gaps.counts, [3, 0, 0, 10, 0, 0, 1, 0, 1]);
+    assert!((gaps.sum - 2.803).abs() < 0.0001);
+    assert_eq!(gaps.counts.len(), RECEIVE_GAP_BUCKETS.len() + 1);
+
+    // Merging is how the server gets the overall one.
+    let mut overall = ReceiveHistogram::default();
+    overall.merge(&gaps);
+    overall.merge(&gaps);
+    assert_eq!(overall.get_total(), 30);
+    assert_eq!(overall.counts[3], 20);
+  }
+}
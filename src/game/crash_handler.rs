@@ -0,0 +1,202 @@
+use std::{
+  any::Any,
+  backtrace::Backtrace,
+  panic,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::file_utilities::{create_dir_all, write_file_atomic};
+
+use super::{game_config::GameConfig, lua_engine::lua_guard::is_inside_guard};
+
+///
+/// Where crash reports go, unless minetest.conf says crash_report_dir.
+///
+pub const DEFAULT_CRASH_DIR: &str = "./crashes";
+
+///
+/// Config keys with any of these in them are left out of a crash report.
+///
+const SECRET_KEYS: [&str; 3] = ["password", "secret", "token"];
+
+///
+/// Install a panic hook that writes a crash report before the usual panic output.
+///
+/// The report has the panic message and location, a backtrace, the engine
+/// version and minetest.conf, with secrets blanked out. It's written into
+/// crash_report_dir as crash-<unix time in ms>.txt.
+///
+/// minetest.conf can turn this off with crash_reports = false.
+///
+/// This only runs on a panic, a graceful shutdown never gets here.
+/// Panics in a Lua binding get caught and logged as Lua errors, they're skipped.
+///
+pub fn install(config: &GameConfig) {
+  if !config.get_bool("crash_reports", true) {
+    return;
+  }
+
+  let crash_dir = config.get_string("crash_report_dir", DEFAULT_CRASH_DIR);
+  let config_summary = get_config_summary(config);
+  let default_hook = panic::take_hook();
+
+  panic::set_hook(Box::new(move |info| {
+    if is_inside_guard() {
+      return;
+    }
+    let location = match info.location() {
+      Some(location) => format!(
+        "{}:{}:{}",
+        location.file(),
+        location.line(),
+        location.column()
+      ),
+      None => "unknown".to_string(),
+    };
+    let report = get_report(info.payload(), &location, &config_summary);
+    match write_report(&crash_dir, &report) {
+      Ok(path) => eprintln!("Minetest: crashed, wrote a crash report to [{}].", path),
+      Err(e) => eprintln!("Minetest: crashed, failed to write a crash report. {}", e),
+    }
+    default_hook(info);
+  }));
+}
+
+///
+/// Get minetest.conf as text, sorted, with the secrets blanked out.
+///
+fn get_config_summary(config: &GameConfig) -> String {
+  let mut lines: Vec<String> = config
+    .iter()
+    .map(|(key, value)| {
+      let is_secret = SECRET_KEYS
+        .iter()
+        .any(|secret| key.to_lowercase().contains(secret));
+      match is_secret {
+        true => format!("{} = <hidden>", key),
+        false => format!("{} = {}", key, value),
+      }
+    })
+    .collect();
+  lines.sort();
+  lines.join("\n")
+}
+
+///
+/// Put together the crash report for a panic.
+///
+fn get_report(payload: &(dyn Any + Send), location: &str, config_summary: &str) -> String {
+  let message = match payload.downcast_ref::<&str>() {
+    Some(message) => message.to_string(),
+    None => match payload.downcast_ref::<String>() {
+      Some(message) => message.clone(),
+      None => "unknown panic".to_string(),
+    },
+  };
+  let thread = std::thread::current()
+    .name()
+    .unwrap_or("unnamed")
+    .to_string();
+
+  format!(
+    "minetest-rust crash report\n\
+     \n\
+     Version: {}\n\
+     Thread: {}\n\
+     Location: {}\n\
+     Message: {}\n\
+     \n\
+     --- Backtrace ---\n\
+     {}\n\
+     \n\
+     --- minetest.conf ---\n\
+     {}\n",
+    env!("CARGO_PKG_VERSION"),
+    thread,
+    location,
+    message,
+    Backtrace::force_capture(),
+    config_summary
+  )
+}
+
+///
+/// Write a crash report into the crash directory.
+///
+/// Returns the path it was written to.
+///
+fn write_report(crash_dir: &str, report: &str) -> Result<String, String> {
+  create_dir_all(crash_dir)?;
+
+  let millis = match SystemTime::now().duration_since(UNIX_EPOCH) {
+    Ok(since_epoch) => since_epoch.as_millis(),
+    Err(_) => 0,
+  };
+  // Two threads can go down in the same millisecond.
+  let mut path = format!("{}/crash-{}.txt", crash_dir, millis);
+  let mut attempt = 1;
+  while std::path::Path::new(&path).exists() {
+    path = format!("{}/crash-{}-{}.txt", crash_dir, millis, attempt);
+    attempt += 1;
+  }
+
+  write_file_atomic(&path, report.as_bytes())?;
+  Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::{read_to_string, remove_dir_all};
+
+  use crate::{
+    file_utilities::test_dir,
+    game::{
+      crash_handler::{get_config_summary, get_report, write_report},
+      game_config::GameConfig,
+    },
+  };
+
+  #[test]
+  fn test_crash_handler_writes_report() {
+    println!("--- BEGIN CRASH HANDLER WRITES REPORT TEST ---");
+    let crash_dir = test_dir("crash_handler");
+
+    // The hook itself is global, installing it here would catch every other test's panics.
+    let config_summary = get_config_summary(&GameConfig::parse(
+      "server_password = hunter2\nmotd = hello",
+    ));
+    let payload: Box<dyn std::any::Any + Send> = Box::new("crash handler test panic");
+    let report = get_report(
+      payload.as_ref(),
+      "src/game/crash_handler.rs:1:1",
+      &config_summary,
+    );
+
+    assert!(report.contains("crash handler test panic"));
+    assert!(report.contains(env!("CARGO_PKG_VERSION")));
+    assert!(report.contains("crash_handler.rs"));
+    assert!(report.contains("--- Backtrace ---"));
+    assert!(report.contains("motd = hello"));
+    assert!(report.contains("server_password = <hidden>"));
+    assert!(!report.contains("hunter2"));
+
+    // A String payload works too.
+    let payload: Box<dyn std::any::Any + Send> = Box::new(format!("formatted [{}]", 7));
+    assert!(get_report(payload.as_ref(), "unknown", "").contains("formatted [7]"));
+
+    // Two reports in the same millisecond don't overwrite each other.
+    let first = match write_report(&crash_dir, &report) {
+      Ok(path) => path,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let second = match write_report(&crash_dir, "second") {
+      Ok(path) => path,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    assert_ne!(first, second);
+    assert_eq!(read_to_string(&first).ok(), Some(report));
+    assert_eq!(read_to_string(&second).ok(), Some("second".to_string()));
+
+    let _ = remove_dir_all(&crash_dir);
+  }
+}
@@ -1,29 +1,88 @@
 use std::time::Instant;
 
+use super::time_step::TimeStep;
+
+///
+/// How much each new delta moves the smoothed delta. [0.0 - 1.0]
+///
+/// Lower is smoother, but slower to react.
+///
+const SMOOTHING_FACTOR: f64 = 0.1;
+
 ///
 /// DeltaTime is a micro struct which encapsulates logic
 /// for tracking the delta time between loop ticks.
 ///
+/// It also keeps a smoothed (exponential moving average) delta
+/// for things that shouldn't jitter.
+///
 pub struct DeltaReporter {
   old_time: Instant,
+  smoothed_delta: Option<f64>,
 }
 
 impl DeltaReporter {
   pub fn new() -> Self {
     DeltaReporter {
       old_time: Instant::now(),
+      smoothed_delta: None,
     }
   }
 
   ///
-  /// Get the time in seconds as f64 since the last loop.
+  /// Get the time since the last loop.
   ///
   /// You can thank the creator of spin_sleep alexheretic for helping me micro opt this!
   ///
-  pub fn report(&mut self) -> f64 {
+  pub fn report(&mut self) -> TimeStep {
     let now = Instant::now();
-    let delta = now.duration_since(self.old_time).as_secs_f64();
+    let delta = TimeStep::from(now.duration_since(self.old_time));
     self.old_time = now;
+    self.smooth(delta.as_secs_f64());
     delta
   }
+
+  ///
+  /// Fold a raw delta into the smoothed delta.
+  ///
+  fn smooth(&mut self, delta: f64) {
+    self.smoothed_delta = Some(match self.smoothed_delta {
+      Some(smoothed_delta) => smoothed_delta + (delta - smoothed_delta) * SMOOTHING_FACTOR,
+      // The first delta is all we have to go on.
+      None => delta,
+    });
+  }
+
+  ///
+  /// Get the smoothed delta time in seconds. 0.0 before the first report.
+  ///
+  pub fn get_smoothed_delta(&self) -> f64 {
+    self.smoothed_delta.unwrap_or(0.0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::game::delta_reporter::DeltaReporter;
+
+  #[test]
+  fn test_delta_reporter_smoothing_converges() {
+    println!("--- BEGIN DELTA REPORTER SMOOTHING CONVERGES TEST ---");
+    let mut delta_reporter = DeltaReporter::new();
+    assert_eq!(delta_reporter.get_smoothed_delta(), 0.0);
+
+    // A big hitch, then a steady 60 FPS.
+    delta_reporter.smooth(0.5);
+    assert_eq!(delta_reporter.get_smoothed_delta(), 0.5);
+
+    let steady_delta = 1.0 / 60.0;
+    delta_reporter.smooth(steady_delta);
+    // Smoothing doesn't jump straight to the new value.
+    assert!(delta_reporter.get_smoothed_delta() > 0.4);
+
+    for _ in 0..200 {
+      delta_reporter.smooth(steady_delta);
+    }
+    assert!((delta_reporter.get_smoothed_delta() - steady_delta).abs() < 1e-6);
+  }
 }
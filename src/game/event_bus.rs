@@ -0,0 +1,140 @@
+use ahash::AHashMap;
+use glam::UVec2;
+
+use super::client::ConnectionStatus;
+
+///
+/// Things that happen inside of the engine that other parts of it
+/// might want to react to.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineEvent {
+  PlayerJoined(String),
+  ChatReceived { name: String, message: String },
+  WindowResized(UVec2),
+  ShutdownRequested,
+  // The Client's connection to the server moved along, see Client::connect.
+  ConnectionStatusChanged(ConnectionStatus),
+}
+
+///
+/// What kind of EngineEvent it is, without the data.
+///
+/// This is what you subscribe to.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EngineEventType {
+  PlayerJoined,
+  ChatReceived,
+  WindowResized,
+  ShutdownRequested,
+  ConnectionStatusChanged,
+}
+
+impl EngineEvent {
+  pub fn get_type(&self) -> EngineEventType {
+    match self {
+      EngineEvent::PlayerJoined(_) => EngineEventType::PlayerJoined,
+      EngineEvent::ChatReceived { .. } => EngineEventType::ChatReceived,
+      EngineEvent::WindowResized(_) => EngineEventType::WindowResized,
+      EngineEvent::ShutdownRequested => EngineEventType::ShutdownRequested,
+      EngineEvent::ConnectionStatusChanged(_) => EngineEventType::ConnectionStatusChanged,
+    }
+  }
+}
+
+///
+/// A callback that gets run when an EngineEvent is published.
+///
+pub type EventCallback = Box<dyn FnMut(&EngineEvent)>;
+
+///
+/// The EventBus lets parts of the engine react to each other without
+/// holding references to each other.
+///
+/// The Server and Client queue up EngineEvents during their tick, then
+/// the Game publishes them. Everything runs synchronously on the main
+/// thread, in the order it was subscribed.
+///
+#[derive(Default)]
+pub struct EventBus {
+  subscribers: AHashMap<EngineEventType, Vec<EventCallback>>,
+}
+
+impl EventBus {
+  pub fn new() -> Self {
+    EventBus {
+      subscribers: AHashMap::new(),
+    }
+  }
+
+  ///
+  /// Run a callback every time an event of this type is published.
+  ///
+  pub fn subscribe(
+    &mut self,
+    event_type: EngineEventType,
+    callback: impl FnMut(&EngineEvent) + 'static,
+  ) {
+    self
+      .subscribers
+      .entry(event_type)
+      .or_default()
+      .push(Box::new(callback));
+  }
+
+  ///
+  /// Send an event to everything subscribed to it's type.
+  ///
+  /// Returns how many subscribers got it.
+  ///
+  pub fn publish(&mut self, event: &EngineEvent) -> usize {
+    match self.subscribers.get_mut(&event.get_type()) {
+      Some(callbacks) => {
+        for callback in callbacks.iter_mut() {
+          callback(event);
+        }
+        callbacks.len()
+      }
+      None => 0,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, rc::Rc};
+
+  use glam::UVec2;
+
+  use crate::game::event_bus::{EngineEvent, EngineEventType, EventBus};
+
+  #[test]
+  fn test_event_bus_publish() {
+    println!("--- BEGIN EVENT BUS PUBLISH TEST ---");
+    let mut event_bus = EventBus::new();
+    let received = Rc::new(RefCell::new(vec![]));
+
+    for subscriber in ["fps_reporter", "ui"] {
+      let received = received.clone();
+      event_bus.subscribe(EngineEventType::WindowResized, move |event| {
+        received.borrow_mut().push((subscriber, event.clone()));
+      });
+    }
+    let chat_received = received.clone();
+    event_bus.subscribe(EngineEventType::ChatReceived, move |event| {
+      chat_received.borrow_mut().push(("chat", event.clone()));
+    });
+
+    let resized = EngineEvent::WindowResized(UVec2::new(800, 600));
+    assert_eq!(event_bus.publish(&resized), 2);
+    assert_eq!(
+      *received.borrow(),
+      vec![("fps_reporter", resized.clone()), ("ui", resized)]
+    );
+
+    // Nobody is listening.
+    assert_eq!(event_bus.publish(&EngineEvent::ShutdownRequested), 0);
+    assert_eq!(received.borrow().len(), 2);
+  }
+}
@@ -0,0 +1,162 @@
+use spin_sleep::{SpinSleeper, SpinStrategy};
+use spin_sleep_util::Interval;
+
+use super::game_config::GameConfig;
+
+///
+/// How long before the deadline YieldSleep stops sleeping and starts spinning. [nanoseconds]
+///
+/// The OS scheduler can oversleep by about this much.
+///
+const YIELD_SLEEP_SPIN_NANOS: u32 = 100_000;
+
+///
+/// How the main loop waits out the rest of a frame or tick.
+///
+/// PreciseSpin is spin_sleep's own accuracy, which on some platforms spins
+/// for milliseconds. YieldSleep hands nearly all of it to std::thread::sleep
+/// and only spins the last YIELD_SLEEP_SPIN_NANOS, yielding while it does.
+/// A little more jitter for a lot less CPU, nice on a laptop battery.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameLimitStrategy {
+  PreciseSpin,
+  YieldSleep,
+}
+
+impl FrameLimitStrategy {
+  ///
+  /// Read the strategy out of minetest.conf. Defaults to precise.
+  ///
+  /// server_frame_limit_strategy and client_frame_limit_strategy win over
+  /// frame_limit_strategy, so one minetest.conf can set both.
+  ///
+  pub fn from_config(config: &GameConfig, is_server: bool) -> Self {
+    let specific_key = match is_server {
+      true => "server_frame_limit_strategy",
+      false => "client_frame_limit_strategy",
+    };
+    let strategy = match config.get(specific_key) {
+      Some(strategy) => strategy.clone(),
+      None => config.get_string("frame_limit_strategy", "precise"),
+    };
+
+    match strategy.as_str() {
+      "precise" => FrameLimitStrategy::PreciseSpin,
+      "yield" => FrameLimitStrategy::YieldSleep,
+      unknown => {
+        println!(
+          "FrameLimiter: unknown frame limit strategy [{}]. Using precise.",
+          unknown
+        );
+        FrameLimitStrategy::PreciseSpin
+      }
+    }
+  }
+
+  ///
+  /// Get the sleeper that waits the way this strategy does.
+  ///
+  pub fn get_spin_sleeper(&self) -> SpinSleeper {
+    match self {
+      FrameLimitStrategy::PreciseSpin => SpinSleeper::default(),
+      FrameLimitStrategy::YieldSleep => {
+        SpinSleeper::new(YIELD_SLEEP_SPIN_NANOS).with_spin_strategy(SpinStrategy::YieldThread)
+      }
+    }
+  }
+
+  ///
+  /// Make an interval wait the way this strategy does.
+  ///
+  pub fn apply(&self, interval: Interval) -> Interval {
+    interval.with_spin_sleeper(self.get_spin_sleeper())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::{Duration, Instant};
+
+  use spin_sleep_util::interval;
+
+  use crate::game::{frame_limiter::FrameLimitStrategy, game_config::GameConfig};
+
+  #[test]
+  fn test_frame_limit_strategy_from_config() {
+    println!("--- BEGIN FRAME LIMIT STRATEGY FROM CONFIG TEST ---");
+    let strategy = |raw_config: &str, is_server: bool| {
+      FrameLimitStrategy::from_config(&GameConfig::parse(raw_config), is_server)
+    };
+
+    assert_eq!(strategy("", false), FrameLimitStrategy::PreciseSpin);
+    assert_eq!(
+      strategy("frame_limit_strategy = yield", true),
+      FrameLimitStrategy::YieldSleep
+    );
+    assert_eq!(
+      strategy("frame_limit_strategy = sometimes", false),
+      FrameLimitStrategy::PreciseSpin
+    );
+
+    // Client and server can differ.
+    let split = "frame_limit_strategy = yield\nclient_frame_limit_strategy = precise";
+    assert_eq!(strategy(split, true), FrameLimitStrategy::YieldSleep);
+    assert_eq!(strategy(split, false), FrameLimitStrategy::PreciseSpin);
+  }
+
+  ///
+  /// Get how much CPU time this thread has used, from /proc. Linux only.
+  ///
+  fn get_thread_cpu_time() -> Option<Duration> {
+    let stat = std::fs::read_to_string("/proc/thread-self/stat").ok()?;
+    // The command name can have spaces in it, everything after the ) is fixed.
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    // utime and stime, in clock ticks at the usual 100 Hz.
+    let ticks = fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?;
+    Some(Duration::from_millis(ticks * 10))
+  }
+
+  ///
+  /// Run with: cargo test --release bench_frame_limit -- --ignored --nocapture
+  ///
+  #[test]
+  #[ignore]
+  fn bench_frame_limit_strategies() {
+    println!("--- BEGIN FRAME LIMIT STRATEGIES BENCHMARK ---");
+    const FRAMES: u32 = 300;
+    let period = Duration::from_secs_f64(1.0 / 60.0);
+
+    for strategy in [
+      FrameLimitStrategy::PreciseSpin,
+      FrameLimitStrategy::YieldSleep,
+    ] {
+      let mut ticker = strategy.apply(interval(period));
+      let cpu_start = get_thread_cpu_time();
+      let wall_start = Instant::now();
+      let mut worst_lateness = Duration::ZERO;
+
+      for frame in 1..=FRAMES {
+        ticker.tick();
+        let lateness = wall_start.elapsed().saturating_sub(period * frame);
+        worst_lateness = worst_lateness.max(lateness);
+      }
+
+      let wall = wall_start.elapsed();
+      match (cpu_start, get_thread_cpu_time()) {
+        (Some(cpu_start), Some(cpu_end)) => println!(
+          "{:?}: [{}] frames in [{:.2?}], [{:.1}]% CPU, worst lateness [{:.2?}]",
+          strategy,
+          FRAMES,
+          wall,
+          (cpu_end - cpu_start).as_secs_f64() / wall.as_secs_f64() * 100.0,
+          worst_lateness
+        ),
+        _ => println!(
+          "{:?}: [{}] frames in [{:.2?}], CPU time is not available here, worst lateness [{:.2?}]",
+          strategy, FRAMES, wall, worst_lateness
+        ),
+      }
+    }
+  }
+}
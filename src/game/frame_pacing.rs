@@ -0,0 +1,241 @@
+use std::collections::VecDeque;
+
+use super::time_step::TimeStep;
+
+///
+/// How many of the latest frame intervals are kept. (~5 seconds at 60 FPS)
+///
+const MAX_SAMPLES: usize = 300;
+
+///
+/// How far past the target period a frame can run before it counts
+/// as a missed deadline. 0.1 = 10% late.
+///
+/// Timers are never perfect, this keeps tiny jitter from counting.
+///
+const DEADLINE_TOLERANCE: f64 = 0.1;
+
+///
+/// Missing more frames than this [%] is a pacing problem,
+/// not a hiccup.
+///
+const CONSISTENTLY_MISSED_PERCENT: f64 = 10.0;
+
+///
+/// If the work alone takes longer than the target period in more than
+/// this [%] of frames, no amount of sleeping can hit the cap.
+///
+const WORK_BOUND_PERCENT: f64 = 50.0;
+
+///
+/// A summary of how well the frame intervals match the target.
+///
+/// The interval is the whole frame, sleep included. The work is
+/// only the part before the sleep.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PacingReport {
+  pub target_period: TimeStep,
+  pub mean_interval: TimeStep,
+  pub stddev_interval: TimeStep,
+  pub missed_deadline_percent: f64,
+  pub sample_count: usize,
+  pub mean_work: TimeStep,
+  pub over_budget_percent: f64,
+}
+
+impl PacingReport {
+  ///
+  /// Check if frames keep coming in late.
+  ///
+  /// This usually means the interval isn't being honored, or
+  /// vsync is fighting the frame cap. Try another vsync mode.
+  ///
+  pub fn is_consistently_missing(&self) -> bool {
+    self.sample_count > 0 && self.missed_deadline_percent > CONSISTENTLY_MISSED_PERCENT
+  }
+
+  ///
+  /// Check if the frames themselves are too slow for the target.
+  ///
+  /// This means the game is CPU or GPU bound. The interval returns
+  /// right away and the cap can't be hit, lower the settings or the cap.
+  ///
+  pub fn is_work_bound(&self) -> bool {
+    self.over_budget_percent > WORK_BOUND_PERCENT
+  }
+}
+
+///
+/// FramePacing records the real time between frames so micro-stutter
+/// can be diagnosed.
+///
+/// The FPS counter only says how many frames there were, this says
+/// how evenly they came.
+///
+pub struct FramePacing {
+  intervals: VecDeque<f64>,
+  work_times: VecDeque<f64>,
+}
+
+impl FramePacing {
+  pub fn new() -> Self {
+    FramePacing {
+      intervals: VecDeque::with_capacity(MAX_SAMPLES),
+      work_times: VecDeque::with_capacity(MAX_SAMPLES),
+    }
+  }
+
+  ///
+  /// Keep the newest MAX_SAMPLES.
+  ///
+  fn push_sample(samples: &mut VecDeque<f64>, sample: TimeStep) {
+    if samples.len() >= MAX_SAMPLES {
+      samples.pop_front();
+    }
+    samples.push_back(sample.as_secs_f64());
+  }
+
+  ///
+  /// Record the time one frame actually took.
+  ///
+  pub fn record(&mut self, interval: TimeStep) {
+    Self::push_sample(&mut self.intervals, interval);
+  }
+
+  ///
+  /// Record how long one frame's work took, before it slept.
+  ///
+  pub fn record_work(&mut self, work: TimeStep) {
+    Self::push_sample(&mut self.work_times, work);
+  }
+
+  ///
+  /// Get the mean work time, and how many frames [%] went over the period.
+  ///
+  fn get_work_stats(&self, target_period: TimeStep) -> (TimeStep, f64) {
+    if self.work_times.is_empty() {
+      return (TimeStep::ZERO, 0.0);
+    }
+    let count = self.work_times.len() as f64;
+    let mean = self.work_times.iter().sum::<f64>() / count;
+    let over_budget = self
+      .work_times
+      .iter()
+      .filter(|work| **work > target_period.as_secs_f64())
+      .count();
+    (
+      TimeStep::from_secs_f64(mean),
+      over_budget as f64 / count * 100.0,
+    )
+  }
+
+  ///
+  /// Compare the recorded intervals against the target period.
+  ///
+  pub fn report(&self, target_period: TimeStep) -> PacingReport {
+    let (mean_work, over_budget_percent) = self.get_work_stats(target_period);
+
+    let sample_count = self.intervals.len();
+    if sample_count == 0 {
+      return PacingReport {
+        target_period,
+        mean_interval: TimeStep::ZERO,
+        stddev_interval: TimeStep::ZERO,
+        missed_deadline_percent: 0.0,
+        sample_count,
+        mean_work,
+        over_budget_percent,
+      };
+    }
+
+    let count = sample_count as f64;
+    let mean = self.intervals.iter().sum::<f64>() / count;
+    let variance = self
+      .intervals
+      .iter()
+      .map(|interval| (interval - mean).powi(2))
+      .sum::<f64>()
+      / count;
+
+    let deadline = target_period.as_secs_f64() * (1.0 + DEADLINE_TOLERANCE);
+    let missed = self
+      .intervals
+      .iter()
+      .filter(|interval| **interval > deadline)
+      .count();
+
+    PacingReport {
+      target_period,
+      mean_interval: TimeStep::from_secs_f64(mean),
+      stddev_interval: TimeStep::from_secs_f64(variance.sqrt()),
+      missed_deadline_percent: missed as f64 / count * 100.0,
+      sample_count,
+      mean_work,
+      over_budget_percent,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::game::{frame_pacing::FramePacing, time_step::TimeStep};
+
+  #[test]
+  fn test_frame_pacing_missed_deadlines() {
+    println!("--- BEGIN FRAME PACING MISSED DEADLINES TEST ---");
+    let target_period = TimeStep::from_millis(1000.0 / 60.0);
+    let mut frame_pacing = FramePacing::new();
+
+    let report = frame_pacing.report(target_period);
+    assert_eq!(report.sample_count, 0);
+    assert!(!report.is_consistently_missing());
+
+    // Slightly late frames are within tolerance.
+    for _ in 0..15 {
+      frame_pacing.record(TimeStep::from_millis(17.0));
+    }
+    // Every 4th frame waits for the next vblank.
+    for _ in 0..5 {
+      frame_pacing.record(TimeStep::from_millis(33.3));
+    }
+
+    let report = frame_pacing.report(target_period);
+    assert_eq!(report.sample_count, 20);
+    assert_eq!(report.target_period, target_period);
+    assert!((report.missed_deadline_percent - 25.0).abs() < 1e-9);
+    assert!(report.is_consistently_missing());
+    assert!((report.mean_interval.as_millis() - 21.075).abs() < 1e-9);
+    assert!(report.stddev_interval.as_millis() > 7.0);
+
+    // Old samples fall off.
+    for _ in 0..1000 {
+      frame_pacing.record(TimeStep::from_millis(16.6));
+    }
+    let report = frame_pacing.report(target_period);
+    assert_eq!(report.missed_deadline_percent, 0.0);
+    assert!(report.stddev_interval.as_millis() < 1e-9);
+  }
+
+  #[test]
+  fn test_frame_pacing_work_bound() {
+    println!("--- BEGIN FRAME PACING WORK BOUND TEST ---");
+    let target_period = TimeStep::from_millis(1000.0 / 60.0);
+    let mut frame_pacing = FramePacing::new();
+
+    // Plenty of room to sleep.
+    for _ in 0..10 {
+      frame_pacing.record_work(TimeStep::from_millis(5.0));
+    }
+    assert!(!frame_pacing.report(target_period).is_work_bound());
+
+    // Now every frame takes longer than the period, before sleeping.
+    for _ in 0..30 {
+      frame_pacing.record_work(TimeStep::from_millis(25.0));
+    }
+    let report = frame_pacing.report(target_period);
+    assert!((report.over_budget_percent - 75.0).abs() < 1e-9);
+    assert!((report.mean_work.as_millis() - 20.0).abs() < 1e-9);
+    assert!(report.is_work_bound());
+  }
+}
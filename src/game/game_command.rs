@@ -0,0 +1,37 @@
+use std::sync::mpsc::Sender;
+
+use super::VSyncMode;
+
+///
+/// Where the output of a command gets sent back to.
+///
+/// A channel has nothing to compare, so two of these are never equal.
+///
+#[derive(Debug, Clone)]
+pub struct CommandReply(pub Sender<String>);
+
+impl PartialEq for CommandReply {
+  fn eq(&self, _: &Self) -> bool {
+    false
+  }
+}
+
+///
+/// Commands that can be sent to the Game from any thread.
+///
+/// Game is single threaded. Instead of reaching into it, other threads
+/// send these through the Sender from Game::command_sender() and
+/// Game::main applies them at the start of the next frame.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameCommand {
+  Shutdown,
+  SetFpsTarget(f64),
+  SetVSync(VSyncMode),
+  // Clears out and rebuilds the LuaEngine.
+  Reload,
+  // A line typed into the ServerConsole.
+  ConsoleCommand(String),
+  // A line sent to the RemoteConsole, the output goes back through the reply.
+  RemoteCommand(String, CommandReply),
+}
@@ -0,0 +1,509 @@
+use std::{
+  fs::canonicalize,
+  path::{Path, PathBuf},
+  str::FromStr,
+};
+
+use ahash::AHashMap;
+use glam::Vec3A;
+
+use crate::file_utilities::{file_exists, read_file_to_string, write_file_atomic};
+
+///
+/// GameConfig is the minetest.conf parser and storage.
+///
+/// minetest.conf is a flat list of key = value pairs.
+/// Lines starting with # are comments.
+///
+/// #include <path> pulls in another conf file right there, relative to the
+/// file it's in. Whatever comes later wins, so settings after an include
+/// override it's ones. A missing include or one that loops back is warned
+/// about and skipped.
+///
+/// Everything is stored as a raw String, the getters parse it into
+/// whatever type you ask for. If a key is missing or malformed you
+/// get the default you passed in.
+///
+#[derive(Debug, Clone, Default)]
+pub struct GameConfig {
+  values: AHashMap<String, String>,
+  // If a file had an #include, see save().
+  has_includes: bool,
+}
+
+impl GameConfig {
+  pub fn new() -> Self {
+    GameConfig {
+      values: AHashMap::new(),
+      has_includes: false,
+    }
+  }
+
+  ///
+  /// Load up a minetest.conf file.
+  ///
+  /// A missing file is not an error, you simply get the defaults.
+  ///
+  pub fn load(path: &str) -> Self {
+    if !file_exists(path) {
+      println!("GameConfig: [{}] does not exist. Using defaults.", path);
+      return GameConfig::new();
+    }
+
+    let (config, warnings) = GameConfig::load_with_warnings(path);
+    for warning in warnings {
+      println!("GameConfig: {}", warning);
+    }
+    println!("GameConfig: parsed [{}].", path);
+    config
+  }
+
+  ///
+  /// load() but the warnings are handed back instead of printed.
+  ///
+  pub fn load_with_warnings(path: &str) -> (Self, Vec<String>) {
+    let mut config = GameConfig::new();
+    let mut warnings = vec![];
+    if !file_exists(path) {
+      warnings.push(format!("[{}] does not exist. Using defaults.", path));
+      return (config, warnings);
+    }
+    config.include(Path::new(path), true, &mut vec![], &mut warnings);
+    (config, warnings)
+  }
+
+  ///
+  /// Parse the raw text of a minetest.conf file.
+  ///
+  /// A key that's set more than once gets the last value, with a warning.
+  ///
+  pub fn parse(raw_config: &str) -> Self {
+    let (config, warnings) = GameConfig::parse_with_warnings(raw_config);
+    for warning in warnings {
+      println!("GameConfig: {}", warning);
+    }
+    config
+  }
+
+  ///
+  /// parse() but the warnings are handed back instead of printed.
+  ///
+  /// There's no file to be relative to, so #include lines are skipped with a warning.
+  ///
+  pub fn parse_with_warnings(raw_config: &str) -> (Self, Vec<String>) {
+    let mut config = GameConfig::new();
+    let mut warnings = vec![];
+    config.parse_into(raw_config, None, &mut vec![], &mut warnings);
+    (config, warnings)
+  }
+
+  ///
+  /// Parse a conf file into this config, on top of what's already there.
+  ///
+  /// is_root is for the file load() was asked for, it's the only one that has to be readable.
+  /// chain is every file that's being included right now, to catch loops.
+  ///
+  fn include(
+    &mut self,
+    path: &Path,
+    is_root: bool,
+    chain: &mut Vec<PathBuf>,
+    warnings: &mut Vec<String>,
+  ) {
+    let full_path = match canonicalize(path) {
+      Ok(full_path) => full_path,
+      Err(e) => {
+        warnings.push(format!(
+          "can't include [{}], skipping it. {}",
+          path.display(),
+          e
+        ));
+        return;
+      }
+    };
+    if chain.contains(&full_path) {
+      warnings.push(format!(
+        "[{}] is already being included, skipping it so it doesn't loop.",
+        path.display()
+      ));
+      return;
+    }
+
+    let raw_config = match read_file_to_string(&full_path.to_string_lossy()) {
+      Ok(raw_config) => raw_config,
+      Err(e) => {
+        if is_root {
+          panic!("GameConfig: {}", e);
+        }
+        warnings.push(format!(
+          "can't include [{}], skipping it. {}",
+          path.display(),
+          e
+        ));
+        return;
+      }
+    };
+
+    let directory = match full_path.parent() {
+      Some(directory) => directory.to_path_buf(),
+      None => PathBuf::from("."),
+    };
+    chain.push(full_path);
+    self.parse_into(&raw_config, Some(&directory), chain, warnings);
+    chain.pop();
+  }
+
+  ///
+  /// Parse raw conf text into this config, on top of what's already there.
+  ///
+  /// Includes are relative to directory. Without one they're skipped.
+  ///
+  fn parse_into(
+    &mut self,
+    raw_config: &str,
+    directory: Option<&Path>,
+    chain: &mut Vec<PathBuf>,
+    warnings: &mut Vec<String>,
+  ) {
+    // Where each key was last set in this file, and to what, to point at both lines of a duplicate.
+    // An include setting it again is meant to, that's not a duplicate.
+    let mut set_on_line: AHashMap<String, (usize, String)> = AHashMap::new();
+
+    for (line_number, raw_line) in raw_config.lines().enumerate() {
+      let line = raw_line.trim();
+      let line_number = line_number + 1;
+
+      // #included is just a comment.
+      let include = line
+        .strip_prefix("#include")
+        .filter(|include| include.is_empty() || include.starts_with(char::is_whitespace));
+      if let Some(include) = include {
+        let include = include.trim();
+        let include = include
+          .strip_prefix('<')
+          .and_then(|include| include.strip_suffix('>'))
+          .unwrap_or(include)
+          .trim_matches('"');
+        if include.is_empty() {
+          warnings.push(format!(
+            "ignoring #include with no path on line [{}].",
+            line_number
+          ));
+          continue;
+        }
+        match directory {
+          Some(directory) => {
+            self.has_includes = true;
+            self.include(&directory.join(include), false, chain, warnings)
+          }
+          None => warnings.push(format!(
+            "ignoring #include on line [{}], includes only work in a file.",
+            line_number
+          )),
+        }
+        continue;
+      }
+
+      // Blank lines and comments.
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let (key, value) = match line.split_once('=') {
+        Some((key, value)) => (key.trim(), value.trim()),
+        None => {
+          warnings.push(format!(
+            "ignoring malformed line [{}]: {}",
+            line_number, line
+          ));
+          continue;
+        }
+      };
+
+      if let Some((previous_line, previous_value)) = set_on_line.get(key) {
+        warnings.push(format!(
+          "[{}] is set twice, to [{}] on line [{}] and to [{}] on line [{}]. The last one wins.",
+          key, previous_value, previous_line, value, line_number
+        ));
+      }
+
+      set_on_line.insert(key.to_owned(), (line_number, value.to_owned()));
+      self.set(key, value);
+    }
+  }
+
+  ///
+  /// Turn the config back into minetest.conf text.
+  ///
+  /// Keys are sorted so the file doesn't shuffle around on every save.
+  /// ! Comments from the original file are not kept !
+  /// ! Included files get flattened into this one, save() won't do that !
+  ///
+  pub fn to_conf_string(&self) -> String {
+    let mut keys: Vec<&String> = self.values.keys().collect();
+    keys.sort();
+
+    let mut raw_config = String::new();
+    for key in keys {
+      raw_config.push_str(&format!("{} = {}\n", key, self.values[key]));
+    }
+    raw_config
+  }
+
+  ///
+  /// Write the config out to a file.
+  ///
+  /// A config loaded with an #include can't be saved, the include lines would be lost.
+  ///
+  pub fn save(&self, path: &str) -> Result<(), String> {
+    if self.has_includes {
+      return Err(format!(
+        "Not saving [{}], it was loaded with #include and they would get flattened into it.",
+        path
+      ));
+    }
+    write_file_atomic(path, self.to_conf_string().as_bytes())
+  }
+
+  ///
+  /// Set a value in the config.
+  ///
+  pub fn set(&mut self, key: &str, value: &str) {
+    self.values.insert(key.to_owned(), value.to_owned());
+  }
+
+  ///
+  /// Get the raw String value of a key.
+  ///
+  pub fn get(&self, key: &str) -> Option<&String> {
+    self.values.get(key)
+  }
+
+  ///
+  /// Iterate over every raw key and value, in no particular order.
+  ///
+  pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+    self.values.iter()
+  }
+
+  ///
+  /// Check if the config has a key.
+  ///
+  pub fn has(&self, key: &str) -> bool {
+    self.values.contains_key(key)
+  }
+
+  ///
+  /// Get a String value, or the default if it's missing.
+  ///
+  pub fn get_string(&self, key: &str, default: &str) -> String {
+    match self.get(key) {
+      Some(value) => value.clone(),
+      None => default.to_owned(),
+    }
+  }
+
+  ///
+  /// Get a boolean value, or the default if it's missing or malformed.
+  ///
+  pub fn get_bool(&self, key: &str, default: bool) -> bool {
+    match self.get(key).map(|value| value.as_str()) {
+      Some("true") | Some("1") => true,
+      Some("false") | Some("0") => false,
+      Some(value) => {
+        println!(
+          "GameConfig: [{}] is not a boolean, got [{}]. Using default [{}].",
+          key, value, default
+        );
+        default
+      }
+      None => default,
+    }
+  }
+
+  ///
+  /// Get any numeric (or FromStr) value, or the default if it's missing or malformed.
+  ///
+  pub fn get_parsed<T: FromStr + std::fmt::Display>(&self, key: &str, default: T) -> T {
+    match self.get(key) {
+      Some(value) => match value.parse::<T>() {
+        Ok(parsed) => parsed,
+        Err(_) => {
+          println!(
+            "GameConfig: [{}] is malformed, got [{}]. Using default [{}].",
+            key, value, default
+          );
+          default
+        }
+      },
+      None => default,
+    }
+  }
+
+  ///
+  /// Get a position, written like (x, y, z) or x,y,z. None if it's missing or malformed.
+  ///
+  pub fn get_pos(&self, key: &str) -> Option<Vec3A> {
+    let value = self.get(key)?;
+    let parts: Vec<Result<f32, _>> = value
+      .trim()
+      .trim_start_matches('(')
+      .trim_end_matches(')')
+      .split(',')
+      .map(|part| part.trim().parse::<f32>())
+      .collect();
+    match parts.as_slice() {
+      [Ok(x), Ok(y), Ok(z)] => Some(Vec3A::new(*x, *y, *z)),
+      _ => {
+        println!(
+          "GameConfig: [{}] is not a position, got [{}]. Expected (x, y, z).",
+          key, value
+        );
+        None
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::{create_dir_all, remove_dir_all, write};
+
+  use glam::Vec3A;
+
+  use crate::{file_utilities::test_dir, game::game_config::GameConfig};
+
+  #[test]
+  fn test_game_config_parse() {
+    println!("--- BEGIN GAME CONFIG PARSE TEST ---");
+    let config = GameConfig::parse(
+      "# A comment.
+      server_name = My Server
+      max_users=15
+
+      enable_damage = false
+      motd = Hello = world
+      this line is broken",
+    );
+
+    assert_eq!(config.get_string("server_name", ""), "My Server");
+    assert_eq!(config.get_parsed::<u32>("max_users", 0), 15);
+    assert!(!config.get_bool("enable_damage", true));
+    // Only the first = splits.
+    assert_eq!(config.get_string("motd", ""), "Hello = world");
+    // Missing and malformed keys fall back to the default.
+    assert_eq!(config.get_parsed::<u32>("server_name", 7), 7);
+    assert!(!config.has("this line is broken"));
+
+    // Positions, with or without the brackets.
+    let config_with_pos = GameConfig::parse("spawn = (1, -2.5, 3)\nhome = 4,5,6\nbad = 1,2");
+    assert_eq!(
+      config_with_pos.get_pos("spawn"),
+      Some(Vec3A::new(1.0, -2.5, 3.0))
+    );
+    assert_eq!(
+      config_with_pos.get_pos("home"),
+      Some(Vec3A::new(4.0, 5.0, 6.0))
+    );
+    assert_eq!(config_with_pos.get_pos("bad"), None);
+    assert_eq!(config_with_pos.get_pos("missing"), None);
+
+    // Survives a round trip.
+    let round_trip = GameConfig::parse(&config.to_conf_string());
+    assert_eq!(round_trip.get_string("motd", ""), "Hello = world");
+    assert_eq!(round_trip.get_parsed::<u32>("max_users", 0), 15);
+  }
+
+  #[test]
+  fn test_game_config_duplicate_keys() {
+    println!("--- BEGIN GAME CONFIG DUPLICATE KEYS TEST ---");
+    let (config, warnings) = GameConfig::parse_with_warnings(
+      "max_users = 15
+      motd = hi
+      max_users = 30",
+    );
+
+    // The last one wins, and both values get mentioned.
+    assert_eq!(config.get_parsed::<u32>("max_users", 0), 30);
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("[max_users]"));
+    assert!(warnings[0].contains("[15] on line [1]"));
+    assert!(warnings[0].contains("[30] on line [3]"));
+
+    // Text isn't a file, there's nothing for an include to be relative to.
+    let (config, warnings) =
+      GameConfig::parse_with_warnings("#include <minetest.conf>\nmax_users = 3");
+    assert_eq!(config.get_parsed::<u32>("max_users", 0), 3);
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("only work in a file"));
+
+    // Nothing twice, nothing to say.
+    let (_, warnings) = GameConfig::parse_with_warnings("max_users = 15\nmotd = hi");
+    assert!(warnings.is_empty());
+  }
+
+  #[test]
+  fn test_game_config_include() {
+    println!("--- BEGIN GAME CONFIG INCLUDE TEST ---");
+    let directory = test_dir("game_config_include");
+    if let Err(e) = create_dir_all(format!("{}/conf.d", directory)) {
+      panic!("Unit test is broken. {}", e);
+    }
+    let write = |name: &str, raw_config: &str| {
+      if let Err(e) = write(format!("{}/{}", directory, name), raw_config) {
+        panic!("Unit test is broken. {}", e);
+      }
+    };
+    // conf.d/network.conf is relative to minetest.conf, limits.conf to network.conf.
+    write(
+      "minetest.conf",
+      "server_name = Base\nmax_users = 10\n#include <conf.d/network.conf>\nmax_users = 50\n#include missing.conf\n#include conf.d",
+    );
+    write(
+      "conf.d/network.conf",
+      "port = 30005\nserver_name = Included\nmax_users = 20\n#include limits.conf",
+    );
+    // Loops back around to the file including it.
+    write(
+      "conf.d/limits.conf",
+      "mapgen_limit = 1000\n#include \"network.conf\"",
+    );
+
+    let path = format!("{}/minetest.conf", directory);
+    let (config, warnings) = GameConfig::load_with_warnings(&path);
+
+    // Included settings win over the ones before, the ones after win over them.
+    assert_eq!(config.get_string("server_name", ""), "Included");
+    assert_eq!(config.get_parsed::<u32>("max_users", 0), 50);
+    assert_eq!(config.get_parsed::<u16>("port", 0), 30005);
+    assert_eq!(config.get_parsed::<f32>("mapgen_limit", 0.0), 1000.0);
+
+    // The loop, the missing file and the unreadable one get skipped. Overriding
+    // an include isn't a duplicate, but setting it twice in the same file still is.
+    assert_eq!(warnings.len(), 4, "{:?}", warnings);
+    assert!(warnings[0].contains("network.conf") && warnings[0].contains("loop"));
+    assert!(warnings[1].contains("[max_users] is set twice"));
+    assert!(warnings[2].contains("missing.conf"));
+    assert!(warnings[3].contains("conf.d"));
+
+    // Saving would lose the include lines.
+    assert!(config.save(&path).is_err());
+    match GameConfig::parse("max_users = 5").save(&path) {
+      Ok(_) => assert_eq!(GameConfig::load(&path).get_parsed::<u32>("max_users", 0), 5),
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+
+    // Only #include followed by a space is an include, the rest are comments.
+    write(
+      "comments.conf",
+      "#included by hand\n#includes <missing.conf>\nmax_users = 7",
+    );
+    let path = format!("{}/comments.conf", directory);
+    let (config, warnings) = GameConfig::load_with_warnings(&path);
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(config.get_parsed::<u32>("max_users", 0), 7);
+    assert!(config.save(&path).is_ok());
+
+    let _ = remove_dir_all(&directory);
+  }
+}
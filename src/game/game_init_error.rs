@@ -0,0 +1,88 @@
+use std::fmt;
+
+use super::{
+  client::render_engine::render_init_error::RenderInitError,
+  server::server_connection::ConnectionError, settings::SettingsError,
+};
+
+///
+/// Why the Game couldn't start.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameInitError {
+  // The command line options don't make sense together.
+  CommandLine(String),
+  // The server couldn't bind.
+  Connection(ConnectionError),
+  // The client couldn't render.
+  Render(RenderInitError),
+  // minetest.conf has values that don't make sense.
+  Settings(Vec<SettingsError>),
+  // --record couldn't create it's file, or --replay couldn't load one.
+  Replay(String),
+  // --strict found missing assets or broken mods, see StartupReport.
+  Startup(Vec<String>),
+  // The world can't be opened, like when it's map backend isn't supported.
+  World(String),
+  // The game's directory isn't there, or is missing game.conf or mods/.
+  Game {
+    game: String,
+    path: String,
+    missing: Vec<String>,
+  },
+}
+
+impl fmt::Display for GameInitError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      GameInitError::CommandLine(e) => write!(f, "{} See --help.", e),
+      GameInitError::Connection(e) => write!(f, "{}", e),
+      GameInitError::Render(e) => write!(f, "{}", e),
+      GameInitError::Replay(e) => write!(f, "{}", e),
+      GameInitError::World(e) => write!(f, "{}", e),
+      GameInitError::Game {
+        game,
+        path,
+        missing,
+      } => write!(
+        f,
+        "Game [{}] is not installed. Looked in [{}], missing [{}]. Install it or pick another with --game.",
+        game,
+        path,
+        missing.join(", ")
+      ),
+      GameInitError::Startup(problems) => {
+        write!(f, "Startup checks failed.")?;
+        for problem in problems {
+          write!(f, " {}", problem)?;
+        }
+        Ok(())
+      }
+      GameInitError::Settings(errors) => {
+        write!(f, "minetest.conf is invalid.")?;
+        for error in errors {
+          write!(f, " {}", error)?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+impl From<ConnectionError> for GameInitError {
+  fn from(error: ConnectionError) -> Self {
+    GameInitError::Connection(error)
+  }
+}
+
+impl From<Vec<SettingsError>> for GameInitError {
+  fn from(errors: Vec<SettingsError>) -> Self {
+    GameInitError::Settings(errors)
+  }
+}
+
+impl From<RenderInitError> for GameInitError {
+  fn from(error: RenderInitError) -> Self {
+    GameInitError::Render(error)
+  }
+}
@@ -0,0 +1,528 @@
+use std::{
+  fs::{self, File, OpenOptions},
+  io::{self, Write},
+  path::Path,
+};
+#[cfg(unix)]
+use std::{
+  os::fd::RawFd,
+  sync::{Arc, Mutex},
+  thread::{self, JoinHandle},
+};
+
+#[cfg(unix)]
+use nix::{
+  errno::Errno,
+  unistd::{close, dup, dup2, pipe, read, write},
+};
+
+use crate::file_utilities::{create_dir_all, file_exists};
+
+use super::game_config::GameConfig;
+
+///
+/// How big the log gets before it's rotated, unless minetest.conf says debug_log_size_max. [MB]
+///
+pub const DEFAULT_LOG_SIZE_MAX_MB: u64 = 50;
+
+///
+/// How many rotated out logs are kept, unless minetest.conf says debug_log_file_count.
+///
+pub const DEFAULT_LOG_FILE_COUNT: usize = 5;
+
+///
+/// A log file that moves itself out of the way when it gets too big.
+///
+/// debug.txt rotates into debug.txt.1, debug.txt.1 into debug.txt.2 and so on.
+/// Only the newest kept_files of those stick around.
+///
+/// Nothing is ever cut in half, a write that would go over the limit
+/// goes into a fresh file instead.
+///
+pub struct RotatingLogFile {
+  path: String,
+  size_max: u64,
+  kept_files: usize,
+  file: File,
+  size: u64,
+}
+
+impl RotatingLogFile {
+  ///
+  /// Open a log file, appending to it if it's already there. size_max is in bytes.
+  ///
+  pub fn new(path: &str, size_max: u64, kept_files: usize) -> Result<Self, String> {
+    let file = Self::open(path)?;
+    let size = match file.metadata() {
+      Ok(metadata) => metadata.len(),
+      Err(e) => return Err(format!("RotatingLogFile: failed to read [{}]. {}", path, e)),
+    };
+    Ok(RotatingLogFile {
+      path: path.to_owned(),
+      size_max,
+      kept_files,
+      file,
+      size,
+    })
+  }
+
+  fn open(path: &str) -> Result<File, String> {
+    match OpenOptions::new().create(true).append(true).open(path) {
+      Ok(file) => Ok(file),
+      Err(e) => Err(format!("RotatingLogFile: failed to open [{}]. {}", path, e)),
+    }
+  }
+
+  ///
+  /// Get the path of a rotated out log, 1 is the newest.
+  ///
+  pub fn get_rotated_path(&self, index: usize) -> String {
+    format!("{}.{}", self.path, index)
+  }
+
+  ///
+  /// Shift every old log back one, and start a fresh one.
+  ///
+  /// A rename never leaves a half written log behind, so this is safe to crash in.
+  ///
+  fn rotate(&mut self) -> Result<(), String> {
+    if let Err(e) = self.file.flush() {
+      return Err(format!("RotatingLogFile: failed to flush. {}", e));
+    }
+
+    if self.kept_files == 0 {
+      if let Err(e) = fs::remove_file(&self.path) {
+        return Err(format!(
+          "RotatingLogFile: failed to remove the old log. {}",
+          e
+        ));
+      }
+    } else {
+      let oldest = self.get_rotated_path(self.kept_files);
+      if file_exists(&oldest) {
+        if let Err(e) = fs::remove_file(&oldest) {
+          return Err(format!(
+            "RotatingLogFile: failed to remove [{}]. {}",
+            oldest, e
+          ));
+        }
+      }
+      for index in (1..self.kept_files).rev() {
+        let from = self.get_rotated_path(index);
+        if file_exists(&from) {
+          if let Err(e) = fs::rename(&from, self.get_rotated_path(index + 1)) {
+            return Err(format!(
+              "RotatingLogFile: failed to rotate [{}]. {}",
+              from, e
+            ));
+          }
+        }
+      }
+      if let Err(e) = fs::rename(&self.path, self.get_rotated_path(1)) {
+        return Err(format!("RotatingLogFile: failed to rotate the log. {}", e));
+      }
+    }
+
+    self.file = Self::open(&self.path)?;
+    self.size = 0;
+    Ok(())
+  }
+}
+
+impl Write for RotatingLogFile {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    if self.size > 0 && self.size + buf.len() as u64 > self.size_max {
+      if let Err(e) = self.rotate() {
+        // Keep logging into the big file, losing logs is worse.
+        eprintln!("{}", e);
+      }
+    }
+    let written = self.file.write(buf)?;
+    self.size += written as u64;
+    Ok(written)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.file.flush()
+  }
+}
+
+///
+/// Sends everything printed to stdout and stderr into a RotatingLogFile too.
+///
+/// Almost everything in the engine is a println!, not a log record, so
+/// tapping env_logger alone would leave the log file nearly empty.
+/// Instead stdout and stderr are swapped for pipes, and a thread per pipe
+/// copies what comes out to the terminal and the log file.
+///
+/// Dropping it puts stdout and stderr back, and waits until everything
+/// printed so far has been written.
+///
+#[cfg(unix)]
+pub struct StdioCapture {
+  // (the fd that was swapped out, a copy of the original to put back)
+  streams: Vec<(RawFd, RawFd)>,
+  threads: Vec<JoinHandle<()>>,
+}
+
+#[cfg(unix)]
+impl StdioCapture {
+  pub fn start(file: RotatingLogFile) -> Result<Self, String> {
+    let file = Arc::new(Mutex::new(file));
+    let mut capture = StdioCapture {
+      streams: vec![],
+      threads: vec![],
+    };
+    // If stderr fails stdout is already captured, the drop puts it back.
+    for target in [1, 2] {
+      capture.capture(target, file.clone())?;
+    }
+    Ok(capture)
+  }
+
+  ///
+  /// Swap one fd for a pipe, and start copying out of it.
+  ///
+  fn capture(&mut self, target: RawFd, file: Arc<Mutex<RotatingLogFile>>) -> Result<(), String> {
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+
+    let fail = |e: Errno| format!("StdioCapture: failed to capture fd [{}]. {}", target, e);
+    let original = dup(target).map_err(fail)?;
+    let terminal = match dup(original) {
+      Ok(terminal) => terminal,
+      Err(e) => {
+        let _ = close(original);
+        return Err(fail(e));
+      }
+    };
+    let (read_end, write_end) = match pipe() {
+      Ok(ends) => ends,
+      Err(e) => {
+        let _ = close(original);
+        let _ = close(terminal);
+        return Err(fail(e));
+      }
+    };
+    let swapped = dup2(write_end, target);
+    // target is the only write end from here on, so it closing means EOF.
+    let _ = close(write_end);
+    if let Err(e) = swapped {
+      let _ = close(original);
+      let _ = close(terminal);
+      let _ = close(read_end);
+      return Err(fail(e));
+    }
+
+    self.streams.push((target, original));
+    self
+      .threads
+      .push(thread::spawn(move || copy_out(read_end, terminal, &file)));
+    Ok(())
+  }
+}
+
+#[cfg(unix)]
+impl Drop for StdioCapture {
+  fn drop(&mut self) {
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    for (target, original) in self.streams.drain(..) {
+      // Putting the original back closes the pipe's write end.
+      let _ = dup2(original, target);
+      let _ = close(original);
+    }
+    for thread in self.threads.drain(..) {
+      let _ = thread.join();
+    }
+  }
+}
+
+///
+/// Copy everything out of a pipe into the terminal and the log file, until it closes.
+///
+#[cfg(unix)]
+fn copy_out(read_end: RawFd, terminal: RawFd, file: &Mutex<RotatingLogFile>) {
+  let mut buffer = [0u8; 4096];
+  loop {
+    let length = match read(read_end, &mut buffer) {
+      Ok(0) => break,
+      Ok(length) => length,
+      Err(Errno::EINTR) => continue,
+      Err(_) => break,
+    };
+    // The terminal going away shouldn't stop the log file.
+    let mut written = 0;
+    while written < length {
+      match write(terminal, &buffer[written..length]) {
+        Ok(count) => written += count,
+        Err(Errno::EINTR) => continue,
+        Err(_) => break,
+      }
+    }
+    if let Ok(mut file) = file.lock() {
+      if let Err(e) = file.write_all(&buffer[..length]) {
+        let _ = write(terminal, format!("{}\n", e).as_bytes());
+      }
+    }
+  }
+  let _ = close(read_end);
+  let _ = close(terminal);
+}
+
+///
+/// Nothing to capture with, see the unix one.
+///
+#[cfg(not(unix))]
+pub struct StdioCapture;
+
+///
+/// Writes the log to the terminal like usual, and into a RotatingLogFile.
+///
+/// Without a StdioCapture this only gets log records, println! never shows up in the file.
+///
+#[cfg(not(unix))]
+struct LogTee {
+  file: RotatingLogFile,
+}
+
+#[cfg(not(unix))]
+impl Write for LogTee {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    // The terminal going away shouldn't stop the log file.
+    let _ = io::stderr().write_all(buf);
+    self.file.write_all(buf)?;
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    let _ = io::stderr().flush();
+    self.file.flush()
+  }
+}
+
+///
+/// Get where a log file goes. A relative path is relative to log_dir, not
+/// wherever minetest happened to be started from.
+///
+pub fn get_log_path(path: &str, log_dir: &str) -> String {
+  let path = Path::new(path);
+  match path.is_absolute() {
+    true => path.to_string_lossy().to_string(),
+    false => Path::new(log_dir).join(path).to_string_lossy().to_string(),
+  }
+}
+
+///
+/// Set up the environment logger, logging into a file too if minetest.conf asks for it.
+///
+/// debug_log_file = debug.txt (relative to log_dir)
+/// debug_log_size_max = 50 (MB)
+/// debug_log_file_count = 5
+///
+/// On unix everything printed goes into the file, see StdioCapture. It
+/// has to be kept around for that, dropping it stops the capture.
+/// Elsewhere only log records do.
+///
+/// This can only happen once per process, it does nothing if it already did.
+///
+pub fn init_logger(config: &GameConfig, log_dir: &str) -> Option<StdioCapture> {
+  let mut builder = env_logger::Builder::from_default_env();
+  let mut capture = None;
+
+  let path = config.get_string("debug_log_file", "");
+  if !path.is_empty() {
+    let path = get_log_path(&path, log_dir);
+    let size_max_mb = match config.get("debug_log_size_max") {
+      Some(size_max) => match size_max.parse::<u64>() {
+        Ok(size_max) if size_max > 0 => size_max,
+        _ => {
+          println!("LogFile: ignoring debug_log_size_max [{}].", size_max);
+          DEFAULT_LOG_SIZE_MAX_MB
+        }
+      },
+      None => DEFAULT_LOG_SIZE_MAX_MB,
+    };
+    let kept_files = match config.get("debug_log_file_count") {
+      Some(count) => match count.parse::<usize>() {
+        Ok(count) => count,
+        Err(e) => {
+          println!("LogFile: ignoring debug_log_file_count [{}]. {}", count, e);
+          DEFAULT_LOG_FILE_COUNT
+        }
+      },
+      None => DEFAULT_LOG_FILE_COUNT,
+    };
+
+    if let Err(e) = create_dir_all_for(&path) {
+      println!("LogFile: {}", e);
+    }
+    match RotatingLogFile::new(&path, size_max_mb * 1024 * 1024, kept_files) {
+      #[cfg(unix)]
+      Ok(file) => match StdioCapture::start(file) {
+        Ok(started) => {
+          println!("LogFile: logging into [{}].", path);
+          capture = Some(started);
+        }
+        Err(e) => println!("LogFile: only logging to the terminal. {}", e),
+      },
+      #[cfg(not(unix))]
+      Ok(file) => {
+        builder.target(env_logger::Target::Pipe(Box::new(LogTee { file })));
+      }
+      Err(e) => println!("LogFile: only logging to the terminal. {}", e),
+    }
+  }
+
+  let _ = builder.try_init();
+  capture
+}
+
+///
+/// Make the directory a log file goes in, a fresh world doesn't have one yet.
+///
+fn create_dir_all_for(path: &str) -> Result<(), String> {
+  match Path::new(path).parent() {
+    Some(parent) if !parent.as_os_str().is_empty() => match parent.to_str() {
+      Some(parent) => create_dir_all(parent),
+      None => Ok(()),
+    },
+    _ => Ok(()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs::{create_dir_all, read_to_string, remove_dir_all},
+    io::Write,
+  };
+
+  use crate::{
+    file_utilities::{file_exists, test_dir},
+    game::log_file::{get_log_path, RotatingLogFile},
+  };
+
+  #[test]
+  fn test_rotating_log_file() {
+    println!("--- BEGIN ROTATING LOG FILE TEST ---");
+    let log_dir = test_dir("rotating_log_file");
+    if let Err(e) = create_dir_all(&log_dir) {
+      panic!("Unit test is broken. {}", e);
+    }
+    let path = format!("{}/debug.txt", log_dir);
+
+    // 10 bytes a file, 2 old ones kept.
+    let mut log = match RotatingLogFile::new(&path, 10, 2) {
+      Ok(log) => log,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    for line in ["aaaaaaaa\n", "bbbbbbbb\n", "cccccccc\n", "dddddddd\n"] {
+      if let Err(e) = log.write_all(line.as_bytes()) {
+        panic!("Unit test is broken. {}", e);
+      }
+    }
+    if let Err(e) = log.flush() {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let read = |path: &str| match read_to_string(path) {
+      Ok(text) => text,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    assert_eq!(read(&path), "dddddddd\n");
+    assert_eq!(read(&log.get_rotated_path(1)), "cccccccc\n");
+    assert_eq!(read(&log.get_rotated_path(2)), "bbbbbbbb\n");
+    // The oldest fell off the end.
+    assert!(!file_exists(&log.get_rotated_path(3)));
+    drop(log);
+
+    // Opening it again appends, and still rotates at the limit.
+    let mut log = match RotatingLogFile::new(&path, 10, 2) {
+      Ok(log) => log,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    if let Err(e) = log.write_all(b"e\n") {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = log.flush() {
+      panic!("Unit test is broken. {}", e);
+    }
+    assert_eq!(read(&path), "e\n");
+    assert_eq!(read(&log.get_rotated_path(1)), "dddddddd\n");
+    assert_eq!(read(&log.get_rotated_path(2)), "cccccccc\n");
+
+    let _ = remove_dir_all(&log_dir);
+  }
+
+  #[test]
+  fn test_log_path() {
+    println!("--- BEGIN LOG PATH TEST ---");
+    // Relative goes in the log directory, not wherever this was started from.
+    assert_eq!(
+      get_log_path("debug.txt", "./worlds/world"),
+      "./worlds/world/debug.txt"
+    );
+    assert_eq!(
+      get_log_path("logs/debug.txt", "/home/someone/.minetest"),
+      "/home/someone/.minetest/logs/debug.txt"
+    );
+    #[cfg(unix)]
+    assert_eq!(
+      get_log_path("/var/log/minetest.txt", "./worlds/world"),
+      "/var/log/minetest.txt"
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_log_copy_out() {
+    use std::sync::Mutex;
+
+    use nix::unistd::{close, pipe, read, write};
+
+    use crate::game::log_file::copy_out;
+
+    println!("--- BEGIN LOG COPY OUT TEST ---");
+    let log_dir = test_dir("log_copy_out");
+    if let Err(e) = create_dir_all(&log_dir) {
+      panic!("Unit test is broken. {}", e);
+    }
+    let path = format!("{}/debug.txt", log_dir);
+    let log = match RotatingLogFile::new(&path, 1024, 1) {
+      Ok(log) => Mutex::new(log),
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+
+    // A pipe standing in for stdout, and one for the terminal.
+    let (captured_read, captured_write) = match pipe() {
+      Ok(ends) => ends,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let (terminal_read, terminal_write) = match pipe() {
+      Ok(ends) => ends,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    if let Err(e) = write(captured_write, b"printed\n") {
+      panic!("Unit test is broken. {}", e);
+    }
+    let _ = close(captured_write);
+
+    // Returns once the write end is closed.
+    copy_out(captured_read, terminal_write, &log);
+
+    let mut buffer = [0u8; 64];
+    let length = match read(terminal_read, &mut buffer) {
+      Ok(length) => length,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let _ = close(terminal_read);
+    assert_eq!(&buffer[..length], b"printed\n");
+    match read_to_string(&path) {
+      Ok(text) => assert_eq!(text, "printed\n"),
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+
+    let _ = remove_dir_all(&log_dir);
+  }
+}
@@ -1,13 +1,69 @@
-mod lua_file_helpers;
+pub mod lua_file_helpers;
+pub mod lua_guard;
+mod lua_input;
+mod lua_inventory;
+mod lua_items;
+mod lua_maintenance;
+mod lua_nodes;
+mod lua_players;
+mod lua_privileges;
+mod lua_pseudo_random;
+mod lua_server_info;
+mod lua_sound;
+mod lua_time_of_day;
+mod lua_vector;
+mod lua_world_limits;
 
 use core::panic;
+use std::{cell::RefCell, rc::Rc};
 
 use configparser::ini::Ini;
-use mlua::Lua;
+use glam::{IVec3, Vec3A};
+use mlua::{Function, Lua, Table, Value, Variadic};
 
-use crate::file_utilities::read_file_to_string;
+use crate::{
+  file_utilities::read_file_to_string,
+  game::{
+    client::input_events::InputEventQueue,
+    server::{
+      inventory::Inventories, item_registry::ItemRegistry, maintenance::MaintenanceRequest,
+      node_registry::NodeRegistry, privileges::Privileges, server_info::ServerInfo,
+      sound_request::SoundRequest, world_limits::WorldLimits,
+    },
+    time_of_day::TimeOfDay,
+    time_step::TimeStep,
+  },
+};
 
-use self::lua_file_helpers::{check_game, get_game_mod_folders, get_game_path};
+use self::{
+  lua_file_helpers::{check_game, get_game_mod_folders, get_game_path},
+  lua_guard::create_guarded_function,
+  lua_input::create_input_api,
+  lua_inventory::create_inventory_api,
+  lua_items::create_item_api,
+  lua_maintenance::create_maintenance_api,
+  lua_nodes::create_node_api,
+  lua_players::create_player_api,
+  lua_privileges::create_privileges_api,
+  lua_pseudo_random::{create_pseudo_random_api, set_default_seed},
+  lua_server_info::create_server_info_api,
+  lua_sound::create_sound_api,
+  lua_time_of_day::create_time_of_day_api,
+  lua_vector::{create_vector_api, LuaVector},
+  lua_world_limits::create_world_limits_api,
+};
+
+///
+/// Where the name of the mod that's currently loading is kept in the Lua registry.
+///
+const CURRENT_MOD_NAME_KEY: &str = "minetest_current_mod_name";
+
+///
+/// Where games are installed.
+///
+/// Todo: Maybe this can be chosen between run-in-place or system installed.
+///
+pub const GAMES_DIR: &str = "./games";
 
 ///
 /// LuaEngine encapsulates the LuauJIT virtual machine.
@@ -18,6 +74,12 @@ pub struct LuaEngine {
   lua: Lua,
   output_code_string: bool,
   server_vm: bool,
+  // The mods load_game() actually ran, in load order.
+  loaded_mods: Vec<String>,
+  // Why the mods that failed to load did, see get_mod_errors.
+  mod_errors: Vec<String>,
+  // Otherwise a mod that fails to load crashes the server.
+  skip_broken_mods: bool,
 }
 
 impl LuaEngine {
@@ -26,6 +88,9 @@ impl LuaEngine {
       lua: Lua::new(),
       output_code_string: false,
       server_vm,
+      loaded_mods: vec![],
+      mod_errors: vec![],
+      skip_broken_mods: false,
     };
 
     new_engine.generate_internal();
@@ -36,14 +101,91 @@ impl LuaEngine {
   ///
   /// Run the global on_tick function in the LuauJIT VM environment.
   ///
-  pub fn on_tick(&self, delta: f64) {
-    self.run_code(format!("_G.engine_on_tick_function({})", delta))
+  /// Like on_generated, a mod whose callback errors doesn't stop the rest.
+  ///
+  pub fn on_tick(&self, delta: TimeStep) {
+    // Lua gets plain seconds.
+    self.run_code(format!(
+      "_G.engine_on_tick_function({})",
+      delta.as_secs_f64()
+    ))
+  }
+
+  ///
+  /// Run every minetest.register_on_generated callback for a new map area.
+  ///
+  /// A callback that errors is reported by the Lua side and the rest
+  /// still run, so this only fails if the engine hook itself is broken.
+  ///
+  pub fn on_generated(&self, min: IVec3, max: IVec3, seed: u64) -> Result<(), String> {
+    let result = self
+      .lua
+      .globals()
+      .get::<_, Function>("engine_on_generated_function")
+      .and_then(|on_generated| {
+        // Area seeds fit in 53 bits, so a Lua number holds them exactly.
+        on_generated.call::<_, ()>((
+          LuaVector(min.as_vec3a()),
+          LuaVector(max.as_vec3a()),
+          seed as f64,
+        ))
+      });
+
+    match result {
+      Ok(_) => Ok(()),
+      Err(e) => Err(format!(
+        "LuaEngine: on_generated failed for area [{}] to [{}]. {}",
+        min, max, e
+      )),
+    }
+  }
+
+  ///
+  /// Run every minetest.register_on_maintenance_done callback.
+  ///
+  pub fn on_maintenance_done(&self, name: &str, cancelled: bool) -> Result<(), String> {
+    let result = self
+      .lua
+      .globals()
+      .get::<_, Function>("engine_on_maintenance_done_function")
+      .and_then(|on_maintenance_done| on_maintenance_done.call::<_, ()>((name, cancelled)));
+
+    match result {
+      Ok(_) => Ok(()),
+      Err(e) => Err(format!(
+        "LuaEngine: on_maintenance_done failed for [{}]. {}",
+        name, e
+      )),
+    }
+  }
+
+  ///
+  /// Run every minetest.register_on_shutdown callback.
+  ///
+  /// Like on_generated, a callback that errors doesn't stop the rest.
+  ///
+  pub fn on_shutdown(&self) -> Result<(), String> {
+    let result = self
+      .lua
+      .globals()
+      .get::<_, Function>("engine_on_shutdown_function")
+      .and_then(|on_shutdown| on_shutdown.call::<_, ()>(()));
+
+    match result {
+      Ok(_) => Ok(()),
+      Err(e) => Err(format!("LuaEngine: on_shutdown failed. {}", e)),
+    }
   }
 
   ///
   /// Generates the on_tick(delta: number) function so it becomes a secret and hidden engine component.
   ///
   pub fn generate_internal(&self) {
+    // The engine side of the minetest table has to exist before api.lua picks it up.
+    if let Err(e) = self.generate_engine_api() {
+      panic!("LuaEngine: Failed to create engine API. {}", e);
+    }
+
     // We want the game to simply crash if the internals have problems.
     // You can't build upon what is fundamentally broken.
     if self.server_vm {
@@ -61,6 +203,298 @@ impl LuaEngine {
     }
   }
 
+  ///
+  /// Creates the global minetest table with the functions implemented in Rust.
+  ///
+  /// api.lua builds the rest of the API on top of this.
+  ///
+  fn generate_engine_api(&self) -> mlua::Result<()> {
+    let minetest = self.lua.create_table()?;
+
+    // Returns nil when no mod is loading.
+    let get_current_modname =
+      create_guarded_function(&self.lua, "minetest.get_current_modname", |lua, ()| {
+        lua.named_registry_value::<Option<String>>(CURRENT_MOD_NAME_KEY)
+      })?;
+    minetest.set("get_current_modname", get_current_modname)?;
+
+    create_pseudo_random_api(&self.lua, &minetest)?;
+
+    self.lua.globals().set("minetest", minetest)?;
+
+    // Luau's own print goes through C's stdout buffer, which can sit on it
+    // until exit. This one goes out the same way as the engine's, see StdioCapture.
+    let print = create_guarded_function(&self.lua, "print", |lua, values: Variadic<Value>| {
+      let tostring = lua.globals().get::<_, Function>("tostring")?;
+      let mut line = vec![];
+      for value in values {
+        line.push(tostring.call::<_, String>(value)?);
+      }
+      println!("{}", line.join("\t"));
+      Ok(())
+    })?;
+    self.lua.globals().set("print", print)?;
+
+    // The vector table is backed by glam, see lua_vector.
+    create_vector_api(&self.lua)
+  }
+
+  ///
+  /// Seed minetest.pseudo_random() for mods that don't give it a seed.
+  ///
+  pub fn set_default_seed(&self, seed: u64) {
+    set_default_seed(&self.lua, seed);
+  }
+
+  ///
+  /// Give Lua access to the Server's Privileges.
+  ///
+  /// This is server only, the client doesn't know anyone's privileges.
+  ///
+  pub fn set_privileges(&self, privileges: Rc<RefCell<Privileges>>) {
+    let result = self
+      .lua
+      .globals()
+      .get::<_, Table>("minetest")
+      .and_then(|minetest| create_privileges_api(&self.lua, &minetest, privileges));
+    if let Err(e) = result {
+      panic!("LuaEngine: Failed to create privileges API. {}", e);
+    }
+  }
+
+  ///
+  /// Give Lua access to the Server's TimeOfDay.
+  ///
+  /// This is server only, the client's clock just follows the server.
+  ///
+  pub fn set_time_of_day(&self, time_of_day: Rc<RefCell<TimeOfDay>>) {
+    let result = self
+      .lua
+      .globals()
+      .get::<_, Table>("minetest")
+      .and_then(|minetest| create_time_of_day_api(&self.lua, &minetest, time_of_day));
+    if let Err(e) = result {
+      panic!("LuaEngine: Failed to create time of day API. {}", e);
+    }
+  }
+
+  ///
+  /// Give Lua minetest.register_node, filling in the Server's NodeRegistry.
+  ///
+  pub fn set_nodes(&self, nodes: Rc<RefCell<NodeRegistry>>) {
+    let result = self
+      .lua
+      .globals()
+      .get::<_, Table>("minetest")
+      .and_then(|minetest| create_node_api(&self.lua, &minetest, nodes));
+    if let Err(e) = result {
+      panic!("LuaEngine: Failed to create node API. {}", e);
+    }
+  }
+
+  ///
+  /// Give Lua minetest.register_craftitem, filling in the Server's ItemRegistry.
+  ///
+  pub fn set_items(&self, items: Rc<RefCell<ItemRegistry>>) {
+    let result = self
+      .lua
+      .globals()
+      .get::<_, Table>("minetest")
+      .and_then(|minetest| create_item_api(&self.lua, &minetest, items));
+    if let Err(e) = result {
+      panic!("LuaEngine: Failed to create item API. {}", e);
+    }
+  }
+
+  ///
+  /// Give Lua access to the players' inventories.
+  ///
+  /// The registries are needed to know which items exist and how high they stack.
+  ///
+  pub fn set_inventories(
+    &self,
+    inventories: Rc<RefCell<Inventories>>,
+    items: Rc<RefCell<ItemRegistry>>,
+    nodes: Rc<RefCell<NodeRegistry>>,
+  ) {
+    let result = self
+      .lua
+      .globals()
+      .get::<_, Table>("minetest")
+      .and_then(|minetest| create_inventory_api(&self.lua, &minetest, inventories, items, nodes));
+    if let Err(e) = result {
+      panic!("LuaEngine: Failed to create inventory API. {}", e);
+    }
+  }
+
+  ///
+  /// Give Lua minetest.sound_play, queueing sounds up for the Server to send.
+  ///
+  pub fn set_sounds(&self, sounds: Rc<RefCell<Vec<SoundRequest>>>) {
+    let result = self
+      .lua
+      .globals()
+      .get::<_, Table>("minetest")
+      .and_then(|minetest| create_sound_api(&self.lua, &minetest, sounds));
+    if let Err(e) = result {
+      panic!("LuaEngine: Failed to create sound API. {}", e);
+    }
+  }
+
+  ///
+  /// Give Lua the Server's uptime, player count, and status.
+  ///
+  pub fn set_server_info(&self, server_info: Rc<RefCell<ServerInfo>>) {
+    let result = self
+      .lua
+      .globals()
+      .get::<_, Table>("minetest")
+      .and_then(|minetest| create_server_info_api(&self.lua, &minetest, server_info));
+    if let Err(e) = result {
+      panic!("LuaEngine: Failed to create server info API. {}", e);
+    }
+  }
+
+  ///
+  /// Give Lua minetest.get_player_by_name, for the players in the ServerInfo.
+  ///
+  /// Teleports go into teleport_requests for the Server. (name, position)
+  ///
+  pub fn set_players(
+    &self,
+    server_info: Rc<RefCell<ServerInfo>>,
+    teleport_requests: Rc<RefCell<Vec<(String, Vec3A)>>>,
+  ) {
+    let result = self
+      .lua
+      .globals()
+      .get::<_, Table>("minetest")
+      .and_then(|minetest| create_player_api(&self.lua, &minetest, server_info, teleport_requests));
+    if let Err(e) = result {
+      panic!("LuaEngine: Failed to create player API. {}", e);
+    }
+  }
+
+  ///
+  /// Give Lua the Server's spawn point and world border.
+  ///
+  pub fn set_world_limits(&self, world_limits: WorldLimits) {
+    let result = self
+      .lua
+      .globals()
+      .get::<_, Table>("minetest")
+      .and_then(|minetest| create_world_limits_api(&self.lua, &minetest, world_limits));
+    if let Err(e) = result {
+      panic!("LuaEngine: Failed to create world limits API. {}", e);
+    }
+  }
+
+  ///
+  /// Give Lua minetest.get_input_events, reading the Client's InputEventQueue.
+  ///
+  /// This is client only, the server never sees raw input.
+  ///
+  pub fn set_input_events(&self, input_events: Rc<RefCell<InputEventQueue>>) {
+    let result = self
+      .lua
+      .globals()
+      .get::<_, Table>("minetest")
+      .and_then(|minetest| create_input_api(&self.lua, &minetest, input_events));
+    if let Err(e) = result {
+      panic!("LuaEngine: Failed to create input API. {}", e);
+    }
+  }
+
+  ///
+  /// Give Lua minetest.clear_objects and minetest.cancel_maintenance.
+  ///
+  pub fn set_maintenance_requests(&self, requests: Rc<RefCell<Vec<MaintenanceRequest>>>) {
+    let result = self
+      .lua
+      .globals()
+      .get::<_, Table>("minetest")
+      .and_then(|minetest| create_maintenance_api(&self.lua, &minetest, requests));
+    if let Err(e) = result {
+      panic!("LuaEngine: Failed to create maintenance API. {}", e);
+    }
+  }
+
+  ///
+  /// Creates a sandboxed environment table for a mod.
+  ///
+  /// Globals a mod creates without local land in here instead of _G.
+  /// Reading falls through to _G so the shared minetest API is still there.
+  /// Since no mod can see another mod's environment, poking at another
+  /// mod's internals simply gives you nil.
+  ///
+  fn create_mod_environment(&self) -> mlua::Result<Table<'_>> {
+    let environment = self.lua.create_table()?;
+    let metatable = self.lua.create_table()?;
+    metatable.set("__index", self.lua.globals())?;
+    environment.set_metatable(Some(metatable));
+    Ok(environment)
+  }
+
+  ///
+  /// Run a mod's code in it's own sandboxed environment.
+  ///
+  /// While this runs, minetest.get_current_modname() returns the mod's name
+  /// so anything it registers can be attributed to it.
+  ///
+  pub fn run_mod_code(
+    &self,
+    mod_name: &str,
+    chunk_name: &str,
+    raw_code: &str,
+  ) -> Result<(), String> {
+    let environment = match self.create_mod_environment() {
+      Ok(environment) => environment,
+      Err(e) => panic!("LuaEngine: Failed to create mod environment. {}", e),
+    };
+
+    if let Err(e) = self
+      .lua
+      .set_named_registry_value(CURRENT_MOD_NAME_KEY, mod_name)
+    {
+      panic!("LuaEngine: Failed to set current mod name. {}", e);
+    }
+
+    let result = self
+      .lua
+      .load(raw_code)
+      .set_name(chunk_name)
+      .set_environment(environment)
+      .exec();
+
+    if let Err(e) = self.lua.unset_named_registry_value(CURRENT_MOD_NAME_KEY) {
+      panic!("LuaEngine: Failed to clear current mod name. {}", e);
+    }
+
+    match result {
+      Ok(_) => Ok(()),
+      Err(err) => Err(format!(
+        "LuaEngine: encountered fatal error in mod [{}] {}: {}",
+        mod_name, chunk_name, err
+      )),
+    }
+  }
+
+  ///
+  /// Read a mod file and run it in the mod's sandboxed environment.
+  ///
+  pub fn run_mod_file(&self, mod_name: &str, file_location: &str) -> Result<(), String> {
+    let raw_code_string = match read_file_to_string(file_location) {
+      Ok(raw_code) => raw_code,
+      Err(e) => return Err(format!("LuaEngine: {}", e)),
+    };
+
+    if self.output_code_string {
+      println!("{}", raw_code_string);
+    }
+
+    self.run_mod_code(mod_name, file_location, &raw_code_string)
+  }
+
   ///
   /// Completely unfiltered and unsandboxed code compiler/runner.
   ///
@@ -145,10 +579,18 @@ impl LuaEngine {
   /// If you modified the source code and removed check_game() from load_game():
   /// _You're asking for trouble._
   ///
-  fn load_game_files(&self, games_dir: &str, game_name: &str) {
+  fn load_game_files(&mut self, games_dir: &str, game_name: &str, disabled_mods: &[String]) {
     let game_mod_path = get_game_path(games_dir, game_name);
 
     for mod_directory in get_game_mod_folders(games_dir, game_name) {
+      if disabled_mods.contains(&mod_directory.mod_name) {
+        println!(
+          "LuaEngine: Server skipping disabled mod [{}]",
+          mod_directory.mod_name
+        );
+        continue;
+      }
+
       // ! this is a naive approach.
       // ! this might not work on windows!
       let mut mod_path = mod_directory.mod_path.clone();
@@ -159,24 +601,65 @@ impl LuaEngine {
         &mod_path
       );
 
-      // This simply panics for now, but in the future we can push errors to the GUI.
-      match self.run_file(&mod_path) {
+      // This simply panics, unless broken mods are skipped. Then --strict
+      // turns them into a startup failure.
+      // todo: in the future we can push errors to the GUI.
+      match self.run_mod_file(&mod_directory.mod_name, &mod_path) {
         Ok(_) => println!(
           "LuaEngine: Server loaded mod file [{}]\n--------------------",
           &mod_path
         ),
-        Err(e) => panic!("{}", e),
+        Err(e) if !self.skip_broken_mods => panic!("{}", e),
+        Err(e) => {
+          println!(
+            "LuaEngine: Server skipping broken mod [{}]. {}",
+            mod_directory.mod_name, e
+          );
+          self.mod_errors.push(format!(
+            "Mod [{}] failed to load. {}",
+            mod_directory.mod_name, e
+          ));
+          continue;
+        }
       }
+
+      self.loaded_mods.push(mod_directory.mod_name);
     }
   }
 
+  ///
+  /// Get the names of the mods load_game() ran, in load order.
+  ///
+  pub fn get_loaded_mods(&self) -> &[String] {
+    &self.loaded_mods
+  }
+
+  ///
+  /// Skip mods that fail to load in load_game(), instead of panicking.
+  ///
+  /// minetest.conf turns this on with skip_broken_mods = true, and --strict
+  /// always does, so it can report every broken mod at once.
+  ///
+  pub fn set_skip_broken_mods(&mut self, skip_broken_mods: bool) {
+    self.skip_broken_mods = skip_broken_mods;
+  }
+
+  ///
+  /// Get why each mod that load_game() skipped failed to load.
+  ///
+  pub fn get_mod_errors(&self) -> &[String] {
+    &self.mod_errors
+  }
+
   ///
   /// Load up a game directly.
   ///
+  /// Mods in disabled_mods are skipped.
+  ///
   /// This should _only_ be run on a server LuaEngine.
   /// ! **Never run this function on a Client!**
   ///
-  pub fn load_game(&mut self, game_name: String) {
+  pub fn load_game(&mut self, game_name: String, disabled_mods: &[String]) {
     // We _do not_ want a client to even attempt to load anything.
     // All required information should be sent by the Server to the Client.
     // Then it should be passed into the LuaEngine as needed.
@@ -184,9 +667,7 @@ impl LuaEngine {
       panic!("LuaEngine: tried to load game lua files on a client LuaEngine!")
     }
 
-    // Todo: Maybe this can be a compile time const?
-    // We can choose between run-in-place or system installed.
-    let games_dir = String::from("./games");
+    let games_dir = String::from(GAMES_DIR);
 
     // Comes from lua_file_helpers.
     check_game(&games_dir, &game_name);
@@ -196,6 +677,130 @@ impl LuaEngine {
     self.parse_game_conf(&games_dir, &game_name);
 
     // Now we finally load the actual game files into the LuaEngine.
-    self.load_game_files(&games_dir, &game_name);
+    self.load_game_files(&games_dir, &game_name, disabled_mods);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::IVec3;
+  use mlua::Function;
+
+  use crate::game::{lua_engine::LuaEngine, time_step::TimeStep};
+
+  #[test]
+  fn test_lua_engine_mod_sandboxing() {
+    println!("--- BEGIN LUA ENGINE MOD SANDBOXING TEST ---");
+    let lua_engine = LuaEngine::new(true);
+
+    // Both mods use the same global name.
+    let mod_a = "
+      shared_name = 'a'
+      a_internal = 'secret'
+      _G.get_a = function() return shared_name end
+    ";
+    let mod_b = "
+      shared_name = 'b'
+      _G.get_b = function() return shared_name end
+      _G.b_sees_a_internal = a_internal ~= nil
+      _G.b_mod_name = minetest.get_current_modname()
+    ";
+
+    for (mod_name, code) in [("a", mod_a), ("b", mod_b)] {
+      if let Err(e) = lua_engine.run_mod_code(mod_name, mod_name, code) {
+        panic!("Unit test is broken. {}", e);
+      }
+    }
+
+    let globals = lua_engine.lua.globals();
+    let get = |name: &str| -> String {
+      let function: Function = match globals.get(name) {
+        Ok(function) => function,
+        Err(e) => panic!("Unit test is broken. {}", e),
+      };
+      match function.call(()) {
+        Ok(value) => value,
+        Err(e) => panic!("Unit test is broken. {}", e),
+      }
+    };
+
+    // No collision.
+    assert_eq!(get("get_a"), "a");
+    assert_eq!(get("get_b"), "b");
+
+    // Nothing leaked into _G or the other mod, the shared API is still visible.
+    assert!(matches!(
+      globals.get::<_, Option<String>>("shared_name"),
+      Ok(None)
+    ));
+    assert!(matches!(
+      globals.get::<_, bool>("b_sees_a_internal"),
+      Ok(false)
+    ));
+    assert!(matches!(globals.get::<_, String>("b_mod_name"), Ok(value) if value == "b"));
+  }
+
+  #[test]
+  fn test_lua_engine_mod_origin() {
+    println!("--- BEGIN LUA ENGINE MOD ORIGIN TEST ---");
+    let mut lua_engine = LuaEngine::new(true);
+    lua_engine.load_game("minetest".to_string(), &[]);
+
+    // Registrations are attributed to the mod that made them.
+    let origin = lua_engine
+      .lua
+      .load("return _G.blocks['minetest:stone'].mod_origin")
+      .eval::<String>();
+    assert!(matches!(origin, Ok(mod_name) if mod_name == "main"));
+  }
+
+  #[test]
+  fn test_lua_engine_on_tick_isolation() {
+    println!("--- BEGIN LUA ENGINE ON TICK ISOLATION TEST ---");
+    let lua_engine = LuaEngine::new(true);
+
+    // The broken mod registers first, it can't stop the working one.
+    let broken = "minetest.register_on_tick(function() error('oops') end)";
+    let working = "minetest.register_on_tick(function(delta) _G.ticked = delta end)";
+    for (mod_name, code) in [("broken", broken), ("working", working)] {
+      if let Err(e) = lua_engine.run_mod_code(mod_name, mod_name, code) {
+        panic!("Unit test is broken. {}", e);
+      }
+    }
+
+    lua_engine.on_tick(TimeStep::from_millis(50.0));
+
+    let ticked = lua_engine.lua.load("return _G.ticked").eval::<f64>();
+    assert!(matches!(ticked, Ok(delta) if delta == 0.05));
+  }
+
+  #[test]
+  fn test_lua_engine_on_generated() {
+    println!("--- BEGIN LUA ENGINE ON GENERATED TEST ---");
+    let lua_engine = LuaEngine::new(true);
+
+    // The broken mod registers first, it can't stop the working one.
+    let broken = "minetest.register_on_generated(function() error('oops') end)";
+    let working = "
+      minetest.register_on_generated(function(minp, maxp, seed)
+        _G.generated = {minp.x, maxp.z, seed}
+      end)
+    ";
+    for (mod_name, code) in [("broken", broken), ("working", working)] {
+      if let Err(e) = lua_engine.run_mod_code(mod_name, mod_name, code) {
+        panic!("Unit test is broken. {}", e);
+      }
+    }
+
+    let seed = (1 << 53) - 1;
+    if let Err(e) = lua_engine.on_generated(IVec3::new(-16, 0, 0), IVec3::new(-1, 15, 15), seed) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let generated = lua_engine
+      .lua
+      .load("return _G.generated[1], _G.generated[2], _G.generated[3]")
+      .eval::<(f32, f32, f64)>();
+    assert!(matches!(generated, Ok(result) if result == (-16.0, 15.0, seed as f64)));
   }
 }
@@ -9,7 +9,10 @@
 ///
 use std::fs::{read_dir, ReadDir};
 
-use crate::file_utilities::{dir_exists, file_exists};
+use crate::{
+  file_utilities::{dir_exists, file_exists, read_file_to_string},
+  game::game_config::GameConfig,
+};
 
 ///
 /// Makes the implementation of working with mod folders easier
@@ -18,6 +21,8 @@ use crate::file_utilities::{dir_exists, file_exists};
 pub struct ModDirectory {
   pub mod_name: String,
   pub mod_path: String,
+  // From depends in mod.conf.
+  pub depends: Vec<String>,
 }
 
 ///
@@ -78,7 +83,7 @@ fn game_has_conf_file(games_dir: &str, game_name: &str) -> bool {
   let mut base_path = get_game_path(games_dir, game_name);
   base_path.push_str("/game.conf");
 
-  dir_exists(&base_path)
+  file_exists(&base_path)
 }
 
 ///
@@ -159,6 +164,35 @@ fn game_mods_have_main_and_conf(games_dir: &str, game_name: &str) -> Result<(),
   Ok(())
 }
 
+///
+/// Read the depends list out of a mod's mod.conf.
+///
+/// mod.conf is the same key = value format as minetest.conf:
+/// depends = mod_a, mod_b
+///
+/// A missing mod.conf has no dependencies, check_game() complains about it.
+///
+fn get_mod_depends(mod_path: &str) -> Vec<String> {
+  let mut mod_conf_file = mod_path.to_owned();
+  mod_conf_file.push_str("/mod.conf");
+
+  if !file_exists(&mod_conf_file) {
+    return vec![];
+  }
+
+  let raw_mod_conf = match read_file_to_string(&mod_conf_file) {
+    Ok(raw_mod_conf) => raw_mod_conf,
+    Err(e) => panic!("LuaHelpers: {}", e),
+  };
+
+  GameConfig::parse(&raw_mod_conf)
+    .get_string("depends", "")
+    .split(',')
+    .map(|depend| depend.trim().to_string())
+    .filter(|depend| !depend.is_empty())
+    .collect()
+}
+
 ///
 /// Automatically get the mod folders in a game's directory as a vector of ModDirectory.
 ///
@@ -186,7 +220,13 @@ pub fn get_game_mod_folders(games_dir: &str, game_name: &str) -> Vec<ModDirector
               ),
             };
 
-            container.push(ModDirectory { mod_name, mod_path })
+            let depends = get_mod_depends(&mod_path);
+
+            container.push(ModDirectory {
+              mod_name,
+              mod_path,
+              depends,
+            })
           }
         }
         Err(e) => panic!(
@@ -204,6 +244,27 @@ pub fn get_game_mod_folders(games_dir: &str, game_name: &str) -> Vec<ModDirector
   container
 }
 
+///
+/// Get what's missing for a game to be loadable, so startup can say so.
+///
+/// Empty if the game's directory, game.conf, and mods/ are all there.
+/// Unlike check_game() this never panics, and only checks the layout.
+///
+pub fn get_missing_game_parts(games_dir: &str, game_name: &str) -> Vec<String> {
+  if !game_exists(games_dir, game_name) {
+    return vec![format!("{}/", get_game_path(games_dir, game_name))];
+  }
+
+  let mut missing = vec![];
+  if !game_has_conf_file(games_dir, game_name) {
+    missing.push("game.conf".to_string());
+  }
+  if !game_mods_folder_exists(games_dir, game_name) {
+    missing.push("mods/".to_string());
+  }
+  missing
+}
+
 ///
 /// Runs all checks in one clean procedure.
 ///
@@ -0,0 +1,209 @@
+use std::{
+  any::Any,
+  cell::Cell,
+  panic::{self, AssertUnwindSafe},
+};
+
+use mlua::{FromLuaMulti, Function, IntoLuaMulti, Lua, UserDataMethods};
+
+thread_local! {
+  // How many guards deep this thread is. Bindings can call Lua that calls bindings.
+  static GUARD_DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+///
+/// Get if a panic on this thread right now would be caught by a guard.
+///
+/// The crash handler checks this, a caught panic isn't a crash.
+///
+pub fn is_inside_guard() -> bool {
+  GUARD_DEPTH.with(|depth| depth.get() > 0)
+}
+
+///
+/// Run a Rust binding, turning a panic into a Lua error.
+///
+/// A panic that unwinds through Luau's C frames is undefined behavior. mlua
+/// catches them too, but it starts the panic back up once the call gets
+/// back to Rust, taking the whole server down over one broken binding.
+/// Caught here it's a plain error, pcall gets it and the VM carries on.
+///
+/// It's logged as a Lua error, the crash handler skips it.
+///
+pub fn guard<R>(name: &str, call: impl FnOnce() -> mlua::Result<R>) -> mlua::Result<R> {
+  GUARD_DEPTH.with(|depth| depth.set(depth.get() + 1));
+  let result = panic::catch_unwind(AssertUnwindSafe(call));
+  GUARD_DEPTH.with(|depth| depth.set(depth.get() - 1));
+  match result {
+    Ok(result) => result,
+    Err(payload) => {
+      let message = format!(
+        "[{}] panicked. {}",
+        name,
+        get_panic_message(payload.as_ref())
+      );
+      println!("LuaGuard: Lua error. {}", message);
+      Err(mlua::Error::RuntimeError(message))
+    }
+  }
+}
+
+fn get_panic_message(payload: &(dyn Any + Send)) -> String {
+  match payload.downcast_ref::<&str>() {
+    Some(message) => message.to_string(),
+    None => match payload.downcast_ref::<String>() {
+      Some(message) => message.clone(),
+      None => "unknown panic".to_string(),
+    },
+  }
+}
+
+///
+/// lua.create_function, but guarded. Every minetest.* function goes through here.
+///
+pub fn create_guarded_function<'lua, A, R, F>(
+  lua: &'lua Lua,
+  name: &'static str,
+  function: F,
+) -> mlua::Result<Function<'lua>>
+where
+  A: FromLuaMulti<'lua>,
+  R: IntoLuaMulti<'lua>,
+  F: Fn(&'lua Lua, A) -> mlua::Result<R> + 'static,
+{
+  lua.create_function(move |lua, args: A| guard(name, || function(lua, args)))
+}
+
+///
+/// The add_method family, but guarded. Every UserData method goes through here.
+///
+pub trait GuardedMethods<'lua, T> {
+  fn add_guarded_method<M, A, R>(&mut self, name: &'static str, method: M)
+  where
+    M: Fn(&'lua Lua, &T, A) -> mlua::Result<R> + 'static,
+    A: FromLuaMulti<'lua>,
+    R: IntoLuaMulti<'lua>;
+
+  fn add_guarded_method_mut<M, A, R>(&mut self, name: &'static str, method: M)
+  where
+    M: FnMut(&'lua Lua, &mut T, A) -> mlua::Result<R> + 'static,
+    A: FromLuaMulti<'lua>,
+    R: IntoLuaMulti<'lua>;
+
+  fn add_guarded_meta_method<M, A, R>(&mut self, meta_method: mlua::MetaMethod, method: M)
+  where
+    M: Fn(&'lua Lua, &T, A) -> mlua::Result<R> + 'static,
+    A: FromLuaMulti<'lua>,
+    R: IntoLuaMulti<'lua>;
+}
+
+impl<'lua, T, U: UserDataMethods<'lua, T>> GuardedMethods<'lua, T> for U {
+  fn add_guarded_method<M, A, R>(&mut self, name: &'static str, method: M)
+  where
+    M: Fn(&'lua Lua, &T, A) -> mlua::Result<R> + 'static,
+    A: FromLuaMulti<'lua>,
+    R: IntoLuaMulti<'lua>,
+  {
+    self.add_method(name, move |lua, this, args: A| {
+      guard(name, || method(lua, this, args))
+    });
+  }
+
+  fn add_guarded_method_mut<M, A, R>(&mut self, name: &'static str, mut method: M)
+  where
+    M: FnMut(&'lua Lua, &mut T, A) -> mlua::Result<R> + 'static,
+    A: FromLuaMulti<'lua>,
+    R: IntoLuaMulti<'lua>,
+  {
+    self.add_method_mut(name, move |lua, this, args: A| {
+      guard(name, || method(lua, this, args))
+    });
+  }
+
+  fn add_guarded_meta_method<M, A, R>(&mut self, meta_method: mlua::MetaMethod, method: M)
+  where
+    M: Fn(&'lua Lua, &T, A) -> mlua::Result<R> + 'static,
+    A: FromLuaMulti<'lua>,
+    R: IntoLuaMulti<'lua>,
+  {
+    let name = meta_method.name();
+    self.add_meta_method(meta_method, move |lua, this, args: A| {
+      guard(name, || method(lua, this, args))
+    });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use mlua::{Lua, UserData, UserDataMethods};
+
+  use crate::game::lua_engine::lua_guard::{
+    create_guarded_function, guard, is_inside_guard, GuardedMethods,
+  };
+
+  struct Fragile;
+
+  impl UserData for Fragile {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+      methods.add_guarded_method("shatter", |_, _, ()| -> mlua::Result<()> {
+        panic!("the method broke");
+      });
+    }
+  }
+
+  #[test]
+  fn test_lua_guard_catches_panics() {
+    println!("--- BEGIN LUA GUARD CATCHES PANICS TEST ---");
+    let lua = Lua::new();
+    let broken = create_guarded_function(&lua, "broken", |_, index: usize| {
+      let list: Vec<u32> = vec![];
+      Ok(list[index])
+    });
+    let working = create_guarded_function(&lua, "working", |_, number: u32| Ok(number * 2));
+    for (name, function) in [("broken", broken), ("working", working)] {
+      match function {
+        Ok(function) => {
+          if let Err(e) = lua.globals().set(name, function) {
+            panic!("Unit test is broken. {}", e);
+          }
+        }
+        Err(e) => panic!("Unit test is broken. {}", e),
+      }
+    }
+    if let Err(e) = lua.globals().set("fragile", Fragile) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    // pcall gets them like any other error, and the VM keeps going after.
+    let result = lua
+      .load(
+        "local ok, e = pcall(broken, 3)
+        local method_ok, method_e = pcall(function() fragile:shatter() end)
+        return ok, tostring(e), method_ok, tostring(method_e), working(21)",
+      )
+      .eval::<(bool, String, bool, String, u32)>();
+    match result {
+      Ok((ok, e, method_ok, method_e, doubled)) => {
+        assert!(!ok);
+        assert!(e.contains("[broken] panicked."));
+        assert!(e.contains("index out of bounds"));
+        assert!(!method_ok);
+        assert!(method_e.contains("[shatter] panicked. the method broke"));
+        assert_eq!(doubled, 42);
+      }
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+
+    // The crash handler can tell a panic is going to be caught.
+    assert!(!is_inside_guard());
+    let inside = guard("inner", || Ok(guard("nested", || Ok(is_inside_guard()))));
+    assert!(matches!(inside, Ok(Ok(true))));
+    assert!(!is_inside_guard());
+
+    // Without a pcall it comes back to Rust as an error, not a panic.
+    match lua.load("broken(0)").exec() {
+      Ok(_) => panic!("Unit test is broken. The panic went missing."),
+      Err(e) => assert!(e.to_string().contains("[broken] panicked.")),
+    }
+  }
+}
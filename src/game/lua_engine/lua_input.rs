@@ -0,0 +1,126 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::{Lua, Table};
+
+use crate::game::client::input_events::{InputEvent, InputEventQueue};
+
+use super::lua_guard::create_guarded_function;
+
+///
+/// Adds minetest.get_input_events() to the minetest table.
+///
+/// It returns this frame's input as an array, oldest first:
+/// {type = "key_down", key = "W"}
+/// {type = "key_up", key = "W"}
+/// {type = "mouse_move", x = 3, y = -1}
+///
+/// This is client only. Calling it twice in a frame gives the same events.
+///
+pub fn create_input_api(
+  lua: &Lua,
+  minetest: &Table,
+  input_events: Rc<RefCell<InputEventQueue>>,
+) -> mlua::Result<()> {
+  minetest.set(
+    "get_input_events",
+    create_guarded_function(lua, "minetest.get_input_events", move |lua, ()| {
+      let input_events = input_events
+        .try_borrow()
+        .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+
+      let events = lua.create_table()?;
+      for (index, event) in input_events.get_events().iter().enumerate() {
+        let table = lua.create_table()?;
+        match event {
+          InputEvent::KeyDown(key) => {
+            table.set("type", "key_down")?;
+            table.set("key", key.as_str())?;
+          }
+          InputEvent::KeyUp(key) => {
+            table.set("type", "key_up")?;
+            table.set("key", key.as_str())?;
+          }
+          InputEvent::MouseMove(delta) => {
+            table.set("type", "mouse_move")?;
+            table.set("x", delta.x)?;
+            table.set("y", delta.y)?;
+          }
+        }
+        events.set(index + 1, table)?;
+      }
+      Ok(events)
+    })?,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, rc::Rc};
+
+  use glam::IVec2;
+  use mlua::Lua;
+
+  use crate::game::{
+    client::input_events::InputEventQueue, lua_engine::lua_input::create_input_api,
+  };
+
+  #[test]
+  fn test_lua_input_api() {
+    println!("--- BEGIN LUA INPUT API TEST ---");
+    let input_events = Rc::new(RefCell::new(InputEventQueue::new()));
+
+    let lua = Lua::new();
+    let minetest = match lua.create_table() {
+      Ok(minetest) => minetest,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    if let Err(e) = create_input_api(&lua, &minetest, input_events.clone()) {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = lua.globals().set("minetest", minetest) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let count_events = || match lua
+      .load("return #minetest.get_input_events()")
+      .eval::<usize>()
+    {
+      Ok(count) => count,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    assert_eq!(count_events(), 0);
+
+    input_events
+      .borrow_mut()
+      .push_key_changes(&[("E".to_string(), true)]);
+    input_events
+      .borrow_mut()
+      .push_mouse_delta(IVec2::new(4, -2));
+    let events = lua
+      .load(
+        "
+        local events = minetest.get_input_events()
+        return events[1].type, events[1].key, events[2].type, events[2].x, events[2].y
+        ",
+      )
+      .eval::<(String, String, String, i32, i32)>();
+    match events {
+      Ok(events) => assert_eq!(
+        events,
+        (
+          "key_down".to_string(),
+          "E".to_string(),
+          "mouse_move".to_string(),
+          4,
+          -2
+        )
+      ),
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+    // Every mod gets to read them, only the Client clears them.
+    assert_eq!(count_events(), 2);
+
+    input_events.borrow_mut().clear();
+    assert_eq!(count_events(), 0);
+  }
+}
@@ -0,0 +1,328 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::{Lua, Table, UserData, UserDataMethods};
+
+use crate::game::server::{
+  inventory::{Inventories, Inventory, ItemStack},
+  item_registry::ItemRegistry,
+  node_registry::NodeRegistry,
+};
+
+use super::lua_guard::{create_guarded_function, GuardedMethods};
+
+///
+/// A player's inventory, handed to Lua by minetest.get_inventory.
+///
+/// Like an InvRef in minetest C++, it only holds on to the player's name.
+/// Every call goes through to the Server's Inventories.
+///
+#[derive(Clone)]
+pub struct LuaInventoryRef {
+  player: String,
+  inventories: Rc<RefCell<Inventories>>,
+  items: Rc<RefCell<ItemRegistry>>,
+  nodes: Rc<RefCell<NodeRegistry>>,
+}
+
+impl LuaInventoryRef {
+  ///
+  /// Run something on the player's inventory.
+  ///
+  fn with_inventory<T>(
+    &self,
+    f: impl FnOnce(&mut Inventory) -> Result<T, String>,
+  ) -> mlua::Result<T> {
+    let mut inventories = self
+      .inventories
+      .try_borrow_mut()
+      .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+    match inventories.get_mut(&self.player) {
+      Some(inventory) => {
+        f(inventory).map_err(|e| mlua::Error::RuntimeError(format!("minetest: {}", e)))
+      }
+      None => Err(mlua::Error::RuntimeError(format!(
+        "minetest: player [{}] has no inventory.",
+        self.player
+      ))),
+    }
+  }
+
+  ///
+  /// Parse an item string, only nodes and craftitems that were registered are allowed.
+  ///
+  /// Returns the stack and how many of it fit in a slot.
+  ///
+  fn read_item_string(&self, item_string: &str) -> mlua::Result<(ItemStack, u32)> {
+    let stack = ItemStack::from_string(item_string)
+      .map_err(|e| mlua::Error::RuntimeError(format!("minetest: {}", e)))?;
+
+    let items = self
+      .items
+      .try_borrow()
+      .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+    let nodes = self
+      .nodes
+      .try_borrow()
+      .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+    if items.get(&stack.name).is_none() && nodes.get(&stack.name).is_none() {
+      return Err(mlua::Error::RuntimeError(format!(
+        "minetest: unknown item [{}].",
+        stack.name
+      )));
+    }
+
+    let stack_max = items.get_stack_max(&stack.name);
+    Ok((stack, stack_max))
+  }
+}
+
+impl UserData for LuaInventoryRef {
+  fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+    methods.add_guarded_method("get_size", |_, this, list_name: String| {
+      this.with_inventory(|inventory| Ok(inventory.get_size(&list_name)))
+    });
+
+    methods.add_guarded_method("set_size", |_, this, (list_name, size): (String, usize)| {
+      this.with_inventory(|inventory| inventory.set_size(&list_name, size))
+    });
+
+    // Empty slots are "".
+    methods.add_guarded_method("get_list", |lua, this, list_name: String| {
+      let slots = this.with_inventory(|inventory| match inventory.get_list(&list_name) {
+        Some(slots) => Ok(
+          slots
+            .iter()
+            .map(|slot| match slot {
+              Some(stack) => stack.to_item_string(),
+              None => String::new(),
+            })
+            .collect::<Vec<String>>(),
+        ),
+        None => Err(format!("Inventory has no list [{}].", list_name)),
+      })?;
+      lua.create_sequence_from(slots)
+    });
+
+    // Returns how many didn't fit.
+    methods.add_guarded_method(
+      "add_item",
+      |_, this, (list_name, item_string): (String, String)| {
+        let (stack, stack_max) = this.read_item_string(&item_string)?;
+        this.with_inventory(|inventory| inventory.add_item(&list_name, &stack, stack_max))
+      },
+    );
+
+    // Returns how many were taken.
+    methods.add_guarded_method(
+      "remove_item",
+      |_, this, (list_name, item_string): (String, String)| {
+        let (stack, _) = this.read_item_string(&item_string)?;
+        this.with_inventory(|inventory| inventory.remove_item(&list_name, &stack))
+      },
+    );
+
+    methods.add_guarded_method(
+      "contains_item",
+      |_, this, (list_name, item_string): (String, String)| {
+        let (stack, _) = this.read_item_string(&item_string)?;
+        this.with_inventory(|inventory| {
+          Ok(inventory.count_item(&list_name, &stack.name) >= stack.count)
+        })
+      },
+    );
+  }
+}
+
+///
+/// Adds the inventory functions to the minetest table.
+///
+/// minetest.get_inventory({type = "player", name = name}) -> InvRef or nil
+///
+/// InvRef:get_size(list), InvRef:set_size(list, size)
+/// InvRef:get_list(list) -> {item string}
+/// InvRef:add_item(list, item string) -> how many didn't fit
+/// InvRef:remove_item(list, item string) -> how many were taken
+/// InvRef:contains_item(list, item string) -> bool
+///
+/// minetest.set_starter_kit({item string}) -> what brand new players get, instead of initial_stuff
+///
+/// Only players have inventories for now, and only once they've joined.
+/// Unlike minetest C++, add_item and remove_item give back counts, not ItemStacks.
+/// set_size errors past MAX_LIST_SIZE slots.
+///
+pub fn create_inventory_api(
+  lua: &Lua,
+  minetest: &Table,
+  inventories: Rc<RefCell<Inventories>>,
+  items: Rc<RefCell<ItemRegistry>>,
+  nodes: Rc<RefCell<NodeRegistry>>,
+) -> mlua::Result<()> {
+  let kit_inventories = inventories.clone();
+  minetest.set(
+    "set_starter_kit",
+    create_guarded_function(
+      lua,
+      "minetest.set_starter_kit",
+      move |_, item_strings: Vec<String>| {
+        let mut kit = vec![];
+        for item_string in item_strings {
+          kit.push(
+            ItemStack::from_string(&item_string)
+              .map_err(|e| mlua::Error::RuntimeError(format!("minetest: {}", e)))?,
+          );
+        }
+        kit_inventories
+          .try_borrow_mut()
+          .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?
+          .set_starter_kit(kit);
+        Ok(())
+      },
+    )?,
+  )?;
+
+  minetest.set(
+    "get_inventory",
+    create_guarded_function(lua, "minetest.get_inventory", move |_, location: Table| {
+      let location_type = location.get::<_, String>("type")?;
+      if location_type != "player" {
+        return Err(mlua::Error::RuntimeError(format!(
+          "minetest: can't get a [{}] inventory, only player inventories exist.",
+          location_type
+        )));
+      }
+      let player = location.get::<_, String>("name")?;
+
+      let has_inventory = inventories
+        .try_borrow()
+        .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?
+        .get(&player)
+        .is_some();
+      Ok(has_inventory.then(|| LuaInventoryRef {
+        player,
+        inventories: inventories.clone(),
+        items: items.clone(),
+        nodes: nodes.clone(),
+      }))
+    })?,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, fs::remove_dir_all, rc::Rc};
+
+  use mlua::Lua;
+
+  use crate::{
+    file_utilities::test_dir,
+    game::{
+      lua_engine::lua_inventory::create_inventory_api,
+      server::{
+        inventory::{Inventories, ItemStack, PLAYER_MAIN_LIST},
+        item_registry::{ItemDefinition, ItemRegistry},
+        node_registry::{NodeDefinition, NodeRegistry},
+      },
+    },
+  };
+
+  #[test]
+  fn test_lua_inventory_api() {
+    println!("--- BEGIN LUA INVENTORY API TEST ---");
+    let world_path = test_dir("lua_inventory_api");
+
+    let inventories = Rc::new(RefCell::new(Inventories::load(&world_path)));
+    inventories.borrow_mut().create_if_missing("bob");
+    let items = Rc::new(RefCell::new(ItemRegistry::new()));
+    let apple = ItemDefinition {
+      stack_max: 10,
+      ..ItemDefinition::new("food:apple")
+    };
+    if let Err(e) = items.borrow_mut().register(apple) {
+      panic!("Unit test is broken. {}", e);
+    }
+    let nodes = Rc::new(RefCell::new(NodeRegistry::new(vec![])));
+    if let Err(e) = nodes
+      .borrow_mut()
+      .register(NodeDefinition::new("rocks:stone"))
+    {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let lua = Lua::new();
+    let minetest = match lua.create_table() {
+      Ok(minetest) => minetest,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    if let Err(e) = create_inventory_api(&lua, &minetest, inventories.clone(), items, nodes) {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = lua.globals().set("minetest", minetest) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let code = "
+      local inv = minetest.get_inventory({type = 'player', name = 'bob'})
+      local leftover = inv:add_item('main', 'food:apple 25')
+      inv:add_item('main', 'rocks:stone')
+      local list = inv:get_list('main')
+      local removed = inv:remove_item('main', 'food:apple 100')
+      return leftover, list[3], list[4], list[5], removed,
+        inv:contains_item('main', 'rocks:stone'),
+        minetest.get_inventory({type = 'player', name = 'alice'}) == nil
+    ";
+    match lua
+      .load(code)
+      .eval::<(u32, String, String, String, u32, bool, bool)>()
+    {
+      Ok(result) => assert_eq!(
+        result,
+        (
+          0,
+          "food:apple 5".to_string(),
+          "rocks:stone".to_string(),
+          "".to_string(),
+          25,
+          true,
+          true
+        )
+      ),
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+
+    // Lua and the Server see the same inventory.
+    match inventories.borrow().get("bob") {
+      Some(bob) => {
+        assert_eq!(bob.count_item(PLAYER_MAIN_LIST, "food:apple"), 0);
+        assert_eq!(bob.count_item(PLAYER_MAIN_LIST, "rocks:stone"), 1);
+      }
+      None => panic!("Unit test is broken. Bob has no inventory."),
+    }
+
+    // Mods pick what new players get.
+    if let Err(e) = lua
+      .load("minetest.set_starter_kit({'food:apple 3', 'rocks:stone'})")
+      .exec()
+    {
+      panic!("Unit test is broken. {}", e);
+    }
+    assert_eq!(
+      inventories.borrow().get_starter_kit(),
+      &[
+        ItemStack::new("food:apple", 3),
+        ItemStack::new("rocks:stone", 1)
+      ]
+    );
+    assert!(lua
+      .load("minetest.set_starter_kit({'food:apple three'})")
+      .exec()
+      .is_err());
+
+    // Unknown items are an error.
+    assert!(lua
+      .load("minetest.get_inventory({type = 'player', name = 'bob'}):add_item('main', 'food:pear')")
+      .exec()
+      .is_err());
+
+    let _ = remove_dir_all(&world_path);
+  }
+}
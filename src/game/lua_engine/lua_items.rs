@@ -0,0 +1,113 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::{Lua, Table};
+
+use crate::game::server::item_registry::{ItemDefinition, ItemRegistry, DEFAULT_STACK_MAX};
+
+use super::{lua_guard::create_guarded_function, CURRENT_MOD_NAME_KEY};
+
+///
+/// Adds the craftitem registration functions to the minetest table.
+///
+/// minetest.register_craftitem(name, {description, inventory_image, stack_max})
+/// minetest.registered_craftitems -> {[name] = definition}
+///
+/// Works like register_node, the definitions go into the Server's ItemRegistry.
+///
+pub fn create_item_api(
+  lua: &Lua,
+  minetest: &Table,
+  items: Rc<RefCell<ItemRegistry>>,
+) -> mlua::Result<()> {
+  let registered_craftitems = lua.create_table()?;
+  minetest.set("registered_craftitems", registered_craftitems.clone())?;
+  let registered_craftitems = lua.create_registry_value(registered_craftitems)?;
+
+  minetest.set(
+    "register_craftitem",
+    create_guarded_function(
+      lua,
+      "minetest.register_craftitem",
+      move |lua, (name, definition): (String, Table)| {
+        let item_definition = ItemDefinition {
+          description: definition
+            .get::<_, Option<String>>("description")?
+            .unwrap_or_default(),
+          inventory_image: definition
+            .get::<_, Option<String>>("inventory_image")?
+            .unwrap_or_default(),
+          stack_max: definition
+            .get::<_, Option<u32>>("stack_max")?
+            .unwrap_or(DEFAULT_STACK_MAX),
+          mod_origin: lua
+            .named_registry_value::<Option<String>>(CURRENT_MOD_NAME_KEY)?
+            .unwrap_or_else(|| "unknown".to_string()),
+          ..ItemDefinition::new(&name)
+        };
+        let mod_origin = item_definition.mod_origin.clone();
+
+        items
+          .try_borrow_mut()
+          .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?
+          .register(item_definition)
+          .map_err(|e| mlua::Error::RuntimeError(format!("minetest: {}", e)))?;
+
+        definition.set("name", name.clone())?;
+        definition.set("mod_origin", mod_origin)?;
+        lua
+          .registry_value::<Table>(&registered_craftitems)?
+          .set(name, definition)
+      },
+    )?,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, rc::Rc};
+
+  use mlua::Lua;
+
+  use crate::game::{lua_engine::lua_items::create_item_api, server::item_registry::ItemRegistry};
+
+  #[test]
+  fn test_lua_item_api() {
+    println!("--- BEGIN LUA ITEM API TEST ---");
+    let items = Rc::new(RefCell::new(ItemRegistry::new()));
+
+    let lua = Lua::new();
+    let minetest = match lua.create_table() {
+      Ok(minetest) => minetest,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    if let Err(e) = create_item_api(&lua, &minetest, items.clone()) {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = lua.globals().set("minetest", minetest) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let code = "
+      minetest.register_craftitem('food:apple', {description = 'Apple', stack_max = 10})
+      local apple = minetest.registered_craftitems['food:apple']
+      return apple.name, apple.description, apple.mod_origin
+    ";
+    match lua.load(code).eval::<(String, String, String)>() {
+      Ok(result) => assert_eq!(
+        result,
+        (
+          "food:apple".to_string(),
+          "Apple".to_string(),
+          "unknown".to_string()
+        )
+      ),
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+    assert_eq!(items.borrow().get_stack_max("food:apple"), 10);
+
+    assert!(lua
+      .load("minetest.register_craftitem('food:apple', {})")
+      .exec()
+      .is_err());
+  }
+}
@@ -0,0 +1,46 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::{Lua, Table};
+
+use crate::game::server::maintenance::MaintenanceRequest;
+
+use super::lua_guard::create_guarded_function;
+
+///
+/// Adds the maintenance functions to the minetest table.
+///
+/// minetest.clear_objects() removes every entity that isn't a player
+/// minetest.cancel_maintenance() stops whatever maintenance is running
+///
+/// These only queue the request, the Server picks it up after this tick's
+/// Lua is done. minetest.register_on_maintenance_done hears when it stops.
+///
+pub fn create_maintenance_api(
+  lua: &Lua,
+  minetest: &Table,
+  requests: Rc<RefCell<Vec<MaintenanceRequest>>>,
+) -> mlua::Result<()> {
+  let shared = requests.clone();
+  minetest.set(
+    "clear_objects",
+    create_guarded_function(lua, "minetest.clear_objects", move |_, ()| {
+      shared
+        .try_borrow_mut()
+        .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?
+        .push(MaintenanceRequest::Start("clear_entities".to_string()));
+      Ok(())
+    })?,
+  )?;
+
+  let shared = requests;
+  minetest.set(
+    "cancel_maintenance",
+    create_guarded_function(lua, "minetest.cancel_maintenance", move |_, ()| {
+      shared
+        .try_borrow_mut()
+        .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?
+        .push(MaintenanceRequest::Cancel);
+      Ok(())
+    })?,
+  )
+}
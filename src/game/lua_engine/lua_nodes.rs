@@ -0,0 +1,189 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::{Lua, Table};
+
+use crate::game::server::node_registry::{DrawType, NodeDefinition, NodeRegistry, LIGHT_MAX};
+
+use super::{lua_guard::create_guarded_function, CURRENT_MOD_NAME_KEY};
+
+///
+/// Turn a node definition table from Lua into a NodeDefinition.
+///
+/// Everything but the name is optional.
+///
+fn read_node_definition(
+  lua: &Lua,
+  name: String,
+  definition: &Table,
+) -> mlua::Result<NodeDefinition> {
+  let drawtype_number = definition.get::<_, Option<u8>>("drawtype")?.unwrap_or(1);
+  let drawtype = match DrawType::from_number(drawtype_number) {
+    Some(drawtype) => drawtype,
+    None => {
+      return Err(mlua::Error::RuntimeError(format!(
+        "minetest: node [{}] has unknown drawtype [{}], use minetest.draw_type.",
+        name, drawtype_number
+      )))
+    }
+  };
+
+  Ok(NodeDefinition {
+    description: definition
+      .get::<_, Option<String>>("description")?
+      .unwrap_or_default(),
+    textures: definition
+      .get::<_, Option<Vec<String>>>("textures")?
+      .unwrap_or_default(),
+    drawtype,
+    walkable: definition
+      .get::<_, Option<bool>>("walkable")?
+      .unwrap_or(true),
+    light_source: definition
+      .get::<_, Option<u8>>("light_source")?
+      .unwrap_or(0)
+      .min(LIGHT_MAX),
+    mod_origin: lua
+      .named_registry_value::<Option<String>>(CURRENT_MOD_NAME_KEY)?
+      .unwrap_or_else(|| "unknown".to_string()),
+    ..NodeDefinition::new(&name)
+  })
+}
+
+///
+/// Adds the node registration functions to the minetest table.
+///
+/// minetest.register_node(name, {description, textures, drawtype, walkable, light_source})
+/// minetest.registered_nodes -> {[name] = definition}
+///
+/// The definitions go into the Server's NodeRegistry. registered_nodes
+/// hands back the tables the mods registered, with name and mod_origin
+/// filled in. Changing them afterwards doesn't change the NodeRegistry.
+///
+pub fn create_node_api(
+  lua: &Lua,
+  minetest: &Table,
+  nodes: Rc<RefCell<NodeRegistry>>,
+) -> mlua::Result<()> {
+  let registered_nodes = lua.create_table()?;
+  minetest.set("registered_nodes", registered_nodes.clone())?;
+  // Tables can't be moved into a function, keep it in the Lua registry.
+  let registered_nodes = lua.create_registry_value(registered_nodes)?;
+
+  minetest.set(
+    "register_node",
+    create_guarded_function(
+      lua,
+      "minetest.register_node",
+      move |lua, (name, definition): (String, Table)| {
+        let node_definition = read_node_definition(lua, name.clone(), &definition)?;
+        let mod_origin = node_definition.mod_origin.clone();
+
+        nodes
+          .try_borrow_mut()
+          .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?
+          .register(node_definition)
+          .map_err(|e| mlua::Error::RuntimeError(format!("minetest: {}", e)))?;
+
+        definition.set("name", name.clone())?;
+        definition.set("mod_origin", mod_origin)?;
+        lua
+          .registry_value::<Table>(&registered_nodes)?
+          .set(name, definition)
+      },
+    )?,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, rc::Rc};
+
+  use mlua::Lua;
+
+  use crate::game::{
+    lua_engine::{lua_nodes::create_node_api, CURRENT_MOD_NAME_KEY},
+    server::node_registry::{DrawType, NodeRegistry},
+  };
+
+  #[test]
+  fn test_lua_node_api() {
+    println!("--- BEGIN LUA NODE API TEST ---");
+    let nodes = Rc::new(RefCell::new(NodeRegistry::new(vec![])));
+
+    let lua = Lua::new();
+    let minetest = match lua.create_table() {
+      Ok(minetest) => minetest,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    if let Err(e) = create_node_api(&lua, &minetest, nodes.clone()) {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = lua.globals().set("minetest", minetest) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    // Two mods, one registry.
+    let rocks = "
+      minetest.register_node('rocks:lamp', {
+        description = 'Lamp',
+        textures = {'rocks_lamp.png'},
+        drawtype = 3,
+        walkable = false,
+        light_source = 99,
+      })
+    ";
+    let dirt = "minetest.register_node('dirt:dirt', {description = 'Dirt'})";
+    for (mod_name, code) in [("rocks", rocks), ("dirt", dirt)] {
+      if let Err(e) = lua.set_named_registry_value(CURRENT_MOD_NAME_KEY, mod_name) {
+        panic!("Unit test is broken. {}", e);
+      }
+      if let Err(e) = lua.load(code).exec() {
+        panic!("Unit test is broken. {}", e);
+      }
+    }
+
+    let code = "
+      local lamp = minetest.registered_nodes['rocks:lamp']
+      return lamp.name, lamp.description, lamp.textures[1], lamp.mod_origin,
+        minetest.registered_nodes['dirt:dirt'].mod_origin
+    ";
+    match lua
+      .load(code)
+      .eval::<(String, String, String, String, String)>()
+    {
+      Ok(result) => assert_eq!(
+        result,
+        (
+          "rocks:lamp".to_string(),
+          "Lamp".to_string(),
+          "rocks_lamp.png".to_string(),
+          "rocks".to_string(),
+          "dirt".to_string()
+        )
+      ),
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+
+    // The Server sees the same nodes.
+    let nodes = nodes.borrow();
+    assert_eq!(nodes.get_names(), vec!["dirt:dirt", "rocks:lamp"]);
+    let lamp = match nodes.get("rocks:lamp") {
+      Some(lamp) => lamp,
+      None => panic!("Unit test is broken. The lamp was not registered."),
+    };
+    assert_eq!(lamp.drawtype, DrawType::Mesh);
+    assert!(!lamp.walkable);
+    assert_eq!(lamp.light_source, 14);
+    drop(nodes);
+
+    // Bad names and duplicates are errors.
+    assert!(lua
+      .load("minetest.register_node('lamp', {})")
+      .exec()
+      .is_err());
+    assert!(lua
+      .load("minetest.register_node('dirt:dirt', {})")
+      .exec()
+      .is_err());
+  }
+}
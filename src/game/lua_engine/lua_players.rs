@@ -0,0 +1,126 @@
+use std::{cell::RefCell, rc::Rc};
+
+use glam::Vec3A;
+use mlua::{Lua, Table, UserData, UserDataMethods};
+
+use crate::game::server::server_info::ServerInfo;
+
+use super::{
+  lua_guard::{create_guarded_function, GuardedMethods},
+  lua_vector::LuaVector,
+};
+
+///
+/// A connected player, as Lua sees them. Only holds the name, so a player
+/// kept around after they leave is harmless.
+///
+struct LuaPlayer {
+  name: String,
+  // (name, position), the Server picks these up after this tick's Lua.
+  teleport_requests: Rc<RefCell<Vec<(String, Vec3A)>>>,
+}
+
+impl UserData for LuaPlayer {
+  fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+    methods.add_guarded_method("get_player_name", |_, this, ()| Ok(this.name.clone()));
+    methods.add_guarded_method("is_player", |_, _, ()| Ok(true));
+    methods.add_guarded_method("set_pos", |_, this, position: LuaVector| {
+      this
+        .teleport_requests
+        .try_borrow_mut()
+        .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?
+        .push((this.name.clone(), position.0));
+      Ok(())
+    });
+  }
+}
+
+///
+/// Adds the player functions to the minetest table.
+///
+/// minetest.get_player_by_name(name) -> the player, or nil if they aren't connected
+/// player:get_player_name() -> their name
+/// player:set_pos(vector) -> teleport them, like /teleport
+///
+/// set_pos only queues it, the Server moves them after this tick's Lua is done.
+///
+pub fn create_player_api(
+  lua: &Lua,
+  minetest: &Table,
+  server_info: Rc<RefCell<ServerInfo>>,
+  teleport_requests: Rc<RefCell<Vec<(String, Vec3A)>>>,
+) -> mlua::Result<()> {
+  minetest.set(
+    "get_player_by_name",
+    create_guarded_function(
+      lua,
+      "minetest.get_player_by_name",
+      move |_, name: String| {
+        let server_info = server_info
+          .try_borrow()
+          .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+        if !server_info.get_player_names().contains(&name) {
+          return Ok(None);
+        }
+        Ok(Some(LuaPlayer {
+          name,
+          teleport_requests: teleport_requests.clone(),
+        }))
+      },
+    )?,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, rc::Rc, time::Instant};
+
+  use glam::Vec3A;
+  use mlua::Lua;
+
+  use crate::game::{
+    lua_engine::{lua_players::create_player_api, lua_vector::create_vector_api},
+    server::server_info::ServerInfo,
+  };
+
+  #[test]
+  fn test_lua_player_set_pos() {
+    println!("--- BEGIN LUA PLAYER SET POS TEST ---");
+    let server_info = Rc::new(RefCell::new(ServerInfo::new(Instant::now())));
+    server_info
+      .borrow_mut()
+      .set_player_names(vec!["alice".to_string()]);
+    let teleport_requests = Rc::new(RefCell::new(vec![]));
+
+    let lua = Lua::new();
+    if let Err(e) = create_vector_api(&lua) {
+      panic!("Unit test is broken. {}", e);
+    }
+    let minetest = match lua.create_table() {
+      Ok(minetest) => minetest,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    if let Err(e) = create_player_api(&lua, &minetest, server_info, teleport_requests.clone()) {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = lua.globals().set("minetest", minetest) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let result = lua
+      .load(
+        "local alice = minetest.get_player_by_name('alice')
+        alice:set_pos(vector.new(1, 2, 3))
+        return alice:get_player_name(), minetest.get_player_by_name('bob') == nil",
+      )
+      .eval::<(String, bool)>();
+    match result {
+      Ok(result) => assert_eq!(result, ("alice".to_string(), true)),
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+    assert_eq!(
+      *teleport_requests.borrow(),
+      vec![("alice".to_string(), Vec3A::new(1.0, 2.0, 3.0))]
+    );
+  }
+}
@@ -0,0 +1,208 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::{Lua, Table, Value};
+
+use crate::game::server::privileges::Privileges;
+
+use super::lua_guard::create_guarded_function;
+
+///
+/// Turn a privilege list from Lua into names.
+///
+/// Takes what minetest C++ takes:
+/// "shout, kick", {shout = true, kick = true}, or {"shout", "kick"}
+///
+fn read_privilege_list(privs: Value) -> mlua::Result<Vec<String>> {
+  match privs {
+    Value::String(raw_privileges) => Ok(
+      raw_privileges
+        .to_str()?
+        .split(',')
+        .map(|privilege| privilege.trim().to_string())
+        .filter(|privilege| !privilege.is_empty())
+        .collect(),
+    ),
+    Value::Table(table) => {
+      let mut privileges = vec![];
+      for pair in table.pairs::<Value, Value>() {
+        match pair? {
+          (Value::String(privilege), Value::Boolean(true)) => {
+            privileges.push(privilege.to_str()?.to_string())
+          }
+          (Value::String(_), Value::Boolean(false)) => (),
+          (_, Value::String(privilege)) => privileges.push(privilege.to_str()?.to_string()),
+          (key, value) => {
+            return Err(mlua::Error::RuntimeError(format!(
+              "minetest: bad privilege list entry [{}] = [{}].",
+              key.type_name(),
+              value.type_name()
+            )))
+          }
+        }
+      }
+      Ok(privileges)
+    }
+    _ => Err(mlua::Error::FromLuaConversionError {
+      from: privs.type_name(),
+      to: "privilege list",
+      message: None,
+    }),
+  }
+}
+
+///
+/// Adds the privilege functions to the minetest table.
+///
+/// minetest.get_player_privs(name) -> {privilege = true}
+/// minetest.check_player_privs(name, privs) -> has all, missing
+/// minetest.grant_player_privs(name, privs)
+/// minetest.revoke_player_privs(name, privs)
+///
+/// These all share the Server's Privileges, so a grant from Lua shows
+/// up in chat commands and the other way around.
+///
+pub fn create_privileges_api(
+  lua: &Lua,
+  minetest: &Table,
+  privileges: Rc<RefCell<Privileges>>,
+) -> mlua::Result<()> {
+  let shared = privileges.clone();
+  minetest.set(
+    "get_player_privs",
+    create_guarded_function(
+      lua,
+      "minetest.get_player_privs",
+      move |lua, name: String| {
+        let privileges = shared
+          .try_borrow()
+          .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+        let table = lua.create_table()?;
+        for privilege in privileges.get(&name) {
+          table.set(privilege, true)?;
+        }
+        Ok(table)
+      },
+    )?,
+  )?;
+
+  let shared = privileges.clone();
+  minetest.set(
+    "check_player_privs",
+    create_guarded_function(
+      lua,
+      "minetest.check_player_privs",
+      move |_, (name, privs): (String, Value)| {
+        let privileges = shared
+          .try_borrow()
+          .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+        let missing: Vec<String> = read_privilege_list(privs)?
+          .into_iter()
+          .filter(|privilege| !privileges.has(&name, privilege))
+          .collect();
+        Ok((missing.is_empty(), missing))
+      },
+    )?,
+  )?;
+
+  let shared = privileges.clone();
+  minetest.set(
+    "grant_player_privs",
+    create_guarded_function(
+      lua,
+      "minetest.grant_player_privs",
+      move |_, (name, privs): (String, Value)| {
+        let mut privileges = shared
+          .try_borrow_mut()
+          .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+        for privilege in read_privilege_list(privs)? {
+          privileges
+            .grant(&name, &privilege)
+            .map_err(mlua::Error::RuntimeError)?;
+        }
+        Ok(())
+      },
+    )?,
+  )?;
+
+  let shared = privileges;
+  minetest.set(
+    "revoke_player_privs",
+    create_guarded_function(
+      lua,
+      "minetest.revoke_player_privs",
+      move |_, (name, privs): (String, Value)| {
+        let mut privileges = shared
+          .try_borrow_mut()
+          .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+        for privilege in read_privilege_list(privs)? {
+          privileges
+            .revoke(&name, &privilege)
+            .map_err(mlua::Error::RuntimeError)?;
+        }
+        Ok(())
+      },
+    )?,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, fs::remove_dir_all, rc::Rc};
+
+  use mlua::Lua;
+
+  use crate::{
+    file_utilities::test_dir,
+    game::{
+      lua_engine::lua_privileges::create_privileges_api,
+      server::privileges::{Privileges, DEFAULT_PRIVILEGES},
+    },
+  };
+
+  #[test]
+  fn test_lua_privileges_api() {
+    println!("--- BEGIN LUA PRIVILEGES API TEST ---");
+    let world_path = test_dir("lua_privileges_api");
+
+    let privileges = Rc::new(RefCell::new(Privileges::load(
+      &world_path,
+      DEFAULT_PRIVILEGES,
+    )));
+
+    let lua = Lua::new();
+    let minetest = match lua.create_table() {
+      Ok(minetest) => minetest,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    if let Err(e) = create_privileges_api(&lua, &minetest, privileges.clone()) {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = lua.globals().set("minetest", minetest) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let code = "
+      local before, missing = minetest.check_player_privs('bob', {kick = true, shout = true})
+      minetest.grant_player_privs('bob', 'kick')
+      minetest.revoke_player_privs('bob', {'shout'})
+      local after = minetest.check_player_privs('bob', {kick = true})
+      return before, missing[1], after, minetest.get_player_privs('bob').shout == nil
+    ";
+    match lua.load(code).eval::<(bool, String, bool, bool)>() {
+      Ok(result) => assert_eq!(result, (false, "kick".to_string(), true, true)),
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+
+    // Lua and the Server see the same privileges.
+    assert!(privileges.borrow().has("bob", "kick"));
+    assert!(!privileges.borrow().has("bob", "shout"));
+
+    // Unknown privileges are an error.
+    assert!(lua
+      .load("minetest.grant_player_privs('bob', 'fly')")
+      .exec()
+      .is_err());
+
+    let _ = remove_dir_all(&world_path);
+  }
+}
@@ -0,0 +1,220 @@
+///
+/// A small deterministic PRNG for reproducible generation.
+///
+/// This is xorshift64* seeded through splitmix64. It's only integer math,
+/// so the same seed gives the exact same sequence on every platform.
+///
+/// ! This is not OS randomness, do not use this for anything secret.
+///
+use mlua::{Lua, Table, UserData, UserDataMethods};
+
+use super::lua_guard::{create_guarded_function, GuardedMethods};
+
+///
+/// The biggest number next_int() will give you. Same as Minetest C++'s PseudoRandom.
+///
+pub const PSEUDO_RANDOM_MAX: u32 = 32767;
+
+#[derive(Clone, Debug)]
+pub struct PseudoRandom {
+  state: u64,
+}
+
+impl PseudoRandom {
+  pub fn new(seed: i64) -> Self {
+    // splitmix64 so that nearby seeds don't produce nearby sequences.
+    let mut mixed = (seed as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    mixed ^= mixed >> 31;
+
+    // xorshift gets stuck on 0 forever.
+    if mixed == 0 {
+      mixed = 0x9E37_79B9_7F4A_7C15;
+    }
+
+    PseudoRandom { state: mixed }
+  }
+
+  ///
+  /// Get the next raw 64 bits.
+  ///
+  pub fn next_u64(&mut self) -> u64 {
+    self.state ^= self.state >> 12;
+    self.state ^= self.state << 25;
+    self.state ^= self.state >> 27;
+    self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+  }
+
+  ///
+  /// Get the next number in [0, PSEUDO_RANDOM_MAX].
+  ///
+  pub fn next_int(&mut self) -> u32 {
+    (self.next_u64() >> 49) as u32
+  }
+
+  ///
+  /// Get the next number in [min, max]. Both ends are included.
+  ///
+  pub fn next_range(&mut self, min: i64, max: i64) -> Result<i64, String> {
+    if min > max {
+      return Err(format!(
+        "PseudoRandom: min [{}] is bigger than max [{}].",
+        min, max
+      ));
+    }
+
+    // Multiply shift instead of modulo to keep it unbiased enough and fast.
+    let span = (max as i128 - min as i128 + 1) as u128;
+    let offset = ((self.next_u64() as u128 * span) >> 64) as i128;
+
+    Ok((min as i128 + offset) as i64)
+  }
+}
+
+impl UserData for PseudoRandom {
+  fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+    methods.add_guarded_method_mut("next", |_, this, ()| Ok(this.next_int()));
+    methods.add_guarded_method_mut("next_range", |_, this, (min, max): (i64, i64)| {
+      this.next_range(min, max).map_err(mlua::Error::RuntimeError)
+    });
+  }
+}
+
+///
+/// Hands out the seeds of PRNGs that were made without one.
+///
+/// Kept in the Lua state's app data, see set_default_seed().
+///
+struct DefaultSeeder(PseudoRandom);
+
+///
+/// Make minetest.pseudo_random() without a seed deterministic.
+///
+/// Every PRNG made without a seed after this gets the next seed in a
+/// sequence that starts at this seed. Until this is called they get
+/// OS randomness.
+///
+pub fn set_default_seed(lua: &Lua, seed: u64) {
+  lua.set_app_data(DefaultSeeder(PseudoRandom::new(seed as i64)));
+}
+
+///
+/// Adds minetest.pseudo_random([seed]) to the minetest table.
+///
+pub fn create_pseudo_random_api(lua: &Lua, minetest: &Table) -> mlua::Result<()> {
+  minetest.set(
+    "pseudo_random",
+    create_guarded_function(lua, "minetest.pseudo_random", |lua, seed: Option<i64>| {
+      let seed = match seed {
+        Some(seed) => seed,
+        None => match lua.app_data_mut::<DefaultSeeder>() {
+          Some(mut seeder) => seeder.0.next_u64() as i64,
+          None => rand::random::<i64>(),
+        },
+      };
+      Ok(PseudoRandom::new(seed))
+    })?,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use mlua::Lua;
+
+  use crate::game::lua_engine::lua_pseudo_random::{
+    create_pseudo_random_api, set_default_seed, PseudoRandom, PSEUDO_RANDOM_MAX,
+  };
+
+  #[test]
+  fn test_pseudo_random_determinism() {
+    println!("--- BEGIN PSEUDO RANDOM DETERMINISM TEST ---");
+    let lua = Lua::new();
+    let minetest = match lua.create_table() {
+      Ok(minetest) => minetest,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    if let Err(e) = create_pseudo_random_api(&lua, &minetest) {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = lua.globals().set("minetest", minetest) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let sequence = |seed: i64| -> Vec<i64> {
+      let code = format!(
+        "local random = minetest.pseudo_random({})
+        local out = {{}}
+        for i = 1, 16 do
+          out[i] = (i % 2 == 0) and random:next() or random:next_range(-100, 100)
+        end
+        return out",
+        seed
+      );
+      match lua.load(code).eval::<Vec<i64>>() {
+        Ok(sequence) => sequence,
+        Err(e) => panic!("Unit test is broken. {}", e),
+      }
+    };
+
+    // Same seed, same sequence.
+    assert_eq!(sequence(1337), sequence(1337));
+    // Different seeds diverge.
+    assert_ne!(sequence(1337), sequence(1338));
+
+    // Lua and Rust agree.
+    let mut random = PseudoRandom::new(1337);
+    assert_eq!(random.next_range(-100, 100), Ok(sequence(1337)[0]));
+  }
+
+  #[test]
+  fn test_pseudo_random_default_seed() {
+    println!("--- BEGIN PSEUDO RANDOM DEFAULT SEED TEST ---");
+    let run = |default_seed: u64| -> Vec<u32> {
+      let lua = Lua::new();
+      let minetest = match lua.create_table() {
+        Ok(minetest) => minetest,
+        Err(e) => panic!("Unit test is broken. {}", e),
+      };
+      if let Err(e) = create_pseudo_random_api(&lua, &minetest) {
+        panic!("Unit test is broken. {}", e);
+      }
+      if let Err(e) = lua.globals().set("minetest", minetest) {
+        panic!("Unit test is broken. {}", e);
+      }
+      set_default_seed(&lua, default_seed);
+
+      let code = "
+        local first = minetest.pseudo_random()
+        local second = minetest.pseudo_random()
+        return {first:next(), first:next(), second:next(), second:next()}
+      ";
+      match lua.load(code).eval::<Vec<u32>>() {
+        Ok(sequence) => sequence,
+        Err(e) => panic!("Unit test is broken. {}", e),
+      }
+    };
+
+    // Two runs with the same seed match, every PRNG in them too.
+    assert_eq!(run(42), run(42));
+    assert_ne!(run(42), run(43));
+    let sequence = run(42);
+    assert_ne!(sequence[0..2], sequence[2..4]);
+  }
+
+  #[test]
+  fn test_pseudo_random_ranges() {
+    println!("--- BEGIN PSEUDO RANDOM RANGES TEST ---");
+    let mut random = PseudoRandom::new(0);
+    for _ in 0..1000 {
+      assert!(random.next_int() <= PSEUDO_RANDOM_MAX);
+      match random.next_range(-3, 3) {
+        Ok(value) => assert!((-3..=3).contains(&value)),
+        Err(e) => panic!("Unit test is broken. {}", e),
+      }
+    }
+    assert_eq!(random.next_range(5, 5), Ok(5));
+    assert!(random.next_range(i64::MIN, i64::MAX).is_ok());
+    assert!(random.next_range(1, 0).is_err());
+  }
+}
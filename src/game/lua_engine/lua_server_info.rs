@@ -0,0 +1,121 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::{Lua, Table};
+
+use crate::game::server::server_info::ServerInfo;
+
+use super::lua_guard::create_guarded_function;
+
+///
+/// Adds the server info functions to the minetest table.
+///
+/// minetest.get_server_uptime() -> seconds since the Server started
+/// minetest.get_player_count() -> how many players are connected
+/// minetest.get_server_status() -> the same text as the status command
+///
+/// The count and status are as of the start of this tick.
+///
+pub fn create_server_info_api(
+  lua: &Lua,
+  minetest: &Table,
+  server_info: Rc<RefCell<ServerInfo>>,
+) -> mlua::Result<()> {
+  let shared = server_info.clone();
+  minetest.set(
+    "get_server_uptime",
+    create_guarded_function(lua, "minetest.get_server_uptime", move |_, ()| {
+      let server_info = shared
+        .try_borrow()
+        .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+      Ok(server_info.get_uptime())
+    })?,
+  )?;
+
+  let shared = server_info.clone();
+  minetest.set(
+    "get_player_count",
+    create_guarded_function(lua, "minetest.get_player_count", move |_, ()| {
+      let server_info = shared
+        .try_borrow()
+        .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+      Ok(server_info.get_player_count())
+    })?,
+  )?;
+
+  let shared = server_info;
+  minetest.set(
+    "get_server_status",
+    create_guarded_function(lua, "minetest.get_server_status", move |_, ()| {
+      let server_info = shared
+        .try_borrow()
+        .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+      Ok(server_info.get_status().to_string())
+    })?,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, rc::Rc, thread, time::Duration, time::Instant};
+
+  use mlua::Lua;
+
+  use crate::game::{
+    lua_engine::lua_server_info::create_server_info_api, server::server_info::ServerInfo,
+  };
+
+  #[test]
+  fn test_lua_server_info_api() {
+    println!("--- BEGIN LUA SERVER INFO API TEST ---");
+    let server_info = Rc::new(RefCell::new(ServerInfo::new(Instant::now())));
+
+    let lua = Lua::new();
+    let minetest = match lua.create_table() {
+      Ok(minetest) => minetest,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    if let Err(e) = create_server_info_api(&lua, &minetest, server_info.clone()) {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = lua.globals().set("minetest", minetest) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    // Uptime only goes up.
+    let mut last_uptime = 0.0;
+    for _ in 0..3 {
+      thread::sleep(Duration::from_millis(5));
+      let uptime = match lua
+        .load("return minetest.get_server_uptime()")
+        .eval::<f64>()
+      {
+        Ok(uptime) => uptime,
+        Err(e) => panic!("Unit test is broken. {}", e),
+      };
+      assert!(uptime > last_uptime);
+      last_uptime = uptime;
+    }
+
+    // The count follows whoever the Server says is connected.
+    let count = || match lua.load("return minetest.get_player_count()").eval::<u32>() {
+      Ok(count) => count,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    assert_eq!(count(), 0);
+    server_info
+      .borrow_mut()
+      .set_player_names(vec!["alice".to_string(), "bob".to_string()]);
+    assert_eq!(count(), 2);
+
+    server_info
+      .borrow_mut()
+      .set_status("Server [Test]".to_string());
+    match lua
+      .load("return minetest.get_server_status()")
+      .eval::<String>()
+    {
+      Ok(status) => assert_eq!(status, "Server [Test]"),
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+  }
+}
@@ -0,0 +1,146 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::{Lua, Table, Value};
+
+use crate::game::server::sound_request::SoundRequest;
+
+use super::lua_guard::create_guarded_function;
+
+///
+/// Read a gain or pitch multiplier out of a table, 1.0 if it's not there.
+///
+fn read_multiplier(table: Option<&Table>, key: &str) -> mlua::Result<f32> {
+  let multiplier = match table {
+    Some(table) => table.get::<_, Option<f32>>(key)?.unwrap_or(1.0),
+    None => 1.0,
+  };
+  if !multiplier.is_finite() || multiplier < 0.0 {
+    return Err(mlua::Error::RuntimeError(format!(
+      "minetest: sound {} has to be a positive number, got [{}].",
+      key, multiplier
+    )));
+  }
+  Ok(multiplier)
+}
+
+///
+/// Adds the sound functions to the minetest table.
+///
+/// minetest.sound_play(spec, {gain, pitch, to_player})
+///
+/// spec is a sound name, or a table {name, gain, pitch} like minetest
+/// C++'s SimpleSoundSpec. Gains and pitches from both get multiplied.
+/// An empty name plays nothing.
+///
+/// The requests go into a queue the Server sends out every tick.
+///
+pub fn create_sound_api(
+  lua: &Lua,
+  minetest: &Table,
+  sounds: Rc<RefCell<Vec<SoundRequest>>>,
+) -> mlua::Result<()> {
+  minetest.set(
+    "sound_play",
+    create_guarded_function(
+      lua,
+      "minetest.sound_play",
+      move |_, (spec, params): (Value, Option<Table>)| {
+        let (name, spec_table) = match spec {
+          Value::String(name) => (name.to_str()?.to_string(), None),
+          Value::Table(spec_table) => (spec_table.get::<_, String>("name")?, Some(spec_table)),
+          _ => {
+            return Err(mlua::Error::FromLuaConversionError {
+              from: spec.type_name(),
+              to: "sound spec",
+              message: None,
+            })
+          }
+        };
+        if name.is_empty() {
+          return Ok(());
+        }
+
+        let request = SoundRequest {
+          name,
+          gain: read_multiplier(spec_table.as_ref(), "gain")?
+            * read_multiplier(params.as_ref(), "gain")?,
+          pitch: read_multiplier(spec_table.as_ref(), "pitch")?
+            * read_multiplier(params.as_ref(), "pitch")?,
+          to_player: match &params {
+            Some(params) => params.get::<_, Option<String>>("to_player")?,
+            None => None,
+          },
+        };
+
+        sounds
+          .try_borrow_mut()
+          .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?
+          .push(request);
+        Ok(())
+      },
+    )?,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, rc::Rc};
+
+  use mlua::Lua;
+
+  use crate::game::{lua_engine::lua_sound::create_sound_api, network_message::NetworkMessage};
+
+  #[test]
+  fn test_lua_sound_play() {
+    println!("--- BEGIN LUA SOUND PLAY TEST ---");
+    let sounds = Rc::new(RefCell::new(vec![]));
+
+    let lua = Lua::new();
+    let minetest = match lua.create_table() {
+      Ok(minetest) => minetest,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    if let Err(e) = create_sound_api(&lua, &minetest, sounds.clone()) {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = lua.globals().set("minetest", minetest) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let code = "
+      minetest.sound_play('default_dig', {gain = 0.5, to_player = 'bob'})
+      minetest.sound_play({name = 'default_place', pitch = 2.0}, {pitch = 0.5, gain = 0.8})
+      minetest.sound_play('')
+    ";
+    if let Err(e) = lua.load(code).exec() {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let sounds = sounds.borrow();
+    assert_eq!(sounds.len(), 2);
+    assert_eq!(sounds[0].to_player, Some("bob".to_string()));
+    assert_eq!(
+      sounds[0].to_message(),
+      NetworkMessage::PlaySound {
+        name: "default_dig".to_string(),
+        gain: 0.5,
+        pitch: 1.0,
+      }
+    );
+    assert_eq!(sounds[1].to_player, None);
+    assert_eq!(
+      sounds[1].to_message(),
+      NetworkMessage::PlaySound {
+        name: "default_place".to_string(),
+        gain: 0.8,
+        pitch: 1.0,
+      }
+    );
+    drop(sounds);
+
+    assert!(lua
+      .load("minetest.sound_play('default_dig', {gain = -1})")
+      .exec()
+      .is_err());
+  }
+}
@@ -0,0 +1,96 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::{Lua, Table};
+
+use crate::game::time_of_day::TimeOfDay;
+
+use super::lua_guard::create_guarded_function;
+
+///
+/// Adds the time of day functions to the minetest table.
+///
+/// minetest.get_timeofday() -> 0.0 to 1.0, 0.5 is noon
+/// minetest.set_timeofday(time)
+///
+/// These share the Server's TimeOfDay, a set goes out to the clients
+/// on the next tick.
+///
+pub fn create_time_of_day_api(
+  lua: &Lua,
+  minetest: &Table,
+  time_of_day: Rc<RefCell<TimeOfDay>>,
+) -> mlua::Result<()> {
+  let shared = time_of_day.clone();
+  minetest.set(
+    "get_timeofday",
+    create_guarded_function(lua, "minetest.get_timeofday", move |_, ()| {
+      let time_of_day = shared
+        .try_borrow()
+        .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+      Ok(time_of_day.get())
+    })?,
+  )?;
+
+  let shared = time_of_day;
+  minetest.set(
+    "set_timeofday",
+    create_guarded_function(lua, "minetest.set_timeofday", move |_, time: f64| {
+      // minetest C++ refuses these too.
+      if !(0.0..=1.0).contains(&time) {
+        return Err(mlua::Error::RuntimeError(format!(
+          "minetest: set_timeofday takes 0.0 to 1.0, got [{}].",
+          time
+        )));
+      }
+      let mut time_of_day = shared
+        .try_borrow_mut()
+        .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+      time_of_day.set(time);
+      Ok(())
+    })?,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, rc::Rc};
+
+  use mlua::Lua;
+
+  use crate::game::{lua_engine::lua_time_of_day::create_time_of_day_api, time_of_day::TimeOfDay};
+
+  #[test]
+  fn test_lua_time_of_day_api() {
+    println!("--- BEGIN LUA TIME OF DAY API TEST ---");
+    let time_of_day = Rc::new(RefCell::new(TimeOfDay::new(0.25, 72.0)));
+
+    let lua = Lua::new();
+    let minetest = match lua.create_table() {
+      Ok(minetest) => minetest,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    if let Err(e) = create_time_of_day_api(&lua, &minetest, time_of_day.clone()) {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = lua.globals().set("minetest", minetest) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let code = "
+      local before = minetest.get_timeofday()
+      minetest.set_timeofday(0.5)
+      return before, minetest.get_timeofday()
+    ";
+    match lua.load(code).eval::<(f64, f64)>() {
+      Ok(result) => assert_eq!(result, (0.25, 0.5)),
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+
+    // Lua and the Server see the same clock.
+    assert_eq!(time_of_day.borrow().get(), 0.5);
+    assert!(time_of_day.borrow_mut().take_was_set());
+
+    // Out of range is an error.
+    assert!(lua.load("minetest.set_timeofday(2)").exec().is_err());
+  }
+}
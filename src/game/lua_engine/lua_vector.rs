@@ -0,0 +1,184 @@
+///
+/// Lua bindings for glam's Vec3A.
+///
+/// Mods do a lot of vector math. Handing them userdata instead of
+/// {x, y, z} tables avoids churning through tables on every operation.
+///
+use glam::Vec3A;
+use mlua::{FromLua, Lua, MetaMethod, UserData, UserDataFields, UserDataMethods, Value};
+
+use super::lua_guard::{create_guarded_function, GuardedMethods};
+
+///
+/// A Vec3A wrapped up so it can live inside of Lua.
+///
+#[derive(Clone, Copy, Debug)]
+pub struct LuaVector(pub Vec3A);
+
+impl UserData for LuaVector {
+  fn add_fields<'lua, F: UserDataFields<'lua, Self>>(fields: &mut F) {
+    fields.add_field_method_get("x", |_, this| Ok(this.0.x));
+    fields.add_field_method_get("y", |_, this| Ok(this.0.y));
+    fields.add_field_method_get("z", |_, this| Ok(this.0.z));
+
+    fields.add_field_method_set("x", |_, this, x: f32| {
+      this.0.x = x;
+      Ok(())
+    });
+    fields.add_field_method_set("y", |_, this, y: f32| {
+      this.0.y = y;
+      Ok(())
+    });
+    fields.add_field_method_set("z", |_, this, z: f32| {
+      this.0.z = z;
+      Ok(())
+    });
+  }
+
+  fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+    methods.add_guarded_meta_method(MetaMethod::Add, |_, this, other: LuaVector| {
+      Ok(LuaVector(this.0 + other.0))
+    });
+    methods.add_guarded_meta_method(MetaMethod::Sub, |_, this, other: LuaVector| {
+      Ok(LuaVector(this.0 - other.0))
+    });
+    methods.add_guarded_meta_method(MetaMethod::Unm, |_, this, ()| Ok(LuaVector(-this.0)));
+    methods.add_guarded_meta_method(MetaMethod::Eq, |_, this, other: LuaVector| {
+      Ok(this.0 == other.0)
+    });
+    methods.add_guarded_meta_method(MetaMethod::ToString, |_, this, ()| {
+      Ok(format!("({}, {}, {})", this.0.x, this.0.y, this.0.z))
+    });
+  }
+}
+
+impl<'lua> FromLua<'lua> for LuaVector {
+  fn from_lua(value: Value<'lua>, _: &'lua Lua) -> mlua::Result<Self> {
+    match value {
+      Value::UserData(user_data) => Ok(*user_data.borrow::<LuaVector>()?),
+      _ => Err(mlua::Error::FromLuaConversionError {
+        from: value.type_name(),
+        to: "vector",
+        message: None,
+      }),
+    }
+  }
+}
+
+///
+/// Creates the global vector table.
+///
+/// NaN and inf go straight through, but normalizing a zero vector
+/// gives you a zero vector instead of NaN.
+///
+pub fn create_vector_api(lua: &Lua) -> mlua::Result<()> {
+  let vector = lua.create_table()?;
+
+  vector.set(
+    "new",
+    create_guarded_function(lua, "vector.new", |_, (x, y, z): (f32, f32, f32)| {
+      Ok(LuaVector(Vec3A::new(x, y, z)))
+    })?,
+  )?;
+  vector.set(
+    "add",
+    create_guarded_function(lua, "vector.add", |_, (a, b): (LuaVector, LuaVector)| {
+      Ok(LuaVector(a.0 + b.0))
+    })?,
+  )?;
+  vector.set(
+    "subtract",
+    create_guarded_function(
+      lua,
+      "vector.subtract",
+      |_, (a, b): (LuaVector, LuaVector)| Ok(LuaVector(a.0 - b.0)),
+    )?,
+  )?;
+  vector.set(
+    "length",
+    create_guarded_function(lua, "vector.length", |_, a: LuaVector| Ok(a.0.length()))?,
+  )?;
+  vector.set(
+    "normalize",
+    create_guarded_function(lua, "vector.normalize", |_, a: LuaVector| {
+      Ok(LuaVector(a.0.normalize_or_zero()))
+    })?,
+  )?;
+  vector.set(
+    "distance",
+    create_guarded_function(
+      lua,
+      "vector.distance",
+      |_, (a, b): (LuaVector, LuaVector)| Ok(a.0.distance(b.0)),
+    )?,
+  )?;
+
+  lua.globals().set("vector", vector)
+}
+
+#[cfg(test)]
+mod tests {
+  use mlua::Lua;
+
+  use crate::game::lua_engine::lua_vector::create_vector_api;
+
+  ///
+  /// Run some Lua with the vector API and get the results back.
+  ///
+  fn eval<T: for<'lua> mlua::FromLuaMulti<'lua>>(code: &str) -> T {
+    let lua = Lua::new();
+    if let Err(e) = create_vector_api(&lua) {
+      panic!("Unit test is broken. {}", e);
+    }
+    match lua.load(code).eval::<T>() {
+      Ok(result) => result,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+  }
+
+  #[test]
+  fn test_lua_vector_metamethods() {
+    println!("--- BEGIN LUA VECTOR METAMETHODS TEST ---");
+    let added: (f32, f32, f32) = eval(
+      "local c = vector.new(1, 2, 3) + vector.new(4, 5, 6)
+      return c.x, c.y, c.z",
+    );
+    assert_eq!(added, (5.0, 7.0, 9.0));
+
+    let subtracted: (f32, f32, f32) = eval(
+      "local c = vector.new(1, 2, 3) - vector.new(4, 6, 8)
+      return c.x, c.y, c.z",
+    );
+    assert_eq!(subtracted, (-3.0, -4.0, -5.0));
+
+    // The functions match the metamethods.
+    let same: bool = eval(
+      "local a, b = vector.new(1, 2, 3), vector.new(4, 5, 6)
+      return vector.add(a, b) == a + b and vector.subtract(a, b) == a - b",
+    );
+    assert!(same);
+  }
+
+  #[test]
+  fn test_lua_vector_math() {
+    println!("--- BEGIN LUA VECTOR MATH TEST ---");
+    let length: f32 = eval("return vector.length(vector.new(3, 4, 0))");
+    assert_eq!(length, 5.0);
+
+    let distance: f32 = eval("return vector.distance(vector.new(1, 1, 1), vector.new(1, 1, 3))");
+    assert_eq!(distance, 2.0);
+
+    let normalized: (f32, f32, f32) = eval(
+      "local n = vector.normalize(vector.new(0, 0, 10))
+      return n.x, n.y, n.z",
+    );
+    assert_eq!(normalized, (0.0, 0.0, 1.0));
+
+    // Zero stays zero instead of turning into NaN.
+    let zero: (f32, f32, f32) = eval(
+      "local n = vector.normalize(vector.new(0, 0, 0))
+      return n.x, n.y, n.z",
+    );
+    assert_eq!(zero, (0.0, 0.0, 0.0));
+  }
+}
@@ -0,0 +1,83 @@
+use mlua::{Lua, Table};
+
+use crate::game::server::world_limits::WorldLimits;
+
+use super::{lua_guard::create_guarded_function, lua_vector::LuaVector};
+
+///
+/// Adds the spawn point and world border to the minetest table.
+///
+/// minetest.get_spawn_point() -> vector, static_spawnpoint from minetest.conf
+/// minetest.get_mapgen_limit() -> how far the world goes on each axis
+///
+/// These don't change while the Server is running.
+///
+pub fn create_world_limits_api(
+  lua: &Lua,
+  minetest: &Table,
+  world_limits: WorldLimits,
+) -> mlua::Result<()> {
+  minetest.set(
+    "get_spawn_point",
+    create_guarded_function(lua, "minetest.get_spawn_point", move |_, ()| {
+      Ok(LuaVector(world_limits.get_spawn_point()))
+    })?,
+  )?;
+  minetest.set(
+    "get_mapgen_limit",
+    create_guarded_function(lua, "minetest.get_mapgen_limit", move |_, ()| {
+      Ok(world_limits.get_mapgen_limit())
+    })?,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use mlua::Lua;
+
+  use crate::game::{
+    game_config::GameConfig, lua_engine::lua_vector::create_vector_api,
+    lua_engine::lua_world_limits::create_world_limits_api, server::world_limits::WorldLimits,
+  };
+
+  #[test]
+  fn test_lua_world_limits_api() {
+    println!("--- BEGIN LUA WORLD LIMITS API TEST ---");
+    // The spawn point can't be outside of the border.
+    let config = GameConfig::parse("static_spawnpoint = 5, 500, -5\nmapgen_limit = 200");
+    let world_limits = WorldLimits::from_config(&config);
+
+    let lua = Lua::new();
+    if let Err(e) = create_vector_api(&lua) {
+      panic!("Unit test is broken. {}", e);
+    }
+    let minetest = match lua.create_table() {
+      Ok(minetest) => minetest,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    if let Err(e) = create_world_limits_api(&lua, &minetest, world_limits) {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = lua.globals().set("minetest", minetest) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    match lua
+      .load(
+        "local spawn = minetest.get_spawn_point()
+        return spawn.x, spawn.y, spawn.z, minetest.get_mapgen_limit()",
+      )
+      .eval::<(f32, f32, f32, f32)>()
+    {
+      Ok(limits) => assert_eq!(limits, (5.0, 200.0, -5.0, 200.0)),
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+
+    // Past the most minetest C++ allows is brought back down.
+    let config = GameConfig::parse("mapgen_limit = 99999");
+    assert_eq!(
+      WorldLimits::from_config(&config).get_mapgen_limit(),
+      31007.0
+    );
+  }
+}
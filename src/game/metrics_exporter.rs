@@ -0,0 +1,400 @@
+use std::{
+  fmt::Write as _,
+  io::{BufRead, BufReader, Read, Write},
+  net::{SocketAddr, TcpListener, TcpStream},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+  },
+  thread,
+  time::{Duration, Instant},
+};
+
+use super::{
+  connection_stats::{ReceiveHistogram, RECEIVE_GAP_BUCKETS},
+  game_config::GameConfig,
+};
+
+///
+/// How long a scraper gets to send its whole request, and to take the answer.
+///
+/// Scrapes are answered one at a time, a stuck one can't hold the rest up for longer.
+///
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+///
+/// The most a request can be before it's dropped. [bytes]
+///
+/// A scrape is a line or two, this stops a client trickling headers in forever.
+///
+const MAX_REQUEST_BYTES: u64 = 8 * 1024;
+
+///
+/// The numbers a server exposes for monitoring.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ServerMetrics {
+  pub player_count: u32,
+  pub ticks_per_second: f64,
+  // How long the last tick took. [seconds]
+  pub tick_duration: f64,
+  pub lagged_ticks: u64,
+  // Summed over every player's session.
+  pub bytes_in_per_second: f64,
+  pub bytes_out_per_second: f64,
+  // Time between packets from players, over every session.
+  pub receive_gaps: ReceiveHistogram,
+}
+
+impl ServerMetrics {
+  ///
+  /// Turn the metrics into the Prometheus text format.
+  ///
+  pub fn to_prometheus(self) -> String {
+    let metrics: [(&str, &str, &str, f64); 6] = [
+      (
+        "minetest_players",
+        "gauge",
+        "Players with a session.",
+        self.player_count as f64,
+      ),
+      (
+        "minetest_ticks_per_second",
+        "gauge",
+        "Server ticks per second.",
+        self.ticks_per_second,
+      ),
+      (
+        "minetest_tick_duration_seconds",
+        "gauge",
+        "How long the last tick took.",
+        self.tick_duration,
+      ),
+      (
+        "minetest_lagged_ticks_total",
+        "counter",
+        "Ticks that went over the tick budget.",
+        self.lagged_ticks as f64,
+      ),
+      (
+        "minetest_network_receive_bytes_per_second",
+        "gauge",
+        "Bytes per second received from players.",
+        self.bytes_in_per_second,
+      ),
+      (
+        "minetest_network_transmit_bytes_per_second",
+        "gauge",
+        "Bytes per second sent to players.",
+        self.bytes_out_per_second,
+      ),
+    ];
+
+    let mut text = String::new();
+    for (name, kind, help, value) in metrics {
+      let _ = write!(
+        text,
+        "# HELP {} {}\n# TYPE {} {}\n{} {}\n",
+        name, help, name, kind, name, value
+      );
+    }
+
+    // Prometheus buckets count everything up to their bound, not just their own slice.
+    let name = "minetest_network_receive_gap_seconds";
+    let _ = write!(
+      text,
+      "# HELP {} Time between packets from players.\n# TYPE {} histogram\n",
+      name, name
+    );
+    let mut cumulative = 0;
+    for (bound, count) in RECEIVE_GAP_BUCKETS.iter().zip(self.receive_gaps.counts) {
+      cumulative += count;
+      let _ = writeln!(text, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+    }
+    let total = self.receive_gaps.get_total();
+    let _ = writeln!(text, "{}_bucket{{le=\"+Inf\"}} {}", name, total);
+    let _ = writeln!(text, "{}_sum {}", name, self.receive_gaps.sum);
+    let _ = writeln!(text, "{}_count {}", name, total);
+    text
+  }
+}
+
+///
+/// A Prometheus endpoint for the server's metrics, over HTTP.
+///
+/// It's off unless minetest.conf has metrics_port, bound to
+/// metrics_address. (default 127.0.0.1) Any path answers with the metrics.
+///
+/// The Game publishes the ServerMetrics every tick, and the scrapes are
+/// answered on a background thread. Publishing never waits on a scrape,
+/// if one is reading the metrics that tick's are just skipped.
+///
+pub struct MetricsExporter {
+  address: SocketAddr,
+  running: Arc<AtomicBool>,
+  metrics: Arc<Mutex<ServerMetrics>>,
+}
+
+impl MetricsExporter {
+  ///
+  /// Start the exporter, if minetest.conf turns it on.
+  ///
+  pub fn from_config(config: &GameConfig) -> Option<Self> {
+    if !config.has("metrics_port") {
+      return None;
+    }
+
+    let address = config.get_string("metrics_address", "127.0.0.1");
+    let port = config.get_parsed::<u16>("metrics_port", 0);
+
+    match Self::new(&address, port) {
+      Ok(metrics_exporter) => Some(metrics_exporter),
+      Err(e) => {
+        println!("MetricsExporter: {}", e);
+        None
+      }
+    }
+  }
+
+  pub fn new(address: &str, port: u16) -> Result<Self, String> {
+    let listener = match TcpListener::bind((address, port)) {
+      Ok(listener) => listener,
+      Err(e) => return Err(format!("Failed to bind [{}:{}]. {}", address, port, e)),
+    };
+    let real_address = match listener.local_addr() {
+      Ok(real_address) => real_address,
+      Err(e) => return Err(format!("Failed to get the bound address. {}", e)),
+    };
+
+    let running = Arc::new(AtomicBool::new(true));
+    let metrics = Arc::new(Mutex::new(ServerMetrics::default()));
+    let thread_running = running.clone();
+    let thread_metrics = metrics.clone();
+
+    // ? Like the RemoteConsole, this thread can't be woken up while it's
+    // ? blocked on accept. It finishes on the next scrape after the
+    // ? exporter is dropped, or when the process exits.
+    let spawn_result = thread::Builder::new()
+      .name("metrics_exporter".to_string())
+      .spawn(move || Self::accept_scrapes(listener, thread_metrics, thread_running));
+
+    if let Err(e) = spawn_result {
+      return Err(format!("Failed to start metrics exporter thread. {}", e));
+    }
+
+    println!("MetricsExporter: listening on [{}].", real_address);
+
+    Ok(MetricsExporter {
+      address: real_address,
+      running,
+      metrics,
+    })
+  }
+
+  ///
+  /// Get the address the exporter actually bound to.
+  ///
+  pub fn get_address(&self) -> SocketAddr {
+    self.address
+  }
+
+  ///
+  /// Hand over this tick's metrics. This never blocks.
+  ///
+  pub fn publish(&self, metrics: ServerMetrics) {
+    if let Ok(mut published) = self.metrics.try_lock() {
+      *published = metrics;
+    }
+  }
+
+  ///
+  /// Answer scrapes, one at a time, until the exporter is stopped.
+  ///
+  /// Scrapes are rare and tiny, they don't need their own threads.
+  ///
+  fn accept_scrapes(
+    listener: TcpListener,
+    metrics: Arc<Mutex<ServerMetrics>>,
+    running: Arc<AtomicBool>,
+  ) {
+    for stream_result in listener.incoming() {
+      if !running.load(Ordering::Relaxed) {
+        break;
+      }
+
+      let stream = match stream_result {
+        Ok(stream) => stream,
+        Err(e) => {
+          println!("MetricsExporter: Failed to accept scrape. {}", e);
+          continue;
+        }
+      };
+
+      if let Err(e) = Self::serve_scrape(stream, &metrics) {
+        println!("MetricsExporter: {}", e);
+      }
+    }
+  }
+
+  ///
+  /// Read an HTTP request and answer it with the metrics.
+  ///
+  fn serve_scrape(stream: TcpStream, metrics: &Mutex<ServerMetrics>) -> Result<(), String> {
+    if let Err(e) = stream.set_write_timeout(Some(REQUEST_TIMEOUT)) {
+      return Err(format!("Failed to set the write timeout. {}", e));
+    }
+    let mut writer = match stream.try_clone() {
+      Ok(writer) => writer,
+      Err(e) => return Err(format!("Failed to clone stream. {}", e)),
+    };
+
+    // Skip through the headers, up to the empty line. The request doesn't matter.
+    // The read timeout shrinks as it goes, the deadline is for the whole request.
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST_BYTES));
+    let mut line = String::new();
+    loop {
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      if remaining.is_zero() {
+        return Err("The request took too long.".to_string());
+      }
+      if let Err(e) = reader.get_ref().get_ref().set_read_timeout(Some(remaining)) {
+        return Err(format!("Failed to set the read timeout. {}", e));
+      }
+
+      line.clear();
+      match reader.read_line(&mut line) {
+        Ok(0) => return Err("The request ended early, or was too big.".to_string()),
+        Ok(_) if line.trim().is_empty() => break,
+        Ok(_) => (),
+        Err(e) => return Err(format!("Failed to read the request. {}", e)),
+      }
+    }
+
+    // Copy them out, so the lock isn't held while writing.
+    let body = match metrics.lock() {
+      Ok(metrics) => *metrics,
+      Err(e) => return Err(format!("Metrics are poisoned. {}", e)),
+    }
+    .to_prometheus();
+
+    let response = format!(
+      "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+      body.len(),
+      body
+    );
+    match writer.write_all(response.as_bytes()) {
+      Ok(_) => Ok(()),
+      Err(e) => Err(format!("Failed to send the metrics. {}", e)),
+    }
+  }
+}
+
+impl Drop for MetricsExporter {
+  fn drop(&mut self) {
+    self.running.store(false, Ordering::Relaxed);
+    println!("MetricsExporter dropped!");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    time::Duration,
+  };
+
+  use crate::game::{
+    connection_stats::ReceiveHistogram,
+    game_config::GameConfig,
+    metrics_exporter::{MetricsExporter, ServerMetrics},
+  };
+
+  #[test]
+  fn test_metrics_exporter_scrape() {
+    println!("--- BEGIN METRICS EXPORTER SCRAPE TEST ---");
+    // Off by default.
+    assert!(MetricsExporter::from_config(&GameConfig::new()).is_none());
+
+    let metrics_exporter =
+      match MetricsExporter::from_config(&GameConfig::parse("metrics_port = 0")) {
+        Some(metrics_exporter) => metrics_exporter,
+        None => panic!("Unit test is broken. The exporter did not start."),
+      };
+    metrics_exporter.publish(ServerMetrics {
+      player_count: 3,
+      ticks_per_second: 20.0,
+      tick_duration: 0.004,
+      lagged_ticks: 7,
+      bytes_in_per_second: 1200.0,
+      bytes_out_per_second: 3400.0,
+      receive_gaps: ReceiveHistogram {
+        counts: [1, 0, 0, 4, 0, 0, 0, 0, 2],
+        sum: 5.2,
+      },
+    });
+
+    let mut stream = match TcpStream::connect(metrics_exporter.get_address()) {
+      Ok(stream) => stream,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+    if let Err(e) = stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n") {
+      panic!("Unit test is broken. {}", e);
+    }
+    let mut response = String::new();
+    if let Err(e) = stream.read_to_string(&mut response) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    for expected in [
+      "minetest_players 3",
+      "minetest_ticks_per_second 20",
+      "minetest_tick_duration_seconds 0.004",
+      "minetest_lagged_ticks_total 7",
+      "minetest_network_receive_bytes_per_second 1200",
+      "minetest_network_transmit_bytes_per_second 3400",
+      "# TYPE minetest_lagged_ticks_total counter",
+      "# TYPE minetest_network_receive_gap_seconds histogram",
+      "minetest_network_receive_gap_seconds_bucket{le=\"0.005\"} 1",
+      "minetest_network_receive_gap_seconds_bucket{le=\"0.05\"} 5",
+      "minetest_network_receive_gap_seconds_bucket{le=\"1\"} 5",
+      "minetest_network_receive_gap_seconds_bucket{le=\"+Inf\"} 7",
+      "minetest_network_receive_gap_seconds_sum 5.2",
+      "minetest_network_receive_gap_seconds_count 7",
+    ] {
+      assert!(response.contains(expected), "Missing [{}]", expected);
+    }
+
+    // Headers that never end get cut off, and the next scrape still gets through.
+    let mut stream = match TcpStream::connect(metrics_exporter.get_address()) {
+      Ok(stream) => stream,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+    let junk = format!(
+      "GET /metrics HTTP/1.1\r\nX-Junk: {}\r\n",
+      "a".repeat(10_000)
+    );
+    let _ = stream.write_all(junk.as_bytes());
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+    assert!(!response.contains("200 OK"));
+
+    let mut stream = match TcpStream::connect(metrics_exporter.get_address()) {
+      Ok(stream) => stream,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+    if let Err(e) = stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n") {
+      panic!("Unit test is broken. {}", e);
+    }
+    let mut response = String::new();
+    if let Err(e) = stream.read_to_string(&mut response) {
+      panic!("Unit test is broken. {}", e);
+    }
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+  }
+}
@@ -0,0 +1,211 @@
+use std::{fmt, io};
+
+use super::{
+  client::render_engine::render_init_error::RenderInitError, game_init_error::GameInitError,
+  serial::DeserializeError, server::server_connection::ConnectionError, settings::SettingsError,
+  transport_error::TransportError,
+};
+
+///
+/// Any error in the engine, sorted by which part of it went wrong.
+///
+/// The subsystems keep their own detailed errors, like ConnectionError.
+/// Those all turn into a MinetestError with ?, so code that touches more
+/// than one subsystem doesn't need a match per error type.
+///
+/// Most of the engine still uses Result<_, String>, a MinetestError
+/// turns into one of those with ? too.
+///
+/// main() gets one from Game::try_new and Game::enter_main_loop.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum MinetestError {
+  // Reading or writing files. The kind is kept, so NotFound can be told apart.
+  Io(io::ErrorKind, String),
+  // minetest.conf or the command line.
+  Config(String),
+  Network(String),
+  Render(String),
+  Lua(String),
+  // Textures, models, games and mods that are missing or broken.
+  Asset(String),
+}
+
+impl fmt::Display for MinetestError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      MinetestError::Io(_, reason) => write!(f, "File error. {}", reason),
+      MinetestError::Config(reason) => write!(f, "Config error. {}", reason),
+      MinetestError::Network(reason) => write!(f, "Network error. {}", reason),
+      MinetestError::Render(reason) => write!(f, "Render error. {}", reason),
+      MinetestError::Lua(reason) => write!(f, "Lua error. {}", reason),
+      MinetestError::Asset(reason) => write!(f, "Asset error. {}", reason),
+    }
+  }
+}
+
+impl From<MinetestError> for String {
+  fn from(error: MinetestError) -> Self {
+    error.to_string()
+  }
+}
+
+impl From<io::Error> for MinetestError {
+  fn from(error: io::Error) -> Self {
+    MinetestError::Io(error.kind(), error.to_string())
+  }
+}
+
+impl From<SettingsError> for MinetestError {
+  fn from(error: SettingsError) -> Self {
+    MinetestError::Config(error.to_string())
+  }
+}
+
+impl From<Vec<SettingsError>> for MinetestError {
+  fn from(errors: Vec<SettingsError>) -> Self {
+    let reasons: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+    MinetestError::Config(reasons.join(" "))
+  }
+}
+
+impl From<ConnectionError> for MinetestError {
+  fn from(error: ConnectionError) -> Self {
+    MinetestError::Network(error.to_string())
+  }
+}
+
+impl From<TransportError> for MinetestError {
+  fn from(error: TransportError) -> Self {
+    MinetestError::Network(error.to_string())
+  }
+}
+
+impl From<DeserializeError> for MinetestError {
+  fn from(error: DeserializeError) -> Self {
+    MinetestError::Network(error.to_string())
+  }
+}
+
+impl From<RenderInitError> for MinetestError {
+  fn from(error: RenderInitError) -> Self {
+    MinetestError::Render(error.to_string())
+  }
+}
+
+impl From<mlua::Error> for MinetestError {
+  fn from(error: mlua::Error) -> Self {
+    MinetestError::Lua(error.to_string())
+  }
+}
+
+impl From<image::ImageError> for MinetestError {
+  fn from(error: image::ImageError) -> Self {
+    MinetestError::Asset(error.to_string())
+  }
+}
+
+impl From<GameInitError> for MinetestError {
+  fn from(error: GameInitError) -> Self {
+    let reason = error.to_string();
+    match error {
+      GameInitError::CommandLine(_) | GameInitError::Settings(_) | GameInitError::World(_) => {
+        MinetestError::Config(reason)
+      }
+      GameInitError::Connection(_) => MinetestError::Network(reason),
+      GameInitError::Render(_) => MinetestError::Render(reason),
+      GameInitError::Replay(_) => MinetestError::Io(io::ErrorKind::Other, reason),
+      GameInitError::Startup(_) | GameInitError::Game { .. } => MinetestError::Asset(reason),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr},
+  };
+
+  use mlua::Lua;
+
+  use crate::{
+    file_utilities::file_name_from_path,
+    game::{
+      client::render_engine::render_init_error::RenderInitError, game_init_error::GameInitError,
+      minetest_error::MinetestError, serial::deserialize,
+      server::server_connection::ConnectionError, settings::SettingsError,
+    },
+  };
+
+  #[test]
+  fn test_minetest_error_from_subsystems() {
+    println!("--- BEGIN MINETEST ERROR FROM SUBSYSTEMS TEST ---");
+    let io_error = io::Error::new(io::ErrorKind::NotFound, "gone");
+    assert!(matches!(
+      MinetestError::from(io_error),
+      MinetestError::Io(io::ErrorKind::NotFound, reason) if reason == "gone"
+    ));
+    assert!(matches!(
+      file_name_from_path("./this/does/not/exist.png"),
+      Err(MinetestError::Io(io::ErrorKind::NotFound, _))
+    ));
+
+    let settings_error = SettingsError::Malformed("fov is not a number".to_string());
+    assert!(matches!(
+      MinetestError::from(vec![settings_error]),
+      MinetestError::Config(reason) if reason == "fov is not a number"
+    ));
+    assert!(matches!(
+      MinetestError::from(GameInitError::CommandLine(
+        "--port without --server".to_string()
+      )),
+      MinetestError::Config(_)
+    ));
+
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, 30000));
+    assert!(matches!(
+      MinetestError::from(ConnectionError::PortInUse(address)),
+      MinetestError::Network(reason) if reason.contains("127.0.0.1:30000")
+    ));
+    match deserialize(b"junk") {
+      Ok(_) => panic!("Unit test is broken. Junk deserialized."),
+      Err(e) => assert!(matches!(MinetestError::from(e), MinetestError::Network(_))),
+    }
+
+    assert!(matches!(
+      MinetestError::from(RenderInitError::Device("lost".to_string())),
+      MinetestError::Render(_)
+    ));
+    assert!(matches!(
+      MinetestError::from(GameInitError::Render(RenderInitError::NoAdapter(vec![]))),
+      MinetestError::Render(_)
+    ));
+
+    match Lua::new().load("error('broken mod')").exec() {
+      Ok(_) => panic!("Unit test is broken. error() didn't error."),
+      Err(e) => assert!(matches!(
+        MinetestError::from(e),
+        MinetestError::Lua(reason) if reason.contains("broken mod")
+      )),
+    }
+
+    match image::load_from_memory(b"not an image") {
+      Ok(_) => panic!("Unit test is broken. Garbage decoded."),
+      Err(e) => assert!(matches!(MinetestError::from(e), MinetestError::Asset(_))),
+    }
+    let missing_game = GameInitError::Game {
+      game: "nope".to_string(),
+      path: "./games/nope".to_string(),
+      missing: vec!["./games/nope/".to_string()],
+    };
+    assert!(matches!(
+      MinetestError::from(missing_game),
+      MinetestError::Asset(_)
+    ));
+
+    // The rest of the engine gets it as a String.
+    let as_string: String = MinetestError::Lua("oops".to_string()).into();
+    assert_eq!(as_string, "Lua error. oops");
+  }
+}
@@ -0,0 +1,292 @@
+use std::{
+  any::Any,
+  panic::{catch_unwind, AssertUnwindSafe},
+};
+
+use super::time_step::TimeStep;
+
+///
+/// A native (Rust/FFI) extension that runs next to the Lua mods.
+///
+/// Plugins are compiled in and handed to Game::new through a PluginRegistry.
+/// The Server drives them: on_load once it's up, on_tick every tick after
+/// the LuaEngine, and on_unload when it goes down.
+///
+/// Every hook has a default that does nothing, implement the ones you need.
+///
+pub trait NativePlugin {
+  ///
+  /// Get the name used when logging about this plugin.
+  ///
+  fn get_name(&self) -> &str;
+
+  fn on_load(&mut self) -> Result<(), String> {
+    Ok(())
+  }
+
+  fn on_tick(&mut self, delta: TimeStep) -> Result<(), String> {
+    Ok(())
+  }
+
+  fn on_unload(&mut self) -> Result<(), String> {
+    Ok(())
+  }
+}
+
+///
+/// How a plugin hook went wrong.
+///
+enum HookFailure {
+  // The hook returned an error, the plugin is still fine.
+  Error,
+  // The hook panicked, the plugin can't be trusted anymore.
+  Panic,
+}
+
+///
+/// Holds the NativePlugins and runs their hooks.
+///
+/// Build one up before starting the Game:
+/// PluginRegistry::new().with(MyPlugin::new())
+///
+/// A failing hook is logged against it's plugin and never reaches the
+/// rest of the server. Plugins that fail on_load, or panic in any hook,
+/// are dropped.
+///
+#[derive(Default)]
+pub struct PluginRegistry {
+  // Registered but not loaded yet.
+  pending: Vec<Box<dyn NativePlugin>>,
+  loaded: Vec<Box<dyn NativePlugin>>,
+}
+
+impl PluginRegistry {
+  pub fn new() -> Self {
+    PluginRegistry {
+      pending: vec![],
+      loaded: vec![],
+    }
+  }
+
+  ///
+  /// Register a plugin. It loads when the Server does.
+  ///
+  pub fn with(mut self, plugin: impl NativePlugin + 'static) -> Self {
+    self.pending.push(Box::new(plugin));
+    self
+  }
+
+  ///
+  /// Get how many plugins are registered, loaded or not.
+  ///
+  pub fn len(&self) -> usize {
+    self.pending.len() + self.loaded.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  ///
+  /// Get the names of the loaded plugins, in the order they loaded.
+  ///
+  pub fn get_loaded_names(&self) -> Vec<String> {
+    self
+      .loaded
+      .iter()
+      .map(|plugin| plugin.get_name().to_string())
+      .collect()
+  }
+
+  ///
+  /// Get a readable message out of a panic.
+  ///
+  fn get_panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+      Some(message) => message.to_string(),
+      None => match payload.downcast_ref::<String>() {
+        Some(message) => message.clone(),
+        None => "unknown panic".to_string(),
+      },
+    }
+  }
+
+  ///
+  /// Run one hook of one plugin, logging whatever goes wrong.
+  ///
+  fn run_hook(
+    plugin: &mut dyn NativePlugin,
+    hook_name: &str,
+    hook: impl FnOnce(&mut dyn NativePlugin) -> Result<(), String>,
+  ) -> Result<(), HookFailure> {
+    match catch_unwind(AssertUnwindSafe(|| hook(&mut *plugin))) {
+      Ok(Ok(())) => Ok(()),
+      Ok(Err(e)) => {
+        println!(
+          "PluginRegistry: [{}] {} failed. {}",
+          plugin.get_name(),
+          hook_name,
+          e
+        );
+        Err(HookFailure::Error)
+      }
+      Err(payload) => {
+        println!(
+          "PluginRegistry: [{}] panicked in {}, dropping it. {}",
+          plugin.get_name(),
+          hook_name,
+          Self::get_panic_message(payload.as_ref())
+        );
+        Err(HookFailure::Panic)
+      }
+    }
+  }
+
+  ///
+  /// Load every registered plugin that isn't loaded yet.
+  ///
+  pub fn load(&mut self) {
+    for mut plugin in std::mem::take(&mut self.pending) {
+      match Self::run_hook(plugin.as_mut(), "on_load", |plugin| plugin.on_load()) {
+        Ok(()) => {
+          println!("PluginRegistry: loaded [{}].", plugin.get_name());
+          self.loaded.push(plugin);
+        }
+        Err(_) => println!("PluginRegistry: [{}] was not loaded.", plugin.get_name()),
+      }
+    }
+  }
+
+  ///
+  /// Tick every loaded plugin.
+  ///
+  pub fn on_tick(&mut self, delta: TimeStep) {
+    self.loaded.retain_mut(|plugin| {
+      !matches!(
+        Self::run_hook(plugin.as_mut(), "on_tick", |plugin| plugin.on_tick(delta)),
+        Err(HookFailure::Panic)
+      )
+    });
+  }
+
+  ///
+  /// Unload every loaded plugin, last loaded first.
+  ///
+  pub fn unload(&mut self) {
+    while let Some(mut plugin) = self.loaded.pop() {
+      // It's going away either way.
+      let _ = Self::run_hook(plugin.as_mut(), "on_unload", |plugin| plugin.on_unload());
+      println!("PluginRegistry: unloaded [{}].", plugin.get_name());
+    }
+  }
+}
+
+impl Drop for PluginRegistry {
+  fn drop(&mut self) {
+    self.unload();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, rc::Rc};
+
+  use crate::game::{
+    native_plugin::{NativePlugin, PluginRegistry},
+    time_step::TimeStep,
+  };
+
+  ///
+  /// Writes down every hook call into a shared log.
+  ///
+  struct DummyPlugin {
+    name: String,
+    log: Rc<RefCell<Vec<String>>>,
+    fail_load: bool,
+    panic_on_tick: bool,
+  }
+
+  impl DummyPlugin {
+    fn new(name: &str, log: &Rc<RefCell<Vec<String>>>) -> Self {
+      DummyPlugin {
+        name: name.to_string(),
+        log: log.clone(),
+        fail_load: false,
+        panic_on_tick: false,
+      }
+    }
+
+    fn record(&self, hook: &str) {
+      self
+        .log
+        .borrow_mut()
+        .push(format!("{} {}", self.name, hook));
+    }
+  }
+
+  impl NativePlugin for DummyPlugin {
+    fn get_name(&self) -> &str {
+      &self.name
+    }
+
+    fn on_load(&mut self) -> Result<(), String> {
+      self.record("on_load");
+      match self.fail_load {
+        true => Err("Refusing to load.".to_string()),
+        false => Ok(()),
+      }
+    }
+
+    fn on_tick(&mut self, delta: TimeStep) -> Result<(), String> {
+      self.record(&format!("on_tick {}", delta.as_secs_f64()));
+      if self.panic_on_tick {
+        panic!("on_tick went wrong");
+      }
+      Ok(())
+    }
+
+    fn on_unload(&mut self) -> Result<(), String> {
+      self.record("on_unload");
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_plugin_registry_hook_order() {
+    println!("--- BEGIN PLUGIN REGISTRY HOOK ORDER TEST ---");
+    let log = Rc::new(RefCell::new(vec![]));
+
+    let mut broken = DummyPlugin::new("broken", &log);
+    broken.fail_load = true;
+    let mut panicky = DummyPlugin::new("panicky", &log);
+    panicky.panic_on_tick = true;
+
+    let mut plugins = PluginRegistry::new()
+      .with(DummyPlugin::new("dummy", &log))
+      .with(broken)
+      .with(panicky);
+    assert_eq!(plugins.len(), 3);
+
+    plugins.load();
+    assert_eq!(plugins.get_loaded_names(), vec!["dummy", "panicky"]);
+
+    // The panic stays with panicky, dummy keeps ticking.
+    plugins.on_tick(TimeStep::from_secs_f64(0.05));
+    assert_eq!(plugins.get_loaded_names(), vec!["dummy"]);
+    plugins.on_tick(TimeStep::from_secs_f64(0.1));
+    drop(plugins);
+
+    assert_eq!(
+      *log.borrow(),
+      vec![
+        "dummy on_load",
+        "broken on_load",
+        "panicky on_load",
+        "dummy on_tick 0.05",
+        "panicky on_tick 0.05",
+        "dummy on_tick 0.1",
+        "dummy on_unload",
+      ]
+    );
+  }
+}
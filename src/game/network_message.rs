@@ -0,0 +1,203 @@
+use glam::Vec3A;
+use serde::{Deserialize, Serialize};
+
+use super::serial::base64_bytes;
+
+///
+/// The most media bytes one Media message carries. [bytes]
+///
+/// Bigger media is split up and the client asks for each chunk in turn.
+/// It goes over as base64, so a full chunk is a third bigger on the wire
+/// and still fits under DEFAULT_MAX_MESSAGE_BYTES.
+///
+pub const MEDIA_CHUNK_BYTES: usize = 16 * 1024;
+
+///
+/// Everything the Client and the Server can say to each other.
+///
+/// These go over the wire through the serial component.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum NetworkMessage {
+  Hi,
+  HiThere,
+
+  // The password is empty if the player doesn't have one.
+  HandShake {
+    name: String,
+    password: String,
+  },
+  HandShakeConfirmed,
+  HandShakeRejected {
+    reason: String,
+  },
+
+  // The server is closing the session. (kicked, banned, shutting down)
+  Disconnect {
+    reason: String,
+  },
+
+  // The ID comes back in the confirmation, so the sender can time the round trip.
+  PingRequest {
+    id: u32,
+  },
+  PingConfirmation {
+    id: u32,
+  },
+
+  ShutDownRequest,
+
+  // A line of chat, already formatted for display.
+  ChatMessage(String),
+
+  // Where a player is, sent every tick. Only the newest one matters.
+  PlayerMove {
+    position: Vec3A,
+    rotation: Vec3A,
+  },
+  // The server put the player somewhere, like at spawn or back inside the border.
+  // acknowledged is the sequence of the newest PlayerMove it took before that,
+  // 0 for none. The client replays the moves it sent after, see MovePrediction.
+  MovePlayer {
+    position: Vec3A,
+    acknowledged: u32,
+  },
+  // From a client, asking to start or stop spectating.
+  // From the server, the mode they're actually in now.
+  Spectate {
+    enabled: bool,
+  },
+
+  // The server's clock, sent every few seconds. The client runs it's own in between.
+  TimeOfDay {
+    time_of_day: f64,
+    time_speed: f64,
+  },
+
+  // * Server browser queries.
+  // * These work without a handshake and never create a session.
+  StatusRequest,
+  StatusResponse {
+    name: String,
+    description: String,
+    motd: String,
+    players: u32,
+    max_players: u32,
+    version: String,
+  },
+
+  // * Media the client doesn't have cached, asked for by it's SHA1 hash.
+  // * It comes in MEDIA_CHUNK_BYTES chunks, counting from 0. Media that fits in
+  // * one is just chunk 0 of 1.
+  // * Only players with a session get an answer.
+  MediaRequest {
+    hash: String,
+    chunk: u32,
+  },
+  Media {
+    hash: String,
+    chunk: u32,
+    chunk_count: u32,
+    #[serde(with = "base64_bytes")]
+    data: Vec<u8>,
+  },
+
+  // A sound for the client to play, by name. Gain and pitch are multipliers.
+  PlaySound {
+    name: String,
+    gain: f32,
+    pitch: f32,
+  },
+
+  // Something the client should start drawing, like another player.
+  AddEntity {
+    id: u64,
+    kind: String,
+    position: Vec3A,
+  },
+  // The entity is gone, stop drawing it.
+  RemoveEntity {
+    id: u64,
+  },
+  // Where every entity is, sent every broadcast. (id, position)
+  // One message for all of them, Latest would drop one entity's update for another's.
+  EntityPositions {
+    positions: Vec<(u64, Vec3A)>,
+  },
+}
+
+///
+/// How a NetworkMessage is handed over when UDP mixes packets up.
+///
+/// Duplicates are always dropped, whatever the Delivery.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+  // Handled the moment it arrives.
+  Unordered,
+  // Held back a moment if an earlier packet is missing, so it comes out in order.
+  Ordered,
+  // Anything older than the newest of the same kind is dropped.
+  Latest,
+}
+
+///
+/// What a backed up send queue drops first, see SendQueue.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+  // Gets superseded or resent anyway, losing one is fine.
+  Low,
+  // Has to get there.
+  High,
+}
+
+impl NetworkMessage {
+  ///
+  /// Get how important it is that this kind of message gets sent.
+  ///
+  pub fn get_priority(&self) -> Priority {
+    match self {
+      NetworkMessage::PlayerMove { .. }
+      | NetworkMessage::TimeOfDay { .. }
+      | NetworkMessage::PingRequest { .. }
+      | NetworkMessage::PingConfirmation { .. }
+      | NetworkMessage::PlaySound { .. }
+      | NetworkMessage::EntityPositions { .. } => Priority::Low,
+      _ => Priority::High,
+    }
+  }
+
+  ///
+  /// Get how this kind of message has to be delivered.
+  ///
+  pub fn get_delivery(&self) -> Delivery {
+    match self {
+      // Chat that reads out of order doesn't make sense.
+      NetworkMessage::ChatMessage(_) => Delivery::Ordered,
+      // A removal overtaking it's spawn would leave a ghost behind.
+      NetworkMessage::AddEntity { .. } | NetworkMessage::RemoveEntity { .. } => Delivery::Ordered,
+      // Toggling twice has to end up where it started.
+      NetworkMessage::Spectate { .. } => Delivery::Ordered,
+      // An old position would snap the player back.
+      NetworkMessage::PlayerMove { .. }
+      | NetworkMessage::MovePlayer { .. }
+      | NetworkMessage::TimeOfDay { .. }
+      | NetworkMessage::EntityPositions { .. } => Delivery::Latest,
+      _ => Delivery::Unordered,
+    }
+  }
+}
+
+///
+/// What actually goes over the wire, a NetworkMessage and it's sequence number.
+///
+/// Each side numbers the packets it sends a session, starting at 1. Gaps
+/// in the sequence are counted as packet loss, see ConnectionTracker.
+/// Packets sent without a session (status, rejections) are sequence 0.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Packet {
+  pub sequence: u32,
+  pub message: NetworkMessage,
+}
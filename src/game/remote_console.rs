@@ -0,0 +1,515 @@
+use std::{
+  io::{BufRead, BufReader, Read, Write},
+  net::{SocketAddr, TcpListener, TcpStream},
+  sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    mpsc::{channel, Sender},
+    Arc, Mutex,
+  },
+  thread,
+  time::Duration,
+};
+
+use super::{
+  game_command::{CommandReply, GameCommand},
+  game_config::GameConfig,
+  server::rate_limiter::RateLimiter,
+};
+
+///
+/// How many failed logins an address gets per window.
+///
+const FAILED_LOGINS_PER_WINDOW: u32 = 3;
+
+///
+/// The failed login rate limit window.
+///
+const FAILED_LOGIN_WINDOW: Duration = Duration::from_secs(60);
+
+///
+/// How long a client gets to send the password.
+///
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+///
+/// The longest password line read before a client has logged in. [bytes]
+///
+const MAX_PASSWORD_LINE: u64 = 1024;
+
+///
+/// How many clients can be connected at once, logged in or not.
+///
+const MAX_CLIENTS: usize = 8;
+
+///
+/// How long to wait for the Game to run a command.
+///
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+///
+/// A remote admin console over TCP. (RCON)
+///
+/// This takes the same commands as the ServerConsole, so tools can
+/// manage a running server. It's off unless minetest.conf has both
+/// remote_console_port and remote_console_password.
+///
+/// The protocol is plain lines:
+/// 1.) The client sends the password. The server answers OK or DENIED.
+///     Past MAX_CLIENTS connections it's DENIED before that.
+/// 2.) The client sends a command. The server answers with the output,
+///     then an empty line.
+///
+/// ! The password goes over the wire in plain text. Keep this bound to
+/// ! localhost or tunnel it. (remote_console_address)
+///
+pub struct RemoteConsole {
+  address: SocketAddr,
+  running: Arc<AtomicBool>,
+}
+
+impl RemoteConsole {
+  ///
+  /// Start the remote console, if minetest.conf turns it on.
+  ///
+  pub fn from_config(config: &GameConfig, command_sender: Sender<GameCommand>) -> Option<Self> {
+    if !config.has("remote_console_port") {
+      return None;
+    }
+
+    let password = config.get_string("remote_console_password", "");
+    if password.is_empty() {
+      println!("RemoteConsole: remote_console_password is not set. Not starting.");
+      return None;
+    }
+
+    let address = config.get_string("remote_console_address", "127.0.0.1");
+    let port = config.get_parsed::<u16>("remote_console_port", 0);
+
+    match Self::new(&address, port, password, command_sender) {
+      Ok(remote_console) => Some(remote_console),
+      Err(e) => {
+        println!("RemoteConsole: {}", e);
+        None
+      }
+    }
+  }
+
+  pub fn new(
+    address: &str,
+    port: u16,
+    password: String,
+    command_sender: Sender<GameCommand>,
+  ) -> Result<Self, String> {
+    let listener = match TcpListener::bind((address, port)) {
+      Ok(listener) => listener,
+      Err(e) => return Err(format!("Failed to bind [{}:{}]. {}", address, port, e)),
+    };
+    let real_address = match listener.local_addr() {
+      Ok(real_address) => real_address,
+      Err(e) => return Err(format!("Failed to get the bound address. {}", e)),
+    };
+
+    let running = Arc::new(AtomicBool::new(true));
+    let thread_running = running.clone();
+
+    // ? Like the ServerConsole, this thread can't be woken up while it's
+    // ? blocked on accept. It finishes on the next connection after the
+    // ? console is dropped, or when the process exits.
+    let spawn_result = thread::Builder::new()
+      .name("remote_console".to_string())
+      .spawn(move || Self::accept_clients(listener, password, command_sender, thread_running));
+
+    if let Err(e) = spawn_result {
+      return Err(format!("Failed to start remote console thread. {}", e));
+    }
+
+    println!("RemoteConsole: listening on [{}].", real_address);
+
+    Ok(RemoteConsole {
+      address: real_address,
+      running,
+    })
+  }
+
+  ///
+  /// Get the address the remote console actually bound to.
+  ///
+  pub fn get_address(&self) -> SocketAddr {
+    self.address
+  }
+
+  ///
+  /// Hand every client their own thread until the console is stopped.
+  ///
+  fn accept_clients(
+    listener: TcpListener,
+    password: String,
+    command_sender: Sender<GameCommand>,
+    running: Arc<AtomicBool>,
+  ) {
+    let password = Arc::new(password);
+    let failed_logins = Arc::new(Mutex::new(RateLimiter::new(
+      FAILED_LOGINS_PER_WINDOW,
+      FAILED_LOGIN_WINDOW,
+    )));
+    // Counted up here and down when a client thread finishes.
+    let client_count = Arc::new(AtomicUsize::new(0));
+
+    for stream_result in listener.incoming() {
+      if !running.load(Ordering::Relaxed) {
+        break;
+      }
+
+      let mut stream = match stream_result {
+        Ok(stream) => stream,
+        Err(e) => {
+          println!("RemoteConsole: Failed to accept client. {}", e);
+          continue;
+        }
+      };
+
+      // Every client is a thread, don't let connections pile them up.
+      if client_count.load(Ordering::Relaxed) >= MAX_CLIENTS {
+        println!("RemoteConsole: too many clients, turning one away.");
+        let _ = stream.write_all(b"DENIED too many connections\n");
+        continue;
+      }
+      client_count.fetch_add(1, Ordering::Relaxed);
+
+      let password = password.clone();
+      let failed_logins = failed_logins.clone();
+      let command_sender = command_sender.clone();
+      let thread_client_count = client_count.clone();
+      let spawn_result = thread::Builder::new()
+        .name("remote_console_client".to_string())
+        .spawn(move || {
+          if let Err(e) = Self::serve_client(stream, &password, &failed_logins, command_sender) {
+            println!("RemoteConsole: {}", e);
+          }
+          thread_client_count.fetch_sub(1, Ordering::Relaxed);
+        });
+      if let Err(e) = spawn_result {
+        println!("RemoteConsole: Failed to start client thread. {}", e);
+        client_count.fetch_sub(1, Ordering::Relaxed);
+      }
+    }
+  }
+
+  ///
+  /// Compare passwords without bailing out on the first wrong byte.
+  ///
+  /// This keeps the response time from leaking how much was right.
+  ///
+  fn password_matches(attempt: &str, password: &str) -> bool {
+    let attempt = attempt.as_bytes();
+    let password = password.as_bytes();
+    let mut difference = attempt.len() ^ password.len();
+    for (index, byte) in password.iter().enumerate() {
+      difference |= (byte ^ attempt.get(index).copied().unwrap_or(0)) as usize;
+    }
+    difference == 0
+  }
+
+  ///
+  /// Log a client in, then run their commands until they hang up.
+  ///
+  fn serve_client(
+    stream: TcpStream,
+    password: &str,
+    failed_logins: &Mutex<RateLimiter>,
+    command_sender: Sender<GameCommand>,
+  ) -> Result<(), String> {
+    let peer = match stream.peer_addr() {
+      Ok(peer) => peer,
+      Err(e) => return Err(format!("Failed to get client address. {}", e)),
+    };
+    let mut writer = match stream.try_clone() {
+      Ok(writer) => writer,
+      Err(e) => return Err(format!("Failed to clone stream for [{}]. {}", peer, e)),
+    };
+    let mut reader = BufReader::new(stream);
+    let mut send = |text: &str| -> Result<(), String> {
+      match writer.write_all(text.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Failed to write to [{}]. {}", peer, e)),
+      }
+    };
+
+    // Don't let a connection hang around forever without logging in,
+    // or send an endless password.
+    let _ = reader.get_ref().set_read_timeout(Some(LOGIN_TIMEOUT));
+    let mut attempt = String::new();
+    if let Err(e) = reader
+      .by_ref()
+      .take(MAX_PASSWORD_LINE)
+      .read_line(&mut attempt)
+    {
+      return Err(format!("[{}] never sent a password. {}", peer, e));
+    }
+
+    // Every attempt counts before the password is checked, so parallel
+    // connections can't all get a guess in before the first one fails.
+    // Locked out addresses don't even get the password checked.
+    let allowed = match failed_logins.lock() {
+      Ok(mut failed_logins) => failed_logins.check(peer.ip()),
+      Err(e) => return Err(format!("Failed login limiter is poisoned. {}", e)),
+    };
+    if !allowed {
+      println!("RemoteConsole: rate limiting logins from [{}].", peer);
+      return send("DENIED too many failed logins\n");
+    }
+
+    if !Self::password_matches(attempt.trim_end_matches(['\r', '\n']), password) {
+      println!("RemoteConsole: failed login from [{}].", peer);
+      return send("DENIED\n");
+    }
+
+    // Only failed logins use up the budget.
+    if let Ok(mut failed_logins) = failed_logins.lock() {
+      failed_logins.refund(peer.ip());
+    }
+
+    println!("RemoteConsole: [{}] logged in.", peer);
+    let _ = reader.get_ref().set_read_timeout(None);
+    send("OK\n")?;
+
+    for line_result in reader.lines() {
+      let line = match line_result {
+        Ok(line) => line,
+        Err(e) => return Err(format!("Failed to read from [{}]. {}", peer, e)),
+      };
+      if line.trim().is_empty() {
+        continue;
+      }
+
+      println!("RemoteConsole: [{}] sent [{}].", peer, line.trim());
+
+      // The Game runs it on the main thread and sends the output back.
+      let (reply_sender, reply_receiver) = channel();
+      if command_sender
+        .send(GameCommand::RemoteCommand(line, CommandReply(reply_sender)))
+        .is_err()
+      {
+        return send("The server is shutting down.\n\n");
+      }
+
+      let reply = match reply_receiver.recv_timeout(REPLY_TIMEOUT) {
+        Ok(reply) => reply,
+        Err(_) => "The server did not answer in time.".to_string(),
+      };
+      send(&format!("{}\n\n", reply))?;
+    }
+
+    println!("RemoteConsole: [{}] disconnected.", peer);
+    Ok(())
+  }
+}
+
+impl Drop for RemoteConsole {
+  fn drop(&mut self) {
+    self.running.store(false, Ordering::Relaxed);
+    println!("RemoteConsole dropped!");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs::remove_dir_all,
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpStream},
+    sync::mpsc::channel,
+    thread,
+    time::{Duration, Instant},
+  };
+
+  use crate::{
+    file_utilities::test_dir,
+    game::{
+      game_command::GameCommand,
+      game_config::GameConfig,
+      remote_console::{RemoteConsole, FAILED_LOGINS_PER_WINDOW, MAX_CLIENTS, MAX_PASSWORD_LINE},
+      server::{Issuer, Server},
+    },
+  };
+
+  ///
+  /// Log in, run some commands, and get back every line the server sent.
+  ///
+  fn run_client(address: SocketAddr, password: &str, commands: &[&str]) -> Vec<String> {
+    let mut stream = match TcpStream::connect(address) {
+      Ok(stream) => stream,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+
+    let mut input = format!("{}\n", password);
+    for command in commands {
+      input.push_str(command);
+      input.push('\n');
+    }
+    if let Err(e) = stream.write_all(input.as_bytes()) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    // Stop after every command got its empty line back.
+    let mut lines = vec![];
+    let mut answered = 0;
+    for line in BufReader::new(stream).lines() {
+      let line = match line {
+        Ok(line) => line,
+        Err(_) => break,
+      };
+      if line.is_empty() {
+        answered += 1;
+      }
+      let finished = line.starts_with("DENIED") || answered == commands.len();
+      lines.push(line);
+      if finished {
+        break;
+      }
+    }
+    lines
+  }
+
+  #[test]
+  fn test_remote_console_authentication() {
+    println!("--- BEGIN REMOTE CONSOLE AUTHENTICATION TEST ---");
+
+    // Off by default.
+    let (command_sender, command_receiver) = channel();
+    assert!(RemoteConsole::from_config(&GameConfig::new(), command_sender.clone()).is_none());
+    assert!(RemoteConsole::from_config(
+      &GameConfig::parse("remote_console_port = 0\n"),
+      command_sender.clone()
+    )
+    .is_none());
+
+    let world_path = test_dir("remote_console_authentication");
+    let config = GameConfig::parse(
+      "server_name = Remote\nremote_console_port = 0\nremote_console_password = hunter2\n",
+    );
+    let remote_console = match RemoteConsole::from_config(&config, command_sender) {
+      Some(remote_console) => remote_console,
+      None => panic!("Unit test is broken. Remote console did not start."),
+    };
+    let address = remote_console.get_address();
+
+    let mut server = Server::new(
+      "127.0.0.1".to_string(),
+      0,
+      "minetest".to_string(),
+      &world_path,
+      &config,
+      20.0,
+    );
+
+    let clients = thread::spawn(move || {
+      let rejected = run_client(address, "wrong", &[]);
+      let accepted = run_client(address, "hunter2", &["status"]);
+
+      // Keep failing until the address gets locked out.
+      for _ in 1..FAILED_LOGINS_PER_WINDOW {
+        run_client(address, "wrong", &[]);
+      }
+      let locked_out = run_client(address, "hunter2", &[]);
+
+      (rejected, accepted, locked_out)
+    });
+
+    // Be the Game's main loop.
+    let mut commands_run = 0;
+    while !clients.is_finished() {
+      if let Ok(GameCommand::RemoteCommand(line, reply)) =
+        command_receiver.recv_timeout(Duration::from_millis(10))
+      {
+        commands_run += 1;
+        let _ = reply
+          .0
+          .send(server.run_chat_command(&Issuer::Console, &line));
+      }
+    }
+
+    let (rejected, accepted, locked_out) = match clients.join() {
+      Ok(results) => results,
+      Err(_) => panic!("Unit test is broken. Client thread panicked."),
+    };
+
+    assert_eq!(rejected, vec!["DENIED".to_string()]);
+    assert_eq!(accepted[0], "OK");
+    assert!(accepted[1].contains("Server [Remote]"));
+    assert!(locked_out[0].starts_with("DENIED"));
+
+    assert_eq!(commands_run, 1);
+
+    drop(server);
+    let _ = remove_dir_all(&world_path);
+  }
+
+  ///
+  /// Connect without sending anything, and get the first line the server sends.
+  ///
+  fn read_first_line(stream: &TcpStream) -> String {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+    let mut line = String::new();
+    if let Err(e) = BufReader::new(stream).read_line(&mut line) {
+      panic!("Unit test is broken. {}", e);
+    }
+    line.trim_end().to_string()
+  }
+
+  #[test]
+  fn test_remote_console_limits() {
+    println!("--- BEGIN REMOTE CONSOLE LIMITS TEST ---");
+    let (command_sender, _command_receiver) = channel();
+    let start = || match RemoteConsole::new(
+      "127.0.0.1",
+      0,
+      "hunter2".to_string(),
+      command_sender.clone(),
+    ) {
+      Ok(remote_console) => remote_console,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let connect = |address: SocketAddr| match TcpStream::connect(address) {
+      Ok(stream) => stream,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+
+    // An endless password is cut off, not read until the timeout.
+    let remote_console = start();
+    let address = remote_console.get_address();
+    let mut endless = connect(address);
+    let started = Instant::now();
+    if let Err(e) = endless.write_all(&vec![b'a'; MAX_PASSWORD_LINE as usize]) {
+      panic!("Unit test is broken. {}", e);
+    }
+    assert_eq!(read_first_line(&endless), "DENIED");
+    assert!(started.elapsed() < Duration::from_secs(5));
+    drop(endless);
+
+    // Guessing in parallel still only gets the budget's worth of guesses.
+    // The endless one was the first.
+    let guessers: Vec<_> = (0..FAILED_LOGINS_PER_WINDOW * 2)
+      .map(|_| thread::spawn(move || run_client(address, "wrong", &[])))
+      .collect();
+    let mut guesses_checked = 1;
+    for guesser in guessers {
+      match guesser.join() {
+        Ok(lines) if lines == vec!["DENIED".to_string()] => guesses_checked += 1,
+        Ok(lines) => assert_eq!(lines[0], "DENIED too many failed logins"),
+        Err(_) => panic!("Unit test is broken. Client thread panicked."),
+      }
+    }
+    assert_eq!(guesses_checked, FAILED_LOGINS_PER_WINDOW);
+
+    // Connections past the cap are turned away without a thread. A fresh
+    // console, so none of the clients above are still counted.
+    let remote_console = start();
+    let address = remote_console.get_address();
+    let waiting: Vec<TcpStream> = (0..MAX_CLIENTS).map(|_| connect(address)).collect();
+    let turned_away = connect(address);
+    assert_eq!(read_first_line(&turned_away), "DENIED too many connections");
+    drop(waiting);
+  }
+}
@@ -0,0 +1,465 @@
+use std::{
+  cell::RefCell,
+  fs::File,
+  io::{BufRead, BufWriter, Write},
+  net::{SocketAddr, UdpSocket},
+  rc::Rc,
+  time::{Duration, Instant},
+};
+
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::file_utilities::read_path_to_buf_read;
+
+use super::{
+  network_message::{NetworkMessage, Packet},
+  serial::serialize,
+};
+
+///
+/// The version of the recording format.
+///
+/// Bump this whenever ReplayHeader, ReplayEntry or ReplayEvent change shape.
+/// NetworkMessage changing shape breaks old recordings too.
+///
+pub const REPLAY_FORMAT_VERSION: u32 = 2;
+
+///
+/// How many packets a fast replay sends per tick.
+///
+/// Everything at once would overrun the server's UDP receive buffer.
+///
+const FAST_PACKETS_PER_TICK: usize = 64;
+
+///
+/// How long a replay keeps the server running after the last packet.
+///
+/// The last packets still have to arrive and get handled.
+///
+pub const REPLAY_SETTLE_TIME: Duration = Duration::from_millis(500);
+
+///
+/// The first line of a recording.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct ReplayHeader {
+  version: u32,
+}
+
+///
+/// Something that happened during a recording.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ReplayEvent {
+  // A packet as it came off the wire, duplicates and all.
+  // Peers are numbered in the order they first showed up.
+  Network { peer: u32, packet: Packet },
+}
+
+///
+/// One line of a recording, after the header.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplayEntry {
+  // Since the recording started. [seconds]
+  pub at: f64,
+  pub event: ReplayEvent,
+}
+
+///
+/// The open recording file, shared by everything that records into it.
+///
+struct RecordingFile {
+  writer: BufWriter<File>,
+  start: Instant,
+  peers: AHashMap<SocketAddr, u32>,
+}
+
+///
+/// Writes incoming network packets into a recording.
+///
+/// A recording is JSON lines, a ReplayHeader and then one ReplayEntry per
+/// event. Clones write into the same file.
+///
+/// Passwords in handshakes are blanked out, a recording is meant to be
+/// passed around. Replaying logins against a server with auth gets them rejected.
+///
+/// A failed write is logged and the recording stops, the game keeps going.
+///
+#[derive(Clone)]
+pub struct Recorder {
+  file: Rc<RefCell<Option<RecordingFile>>>,
+}
+
+impl Recorder {
+  ///
+  /// Start a new recording, overwriting whatever was at the path.
+  ///
+  pub fn create(path: &str) -> Result<Self, String> {
+    let file = match File::create(path) {
+      Ok(file) => file,
+      Err(e) => return Err(format!("Failed to create recording [{}]. {}", path, e)),
+    };
+
+    let mut recording = RecordingFile {
+      writer: BufWriter::new(file),
+      start: Instant::now(),
+      peers: AHashMap::new(),
+    };
+    let header = ReplayHeader {
+      version: REPLAY_FORMAT_VERSION,
+    };
+    if let Err(e) = Self::write_line(&mut recording.writer, &header) {
+      return Err(format!("Failed to write recording [{}]. {}", path, e));
+    }
+
+    println!("Recorder: recording into [{}].", path);
+
+    Ok(Recorder {
+      file: Rc::new(RefCell::new(Some(recording))),
+    })
+  }
+
+  fn write_line(writer: &mut BufWriter<File>, line: &impl Serialize) -> Result<(), String> {
+    let mut data = match serde_json::to_vec(line) {
+      Ok(data) => data,
+      Err(e) => return Err(e.to_string()),
+    };
+    data.push(b'\n');
+    match writer.write_all(&data) {
+      Ok(_) => Ok(()),
+      Err(e) => Err(e.to_string()),
+    }
+  }
+
+  ///
+  /// Write an event down, stamped with how far into the recording it is.
+  ///
+  fn record(&self, now: Instant, event: impl FnOnce(&mut RecordingFile) -> ReplayEvent) {
+    let mut file = self.file.borrow_mut();
+    let recording = match file.as_mut() {
+      Some(recording) => recording,
+      None => return,
+    };
+
+    let entry = ReplayEntry {
+      at: now.saturating_duration_since(recording.start).as_secs_f64(),
+      event: event(recording),
+    };
+    if let Err(e) = Self::write_line(&mut recording.writer, &entry) {
+      println!("Recorder: Failed to write, stopping the recording. {}", e);
+      *file = None;
+    }
+  }
+
+  ///
+  /// Record a packet that came in from an address.
+  ///
+  pub fn record_packet(&self, from: SocketAddr, packet: &Packet, now: Instant) {
+    self.record(now, |recording| {
+      let next_peer = recording.peers.len() as u32;
+      let peer = *recording.peers.entry(from).or_insert(next_peer);
+      let mut packet = packet.clone();
+      if let NetworkMessage::HandShake { password, .. } = &mut packet.message {
+        password.clear();
+      }
+      ReplayEvent::Network { peer, packet }
+    });
+  }
+
+  ///
+  /// Push everything written so far out to the file.
+  ///
+  pub fn flush(&self) {
+    if let Some(recording) = self.file.borrow_mut().as_mut() {
+      if let Err(e) = recording.writer.flush() {
+        println!("Recorder: Failed to flush. {}", e);
+      }
+    }
+  }
+}
+
+///
+/// A recording, loaded back in.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+  entries: Vec<ReplayEntry>,
+}
+
+impl Replay {
+  ///
+  /// Load a recording. Fails if it's from another version of the format.
+  ///
+  pub fn load(path: &str) -> Result<Self, String> {
+    let reader = read_path_to_buf_read(path)?;
+    let mut lines = reader.lines();
+
+    let header = match lines.next() {
+      Some(Ok(line)) => match serde_json::from_str::<ReplayHeader>(&line) {
+        Ok(header) => header,
+        Err(e) => return Err(format!("[{}] is not a recording. {}", path, e)),
+      },
+      Some(Err(e)) => return Err(format!("Failed to read [{}]. {}", path, e)),
+      None => return Err(format!("[{}] is empty.", path)),
+    };
+    if header.version != REPLAY_FORMAT_VERSION {
+      return Err(format!(
+        "[{}] is recording format version [{}], this build reads version [{}].",
+        path, header.version, REPLAY_FORMAT_VERSION
+      ));
+    }
+
+    let mut entries = vec![];
+    for (index, line_result) in lines.enumerate() {
+      let line = match line_result {
+        Ok(line) => line,
+        Err(e) => return Err(format!("Failed to read [{}]. {}", path, e)),
+      };
+      // A recording cut off by a crash ends in a half written line.
+      match serde_json::from_str::<ReplayEntry>(&line) {
+        Ok(entry) => entries.push(entry),
+        Err(e) => {
+          println!(
+            "Replay: [{}] line [{}] is broken, stopping there. {}",
+            path,
+            index + 2,
+            e
+          );
+          break;
+        }
+      }
+    }
+
+    Ok(Replay { entries })
+  }
+
+  pub fn get_entries(&self) -> &[ReplayEntry] {
+    &self.entries
+  }
+}
+
+///
+/// How fast a Replayer plays a recording back.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplaySpeed {
+  // Every event goes out when it originally happened.
+  Original,
+  // As fast as the server keeps up, FAST_PACKETS_PER_TICK at a time.
+  Fast,
+}
+
+///
+/// Feeds a recording's network packets into a server over loopback.
+///
+/// Every recorded peer gets it's own socket, so the server sees the same
+/// sessions, sequence numbers and duplicates it did the first time.
+///
+pub struct Replayer {
+  entries: Vec<ReplayEntry>,
+  next_entry: usize,
+  speed: ReplaySpeed,
+  server_address: SocketAddr,
+  sockets: AHashMap<u32, UdpSocket>,
+  start: Option<Instant>,
+  finished_at: Option<Instant>,
+}
+
+impl Replayer {
+  pub fn new(replay: Replay, server_address: SocketAddr, speed: ReplaySpeed) -> Self {
+    Replayer {
+      entries: replay.entries,
+      next_entry: 0,
+      speed,
+      server_address,
+      sockets: AHashMap::new(),
+      start: None,
+      finished_at: None,
+    }
+  }
+
+  ///
+  /// Get the socket a peer sends from, opening it the first time.
+  ///
+  fn get_socket(&mut self, peer: u32) -> Result<&UdpSocket, String> {
+    if !self.sockets.contains_key(&peer) {
+      let bind_address = match self.server_address {
+        SocketAddr::V4(_) => "127.0.0.1:0",
+        SocketAddr::V6(_) => "[::1]:0",
+      };
+      let socket = match UdpSocket::bind(bind_address) {
+        Ok(socket) => socket,
+        Err(e) => {
+          return Err(format!(
+            "Failed to open a socket for peer [{}]. {}",
+            peer, e
+          ))
+        }
+      };
+      self.sockets.insert(peer, socket);
+    }
+    match self.sockets.get(&peer) {
+      Some(socket) => Ok(socket),
+      None => Err(format!("Lost the socket for peer [{}].", peer)),
+    }
+  }
+
+  ///
+  /// Send whatever is due. Call this every tick, before the server's on_tick.
+  ///
+  /// The clock starts on the first step.
+  ///
+  pub fn step(&mut self, now: Instant) {
+    let start = *self.start.get_or_insert(now);
+    let elapsed = now.saturating_duration_since(start).as_secs_f64();
+
+    let mut sent = 0;
+    while let Some(entry) = self.entries.get(self.next_entry) {
+      let is_due = match self.speed {
+        ReplaySpeed::Original => entry.at <= elapsed,
+        ReplaySpeed::Fast => sent < FAST_PACKETS_PER_TICK,
+      };
+      if !is_due {
+        break;
+      }
+
+      let ReplayEvent::Network { peer, packet } = entry.event.clone();
+      let server_address = self.server_address;
+      let data = serialize(packet.sequence, &packet.message);
+      match self.get_socket(peer) {
+        Ok(socket) => {
+          if let Err(e) = socket.send_to(&data, server_address) {
+            println!("Replayer: Failed to send to peer [{}]. {}", peer, e);
+          }
+        }
+        Err(e) => println!("Replayer: {}", e),
+      }
+      sent += 1;
+      self.next_entry += 1;
+    }
+
+    if self.next_entry >= self.entries.len() && self.finished_at.is_none() {
+      println!("Replayer: sent the whole recording.");
+      self.finished_at = Some(now);
+    }
+  }
+
+  ///
+  /// Get if everything was sent and the server had REPLAY_SETTLE_TIME to handle it.
+  ///
+  pub fn is_finished(&self, now: Instant) -> bool {
+    match self.finished_at {
+      Some(finished_at) => now.saturating_duration_since(finished_at) >= REPLAY_SETTLE_TIME,
+      None => false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs::{create_dir_all, remove_dir_all},
+    time::Instant,
+  };
+
+  use glam::Vec3A;
+
+  use crate::{
+    file_utilities::test_dir,
+    game::{
+      network_message::{NetworkMessage, Packet},
+      replay::{Recorder, Replay, ReplayEvent},
+    },
+  };
+
+  #[test]
+  fn test_replay_format() {
+    println!("--- BEGIN REPLAY FORMAT TEST ---");
+    let directory = test_dir("replay_format");
+    if let Err(e) = create_dir_all(&directory) {
+      panic!("Unit test is broken. {}", e);
+    }
+    let path = format!("{}/format.replay", directory);
+
+    let recorder = match Recorder::create(&path) {
+      Ok(recorder) => recorder,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let alice = match "127.0.0.1:4000".parse() {
+      Ok(alice) => alice,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let bob = match "127.0.0.1:4001".parse() {
+      Ok(bob) => bob,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let player_move = Packet {
+      sequence: 2,
+      message: NetworkMessage::PlayerMove {
+        position: Vec3A::new(1.0, 2.0, 3.0),
+        rotation: Vec3A::ZERO,
+      },
+    };
+    let handshake = Packet {
+      sequence: 1,
+      message: NetworkMessage::HandShake {
+        name: "alice".to_string(),
+        password: "hunter2".to_string(),
+      },
+    };
+    let now = Instant::now();
+    recorder.record_packet(alice, &player_move, now);
+    recorder.record_packet(bob, &handshake, now);
+    recorder.record_packet(bob, &player_move, now);
+    recorder.record_packet(alice, &player_move, now);
+    recorder.flush();
+
+    let replay = match Replay::load(&path) {
+      Ok(replay) => replay,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let events: Vec<ReplayEvent> = replay
+      .get_entries()
+      .iter()
+      .map(|entry| entry.event.clone())
+      .collect();
+    assert_eq!(
+      events,
+      vec![
+        ReplayEvent::Network {
+          peer: 0,
+          packet: player_move.clone()
+        },
+        // Never written down.
+        ReplayEvent::Network {
+          peer: 1,
+          packet: Packet {
+            sequence: 1,
+            message: NetworkMessage::HandShake {
+              name: "alice".to_string(),
+              password: String::new(),
+            },
+          }
+        },
+        ReplayEvent::Network {
+          peer: 1,
+          packet: player_move.clone()
+        },
+        ReplayEvent::Network {
+          peer: 0,
+          packet: player_move
+        },
+      ]
+    );
+
+    // Recordings from another version are refused.
+    if let Err(e) = std::fs::write(&path, "{\"version\":0}\n") {
+      panic!("Unit test is broken. {}", e);
+    }
+    assert!(Replay::load(&path).is_err());
+
+    let _ = remove_dir_all(&directory);
+  }
+}
@@ -0,0 +1,323 @@
+use std::{
+  collections::BTreeMap,
+  mem::{discriminant, Discriminant},
+  time::{Duration, Instant},
+};
+
+use ahash::AHashMap;
+
+use super::network_message::{Delivery, NetworkMessage};
+
+///
+/// How many sequence numbers back a duplicate can be caught.
+///
+/// Anything older than this is dropped, there's no telling if it's a duplicate.
+///
+const DUPLICATE_WINDOW: u32 = u64::BITS;
+
+///
+/// How far ahead of the newest packet a sequence number can be.
+///
+/// Further than that isn't packet loss, it's a broken or hostile peer. Taking
+/// it would push the duplicate window so far ahead that every real packet
+/// after it gets dropped as too old.
+///
+const MAX_SEQUENCE_JUMP: u32 = 4096;
+
+///
+/// How long an Ordered message waits on the packets before it.
+///
+/// If they haven't shown up by then they're probably lost, it goes out anyway.
+///
+pub const ORDER_HOLD: Duration = Duration::from_millis(100);
+
+///
+/// Puts the packets from one peer back into shape.
+///
+/// Raw UDP can duplicate and reorder packets. Every packet's sequence
+/// number is checked here, duplicates are dropped and each message is
+/// handed over by it's Delivery.
+///
+/// todo: sequence numbers are compared straight, a wrap around isn't handled.
+/// todo: At 60 packets a second that's a couple years into one session.
+///
+pub struct Sequencer {
+  highest_sequence: Option<u32>,
+  lowest_sequence: u32,
+  // Bit i is set if highest_sequence - i was received.
+  received_mask: u64,
+
+  // Ordered messages waiting on earlier packets. sequence -> (arrived, message)
+  held: BTreeMap<u32, (Instant, NetworkMessage)>,
+  last_ordered: Option<u32>,
+
+  // The newest sequence handed over for each Latest kind of message.
+  latest: AHashMap<Discriminant<NetworkMessage>, u32>,
+}
+
+impl Sequencer {
+  pub fn new() -> Self {
+    Sequencer {
+      highest_sequence: None,
+      lowest_sequence: 0,
+      received_mask: 0,
+
+      held: BTreeMap::new(),
+      last_ordered: None,
+
+      latest: AHashMap::new(),
+    }
+  }
+
+  ///
+  /// Get if a sequence number has been received.
+  ///
+  /// Anything out of the duplicate window counts as received, it can't
+  /// be waited on anymore.
+  ///
+  fn is_received(&self, sequence: u32) -> bool {
+    let highest_sequence = match self.highest_sequence {
+      Some(highest_sequence) => highest_sequence,
+      None => return false,
+    };
+    if sequence > highest_sequence {
+      return false;
+    }
+    let distance = highest_sequence - sequence;
+    distance >= DUPLICATE_WINDOW || self.received_mask & (1 << distance) != 0
+  }
+
+  ///
+  /// Mark a sequence number as received.
+  ///
+  /// Returns false if it's a duplicate, too old to tell, or too far ahead.
+  ///
+  fn mark_received(&mut self, sequence: u32) -> bool {
+    let highest_sequence = match self.highest_sequence {
+      Some(highest_sequence) => highest_sequence,
+      None => {
+        self.highest_sequence = Some(sequence);
+        self.lowest_sequence = sequence;
+        self.received_mask = 1;
+        return true;
+      }
+    };
+
+    if sequence > highest_sequence {
+      let shift = sequence - highest_sequence;
+      if shift > MAX_SEQUENCE_JUMP {
+        return false;
+      }
+      self.received_mask = match shift >= DUPLICATE_WINDOW {
+        true => 0,
+        false => self.received_mask << shift,
+      } | 1;
+      self.highest_sequence = Some(sequence);
+      return true;
+    }
+
+    let distance = highest_sequence - sequence;
+    if distance >= DUPLICATE_WINDOW || self.received_mask & (1 << distance) != 0 {
+      return false;
+    }
+    self.received_mask |= 1 << distance;
+    true
+  }
+
+  ///
+  /// Get if a packet before this one, and after the last Ordered message, is missing.
+  ///
+  fn has_gap_before(&self, sequence: u32) -> bool {
+    let floor = self
+      .lowest_sequence
+      .max(
+        self
+          .last_ordered
+          .map_or(0, |last_ordered| last_ordered.saturating_add(1)),
+      )
+      .max(sequence.saturating_sub(DUPLICATE_WINDOW - 1));
+    (floor..sequence).any(|earlier| !self.is_received(earlier))
+  }
+
+  ///
+  /// Hand over the held Ordered messages that aren't waiting anymore.
+  ///
+  fn release_held(&mut self, now: Instant, ready: &mut Vec<NetworkMessage>) {
+    while let Some((sequence, (arrived, _))) = self.held.first_key_value() {
+      let sequence = *sequence;
+      if self.has_gap_before(sequence) && now.duration_since(*arrived) < ORDER_HOLD {
+        break;
+      }
+      if let Some((_, (_, message))) = self.held.pop_first() {
+        ready.push(message);
+      }
+      self.last_ordered = Some(
+        self
+          .last_ordered
+          .map_or(sequence, |last| last.max(sequence)),
+      );
+    }
+  }
+
+  ///
+  /// Run a packet through the Sequencer.
+  ///
+  /// Returns the messages that are ready to be handled, in order. That can
+  /// be none (held back), or more than one (it filled a gap).
+  /// Returns None if the packet was a duplicate, or can't be trusted.
+  ///
+  /// Sequence 0 is sessionless, it's always handed over.
+  ///
+  pub fn receive(
+    &mut self,
+    sequence: u32,
+    message: NetworkMessage,
+    now: Instant,
+  ) -> Option<Vec<NetworkMessage>> {
+    if sequence == 0 {
+      return Some(vec![message]);
+    }
+    if !self.mark_received(sequence) {
+      return None;
+    }
+
+    let mut ready = vec![];
+    match message.get_delivery() {
+      Delivery::Unordered => ready.push(message),
+      Delivery::Latest => {
+        let kind = discriminant(&message);
+        match self.latest.get(&kind) {
+          Some(newest) if *newest > sequence => (),
+          _ => {
+            self.latest.insert(kind, sequence);
+            ready.push(message);
+          }
+        }
+      }
+      Delivery::Ordered => {
+        self.held.insert(sequence, (now, message));
+      }
+    }
+
+    // This might have been what a held message was waiting on.
+    self.release_held(now, &mut ready);
+    Some(ready)
+  }
+
+  ///
+  /// Get the held messages that waited long enough. Call this every tick.
+  ///
+  pub fn take_ready(&mut self, now: Instant) -> Vec<NetworkMessage> {
+    let mut ready = vec![];
+    self.release_held(now, &mut ready);
+    ready
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Instant;
+
+  use glam::Vec3A;
+
+  use crate::game::{
+    network_message::NetworkMessage,
+    sequencer::{Sequencer, ORDER_HOLD},
+  };
+
+  fn chat(text: &str) -> NetworkMessage {
+    NetworkMessage::ChatMessage(text.to_string())
+  }
+
+  fn player_move(x: f32) -> NetworkMessage {
+    NetworkMessage::PlayerMove {
+      position: Vec3A::new(x, 0.0, 0.0),
+      rotation: Vec3A::ZERO,
+    }
+  }
+
+  #[test]
+  fn test_sequencer_drops_duplicates() {
+    println!("--- BEGIN SEQUENCER DROPS DUPLICATES TEST ---");
+    let now = Instant::now();
+    let mut sequencer = Sequencer::new();
+
+    assert_eq!(
+      sequencer.receive(1, chat("hi"), now),
+      Some(vec![chat("hi")])
+    );
+    assert_eq!(sequencer.receive(1, chat("hi"), now), None);
+    assert_eq!(
+      sequencer.receive(2, NetworkMessage::Hi, now),
+      Some(vec![NetworkMessage::Hi])
+    );
+
+    // A duplicate behind the newest packet is still caught.
+    assert_eq!(
+      sequencer.receive(70, NetworkMessage::Hi, now),
+      Some(vec![NetworkMessage::Hi])
+    );
+    assert_eq!(sequencer.receive(2, NetworkMessage::Hi, now), None);
+    // Too far behind to tell.
+    assert_eq!(sequencer.receive(3, NetworkMessage::Hi, now), None);
+
+    // A jump nobody could have lost that many packets for is dropped,
+    // and doesn't push the window away from the real packets.
+    assert_eq!(sequencer.receive(u32::MAX, chat("forged"), now), None);
+    assert_eq!(
+      sequencer.receive(71, NetworkMessage::Hi, now),
+      Some(vec![NetworkMessage::Hi])
+    );
+
+    // Sessionless packets aren't sequenced.
+    assert!(sequencer.receive(0, NetworkMessage::Hi, now).is_some());
+    assert!(sequencer.receive(0, NetworkMessage::Hi, now).is_some());
+  }
+
+  #[test]
+  fn test_sequencer_delivery() {
+    println!("--- BEGIN SEQUENCER DELIVERY TEST ---");
+    let now = Instant::now();
+    let mut sequencer = Sequencer::new();
+
+    // An older movement update is ignored in favor of the newer one.
+    assert_eq!(
+      sequencer.receive(2, player_move(2.0), now),
+      Some(vec![player_move(2.0)])
+    );
+    assert_eq!(sequencer.receive(1, player_move(1.0), now), Some(vec![]));
+    assert_eq!(
+      sequencer.receive(3, player_move(3.0), now),
+      Some(vec![player_move(3.0)])
+    );
+
+    // Chat 5 shows up before chat 4, it waits for it.
+    assert_eq!(sequencer.receive(5, chat("second"), now), Some(vec![]));
+    assert_eq!(
+      sequencer.receive(4, chat("first"), now),
+      Some(vec![chat("first"), chat("second")])
+    );
+
+    // 6 never shows up. 7 waits, but not forever.
+    assert_eq!(sequencer.receive(7, chat("lost"), now), Some(vec![]));
+    assert_eq!(sequencer.take_ready(now), vec![]);
+    assert_eq!(sequencer.take_ready(now + ORDER_HOLD), vec![chat("lost")]);
+
+    // When 6 finally shows up, it's late, it goes straight out.
+    assert_eq!(
+      sequencer.receive(6, chat("late"), now + ORDER_HOLD),
+      Some(vec![chat("late")])
+    );
+
+    // The last possible sequence number doesn't overflow what comes after it.
+    let mut sequencer = Sequencer::new();
+    assert_eq!(
+      sequencer.receive(u32::MAX, chat("last"), now),
+      Some(vec![chat("last")])
+    );
+    assert_eq!(
+      sequencer.receive(u32::MAX - 1, chat("late"), now),
+      Some(vec![chat("late")])
+    );
+  }
+}
@@ -1,22 +1,421 @@
+//!
+//! This is where we implement any Serde serialization
+//! and deserialization components purely functionally.
+//!
+//! This will clean up code as it's easier to read these two
+//! functions encapsulating a component than it is to read
+//! the (possibly) complex process it _might_ be to utilize serde.
+//!
+
+use std::fmt;
+
+use serde::Serialize;
+
+use super::{
+  game_config::GameConfig,
+  network_message::{NetworkMessage, Packet},
+};
+
+///
+/// The biggest message taken off the wire, unless minetest.conf says max_message_bytes. [bytes]
+///
+/// A UDP packet can't be bigger than 64 KiB anyway, so this sits well
+/// under that to actually keep big garbage away from serde.
+///
+/// Media comes in MEDIA_CHUNK_BYTES chunks of base64 that fit under this.
+/// Going much lower than the default keeps media from getting through at all.
+///
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 32 * 1024;
+
+///
+/// Why raw bytes couldn't be turned into a NetworkMessage.
+///
+/// The checks before serde don't build a String, so a flood of garbage
+/// packets mostly costs nothing. Something that got as far as serde
+/// keeps serde's reason, that's the part worth reading.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeserializeError {
+  // Rejected before serde ever saw it.
+  NotAMessage {
+    length: usize,
+  },
+  // Bigger than max_message_bytes, also before serde.
+  TooBig {
+    length: usize,
+    max: usize,
+  },
+  // Why and where serde gave up.
+  Malformed {
+    reason: String,
+    line: usize,
+    column: usize,
+  },
+}
+
+impl fmt::Display for DeserializeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      DeserializeError::NotAMessage { length } => write!(
+        f,
+        "serial: Failed to deserialize NetworkMessage. [{}] bytes that are not a message.",
+        length
+      ),
+      DeserializeError::TooBig { length, max } => write!(
+        f,
+        "serial: Refused to deserialize NetworkMessage. [{}] bytes is over the [{}] byte limit.",
+        length, max
+      ),
+      DeserializeError::Malformed {
+        reason,
+        line,
+        column,
+      } => write!(
+        f,
+        "serial: Failed to deserialize NetworkMessage. Malformed at line [{}] column [{}]. {}",
+        line, column, reason
+      ),
+    }
+  }
+}
+
+///
+/// A Packet that borrows it's message, so sending doesn't clone it.
+///
+/// This has to serialize exactly like Packet.
 ///
-/// Todo:
+#[derive(Serialize)]
+struct PacketRef<'a> {
+  sequence: u32,
+  message: &'a NetworkMessage,
+}
+
+///
+/// Turn a NetworkMessage into raw bytes to send over the wire.
+///
+/// This allocates a new Vec every time, anything that sends a lot
+/// should keep a buffer around for serialize_into.
+///
+pub fn serialize(sequence: u32, message: &NetworkMessage) -> Vec<u8> {
+  let mut data = vec![];
+  serialize_into(&mut data, sequence, message);
+  data
+}
+
+///
+/// serialize(), but into a buffer that gets reused.
+///
+/// The buffer is cleared first, it keeps its capacity between calls
+/// so a connection stops allocating once it's sent its biggest message.
+///
+pub fn serialize_into(buffer: &mut Vec<u8>, sequence: u32, message: &NetworkMessage) {
+  buffer.clear();
+  if let Err(e) = serde_json::to_writer(&mut *buffer, &PacketRef { sequence, message }) {
+    // A plain enum cannot fail to serialize, this is a bug.
+    panic!("serial: Failed to serialize NetworkMessage. {}", e);
+  }
+}
+
+///
+/// A Packet is always a JSON object.
+///
+/// Checking the ends is nearly free, and most junk off the internet
+/// fails it without going through serde at all.
+///
+fn could_be_message(data: &[u8]) -> bool {
+  data.len() >= 2 && matches!((data.first(), data.last()), (Some(b'{'), Some(b'}')))
+}
+
 ///
-/// This is where we shall implement any Serde serialization
-/// and deserialization components purely functionally.
+/// Turn raw bytes from the wire back into a Packet.
 ///
-/// This will clean up code as it's easier to read these two
-/// functions encapsulating a component than it is to read
-/// the (possibly) complex process it _might_ be to utilize serde.
+/// Reads straight from the slice, there's no intermediate String.
 ///
-/// ! This is highly subjective at the moment and will possibly be
-/// ! dropped at any moment due to not having any implementation
-/// ! in this stage of development.
+/// ! This is untrusted data, it must never panic.
 ///
+pub fn deserialize(data: &[u8]) -> Result<Packet, DeserializeError> {
+  if !could_be_message(data) {
+    return Err(DeserializeError::NotAMessage { length: data.len() });
+  }
 
-pub fn serialize() {
-  println!("serial");
+  match serde_json::from_slice(data) {
+    Ok(packet) => Ok(packet),
+    Err(e) => Err(DeserializeError::Malformed {
+      reason: e.to_string(),
+      line: e.line(),
+      column: e.column(),
+    }),
+  }
 }
 
-pub fn deserialize() {
-  println!("deserial")
+///
+/// Turn raw bytes from the wire back into a Packet, if they aren't over max_bytes.
+///
+/// The length is checked first, so an oversized message never gets parsed.
+///
+pub fn deserialize_bounded(data: &[u8], max_bytes: usize) -> Result<Packet, DeserializeError> {
+  if data.len() > max_bytes {
+    return Err(DeserializeError::TooBig {
+      length: data.len(),
+      max: max_bytes,
+    });
+  }
+  deserialize(data)
+}
+
+///
+/// Raw bytes as a base64 string, for #[serde(with = "base64_bytes")].
+///
+/// JSON spells a Vec<u8> out as numbers, up to 4 characters a byte.
+/// Base64 is always 4 for every 3.
+///
+pub mod base64_bytes {
+  use base64::{engine::general_purpose::STANDARD, Engine};
+  use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+  pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&STANDARD.encode(data))
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+    STANDARD.decode(encoded).map_err(Error::custom)
+  }
+}
+
+///
+/// Read the biggest message to accept out of minetest.conf. (max_message_bytes)
+///
+pub fn get_max_message_bytes(config: &GameConfig) -> usize {
+  match config.get("max_message_bytes") {
+    Some(max_bytes) => match max_bytes.parse::<usize>() {
+      Ok(max_bytes) if max_bytes > 0 => max_bytes,
+      _ => {
+        println!("serial: ignoring max_message_bytes [{}].", max_bytes);
+        DEFAULT_MAX_MESSAGE_BYTES
+      }
+    },
+    None => DEFAULT_MAX_MESSAGE_BYTES,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::game::{
+    game_config::GameConfig,
+    network_message::{NetworkMessage, Packet, MEDIA_CHUNK_BYTES},
+    serial::{
+      deserialize, deserialize_bounded, get_max_message_bytes, serialize, serialize_into,
+      DeserializeError, DEFAULT_MAX_MESSAGE_BYTES,
+    },
+  };
+
+  #[test]
+  fn test_serial_round_trip() {
+    println!("--- BEGIN SERIAL ROUND TRIP TEST ---");
+    let message = NetworkMessage::StatusResponse {
+      name: "server".to_string(),
+      description: "a server".to_string(),
+      motd: "hello".to_string(),
+      players: 3,
+      max_players: 15,
+      version: "0.0.0".to_string(),
+    };
+
+    assert_eq!(
+      deserialize(&serialize(7, &message)),
+      Ok(Packet {
+        sequence: 7,
+        message
+      })
+    );
+
+    // Garbage must be rejected, not panic.
+    assert_eq!(
+      deserialize(b"\xff\x00garbage"),
+      Err(DeserializeError::NotAMessage { length: 9 })
+    );
+    assert_eq!(
+      deserialize(b"\""),
+      Err(DeserializeError::NotAMessage { length: 1 })
+    );
+    match deserialize(b"{\"Nope\":1}") {
+      Err(e @ DeserializeError::Malformed { .. }) => {
+        // serde's reason makes it into the message.
+        assert!(e.to_string().contains("missing field"));
+      }
+      other => panic!("Unit test is broken. Got [{:?}].", other),
+    }
+
+    // A reused buffer gives the same bytes, with nothing left over from before.
+    let mut buffer = vec![];
+    serialize_into(
+      &mut buffer,
+      1,
+      &NetworkMessage::ChatMessage("a".repeat(100)),
+    );
+    let small = NetworkMessage::ChatMessage("hi".to_string());
+    serialize_into(&mut buffer, 7, &small);
+    assert_eq!(buffer, serialize(7, &small));
+  }
+
+  #[test]
+  fn test_serial_max_message_bytes() {
+    println!("--- BEGIN SERIAL MAX MESSAGE BYTES TEST ---");
+    let max_bytes = |raw_config: &str| get_max_message_bytes(&GameConfig::parse(raw_config));
+    assert_eq!(max_bytes(""), DEFAULT_MAX_MESSAGE_BYTES);
+    assert_eq!(max_bytes("max_message_bytes = 4096"), 4096);
+    assert_eq!(
+      max_bytes("max_message_bytes = 0"),
+      DEFAULT_MAX_MESSAGE_BYTES
+    );
+
+    // A full chunk of media fits by default.
+    let media = serialize(
+      u32::MAX,
+      &NetworkMessage::Media {
+        hash: "0".repeat(40),
+        chunk: u32::MAX,
+        chunk_count: u32::MAX,
+        data: vec![255; MEDIA_CHUNK_BYTES],
+      },
+    );
+    assert!(deserialize_bounded(&media, DEFAULT_MAX_MESSAGE_BYTES).is_ok());
+
+    // Media that isn't base64 is malformed, not a panic.
+    assert!(matches!(
+      deserialize(
+        br#"{"sequence":1,"message":{"Media":{"hash":"","chunk":0,"chunk_count":1,"data":"no!"}}}"#
+      ),
+      Err(DeserializeError::Malformed { .. })
+    ));
+
+    let small = serialize(1, &NetworkMessage::ChatMessage("hi".to_string()));
+    assert!(deserialize_bounded(&small, 4096).is_ok());
+    // Right at the limit is fine.
+    assert!(deserialize_bounded(&small, small.len()).is_ok());
+
+    // A valid message that's too big comes back as TooBig, not a Packet.
+    // If serde had seen it, it would have parsed.
+    let big = serialize(2, &NetworkMessage::ChatMessage("a".repeat(5000)));
+    assert!(deserialize(&big).is_ok());
+    assert_eq!(
+      deserialize_bounded(&big, 4096),
+      Err(DeserializeError::TooBig {
+        length: big.len(),
+        max: 4096
+      })
+    );
+
+    // Garbage that's too big never gets looked at either.
+    let garbage = vec![b'{'; 5000];
+    assert!(matches!(
+      deserialize_bounded(&garbage, 4096),
+      Err(DeserializeError::TooBig { .. })
+    ));
+  }
+
+  ///
+  /// Run with: cargo test --release bench_deserialize -- --ignored --nocapture
+  ///
+  #[test]
+  #[ignore]
+  fn bench_deserialize_small_messages() {
+    println!("--- BEGIN DESERIALIZE SMALL MESSAGES BENCHMARK ---");
+    let valid = [
+      serialize(1, &NetworkMessage::PingRequest { id: 0 }),
+      serialize(2, &NetworkMessage::ChatMessage("hi".to_string())),
+      serialize(
+        3,
+        &NetworkMessage::HandShake {
+          name: "singleplayer".to_string(),
+          password: String::new(),
+        },
+      ),
+    ];
+    let garbage: [&[u8]; 3] = [b"\xff\x00garbage", b"{\"Nope\":1}", b"\"PingRequ"];
+
+    const ITERATIONS: usize = 200_000;
+    const SAMPLES: usize = 10;
+
+    let run = |label: &str, messages: &[&[u8]]| {
+      // Warm up.
+      for message in messages.iter().cycle().take(ITERATIONS / 10) {
+        let _ = std::hint::black_box(deserialize(message));
+      }
+      let mut samples = vec![];
+      for _ in 0..SAMPLES {
+        let start = std::time::Instant::now();
+        for message in messages.iter().cycle().take(ITERATIONS) {
+          let _ = std::hint::black_box(deserialize(std::hint::black_box(message)));
+        }
+        samples.push(start.elapsed().as_nanos() as f64 / ITERATIONS as f64);
+      }
+      samples.sort_by(|a, b| a.total_cmp(b));
+      println!(
+        "{}: median [{:.1}ns] min [{:.1}ns] max [{:.1}ns] per message",
+        label,
+        samples[SAMPLES / 2],
+        samples[0],
+        samples[SAMPLES - 1]
+      );
+    };
+
+    let valid: Vec<&[u8]> = valid.iter().map(|message| message.as_slice()).collect();
+    run("valid", &valid);
+    run("garbage", &garbage);
+  }
+
+  ///
+  /// Run with: cargo test --release bench_serialize -- --ignored --nocapture
+  ///
+  #[test]
+  #[ignore]
+  fn bench_serialize_reused_buffer() {
+    println!("--- BEGIN SERIALIZE REUSED BUFFER BENCHMARK ---");
+    let messages = [
+      NetworkMessage::PingRequest { id: 0 },
+      NetworkMessage::ChatMessage("hello everyone".to_string()),
+      NetworkMessage::EntityPositions {
+        positions: (0..32)
+          .map(|id| (id, glam::Vec3A::splat(id as f32)))
+          .collect(),
+      },
+    ];
+
+    const ITERATIONS: usize = 200_000;
+    const SAMPLES: usize = 10;
+
+    let run = |label: &str, send: &mut dyn FnMut(u32, &NetworkMessage) -> usize| {
+      // Warm up.
+      for (sequence, message) in messages.iter().cycle().take(ITERATIONS / 10).enumerate() {
+        std::hint::black_box(send(sequence as u32, message));
+      }
+      let mut samples = vec![];
+      for _ in 0..SAMPLES {
+        let start = std::time::Instant::now();
+        for (sequence, message) in messages.iter().cycle().take(ITERATIONS).enumerate() {
+          std::hint::black_box(send(sequence as u32, std::hint::black_box(message)));
+        }
+        samples.push(start.elapsed().as_nanos() as f64 / ITERATIONS as f64);
+      }
+      samples.sort_by(|a, b| a.total_cmp(b));
+      println!(
+        "{}: median [{:.1}ns] min [{:.1}ns] max [{:.1}ns] per message",
+        label,
+        samples[SAMPLES / 2],
+        samples[0],
+        samples[SAMPLES - 1]
+      );
+    };
+
+    run("new Vec", &mut |sequence, message| {
+      serialize(sequence, message).len()
+    });
+    let mut buffer = vec![];
+    run("reused buffer", &mut |sequence, message| {
+      serialize_into(&mut buffer, sequence, message);
+      buffer.len()
+    });
+  }
 }
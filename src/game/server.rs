@@ -1,8 +1,123 @@
-mod server_connection;
+pub mod auth;
+mod ban_list;
+mod broadcast_clock;
+mod chat_command;
+pub mod entities;
+pub mod entity_physics;
+pub mod inventory;
+pub mod item_registry;
+mod joined_players;
+pub mod maintenance;
+pub mod map_backend;
+pub mod media_index;
+mod motd;
+pub mod node_registry;
+pub mod privileges;
+pub mod rate_limiter;
+mod send_queue;
+pub mod server_connection;
+pub mod server_info;
+mod shutdown_countdown;
+pub mod sound_request;
+mod tick_budget;
+pub mod world_limits;
+mod world_meta;
 
-use self::server_connection::ServerConnection;
+use std::{
+  cell::RefCell,
+  net::SocketAddr,
+  rc::Rc,
+  time::{Duration, Instant},
+};
 
-use super::lua_engine::LuaEngine;
+use glam::{IVec3, Vec3A};
+
+use self::{
+  auth::PasswordAuth,
+  broadcast_clock::BroadcastClock,
+  chat_command::{ChatCommand, ChatCommandInfo, TeleportTarget, CHAT_COMMANDS},
+  entities::ServerEntities,
+  entity_physics::EntityPhysics,
+  inventory::{starter_kit_from_config, Inventories},
+  item_registry::ItemRegistry,
+  joined_players::JoinedPlayers,
+  maintenance::{
+    maintenance_task_from_name, Maintenance, MaintenanceContext, MaintenanceRequest,
+    DEFAULT_MAINTENANCE_STEPS_PER_TICK,
+  },
+  map_backend::{map_backend_from_world, MapBackend},
+  media_index::MediaIndex,
+  node_registry::NodeRegistry,
+  privileges::{Privileges, DEFAULT_PRIVILEGES},
+  server_connection::ServerConnection,
+  server_info::ServerInfo,
+  shutdown_countdown::ShutdownCountdown,
+  sound_request::SoundRequest,
+  tick_budget::TickBudget,
+  world_meta::{WorldMeta, PSEUDO_RANDOM_SUBSYSTEM},
+};
+
+use super::{
+  connection_stats::ReceiveHistogram,
+  event_bus::EngineEvent,
+  game_config::GameConfig,
+  game_init_error::GameInitError,
+  lua_engine::{
+    lua_file_helpers::{get_game_mod_folders, get_game_path, get_missing_game_parts, ModDirectory},
+    LuaEngine, GAMES_DIR,
+  },
+  native_plugin::PluginRegistry,
+  network_message::NetworkMessage,
+  replay::Recorder,
+  startup_check::StartupReport,
+  time_of_day::TimeOfDay,
+  time_step::TimeStep,
+};
+
+///
+/// The name the server console shows up as, like in /say.
+///
+/// Players can't join with it.
+///
+pub const CONSOLE_ISSUER: &str = "console";
+
+///
+/// Who's running a command.
+///
+/// The console has every privilege. It's not a name, so no player can pass for it.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issuer {
+  Console,
+  Player(String),
+}
+
+impl Issuer {
+  pub fn player(name: &str) -> Self {
+    Issuer::Player(name.to_string())
+  }
+
+  pub fn get_name(&self) -> &str {
+    match self {
+      Issuer::Console => CONSOLE_ISSUER,
+      Issuer::Player(name) => name,
+    }
+  }
+}
+
+///
+/// How often the time of day goes out to the players. [seconds]
+///
+/// Like minetest C++'s time_send_interval. Clients run the clock in between.
+///
+const TIME_SEND_INTERVAL: f64 = 5.0;
+
+///
+/// How often inventories that changed get written out. [seconds]
+///
+/// A crash loses at most this much, without writing the file every tick.
+///
+const INVENTORY_SAVE_INTERVAL: f64 = 10.0;
 
 ///
 /// The Server component for the engine.
@@ -10,39 +125,233 @@ use super::lua_engine::LuaEngine;
 /// The Server component has 4 jobs:
 /// 1.) Processes LuaEngine just as LuaJIT does in Minetest C++'s server.
 /// 2.) Hold a ServerConnection component which will handle talking to clients.
-/// * 3.) [in the future] Be the main handler for ServerAuthentication.
+/// 3.) Utilize the GameConfig (minetest.conf) during runtime.
+/// * 4.) [in the future] Be the main handler for ServerAuthentication.
 /// *  - ServerAuthentication does exactly what you think it does.
 /// *  - It handles the client auth for the server.
-/// ? 4.) Handle GameConfig as a component to be utilized during runtime.
-/// ?  - Marked with ? because it's still being thought out at the moment.
 ///
 pub struct Server {
   lua_engine: LuaEngine,
   connection: ServerConnection,
+  // Shared with the LuaEngine, see lua_privileges.
+  privileges: Rc<RefCell<Privileges>>,
+  // Shared with the LuaEngine, see lua_time_of_day.
+  time_of_day: Rc<RefCell<TimeOfDay>>,
+  // Shared with the LuaEngine, see lua_nodes.
+  nodes: Rc<RefCell<NodeRegistry>>,
+  // Shared with the LuaEngine, see lua_items.
+  items: Rc<RefCell<ItemRegistry>>,
+  // Shared with the LuaEngine, see lua_inventory.
+  inventories: Rc<RefCell<Inventories>>,
+  // Who gets the starter kit, only players that aren't on it.
+  joined_players: JoinedPlayers,
+  // Filled by the LuaEngine, see lua_sound. Sent out every broadcast.
+  sounds: Rc<RefCell<Vec<SoundRequest>>>,
+  // Read by the LuaEngine, see lua_server_info. Refreshed every tick.
+  server_info: Rc<RefCell<ServerInfo>>,
+  time_send_timer: f64,
+  inventory_save_timer: f64,
+  // Which ticks send state out. Follows the tick rate without a network_broadcast_rate.
+  broadcast_clock: BroadcastClock,
+  network_broadcast_rate: Option<f64>,
+  world_path: String,
+  world_meta: WorldMeta,
+  // Picked by world.mt's backend.
+  map_backend: Box<dyn MapBackend>,
+  // Players and everything else the clients draw, see AddEntity.
+  entities: ServerEntities,
+  // Gravity and velocity, run every tick.
+  physics: EntityPhysics,
+  // Long running admin jobs like clearobjects, a slice every tick.
+  maintenance: Maintenance,
+  // Filled by the LuaEngine, see lua_maintenance.
+  maintenance_requests: Rc<RefCell<Vec<MaintenanceRequest>>>,
+  // Filled by the LuaEngine, see lua_players. (name, position)
+  teleport_requests: Rc<RefCell<Vec<(String, Vec3A)>>>,
   shutdown_approved: bool,
+  // So on_shutdown only runs the mods' callbacks once.
+  shutdown_callbacks_done: bool,
+  shutdown_countdown: Option<ShutdownCountdown>,
+  tick_budget: TickBudget,
+  had_network_activity: bool,
+
+  // Queued up for the Game's EventBus.
+  events: Vec<EngineEvent>,
+
+  plugins: PluginRegistry,
+
+  game_name: String,
+  server_description: String,
+  // Skip a mod that fails to load instead of crashing, see LuaEngine::set_skip_broken_mods.
+  skip_broken_mods: bool,
 }
 
 impl Server {
-  pub fn new(address: String, port: i32, game_name: String) -> Self {
+  ///
+  /// Create a Server, panicking if the connection can't be created
+  /// or the game isn't installed.
+  ///
+  /// Use try_new() to handle that instead.
+  ///
+  pub fn new(
+    address: String,
+    port: i32,
+    game_name: String,
+    world_path: &str,
+    config: &GameConfig,
+    goal_ticks_per_second: f64,
+  ) -> Self {
+    match Self::try_new(
+      address,
+      port,
+      game_name,
+      world_path,
+      config,
+      goal_ticks_per_second,
+    ) {
+      Ok(new_server) => new_server,
+      Err(e) => panic!("Server: {}", e),
+    }
+  }
+
+  ///
+  /// Create a Server.
+  ///
+  /// Fails if the address can't be resolved or bound, like when
+  /// another server is already on the port.
+  /// Also fails if the game's directory, game.conf, or mods/ is missing.
+  ///
+  pub fn try_new(
+    address: String,
+    port: i32,
+    game_name: String,
+    world_path: &str,
+    config: &GameConfig,
+    goal_ticks_per_second: f64,
+  ) -> Result<Self, GameInitError> {
     // Create a connection.
-    let connection = ServerConnection::new(address, port);
+    let connection = ServerConnection::new(address, port, config, world_path)?;
+
+    // The world decides which game it runs, world.mt ties them together.
+    // A fixed seed makes the whole run reproducible, see WorldMeta::get_derived_seed.
+    let fixed_seed = config
+      .get("fixed_map_seed")
+      .and_then(|seed| match seed.parse::<u64>() {
+        Ok(seed) => Some(seed),
+        Err(e) => {
+          println!("Server: ignoring fixed_map_seed [{}]. {}", seed, e);
+          None
+        }
+      });
+    let mut world_meta = WorldMeta::load_with_seed(world_path, fixed_seed);
+
+    // Catch a game that isn't there before mod loading trips over it,
+    // and before the world gets tied to it.
+    let checked_game = match world_meta.get_game_id() {
+      Some(game_id) => game_id.clone(),
+      None => game_name.clone(),
+    };
+    let missing = get_missing_game_parts(GAMES_DIR, &checked_game);
+    if !missing.is_empty() {
+      return Err(GameInitError::Game {
+        path: get_game_path(GAMES_DIR, &checked_game),
+        game: checked_game,
+        missing,
+      });
+    }
+
+    let game_name = match world_meta.reconcile_game(&game_name) {
+      Ok(game_name) => game_name,
+      Err(e) => {
+        println!("Server: failed to save the world's game. {}", e);
+        game_name
+      }
+    };
+    for mod_name in world_meta.get_unknown_mods(&get_game_mod_folders(GAMES_DIR, &game_name)) {
+      println!(
+        "Server: world.mt mentions mod [{}], game [{}] doesn't have it.",
+        mod_name, game_name
+      );
+    }
+
+    let map_backend = map_backend_from_world(world_path, &world_meta.get_backend())
+      .map_err(GameInitError::World)?;
 
     // Create the base Luau virtual machine.
     let lua_engine = LuaEngine::new(true);
 
+    // How much a maintenance task does per tick, see Maintenance.
+    let maintenance_steps_per_tick = match config.get("maintenance_steps_per_tick") {
+      Some(steps) => match steps.parse::<usize>() {
+        Ok(steps) if steps > 0 => steps,
+        _ => {
+          println!("Server: ignoring maintenance_steps_per_tick [{}].", steps);
+          DEFAULT_MAINTENANCE_STEPS_PER_TICK
+        }
+      },
+      None => DEFAULT_MAINTENANCE_STEPS_PER_TICK,
+    };
+
+    let mut inventories = Inventories::load(world_path);
+    inventories.set_default_starter_kit(starter_kit_from_config(config));
+    let privileges = Privileges::load(
+      world_path,
+      &config.get_string("default_privs", DEFAULT_PRIVILEGES),
+    );
+    // A world from before joined.txt only has these to go on.
+    let mut known_players = inventories.get_names();
+    known_players.extend(privileges.get_names());
+    known_players.extend(PasswordAuth::load(world_path, false).get_names());
+    let joined_players = JoinedPlayers::load(world_path, &known_players);
+
     let mut new_server = Server {
       lua_engine,
       connection,
+      privileges: Rc::new(RefCell::new(privileges)),
+      time_of_day: Rc::new(RefCell::new(TimeOfDay::load(world_path, config))),
+      nodes: Rc::new(RefCell::new(NodeRegistry::new(vec![]))),
+      items: Rc::new(RefCell::new(ItemRegistry::new())),
+      inventories: Rc::new(RefCell::new(inventories)),
+      joined_players,
+      sounds: Rc::new(RefCell::new(vec![])),
+      server_info: Rc::new(RefCell::new(ServerInfo::new(Instant::now()))),
+      time_send_timer: 0.0,
+      inventory_save_timer: 0.0,
+      broadcast_clock: BroadcastClock::new(goal_ticks_per_second),
+      network_broadcast_rate: None,
+      world_path: world_path.to_owned(),
+      map_backend,
+      entities: ServerEntities::new(),
+      physics: EntityPhysics::from_config(config),
+      maintenance: Maintenance::new(maintenance_steps_per_tick),
+      maintenance_requests: Rc::new(RefCell::new(vec![])),
+      teleport_requests: Rc::new(RefCell::new(vec![])),
+      world_meta,
       shutdown_approved: false,
+      shutdown_callbacks_done: false,
+      shutdown_countdown: None,
+      tick_budget: TickBudget::new(goal_ticks_per_second),
+      had_network_activity: false,
+
+      events: vec![],
+
+      plugins: PluginRegistry::new(),
+
+      game_name: game_name.clone(),
+      server_description: config.get_string("server_description", ""),
+      skip_broken_mods: config.get_bool("skip_broken_mods", false),
     };
 
     // Automatically create a new Server LuaEngine.
     new_server.reset_lua_vm();
 
+    // Mods can ask for the status while they load.
+    new_server.refresh_server_info();
+
     // Automatically load up the requested game into memory.
     new_server.load_game(game_name);
 
-    new_server
+    Ok(new_server)
   }
 
   ///
@@ -51,69 +360,1789 @@ impl Server {
   ///
   pub fn reset_lua_vm(&mut self) {
     self.lua_engine = LuaEngine::new(true);
+    self.lua_engine.set_skip_broken_mods(self.skip_broken_mods);
+    self
+      .lua_engine
+      .set_default_seed(self.world_meta.get_derived_seed(PSEUDO_RANDOM_SUBSYSTEM));
+    self.lua_engine.set_privileges(self.privileges.clone());
+    self.lua_engine.set_time_of_day(self.time_of_day.clone());
+    // The mods register their nodes all over again.
+    self.nodes = Rc::new(RefCell::new(NodeRegistry::from_game(
+      GAMES_DIR,
+      &self.game_name,
+    )));
+    self.lua_engine.set_nodes(self.nodes.clone());
+    self.items = Rc::new(RefCell::new(ItemRegistry::new()));
+    self.lua_engine.set_items(self.items.clone());
+    // And set their starter kit up all over again too.
+    self.inventories.borrow_mut().reset_starter_kit();
+    self.lua_engine.set_inventories(
+      self.inventories.clone(),
+      self.items.clone(),
+      self.nodes.clone(),
+    );
+    self.lua_engine.set_sounds(self.sounds.clone());
+    self.lua_engine.set_server_info(self.server_info.clone());
+    self
+      .lua_engine
+      .set_world_limits(*self.connection.get_world_limits());
+    self
+      .lua_engine
+      .set_maintenance_requests(self.maintenance_requests.clone());
+    self
+      .lua_engine
+      .set_players(self.server_info.clone(), self.teleport_requests.clone());
   }
 
   ///
   /// Chain initial game load into LuaEngine to clean up new() implemenetation.
   ///
+  /// Mods the world has disabled are skipped.
+  ///
   pub fn load_game(&mut self, game_name: String) {
-    self.lua_engine.load_game(game_name)
+    let (enabled_mods, disabled_mods): (Vec<ModDirectory>, Vec<ModDirectory>) =
+      get_game_mod_folders(GAMES_DIR, &game_name)
+        .into_iter()
+        .partition(|mod_directory| self.world_meta.is_mod_enabled(&mod_directory.mod_name));
+    // Clients can only ask for the media of mods that are actually running.
+    self
+      .connection
+      .set_media_index(MediaIndex::from_mods(&enabled_mods));
+    let disabled_mods: Vec<String> = disabled_mods
+      .into_iter()
+      .map(|mod_directory| mod_directory.mod_name)
+      .collect();
+    self.lua_engine.load_game(game_name, &disabled_mods)
   }
 
   ///
-  /// Allows the game to check if the server has approved
-  /// a shutdown request from a client.
+  /// Wipe the LuaEngine and load the game back up from disk.
   ///
-  pub fn shutdown_is_approved(&self) -> bool {
-    self.shutdown_approved
+  pub fn reload(&mut self) {
+    println!("Server: reloading game [{}].", self.game_name);
+    self.reset_lua_vm();
+    self.load_game(self.game_name.clone());
   }
 
   ///
-  /// ! (will) [not implemented yet]
-  /// todo: implement this somehow
-  /// Automatically validate and accept/deny shutdown requests
+  /// Take over the native plugins and load them.
   ///
-  fn check_shutdown_requests(&mut self) {
-    // Let's clear out the entire list so we don't cause a memory leak.
-    // ! This NEEDS to check the database for a bad actor.
-    while !self.connection.shutdown_requests.is_empty() {
-      println!("looping");
-      if let Some(shutdown_requester) = self.connection.shutdown_requests.pop() {
-        println!(
-          "Server: shutdown requested by [{}]",
-          shutdown_requester.addr()
-        );
-        self.shutdown_approved = true
-      }
+  /// They tick after the LuaEngine, and unload when the Server drops.
+  ///
+  pub fn load_plugins(&mut self, plugins: PluginRegistry) {
+    // The old registry unloads its plugins as it drops.
+    self.plugins = plugins;
+    self.plugins.load();
+  }
+
+  ///
+  /// Get every mod the game has.
+  ///
+  fn get_game_mods(&self) -> Vec<ModDirectory> {
+    get_game_mod_folders(GAMES_DIR, &self.game_name)
+  }
+
+  ///
+  /// Get the names of the game's mods this world has enabled, sorted.
+  ///
+  pub fn get_enabled_mods(&self) -> Vec<String> {
+    let mut enabled_mods: Vec<String> = self
+      .get_game_mods()
+      .into_iter()
+      .map(|mod_directory| mod_directory.mod_name)
+      .filter(|mod_name| self.world_meta.is_mod_enabled(mod_name))
+      .collect();
+    enabled_mods.sort();
+    enabled_mods
+  }
+
+  ///
+  /// Turn a mod off for this world, then reload the game without it.
+  ///
+  /// Refuses if an enabled mod depends on it.
+  ///
+  pub fn disable_mod(&mut self, mod_name: &str) -> Result<(), String> {
+    let mods = self.get_game_mods();
+    if !mods
+      .iter()
+      .any(|mod_directory| mod_directory.mod_name == mod_name)
+    {
+      return Err(format!("Server: game has no mod [{}].", mod_name));
     }
+
+    let dependents = self.world_meta.get_enabled_dependents(mod_name, &mods);
+    if !dependents.is_empty() {
+      return Err(format!(
+        "Server: can't disable [{}], these mods depend on it: [{}]",
+        mod_name,
+        dependents.join(", ")
+      ));
+    }
+
+    self.world_meta.set_mod_enabled(mod_name, false)?;
+    println!("Server: disabled mod [{}].", mod_name);
+    self.reload();
+    Ok(())
   }
 
   ///
-  /// Tick tock.
+  /// Turn a mod back on for this world, then reload the game with it.
   ///
-  /// Every time the game goes into the next main loop iteration
-  /// this is run.
+  /// Refuses if one of its dependencies is missing or disabled.
   ///
-  /// This is referred to as on_step in C++ minetest.
+  pub fn enable_mod(&mut self, mod_name: &str) -> Result<(), String> {
+    let mods = self.get_game_mods();
+    if !mods
+      .iter()
+      .any(|mod_directory| mod_directory.mod_name == mod_name)
+    {
+      return Err(format!("Server: game has no mod [{}].", mod_name));
+    }
+
+    let missing = self.world_meta.get_missing_dependencies(mod_name, &mods);
+    if !missing.is_empty() {
+      return Err(format!(
+        "Server: can't enable [{}], it depends on: [{}]",
+        mod_name,
+        missing.join(", ")
+      ));
+    }
+
+    self.world_meta.set_mod_enabled(mod_name, true)?;
+    println!("Server: enabled mod [{}].", mod_name);
+    self.reload();
+    Ok(())
+  }
+
   ///
-  /// Returns shutdown signal.
+  /// Get the world seed from world.mt.
   ///
-  pub fn on_tick(&mut self, delta: f64) {
-    // Process any incoming network traffic. (non blocking)
+  pub fn get_world_seed(&self) -> u64 {
+    self.world_meta.get_seed()
+  }
 
-    self.connection.receive();
+  ///
+  /// Get every node the mods registered.
+  ///
+  pub fn get_node_registry(&self) -> Rc<RefCell<NodeRegistry>> {
+    self.nodes.clone()
+  }
 
-    self.check_shutdown_requests();
-    if self.shutdown_approved {
+  ///
+  /// Get every craftitem the mods registered.
+  ///
+  pub fn get_item_registry(&self) -> Rc<RefCell<ItemRegistry>> {
+    self.items.clone()
+  }
+
+  ///
+  /// Get the inventory of every player that ever joined.
+  ///
+  pub fn get_inventories(&self) -> Rc<RefCell<Inventories>> {
+    self.inventories.clone()
+  }
+
+  ///
+  /// Get the time of day. [0.0 - 1.0]
+  ///
+  pub fn get_time_of_day(&self) -> f64 {
+    self.time_of_day.borrow().get()
+  }
+
+  ///
+  /// Jump to a time of day. The players hear about it next tick.
+  ///
+  pub fn set_time_of_day(&mut self, time: f64) {
+    self.time_of_day.borrow_mut().set(time);
+  }
+
+  fn get_time_of_day_message(&self) -> NetworkMessage {
+    let time_of_day = self.time_of_day.borrow();
+    NetworkMessage::TimeOfDay {
+      time_of_day: time_of_day.get(),
+      time_speed: time_of_day.get_speed(),
+    }
+  }
+
+  ///
+  /// Move the clock along.
+  ///
+  fn advance_time_of_day(&mut self, delta: TimeStep) {
+    self.time_of_day.borrow_mut().advance(delta);
+    self.time_send_timer += delta.as_secs_f64();
+  }
+
+  ///
+  /// Tell the players the time every TIME_SEND_INTERVAL.
+  ///
+  /// A time set since the last broadcast goes out right away.
+  ///
+  fn send_time_of_day(&mut self) {
+    let was_set = self.time_of_day.borrow_mut().take_was_set();
+    if was_set || self.time_send_timer >= TIME_SEND_INTERVAL {
+      self.time_send_timer = 0.0;
+      let time_of_day = self.get_time_of_day_message();
+      self.connection.broadcast(&time_of_day);
+    }
+  }
+
+  ///
+  /// Write out the inventories every INVENTORY_SAVE_INTERVAL, if they changed.
+  ///
+  fn save_changed_inventories(&mut self, delta: TimeStep) {
+    self.inventory_save_timer += delta.as_secs_f64();
+    if self.inventory_save_timer < INVENTORY_SAVE_INTERVAL {
       return;
     }
+    self.inventory_save_timer = 0.0;
+    if let Err(e) = self.inventories.borrow_mut().save_if_changed() {
+      println!("Server: failed to save the inventories. {}", e);
+    }
+  }
 
-    self.lua_engine.on_tick(delta);
+  ///
+  /// Let the mods decorate a freshly generated map area.
+  ///
+  /// Runs every minetest.register_on_generated callback with the area's
+  /// bounds and it's seed, so the same world always generates the same.
+  ///
+  /// todo: The map calls this once it generates areas.
+  ///
+  pub fn on_area_generated(&mut self, min: IVec3, max: IVec3) {
+    let seed = self.world_meta.get_area_seed(min);
+    if let Err(e) = self.lua_engine.on_generated(min, max, seed) {
+      println!("Server: {}", e);
+    }
   }
-}
 
-impl Drop for Server {
-  fn drop(&mut self) {
-    println!("Server dropped!");
+  ///
+  /// Check the game the Server loaded for missing textures and broken mods.
+  ///
+  pub fn check_assets(&self, report: &mut StartupReport) {
+    report.check_textures(&self.nodes.borrow(), &self.items.borrow());
+    report.add_mod_errors(self.lua_engine.get_mod_errors());
+  }
+
+  ///
+  /// Get where the world's map blocks are stored.
+  ///
+  pub fn get_map_backend(&mut self) -> &mut dyn MapBackend {
+    self.map_backend.as_mut()
+  }
+
+  ///
+  /// Get the server description from minetest.conf.
+  ///
+  pub fn get_server_description(&self) -> String {
+    self.server_description.clone()
+  }
+
+  ///
+  /// Get the message of the day from minetest.conf.
+  ///
+  pub fn get_motd(&self) -> String {
+    self.connection.get_motd()
+  }
+
+  ///
+  /// Get the names of every player that's connected.
+  ///
+  pub fn get_player_names(&self) -> Vec<String> {
+    self.connection.get_player_names()
+  }
+
+  ///
+  /// Get where a player last said they were, (position, rotation).
+  ///
+  pub fn get_player_position(&self, name: &str) -> Option<(Vec3A, Vec3A)> {
+    self.connection.get_player_position(name)
+  }
+
+  ///
+  /// Get the address the server actually bound to.
+  ///
+  pub fn get_real_address(&self) -> SocketAddr {
+    self.connection.get_real_address()
+  }
+
+  ///
+  /// Update the tick budget to match the game's TPS goal.
+  ///
+  pub fn set_goal_ticks_per_second(&mut self, goal_ticks_per_second: f64) {
+    self
+      .tick_budget
+      .set_goal_ticks_per_second(goal_ticks_per_second);
+    if self.network_broadcast_rate.is_none() {
+      self.broadcast_clock = BroadcastClock::new(goal_ticks_per_second);
+    }
+  }
+
+  ///
+  /// Set how often state goes out to the players, see Settings::network_broadcast_rate.
+  ///
+  /// Broadcasting slower than the simulation saves bandwidth. None follows the tick rate.
+  ///
+  pub fn set_network_broadcast_rate(&mut self, network_broadcast_rate: Option<f64>) {
+    self.network_broadcast_rate = network_broadcast_rate;
+    let broadcasts_per_second = match network_broadcast_rate {
+      Some(network_broadcast_rate) => network_broadcast_rate,
+      None => 1.0 / self.tick_budget.get_target_period().as_secs_f64(),
+    };
+    self.broadcast_clock = BroadcastClock::new(broadcasts_per_second);
+  }
+
+  ///
+  /// Get the time between two state broadcasts. [seconds]
+  ///
+  pub fn get_broadcast_period(&self) -> f64 {
+    self.broadcast_clock.get_period()
+  }
+
+  ///
+  /// Check if the server had nothing to do last tick.
+  ///
+  /// That's no network traffic and finishing well under the tick budget.
+  ///
+  pub fn is_idle(&self) -> bool {
+    !self.had_network_activity
+      && self.tick_budget.get_last_tick_duration() < self.tick_budget.get_target_period() / 2
+  }
+
+  ///
+  /// Start recording every incoming packet into a file, for Replay.
+  ///
+  pub fn start_recording(&mut self, path: &str) -> Result<(), String> {
+    self.connection.set_recorder(Recorder::create(path)?);
+    Ok(())
+  }
+
+  ///
+  /// Get how long the last tick took.
+  ///
+  pub fn get_last_tick_duration(&self) -> Duration {
+    self.tick_budget.get_last_tick_duration()
+  }
+
+  ///
+  /// Get the traffic with every player, (bytes in, bytes out) per second.
+  ///
+  pub fn get_bytes_per_second(&self) -> (f64, f64) {
+    self.connection.get_bytes_per_second()
+  }
+
+  ///
+  /// Get the time between packets from players, over every session.
+  ///
+  pub fn get_receive_gaps(&self) -> ReceiveHistogram {
+    self.connection.get_receive_gaps()
+  }
+
+  ///
+  /// Get how many ticks took longer than the target tick period.
+  ///
+  pub fn get_lagged_ticks(&self) -> u64 {
+    self.tick_budget.get_lagged_ticks()
+  }
+
+  ///
+  /// Kick a player off the server.
+  ///
+  /// Returns if the player was connected.
+  ///
+  pub fn kick(&mut self, name: &str, reason: &str) -> bool {
+    self.connection.kick(name, reason)
+  }
+
+  ///
+  /// Ban a player. They get kicked if they're online.
+  ///
+  /// This is saved into the world's banned.txt.
+  ///
+  pub fn ban(&mut self, name: &str, reason: &str) -> Result<(), String> {
+    self.connection.ban(name, reason)
+  }
+
+  ///
+  /// Lift a ban.
+  ///
+  /// Returns if the player was banned.
+  ///
+  pub fn unban(&mut self, name: &str) -> Result<bool, String> {
+    self.connection.unban(name)
+  }
+
+  ///
+  /// Give a player a privilege.
+  ///
+  /// Returns if they didn't already have it.
+  ///
+  pub fn grant(&mut self, name: &str, privilege: &str) -> Result<bool, String> {
+    self.privileges.borrow_mut().grant(name, privilege)
+  }
+
+  ///
+  /// Take a privilege away from a player.
+  ///
+  /// Returns if they had it.
+  ///
+  pub fn revoke(&mut self, name: &str, privilege: &str) -> Result<bool, String> {
+    let revoked = self.privileges.borrow_mut().revoke(name, privilege)?;
+    // Losing it mid flight lands them back where they started.
+    if revoked && privilege == "noclip" && self.connection.is_spectating(name) {
+      self.connection.set_spectating(name, false);
+    }
+    Ok(revoked)
+  }
+
+  ///
+  /// Check if a player has every one of these privileges.
+  ///
+  /// todo: digging and placing need to check interact once they exist.
+  ///
+  pub fn check_player_privs(&self, name: &str, privs: &[&str]) -> bool {
+    let privileges = self.privileges.borrow();
+    privs
+      .iter()
+      .all(|privilege| privileges.has(name, privilege))
+  }
+
+  ///
+  /// Check if whoever's running a command has every privilege it needs.
+  ///
+  fn can_run(&self, issuer: &Issuer, info: &ChatCommandInfo) -> bool {
+    match issuer {
+      Issuer::Console => true,
+      Issuer::Player(name) => self.check_player_privs(name, info.privs),
+    }
+  }
+
+  ///
+  /// Get the /help text for whoever's asking.
+  ///
+  /// Without a command this lists every command they can run.
+  ///
+  pub fn get_help(&self, issuer: &Issuer, command: Option<&str>) -> String {
+    match command {
+      Some(command) => match ChatCommand::get_info_by_name(command) {
+        Some(info) => info.get_details(),
+        None => format!("Unknown command [{}].", command),
+      },
+      None => {
+        let summaries: Vec<String> = CHAT_COMMANDS
+          .iter()
+          .filter(|info| self.can_run(issuer, info))
+          .map(|info| info.get_summary())
+          .collect();
+        format!("Available commands:\n{}", summaries.join("\n"))
+      }
+    }
+  }
+
+  ///
+  /// Get a human readable summary of the server.
+  ///
+  pub fn get_status(&self) -> String {
+    format!(
+      "Server [{}] | version [{}] | uptime [{:.0}s] | players [{}/{}] {:?} | lagged ticks [{}]",
+      self.connection.get_server_name(),
+      env!("CARGO_PKG_VERSION"),
+      self.get_uptime(),
+      self.connection.get_player_count(),
+      self.connection.get_max_players(),
+      self.connection.get_player_names(),
+      self.get_lagged_ticks()
+    )
+  }
+
+  ///
+  /// Get how long the Server has been up. [seconds]
+  ///
+  pub fn get_uptime(&self) -> f64 {
+    self.server_info.borrow().get_uptime()
+  }
+
+  ///
+  /// Hand the LuaEngine this tick's player list and status.
+  ///
+  fn refresh_server_info(&mut self) {
+    let status = self.get_status();
+    let mut server_info = self.server_info.borrow_mut();
+    server_info.set_player_names(self.connection.get_player_names());
+    server_info.set_status(status);
+  }
+
+  ///
+  /// Run an admin command.
+  ///
+  /// The server console and in-game chat commands both go through here.
+  ///
+  /// Returns the result as text for whoever ran it.
+  ///
+  pub fn run_chat_command(&mut self, issuer: &Issuer, line: &str) -> String {
+    let command = match ChatCommand::parse(line) {
+      Ok(command) => command,
+      Err(e) => return e,
+    };
+
+    let info = command.get_info();
+    if !self.can_run(issuer, info) {
+      println!(
+        "Server: [{}] was denied command {:?}",
+        issuer.get_name(),
+        command
+      );
+      return format!(
+        "You don't have permission to run [{}]. (requires privileges: {})",
+        info.name,
+        info.privs.join(", ")
+      );
+    }
+
+    println!("Server: [{}] ran command {:?}", issuer.get_name(), command);
+
+    match command {
+      ChatCommand::Help(command) => self.get_help(issuer, command.as_deref()),
+      ChatCommand::Status => self.get_status(),
+      ChatCommand::Kick { name, reason } => match self.kick(&name, &reason) {
+        true => format!("Kicked [{}].", name),
+        false => format!("Player [{}] is not connected.", name),
+      },
+      ChatCommand::Ban { name, reason } => match self.ban(&name, &reason) {
+        Ok(_) => format!("Banned [{}].", name),
+        Err(e) => e,
+      },
+      ChatCommand::Unban(name) => match self.unban(&name) {
+        Ok(true) => format!("Unbanned [{}].", name),
+        Ok(false) => format!("Player [{}] is not banned.", name),
+        Err(e) => e,
+      },
+      ChatCommand::Grant { name, privilege } => match self.grant(&name, &privilege) {
+        Ok(true) => format!("Granted [{}] to [{}].", privilege, name),
+        Ok(false) => format!("[{}] already has [{}].", name, privilege),
+        Err(e) => e,
+      },
+      ChatCommand::Teleport(target) => match self.teleport_issuer(issuer, &target) {
+        Ok(position) => format!(
+          "Teleported to ({}, {}, {}).",
+          position.x, position.y, position.z
+        ),
+        Err(e) => e,
+      },
+      ChatCommand::Revoke { name, privilege } => match self.revoke(&name, &privilege) {
+        Ok(true) => format!("Revoked [{}] from [{}].", privilege, name),
+        Ok(false) => format!("[{}] doesn't have [{}].", name, privilege),
+        Err(e) => e,
+      },
+      ChatCommand::Say(message) => {
+        let chat_message = format!("[{}] {}", issuer.get_name(), message);
+        self
+          .connection
+          .broadcast(&NetworkMessage::ChatMessage(chat_message.clone()));
+        chat_message
+      }
+      ChatCommand::Shutdown => {
+        self.shutdown_approved = true;
+        "Shutting down.".to_string()
+      }
+      ChatCommand::ScheduleShutdown(seconds) => {
+        self.schedule_shutdown(seconds);
+        format!("Shutting down in [{}] seconds.", seconds)
+      }
+      ChatCommand::CancelShutdown => match self.cancel_shutdown() {
+        true => "Shutdown cancelled.".to_string(),
+        false => "No shutdown is scheduled.".to_string(),
+      },
+      ChatCommand::ClearObjects => match self.start_maintenance("clear_entities") {
+        Ok(_) => "Clearing entities, see /maintenance for progress.".to_string(),
+        Err(e) => e,
+      },
+      ChatCommand::Maintenance => self.maintenance.get_status(),
+      ChatCommand::CancelMaintenance => match self.cancel_maintenance() {
+        Some(name) => format!("Cancelled maintenance [{}].", name),
+        None => "No maintenance is running.".to_string(),
+      },
+    }
+  }
+
+  ///
+  /// A player said something in chat.
+  ///
+  /// Lines starting with / are chat commands. Everything else gets
+  /// broadcast, if they have the shout privilege.
+  ///
+  /// Returns what only they should see, if anything.
+  ///
+  pub fn receive_chat_message(&mut self, name: &str, message: &str) -> Option<String> {
+    let message = message.trim();
+    if message.is_empty() {
+      return None;
+    }
+
+    self.events.push(EngineEvent::ChatReceived {
+      name: name.to_string(),
+      message: message.to_string(),
+    });
+
+    if message.starts_with('/') {
+      return Some(self.run_chat_command(&Issuer::player(name), message));
+    }
+
+    if !self.check_player_privs(name, &["shout"]) {
+      return Some("You don't have permission to shout.".to_string());
+    }
+
+    self
+      .connection
+      .broadcast(&NetworkMessage::ChatMessage(format!(
+        "<{}> {}",
+        name, message
+      )));
+    None
+  }
+
+  ///
+  /// Hand over the EngineEvents from this tick.
+  ///
+  pub fn take_events(&mut self) -> Vec<EngineEvent> {
+    std::mem::take(&mut self.events)
+  }
+
+  ///
+  /// Send out the sounds the mods played since the last broadcast.
+  ///
+  fn send_sounds(&mut self) {
+    let sounds = std::mem::take(&mut *self.sounds.borrow_mut());
+    for sound in sounds {
+      match &sound.to_player {
+        // A player that left already just misses it.
+        Some(name) => {
+          self.connection.send_to_player(name, &sound.to_message());
+        }
+        None => self.connection.broadcast(&sound.to_message()),
+      }
+    }
+  }
+
+  ///
+  /// Tell every player where the entities they can see are, see InterpolationBuffer.
+  ///
+  fn send_entity_positions(&mut self) {
+    for name in self.connection.get_player_names() {
+      if let Some(message) = self.entities.get_positions_message(&name) {
+        self.connection.send_to_player(&name, &message);
+      }
+    }
+  }
+
+  ///
+  /// Give a brand new player the starter kit, and save that they got it.
+  ///
+  /// Both are saved right away, a crash later on can't hand it out twice.
+  ///
+  fn give_starter_kit(&mut self, name: &str) {
+    let warnings = self.inventories.borrow_mut().give_starter_kit(
+      name,
+      &self.items.borrow(),
+      &self.nodes.borrow(),
+    );
+    for warning in warnings {
+      println!("Server: {}", warning);
+    }
+    if let Err(e) = self.inventories.borrow_mut().save() {
+      println!("Server: failed to save the inventories. {}", e);
+    }
+    if let Err(e) = self.joined_players.mark_joined(name) {
+      println!("Server: failed to save that [{}] joined. {}", name, e);
+    }
+  }
+
+  ///
+  /// Queue up everyone that joined this tick for the EventBus.
+  ///
+  fn check_joined_players(&mut self) {
+    for name in std::mem::take(&mut self.connection.joined_players) {
+      println!(
+        "Server: [{}] joined [{}].",
+        name,
+        self.connection.get_server_name()
+      );
+      self.inventories.borrow_mut().create_if_missing(&name);
+      if !self.joined_players.has_joined(&name) {
+        self.give_starter_kit(&name);
+      }
+      // They shouldn't see the sky jump a few seconds in.
+      let time_of_day = self.get_time_of_day_message();
+      self.connection.send_to_player(&name, &time_of_day);
+      if let Some(greeting) = self.connection.get_greeting(&name) {
+        self
+          .connection
+          .send_to_player(&name, &NetworkMessage::ChatMessage(greeting));
+      }
+
+      // Everything that's already here, then them to everyone else.
+      for message in self.entities.get_add_messages() {
+        self.connection.send_to_player(&name, &message);
+      }
+      let spawn_point = self.connection.get_world_limits().get_spawn_point();
+      let id = self.entities.add_player(&name, spawn_point);
+      if let Some(message) = self.entities.get_add_message(id) {
+        self.connection.broadcast_except(&name, &message);
+      }
+
+      self.events.push(EngineEvent::PlayerJoined(name));
+    }
+  }
+
+  ///
+  /// Despawn everyone that left or got kicked this tick.
+  ///
+  fn check_left_players(&mut self) {
+    for name in std::mem::take(&mut self.connection.left_players) {
+      if let Some(id) = self.entities.remove_player(&name) {
+        self
+          .connection
+          .broadcast(&NetworkMessage::RemoveEntity { id });
+      }
+    }
+  }
+
+  ///
+  /// Move the player entities to where their PlayerMove last put them.
+  ///
+  /// So whoever joins next sees them in the right place.
+  ///
+  fn sync_player_entities(&mut self) {
+    for name in self.connection.get_player_names() {
+      // A spectator's body stays where they left it.
+      if self.connection.is_spectating(&name) {
+        continue;
+      }
+      if let Some((position, _)) = self.connection.get_player_position(&name) {
+        self.entities.set_player_position(&name, position);
+      }
+    }
+  }
+
+  ///
+  /// Handle all the chat that came in from players this tick.
+  ///
+  fn check_chat_messages(&mut self) {
+    for (name, message) in std::mem::take(&mut self.connection.chat_messages) {
+      if let Some(reply) = self.receive_chat_message(&name, &message) {
+        self
+          .connection
+          .send_to_player(&name, &NetworkMessage::ChatMessage(reply));
+      }
+    }
+  }
+
+  ///
+  /// /teleport, whoever ran it is who goes.
+  ///
+  fn teleport_issuer(&mut self, issuer: &Issuer, target: &TeleportTarget) -> Result<Vec3A, String> {
+    match issuer {
+      Issuer::Console => Err("The console isn't a player, it can't teleport.".to_string()),
+      Issuer::Player(name) => self.teleport(name, target),
+    }
+  }
+
+  ///
+  /// Move a player to a position or to another player, and tell their client.
+  ///
+  /// Returns where they ended up, the border might have pulled them in.
+  ///
+  pub fn teleport(&mut self, name: &str, target: &TeleportTarget) -> Result<Vec3A, String> {
+    let position = match target {
+      TeleportTarget::Position(position) => *position,
+      TeleportTarget::Player(other) => match self.connection.get_player_position(other) {
+        Some((position, _)) => position,
+        None => return Err(format!("Player [{}] is not connected.", other)),
+      },
+    };
+    match self.connection.teleport_player(name, position) {
+      Some(position) => {
+        self.entities.set_player_position(name, position);
+        Ok(position)
+      }
+      None => Err(format!("Player [{}] is not connected.", name)),
+    }
+  }
+
+  ///
+  /// Teleport whoever the mods moved this tick.
+  ///
+  fn check_teleport_requests(&mut self) {
+    let requests = std::mem::take(&mut *self.teleport_requests.borrow_mut());
+    for (name, position) in requests {
+      if let Err(e) = self.teleport(&name, &TeleportTarget::Position(position)) {
+        println!("Server: {}", e);
+      }
+    }
+  }
+
+  ///
+  /// Let players start spectating if they have noclip, stopping is always fine.
+  ///
+  fn check_spectate_requests(&mut self) {
+    for (name, enabled) in std::mem::take(&mut self.connection.spectate_requests) {
+      if enabled && !self.check_player_privs(&name, &["noclip"]) {
+        self.connection.send_to_player(
+          &name,
+          &NetworkMessage::ChatMessage("You need the noclip privilege to spectate.".to_string()),
+        );
+        self.connection.set_spectating(&name, false);
+        continue;
+      }
+      self.connection.set_spectating(&name, enabled);
+    }
+  }
+
+  ///
+  /// Start a maintenance task by name, like clear_entities.
+  ///
+  /// Fails if there's no such task, or one is already running.
+  ///
+  pub fn start_maintenance(&mut self, name: &str) -> Result<(), String> {
+    match maintenance_task_from_name(name) {
+      Some(task) => self.maintenance.start(task),
+      None => Err(format!("Unknown maintenance [{}].", name)),
+    }
+  }
+
+  ///
+  /// Stop the running maintenance task. Returns it's name, if one was running.
+  ///
+  pub fn cancel_maintenance(&mut self) -> Option<&'static str> {
+    let name = self.maintenance.cancel()?;
+    if let Err(e) = self.lua_engine.on_maintenance_done(name, true) {
+      println!("Server: {}", e);
+    }
+    Some(name)
+  }
+
+  ///
+  /// Run the maintenance task one tick further, along with what Lua asked for.
+  ///
+  fn advance_maintenance(&mut self) {
+    let requests = std::mem::take(&mut *self.maintenance_requests.borrow_mut());
+    for request in requests {
+      match request {
+        MaintenanceRequest::Start(name) => {
+          if let Err(e) = self.start_maintenance(&name) {
+            println!("Server: {}", e);
+          }
+        }
+        MaintenanceRequest::Cancel => {
+          self.cancel_maintenance();
+        }
+      }
+    }
+
+    if !self.maintenance.is_running() {
+      return;
+    }
+    let mut context = MaintenanceContext {
+      entities: &mut self.entities,
+      removed_entities: vec![],
+    };
+    let finished = self.maintenance.advance(&mut context);
+    for id in context.removed_entities {
+      self
+        .connection
+        .broadcast(&NetworkMessage::RemoveEntity { id });
+    }
+    if let Some((name, _)) = finished {
+      if let Err(e) = self.lua_engine.on_maintenance_done(name, false) {
+        println!("Server: {}", e);
+      }
+    }
+  }
+
+  ///
+  /// Shut down after a countdown, warning the players along the way.
+  ///
+  /// This replaces any countdown that's already running.
+  ///
+  pub fn schedule_shutdown(&mut self, seconds: f64) {
+    let countdown = ShutdownCountdown::new(seconds);
+    println!("Server: {}", countdown.get_announcement());
+    if !countdown.is_expired() {
+      self
+        .connection
+        .broadcast(&NetworkMessage::ChatMessage(countdown.get_announcement()));
+    }
+    self.shutdown_countdown = Some(countdown);
+  }
+
+  ///
+  /// Stop a scheduled shutdown.
+  ///
+  /// Returns if there was one to stop.
+  ///
+  pub fn cancel_shutdown(&mut self) -> bool {
+    if self.shutdown_countdown.take().is_none() {
+      return false;
+    }
+    println!("Server: shutdown cancelled.");
+    self.connection.broadcast(&NetworkMessage::ChatMessage(
+      "Server shutdown cancelled.".to_string(),
+    ));
+    true
+  }
+
+  ///
+  /// Run the shutdown countdown forward, if there is one.
+  ///
+  /// Returns the warning that was broadcast.
+  ///
+  fn advance_shutdown_countdown(&mut self, delta: f64) -> Option<String> {
+    let countdown = self.shutdown_countdown.as_mut()?;
+
+    let warning = countdown.advance(delta);
+    if let Some(warning) = &warning {
+      println!("Server: {}", warning);
+      self
+        .connection
+        .broadcast(&NetworkMessage::ChatMessage(warning.clone()));
+    }
+
+    if countdown.is_expired() {
+      self.shutdown_countdown = None;
+      self.shutdown_approved = true;
+    }
+
+    warning
+  }
+
+  ///
+  /// Allows the game to check if the server has approved
+  /// a shutdown request from a client.
+  ///
+  /// A scheduled shutdown only counts once its countdown is over.
+  ///
+  pub fn shutdown_is_approved(&self) -> bool {
+    self.shutdown_approved
+  }
+
+  ///
+  /// Run the mods' minetest.register_on_shutdown callbacks.
+  ///
+  /// The Game calls this on the way out however the shutdown started,
+  /// and dropping the Server does too in case it didn't. Only the
+  /// first call does anything.
+  ///
+  pub fn on_shutdown(&mut self) {
+    if self.shutdown_callbacks_done {
+      return;
+    }
+    self.shutdown_callbacks_done = true;
+    println!("Server: running shutdown callbacks.");
+    if let Err(e) = self.lua_engine.on_shutdown() {
+      println!("Server: {}", e);
+    }
+  }
+
+  ///
+  /// Handle the players that asked for a shutdown.
+  ///
+  /// It's the same as running /shutdown, so it takes the server privilege.
+  /// Anyone without it is ignored.
+  ///
+  fn check_shutdown_requests(&mut self) {
+    for name in std::mem::take(&mut self.connection.shutdown_requests) {
+      let issuer = Issuer::Player(name.clone());
+      if !self.can_run(&issuer, ChatCommand::Shutdown.get_info()) {
+        println!(
+          "Server: ignored shutdown request from [{}], they don't have the server privilege.",
+          name
+        );
+        continue;
+      }
+      println!("Server: shutdown requested by [{}].", name);
+      self.events.push(EngineEvent::ShutdownRequested);
+      let reply = self.run_chat_command(&issuer, "shutdown");
+      self
+        .connection
+        .send_to_player(&name, &NetworkMessage::ChatMessage(reply));
+    }
+  }
+
+  ///
+  /// Tick tock.
+  ///
+  /// Every time the game goes into the next main loop iteration
+  /// this is run.
+  ///
+  /// This is referred to as on_step in C++ minetest.
+  ///
+  /// Returns shutdown signal.
+  ///
+  pub fn on_tick(&mut self, delta: TimeStep) {
+    let tick_start = Instant::now();
+
+    // Process any incoming network traffic. (non blocking)
+
+    self.had_network_activity = self.connection.receive() > 0;
+
+    self.sync_player_entities();
+    self.check_joined_players();
+    self.check_left_players();
+    self.refresh_server_info();
+    self.check_chat_messages();
+    self.check_spectate_requests();
+    self.check_shutdown_requests();
+    self.advance_shutdown_countdown(delta.as_secs_f64());
+    if self.shutdown_approved {
+      return;
+    }
+
+    self.lua_engine.on_tick(delta);
+    self.plugins.on_tick(delta);
+    self.check_teleport_requests();
+    self.advance_maintenance();
+    self.physics.step(&mut self.entities, delta.as_secs_f64());
+    self.advance_time_of_day(delta);
+    self.save_changed_inventories(delta);
+
+    // State piles up between broadcasts and goes out together.
+    if self.broadcast_clock.advance(delta) {
+      self.send_time_of_day();
+      self.send_sounds();
+      self.send_entity_positions();
+    }
+
+    // What this tick queued up goes out now, not next tick.
+    self.connection.flush();
+
+    self.tick_budget.record(tick_start.elapsed());
+  }
+}
+
+impl Drop for Server {
+  fn drop(&mut self) {
+    // Before anything is saved, the mods might still change it.
+    self.on_shutdown();
+    self.plugins.unload();
+    if let Err(e) = self.time_of_day.borrow().save(&self.world_path) {
+      println!("Server: failed to save the time of day. {}", e);
+    }
+    if let Err(e) = self.inventories.borrow_mut().save() {
+      println!("Server: failed to save the inventories. {}", e);
+    }
+    println!("Server dropped!");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs::remove_dir_all,
+    time::{Duration, Instant},
+  };
+
+  use glam::Vec3A;
+
+  use crate::{
+    file_utilities::{create_dir_all, test_dir, write_file_atomic},
+    game::{
+      event_bus::EngineEvent,
+      game_config::GameConfig,
+      game_init_error::GameInitError,
+      network_message::NetworkMessage,
+      server::{
+        inventory::{Inventories, ItemStack, PLAYER_MAIN_LIST},
+        item_registry::ItemDefinition,
+        Issuer, Server, INVENTORY_SAVE_INTERVAL,
+      },
+      test_client::TestClient,
+      time_step::TimeStep,
+    },
+  };
+
+  #[test]
+  fn test_server_chat_commands() {
+    println!("--- BEGIN SERVER CHAT COMMANDS TEST ---");
+    let world_path = test_dir("server_chat_commands");
+    let config = GameConfig::parse("server_name = Test\nmax_users = 4");
+    let mut server = Server::new(
+      "127.0.0.1".to_string(),
+      0,
+      "minetest".to_string(),
+      &world_path,
+      &config,
+      20.0,
+    );
+
+    // Broadcasts follow the tick rate, unless there's a network_broadcast_rate.
+    assert_eq!(server.get_broadcast_period(), 1.0 / 20.0);
+    server.set_network_broadcast_rate(Some(10.0));
+    server.set_goal_ticks_per_second(40.0);
+    assert_eq!(server.get_broadcast_period(), 1.0 / 10.0);
+    server.set_network_broadcast_rate(None);
+    assert_eq!(server.get_broadcast_period(), 1.0 / 40.0);
+
+    let status = server.run_chat_command(&Issuer::Console, "status");
+    assert!(status.contains("[Test]"));
+    assert!(status.contains("[0/4]"));
+
+    assert_eq!(
+      server.run_chat_command(&Issuer::Console, "kick nobody"),
+      "Player [nobody] is not connected."
+    );
+    assert_eq!(
+      server.run_chat_command(&Issuer::Console, "say hi"),
+      "[console] hi"
+    );
+    assert!(server
+      .run_chat_command(&Issuer::Console, "fly")
+      .contains("Unknown command"));
+
+    assert!(!server.shutdown_is_approved());
+    server.run_chat_command(&Issuer::Console, "shutdown");
+    assert!(server.shutdown_is_approved());
+
+    drop(server);
+    let _ = remove_dir_all(&world_path);
+  }
+
+  #[test]
+  fn test_server_help_and_privileges() {
+    println!("--- BEGIN SERVER HELP AND PRIVILEGES TEST ---");
+    let world_path = test_dir("server_help_and_privileges");
+
+    let config = GameConfig::parse("server_name = Test\n");
+    let mut server = Server::new(
+      "127.0.0.1".to_string(),
+      0,
+      "minetest".to_string(),
+      &world_path,
+      &config,
+      20.0,
+    );
+
+    // A new player only sees what the default privileges allow.
+    let help = server.run_chat_command(&Issuer::player("bob"), "/help");
+    assert!(help.contains("/say <message>"));
+    assert!(help.contains("/status"));
+    assert!(!help.contains("/kick"));
+    assert!(!help.contains("/shutdown"));
+
+    // The console sees everything.
+    let help = server.run_chat_command(&Issuer::Console, "help");
+    assert!(help.contains("/kick <name> [reason]"));
+    assert!(help.contains("/shutdown [seconds]"));
+
+    // Anyone can look up the details of a command.
+    assert!(server
+      .run_chat_command(&Issuer::player("bob"), "help shutdown")
+      .contains("requires privileges: server"));
+
+    // Denied, until it's granted.
+    let denied = server.run_chat_command(&Issuer::player("bob"), "/shutdown");
+    assert!(denied.contains("You don't have permission to run [shutdown]"));
+    assert!(!server.shutdown_is_approved());
+    assert!(server
+      .run_chat_command(&Issuer::player("bob"), "grant bob server")
+      .contains("You don't have permission"));
+
+    assert_eq!(
+      server.run_chat_command(&Issuer::Console, "grant bob server"),
+      "Granted [server] to [bob]."
+    );
+    assert!(server
+      .run_chat_command(&Issuer::player("bob"), "help")
+      .contains("/shutdown"));
+    server.run_chat_command(&Issuer::player("bob"), "/shutdown");
+    assert!(server.shutdown_is_approved());
+
+    // Chat needs shout.
+    server.take_events();
+    assert_eq!(server.receive_chat_message("bob", "hello"), None);
+    assert_eq!(
+      server.take_events(),
+      vec![EngineEvent::ChatReceived {
+        name: "bob".to_string(),
+        message: "hello".to_string()
+      }]
+    );
+    assert!(server
+      .run_chat_command(&Issuer::Console, "revoke bob shout")
+      .contains("Revoked"));
+    assert_eq!(
+      server.receive_chat_message("bob", "hello"),
+      Some("You don't have permission to shout.".to_string())
+    );
+    // Chat commands go through the same privilege checks.
+    assert!(server
+      .receive_chat_message("bob", "/kick alice")
+      .is_some_and(|reply| reply.contains("You don't have permission to run [kick]")));
+
+    drop(server);
+    let _ = remove_dir_all(&world_path);
+  }
+
+  #[test]
+  fn test_server_toggle_mods() {
+    println!("--- BEGIN SERVER TOGGLE MODS TEST ---");
+    let world_path = test_dir("server_toggle_mods");
+
+    let config = GameConfig::new();
+    let new_server = || {
+      Server::new(
+        "127.0.0.1".to_string(),
+        0,
+        "minetest".to_string(),
+        &world_path,
+        &config,
+        20.0,
+      )
+    };
+
+    let mut server = new_server();
+    let world_seed = server.get_world_seed();
+    assert_eq!(server.get_enabled_mods(), vec!["main", "not_main"]);
+    assert!(server.disable_mod("nonexistent").is_err());
+
+    if let Err(e) = server.disable_mod("main") {
+      panic!("Unit test is broken. {}", e);
+    }
+    assert_eq!(server.get_enabled_mods(), vec!["not_main"]);
+    assert!(!server
+      .lua_engine
+      .get_loaded_mods()
+      .contains(&"main".to_string()));
+    drop(server);
+
+    // Still off after a restart, and saving the mods kept the seed.
+    let mut server = new_server();
+    assert_eq!(server.get_world_seed(), world_seed);
+    assert_eq!(
+      server.lua_engine.get_loaded_mods().to_vec(),
+      vec!["not_main"]
+    );
+
+    if let Err(e) = server.enable_mod("main") {
+      panic!("Unit test is broken. {}", e);
+    }
+    assert!(server
+      .lua_engine
+      .get_loaded_mods()
+      .contains(&"main".to_string()));
+
+    drop(server);
+    let _ = remove_dir_all(&world_path);
+  }
+
+  #[test]
+  fn test_server_scheduled_shutdown() {
+    println!("--- BEGIN SERVER SCHEDULED SHUTDOWN TEST ---");
+    let world_path = test_dir("server_scheduled_shutdown");
+    let config = GameConfig::parse("server_name = Test\n");
+    let mut server = Server::new(
+      "127.0.0.1".to_string(),
+      0,
+      "minetest".to_string(),
+      &world_path,
+      &config,
+      20.0,
+    );
+
+    // Cancelling stops the countdown.
+    server.run_chat_command(&Issuer::Console, "shutdown 10");
+    assert!(server.cancel_shutdown());
+    assert!(!server.cancel_shutdown());
+    assert_eq!(server.advance_shutdown_countdown(20.0), None);
+    assert!(!server.shutdown_is_approved());
+
+    // Half second ticks.
+    server.schedule_shutdown(6.0);
+    let mut warnings = vec![];
+    for _ in 0..11 {
+      if let Some(warning) = server.advance_shutdown_countdown(0.5) {
+        warnings.push(warning);
+      }
+      assert!(!server.shutdown_is_approved());
+    }
+    assert_eq!(warnings.len(), 5);
+    assert_eq!(
+      warnings.last(),
+      Some(&"Server shutting down in 1s...".to_string())
+    );
+
+    // This is the tick that hits 0.
+    server.advance_shutdown_countdown(0.5);
+    assert!(server.shutdown_is_approved());
+
+    drop(server);
+    let _ = remove_dir_all(&world_path);
+  }
+
+  #[test]
+  fn test_server_shutdown_requests() {
+    println!("--- BEGIN SERVER SHUTDOWN REQUESTS TEST ---");
+    let world_path = test_dir("server_shutdown_requests");
+    let config = GameConfig::parse("server_name = Test\n");
+    let mut server = Server::new(
+      "127.0.0.1".to_string(),
+      0,
+      "minetest".to_string(),
+      &world_path,
+      &config,
+      20.0,
+    );
+
+    // Asking isn't enough.
+    server
+      .connection
+      .shutdown_requests
+      .push("player".to_string());
+    server.check_shutdown_requests();
+    assert!(!server.shutdown_is_approved());
+    assert!(server.connection.shutdown_requests.is_empty());
+
+    // It takes what /shutdown takes.
+    if let Err(e) = server.grant("player", "server") {
+      panic!("Unit test is broken. {}", e);
+    }
+    server
+      .connection
+      .shutdown_requests
+      .push("player".to_string());
+    server.check_shutdown_requests();
+    assert!(server.shutdown_is_approved());
+
+    drop(server);
+    let _ = remove_dir_all(&world_path);
+  }
+
+  #[test]
+  fn test_server_missing_game() {
+    println!("--- BEGIN SERVER MISSING GAME TEST ---");
+    let world_path = test_dir("server_missing_game");
+
+    let result = Server::try_new(
+      "127.0.0.1".to_string(),
+      0,
+      "not_a_real_game".to_string(),
+      &world_path,
+      &GameConfig::new(),
+      20.0,
+    );
+    match result {
+      Err(GameInitError::Game {
+        game,
+        path,
+        missing,
+      }) => {
+        assert_eq!(game, "not_a_real_game");
+        assert_eq!(path, "./games/not_a_real_game");
+        assert_eq!(missing, vec!["./games/not_a_real_game/"]);
+      }
+      Err(e) => panic!("Wrong error for a missing game. {}", e),
+      Ok(_) => panic!("A missing game started a Server."),
+    }
+
+    // A world made by minetest C++ doesn't open with an empty map.
+    if let Err(e) = create_dir_all(&world_path)
+      .and_then(|_| write_file_atomic(&format!("{}/world.mt", world_path), b"backend = sqlite3\n"))
+    {
+      panic!("Unit test is broken. {}", e);
+    }
+    let result = Server::try_new(
+      "127.0.0.1".to_string(),
+      0,
+      "minetest".to_string(),
+      &world_path,
+      &GameConfig::new(),
+      20.0,
+    );
+    match result {
+      Err(GameInitError::World(e)) => assert!(e.contains("[sqlite3]")),
+      Err(e) => panic!("Wrong error for an unknown backend. {}", e),
+      Ok(_) => panic!("An unknown backend started a Server."),
+    }
+
+    let _ = remove_dir_all(&world_path);
+  }
+
+  #[test]
+  fn test_server_on_shutdown() {
+    println!("--- BEGIN SERVER ON SHUTDOWN TEST ---");
+    let world_path = test_dir("server_on_shutdown");
+    let mut server = Server::new(
+      "127.0.0.1".to_string(),
+      0,
+      "minetest".to_string(),
+      &world_path,
+      &GameConfig::new(),
+      20.0,
+    );
+
+    // The broken mod goes first, the other one still gets to save.
+    let broken = "minetest.register_on_shutdown(function() error('disk full') end)";
+    let working = "minetest.register_on_shutdown(function() minetest.sound_play('saved') end)";
+    for (mod_name, code) in [("broken", broken), ("working", working)] {
+      if let Err(e) = server.lua_engine.run_mod_code(mod_name, mod_name, code) {
+        panic!("Unit test is broken. {}", e);
+      }
+    }
+
+    server.run_chat_command(&Issuer::Console, "shutdown");
+    assert!(server.shutdown_is_approved());
+    assert!(server.sounds.borrow().is_empty());
+
+    // The Game calls it, then dropping the Server would call it again.
+    server.on_shutdown();
+    server.on_shutdown();
+    let sounds: Vec<String> = server
+      .sounds
+      .borrow()
+      .iter()
+      .map(|sound| sound.name.clone())
+      .collect();
+    assert_eq!(sounds, vec!["saved"]);
+
+    drop(server);
+    let _ = remove_dir_all(&world_path);
+  }
+
+  #[test]
+  fn test_server_teleport() {
+    println!("--- BEGIN SERVER TELEPORT TEST ---");
+    let world_path = test_dir("server_teleport");
+    let mut server = Server::new(
+      "127.0.0.1".to_string(),
+      0,
+      "minetest".to_string(),
+      &world_path,
+      &GameConfig::parse("mapgen_limit = 1000"),
+      20.0,
+    );
+    let tick = TimeStep::from_secs_f64(0.05);
+
+    let mut alice = TestClient::new(server.get_real_address());
+    let mut bob = TestClient::new(server.get_real_address());
+    for (name, player) in [("alice", &mut alice), ("bob", &mut bob)] {
+      player.send_handshake(name);
+      assert_eq!(
+        player.wait_for_reply(|| server.on_tick(tick)),
+        Some(NetworkMessage::HandShakeConfirmed)
+      );
+    }
+    let is_move = |message: &NetworkMessage| matches!(message, NetworkMessage::MovePlayer { .. });
+    // Neither of them sent a PlayerMove, nothing to acknowledge.
+    let moved = |position: Vec3A| {
+      Some(NetworkMessage::MovePlayer {
+        position,
+        acknowledged: 0,
+      })
+    };
+    // Everyone starts at spawn.
+    for player in [&mut alice, &mut bob] {
+      assert_eq!(
+        player.wait_for_message(|| server.on_tick(tick), is_move),
+        moved(Vec3A::ZERO)
+      );
+    }
+
+    // Nobody can teleport without the privilege.
+    assert!(server
+      .run_chat_command(&Issuer::player("alice"), "/teleport 1 2 3")
+      .contains("You don't have permission"));
+    server.run_chat_command(&Issuer::Console, "grant alice teleport");
+
+    // To coordinates, and the client gets told where it is now.
+    assert_eq!(
+      server.run_chat_command(&Issuer::player("alice"), "/teleport 10 20 30"),
+      "Teleported to (10, 20, 30)."
+    );
+    assert_eq!(
+      alice.wait_for_message(|| server.on_tick(tick), is_move),
+      moved(Vec3A::new(10.0, 20.0, 30.0))
+    );
+
+    // To another player, wherever the mods put them.
+    let code = "minetest.get_player_by_name('bob'):set_pos(vector.new(-5, 6, 2000))";
+    if let Err(e) = server.lua_engine.run_mod_code("mover", "mover", code) {
+      panic!("Unit test is broken. {}", e);
+    }
+    // The border holds for mods too.
+    assert_eq!(
+      bob.wait_for_message(|| server.on_tick(tick), is_move),
+      moved(Vec3A::new(-5.0, 6.0, 1000.0))
+    );
+    assert_eq!(
+      server.run_chat_command(&Issuer::player("alice"), "/teleport bob"),
+      "Teleported to (-5, 6, 1000)."
+    );
+    assert_eq!(
+      alice.wait_for_message(|| server.on_tick(tick), is_move),
+      moved(Vec3A::new(-5.0, 6.0, 1000.0))
+    );
+
+    // Nonsense gets told off.
+    assert_eq!(
+      server.run_chat_command(&Issuer::player("alice"), "/teleport nobody"),
+      "Player [nobody] is not connected."
+    );
+    assert!(server
+      .run_chat_command(&Issuer::player("alice"), "/teleport 1 up 3")
+      .starts_with("Invalid coordinates"));
+    assert!(server
+      .run_chat_command(&Issuer::Console, "teleport 1 2 3")
+      .contains("isn't a player"));
+
+    drop(server);
+    let _ = remove_dir_all(&world_path);
+  }
+
+  #[test]
+  fn test_server_spectator_speed_limit() {
+    println!("--- BEGIN SERVER SPECTATOR SPEED LIMIT TEST ---");
+    let world_path = test_dir("server_spectator_speed_limit");
+    let mut server = Server::new(
+      "127.0.0.1".to_string(),
+      0,
+      "minetest".to_string(),
+      &world_path,
+      &GameConfig::parse("movement_speed_limit = 10"),
+      20.0,
+    );
+    let tick = TimeStep::from_secs_f64(0.05);
+
+    let mut alice = TestClient::new(server.get_real_address());
+    let mut bob = TestClient::new(server.get_real_address());
+    for (name, player) in [("alice", &mut alice), ("bob", &mut bob)] {
+      player.send_handshake(name);
+      assert_eq!(
+        player.wait_for_reply(|| server.on_tick(tick)),
+        Some(NetworkMessage::HandShakeConfirmed)
+      );
+    }
+    let is_move = |message: &NetworkMessage| matches!(message, NetworkMessage::MovePlayer { .. });
+    let is_spectate = |message: &NetworkMessage| matches!(message, NetworkMessage::Spectate { .. });
+    for player in [&mut alice, &mut bob] {
+      player.wait_for_message(|| server.on_tick(tick), is_move);
+    }
+
+    // Only alice may spectate.
+    server.run_chat_command(&Issuer::Console, "grant alice noclip");
+    for player in [&mut alice, &mut bob] {
+      player.send(&NetworkMessage::Spectate { enabled: true });
+    }
+    assert_eq!(
+      alice.wait_for_message(|| server.on_tick(tick), is_spectate),
+      Some(NetworkMessage::Spectate { enabled: true })
+    );
+    assert_eq!(
+      bob.wait_for_message(|| server.on_tick(tick), is_spectate),
+      Some(NetworkMessage::Spectate { enabled: false })
+    );
+    assert!(server.connection.is_spectating("alice"));
+    assert!(!server.connection.is_spectating("bob"));
+
+    // Both try to cross the map at once.
+    let far = Vec3A::new(500.0, 0.0, 0.0);
+    for player in [&mut alice, &mut bob] {
+      player.send(&NetworkMessage::PlayerMove {
+        position: far,
+        rotation: Vec3A::ZERO,
+      });
+    }
+
+    // bob only gets as far as the speed limit allows, and is told so.
+    let position = match bob.wait_for_message(|| server.on_tick(tick), is_move) {
+      Some(NetworkMessage::MovePlayer { position, .. }) => position,
+      message => panic!("bob's move wasn't held to the speed limit. {:?}", message),
+    };
+    assert!(position.x > 0.0 && position.x <= 11.0, "{:?}", position);
+    assert_eq!(position.y, 0.0);
+
+    // alice isn't held back, but her body stays behind.
+    let start = Instant::now();
+    while server
+      .connection
+      .get_player_position("alice")
+      .map(|(position, _)| position)
+      != Some(far)
+    {
+      server.on_tick(tick);
+      assert!(
+        start.elapsed() < Duration::from_secs(2),
+        "alice's move never went through."
+      );
+    }
+    let body = server
+      .entities
+      .get_player_entity("alice")
+      .and_then(|id| server.entities.get(id))
+      .map(|entity| entity.position);
+    assert_eq!(body, Some(Vec3A::ZERO));
+
+    // Stopping puts her back where she started, after the move she sent.
+    alice.send(&NetworkMessage::Spectate { enabled: false });
+    match alice.wait_for_message(|| server.on_tick(tick), is_move) {
+      Some(NetworkMessage::MovePlayer {
+        position,
+        acknowledged,
+      }) => {
+        assert_eq!(position, Vec3A::ZERO);
+        assert!(acknowledged > 0);
+      }
+      message => panic!("alice didn't get put back. {:?}", message),
+    }
+    assert!(!server.connection.is_spectating("alice"));
+
+    drop(server);
+    let _ = remove_dir_all(&world_path);
+  }
+
+  #[test]
+  fn test_server_motd_greeting() {
+    println!("--- BEGIN SERVER MOTD GREETING TEST ---");
+    let world_path = test_dir("server_motd_greeting");
+    let mut server = Server::new(
+      "127.0.0.1".to_string(),
+      0,
+      "minetest".to_string(),
+      &world_path,
+      &GameConfig::parse(
+        "server_name = Test\nmax_users = 4\nmotd = Hi {name}!\\n{players}/{max_players} on {server_name}",
+      ),
+      20.0,
+    );
+    let tick = TimeStep::from_secs_f64(0.05);
+
+    let mut alice = TestClient::new(server.get_real_address());
+    alice.send_handshake("alice");
+    assert_eq!(
+      alice.wait_for_reply(|| server.on_tick(tick)),
+      Some(NetworkMessage::HandShakeConfirmed)
+    );
+    let is_chat = |message: &NetworkMessage| matches!(message, NetworkMessage::ChatMessage(_));
+    assert_eq!(
+      alice.wait_for_message(|| server.on_tick(tick), is_chat),
+      Some(NetworkMessage::ChatMessage(
+        "Hi alice!\n1/4 on Test".to_string()
+      ))
+    );
+
+    // Server browsers still get it as written.
+    assert_eq!(
+      server.get_motd(),
+      "Hi {name}!\n{players}/{max_players} on {server_name}"
+    );
+
+    drop(server);
+    let _ = remove_dir_all(&world_path);
+  }
+
+  #[test]
+  fn test_server_starter_kit() {
+    println!("--- BEGIN SERVER STARTER KIT TEST ---");
+    let world_path = test_dir("server_starter_kit");
+    let config =
+      GameConfig::parse("initial_stuff = food:apple 3, rocks:stone, nope:missing, food:pear three");
+    let tick = TimeStep::from_secs_f64(0.05);
+    let is_move = |message: &NetworkMessage| matches!(message, NetworkMessage::MovePlayer { .. });
+    let start_server = || {
+      let server = Server::new(
+        "127.0.0.1".to_string(),
+        0,
+        "minetest".to_string(),
+        &world_path,
+        &config,
+        20.0,
+      );
+      for name in ["food:apple", "rocks:stone"] {
+        if let Err(e) = server
+          .items
+          .borrow_mut()
+          .register(ItemDefinition::new(name))
+        {
+          panic!("Unit test is broken. {}", e);
+        }
+      }
+      server
+    };
+    let count =
+      |server: &Server, name: &str, item: &str| match server.inventories.borrow().get(name) {
+        Some(inventory) => inventory.count_item(PLAYER_MAIN_LIST, item),
+        None => panic!("[{}] has no inventory.", name),
+      };
+
+    // First time here, she gets the kit. The broken and unknown entries are left out.
+    let mut server = start_server();
+    let mut alice = TestClient::new(server.get_real_address());
+    alice.send_handshake("alice");
+    if alice
+      .wait_for_message(|| server.on_tick(tick), is_move)
+      .is_none()
+    {
+      panic!("Unit test is broken. alice never spawned.");
+    }
+    assert_eq!(count(&server, "alice", "food:apple"), 3);
+    assert_eq!(count(&server, "alice", "rocks:stone"), 1);
+    assert_eq!(count(&server, "alice", "nope:missing"), 0);
+    // On disk already, a crash now wouldn't give it to her again.
+    match Inventories::load(&world_path).get("alice") {
+      Some(inventory) => assert_eq!(inventory.count_item(PLAYER_MAIN_LIST, "food:apple"), 3),
+      None => panic!("alice's kit wasn't saved."),
+    }
+
+    // She eats the apples and comes back after a restart, no new ones.
+    if let Some(inventory) = server.inventories.borrow_mut().get_mut("alice") {
+      let _ = inventory.remove_item(PLAYER_MAIN_LIST, &ItemStack::new("food:apple", 3));
+    }
+    // That goes to disk by itself, not only when the server stops.
+    server.on_tick(TimeStep::from_secs_f64(INVENTORY_SAVE_INTERVAL));
+    match Inventories::load(&world_path).get("alice") {
+      Some(inventory) => assert_eq!(inventory.count_item(PLAYER_MAIN_LIST, "food:apple"), 0),
+      None => panic!("alice's inventory wasn't saved."),
+    }
+    drop(alice);
+    drop(server);
+
+    let mut server = start_server();
+    let mut alice = TestClient::new(server.get_real_address());
+    alice.send_handshake("alice");
+    let mut bob = TestClient::new(server.get_real_address());
+    bob.send_handshake("bob");
+    for player in [&mut alice, &mut bob] {
+      if player
+        .wait_for_message(|| server.on_tick(tick), is_move)
+        .is_none()
+      {
+        panic!("Unit test is broken. A player never spawned.");
+      }
+    }
+    assert_eq!(count(&server, "alice", "food:apple"), 0);
+    assert_eq!(count(&server, "alice", "rocks:stone"), 1);
+    // Bob's new though.
+    assert_eq!(count(&server, "bob", "food:apple"), 3);
+    drop(alice);
+    drop(bob);
+    drop(server);
+
+    // A world from before anyone got a kit, carol was here but has no inventory.
+    let _ = remove_dir_all(&world_path);
+    if let Err(e) = create_dir_all(&world_path)
+      .and_then(|_| write_file_atomic(&format!("{}/privileges.txt", world_path), b"carol|shout\n"))
+    {
+      panic!("Unit test is broken. {}", e);
+    }
+    let mut server = start_server();
+    let mut carol = TestClient::new(server.get_real_address());
+    carol.send_handshake("carol");
+    if carol
+      .wait_for_message(|| server.on_tick(tick), is_move)
+      .is_none()
+    {
+      panic!("Unit test is broken. carol never spawned.");
+    }
+    assert_eq!(count(&server, "carol", "food:apple"), 0);
+
+    drop(server);
+    let _ = remove_dir_all(&world_path);
   }
 }
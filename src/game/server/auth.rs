@@ -0,0 +1,434 @@
+use std::time::{Duration, Instant};
+
+use ahash::AHashMap;
+use argon2::{
+  password_hash::{phc::PasswordHash, PasswordHasher, PasswordVerifier},
+  Argon2,
+};
+use sha1::{Digest, Sha1};
+
+use crate::{
+  file_utilities::{create_dir_all, file_exists, read_file_to_string, write_file_atomic},
+  game::game_config::GameConfig,
+};
+
+///
+/// What an AuthProvider decided about a login.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthResult {
+  Accepted,
+  // First join, the password was saved for next time.
+  Registered,
+  Rejected(String),
+  // Not a wrong password, the server can't check one right now.
+  Busy(String),
+}
+
+///
+/// Decides who gets to finish the handshake.
+///
+/// The ServerConnection asks this for every handshake that isn't banned.
+/// Implement it to plug in other auth, like an external account service.
+///
+pub trait AuthProvider {
+  fn authenticate(&mut self, name: &str, password: &str) -> AuthResult;
+}
+
+///
+/// Pick the AuthProvider from minetest.conf.
+///
+/// auth_backend = none | password (default none)
+/// auth_allow_registration = true | false (default true)
+///
+pub fn auth_provider_from_config(config: &GameConfig, world_path: &str) -> Box<dyn AuthProvider> {
+  match config.get_string("auth_backend", "none").as_str() {
+    "password" => Box::new(PasswordAuth::load(
+      world_path,
+      config.get_parsed("auth_allow_registration", true),
+    )),
+    "none" => Box::new(NoAuth),
+    unknown => {
+      println!("Auth: unknown auth_backend [{}], using none.", unknown);
+      Box::new(NoAuth)
+    }
+  }
+}
+
+///
+/// Everyone gets in. Fine for singleplayer and LAN.
+///
+pub struct NoAuth;
+
+impl AuthProvider for NoAuth {
+  fn authenticate(&mut self, _name: &str, _password: &str) -> AuthResult {
+    AuthResult::Accepted
+  }
+}
+
+///
+/// Check that a name can go into a name|value line without making another one.
+///
+fn is_record_safe(name: &str) -> bool {
+  !name.contains(['|', '\n', '\r'])
+}
+
+///
+/// How many times argon2 may run a second, for all players together.
+///
+/// Each run stalls the tick. The limits in the ServerConnection go by
+/// address, and UDP addresses can be spoofed, so this one doesn't.
+///
+pub const PASSWORD_HASHES_PER_SECOND: u32 = 8;
+const PASSWORD_HASH_WINDOW: Duration = Duration::from_secs(1);
+
+///
+/// Players log in with a password.
+///
+/// Stored in auth.txt in the world directory, one player per line:
+/// name|argon2 PHC hash
+///
+/// Players without a line can register by joining with a password,
+/// if registration is allowed.
+///
+/// Argon2 is slow on purpose and this runs on the tick thread, so a
+/// password that checked out is remembered. Rejoining doesn't pay for it again.
+/// Past PASSWORD_HASHES_PER_SECOND the rest are turned away as busy.
+///
+pub struct PasswordAuth {
+  world_path: String,
+  allow_registration: bool,
+  hashes: AHashMap<String, String>,
+  verified: AHashMap<String, Vec<u8>>,
+  hash_window_start: Instant,
+  hashes_in_window: u32,
+}
+
+impl PasswordAuth {
+  ///
+  /// Load the passwords of a world. A missing auth.txt means nobody
+  /// has registered yet.
+  ///
+  pub fn load(world_path: &str, allow_registration: bool) -> Self {
+    let mut new_password_auth = PasswordAuth {
+      world_path: world_path.to_owned(),
+      allow_registration,
+      hashes: AHashMap::new(),
+      verified: AHashMap::new(),
+      hash_window_start: Instant::now(),
+      hashes_in_window: 0,
+    };
+
+    let path = new_password_auth.get_path();
+    if !file_exists(&path) {
+      return new_password_auth;
+    }
+
+    let raw_hashes = match read_file_to_string(&path) {
+      Ok(raw_hashes) => raw_hashes,
+      Err(e) => panic!("PasswordAuth: {}", e),
+    };
+
+    for line in raw_hashes.lines() {
+      match line.split_once('|') {
+        Some((name, hash)) if !name.trim().is_empty() && !hash.trim().is_empty() => {
+          new_password_auth
+            .hashes
+            .insert(name.trim().to_string(), hash.trim().to_string());
+        }
+        _ if line.trim().is_empty() => (),
+        _ => println!("PasswordAuth: ignoring malformed line [{}].", line),
+      }
+    }
+
+    new_password_auth
+  }
+
+  ///
+  /// Get the path to auth.txt.
+  ///
+  fn get_path(&self) -> String {
+    let mut path = self.world_path.clone();
+    path.push_str("/auth.txt");
+    path
+  }
+
+  ///
+  /// Write the passwords out to disk.
+  ///
+  fn save(&self) -> Result<(), String> {
+    create_dir_all(&self.world_path)?;
+
+    // Sorted so the file doesn't shuffle around on every save.
+    let mut names: Vec<&String> = self.hashes.keys().collect();
+    names.sort();
+
+    let mut raw_hashes = String::new();
+    for name in names {
+      // One of these would forge another line, it's never written.
+      if !is_record_safe(name) {
+        println!(
+          "PasswordAuth: not saving [{}], it has a | or a newline.",
+          name.escape_debug()
+        );
+        continue;
+      }
+      if let Some(hash) = self.hashes.get(name) {
+        raw_hashes.push_str(&format!("{}|{}\n", name, hash));
+      }
+    }
+
+    write_file_atomic(&self.get_path(), raw_hashes.as_bytes())
+  }
+
+  ///
+  /// Get everyone that has registered.
+  ///
+  pub fn get_names(&self) -> Vec<String> {
+    self.hashes.keys().cloned().collect()
+  }
+
+  ///
+  /// Check if a player has registered.
+  ///
+  pub fn is_registered(&self, name: &str) -> bool {
+    self.hashes.contains_key(name)
+  }
+
+  ///
+  /// Take one argon2 run out of this second's budget.
+  ///
+  /// Returns false if it's used up.
+  ///
+  fn spend_hash_at(&mut self, now: Instant) -> bool {
+    if now.duration_since(self.hash_window_start) >= PASSWORD_HASH_WINDOW {
+      self.hash_window_start = now;
+      self.hashes_in_window = 0;
+    }
+    if self.hashes_in_window >= PASSWORD_HASHES_PER_SECOND {
+      return false;
+    }
+    self.hashes_in_window += 1;
+    true
+  }
+
+  ///
+  /// Hash a password with a fresh salt.
+  ///
+  fn hash(password: &str) -> Result<String, String> {
+    match Argon2::default().hash_password(password.as_bytes()) {
+      Ok(hash) => Ok(hash.to_string()),
+      Err(e) => Err(format!("Failed to hash password. {}", e)),
+    }
+  }
+
+  ///
+  /// Check a password against a stored hash.
+  ///
+  fn verify(password: &str, hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+      Ok(hash) => Argon2::default()
+        .verify_password(password.as_bytes(), &hash)
+        .is_ok(),
+      Err(e) => {
+        println!("PasswordAuth: stored hash is broken. {}", e);
+        false
+      }
+    }
+  }
+
+  ///
+  /// A cheap fingerprint of a password, tied to the hash it was checked against.
+  ///
+  /// Only ever kept in memory, a new hash makes the old fingerprint useless.
+  ///
+  fn get_fingerprint(password: &str, hash: &str) -> Vec<u8> {
+    let mut hasher = Sha1::new();
+    hasher.update(hash.as_bytes());
+    hasher.update([0]);
+    hasher.update(password.as_bytes());
+    hasher.finalize().to_vec()
+  }
+
+  ///
+  /// Check a player's password, skipping argon2 if it checked out before.
+  ///
+  fn check_password(&mut self, name: &str, password: &str, hash: &str) -> AuthResult {
+    let fingerprint = Self::get_fingerprint(password, hash);
+    if self.verified.get(name) == Some(&fingerprint) {
+      return AuthResult::Accepted;
+    }
+    if !self.spend_hash_at(Instant::now()) {
+      return AuthResult::Busy("The server is busy, try again.".to_string());
+    }
+    match Self::verify(password, hash) {
+      true => {
+        self.verified.insert(name.to_string(), fingerprint);
+        AuthResult::Accepted
+      }
+      false => AuthResult::Rejected("Wrong password.".to_string()),
+    }
+  }
+
+  ///
+  /// Save a password for a new player.
+  ///
+  fn register(&mut self, name: &str, password: &str) -> AuthResult {
+    if !is_record_safe(name) {
+      return AuthResult::Rejected("That name can't be registered.".to_string());
+    }
+    if password.is_empty() {
+      return AuthResult::Rejected("Join with a password to register.".to_string());
+    }
+    if !self.spend_hash_at(Instant::now()) {
+      return AuthResult::Busy("The server is busy, try again.".to_string());
+    }
+
+    let hash = match Self::hash(password) {
+      Ok(hash) => hash,
+      Err(e) => return AuthResult::Rejected(e),
+    };
+    self.hashes.insert(name.to_string(), hash);
+
+    if let Err(e) = self.save() {
+      // Don't let them in with a password that will be forgotten.
+      self.hashes.remove(name);
+      println!("PasswordAuth: {}", e);
+      return AuthResult::Rejected("The server failed to save your password.".to_string());
+    }
+
+    println!("PasswordAuth: registered [{}].", name);
+    AuthResult::Registered
+  }
+}
+
+impl AuthProvider for PasswordAuth {
+  fn authenticate(&mut self, name: &str, password: &str) -> AuthResult {
+    match self.hashes.get(name).cloned() {
+      Some(hash) => self.check_password(name, password, &hash),
+      None if self.allow_registration => self.register(name, password),
+      None => AuthResult::Rejected("This server does not allow registration.".to_string()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs::remove_dir_all,
+    time::{Duration, Instant},
+  };
+
+  use crate::{
+    file_utilities::test_dir,
+    game::server::auth::{
+      AuthProvider, AuthResult, NoAuth, PasswordAuth, PASSWORD_HASHES_PER_SECOND,
+    },
+  };
+
+  #[test]
+  fn test_password_auth() {
+    println!("--- BEGIN PASSWORD AUTH TEST ---");
+    let world_path = test_dir("password_auth");
+
+    assert_eq!(NoAuth.authenticate("anyone", ""), AuthResult::Accepted);
+
+    // First time registration.
+    let mut password_auth = PasswordAuth::load(&world_path, true);
+    assert!(matches!(
+      password_auth.authenticate("alice", ""),
+      AuthResult::Rejected(_)
+    ));
+    assert_eq!(
+      password_auth.authenticate("alice", "hunter2"),
+      AuthResult::Registered
+    );
+
+    // Correct and wrong passwords, after a reload.
+    let mut password_auth = PasswordAuth::load(&world_path, false);
+    assert!(password_auth.is_registered("alice"));
+    assert_eq!(
+      password_auth.authenticate("alice", "hunter2"),
+      AuthResult::Accepted
+    );
+    assert!(matches!(
+      password_auth.authenticate("alice", "hunter3"),
+      AuthResult::Rejected(_)
+    ));
+
+    // Remembering a good password doesn't let a wrong one through.
+    assert_eq!(
+      password_auth.authenticate("alice", "hunter2"),
+      AuthResult::Accepted
+    );
+    assert!(matches!(
+      password_auth.authenticate("alice", "hunter3"),
+      AuthResult::Rejected(_)
+    ));
+
+    // Registration is off now.
+    assert!(matches!(
+      password_auth.authenticate("bob", "hunter2"),
+      AuthResult::Rejected(_)
+    ));
+    assert!(!password_auth.is_registered("bob"));
+
+    // A name can't write a line of its own into auth.txt.
+    let mut password_auth = PasswordAuth::load(&world_path, true);
+    assert!(matches!(
+      password_auth.authenticate("mallory|x\nalice", "hunter2"),
+      AuthResult::Rejected(_)
+    ));
+    let password_auth = PasswordAuth::load(&world_path, false);
+    assert!(password_auth.is_registered("alice"));
+    assert!(!password_auth.is_registered("mallory"));
+
+    let _ = remove_dir_all(&world_path);
+  }
+
+  #[test]
+  fn test_password_auth_busy() {
+    println!("--- BEGIN PASSWORD AUTH BUSY TEST ---");
+    let world_path = test_dir("password_auth_busy");
+
+    let mut password_auth = PasswordAuth::load(&world_path, true);
+    assert_eq!(
+      password_auth.authenticate("alice", "hunter2"),
+      AuthResult::Registered
+    );
+    assert_eq!(
+      password_auth.authenticate("alice", "hunter2"),
+      AuthResult::Accepted
+    );
+
+    // Someone used up this second's argon2 runs.
+    let now = Instant::now();
+    while password_auth.spend_hash_at(now) {}
+
+    // Guesses wait, and so do new players.
+    assert!(matches!(
+      password_auth.authenticate("alice", "guess"),
+      AuthResult::Busy(_)
+    ));
+    assert!(matches!(
+      password_auth.authenticate("bob", "hunter2"),
+      AuthResult::Busy(_)
+    ));
+    assert!(!password_auth.is_registered("bob"));
+
+    // A remembered password doesn't need argon2.
+    assert_eq!(
+      password_auth.authenticate("alice", "hunter2"),
+      AuthResult::Accepted
+    );
+
+    // The next second gets a fresh budget.
+    let later = now + Duration::from_secs(1);
+    for _ in 0..PASSWORD_HASHES_PER_SECOND {
+      assert!(password_auth.spend_hash_at(later));
+    }
+    assert!(!password_auth.spend_hash_at(later));
+
+    let _ = remove_dir_all(&world_path);
+  }
+}
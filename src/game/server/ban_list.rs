@@ -0,0 +1,208 @@
+use std::net::IpAddr;
+
+use crate::file_utilities::{create_dir_all, file_exists, read_file_to_string, write_file_atomic};
+
+///
+/// A single ban.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ban {
+  pub name: String,
+  pub address: Option<IpAddr>,
+  pub reason: String,
+}
+
+///
+/// The list of banned players for a world.
+///
+/// Stored in banned.txt in the world directory, one ban per line:
+/// name|address|reason
+///
+/// The address can be empty if the player was banned while offline.
+///
+pub struct BanList {
+  world_path: String,
+  bans: Vec<Ban>,
+}
+
+impl BanList {
+  ///
+  /// Load the ban list of a world. A missing banned.txt is an empty list.
+  ///
+  pub fn load(world_path: &str) -> Self {
+    let mut new_ban_list = BanList {
+      world_path: world_path.to_owned(),
+      bans: vec![],
+    };
+
+    let path = new_ban_list.get_path();
+    if !file_exists(&path) {
+      return new_ban_list;
+    }
+
+    let raw_bans = match read_file_to_string(&path) {
+      Ok(raw_bans) => raw_bans,
+      Err(e) => panic!("BanList: {}", e),
+    };
+
+    for line in raw_bans.lines() {
+      if line.trim().is_empty() {
+        continue;
+      }
+
+      let mut parts = line.splitn(3, '|');
+      let name = parts.next().unwrap_or("").trim().to_string();
+      let address = parts.next().unwrap_or("").trim().parse::<IpAddr>().ok();
+      let reason = parts.next().unwrap_or("").trim().to_string();
+
+      if name.is_empty() && address.is_none() {
+        println!("BanList: ignoring malformed ban [{}].", line);
+        continue;
+      }
+
+      new_ban_list.bans.push(Ban {
+        name,
+        address,
+        reason,
+      });
+    }
+
+    println!("BanList: loaded [{}] ban(s).", new_ban_list.bans.len());
+
+    new_ban_list
+  }
+
+  ///
+  /// Get the path to banned.txt.
+  ///
+  fn get_path(&self) -> String {
+    let mut path = self.world_path.clone();
+    path.push_str("/banned.txt");
+    path
+  }
+
+  ///
+  /// Write the ban list out to disk.
+  ///
+  fn save(&self) -> Result<(), String> {
+    create_dir_all(&self.world_path)?;
+
+    let mut raw_bans = String::new();
+    for ban in &self.bans {
+      let address = match ban.address {
+        Some(address) => address.to_string(),
+        None => String::new(),
+      };
+      raw_bans.push_str(&format!("{}|{}|{}\n", ban.name, address, ban.reason));
+    }
+
+    write_file_atomic(&self.get_path(), raw_bans.as_bytes())
+  }
+
+  ///
+  /// Ban a player by name, and by address if we know it.
+  ///
+  /// The reason is the last field so a | in it is fine, a newline becomes a space.
+  /// A name with either would turn into a different ban once it's loaded back.
+  ///
+  pub fn ban(&mut self, name: &str, address: Option<IpAddr>, reason: &str) -> Result<(), String> {
+    if name.contains(['|', '\n', '\r']) {
+      return Err(format!(
+        "Can't ban [{}], it has a | or a newline.",
+        name.escape_debug()
+      ));
+    }
+
+    // Banning again just updates the ban.
+    self.bans.retain(|ban| ban.name != name);
+
+    self.bans.push(Ban {
+      name: name.to_owned(),
+      address,
+      reason: reason.replace(['\n', '\r'], " "),
+    });
+
+    self.save()
+  }
+
+  ///
+  /// Lift a ban by name.
+  ///
+  /// Returns if the player was banned.
+  ///
+  pub fn unban(&mut self, name: &str) -> Result<bool, String> {
+    let size_before = self.bans.len();
+    self.bans.retain(|ban| ban.name != name);
+
+    if self.bans.len() == size_before {
+      return Ok(false);
+    }
+
+    self.save()?;
+    Ok(true)
+  }
+
+  ///
+  /// Check if a name or an address is banned.
+  ///
+  /// Returns the ban if it is.
+  ///
+  pub fn check(&self, name: &str, address: IpAddr) -> Option<&Ban> {
+    self
+      .bans
+      .iter()
+      .find(|ban| ban.name == name || ban.address == Some(address))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs::remove_dir_all,
+    net::{IpAddr, Ipv4Addr},
+  };
+
+  use crate::{file_utilities::test_dir, game::server::ban_list::BanList};
+
+  #[test]
+  fn test_ban_list_persistence() {
+    println!("--- BEGIN BAN LIST PERSISTENCE TEST ---");
+    let world_path = test_dir("ban_list_persistence");
+
+    let griefer_address = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 66));
+    let someone_else = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+    let mut ban_list = BanList::load(&world_path);
+    assert!(ban_list.check("griefer", griefer_address).is_none());
+
+    if let Err(e) = ban_list.ban("griefer", Some(griefer_address), "griefing | a lot") {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = ban_list.ban("spammer", None, "spam\nfriend||") {
+      panic!("Unit test is broken. {}", e);
+    }
+    // It would load back as a ban on someone else.
+    assert!(ban_list.ban("x|10.0.0.1|", None, "forged").is_err());
+
+    // Bans survive a reload, by name and by address.
+    let mut ban_list = BanList::load(&world_path);
+    match ban_list.check("alt_account", griefer_address) {
+      Some(ban) => assert_eq!(ban.reason, "griefing | a lot"),
+      None => panic!("Address ban was lost."),
+    }
+    match ban_list.check("spammer", someone_else) {
+      Some(ban) => assert_eq!(ban.reason, "spam friend||"),
+      None => panic!("Name ban was lost."),
+    }
+    assert!(ban_list.check("friend", someone_else).is_none());
+    assert!(ban_list.check("x", someone_else).is_none());
+
+    assert_eq!(ban_list.unban("spammer"), Ok(true));
+    assert_eq!(ban_list.unban("spammer"), Ok(false));
+    assert!(BanList::load(&world_path)
+      .check("spammer", someone_else)
+      .is_none());
+
+    let _ = remove_dir_all(&world_path);
+  }
+}
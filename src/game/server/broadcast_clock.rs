@@ -0,0 +1,77 @@
+use crate::game::time_step::TimeStep;
+
+///
+/// Floating point slack, so 2 ticks of 0.05 make a 0.1 period.
+///
+const PERIOD_EPSILON: f64 = 1e-9;
+
+///
+/// Decides which ticks send the world state out to the players.
+///
+/// The simulation can run at 20 TPS while the network only broadcasts at 10 Hz,
+/// what changed in between gets sent out together on the next broadcast.
+/// It can't go faster than the tick rate, at most every tick broadcasts.
+///
+pub struct BroadcastClock {
+  period: f64,
+  accumulator: f64,
+}
+
+impl BroadcastClock {
+  pub fn new(broadcasts_per_second: f64) -> Self {
+    BroadcastClock {
+      period: 1.0 / broadcasts_per_second,
+      accumulator: 0.0,
+    }
+  }
+
+  ///
+  /// Move the clock along by a tick. Returns if this tick broadcasts.
+  ///
+  pub fn advance(&mut self, delta: TimeStep) -> bool {
+    self.accumulator += delta.as_secs_f64();
+    if self.accumulator + PERIOD_EPSILON < self.period {
+      return false;
+    }
+
+    self.accumulator -= self.period;
+    // After a long stall, one broadcast catches everyone up. Don't burst.
+    if self.accumulator > self.period {
+      self.accumulator = 0.0;
+    }
+    true
+  }
+
+  ///
+  /// Get the time between two broadcasts. [seconds]
+  ///
+  pub fn get_period(&self) -> f64 {
+    self.period
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::game::{server::broadcast_clock::BroadcastClock, time_step::TimeStep};
+
+  #[test]
+  fn test_broadcast_clock_half_rate() {
+    println!("--- BEGIN BROADCAST CLOCK HALF RATE TEST ---");
+    let tick = TimeStep::from_secs_f64(1.0 / 20.0);
+
+    // Simulation at 20, network at 10.
+    let mut clock = BroadcastClock::new(10.0);
+    let broadcasts = (0..100).filter(|_| clock.advance(tick)).count();
+    assert_eq!(broadcasts, 50);
+
+    // Faster than the tick rate is every tick.
+    let mut clock = BroadcastClock::new(60.0);
+    let broadcasts = (0..100).filter(|_| clock.advance(tick)).count();
+    assert_eq!(broadcasts, 100);
+
+    // A stall only gets one broadcast.
+    let mut clock = BroadcastClock::new(10.0);
+    assert!(clock.advance(TimeStep::from_secs_f64(5.0)));
+    assert!(!clock.advance(tick));
+  }
+}
@@ -0,0 +1,445 @@
+use glam::Vec3A;
+
+///
+/// What a ChatCommand is, for /help and the privilege check.
+///
+/// params uses <required> and [optional].
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChatCommandInfo {
+  pub name: &'static str,
+  pub params: &'static str,
+  pub description: &'static str,
+  pub privs: &'static [&'static str],
+}
+
+impl ChatCommandInfo {
+  ///
+  /// Get the one line summary /help lists.
+  ///
+  pub fn get_summary(&self) -> String {
+    match self.params.is_empty() {
+      true => format!("/{}: {}", self.name, self.description),
+      false => format!("/{} {}: {}", self.name, self.params, self.description),
+    }
+  }
+
+  ///
+  /// Get the full help for /help <command>.
+  ///
+  pub fn get_details(&self) -> String {
+    match self.privs.is_empty() {
+      true => self.get_summary(),
+      false => format!(
+        "{} (requires privileges: {})",
+        self.get_summary(),
+        self.privs.join(", ")
+      ),
+    }
+  }
+}
+
+///
+/// Every ChatCommand, in the order /help lists them.
+///
+pub const CHAT_COMMANDS: [ChatCommandInfo; 14] = [
+  ChatCommandInfo {
+    name: "help",
+    params: "[command]",
+    description: "List the commands you can run, or get details on one.",
+    privs: &[],
+  },
+  ChatCommandInfo {
+    name: "status",
+    params: "",
+    description: "Show the server status.",
+    privs: &[],
+  },
+  ChatCommandInfo {
+    name: "say",
+    params: "<message>",
+    description: "Send a message to everyone.",
+    privs: &["shout"],
+  },
+  ChatCommandInfo {
+    name: "kick",
+    params: "<name> [reason]",
+    description: "Kick a player off the server.",
+    privs: &["kick"],
+  },
+  ChatCommandInfo {
+    name: "ban",
+    params: "<name> [reason]",
+    description: "Ban a player.",
+    privs: &["ban"],
+  },
+  ChatCommandInfo {
+    name: "unban",
+    params: "<name>",
+    description: "Lift a ban.",
+    privs: &["ban"],
+  },
+  ChatCommandInfo {
+    name: "grant",
+    params: "<name> <privilege>",
+    description: "Give a player a privilege.",
+    privs: &["privs"],
+  },
+  ChatCommandInfo {
+    name: "revoke",
+    params: "<name> <privilege>",
+    description: "Take a privilege away from a player.",
+    privs: &["privs"],
+  },
+  ChatCommandInfo {
+    name: "teleport",
+    params: "<x> <y> <z> | <name>",
+    description: "Teleport yourself to a position, or to another player.",
+    privs: &["teleport"],
+  },
+  ChatCommandInfo {
+    name: "shutdown",
+    params: "[seconds]",
+    description: "Shut the server down, after a countdown if seconds is given.",
+    privs: &["server"],
+  },
+  ChatCommandInfo {
+    name: "cancel_shutdown",
+    params: "",
+    description: "Cancel a scheduled shutdown.",
+    privs: &["server"],
+  },
+  ChatCommandInfo {
+    name: "clearobjects",
+    params: "",
+    description: "Remove every entity that isn't a player, a few each tick.",
+    privs: &["server"],
+  },
+  ChatCommandInfo {
+    name: "maintenance",
+    params: "",
+    description: "Show how far along the running maintenance is.",
+    privs: &["server"],
+  },
+  ChatCommandInfo {
+    name: "cancel_maintenance",
+    params: "",
+    description: "Stop the running maintenance where it is.",
+    privs: &["server"],
+  },
+];
+
+///
+/// Where /teleport sends you.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum TeleportTarget {
+  Position(Vec3A),
+  Player(String),
+}
+
+///
+/// Commands an admin can run on the Server.
+///
+/// These come from the server console or from in-game chat, both go
+/// through the same parser so they always behave the same.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatCommand {
+  Help(Option<String>),
+  Status,
+  Kick { name: String, reason: String },
+  Ban { name: String, reason: String },
+  Unban(String),
+  Say(String),
+  Grant { name: String, privilege: String },
+  Revoke { name: String, privilege: String },
+  Teleport(TeleportTarget),
+  Shutdown,
+  ScheduleShutdown(f64),
+  CancelShutdown,
+  ClearObjects,
+  Maintenance,
+  CancelMaintenance,
+}
+
+impl ChatCommand {
+  ///
+  /// Look up a command's info by name.
+  ///
+  pub fn get_info_by_name(name: &str) -> Option<&'static ChatCommandInfo> {
+    CHAT_COMMANDS.iter().find(|info| info.name == name)
+  }
+
+  ///
+  /// Get the name this command is typed as.
+  ///
+  pub fn get_name(&self) -> &'static str {
+    match self {
+      ChatCommand::Help(_) => "help",
+      ChatCommand::Status => "status",
+      ChatCommand::Kick { .. } => "kick",
+      ChatCommand::Ban { .. } => "ban",
+      ChatCommand::Unban(_) => "unban",
+      ChatCommand::Say(_) => "say",
+      ChatCommand::Grant { .. } => "grant",
+      ChatCommand::Revoke { .. } => "revoke",
+      ChatCommand::Teleport(_) => "teleport",
+      ChatCommand::Shutdown | ChatCommand::ScheduleShutdown(_) => "shutdown",
+      ChatCommand::CancelShutdown => "cancel_shutdown",
+      ChatCommand::ClearObjects => "clearobjects",
+      ChatCommand::Maintenance => "maintenance",
+      ChatCommand::CancelMaintenance => "cancel_maintenance",
+    }
+  }
+
+  ///
+  /// Get this command's info.
+  ///
+  pub fn get_info(&self) -> &'static ChatCommandInfo {
+    match Self::get_info_by_name(self.get_name()) {
+      Some(info) => info,
+      None => panic!(
+        "ChatCommand: [{}] is missing from CHAT_COMMANDS.",
+        self.get_name()
+      ),
+    }
+  }
+
+  ///
+  /// Split "<name> <privilege>" arguments.
+  ///
+  fn split_name_and_privilege(arguments: &str, command: &str) -> Result<(String, String), String> {
+    match arguments.split_once(char::is_whitespace) {
+      Some((name, privilege)) if !privilege.trim().is_empty() => {
+        Ok((name.to_string(), privilege.trim().to_string()))
+      }
+      _ => Err(format!("Usage: {} <name> <privilege>", command)),
+    }
+  }
+
+  ///
+  /// Split "<name> [reason]" arguments.
+  ///
+  fn split_name_and_reason(arguments: &str, command: &str) -> Result<(String, String), String> {
+    let (name, reason) = match arguments.split_once(char::is_whitespace) {
+      Some((name, reason)) => (name, reason.trim()),
+      None => (arguments, ""),
+    };
+    if name.is_empty() {
+      return Err(format!("Usage: {} <name> [reason]", command));
+    }
+    Ok((name.to_string(), reason.to_string()))
+  }
+
+  ///
+  /// Split "<x> <y> <z>" or "<name>" arguments. x,y,z works too, like minetest C++.
+  ///
+  fn parse_teleport_target(arguments: &str) -> Result<TeleportTarget, String> {
+    let usage = "Usage: teleport <x> <y> <z> | <name>";
+    let parts: Vec<&str> = arguments
+      .trim_start_matches('(')
+      .trim_end_matches(')')
+      .split(|c: char| c == ',' || c.is_whitespace())
+      .filter(|part| !part.is_empty())
+      .collect();
+    match parts.as_slice() {
+      [name] => Ok(TeleportTarget::Player(name.to_string())),
+      [x, y, z] => {
+        let coordinates: Vec<Option<f32>> = [x, y, z]
+          .iter()
+          .map(|part| part.parse::<f32>().ok().filter(|value| value.is_finite()))
+          .collect();
+        match coordinates.as_slice() {
+          [Some(x), Some(y), Some(z)] => Ok(TeleportTarget::Position(Vec3A::new(*x, *y, *z))),
+          _ => Err(format!("Invalid coordinates [{}]. {}", arguments, usage)),
+        }
+      }
+      _ => Err(usage.to_string()),
+    }
+  }
+
+  ///
+  /// Parse a line of text into a ChatCommand.
+  ///
+  /// The leading / that in-game chat commands have is optional.
+  ///
+  pub fn parse(line: &str) -> Result<ChatCommand, String> {
+    let line = line.trim();
+    let line = line.strip_prefix('/').unwrap_or(line);
+
+    let (command, arguments) = match line.split_once(char::is_whitespace) {
+      Some((command, arguments)) => (command, arguments.trim()),
+      None => (line, ""),
+    };
+
+    match command {
+      "help" => match arguments.is_empty() {
+        true => Ok(ChatCommand::Help(None)),
+        false => Ok(ChatCommand::Help(Some(
+          arguments.trim_start_matches('/').to_string(),
+        ))),
+      },
+      "status" => Ok(ChatCommand::Status),
+      "kick" => {
+        let (name, reason) = Self::split_name_and_reason(arguments, "kick")?;
+        Ok(ChatCommand::Kick { name, reason })
+      }
+      "ban" => {
+        let (name, reason) = Self::split_name_and_reason(arguments, "ban")?;
+        Ok(ChatCommand::Ban { name, reason })
+      }
+      "unban" => {
+        if arguments.is_empty() {
+          return Err("Usage: unban <name>".to_string());
+        }
+        Ok(ChatCommand::Unban(arguments.to_string()))
+      }
+      "say" => {
+        if arguments.is_empty() {
+          return Err("Usage: say <message>".to_string());
+        }
+        Ok(ChatCommand::Say(arguments.to_string()))
+      }
+      "grant" => {
+        let (name, privilege) = Self::split_name_and_privilege(arguments, "grant")?;
+        Ok(ChatCommand::Grant { name, privilege })
+      }
+      "revoke" => {
+        let (name, privilege) = Self::split_name_and_privilege(arguments, "revoke")?;
+        Ok(ChatCommand::Revoke { name, privilege })
+      }
+      "teleport" => Ok(ChatCommand::Teleport(Self::parse_teleport_target(
+        arguments,
+      )?)),
+      "shutdown" => {
+        if arguments.is_empty() {
+          return Ok(ChatCommand::Shutdown);
+        }
+        match arguments.parse::<f64>() {
+          Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => {
+            Ok(ChatCommand::ScheduleShutdown(seconds))
+          }
+          _ => Err("Usage: shutdown [seconds]".to_string()),
+        }
+      }
+      "cancel_shutdown" => Ok(ChatCommand::CancelShutdown),
+      "clearobjects" => Ok(ChatCommand::ClearObjects),
+      "maintenance" => Ok(ChatCommand::Maintenance),
+      "cancel_maintenance" => Ok(ChatCommand::CancelMaintenance),
+      "" => Err("No command given.".to_string()),
+      _ => Err(format!("Unknown command [{}].", command)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::Vec3A;
+
+  use crate::game::server::chat_command::{ChatCommand, TeleportTarget, CHAT_COMMANDS};
+
+  #[test]
+  fn test_chat_command_parse() {
+    println!("--- BEGIN CHAT COMMAND PARSE TEST ---");
+    assert_eq!(ChatCommand::parse("status"), Ok(ChatCommand::Status));
+    assert_eq!(ChatCommand::parse("/shutdown\n"), Ok(ChatCommand::Shutdown));
+    assert_eq!(
+      ChatCommand::parse("say   hello   there"),
+      Ok(ChatCommand::Say("hello   there".to_string()))
+    );
+    assert_eq!(
+      ChatCommand::parse("kick bob being rude"),
+      Ok(ChatCommand::Kick {
+        name: "bob".to_string(),
+        reason: "being rude".to_string()
+      })
+    );
+    assert_eq!(
+      ChatCommand::parse("ban griefer"),
+      Ok(ChatCommand::Ban {
+        name: "griefer".to_string(),
+        reason: "".to_string()
+      })
+    );
+    assert_eq!(
+      ChatCommand::parse("unban griefer"),
+      Ok(ChatCommand::Unban("griefer".to_string()))
+    );
+    assert_eq!(
+      ChatCommand::parse("shutdown 30"),
+      Ok(ChatCommand::ScheduleShutdown(30.0))
+    );
+    assert_eq!(
+      ChatCommand::parse("cancel_shutdown"),
+      Ok(ChatCommand::CancelShutdown)
+    );
+    assert!(ChatCommand::parse("shutdown -5").is_err());
+    assert_eq!(
+      ChatCommand::parse("help /kick"),
+      Ok(ChatCommand::Help(Some("kick".to_string())))
+    );
+    assert_eq!(
+      ChatCommand::parse("grant bob kick"),
+      Ok(ChatCommand::Grant {
+        name: "bob".to_string(),
+        privilege: "kick".to_string()
+      })
+    );
+    assert!(ChatCommand::parse("grant bob").is_err());
+    assert_eq!(
+      ChatCommand::parse("teleport 1 -2.5 3"),
+      Ok(ChatCommand::Teleport(TeleportTarget::Position(Vec3A::new(
+        1.0, -2.5, 3.0
+      ))))
+    );
+    assert_eq!(
+      ChatCommand::parse("teleport (1,2,3)"),
+      Ok(ChatCommand::Teleport(TeleportTarget::Position(Vec3A::new(
+        1.0, 2.0, 3.0
+      ))))
+    );
+    assert_eq!(
+      ChatCommand::parse("teleport bob"),
+      Ok(ChatCommand::Teleport(TeleportTarget::Player(
+        "bob".to_string()
+      )))
+    );
+    assert!(
+      ChatCommand::parse("teleport 1 two 3").is_err_and(|e| e.starts_with("Invalid coordinates"))
+    );
+    assert!(ChatCommand::parse("teleport 1 2").is_err());
+    assert!(ChatCommand::parse("teleport").is_err());
+    assert!(ChatCommand::parse("kick").is_err());
+    assert!(ChatCommand::parse("say").is_err());
+    assert!(ChatCommand::parse("fly").is_err());
+    assert!(ChatCommand::parse("   ").is_err());
+  }
+
+  #[test]
+  fn test_chat_command_info() {
+    println!("--- BEGIN CHAT COMMAND INFO TEST ---");
+    // Every parsed command has an entry, get_info() panics otherwise.
+    for info in CHAT_COMMANDS {
+      let line = match info.name {
+        "say" | "kick" | "ban" | "unban" => format!("{} bob", info.name),
+        "grant" | "revoke" => format!("{} bob kick", info.name),
+        "teleport" => "teleport 1 2 3".to_string(),
+        name => name.to_string(),
+      };
+      match ChatCommand::parse(&line) {
+        Ok(command) => assert_eq!(command.get_info(), &info),
+        Err(e) => panic!("Unit test is broken. {}", e),
+      }
+    }
+
+    let kick = ChatCommand::parse("kick bob").map(|command| command.get_info().get_details());
+    assert_eq!(
+      kick,
+      Ok(
+        "/kick <name> [reason]: Kick a player off the server. (requires privileges: kick)"
+          .to_string()
+      )
+    );
+  }
+}
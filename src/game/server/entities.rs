@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+
+use ahash::AHashMap;
+use glam::Vec3A;
+
+use crate::game::network_message::NetworkMessage;
+
+///
+/// What kind of entity a player shows up as to everyone else.
+///
+pub const PLAYER_ENTITY_KIND: &str = "player";
+
+///
+/// Something in the world that isn't a node, like a player or a mob.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerEntity {
+  // What the client draws it as, like "player".
+  pub kind: String,
+  pub position: Vec3A,
+  // Moved along by EntityPhysics every tick. [nodes per second]
+  pub velocity: Vec3A,
+  // Players move themselves, EntityPhysics leaves them alone.
+  pub physical: bool,
+}
+
+///
+/// Every entity on the Server, by id.
+///
+/// The clients only know about these through AddEntity and RemoveEntity.
+/// Ids are never reused, so a late RemoveEntity can't hit the wrong one.
+///
+pub struct ServerEntities {
+  next_id: u64,
+  entities: BTreeMap<u64, ServerEntity>,
+  // Player name -> the id of the entity they show up as.
+  players: AHashMap<String, u64>,
+}
+
+impl ServerEntities {
+  pub fn new() -> Self {
+    ServerEntities {
+      next_id: 1,
+      entities: BTreeMap::new(),
+      players: AHashMap::new(),
+    }
+  }
+
+  ///
+  /// Spawn an entity that EntityPhysics moves. Returns it's id.
+  ///
+  pub fn add(&mut self, kind: &str, position: Vec3A) -> u64 {
+    self.insert(ServerEntity {
+      kind: kind.to_string(),
+      position,
+      velocity: Vec3A::ZERO,
+      physical: true,
+    })
+  }
+
+  fn insert(&mut self, entity: ServerEntity) -> u64 {
+    let id = self.next_id;
+    self.next_id += 1;
+    self.entities.insert(id, entity);
+    id
+  }
+
+  pub fn remove(&mut self, id: u64) -> Option<ServerEntity> {
+    self.entities.remove(&id)
+  }
+
+  pub fn get(&self, id: u64) -> Option<&ServerEntity> {
+    self.entities.get(&id)
+  }
+
+  pub fn get_mut(&mut self, id: u64) -> Option<&mut ServerEntity> {
+    self.entities.get_mut(&id)
+  }
+
+  pub fn iter_mut(&mut self) -> impl Iterator<Item = (&u64, &mut ServerEntity)> {
+    self.entities.iter_mut()
+  }
+
+  ///
+  /// Spawn the entity a player shows up as. Returns it's id.
+  ///
+  pub fn add_player(&mut self, name: &str, position: Vec3A) -> u64 {
+    // Joining twice shouldn't leave a ghost behind.
+    if let Some(id) = self.remove_player(name) {
+      println!(
+        "ServerEntities: [{}] already had entity [{}], replacing it.",
+        name, id
+      );
+    }
+    let id = self.insert(ServerEntity {
+      kind: PLAYER_ENTITY_KIND.to_string(),
+      position,
+      velocity: Vec3A::ZERO,
+      physical: false,
+    });
+    self.players.insert(name.to_string(), id);
+    id
+  }
+
+  ///
+  /// Despawn a player's entity. Returns the id it had.
+  ///
+  pub fn remove_player(&mut self, name: &str) -> Option<u64> {
+    let id = self.players.remove(name)?;
+    self.entities.remove(&id);
+    Some(id)
+  }
+
+  ///
+  /// Move a player's entity. Returns if they have one.
+  ///
+  pub fn set_player_position(&mut self, name: &str, position: Vec3A) -> bool {
+    let id = match self.players.get(name) {
+      Some(id) => *id,
+      None => return false,
+    };
+    match self.entities.get_mut(&id) {
+      Some(entity) => {
+        entity.position = position;
+        true
+      }
+      None => false,
+    }
+  }
+
+  ///
+  /// Get every entity that isn't a player, for things like clear_entities.
+  ///
+  pub fn get_non_player_ids(&self) -> Vec<u64> {
+    self
+      .entities
+      .keys()
+      .filter(|id| !self.players.values().any(|player_id| player_id == *id))
+      .copied()
+      .collect()
+  }
+
+  pub fn get_player_entity(&self, name: &str) -> Option<u64> {
+    self.players.get(name).copied()
+  }
+
+  ///
+  /// Get the message that tells a client about an entity.
+  ///
+  pub fn get_add_message(&self, id: u64) -> Option<NetworkMessage> {
+    self
+      .entities
+      .get(&id)
+      .map(|entity| NetworkMessage::AddEntity {
+        id,
+        kind: entity.kind.clone(),
+        position: entity.position,
+      })
+  }
+
+  ///
+  /// Get the messages that tell a client about every entity, for when they join.
+  ///
+  pub fn get_add_messages(&self) -> Vec<NetworkMessage> {
+    self
+      .entities
+      .keys()
+      .filter_map(|id| self.get_add_message(*id))
+      .collect()
+  }
+
+  ///
+  /// Get the message that tells a client where everything is.
+  ///
+  /// A player's own entity is left out, their client already knows where they are.
+  /// None if there's nothing to tell them about.
+  ///
+  pub fn get_positions_message(&self, for_player: &str) -> Option<NetworkMessage> {
+    let own_id = self.get_player_entity(for_player);
+    let positions: Vec<(u64, Vec3A)> = self
+      .entities
+      .iter()
+      .filter(|(id, _)| Some(**id) != own_id)
+      .map(|(id, entity)| (*id, entity.position))
+      .collect();
+    match positions.is_empty() {
+      true => None,
+      false => Some(NetworkMessage::EntityPositions { positions }),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.entities.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entities.is_empty()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::Vec3A;
+
+  use crate::game::{
+    network_message::NetworkMessage,
+    server::entities::{ServerEntities, PLAYER_ENTITY_KIND},
+  };
+
+  #[test]
+  fn test_server_entities_players() {
+    println!("--- BEGIN SERVER ENTITIES PLAYERS TEST ---");
+    let mut entities = ServerEntities::new();
+    let alice = entities.add_player("alice", Vec3A::ZERO);
+    let bob = entities.add_player("bob", Vec3A::ONE);
+    assert_ne!(alice, bob);
+    assert_eq!(entities.len(), 2);
+
+    assert!(entities.set_player_position("alice", Vec3A::X));
+    assert!(!entities.set_player_position("carol", Vec3A::X));
+    assert_eq!(
+      entities.get_add_message(alice),
+      Some(NetworkMessage::AddEntity {
+        id: alice,
+        kind: PLAYER_ENTITY_KIND.to_string(),
+        position: Vec3A::X,
+      })
+    );
+
+    // Rejoining replaces the old entity, and the id isn't reused.
+    let alice_again = entities.add_player("alice", Vec3A::ZERO);
+    assert!(alice_again > bob);
+    assert!(entities.get(alice).is_none());
+    assert_eq!(entities.get_add_messages().len(), 2);
+
+    // Everyone hears where everyone else is, not themselves.
+    assert_eq!(
+      entities.get_positions_message("bob"),
+      Some(NetworkMessage::EntityPositions {
+        positions: vec![(alice_again, Vec3A::ZERO)],
+      })
+    );
+
+    assert_eq!(entities.remove_player("bob"), Some(bob));
+    assert_eq!(entities.remove_player("bob"), None);
+    assert_eq!(entities.len(), 1);
+    assert_eq!(entities.get_positions_message("alice"), None);
+  }
+}
@@ -0,0 +1,210 @@
+use glam::Vec3A;
+
+use crate::game::game_config::GameConfig;
+
+use super::entities::ServerEntities;
+
+///
+/// How fast things fall, like minetest C++'s movement_gravity. [nodes per second squared]
+///
+pub const DEFAULT_GRAVITY: f32 = 9.81;
+
+///
+/// The fastest anything falls. [nodes per second]
+///
+pub const DEFAULT_TERMINAL_VELOCITY: f32 = 60.0;
+
+///
+/// What stops an entity from moving through the world.
+///
+/// Nothing real implements this yet, it's here so a proper collision
+/// system can be dropped in without touching EntityPhysics.
+///
+pub trait Collider {
+  ///
+  /// Move something from one position toward another.
+  ///
+  /// Returns where it ends up and if it landed on something.
+  ///
+  fn sweep(&self, from: Vec3A, to: Vec3A) -> (Vec3A, bool);
+}
+
+///
+/// Nothing to hit, everything falls forever.
+///
+pub struct NoCollider;
+
+impl Collider for NoCollider {
+  fn sweep(&self, _from: Vec3A, to: Vec3A) -> (Vec3A, bool) {
+    (to, false)
+  }
+}
+
+///
+/// A floor at one height, everything lands on it.
+///
+/// Good enough until there's a map to collide with.
+///
+pub struct FlatGround {
+  pub height: f32,
+}
+
+impl Collider for FlatGround {
+  fn sweep(&self, _from: Vec3A, to: Vec3A) -> (Vec3A, bool) {
+    if to.y <= self.height {
+      (Vec3A::new(to.x, self.height, to.z), true)
+    } else {
+      (to, false)
+    }
+  }
+}
+
+///
+/// Moves every physical entity by it's velocity and pulls it down, once per tick.
+///
+pub struct EntityPhysics {
+  gravity: f32,
+  terminal_velocity: f32,
+  collider: Box<dyn Collider>,
+}
+
+impl EntityPhysics {
+  pub fn new(gravity: f32, terminal_velocity: f32, collider: Box<dyn Collider>) -> Self {
+    EntityPhysics {
+      gravity,
+      terminal_velocity,
+      collider,
+    }
+  }
+
+  ///
+  /// Read gravity out of minetest.conf.
+  ///
+  /// movement_gravity = 9.81
+  /// movement_terminal_velocity = 60
+  ///
+  /// Nothing collides yet, see Collider.
+  ///
+  pub fn from_config(config: &GameConfig) -> Self {
+    let read = |key: &str, default: f32| match config.get(key) {
+      Some(value) => match value.parse::<f32>() {
+        Ok(value) if value.is_finite() && value >= 0.0 => value,
+        _ => {
+          println!("EntityPhysics: ignoring {} [{}].", key, value);
+          default
+        }
+      },
+      None => default,
+    };
+
+    EntityPhysics::new(
+      read("movement_gravity", DEFAULT_GRAVITY),
+      read("movement_terminal_velocity", DEFAULT_TERMINAL_VELOCITY),
+      Box::new(NoCollider),
+    )
+  }
+
+  pub fn set_collider(&mut self, collider: Box<dyn Collider>) {
+    self.collider = collider;
+  }
+
+  pub fn get_gravity(&self) -> f32 {
+    self.gravity
+  }
+
+  pub fn get_terminal_velocity(&self) -> f32 {
+    self.terminal_velocity
+  }
+
+  ///
+  /// Run the physics forward by one tick. [seconds]
+  ///
+  pub fn step(&self, entities: &mut ServerEntities, delta: f64) {
+    let delta = delta as f32;
+    for (_, entity) in entities.iter_mut() {
+      if !entity.physical {
+        continue;
+      }
+
+      entity.velocity.y = (entity.velocity.y - self.gravity * delta).max(-self.terminal_velocity);
+
+      let goal = entity.position + entity.velocity * delta;
+      let (position, landed) = self.collider.sweep(entity.position, goal);
+      entity.position = position;
+      if landed && entity.velocity.y < 0.0 {
+        entity.velocity.y = 0.0;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::Vec3A;
+
+  use crate::game::{
+    game_config::GameConfig,
+    server::{
+      entities::ServerEntities,
+      entity_physics::{EntityPhysics, FlatGround},
+    },
+  };
+
+  #[test]
+  fn test_entity_physics_gravity() {
+    println!("--- BEGIN ENTITY PHYSICS GRAVITY TEST ---");
+    let physics = EntityPhysics::from_config(&GameConfig::parse(
+      "movement_gravity = 10\nmovement_terminal_velocity = 25",
+    ));
+    assert_eq!(physics.get_gravity(), 10.0);
+
+    let mut entities = ServerEntities::new();
+    let rock = entities.add("rock", Vec3A::new(0.0, 100.0, 0.0));
+    let player = entities.add_player("alice", Vec3A::new(0.0, 100.0, 0.0));
+
+    // 10 ticks at 10 per second, a second of falling.
+    let mut last_velocity = 0.0;
+    for _ in 0..10 {
+      physics.step(&mut entities, 0.1);
+      let velocity = match entities.get(rock) {
+        Some(rock) => rock.velocity.y,
+        None => panic!("Unit test is broken. The rock is gone."),
+      };
+      assert!((last_velocity - velocity - 1.0).abs() < 0.0001);
+      last_velocity = velocity;
+    }
+    match entities.get(rock) {
+      // Semi-implicit Euler, 0.1 * (1 + 2 + ... + 10) = 5.5 nodes down.
+      Some(rock) => assert!((rock.position.y - 94.5).abs() < 0.0001),
+      None => panic!("Unit test is broken. The rock is gone."),
+    }
+
+    // Players move themselves.
+    match entities.get(player) {
+      Some(player) => assert_eq!(player.position.y, 100.0),
+      None => panic!("Unit test is broken. The player is gone."),
+    }
+
+    // It tops out at the terminal velocity, then lands.
+    for _ in 0..20 {
+      physics.step(&mut entities, 0.1);
+    }
+    match entities.get(rock) {
+      Some(rock) => assert_eq!(rock.velocity.y, -25.0),
+      None => panic!("Unit test is broken. The rock is gone."),
+    }
+
+    let mut physics = physics;
+    physics.set_collider(Box::new(FlatGround { height: 0.0 }));
+    for _ in 0..100 {
+      physics.step(&mut entities, 0.1);
+    }
+    match entities.get(rock) {
+      Some(rock) => {
+        assert_eq!(rock.position.y, 0.0);
+        assert_eq!(rock.velocity.y, 0.0);
+      }
+      None => panic!("Unit test is broken. The rock is gone."),
+    }
+  }
+}
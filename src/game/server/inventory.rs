@@ -0,0 +1,562 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  file_utilities::{create_dir_all, file_exists, read_file_to_string, write_file_atomic},
+  game::game_config::GameConfig,
+};
+
+use super::{item_registry::ItemRegistry, node_registry::NodeRegistry};
+
+///
+/// The list every player inventory starts out with.
+///
+pub const PLAYER_MAIN_LIST: &str = "main";
+
+///
+/// How many slots a player's main list has, like minetest C++.
+///
+pub const PLAYER_MAIN_SIZE: usize = 32;
+
+///
+/// The most slots a list can have. A mod asking for more gets an error, not
+/// gigabytes of empty slots.
+///
+pub const MAX_LIST_SIZE: usize = 1024;
+
+///
+/// Some amount of one item, sitting in a slot.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+  pub name: String,
+  pub count: u32,
+}
+
+impl ItemStack {
+  pub fn new(name: &str, count: u32) -> Self {
+    ItemStack {
+      name: name.to_string(),
+      count,
+    }
+  }
+
+  ///
+  /// Parse an item string, "mod:name" or "mod:name 5".
+  ///
+  /// No count means 1.
+  ///
+  pub fn from_string(item_string: &str) -> Result<Self, String> {
+    let mut parts = item_string.split_whitespace();
+    let name = match parts.next() {
+      Some(name) => name,
+      None => return Err("Empty item string.".to_string()),
+    };
+    let count = match parts.next() {
+      Some(raw_count) => match raw_count.parse::<u32>() {
+        Ok(count) => count,
+        Err(e) => {
+          return Err(format!(
+            "Item string [{}] has a bad count. {}",
+            item_string, e
+          ))
+        }
+      },
+      None => 1,
+    };
+    if parts.next().is_some() {
+      return Err(format!(
+        "Item string [{}] has to look like [mod_name:name count].",
+        item_string
+      ));
+    }
+    Ok(ItemStack::new(name, count))
+  }
+
+  ///
+  /// Get the item string, the other way around from from_string().
+  ///
+  pub fn to_item_string(&self) -> String {
+    match self.count {
+      1 => self.name.clone(),
+      count => format!("{} {}", self.name, count),
+    }
+  }
+}
+
+///
+/// Named lists of slots, each slot empty or holding one ItemStack.
+///
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Inventory {
+  lists: BTreeMap<String, Vec<Option<ItemStack>>>,
+}
+
+impl Inventory {
+  pub fn new() -> Self {
+    Inventory {
+      lists: BTreeMap::new(),
+    }
+  }
+
+  ///
+  /// A fresh inventory for a player, an empty main list.
+  ///
+  pub fn new_player() -> Self {
+    let mut inventory = Inventory::new();
+    inventory
+      .lists
+      .insert(PLAYER_MAIN_LIST.to_string(), vec![None; PLAYER_MAIN_SIZE]);
+    inventory
+  }
+
+  ///
+  /// Create a list or resize it. Shrinking it throws away what's in the cut off slots.
+  ///
+  /// Fails if it's over MAX_LIST_SIZE.
+  ///
+  pub fn set_size(&mut self, list_name: &str, size: usize) -> Result<(), String> {
+    if size > MAX_LIST_SIZE {
+      return Err(format!(
+        "Inventory list [{}] can't have [{}] slots, the most is [{}].",
+        list_name, size, MAX_LIST_SIZE
+      ));
+    }
+    self
+      .lists
+      .entry(list_name.to_string())
+      .or_default()
+      .resize(size, None);
+    Ok(())
+  }
+
+  ///
+  /// Get how many slots a list has, 0 if there's no such list.
+  ///
+  pub fn get_size(&self, list_name: &str) -> usize {
+    match self.lists.get(list_name) {
+      Some(slots) => slots.len(),
+      None => 0,
+    }
+  }
+
+  pub fn get_list(&self, list_name: &str) -> Option<&[Option<ItemStack>]> {
+    self.lists.get(list_name).map(|slots| slots.as_slice())
+  }
+
+  fn get_list_mut(&mut self, list_name: &str) -> Result<&mut Vec<Option<ItemStack>>, String> {
+    match self.lists.get_mut(list_name) {
+      Some(slots) => Ok(slots),
+      None => Err(format!("Inventory has no list [{}].", list_name)),
+    }
+  }
+
+  ///
+  /// Put items into a list.
+  ///
+  /// Stacks of the same item are topped up to stack_max first, then the
+  /// rest overflow into empty slots.
+  ///
+  /// Returns how many didn't fit.
+  ///
+  pub fn add_item(
+    &mut self,
+    list_name: &str,
+    stack: &ItemStack,
+    stack_max: u32,
+  ) -> Result<u32, String> {
+    let slots = self.get_list_mut(list_name)?;
+    let mut leftover = stack.count;
+
+    for existing in slots.iter_mut().flatten() {
+      if leftover == 0 {
+        break;
+      }
+      if existing.name != stack.name || existing.count >= stack_max {
+        continue;
+      }
+      let moved = leftover.min(stack_max - existing.count);
+      existing.count += moved;
+      leftover -= moved;
+    }
+
+    for slot in slots.iter_mut() {
+      if leftover == 0 {
+        break;
+      }
+      if slot.is_some() {
+        continue;
+      }
+      let moved = leftover.min(stack_max);
+      *slot = Some(ItemStack::new(&stack.name, moved));
+      leftover -= moved;
+    }
+
+    Ok(leftover)
+  }
+
+  ///
+  /// Take items out of a list.
+  ///
+  /// Asking for more than there is takes everything there is.
+  /// Returns how many were taken.
+  ///
+  pub fn remove_item(&mut self, list_name: &str, stack: &ItemStack) -> Result<u32, String> {
+    let slots = self.get_list_mut(list_name)?;
+    let mut removed = 0;
+
+    // From the back, so the front of the list stays put.
+    for slot in slots.iter_mut().rev() {
+      if removed == stack.count {
+        break;
+      }
+      let existing = match slot {
+        Some(existing) if existing.name == stack.name => existing,
+        _ => continue,
+      };
+      let taken = existing.count.min(stack.count - removed);
+      existing.count -= taken;
+      removed += taken;
+      if existing.count == 0 {
+        *slot = None;
+      }
+    }
+
+    Ok(removed)
+  }
+
+  ///
+  /// Get how many of an item a list has, over every slot.
+  ///
+  pub fn count_item(&self, list_name: &str, item_name: &str) -> u32 {
+    match self.lists.get(list_name) {
+      Some(slots) => slots
+        .iter()
+        .flatten()
+        .filter(|existing| existing.name == item_name)
+        .map(|existing| existing.count)
+        .sum(),
+      None => 0,
+    }
+  }
+}
+
+///
+/// Get the starter kit from initial_stuff in minetest.conf, item strings split by commas.
+///
+/// initial_stuff = default:pick_wood, default:torch 10
+///
+/// Broken item strings are skipped with a warning.
+///
+pub fn starter_kit_from_config(config: &GameConfig) -> Vec<ItemStack> {
+  let raw_kit = config.get_string("initial_stuff", "");
+  let mut kit = vec![];
+  for item_string in raw_kit.split(',').map(str::trim) {
+    if item_string.is_empty() {
+      continue;
+    }
+    match ItemStack::from_string(item_string) {
+      Ok(stack) => kit.push(stack),
+      Err(e) => println!(
+        "Inventories: ignoring [{}] in initial_stuff. {}",
+        item_string, e
+      ),
+    }
+  }
+  kit
+}
+
+///
+/// The inventory of every player in a world.
+///
+/// Stored in inventories.json in the world directory. A player gets one
+/// the first time they join and keeps it from then on. Who gets the
+/// starter kit is up to JoinedPlayers, not this.
+///
+pub struct Inventories {
+  world_path: String,
+  players: BTreeMap<String, Inventory>,
+  // From minetest.conf, what starter_kit goes back to when the mods reload.
+  default_starter_kit: Vec<ItemStack>,
+  // What new players get, mods can change it with minetest.set_starter_kit.
+  starter_kit: Vec<ItemStack>,
+  // If anything could've changed since the last save.
+  changed: bool,
+}
+
+impl Inventories {
+  ///
+  /// Load the inventories of a world. A missing inventories.json means
+  /// nobody has one yet.
+  ///
+  pub fn load(world_path: &str) -> Self {
+    let mut new_inventories = Inventories {
+      world_path: world_path.to_owned(),
+      players: BTreeMap::new(),
+      default_starter_kit: vec![],
+      starter_kit: vec![],
+      changed: false,
+    };
+
+    let path = new_inventories.get_path();
+    if !file_exists(&path) {
+      return new_inventories;
+    }
+
+    let raw_inventories = match read_file_to_string(&path) {
+      Ok(raw_inventories) => raw_inventories,
+      Err(e) => panic!("Inventories: {}", e),
+    };
+    new_inventories.players = match serde_json::from_str(&raw_inventories) {
+      Ok(players) => players,
+      Err(e) => panic!("Inventories: [{}] is broken. {}", path, e),
+    };
+
+    println!(
+      "Inventories: loaded [{}] player(s).",
+      new_inventories.players.len()
+    );
+
+    new_inventories
+  }
+
+  ///
+  /// Get the path to inventories.json.
+  ///
+  fn get_path(&self) -> String {
+    let mut path = self.world_path.clone();
+    path.push_str("/inventories.json");
+    path
+  }
+
+  ///
+  /// Write every inventory out to disk.
+  ///
+  pub fn save(&mut self) -> Result<(), String> {
+    create_dir_all(&self.world_path)?;
+
+    let raw_inventories = match serde_json::to_string_pretty(&self.players) {
+      Ok(raw_inventories) => raw_inventories,
+      Err(e) => return Err(format!("Inventories: failed to serialize. {}", e)),
+    };
+
+    write_file_atomic(&self.get_path(), raw_inventories.as_bytes())?;
+    self.changed = false;
+    Ok(())
+  }
+
+  ///
+  /// Save, but only if an inventory could've changed since the last save.
+  ///
+  pub fn save_if_changed(&mut self) -> Result<(), String> {
+    match self.changed {
+      true => self.save(),
+      false => Ok(()),
+    }
+  }
+
+  pub fn get(&self, name: &str) -> Option<&Inventory> {
+    self.players.get(name)
+  }
+
+  ///
+  /// Get everyone that has an inventory.
+  ///
+  pub fn get_names(&self) -> Vec<String> {
+    self.players.keys().cloned().collect()
+  }
+
+  ///
+  /// Get a player's inventory to change it. This counts as a change
+  /// whether anything changes or not, see save_if_changed.
+  ///
+  pub fn get_mut(&mut self, name: &str) -> Option<&mut Inventory> {
+    let inventory = self.players.get_mut(name)?;
+    self.changed = true;
+    Some(inventory)
+  }
+
+  ///
+  /// Give a player a fresh inventory, unless they already have one.
+  ///
+  /// Returns true if it's new.
+  ///
+  pub fn create_if_missing(&mut self, name: &str) -> bool {
+    if self.players.contains_key(name) {
+      return false;
+    }
+    self
+      .players
+      .insert(name.to_string(), Inventory::new_player());
+    self.changed = true;
+    true
+  }
+
+  ///
+  /// Set the starter kit from minetest.conf. This is also what it goes back to on reset.
+  ///
+  pub fn set_default_starter_kit(&mut self, kit: Vec<ItemStack>) {
+    self.default_starter_kit = kit.clone();
+    self.starter_kit = kit;
+  }
+
+  pub fn set_starter_kit(&mut self, kit: Vec<ItemStack>) {
+    self.starter_kit = kit;
+  }
+
+  pub fn get_starter_kit(&self) -> &[ItemStack] {
+    &self.starter_kit
+  }
+
+  ///
+  /// Put the starter kit back to what minetest.conf says, for when the mods reload.
+  ///
+  pub fn reset_starter_kit(&mut self) {
+    self.starter_kit = self.default_starter_kit.clone();
+  }
+
+  ///
+  /// Put the starter kit into a player's main list.
+  ///
+  /// Unknown items and whatever doesn't fit are left out, each one gives back a warning.
+  ///
+  pub fn give_starter_kit(
+    &mut self,
+    name: &str,
+    items: &ItemRegistry,
+    nodes: &NodeRegistry,
+  ) -> Vec<String> {
+    let inventory = match self.players.get_mut(name) {
+      Some(inventory) => inventory,
+      None => return vec![format!("Inventories: [{}] has no inventory.", name)],
+    };
+    self.changed = true;
+
+    let mut warnings = vec![];
+    for stack in &self.starter_kit {
+      if items.get(&stack.name).is_none() && nodes.get(&stack.name).is_none() {
+        warnings.push(format!(
+          "Inventories: the starter kit has unknown item [{}].",
+          stack.name
+        ));
+        continue;
+      }
+      match inventory.add_item(PLAYER_MAIN_LIST, stack, items.get_stack_max(&stack.name)) {
+        Ok(0) => {}
+        Ok(leftover) => warnings.push(format!(
+          "Inventories: [{}] of [{}] didn't fit in [{}]'s starter kit.",
+          leftover, stack.name, name
+        )),
+        Err(e) => warnings.push(format!("Inventories: {}", e)),
+      }
+    }
+    warnings
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::remove_dir_all;
+
+  use crate::{
+    file_utilities::test_dir,
+    game::server::inventory::{
+      Inventories, Inventory, ItemStack, MAX_LIST_SIZE, PLAYER_MAIN_LIST, PLAYER_MAIN_SIZE,
+    },
+  };
+
+  #[test]
+  fn test_inventory_add_and_remove() {
+    println!("--- BEGIN INVENTORY ADD AND REMOVE TEST ---");
+    let mut inventory = Inventory::new();
+    if let Err(e) = inventory.set_size("main", 3) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    // 25 apples at 10 a stack split over 3 slots.
+    let apples = ItemStack::new("food:apple", 25);
+    assert_eq!(inventory.add_item("main", &apples, 10), Ok(0));
+    let slots = match inventory.get_list("main") {
+      Some(slots) => slots,
+      None => panic!("Unit test is broken. There's no main list."),
+    };
+    assert_eq!(
+      slots,
+      &[
+        Some(ItemStack::new("food:apple", 10)),
+        Some(ItemStack::new("food:apple", 10)),
+        Some(ItemStack::new("food:apple", 5)),
+      ]
+    );
+
+    // Topping up comes first, then there's no room left.
+    assert_eq!(inventory.add_item("main", &apples, 10), Ok(20));
+    assert_eq!(inventory.count_item("main", "food:apple"), 30);
+    assert!(inventory.add_item("craft", &apples, 10).is_err());
+
+    // Removing more than there is only takes what there is.
+    assert_eq!(
+      inventory.remove_item("main", &ItemStack::new("food:apple", 12)),
+      Ok(12)
+    );
+    assert_eq!(inventory.count_item("main", "food:apple"), 18);
+    assert_eq!(
+      inventory.remove_item("main", &ItemStack::new("food:apple", 100)),
+      Ok(18)
+    );
+    assert_eq!(inventory.get_list("main"), Some(&[None, None, None][..]));
+
+    assert_eq!(
+      ItemStack::from_string("food:apple 7"),
+      Ok(ItemStack::new("food:apple", 7))
+    );
+    assert_eq!(
+      ItemStack::from_string("food:apple"),
+      Ok(ItemStack::new("food:apple", 1))
+    );
+    assert_eq!(
+      ItemStack::new("food:apple", 7).to_item_string(),
+      "food:apple 7"
+    );
+    assert!(ItemStack::from_string("food:apple seven").is_err());
+    assert!(ItemStack::from_string("").is_err());
+
+    // A list can't be made big enough to eat all the memory.
+    assert!(inventory.set_size("main", MAX_LIST_SIZE + 1).is_err());
+    assert_eq!(inventory.get_size("main"), 3);
+    assert_eq!(inventory.set_size("main", MAX_LIST_SIZE), Ok(()));
+  }
+
+  #[test]
+  fn test_inventories_save_and_load() {
+    println!("--- BEGIN INVENTORIES SAVE AND LOAD TEST ---");
+    let world_path = test_dir("inventories");
+
+    let mut inventories = Inventories::load(&world_path);
+    inventories.create_if_missing("bob");
+    let bob = match inventories.get_mut("bob") {
+      Some(bob) => bob,
+      None => panic!("Unit test is broken. Bob has no inventory."),
+    };
+    assert_eq!(bob.get_size(PLAYER_MAIN_LIST), PLAYER_MAIN_SIZE);
+    assert_eq!(
+      bob.add_item(PLAYER_MAIN_LIST, &ItemStack::new("rocks:stone", 5), 99),
+      Ok(0)
+    );
+    if let Err(e) = inventories.save() {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    // Joining again doesn't wipe it.
+    let mut inventories = Inventories::load(&world_path);
+    inventories.create_if_missing("bob");
+    match inventories.get("bob") {
+      Some(bob) => assert_eq!(bob.count_item(PLAYER_MAIN_LIST, "rocks:stone"), 5),
+      None => panic!("Bob's inventory wasn't saved."),
+    }
+    assert!(inventories.get("alice").is_none());
+
+    let _ = remove_dir_all(&world_path);
+  }
+}
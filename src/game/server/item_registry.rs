@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+
+use super::node_registry::NodeRegistry;
+
+///
+/// How many of an item fit in one inventory slot, unless it says otherwise.
+///
+/// Nodes always stack this high.
+///
+pub const DEFAULT_STACK_MAX: u32 = 99;
+
+///
+/// Everything the engine knows about a kind of craftitem.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemDefinition {
+  // "mod:name"
+  pub name: String,
+  pub description: String,
+  pub inventory_image: String,
+  // How many fit in one slot. [1 - u32::MAX]
+  pub stack_max: u32,
+  // The mod that registered it.
+  pub mod_origin: String,
+}
+
+impl ItemDefinition {
+  ///
+  /// A plain item, fill in the rest with struct update syntax.
+  ///
+  pub fn new(name: &str) -> Self {
+    ItemDefinition {
+      name: name.to_string(),
+      description: String::new(),
+      inventory_image: String::new(),
+      stack_max: DEFAULT_STACK_MAX,
+      mod_origin: String::new(),
+    }
+  }
+}
+
+///
+/// Every craftitem registered on the Server, from every mod.
+///
+/// Mods fill this in through minetest.register_craftitem while the game loads.
+/// It's rebuilt from scratch whenever the LuaEngine is, same as the NodeRegistry.
+///
+pub struct ItemRegistry {
+  items: BTreeMap<String, ItemDefinition>,
+}
+
+impl ItemRegistry {
+  pub fn new() -> Self {
+    ItemRegistry {
+      items: BTreeMap::new(),
+    }
+  }
+
+  ///
+  /// Add an item.
+  ///
+  /// Fails on a bad name, a name that's already taken, or a stack_max of 0.
+  ///
+  pub fn register(&mut self, definition: ItemDefinition) -> Result<(), String> {
+    NodeRegistry::check_name(&definition.name)?;
+
+    if definition.stack_max == 0 {
+      return Err(format!(
+        "Item [{}] has a stack_max of 0, nothing would fit.",
+        definition.name
+      ));
+    }
+
+    if let Some(existing) = self.items.get(&definition.name) {
+      return Err(format!(
+        "Item [{}] was already registered by mod [{}].",
+        definition.name, existing.mod_origin
+      ));
+    }
+
+    self.items.insert(definition.name.clone(), definition);
+    Ok(())
+  }
+
+  pub fn get(&self, name: &str) -> Option<&ItemDefinition> {
+    self.items.get(name)
+  }
+
+  ///
+  /// Get how many of an item fit in one slot.
+  ///
+  /// Anything that isn't a craftitem gets DEFAULT_STACK_MAX.
+  ///
+  pub fn get_stack_max(&self, name: &str) -> u32 {
+    match self.items.get(name) {
+      Some(definition) => definition.stack_max,
+      None => DEFAULT_STACK_MAX,
+    }
+  }
+
+  ///
+  /// Get the name of every registered item, sorted.
+  ///
+  pub fn get_names(&self) -> Vec<String> {
+    self.items.keys().cloned().collect()
+  }
+
+  pub fn len(&self) -> usize {
+    self.items.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.items.is_empty()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::game::server::item_registry::{ItemDefinition, ItemRegistry, DEFAULT_STACK_MAX};
+
+  #[test]
+  fn test_item_registry_register() {
+    println!("--- BEGIN ITEM REGISTRY REGISTER TEST ---");
+    let mut registry = ItemRegistry::new();
+    let apple = ItemDefinition {
+      description: "Apple".to_string(),
+      stack_max: 10,
+      mod_origin: "food".to_string(),
+      ..ItemDefinition::new("food:apple")
+    };
+
+    assert!(registry.register(apple.clone()).is_ok());
+    assert!(registry
+      .register(ItemDefinition::new("food:apple"))
+      .is_err());
+    assert!(registry.register(ItemDefinition::new("apple")).is_err());
+    assert!(registry
+      .register(ItemDefinition {
+        stack_max: 0,
+        ..ItemDefinition::new("food:air")
+      })
+      .is_err());
+
+    assert_eq!(registry.get("food:apple"), Some(&apple));
+    assert_eq!(registry.get_stack_max("food:apple"), 10);
+    assert_eq!(registry.get_stack_max("rocks:stone"), DEFAULT_STACK_MAX);
+    assert_eq!(registry.get_names(), vec!["food:apple"]);
+  }
+}
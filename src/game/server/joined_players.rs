@@ -0,0 +1,132 @@
+use std::collections::BTreeSet;
+
+use crate::file_utilities::{create_dir_all, file_exists, read_file_to_string, write_file_atomic};
+
+///
+/// Everyone that has ever joined a world.
+///
+/// Stored in joined.txt in the world directory, one name per line.
+/// A name goes in the moment they first join, and is saved right away.
+/// A player that isn't on it is brand new, see the starter kit.
+///
+pub struct JoinedPlayers {
+  world_path: String,
+  names: BTreeSet<String>,
+}
+
+impl JoinedPlayers {
+  ///
+  /// Load the players that have joined a world.
+  ///
+  /// known_players is everyone the world already has some record of. It's
+  /// only used when joined.txt isn't there yet, so the veterans of a world
+  /// from before it's time don't get treated like new players.
+  ///
+  pub fn load(world_path: &str, known_players: &[String]) -> Self {
+    let mut new_joined_players = JoinedPlayers {
+      world_path: world_path.to_owned(),
+      names: BTreeSet::new(),
+    };
+
+    let path = new_joined_players.get_path();
+    if !file_exists(&path) {
+      if known_players.is_empty() {
+        return new_joined_players;
+      }
+      new_joined_players
+        .names
+        .extend(known_players.iter().cloned());
+      println!(
+        "JoinedPlayers: started [{}] with the [{}] player(s) this world already knew.",
+        path,
+        new_joined_players.names.len()
+      );
+      if let Err(e) = new_joined_players.save() {
+        println!("JoinedPlayers: {}", e);
+      }
+      return new_joined_players;
+    }
+
+    let raw_names = match read_file_to_string(&path) {
+      Ok(raw_names) => raw_names,
+      Err(e) => panic!("JoinedPlayers: {}", e),
+    };
+    for line in raw_names.lines() {
+      if !line.trim().is_empty() {
+        new_joined_players.names.insert(line.trim().to_string());
+      }
+    }
+
+    new_joined_players
+  }
+
+  ///
+  /// Get the path to joined.txt.
+  ///
+  fn get_path(&self) -> String {
+    let mut path = self.world_path.clone();
+    path.push_str("/joined.txt");
+    path
+  }
+
+  ///
+  /// Write the names out to disk.
+  ///
+  fn save(&self) -> Result<(), String> {
+    create_dir_all(&self.world_path)?;
+
+    let mut raw_names = String::new();
+    for name in &self.names {
+      raw_names.push_str(name);
+      raw_names.push('\n');
+    }
+
+    write_file_atomic(&self.get_path(), raw_names.as_bytes())
+  }
+
+  ///
+  /// Check if a player has joined before.
+  ///
+  pub fn has_joined(&self, name: &str) -> bool {
+    self.names.contains(name)
+  }
+
+  ///
+  /// Remember that a player joined, saved right away.
+  ///
+  pub fn mark_joined(&mut self, name: &str) -> Result<(), String> {
+    if !self.names.insert(name.to_string()) {
+      return Ok(());
+    }
+    self.save()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::remove_dir_all;
+
+  use crate::{file_utilities::test_dir, game::server::joined_players::JoinedPlayers};
+
+  #[test]
+  fn test_joined_players_persistence() {
+    println!("--- BEGIN JOINED PLAYERS PERSISTENCE TEST ---");
+    let world_path = test_dir("joined_players_persistence");
+
+    // An older world, the players it already knows count as joined.
+    let mut joined_players = JoinedPlayers::load(&world_path, &["veteran".to_string()]);
+    assert!(joined_players.has_joined("veteran"));
+    assert!(!joined_players.has_joined("newbie"));
+    if let Err(e) = joined_players.mark_joined("newbie") {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    // After that joined.txt is the only thing that counts.
+    let joined_players = JoinedPlayers::load(&world_path, &["stranger".to_string()]);
+    assert!(joined_players.has_joined("veteran"));
+    assert!(joined_players.has_joined("newbie"));
+    assert!(!joined_players.has_joined("stranger"));
+
+    let _ = remove_dir_all(&world_path);
+  }
+}
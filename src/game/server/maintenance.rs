@@ -0,0 +1,323 @@
+use super::entities::ServerEntities;
+
+///
+/// How much work a maintenance task gets to do per tick, unless minetest.conf
+/// says maintenance_steps_per_tick.
+///
+/// For clear_entities that's how many entities get removed.
+///
+pub const DEFAULT_MAINTENANCE_STEPS_PER_TICK: usize = 100;
+
+///
+/// How far along a maintenance task is.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaintenanceProgress {
+  pub done: usize,
+  pub total: usize,
+}
+
+impl MaintenanceProgress {
+  pub fn is_finished(&self) -> bool {
+    self.done >= self.total
+  }
+
+  pub fn get_percent(&self) -> f64 {
+    match self.total {
+      0 => 100.0,
+      total => self.done as f64 / total as f64 * 100.0,
+    }
+  }
+}
+
+///
+/// What a maintenance task gets to touch, and what it changed.
+///
+pub struct MaintenanceContext<'a> {
+  pub entities: &'a mut ServerEntities,
+  // Removed this tick, the clients get told.
+  pub removed_entities: Vec<u64>,
+}
+
+///
+/// A long running admin job, like clearing the map of entities.
+///
+/// It runs a slice at a time across ticks, so the server never freezes.
+///
+pub trait MaintenanceTask {
+  fn get_name(&self) -> &'static str;
+
+  ///
+  /// Do up to steps worth of work. Returns how far along it is now.
+  ///
+  fn step(&mut self, context: &mut MaintenanceContext, steps: usize) -> MaintenanceProgress;
+}
+
+///
+/// Removes every entity that isn't a player, like minetest C++'s /clearobjects.
+///
+/// Only entities that exist when it starts are removed.
+///
+pub struct ClearEntities {
+  // None until the first step looks at what's there.
+  remaining: Option<Vec<u64>>,
+  total: usize,
+}
+
+impl ClearEntities {
+  pub fn new() -> Self {
+    ClearEntities {
+      remaining: None,
+      total: 0,
+    }
+  }
+}
+
+impl MaintenanceTask for ClearEntities {
+  fn get_name(&self) -> &'static str {
+    "clear_entities"
+  }
+
+  fn step(&mut self, context: &mut MaintenanceContext, steps: usize) -> MaintenanceProgress {
+    let remaining = self.remaining.get_or_insert_with(|| {
+      let mut ids = context.entities.get_non_player_ids();
+      // Popped off the back, so oldest go first.
+      ids.reverse();
+      ids
+    });
+    if self.total == 0 {
+      self.total = remaining.len();
+    }
+
+    for _ in 0..steps {
+      let id = match remaining.pop() {
+        Some(id) => id,
+        None => break,
+      };
+      // It might have despawned on it's own since.
+      if context.entities.remove(id).is_some() {
+        context.removed_entities.push(id);
+      }
+    }
+
+    MaintenanceProgress {
+      done: self.total - remaining.len(),
+      total: self.total,
+    }
+  }
+}
+
+///
+/// Get a maintenance task by name.
+///
+pub fn maintenance_task_from_name(name: &str) -> Option<Box<dyn MaintenanceTask>> {
+  match name {
+    "clear_entities" => Some(Box::new(ClearEntities::new())),
+    _ => None,
+  }
+}
+
+///
+/// Something Lua asked the Maintenance to do, see lua_maintenance.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaintenanceRequest {
+  Start(String),
+  Cancel,
+}
+
+///
+/// Runs one MaintenanceTask at a time, a little every tick.
+///
+pub struct Maintenance {
+  task: Option<Box<dyn MaintenanceTask>>,
+  progress: Option<MaintenanceProgress>,
+  steps_per_tick: usize,
+}
+
+impl Maintenance {
+  pub fn new(steps_per_tick: usize) -> Self {
+    Maintenance {
+      task: None,
+      progress: None,
+      steps_per_tick: steps_per_tick.max(1),
+    }
+  }
+
+  ///
+  /// Start a task. Fails if one is already running.
+  ///
+  pub fn start(&mut self, task: Box<dyn MaintenanceTask>) -> Result<(), String> {
+    if let Some(running) = &self.task {
+      return Err(format!(
+        "Maintenance [{}] is already running, cancel it first.",
+        running.get_name()
+      ));
+    }
+    println!("Maintenance: starting [{}].", task.get_name());
+    self.task = Some(task);
+    self.progress = None;
+    Ok(())
+  }
+
+  ///
+  /// Stop the running task where it is. Returns it's name, if one was running.
+  ///
+  /// Whatever it already did stays done.
+  ///
+  pub fn cancel(&mut self) -> Option<&'static str> {
+    let task = self.task.take()?;
+    self.progress = None;
+    println!("Maintenance: cancelled [{}].", task.get_name());
+    Some(task.get_name())
+  }
+
+  pub fn is_running(&self) -> bool {
+    self.task.is_some()
+  }
+
+  ///
+  /// Get the running task's name and how far along it is.
+  ///
+  /// The progress is None until it's had a tick.
+  ///
+  pub fn get_progress(&self) -> Option<(&'static str, Option<MaintenanceProgress>)> {
+    self
+      .task
+      .as_ref()
+      .map(|task| (task.get_name(), self.progress))
+  }
+
+  ///
+  /// Get the progress as text, for the maintenance command.
+  ///
+  pub fn get_status(&self) -> String {
+    match self.get_progress() {
+      Some((name, Some(progress))) => format!(
+        "Maintenance [{}] is [{:.0}%] done. ([{}/{}])",
+        name,
+        progress.get_percent(),
+        progress.done,
+        progress.total
+      ),
+      Some((name, None)) => format!("Maintenance [{}] is starting.", name),
+      None => "No maintenance is running.".to_string(),
+    }
+  }
+
+  ///
+  /// Run the task one tick further.
+  ///
+  /// Returns the task's name and final progress when it finishes.
+  ///
+  pub fn advance(
+    &mut self,
+    context: &mut MaintenanceContext,
+  ) -> Option<(&'static str, MaintenanceProgress)> {
+    let task = self.task.as_mut()?;
+    let progress = task.step(context, self.steps_per_tick);
+    self.progress = Some(progress);
+    if !progress.is_finished() {
+      return None;
+    }
+
+    let name = task.get_name();
+    println!(
+      "Maintenance: [{}] finished, [{}] done.",
+      name, progress.total
+    );
+    self.task = None;
+    self.progress = None;
+    Some((name, progress))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::Vec3A;
+
+  use crate::game::server::{
+    entities::ServerEntities,
+    maintenance::{
+      maintenance_task_from_name, Maintenance, MaintenanceContext, MaintenanceProgress,
+    },
+  };
+
+  #[test]
+  fn test_maintenance_clear_entities() {
+    println!("--- BEGIN MAINTENANCE CLEAR ENTITIES TEST ---");
+    let mut entities = ServerEntities::new();
+    for _ in 0..10 {
+      entities.add("rock", Vec3A::ZERO);
+    }
+    let player = entities.add_player("alice", Vec3A::ZERO);
+
+    let mut maintenance = Maintenance::new(3);
+    assert!(maintenance_task_from_name("defrag").is_none());
+    let start = |maintenance: &mut Maintenance| match maintenance_task_from_name("clear_entities") {
+      Some(task) => maintenance.start(task),
+      None => panic!("Unit test is broken. clear_entities is missing."),
+    };
+    assert!(start(&mut maintenance).is_ok());
+    // One at a time.
+    assert!(start(&mut maintenance).is_err());
+
+    // 3 a tick, so it takes 4 ticks and says how far along it is on each.
+    let mut removed = vec![];
+    let mut finished = None;
+    let mut ticks = 0;
+    while finished.is_none() {
+      ticks += 1;
+      let mut context = MaintenanceContext {
+        entities: &mut entities,
+        removed_entities: vec![],
+      };
+      finished = maintenance.advance(&mut context);
+      removed.extend(context.removed_entities);
+      if finished.is_none() {
+        assert_eq!(
+          maintenance.get_progress(),
+          Some((
+            "clear_entities",
+            Some(MaintenanceProgress {
+              done: ticks * 3,
+              total: 10
+            })
+          ))
+        );
+      }
+      assert!(ticks <= 4, "clear_entities never finished.");
+    }
+    assert_eq!(ticks, 4);
+    assert_eq!(
+      finished,
+      Some((
+        "clear_entities",
+        MaintenanceProgress {
+          done: 10,
+          total: 10
+        }
+      ))
+    );
+    assert_eq!(removed.len(), 10);
+    assert!(!maintenance.is_running());
+
+    // The player is still there.
+    assert_eq!(entities.len(), 1);
+    assert!(entities.get(player).is_some());
+
+    // Cancelling stops it partway.
+    for _ in 0..5 {
+      entities.add("rock", Vec3A::ZERO);
+    }
+    assert!(start(&mut maintenance).is_ok());
+    let mut context = MaintenanceContext {
+      entities: &mut entities,
+      removed_entities: vec![],
+    };
+    assert!(maintenance.advance(&mut context).is_none());
+    assert_eq!(maintenance.cancel(), Some("clear_entities"));
+    assert_eq!(maintenance.cancel(), None);
+    assert_eq!(entities.len(), 3);
+  }
+}
@@ -0,0 +1,153 @@
+use glam::IVec3;
+
+use crate::file_utilities::{
+  create_dir_all, file_exists, read_file_to_byte_vec, write_file_atomic,
+};
+
+///
+/// The map backend that's actually implemented, see map_backend_from_world.
+///
+pub const FILES_BACKEND: &str = "files";
+
+///
+/// Where a world's map is kept.
+///
+/// Blocks go in and out as bytes, how they're serialized is up to the map.
+/// Implement it to store worlds somewhere else, like an SQLite database.
+///
+pub trait MapBackend {
+  ///
+  /// Get a block's data. Ok(None) if it was never saved.
+  ///
+  fn load_block(&mut self, position: IVec3) -> Result<Option<Vec<u8>>, String>;
+
+  fn save_block(&mut self, position: IVec3, data: &[u8]) -> Result<(), String>;
+
+  fn exists(&self, position: IVec3) -> bool;
+}
+
+///
+/// Pick the MapBackend from world.mt's backend.
+///
+/// backend = files
+///
+/// Only files exists so far. Anything else, like minetest C++'s sqlite3, is
+/// an error. Falling back would start an empty map next to the real one.
+///
+pub fn map_backend_from_world(
+  world_path: &str,
+  backend: &str,
+) -> Result<Box<dyn MapBackend>, String> {
+  match backend {
+    FILES_BACKEND => Ok(Box::new(FileMapBackend::new(world_path))),
+    unknown => Err(format!(
+      "World [{}] uses map backend [{}], only [{}] is supported.",
+      world_path, unknown, FILES_BACKEND
+    )),
+  }
+}
+
+///
+/// Every block is it's own file.
+///
+/// Stored in the blocks directory of the world, named after the block position:
+/// blocks/<x>_<y>_<z>.bin
+///
+pub struct FileMapBackend {
+  block_dir: String,
+}
+
+impl FileMapBackend {
+  pub fn new(world_path: &str) -> Self {
+    FileMapBackend {
+      block_dir: format!("{}/blocks", world_path),
+    }
+  }
+
+  ///
+  /// Get the path to a block's file.
+  ///
+  fn get_path(&self, position: IVec3) -> String {
+    format!(
+      "{}/{}_{}_{}.bin",
+      self.block_dir, position.x, position.y, position.z
+    )
+  }
+}
+
+impl MapBackend for FileMapBackend {
+  fn load_block(&mut self, position: IVec3) -> Result<Option<Vec<u8>>, String> {
+    let path = self.get_path(position);
+    if !file_exists(&path) {
+      return Ok(None);
+    }
+    match read_file_to_byte_vec(&path) {
+      Ok(data) => Ok(Some(data)),
+      Err(e) => Err(format!("FileMapBackend: {}", e)),
+    }
+  }
+
+  fn save_block(&mut self, position: IVec3, data: &[u8]) -> Result<(), String> {
+    create_dir_all(&self.block_dir)?;
+    write_file_atomic(&self.get_path(position), data)
+  }
+
+  fn exists(&self, position: IVec3) -> bool {
+    file_exists(&self.get_path(position))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::remove_dir_all;
+
+  use glam::IVec3;
+
+  use crate::{
+    file_utilities::test_dir,
+    game::server::map_backend::{map_backend_from_world, MapBackend, FILES_BACKEND},
+  };
+
+  fn get_backend(world_path: &str) -> Box<dyn MapBackend> {
+    match map_backend_from_world(world_path, FILES_BACKEND) {
+      Ok(backend) => backend,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+  }
+
+  #[test]
+  fn test_file_map_backend_round_trip() {
+    println!("--- BEGIN FILE MAP BACKEND ROUND TRIP TEST ---");
+    let world_path = test_dir("file_map_backend");
+
+    let mut backend = get_backend(&world_path);
+    let position = IVec3::new(-1, 2, 300);
+    assert!(!backend.exists(position));
+    assert_eq!(backend.load_block(position), Ok(None));
+
+    let data: Vec<u8> = (0..=255).collect();
+    if let Err(e) = backend.save_block(position, &data) {
+      panic!("Unit test is broken. {}", e);
+    }
+    assert!(backend.exists(position));
+    assert!(!backend.exists(IVec3::new(1, 2, 300)));
+
+    // A fresh backend reads it back off the disk.
+    let mut backend = get_backend(&world_path);
+    assert_eq!(backend.load_block(position), Ok(Some(data)));
+
+    // Saving again replaces it.
+    if let Err(e) = backend.save_block(position, &[7]) {
+      panic!("Unit test is broken. {}", e);
+    }
+    assert_eq!(backend.load_block(position), Ok(Some(vec![7])));
+
+    // A world from minetest C++ doesn't quietly get an empty map.
+    match map_backend_from_world(&world_path, "sqlite3") {
+      Ok(_) => panic!("An unknown backend was accepted."),
+      Err(e) => assert!(e.contains("[sqlite3]")),
+    }
+
+    let _ = remove_dir_all(&world_path);
+  }
+}
@@ -0,0 +1,161 @@
+use std::fs::read_dir;
+
+use ahash::AHashMap;
+
+use crate::{
+  file_utilities::read_file_to_byte_vec,
+  game::{lua_engine::lua_file_helpers::ModDirectory, network_message::MEDIA_CHUNK_BYTES},
+  media_hash::hash_bytes,
+};
+
+///
+/// The folders in a mod that clients can ask for media out of.
+///
+const MEDIA_FOLDERS: [&str; 3] = ["textures", "sounds", "models"];
+
+///
+/// How much media is kept in memory, so answering a MediaRequest
+/// doesn't read the disk on the tick thread. 64 MB
+///
+/// Whatever doesn't fit is read from disk every time it's asked for.
+///
+const MAX_CACHED_MEDIA_BYTES: usize = 64 * 1024 * 1024;
+
+///
+/// Every piece of media the enabled mods have, by it's SHA1 hash.
+///
+/// This is what a client's MediaRequest gets answered out of. Only the
+/// hash is ever looked up, so a request can't name a file off somewhere else.
+///
+#[derive(Default)]
+pub struct MediaIndex {
+  // hash -> path
+  paths: AHashMap<String, String>,
+  // hash -> bytes, for the media that fit in MAX_CACHED_MEDIA_BYTES.
+  cached: AHashMap<String, Vec<u8>>,
+  cached_bytes: usize,
+}
+
+impl MediaIndex {
+  ///
+  /// Hash the media folders of every mod.
+  ///
+  pub fn from_mods(mods: &[ModDirectory]) -> Self {
+    let mut new_media_index = MediaIndex::default();
+
+    for mod_directory in mods {
+      for folder in MEDIA_FOLDERS {
+        new_media_index.add_folder(&format!("{}/{}", mod_directory.mod_path, folder));
+      }
+    }
+
+    println!(
+      "MediaIndex: [{}] file(s) available to clients. [{}] bytes kept in memory.",
+      new_media_index.paths.len(),
+      new_media_index.cached_bytes
+    );
+
+    new_media_index
+  }
+
+  ///
+  /// Hash every file in a folder. A mod without the folder is fine.
+  ///
+  fn add_folder(&mut self, folder_path: &str) {
+    let raw_files = match read_dir(folder_path) {
+      Ok(raw_files) => raw_files,
+      Err(_) => return,
+    };
+
+    for file in raw_files.flatten() {
+      if !file.path().is_file() {
+        continue;
+      }
+      let path = match file.path().to_str() {
+        Some(path) => path.to_string(),
+        None => continue,
+      };
+      match read_file_to_byte_vec(&path) {
+        Ok(data) => {
+          let hash = hash_bytes(&data);
+          if self.cached_bytes + data.len() <= MAX_CACHED_MEDIA_BYTES {
+            self.cached_bytes += data.len();
+            self.cached.insert(hash.clone(), data);
+          }
+          self.paths.insert(hash, path);
+        }
+        Err(e) => println!("MediaIndex: {}", e),
+      }
+    }
+  }
+
+  ///
+  /// Get one MEDIA_CHUNK_BYTES chunk of a piece of media by it's SHA1 hash.
+  ///
+  /// Returns the chunk and how many chunks there are, or None if no mod
+  /// has it or there's no chunk that far in.
+  ///
+  pub fn get_chunk(&self, hash: &str, chunk: u32) -> Option<(Vec<u8>, u32)> {
+    if let Some(data) = self.cached.get(hash) {
+      return Self::split_chunk(data, chunk);
+    }
+
+    let path = self.paths.get(hash)?;
+    match read_file_to_byte_vec(path) {
+      Ok(data) => Self::split_chunk(&data, chunk),
+      Err(e) => {
+        println!("MediaIndex: {}", e);
+        None
+      }
+    }
+  }
+
+  ///
+  /// Cut a chunk out of some media. Empty media is still 1 chunk.
+  ///
+  fn split_chunk(data: &[u8], chunk: u32) -> Option<(Vec<u8>, u32)> {
+    let chunk_count = data.len().div_ceil(MEDIA_CHUNK_BYTES).max(1);
+    if chunk as usize >= chunk_count {
+      return None;
+    }
+    let start = chunk as usize * MEDIA_CHUNK_BYTES;
+    let end = (start + MEDIA_CHUNK_BYTES).min(data.len());
+    let chunk_count = u32::try_from(chunk_count).ok()?;
+    Some((data.get(start..end)?.to_vec(), chunk_count))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::game::{network_message::MEDIA_CHUNK_BYTES, server::media_index::MediaIndex};
+
+  #[test]
+  fn test_media_index_split_chunk() {
+    println!("--- BEGIN MEDIA INDEX SPLIT CHUNK TEST ---");
+    // Small media is one chunk.
+    assert_eq!(
+      MediaIndex::split_chunk(b"tiny", 0),
+      Some((b"tiny".to_vec(), 1))
+    );
+    assert_eq!(MediaIndex::split_chunk(b"tiny", 1), None);
+    assert_eq!(MediaIndex::split_chunk(b"", 0), Some((vec![], 1)));
+
+    // Two full chunks and a bit, the bit is the last chunk.
+    let data: Vec<u8> = (0..MEDIA_CHUNK_BYTES * 2 + 10)
+      .map(|index| index as u8)
+      .collect();
+    let mut joined = vec![];
+    for chunk in 0..3 {
+      match MediaIndex::split_chunk(&data, chunk) {
+        Some((bytes, 3)) => joined.extend(bytes),
+        other => panic!(
+          "Unit test is broken. Got [{:?}].",
+          other.map(|(_, count)| count)
+        ),
+      }
+    }
+    assert_eq!(joined, data);
+    assert_eq!(MediaIndex::split_chunk(&data, 3), None);
+    assert_eq!(MediaIndex::split_chunk(&data, u32::MAX), None);
+  }
+}
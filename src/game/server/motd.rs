@@ -0,0 +1,104 @@
+///
+/// The message of the day, from motd in minetest.conf.
+///
+/// A conf value is one line, \n in it starts another. These get filled in
+/// for whoever just joined:
+///
+/// {name} -> their name
+/// {players} -> how many players are on, them included
+/// {max_players} -> max_users
+/// {server_name} -> server_name
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Motd {
+  text: String,
+}
+
+impl Motd {
+  pub fn new(raw_motd: &str) -> Self {
+    Motd {
+      text: raw_motd.replace("\\n", "\n"),
+    }
+  }
+
+  ///
+  /// Get the MOTD without anything filled in, like for server browsers.
+  ///
+  pub fn get_text(&self) -> &str {
+    &self.text
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.text.trim().is_empty()
+  }
+
+  ///
+  /// Get the MOTD for a player that just joined. None if there's no MOTD.
+  ///
+  pub fn get_greeting(
+    &self,
+    name: &str,
+    players: u32,
+    max_players: u32,
+    server_name: &str,
+  ) -> Option<String> {
+    if self.is_empty() {
+      return None;
+    }
+
+    // One pass, so a name like {players} doesn't get filled in itself.
+    let mut greeting = String::with_capacity(self.text.len());
+    let mut rest = self.text.as_str();
+    while let Some(start) = rest.find('{') {
+      greeting.push_str(&rest[..start]);
+      rest = &rest[start..];
+      let placeholder = match rest.find('}') {
+        Some(end) => &rest[..=end],
+        None => break,
+      };
+      match placeholder {
+        "{name}" => greeting.push_str(name),
+        "{players}" => greeting.push_str(&players.to_string()),
+        "{max_players}" => greeting.push_str(&max_players.to_string()),
+        "{server_name}" => greeting.push_str(server_name),
+        // Not one of ours, it stays as written.
+        _ => {
+          greeting.push('{');
+          rest = &rest[1..];
+          continue;
+        }
+      }
+      rest = &rest[placeholder.len()..];
+    }
+    greeting.push_str(rest);
+    Some(greeting)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::game::server::motd::Motd;
+
+  #[test]
+  fn test_motd_greeting() {
+    println!("--- BEGIN MOTD GREETING TEST ---");
+    let motd = Motd::new("Hi {name}, welcome to {server_name}!\\n{players}/{max_players} online.");
+    assert_eq!(
+      motd.get_text(),
+      "Hi {name}, welcome to {server_name}!\n{players}/{max_players} online."
+    );
+    assert_eq!(
+      motd.get_greeting("sam", 3, 10, "Test"),
+      Some("Hi sam, welcome to Test!\n3/10 online.".to_string())
+    );
+
+    // A name that looks like a placeholder stays a name, unknown ones stay as written.
+    assert_eq!(
+      Motd::new("{name} {players} {nope} {").get_greeting("{players}", 1, 1, ""),
+      Some("{players} 1 {nope} {".to_string())
+    );
+
+    // Nothing to say, nothing gets sent.
+    assert_eq!(Motd::new("  ").get_greeting("sam", 1, 10, "Test"), None);
+  }
+}
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+
+use crate::{
+  file_utilities::file_exists, game::lua_engine::lua_file_helpers::get_game_mod_folders,
+};
+
+///
+/// The brightest a node can glow, like minetest C++'s LIGHT_MAX.
+///
+pub const LIGHT_MAX: u8 = 14;
+
+///
+/// How a node is drawn, the numbers match minetest.draw_type in api.lua.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawType {
+  Air,
+  Regular,
+  BlockBox,
+  Mesh,
+}
+
+impl DrawType {
+  pub fn from_number(number: u8) -> Option<Self> {
+    match number {
+      0 => Some(DrawType::Air),
+      1 => Some(DrawType::Regular),
+      2 => Some(DrawType::BlockBox),
+      3 => Some(DrawType::Mesh),
+      _ => None,
+    }
+  }
+}
+
+///
+/// Everything the engine knows about a kind of node.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeDefinition {
+  // "mod:name"
+  pub name: String,
+  pub description: String,
+  // File names, looked up in every mod's textures folder.
+  pub textures: Vec<String>,
+  pub drawtype: DrawType,
+  // If players collide with it.
+  pub walkable: bool,
+  // How much light it gives off. [0 - LIGHT_MAX]
+  pub light_source: u8,
+  // The mod that registered it.
+  pub mod_origin: String,
+}
+
+impl NodeDefinition {
+  ///
+  /// A plain regular node, fill in the rest with struct update syntax.
+  ///
+  pub fn new(name: &str) -> Self {
+    NodeDefinition {
+      name: name.to_string(),
+      description: String::new(),
+      textures: vec![],
+      drawtype: DrawType::Regular,
+      walkable: true,
+      light_source: 0,
+      mod_origin: String::new(),
+    }
+  }
+}
+
+///
+/// Every node registered on the Server, from every mod.
+///
+/// Mods fill this in through minetest.register_node while the game loads.
+/// It's rebuilt from scratch whenever the LuaEngine is.
+///
+pub struct NodeRegistry {
+  nodes: BTreeMap<String, NodeDefinition>,
+  // Where textures can come from.
+  texture_dirs: Vec<String>,
+}
+
+impl NodeRegistry {
+  pub fn new(texture_dirs: Vec<String>) -> Self {
+    NodeRegistry {
+      nodes: BTreeMap::new(),
+      texture_dirs,
+    }
+  }
+
+  ///
+  /// Create a NodeRegistry that can see the textures of every mod in a game.
+  ///
+  pub fn from_game(games_dir: &str, game_name: &str) -> Self {
+    Self::new(
+      get_game_mod_folders(games_dir, game_name)
+        .into_iter()
+        .map(|mod_directory| format!("{}/textures", mod_directory.mod_path))
+        .collect(),
+    )
+  }
+
+  ///
+  /// Check that a node name is "mod:name". Item names follow the same rules.
+  ///
+  /// Both halves can only have lowercase letters, digits and underscores,
+  /// same as a mod name.
+  ///
+  pub fn check_name(name: &str) -> Result<(), String> {
+    let is_valid_part = |part: &str| {
+      !part.is_empty()
+        && part
+          .chars()
+          .all(|c| matches!(c, 'a'..='z' | '0'..='9' | '_'))
+    };
+
+    match name.split_once(':') {
+      Some((mod_name, node_name)) if is_valid_part(mod_name) && is_valid_part(node_name) => Ok(()),
+      _ => Err(format!("Name [{}] has to look like [mod_name:name].", name)),
+    }
+  }
+
+  ///
+  /// Get the textures of a definition that aren't in any textures folder.
+  ///
+  pub fn get_missing_textures(&self, definition: &NodeDefinition) -> Vec<String> {
+    definition
+      .textures
+      .iter()
+      .filter(|texture| !self.has_texture(texture))
+      .cloned()
+      .collect()
+  }
+
+  ///
+  /// Check if a texture is in any mod's textures folder.
+  ///
+  pub fn has_texture(&self, texture: &str) -> bool {
+    self
+      .texture_dirs
+      .iter()
+      .any(|texture_dir| file_exists(&format!("{}/{}", texture_dir, texture)))
+  }
+
+  ///
+  /// Get every texture some node uses that no mod has, (node, texture).
+  ///
+  pub fn get_all_missing_textures(&self) -> Vec<(String, String)> {
+    self
+      .nodes
+      .values()
+      .flat_map(|definition| {
+        self
+          .get_missing_textures(definition)
+          .into_iter()
+          .map(|texture| (definition.name.clone(), texture))
+      })
+      .collect()
+  }
+
+  ///
+  /// Add a node.
+  ///
+  /// Fails on a bad name or one that's already taken. Missing textures
+  /// are only warned about, the node still gets registered.
+  ///
+  pub fn register(&mut self, definition: NodeDefinition) -> Result<(), String> {
+    Self::check_name(&definition.name)?;
+
+    if let Some(existing) = self.nodes.get(&definition.name) {
+      return Err(format!(
+        "Node [{}] was already registered by mod [{}].",
+        definition.name, existing.mod_origin
+      ));
+    }
+
+    for texture in self.get_missing_textures(&definition) {
+      println!(
+        "NodeRegistry: warning, node [{}] uses texture [{}], no mod has it.",
+        definition.name, texture
+      );
+    }
+
+    self.nodes.insert(definition.name.clone(), definition);
+    Ok(())
+  }
+
+  pub fn get(&self, name: &str) -> Option<&NodeDefinition> {
+    self.nodes.get(name)
+  }
+
+  ///
+  /// Get the name of every registered node, sorted.
+  ///
+  pub fn get_names(&self) -> Vec<String> {
+    self.nodes.keys().cloned().collect()
+  }
+
+  pub fn len(&self) -> usize {
+    self.nodes.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.nodes.is_empty()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::{create_dir_all, remove_dir_all, write};
+
+  use crate::{
+    file_utilities::test_dir,
+    game::server::node_registry::{NodeDefinition, NodeRegistry},
+  };
+
+  #[test]
+  fn test_node_registry_register() {
+    println!("--- BEGIN NODE REGISTRY REGISTER TEST ---");
+    let texture_dir = test_dir("node_registry_textures");
+    if let Err(e) = create_dir_all(&texture_dir) {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = write(format!("{}/stone.png", texture_dir), []) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    for bad_name in [
+      "stone",
+      ":stone",
+      "mod:",
+      "Mod:stone",
+      "a:b:c",
+      "mod:sto ne",
+    ] {
+      assert!(NodeRegistry::check_name(bad_name).is_err(), "{}", bad_name);
+    }
+
+    let mut registry = NodeRegistry::new(vec![texture_dir.clone()]);
+    let stone = NodeDefinition {
+      textures: vec!["stone.png".to_string(), "missing.png".to_string()],
+      mod_origin: "rocks".to_string(),
+      ..NodeDefinition::new("rocks:stone")
+    };
+    assert_eq!(registry.get_missing_textures(&stone), vec!["missing.png"]);
+
+    // A missing texture is only a warning.
+    assert!(registry.register(stone.clone()).is_ok());
+    assert!(registry
+      .register(NodeDefinition::new("rocks:stone"))
+      .is_err());
+    assert!(registry.register(NodeDefinition::new("bad name")).is_err());
+    assert_eq!(registry.get("rocks:stone"), Some(&stone));
+    assert_eq!(registry.len(), 1);
+
+    let _ = remove_dir_all(&texture_dir);
+  }
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeSet;
+
+use ahash::AHashMap;
+
+use crate::file_utilities::{create_dir_all, file_exists, read_file_to_string, write_file_atomic};
+
+///
+/// Every privilege that can be granted.
+///
+/// interact: Dig and place.
+/// shout: Talk in chat.
+/// kick: Kick players.
+/// ban: Ban and unban players.
+/// privs: Grant and revoke privileges.
+/// server: Shut the server down.
+/// teleport: Teleport yourself.
+/// noclip: Spectate, flying around without the speed limit.
+///
+pub const KNOWN_PRIVILEGES: [&str; 8] = [
+  "interact", "shout", "kick", "ban", "privs", "server", "teleport", "noclip",
+];
+
+///
+/// What every player gets without being granted anything.
+///
+/// This is default_privs in minetest.conf.
+///
+pub const DEFAULT_PRIVILEGES: &str = "interact, shout";
+
+///
+/// The privileges of every player in a world.
+///
+/// Stored in privileges.txt in the world directory, one player per line:
+/// name|privilege,privilege
+///
+/// Players that were never granted anything only have the defaults.
+///
+pub struct Privileges {
+  world_path: String,
+  default_privileges: BTreeSet<String>,
+  players: AHashMap<String, BTreeSet<String>>,
+}
+
+impl Privileges {
+  ///
+  /// Split a comma separated privilege list, skipping unknown privileges.
+  ///
+  fn parse_list(raw_privileges: &str) -> BTreeSet<String> {
+    let mut privileges = BTreeSet::new();
+    for privilege in raw_privileges.split(',') {
+      let privilege = privilege.trim();
+      if privilege.is_empty() {
+        continue;
+      }
+      if !KNOWN_PRIVILEGES.contains(&privilege) {
+        println!("Privileges: ignoring unknown privilege [{}].", privilege);
+        continue;
+      }
+      privileges.insert(privilege.to_string());
+    }
+    privileges
+  }
+
+  ///
+  /// Load the privileges of a world. A missing privileges.txt means
+  /// everyone only has the defaults.
+  ///
+  pub fn load(world_path: &str, default_privileges: &str) -> Self {
+    let mut new_privileges = Privileges {
+      world_path: world_path.to_owned(),
+      default_privileges: Self::parse_list(default_privileges),
+      players: AHashMap::new(),
+    };
+
+    let path = new_privileges.get_path();
+    if !file_exists(&path) {
+      return new_privileges;
+    }
+
+    let raw_players = match read_file_to_string(&path) {
+      Ok(raw_players) => raw_players,
+      Err(e) => panic!("Privileges: {}", e),
+    };
+
+    for line in raw_players.lines() {
+      if line.trim().is_empty() {
+        continue;
+      }
+
+      let (name, raw_privileges) = match line.split_once('|') {
+        Some((name, raw_privileges)) => (name.trim(), raw_privileges),
+        None => (line.trim(), ""),
+      };
+
+      if name.is_empty() {
+        println!("Privileges: ignoring malformed line [{}].", line);
+        continue;
+      }
+
+      new_privileges
+        .players
+        .insert(name.to_string(), Self::parse_list(raw_privileges));
+    }
+
+    println!(
+      "Privileges: loaded [{}] player(s).",
+      new_privileges.players.len()
+    );
+
+    new_privileges
+  }
+
+  ///
+  /// Get the path to privileges.txt.
+  ///
+  fn get_path(&self) -> String {
+    let mut path = self.world_path.clone();
+    path.push_str("/privileges.txt");
+    path
+  }
+
+  ///
+  /// Write the privileges out to disk.
+  ///
+  fn save(&self) -> Result<(), String> {
+    create_dir_all(&self.world_path)?;
+
+    // Sorted so the file doesn't shuffle around on every save.
+    let mut names: Vec<&String> = self.players.keys().collect();
+    names.sort();
+
+    let mut raw_players = String::new();
+    for name in names {
+      if let Some(privileges) = self.players.get(name) {
+        let privileges: Vec<&str> = privileges
+          .iter()
+          .map(|privilege| privilege.as_str())
+          .collect();
+        raw_players.push_str(&format!("{}|{}\n", name, privileges.join(",")));
+      }
+    }
+
+    write_file_atomic(&self.get_path(), raw_players.as_bytes())
+  }
+
+  ///
+  /// Get everyone that was ever granted or revoked something.
+  ///
+  pub fn get_names(&self) -> Vec<String> {
+    self.players.keys().cloned().collect()
+  }
+
+  ///
+  /// Get every privilege a player has, sorted.
+  ///
+  pub fn get(&self, name: &str) -> Vec<String> {
+    match self.players.get(name) {
+      Some(privileges) => privileges.iter().cloned().collect(),
+      None => self.default_privileges.iter().cloned().collect(),
+    }
+  }
+
+  ///
+  /// Check if a player has a privilege.
+  ///
+  pub fn has(&self, name: &str, privilege: &str) -> bool {
+    match self.players.get(name) {
+      Some(privileges) => privileges.contains(privilege),
+      None => self.default_privileges.contains(privilege),
+    }
+  }
+
+  ///
+  /// Give a player a privilege.
+  ///
+  /// Returns if they didn't already have it.
+  ///
+  pub fn grant(&mut self, name: &str, privilege: &str) -> Result<bool, String> {
+    if !KNOWN_PRIVILEGES.contains(&privilege) {
+      return Err(format!(
+        "Unknown privilege [{}]. Known privileges: {:?}",
+        privilege, KNOWN_PRIVILEGES
+      ));
+    }
+
+    // The first grant copies in the defaults so they aren't lost.
+    let privileges = self
+      .players
+      .entry(name.to_string())
+      .or_insert_with(|| self.default_privileges.clone());
+
+    if !privileges.insert(privilege.to_string()) {
+      return Ok(false);
+    }
+
+    self.save()?;
+    Ok(true)
+  }
+
+  ///
+  /// Take a privilege away from a player.
+  ///
+  /// Returns if they had it.
+  ///
+  pub fn revoke(&mut self, name: &str, privilege: &str) -> Result<bool, String> {
+    let privileges = self
+      .players
+      .entry(name.to_string())
+      .or_insert_with(|| self.default_privileges.clone());
+
+    if !privileges.remove(privilege) {
+      return Ok(false);
+    }
+
+    self.save()?;
+    Ok(true)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::remove_dir_all;
+
+  use crate::{
+    file_utilities::test_dir,
+    game::server::privileges::{Privileges, DEFAULT_PRIVILEGES},
+  };
+
+  #[test]
+  fn test_privileges_persistence() {
+    println!("--- BEGIN PRIVILEGES PERSISTENCE TEST ---");
+    let world_path = test_dir("privileges_persistence");
+
+    let mut privileges = Privileges::load(&world_path, DEFAULT_PRIVILEGES);
+    assert!(privileges.has("newbie", "shout"));
+    assert!(!privileges.has("newbie", "kick"));
+
+    assert_eq!(privileges.grant("moderator", "kick"), Ok(true));
+    assert_eq!(privileges.grant("moderator", "kick"), Ok(false));
+    assert!(privileges.grant("moderator", "fly").is_err());
+    assert_eq!(privileges.revoke("muted", "shout"), Ok(true));
+
+    // Survives a reload, and granting kept the defaults.
+    let privileges = Privileges::load(&world_path, DEFAULT_PRIVILEGES);
+    assert_eq!(
+      privileges.get("moderator"),
+      vec!["interact", "kick", "shout"]
+    );
+    assert!(!privileges.has("muted", "shout"));
+    assert!(privileges.has("muted", "interact"));
+
+    let _ = remove_dir_all(&world_path);
+  }
+}
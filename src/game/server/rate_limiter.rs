@@ -0,0 +1,148 @@
+use std::{
+  net::IpAddr,
+  time::{Duration, Instant},
+};
+
+use ahash::AHashMap;
+
+///
+/// If the RateLimiter is tracking more addresses than this,
+/// the stale ones get cleaned out.
+///
+const PRUNE_THRESHOLD: usize = 1024;
+
+///
+/// A fixed window rate limiter keyed by source address.
+///
+/// Each address gets a budget of requests per window. Once the
+/// budget is used up, every request is denied until the window
+/// rolls over.
+///
+pub struct RateLimiter {
+  max_requests: u32,
+  window: Duration,
+
+  // Address -> (window start, requests in this window)
+  windows: AHashMap<IpAddr, (Instant, u32)>,
+}
+
+impl RateLimiter {
+  pub fn new(max_requests: u32, window: Duration) -> Self {
+    RateLimiter {
+      max_requests,
+      window,
+
+      windows: AHashMap::new(),
+    }
+  }
+
+  ///
+  /// Check if an address is allowed to make another request.
+  ///
+  /// Counts the request against the address's budget.
+  ///
+  pub fn check(&mut self, address: IpAddr) -> bool {
+    self.check_at(address, Instant::now())
+  }
+
+  ///
+  /// check() but you supply the time. Makes the limiter testable.
+  ///
+  pub fn check_at(&mut self, address: IpAddr, now: Instant) -> bool {
+    if self.windows.len() > PRUNE_THRESHOLD {
+      self.prune(now);
+    }
+
+    let window = self.window;
+    let (window_start, requests) = self.windows.entry(address).or_insert((now, 0));
+
+    // The window rolled over, fresh budget.
+    if now.duration_since(*window_start) >= window {
+      *window_start = now;
+      *requests = 0;
+    }
+
+    if *requests >= self.max_requests {
+      return false;
+    }
+
+    *requests += 1;
+    true
+  }
+
+  ///
+  /// Give back a request check() counted, because it turned out fine.
+  ///
+  pub fn refund(&mut self, address: IpAddr) {
+    if let Some((_, requests)) = self.windows.get_mut(&address) {
+      *requests = requests.saturating_sub(1);
+    }
+  }
+
+  ///
+  /// Check if an address has used up its budget, without counting against it.
+  ///
+  pub fn is_limited(&self, address: IpAddr) -> bool {
+    self.is_limited_at(address, Instant::now())
+  }
+
+  ///
+  /// is_limited() but you supply the time.
+  ///
+  pub fn is_limited_at(&self, address: IpAddr, now: Instant) -> bool {
+    match self.windows.get(&address) {
+      Some((window_start, requests)) => {
+        now.duration_since(*window_start) < self.window && *requests >= self.max_requests
+      }
+      None => false,
+    }
+  }
+
+  ///
+  /// Forget every address whose window is over.
+  ///
+  fn prune(&mut self, now: Instant) {
+    let window = self.window;
+    self
+      .windows
+      .retain(|_, (window_start, _)| now.duration_since(*window_start) < window);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    net::{IpAddr, Ipv4Addr},
+    time::{Duration, Instant},
+  };
+
+  use crate::game::server::rate_limiter::RateLimiter;
+
+  #[test]
+  fn test_rate_limiter() {
+    println!("--- BEGIN RATE LIMITER TEST ---");
+    let mut rate_limiter = RateLimiter::new(2, Duration::from_secs(1));
+    let abuser = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let bystander = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+    let now = Instant::now();
+
+    assert!(rate_limiter.check_at(abuser, now));
+    assert!(rate_limiter.check_at(abuser, now));
+    assert!(!rate_limiter.check_at(abuser, now));
+    assert!(rate_limiter.is_limited_at(abuser, now));
+    assert!(!rate_limiter.is_limited_at(bystander, now));
+
+    // Other addresses have their own budget.
+    assert!(rate_limiter.check_at(bystander, now));
+
+    // A refund makes room for one more.
+    rate_limiter.refund(abuser);
+    assert!(!rate_limiter.is_limited_at(abuser, now));
+    assert!(rate_limiter.check_at(abuser, now));
+    assert!(!rate_limiter.check_at(abuser, now));
+
+    // The window rolls over.
+    assert!(!rate_limiter.is_limited_at(abuser, now + Duration::from_secs(1)));
+    assert!(rate_limiter.check_at(abuser, now + Duration::from_secs(1)));
+  }
+}
@@ -0,0 +1,191 @@
+use std::{
+  collections::VecDeque,
+  time::{Duration, Instant},
+};
+
+use crate::game::network_message::{NetworkMessage, Priority};
+
+///
+/// How many messages a session can have waiting to go out.
+///
+pub const SEND_QUEUE_CAPACITY: usize = 256;
+
+///
+/// How many messages a session gets sent per flush.
+///
+/// A Server flushes twice a tick, once after reading the network and
+/// once when the tick is done.
+///
+pub const SENDS_PER_FLUSH: usize = 64;
+
+///
+/// A session that stays full for this long can't keep up, it gets disconnected.
+///
+pub const SATURATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+///
+/// The outgoing messages of one session, waiting for their turn.
+///
+/// Once it's full, Low priority messages are dropped to make room for
+/// High priority ones. If it's nothing but High priority messages and
+/// another one comes in, or it stays full for SATURATION_TIMEOUT, the
+/// session can't keep up.
+///
+pub struct SendQueue {
+  messages: VecDeque<NetworkMessage>,
+  dropped: u64,
+  // Set by push() when it had to make room, cleared by take_ready().
+  was_full: bool,
+  // When the queue first started being full, every flush since.
+  full_since: Option<Instant>,
+}
+
+impl SendQueue {
+  pub fn new() -> Self {
+    SendQueue {
+      messages: VecDeque::new(),
+      dropped: 0,
+      was_full: false,
+      full_since: None,
+    }
+  }
+
+  ///
+  /// Queue a message up.
+  ///
+  /// Returns false if it's High priority and there's no Low priority
+  /// message left to drop for it. The session is hopeless then.
+  ///
+  pub fn push(&mut self, message: NetworkMessage) -> bool {
+    if self.messages.len() < SEND_QUEUE_CAPACITY {
+      self.messages.push_back(message);
+      return true;
+    }
+    self.was_full = true;
+
+    if message.get_priority() == Priority::Low {
+      self.dropped += 1;
+      return true;
+    }
+
+    // Make room by dropping the oldest Low priority message.
+    match self
+      .messages
+      .iter()
+      .position(|queued| queued.get_priority() == Priority::Low)
+    {
+      Some(index) => {
+        self.messages.remove(index);
+        self.dropped += 1;
+        self.messages.push_back(message);
+        true
+      }
+      None => false,
+    }
+  }
+
+  ///
+  /// Get the messages to send this flush, oldest first.
+  ///
+  pub fn take_ready(&mut self, now: Instant) -> Vec<NetworkMessage> {
+    self.full_since = match std::mem::take(&mut self.was_full) {
+      true => Some(self.full_since.unwrap_or(now)),
+      false => None,
+    };
+
+    let count = self.messages.len().min(SENDS_PER_FLUSH);
+    self.messages.drain(..count).collect()
+  }
+
+  ///
+  /// Get if the queue has been full for longer than SATURATION_TIMEOUT.
+  ///
+  pub fn is_saturated(&self, now: Instant) -> bool {
+    match self.full_since {
+      Some(full_since) => now.duration_since(full_since) >= SATURATION_TIMEOUT,
+      None => false,
+    }
+  }
+
+  ///
+  /// Get how many Low priority messages were dropped.
+  ///
+  pub fn get_dropped(&self) -> u64 {
+    self.dropped
+  }
+
+  pub fn len(&self) -> usize {
+    self.messages.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.messages.is_empty()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Instant;
+
+  use glam::Vec3A;
+
+  use crate::game::{
+    network_message::NetworkMessage,
+    server::send_queue::{SendQueue, SATURATION_TIMEOUT, SENDS_PER_FLUSH, SEND_QUEUE_CAPACITY},
+  };
+
+  #[test]
+  fn test_send_queue_backpressure() {
+    println!("--- BEGIN SEND QUEUE BACKPRESSURE TEST ---");
+    let now = Instant::now();
+    let mut queue = SendQueue::new();
+    let player_move = |x: f32| NetworkMessage::PlayerMove {
+      position: Vec3A::new(x, 0.0, 0.0),
+      rotation: Vec3A::ZERO,
+    };
+    let chat = |i: usize| NetworkMessage::ChatMessage(format!("{}", i));
+
+    // Fill it up with movement, then flood it with more of everything.
+    for i in 0..SEND_QUEUE_CAPACITY {
+      assert!(queue.push(player_move(i as f32)));
+    }
+    for i in 0..10 {
+      assert!(queue.push(player_move(-1.0)));
+      assert!(queue.push(chat(i)));
+    }
+    assert_eq!(queue.len(), SEND_QUEUE_CAPACITY);
+
+    // The new movement was dropped, and the oldest movement gave way to the chat.
+    assert_eq!(queue.get_dropped(), 20);
+    let mut sent = vec![];
+    while !queue.is_empty() {
+      let ready = queue.take_ready(now);
+      assert!(ready.len() <= SENDS_PER_FLUSH);
+      sent.extend(ready);
+    }
+    assert_eq!(sent[0], player_move(10.0));
+    assert!(!sent.contains(&player_move(-1.0)));
+    let chats: Vec<NetworkMessage> = sent
+      .into_iter()
+      .filter(|message| matches!(message, NetworkMessage::ChatMessage(_)))
+      .collect();
+    assert_eq!(chats, (0..10).map(chat).collect::<Vec<NetworkMessage>>());
+
+    // Nothing but chat and no room, it can't be kept.
+    for i in 0..SEND_QUEUE_CAPACITY {
+      assert!(queue.push(chat(i)));
+    }
+    assert!(!queue.push(chat(0)));
+
+    // Staying full gets it marked as saturated, draining clears it.
+    assert!(!queue.is_saturated(now));
+    queue.take_ready(now);
+    for _ in 0..SENDS_PER_FLUSH + 1 {
+      queue.push(player_move(0.0));
+    }
+    queue.take_ready(now + SATURATION_TIMEOUT);
+    assert!(queue.is_saturated(now + SATURATION_TIMEOUT));
+    queue.take_ready(now + SATURATION_TIMEOUT);
+    assert!(!queue.is_saturated(now + SATURATION_TIMEOUT));
+  }
+}
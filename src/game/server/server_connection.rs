@@ -1,11 +1,167 @@
-use std::{net::ToSocketAddrs, time::Duration};
+use std::{
+  fmt,
+  io::ErrorKind,
+  net::{SocketAddr, ToSocketAddrs},
+  time::{Duration, Instant},
+};
+#[cfg(unix)]
+use std::{fs::read_dir, os::fd::RawFd};
 
 use ahash::AHashMap;
+use glam::Vec3A;
 use message_io::{
   events::EventReceiver,
-  network::{Endpoint, Transport},
+  network::{Endpoint, SendStatus, Transport},
   node::{self, NodeHandler, NodeTask, StoredNetEvent, StoredNodeEvent},
 };
+#[cfg(unix)]
+use nix::sys::socket::{getsockname, getsockopt, setsockopt, sockopt, SockType, SockaddrStorage};
+
+use crate::game::{
+  connection_stats::{ConnectionStats, ConnectionTracker, ReceiveHistogram},
+  game_config::GameConfig,
+  network_message::NetworkMessage,
+  replay::Recorder,
+  serial::{deserialize_bounded, get_max_message_bytes, serialize_into},
+  transport_error::TransportError,
+};
+
+use super::{
+  auth::{auth_provider_from_config, AuthProvider, AuthResult},
+  ban_list::BanList,
+  media_index::MediaIndex,
+  motd::Motd,
+  rate_limiter::RateLimiter,
+  send_queue::SendQueue,
+  world_limits::WorldLimits,
+  CONSOLE_ISSUER,
+};
+
+///
+/// The longest name a player can have, like minetest C++.
+///
+pub const PLAYER_NAME_MAX: usize = 20;
+
+///
+/// Check that a player can join with this name.
+///
+/// Names are A-Z, a-z, 0-9, _ and -, at most PLAYER_NAME_MAX long, like
+/// minetest C++. They end up in files like auth.txt and ipban.txt, so
+/// anything else could forge lines in those.
+///
+pub fn check_player_name(name: &str) -> Result<(), String> {
+  if name.is_empty() {
+    return Err("Join with a name.".to_string());
+  }
+  if name.len() > PLAYER_NAME_MAX {
+    return Err(format!(
+      "Names can be at most [{}] characters long.",
+      PLAYER_NAME_MAX
+    ));
+  }
+  if !name
+    .chars()
+    .all(|character| character.is_ascii_alphanumeric() || character == '_' || character == '-')
+  {
+    return Err("Names can only have letters, numbers, _ and -.".to_string());
+  }
+  // The console runs commands with every privilege, nobody gets to look like it.
+  if name.eq_ignore_ascii_case(CONSOLE_ISSUER) {
+    return Err(format!("The name [{}] is reserved.", name));
+  }
+  Ok(())
+}
+
+///
+/// How many status requests a single address can make per window.
+///
+const STATUS_REQUESTS_PER_WINDOW: u32 = 5;
+
+///
+/// The status request rate limit window.
+///
+const STATUS_REQUEST_WINDOW: Duration = Duration::from_secs(5);
+
+///
+/// How many failed logins an address gets per window.
+///
+const FAILED_LOGINS_PER_WINDOW: u32 = 3;
+
+///
+/// The failed login rate limit window.
+///
+const FAILED_LOGIN_WINDOW: Duration = Duration::from_secs(60);
+
+///
+/// How many handshakes an address can start per window.
+///
+/// Checking a password costs an argon2 hash on the tick thread, a flood
+/// of handshakes would stall the server. Resends of a finished handshake,
+/// bad names and bans don't count.
+///
+const HANDSHAKES_PER_WINDOW: u32 = 5;
+
+///
+/// The handshake rate limit window.
+///
+const HANDSHAKE_WINDOW: Duration = Duration::from_secs(10);
+
+///
+/// How many media chunks an address can ask for per window.
+///
+/// Every chunk is a request of it's own. Past this they're dropped
+/// and the client asks again a bit later.
+///
+const MEDIA_REQUESTS_PER_WINDOW: u32 = 64;
+
+///
+/// The media request rate limit window.
+///
+const MEDIA_REQUEST_WINDOW: Duration = Duration::from_secs(1);
+
+///
+/// How far past the speed limit a move can go, for the jitter in when moves arrive. [nodes]
+///
+const MOVE_SLACK: f32 = 1.0;
+
+///
+/// The longest a player gets credited for between moves, see movement_speed_limit.
+///
+/// Without this, standing still for a minute would buy a minute's worth of speed.
+///
+const MAX_MOVE_GAP: f32 = 1.0;
+
+///
+/// Why a ServerConnection couldn't be created.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionError {
+  // The address:port couldn't be turned into a socket address.
+  AddressResolution { socket: String, reason: String },
+  // Something else is already listening there.
+  PortInUse(SocketAddr),
+  BindFailed { address: SocketAddr, reason: String },
+}
+
+impl fmt::Display for ConnectionError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ConnectionError::AddressResolution { socket, reason } => write!(
+        f,
+        "Failed to resolve [{}]. {} Check the address.",
+        socket, reason
+      ),
+      ConnectionError::PortInUse(address) => write!(
+        f,
+        "[{}] is already in use. Is another server running? Pick another port.",
+        address
+      ),
+      ConnectionError::BindFailed { address, reason } => {
+        write!(f, "Failed to bind [{}]. {}", address, reason)
+      }
+    }
+  }
+}
 
 ///
 /// ServerConnection and Server can be considered 1 entity.
@@ -15,59 +171,333 @@ use message_io::{
 pub struct ServerConnection {
   address: String,
   port: i32,
+  real_address: SocketAddr,
+
+  // What is reported to server browsers.
+  server_name: String,
+  server_description: String,
+  motd: Motd,
+  max_players: u32,
+  status_rate_limiter: RateLimiter,
+
+  // What a MediaRequest is answered out of, the Server fills it once the game loads.
+  media_index: MediaIndex,
+  media_rate_limiter: RateLimiter,
+
+  // Anything bigger is dropped before it's parsed, see max_message_bytes.
+  max_message_bytes: usize,
+  // Everything that goes out is serialized into this, so sending doesn't allocate.
+  send_buffer: Vec<u8>,
+
+  ban_list: BanList,
+  auth: Box<dyn AuthProvider>,
+  failed_logins: RateLimiter,
+  handshake_rate_limiter: RateLimiter,
 
   task: NodeTask,
   handler: NodeHandler<()>,
   event_receiver: EventReceiver<StoredNodeEvent<()>>,
   pub clients: AHashMap<Endpoint, String>,
+  // Only players with a session are tracked, a server browser never is.
+  trackers: AHashMap<Endpoint, ConnectionTracker>,
+  // The receive gaps of sessions that are over, so the overall one never goes down.
+  finished_receive_gaps: ReceiveHistogram,
+  // What's waiting to go out to each player, see SendQueue.
+  send_queues: AHashMap<Endpoint, SendQueue>,
+
+  // Names of the connected players that asked for a shutdown this tick.
+  // The Server checks their privileges.
+  pub shutdown_requests: Vec<String>,
+
+  // Chat from connected players, (name, message). The Server handles these.
+  pub chat_messages: Vec<(String, String)>,
+
+  // Players that finished the handshake since the Server last looked.
+  pub joined_players: Vec<String>,
 
-  // Multiple shutdown requests from valid endpoints can be sent in the same tick.
-  // We want to process them all.
-  pub shutdown_requests: Vec<Endpoint>,
+  // Players that left or got kicked since the Server last looked.
+  pub left_players: Vec<String>,
+
+  // The newest PlayerMove of each player, name -> (position, rotation).
+  player_positions: AHashMap<String, (Vec3A, Vec3A)>,
+  // The spawn point and the border every PlayerMove is held to.
+  world_limits: WorldLimits,
+  // How fast a player can move on their own, movement_speed_limit. [nodes per second]
+  // None takes any move that stays inside the border.
+  move_speed_limit: Option<f32>,
+  // When each player's position was last set, for the speed limit.
+  last_moves: AHashMap<String, Instant>,
+  // The packet sequence of each player's newest PlayerMove, MovePlayer acknowledges it.
+  acknowledged_moves: AHashMap<String, u32>,
+  // Players flying around freely, name -> where they left off.
+  // Their moves skip the speed limit, see set_spectating.
+  spectators: AHashMap<String, Vec3A>,
+  // Players asking to start or stop spectating, (name, enabled). The Server
+  // checks the privilege.
+  pub spectate_requests: Vec<(String, bool)>,
+
+  // Every packet that comes in is written into this, if it's recording.
+  recorder: Option<Recorder>,
 }
 
 impl ServerConnection {
-  pub fn new(address: String, port: i32) -> Self {
-    let socket_address = match Self::get_socket(&address, port).to_socket_addrs() {
+  pub fn new(
+    address: String,
+    port: i32,
+    config: &GameConfig,
+    world_path: &str,
+  ) -> Result<Self, ConnectionError> {
+    let socket = Self::get_socket(&address, port);
+    let socket_address = match socket.to_socket_addrs() {
       Ok(mut iter) => match iter.next() {
         Some(socket_address) => socket_address,
-        None => panic!("ServerConnection: Failed to get socket address. None available."),
+        None => {
+          return Err(Self::log_error(ConnectionError::AddressResolution {
+            socket,
+            reason: "No addresses available.".to_string(),
+          }))
+        }
       },
-      Err(e) => panic!(
-        "ServerConnection: Failed to apply address and port into socket address. {}",
-        e
-      ),
+      Err(e) => {
+        return Err(Self::log_error(ConnectionError::AddressResolution {
+          socket,
+          reason: format!("{}.", e),
+        }))
+      }
     };
     let transport_protocol = Transport::Udp;
 
     let (handler, listener) = node::split::<()>();
 
-    // todo: fixme: this is udp, why are we doing a match here?
     // todo: If this fails, the server probably doesn't have a network
     // todo: adapter! Why is it a server?!
-    match handler.network().listen(transport_protocol, socket_address) {
+    let real_address = match handler.network().listen(transport_protocol, socket_address) {
       Ok((id, real_address)) => {
         println!(
-          "ServerConnection connection created at id [{}], real address [{}]",
-          id, real_address
+          "ServerConnection [{}] created at id [{}], real address [{}]",
+          config.get_string("server_name", "Minetest server"),
+          id,
+          real_address
         );
+        real_address
       }
-      Err(e) => panic!("{}", e),
-    }
+      Err(e) => {
+        // The listener thread isn't running yet, but the handler still has to stop.
+        handler.stop();
+        return Err(Self::log_error(match e.kind() {
+          ErrorKind::AddrInUse => ConnectionError::PortInUse(socket_address),
+          _ => ConnectionError::BindFailed {
+            address: socket_address,
+            reason: e.to_string(),
+          },
+        }));
+      }
+    };
+
+    Self::apply_receive_buffer(config, real_address);
 
     let (task, event_receiver) = listener.enqueue();
 
-    ServerConnection {
+    Ok(ServerConnection {
       address,
       port,
+      real_address,
+
+      server_name: config.get_string("server_name", "Minetest server"),
+      server_description: config.get_string("server_description", ""),
+      motd: Motd::new(&config.get_string("motd", "")),
+      max_players: config.get_parsed("max_users", 15),
+      max_message_bytes: get_max_message_bytes(config),
+      send_buffer: vec![],
+      status_rate_limiter: RateLimiter::new(STATUS_REQUESTS_PER_WINDOW, STATUS_REQUEST_WINDOW),
+
+      media_index: MediaIndex::default(),
+      media_rate_limiter: RateLimiter::new(MEDIA_REQUESTS_PER_WINDOW, MEDIA_REQUEST_WINDOW),
+
+      ban_list: BanList::load(world_path),
+      auth: auth_provider_from_config(config, world_path),
+      failed_logins: RateLimiter::new(FAILED_LOGINS_PER_WINDOW, FAILED_LOGIN_WINDOW),
+      handshake_rate_limiter: RateLimiter::new(HANDSHAKES_PER_WINDOW, HANDSHAKE_WINDOW),
 
       task,
       handler,
       event_receiver,
       clients: AHashMap::new(),
+      trackers: AHashMap::new(),
+      finished_receive_gaps: ReceiveHistogram::default(),
+      send_queues: AHashMap::new(),
 
       shutdown_requests: vec![],
+      chat_messages: vec![],
+      joined_players: vec![],
+      left_players: vec![],
+
+      player_positions: AHashMap::new(),
+      world_limits: WorldLimits::from_config(config),
+      move_speed_limit: match config.get_parsed("movement_speed_limit", 0.0f32) {
+        limit if limit > 0.0 && limit.is_finite() => Some(limit),
+        _ => None,
+      },
+      last_moves: AHashMap::new(),
+      acknowledged_moves: AHashMap::new(),
+      spectators: AHashMap::new(),
+      spectate_requests: vec![],
+
+      recorder: None,
+    })
+  }
+
+  ///
+  /// Apply udp_recv_buffer_bytes from minetest.conf to the listening socket.
+  ///
+  /// Under load the OS default fills up between ticks and packets get
+  /// dropped. The OS is free to change the size, so what it actually
+  /// settled on is logged.
+  ///
+  /// ? Linux clamps it to net.core.rmem_max and then doubles it, and the
+  /// ? read back is the doubled size. Asking for 300000 with rmem_max at
+  /// ? 212992 reads back 425984, see get_usable_receive_buffer.
+  /// ? macOS clamps it to kern.ipc.maxsockbuf.
+  /// ? Windows isn't supported yet, see set_receive_buffer.
+  ///
+  fn apply_receive_buffer(config: &GameConfig, real_address: SocketAddr) {
+    if !config.has("udp_recv_buffer_bytes") {
+      return;
+    }
+    let requested = match config.get_parsed::<usize>("udp_recv_buffer_bytes", 0) {
+      0 => {
+        println!(
+          "ServerConnection: ignoring udp_recv_buffer_bytes [{}].",
+          config.get_string("udp_recv_buffer_bytes", "")
+        );
+        return;
+      }
+      requested => requested,
+    };
+
+    match Self::set_receive_buffer(real_address, requested) {
+      Ok(actual) => match Self::get_usable_receive_buffer(actual) {
+        usable if usable < requested => println!(
+          "ServerConnection: asked for a [{}] byte UDP receive buffer, the OS clamped it to [{}].",
+          requested, usable
+        ),
+        usable => println!(
+          "ServerConnection: UDP receive buffer is [{}] bytes.",
+          usable
+        ),
+      },
+      Err(e) => println!(
+        "ServerConnection: udp_recv_buffer_bytes [{}] not applied. {}",
+        requested, e
+      ),
+    }
+  }
+
+  ///
+  /// Get how much of a SO_RCVBUF read back is the buffer that was asked for.
+  ///
+  /// Linux keeps half of it for it's own bookkeeping and reads back double,
+  /// so that's halved. Everywhere else it's what was set.
+  ///
+  fn get_usable_receive_buffer(actual: usize) -> usize {
+    if cfg!(any(target_os = "linux", target_os = "android")) {
+      actual / 2
+    } else {
+      actual
+    }
+  }
+
+  ///
+  /// Set SO_RCVBUF on the UDP socket bound to local_address.
+  ///
+  /// Returns the size the OS settled on.
+  ///
+  /// message-io owns the socket and never hands it out, so it's found by
+  /// going through this process' open file descriptors for the one
+  /// bound to local_address.
+  ///
+  #[cfg(unix)]
+  fn set_receive_buffer(local_address: SocketAddr, size: usize) -> Result<usize, String> {
+    let file_descriptors = match read_dir("/dev/fd") {
+      Ok(file_descriptors) => file_descriptors,
+      Err(e) => return Err(format!("Failed to list file descriptors. {}", e)),
+    };
+
+    for file_descriptor in file_descriptors.flatten() {
+      let raw_fd = match file_descriptor.file_name().to_str() {
+        Some(name) => match name.parse::<RawFd>() {
+          Ok(raw_fd) => raw_fd,
+          Err(_) => continue,
+        },
+        None => continue,
+      };
+      // Anything that isn't a socket, or got closed in the meantime, just fails these.
+      let is_listener = matches!(
+        getsockopt(raw_fd, sockopt::SockType),
+        Ok(SockType::Datagram)
+      ) && matches!(
+        getsockname::<SockaddrStorage>(raw_fd),
+        Ok(address) if Self::get_socket_address(&address) == Some(local_address)
+      );
+      if !is_listener {
+        continue;
+      }
+
+      if let Err(e) = setsockopt(raw_fd, sockopt::RcvBuf, &size) {
+        return Err(format!("The OS refused it. {}", e));
+      }
+      return match getsockopt(raw_fd, sockopt::RcvBuf) {
+        Ok(actual) => Ok(actual),
+        Err(e) => Err(format!("Failed to read it back. {}", e)),
+      };
+    }
+
+    Err(format!("No UDP socket is bound to [{}].", local_address))
+  }
+
+  ///
+  /// Turn what getsockname found into a SocketAddr, if it's IPv4 or IPv6.
+  ///
+  #[cfg(unix)]
+  fn get_socket_address(address: &SockaddrStorage) -> Option<SocketAddr> {
+    if let Some(address) = address.as_sockaddr_in() {
+      return Some(SocketAddr::V4((*address).into()));
     }
+    address
+      .as_sockaddr_in6()
+      .map(|address| SocketAddr::V6((*address).into()))
+  }
+
+  ///
+  /// Set SO_RCVBUF on the UDP socket bound to local_address.
+  ///
+  /// todo: Windows has no /dev/fd to find message-io's socket in.
+  ///
+  #[cfg(not(unix))]
+  fn set_receive_buffer(_local_address: SocketAddr, _size: usize) -> Result<usize, String> {
+    Err("It can't be set on this platform yet.".to_string())
+  }
+
+  ///
+  /// Print a ConnectionError so it shows up even if the caller drops it.
+  ///
+  fn log_error(error: ConnectionError) -> ConnectionError {
+    println!("ServerConnection: {}", error);
+    error
+  }
+
+  ///
+  /// Swap out how players are authenticated.
+  ///
+  pub fn set_auth_provider(&mut self, auth: Box<dyn AuthProvider>) {
+    self.auth = auth;
+  }
+
+  ///
+  /// Start writing every incoming packet into a Recorder.
+  ///
+  pub fn set_recorder(&mut self, recorder: Recorder) {
+    self.recorder = Some(recorder);
   }
 
   ///
@@ -85,87 +515,1654 @@ impl ServerConnection {
   }
 
   ///
-  /// Construct the address & port into a parsable socket string.
+  /// Get the address the server connection actually bound to.
   ///
-  pub fn get_socket(address: &str, port: i32) -> String {
-    let mut socket = address.to_owned();
-    socket.push(':');
-    socket.push_str(port.to_string().as_str());
+  pub fn get_real_address(&self) -> SocketAddr {
+    self.real_address
+  }
 
-    socket
+  ///
+  /// Get the message of the day.
+  ///
+  pub fn get_motd(&self) -> String {
+    self.motd.get_text().to_string()
   }
 
   ///
-  /// Send raw data to an EndPoint (ClientConnection).
+  /// Get the MOTD filled in for a player that just joined, None if there isn't one.
   ///
-  fn send_data(&self, end_point: Endpoint, data: &str) {
-    self.handler.network().send(end_point, data.as_bytes());
+  pub fn get_greeting(&self, name: &str) -> Option<String> {
+    self.motd.get_greeting(
+      name,
+      self.get_player_count(),
+      self.max_players,
+      &self.server_name,
+    )
   }
 
   ///
-  /// A procedure to react to a network event.
+  /// Get the server name reported to server browsers.
   ///
-  /// Returns if the connection received the shutdown signal from a client
+  pub fn get_server_name(&self) -> String {
+    self.server_name.clone()
+  }
+
   ///
-  pub fn event_reaction(&mut self, event: StoredNetEvent) {
-    // We don't need to match, we're using UDP which is connectionless.
-    if let StoredNetEvent::Message(end_point, raw_message) = event {
-      // todo: use https://github.com/serde-rs/bytes
-      let receieved_string = match String::from_utf8(raw_message) {
-        Ok(new_string) => new_string,
-        Err(_) => {
-          println!("ServerConnection message buffer attack detected, bailing on deserialization!");
-          "".to_string()
-        }
-      };
+  /// Get how many players are connected.
+  ///
+  pub fn get_player_count(&self) -> u32 {
+    self.clients.len() as u32
+  }
 
-      println!("ServerConnection Server received message: {}", receieved_string);
+  ///
+  /// Get how many players are allowed to be connected.
+  ///
+  pub fn get_max_players(&self) -> u32 {
+    self.max_players
+  }
+
+  ///
+  /// Get the names of all connected players.
+  ///
+  pub fn get_player_names(&self) -> Vec<String> {
+    self.clients.values().cloned().collect()
+  }
+
+  ///
+  /// Get how the connection to a player is doing.
+  ///
+  /// None if that EndPoint doesn't have a session.
+  ///
+  pub fn get_connection_stats(&self, end_point: Endpoint) -> Option<ConnectionStats> {
+    self
+      .trackers
+      .get(&end_point)
+      .map(|tracker| tracker.get_stats(Instant::now()))
+  }
+
+  ///
+  /// Get the traffic over every session, (bytes in, bytes out) per second.
+  ///
+  pub fn get_bytes_per_second(&self) -> (f64, f64) {
+    let now = Instant::now();
+    self
+      .trackers
+      .values()
+      .map(|tracker| tracker.get_stats(now))
+      .fold((0.0, 0.0), |(bytes_in, bytes_out), stats| {
+        (
+          bytes_in + stats.bytes_in_per_second,
+          bytes_out + stats.bytes_out_per_second,
+        )
+      })
+  }
+
+  ///
+  /// Get the time between packets, over every session since the server started.
+  ///
+  /// See get_connection_stats for one player's.
+  ///
+  pub fn get_receive_gaps(&self) -> ReceiveHistogram {
+    let now = Instant::now();
+    let mut receive_gaps = self.finished_receive_gaps;
+    for tracker in self.trackers.values() {
+      receive_gaps.merge(&tracker.get_stats(now).receive_gaps);
+    }
+    receive_gaps
+  }
+
+  ///
+  /// Get where a player last said they were, (position, rotation).
+  ///
+  /// That's the spawn point until their first PlayerMove, and never outside the border.
+  ///
+  pub fn get_player_position(&self, name: &str) -> Option<(Vec3A, Vec3A)> {
+    self.player_positions.get(name).copied()
+  }
+
+  ///
+  /// Put a player somewhere and tell their client, like for /teleport.
+  ///
+  /// Returns where they ended up, inside the border. None if they aren't connected.
+  ///
+  pub fn teleport_player(&mut self, name: &str, position: Vec3A) -> Option<Vec3A> {
+    let end_point = self.get_player_end_point(name)?;
+    let position = self.world_limits.clamp(position);
+    let rotation = match self.player_positions.get(name) {
+      Some((_, rotation)) => *rotation,
+      None => Vec3A::ZERO,
+    };
+    self
+      .player_positions
+      .insert(name.to_string(), (position, rotation));
+    self.last_moves.insert(name.to_string(), Instant::now());
+    self.send_move_player(end_point, name, position);
+    Some(position)
+  }
 
-      match receieved_string.as_str() {
-        "hi" => self.send_data(end_point, "hi there!"),
-        "MINETEST_HAND_SHAKE" => self.send_data(end_point, "MINETEST_HAND_SHAKE_CONFIRMED"),
-        "MINETEST_PING_REQUEST" => {
-          println!("ServerConnection ServerConnection got ping request, sending confirmation to ClientConnection.");
-          self.send_data(end_point, "MINETEST_PING_CONFIRMATION")
+  ///
+  /// Start or stop a player spectating, and tell their client.
+  ///
+  /// Check the privilege first, this doesn't. While spectating their moves
+  /// skip the speed limit. Stopping puts them back where they started.
+  ///
+  /// Returns false if they aren't connected.
+  ///
+  pub fn set_spectating(&mut self, name: &str, enabled: bool) -> bool {
+    let end_point = match self.get_player_end_point(name) {
+      Some(end_point) => end_point,
+      None => return false,
+    };
+    match enabled {
+      true => {
+        if !self.spectators.contains_key(name) {
+          let position = match self.player_positions.get(name) {
+            Some((position, _)) => *position,
+            None => self.world_limits.get_spawn_point(),
+          };
+          self.spectators.insert(name.to_string(), position);
+        }
+      }
+      false => {
+        if let Some(position) = self.spectators.remove(name) {
+          self.teleport_player(name, position);
         }
-        "MINETEST_SHUT_DOWN_REQUEST" => self.shutdown_requests.push(end_point),
-        _ => (),
       }
     }
+    self.send_data(end_point, &NetworkMessage::Spectate { enabled });
+    true
+  }
+
+  pub fn is_spectating(&self, name: &str) -> bool {
+    self.spectators.contains_key(name)
   }
 
   ///
-  /// Non-blocking event receiver for network events.
+  /// Tell a client where the server put their player.
+  ///
+  fn send_move_player(&mut self, end_point: Endpoint, name: &str, position: Vec3A) {
+    let acknowledged = self.acknowledged_moves.get(name).copied().unwrap_or(0);
+    self.send_data(
+      end_point,
+      &NetworkMessage::MovePlayer {
+        position,
+        acknowledged,
+      },
+    );
+  }
+
+  pub fn get_world_limits(&self) -> &WorldLimits {
+    &self.world_limits
+  }
+
   ///
-  /// Returns the EndPoint (ClientConnection) that requested to shut
-  /// down the server.
+  /// Send a NetworkMessage to every connected player.
   ///
-  pub fn receive(&mut self) {
-    let mut has_new_event = true;
+  pub fn broadcast(&mut self, message: &NetworkMessage) {
+    let end_points: Vec<Endpoint> = self.clients.keys().copied().collect();
+    for end_point in end_points {
+      self.send_data(end_point, message);
+    }
+  }
 
-    // We want to grind through ALL the events.
-    while has_new_event {
-      if let Some(event) = self.event_receiver.receive_timeout(Duration::new(0, 0)) {
-        match event {
-          StoredNodeEvent::Network(new_event) => {
-            self.event_reaction(new_event.clone());
-          }
-          // todo: figure out what a signal is!
-          StoredNodeEvent::Signal(_) => todo!(),
-        }
-      } else {
-        has_new_event = false;
+  ///
+  /// Send a NetworkMessage to every connected player, except one.
+  ///
+  pub fn broadcast_except(&mut self, name: &str, message: &NetworkMessage) {
+    let end_points: Vec<Endpoint> = self
+      .clients
+      .iter()
+      .filter(|(_, client_name)| client_name.as_str() != name)
+      .map(|(end_point, _)| *end_point)
+      .collect();
+    for end_point in end_points {
+      self.send_data(end_point, message);
+    }
+  }
+
+  ///
+  /// Send a NetworkMessage to one connected player.
+  ///
+  /// Returns if the player was connected.
+  ///
+  pub fn send_to_player(&mut self, name: &str, message: &NetworkMessage) -> bool {
+    match self.get_player_end_point(name) {
+      Some(end_point) => {
+        self.send_data(end_point, message);
+        true
       }
+      None => false,
     }
   }
-}
 
-impl Drop for ServerConnection {
-  fn drop(&mut self) {
-    // ServerConnection must stop the handler entity or the Server
-    // will not shut down.
-    println!("ServerConnection: Shutting down network handler.");
-    NodeHandler::stop(&self.handler);
-    println!("ServerConnection dropped!");
+  ///
+  /// Find the EndPoint of a connected player.
+  ///
+  fn get_player_end_point(&self, name: &str) -> Option<Endpoint> {
+    self
+      .clients
+      .iter()
+      .find(|(_, client_name)| client_name.as_str() == name)
+      .map(|(end_point, _)| *end_point)
+  }
+
+  ///
+  /// Tell a player why they're being disconnected and drop their session.
+  ///
+  /// Returns if the player was connected.
+  ///
+  pub fn kick(&mut self, name: &str, reason: &str) -> bool {
+    let end_point = match self.get_player_end_point(name) {
+      Some(end_point) => end_point,
+      None => return false,
+    };
+
+    // Skip the line, whatever's queued up isn't going out anymore.
+    self.send_queues.remove(&end_point);
+    self.send_now(
+      end_point,
+      &NetworkMessage::Disconnect {
+        reason: reason.to_owned(),
+      },
+    );
+
+    // * UDP endpoints all share the listener's resource, there's nothing
+    // * to close. Forgetting the session is what disconnects them.
+    self.forget_session(end_point);
+
+    println!("ServerConnection: kicked [{}]. Reason: [{}]", name, reason);
+
+    true
+  }
+
+  ///
+  /// Ban a player by name, and by address if they're connected.
+  ///
+  /// They get kicked if they're online.
+  ///
+  pub fn ban(&mut self, name: &str, reason: &str) -> Result<(), String> {
+    let address = self
+      .get_player_end_point(name)
+      .map(|end_point| end_point.addr().ip());
+
+    self.ban_list.ban(name, address, reason)?;
+    self.kick(name, reason);
+
+    Ok(())
+  }
+
+  ///
+  /// Lift a ban by name.
+  ///
+  /// Returns if the player was banned.
+  ///
+  pub fn unban(&mut self, name: &str) -> Result<bool, String> {
+    self.ban_list.unban(name)
+  }
+
+  ///
+  /// A client wants to join.
+  ///
+  /// Bans are checked before a session exists, so a banned player never gets one.
+  /// Past max_users nobody new gets one either.
+  ///
+  fn handshake(&mut self, end_point: Endpoint, name: String, password: String) {
+    // The client resends it until it hears back, it only joins once.
+    if self.clients.get(&end_point) == Some(&name) {
+      self.send_data(end_point, &NetworkMessage::HandShakeConfirmed);
+      return;
+    }
+
+    if let Err(reason) = check_player_name(&name) {
+      println!(
+        "ServerConnection: rejected name [{}] from [{}]. {}",
+        name.escape_debug(),
+        end_point.addr(),
+        reason
+      );
+      self.send_data(end_point, &NetworkMessage::HandShakeRejected { reason });
+      return;
+    }
+
+    // A second session would share the first one's name keyed state, and
+    // kicking one would wipe it out from under the other.
+    if self.get_player_end_point(&name).is_some() {
+      println!(
+        "ServerConnection: rejected [{}] from [{}], they're already connected.",
+        name,
+        end_point.addr()
+      );
+      let rejection = NetworkMessage::HandShakeRejected {
+        reason: "Already connected.".to_string(),
+      };
+      self.send_data(end_point, &rejection);
+      return;
+    }
+
+    if self.clients.len() >= self.max_players as usize {
+      println!(
+        "ServerConnection: rejected [{}] from [{}], the server is full.",
+        name,
+        end_point.addr()
+      );
+      let rejection = NetworkMessage::HandShakeRejected {
+        reason: "The server is full.".to_string(),
+      };
+      self.send_data(end_point, &rejection);
+      return;
+    }
+
+    if let Some(ban) = self.ban_list.check(&name, end_point.addr().ip()) {
+      println!(
+        "ServerConnection: rejected banned player [{}] from [{}].",
+        name,
+        end_point.addr()
+      );
+      let rejection = NetworkMessage::HandShakeRejected {
+        reason: ban.reason.clone(),
+      };
+      self.send_data(end_point, &rejection);
+      return;
+    }
+
+    // Guessing passwords gets an address locked out for a while.
+    let address = end_point.addr().ip();
+    if self.failed_logins.is_limited(address) {
+      let rejection = NetworkMessage::HandShakeRejected {
+        reason: "Too many failed logins. Try again later.".to_string(),
+      };
+      self.send_data(end_point, &rejection);
+      return;
+    }
+
+    // Only the handshakes that get this far cost anything. Dropped without a
+    // reply, like spammed status requests.
+    if !self.handshake_rate_limiter.check(address) {
+      println!(
+        "ServerConnection: rate limiting handshakes from [{}].",
+        end_point.addr()
+      );
+      return;
+    }
+
+    match self.auth.authenticate(&name, &password) {
+      AuthResult::Accepted | AuthResult::Registered => (),
+      AuthResult::Rejected(reason) => {
+        println!(
+          "ServerConnection: failed login for [{}] from [{}]. {}",
+          name,
+          end_point.addr(),
+          reason
+        );
+        self.failed_logins.check(address);
+        self.send_data(end_point, &NetworkMessage::HandShakeRejected { reason });
+        return;
+      }
+      // Not their fault, so it doesn't count against the address.
+      AuthResult::Busy(reason) => {
+        self.send_data(end_point, &NetworkMessage::HandShakeRejected { reason });
+        return;
+      }
+    }
+
+    self.clients.insert(end_point, name.clone());
+    self.finish_tracker(end_point);
+    self
+      .trackers
+      .insert(end_point, ConnectionTracker::new(Instant::now()));
+    self.send_queues.insert(end_point, SendQueue::new());
+    let spawn_point = self.world_limits.get_spawn_point();
+    self
+      .player_positions
+      .insert(name.clone(), (spawn_point, Vec3A::ZERO));
+    self.last_moves.insert(name.clone(), Instant::now());
+    self.acknowledged_moves.remove(&name);
+    self.send_data(end_point, &NetworkMessage::HandShakeConfirmed);
+    self.send_move_player(end_point, &name, spawn_point);
+    self.joined_players.push(name);
+  }
+
+  ///
+  /// Construct the address & port into a parsable socket string.
+  ///
+  pub fn get_socket(address: &str, port: i32) -> String {
+    let mut socket = address.to_owned();
+    socket.push(':');
+    socket.push_str(port.to_string().as_str());
+
+    socket
+  }
+
+  ///
+  /// Send a NetworkMessage to an EndPoint (ClientConnection).
+  ///
+  /// Players with a session get it queued up, it goes out on the next
+  /// flush(). Without a session it goes out right away, as sequence 0.
+  ///
+  fn send_data(&mut self, end_point: Endpoint, message: &NetworkMessage) {
+    let queued = match self.send_queues.get_mut(&end_point) {
+      Some(send_queue) => send_queue.push(message.clone()),
+      None => return self.send_now(end_point, message),
+    };
+    if !queued {
+      self.disconnect_slow_player(end_point);
+    }
+  }
+
+  ///
+  /// Put a NetworkMessage on the wire, skipping the SendQueue.
+  ///
+  fn send_now(&mut self, end_point: Endpoint, message: &NetworkMessage) {
+    match self.trackers.get_mut(&end_point) {
+      Some(tracker) => {
+        serialize_into(&mut self.send_buffer, tracker.get_next_sequence(), message);
+        tracker.record_sent(self.send_buffer.len(), Instant::now());
+      }
+      None => serialize_into(&mut self.send_buffer, 0, message),
+    }
+    let status = self.handler.network().send(end_point, &self.send_buffer);
+    self.handle_send_status(end_point, status);
+  }
+
+  ///
+  /// Shrug off a busy socket, drop the session if it's gone for good.
+  ///
+  fn handle_send_status(&mut self, end_point: Endpoint, status: SendStatus) {
+    match TransportError::check_send(status) {
+      Ok(()) | Err(TransportError::Transient(_)) => (),
+      Err(e @ TransportError::Message(_)) => {
+        println!("ServerConnection: to [{}]. {}", end_point.addr(), e)
+      }
+      Err(e @ TransportError::Fatal(_)) => {
+        println!("ServerConnection: to [{}]. {}", end_point.addr(), e);
+        self.forget_session(end_point);
+      }
+    }
+  }
+
+  ///
+  /// Stop tracking a session, keeping it's receive gaps for get_receive_gaps.
+  ///
+  fn finish_tracker(&mut self, end_point: Endpoint) {
+    if let Some(tracker) = self.trackers.remove(&end_point) {
+      self
+        .finished_receive_gaps
+        .merge(&tracker.get_stats(Instant::now()).receive_gaps);
+    }
+  }
+
+  ///
+  /// Drop everything about a player's session. Returns their name, if they had one.
+  ///
+  fn forget_session(&mut self, end_point: Endpoint) -> Option<String> {
+    self.send_queues.remove(&end_point);
+    self.finish_tracker(end_point);
+    let name = self.clients.remove(&end_point)?;
+    self.player_positions.remove(&name);
+    self.last_moves.remove(&name);
+    self.acknowledged_moves.remove(&name);
+    self.spectators.remove(&name);
+    self.left_players.push(name.clone());
+    Some(name)
+  }
+
+  ///
+  /// Send what's waiting in every SendQueue, as much as the budget allows.
+  ///
+  /// Players that can't keep up are disconnected.
+  ///
+  pub fn flush(&mut self) {
+    self.flush_at(Instant::now());
+  }
+
+  ///
+  /// flush() but you supply the time. Makes the saturation testable.
+  ///
+  fn flush_at(&mut self, now: Instant) {
+    let mut ready = vec![];
+    let mut saturated = vec![];
+    for (end_point, send_queue) in self.send_queues.iter_mut() {
+      ready.push((*end_point, send_queue.take_ready(now)));
+      if send_queue.is_saturated(now) {
+        saturated.push(*end_point);
+      }
+    }
+
+    for (end_point, messages) in ready {
+      for message in messages {
+        self.send_now(end_point, &message);
+      }
+    }
+    for end_point in saturated {
+      self.disconnect_slow_player(end_point);
+    }
+  }
+
+  ///
+  /// Kick a player whose SendQueue can't keep up.
+  ///
+  fn disconnect_slow_player(&mut self, end_point: Endpoint) {
+    let name = match self.clients.get(&end_point) {
+      Some(name) => name.clone(),
+      None => return,
+    };
+    if let Some(send_queue) = self.send_queues.get(&end_point) {
+      println!(
+        "ServerConnection: [{}] can't keep up, [{}] messages were dropped.",
+        name,
+        send_queue.get_dropped()
+      );
+    }
+    self.kick(&name, "Too slow to keep up with the server.");
+  }
+
+  ///
+  /// Ping every player that's due, so their round trip stays up to date.
+  ///
+  fn send_heartbeats(&mut self) {
+    let now = Instant::now();
+    let pings: Vec<(Endpoint, u32)> = self
+      .trackers
+      .iter_mut()
+      .filter(|(_, tracker)| tracker.should_ping(now))
+      .map(|(end_point, tracker)| (*end_point, tracker.start_ping(now)))
+      .collect();
+    for (end_point, id) in pings {
+      self.send_data(end_point, &NetworkMessage::PingRequest { id });
+    }
+  }
+
+  ///
+  /// Answer a server browser query.
+  ///
+  /// This is answered regardless of handshake state and does not create
+  /// a session, so a query never takes up a player slot.
+  ///
+  fn send_status(&mut self, end_point: Endpoint) {
+    // The response is bigger than the request, so this could be used to
+    // amplify traffic at a spoofed address. Limit it.
+    if !self.status_rate_limiter.check(end_point.addr().ip()) {
+      println!(
+        "ServerConnection: rate limiting status requests from [{}].",
+        end_point.addr()
+      );
+      return;
+    }
+
+    let status = NetworkMessage::StatusResponse {
+      name: self.server_name.clone(),
+      description: self.server_description.clone(),
+      motd: self.get_motd(),
+      players: self.get_player_count(),
+      max_players: self.max_players,
+      version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+
+    self.send_data(end_point, &status);
+  }
+
+  ///
+  /// Answer a client asking for a chunk of media it doesn't have.
+  ///
+  /// A chunk is never more than MEDIA_CHUNK_BYTES, so media of any size
+  /// gets through a default max_message_bytes.
+  ///
+  fn send_media(&mut self, end_point: Endpoint, hash: &str, chunk: u32) {
+    if !self.clients.contains_key(&end_point) {
+      return;
+    }
+
+    if !self.media_rate_limiter.check(end_point.addr().ip()) {
+      println!(
+        "ServerConnection: rate limiting media requests from [{}].",
+        end_point.addr()
+      );
+      return;
+    }
+
+    let (data, chunk_count) = match self.media_index.get_chunk(hash, chunk) {
+      Some(chunk) => chunk,
+      None => {
+        println!(
+          "ServerConnection: no media [{}] chunk [{}] to send.",
+          hash, chunk
+        );
+        return;
+      }
+    };
+
+    let media = NetworkMessage::Media {
+      hash: hash.to_string(),
+      chunk,
+      chunk_count,
+      data,
+    };
+    self.send_data(end_point, &media);
+  }
+
+  ///
+  /// Set the media a MediaRequest is answered out of.
+  ///
+  pub fn set_media_index(&mut self, media_index: MediaIndex) {
+    self.media_index = media_index;
+  }
+
+  ///
+  /// A procedure to react to a network event.
+  ///
+  /// Returns if the connection received the shutdown signal from a client
+  ///
+  pub fn event_reaction(&mut self, event: StoredNetEvent) {
+    // We don't need to match, we're using UDP which is connectionless.
+    if let StoredNetEvent::Message(end_point, raw_message) = event {
+      let packet = match deserialize_bounded(&raw_message, self.max_message_bytes) {
+        Ok(packet) => packet,
+        Err(e) => {
+          println!(
+            "ServerConnection: bad message from [{}]. {}",
+            end_point.addr(),
+            e
+          );
+          return;
+        }
+      };
+
+      // Before the Sequencer, so a replay gets the same duplicates and reordering.
+      if let Some(recorder) = &self.recorder {
+        recorder.record_packet(end_point.addr(), &packet, Instant::now());
+      }
+
+      // Players with a session get their packets deduplicated and put in order.
+      let sequence = packet.sequence;
+      let messages = match self.trackers.get_mut(&end_point) {
+        Some(tracker) => tracker.receive(packet, raw_message.len(), Instant::now()),
+        None => vec![packet.message],
+      };
+      for message in messages {
+        // Latest is never held back, so a PlayerMove is always from this packet.
+        if let (NetworkMessage::PlayerMove { .. }, Some(name)) =
+          (&message, self.clients.get(&end_point))
+        {
+          self.acknowledged_moves.insert(name.clone(), sequence);
+        }
+        self.handle_message(end_point, message);
+      }
+    }
+  }
+
+  ///
+  /// Act on one message, after the ConnectionTracker let it through.
+  ///
+  fn handle_message(&mut self, end_point: Endpoint, message: NetworkMessage) {
+    println!("ServerConnection Server received message: {:?}", message);
+
+    match message {
+      NetworkMessage::Hi => self.send_data(end_point, &NetworkMessage::HiThere),
+      NetworkMessage::HandShake { name, password } => self.handshake(end_point, name, password),
+      NetworkMessage::PingRequest { id } => {
+        println!("ServerConnection ServerConnection got ping request, sending confirmation to ClientConnection.");
+        self.send_data(end_point, &NetworkMessage::PingConfirmation { id })
+      }
+      NetworkMessage::PingConfirmation { id } => {
+        if let Some(tracker) = self.trackers.get_mut(&end_point) {
+          tracker.finish_ping(id, Instant::now());
+        }
+      }
+      // Only players that finished the handshake get to ask.
+      NetworkMessage::ShutDownRequest => {
+        if let Some(name) = self.clients.get(&end_point) {
+          self.shutdown_requests.push(name.clone());
+        }
+      }
+      // The player is leaving, forget the session so the slot frees up.
+      NetworkMessage::Disconnect { reason } => {
+        if let Some(name) = self.forget_session(end_point) {
+          println!("ServerConnection: [{}] left. Reason: [{}]", name, reason);
+        }
+      }
+      NetworkMessage::StatusRequest => self.send_status(end_point),
+      NetworkMessage::MediaRequest { hash, chunk } => self.send_media(end_point, &hash, chunk),
+      // Only players that finished the handshake get to talk.
+      NetworkMessage::ChatMessage(message) => {
+        if let Some(name) = self.clients.get(&end_point) {
+          self.chat_messages.push((name.clone(), message));
+        }
+      }
+      NetworkMessage::PlayerMove { position, rotation } => {
+        self.move_player(end_point, position, rotation)
+      }
+      NetworkMessage::Spectate { enabled } => {
+        if let Some(name) = self.clients.get(&end_point) {
+          self.spectate_requests.push((name.clone(), enabled));
+        }
+      }
+      _ => (),
+    }
+  }
+
+  ///
+  /// Take a player's word for where they are, as long as it's inside the border
+  /// and, if there's a movement_speed_limit, they didn't go faster than it.
+  ///
+  /// A player outside of it is put back on the edge, one going too fast only
+  /// gets as far as the limit allows. Either way they're told so. Spectators
+  /// skip the speed limit, but not the border.
+  ///
+  fn move_player(&mut self, end_point: Endpoint, position: Vec3A, rotation: Vec3A) {
+    let name = match self.clients.get(&end_point) {
+      Some(name) => name.clone(),
+      None => return,
+    };
+    if !position.is_finite() || !rotation.is_finite() {
+      println!(
+        "ServerConnection: [{}] sent a broken move, ignoring it.",
+        name
+      );
+      return;
+    }
+
+    let mut clamped = self.world_limits.clamp(position);
+    let now = Instant::now();
+    if let (Some(limit), Some((last_position, _)), Some(last_move), false) = (
+      self.move_speed_limit,
+      self.player_positions.get(&name),
+      self.last_moves.get(&name),
+      self.spectators.contains_key(&name),
+    ) {
+      let gap = now
+        .duration_since(*last_move)
+        .as_secs_f32()
+        .min(MAX_MOVE_GAP);
+      let max_distance = limit * gap + MOVE_SLACK;
+      let offset = clamped - *last_position;
+      if offset.length() > max_distance {
+        clamped = *last_position + offset.normalize_or_zero() * max_distance;
+      }
+    }
+    self
+      .player_positions
+      .insert(name.clone(), (clamped, rotation));
+    self.last_moves.insert(name.clone(), now);
+    if clamped != position {
+      self.send_move_player(end_point, &name, clamped);
+    }
+  }
+
+  ///
+  /// Handle the Ordered messages that are done waiting on lost packets.
+  ///
+  fn release_held_messages(&mut self) {
+    let now = Instant::now();
+    let ready: Vec<(Endpoint, NetworkMessage)> = self
+      .trackers
+      .iter_mut()
+      .flat_map(|(end_point, tracker)| {
+        let end_point = *end_point;
+        tracker
+          .take_ready(now)
+          .into_iter()
+          .map(move |message| (end_point, message))
+      })
+      .collect();
+    for (end_point, message) in ready {
+      self.handle_message(end_point, message);
+    }
+  }
+
+  ///
+  /// Non-blocking event receiver for network events.
+  ///
+  /// Returns how many network events were processed.
+  ///
+  pub fn receive(&mut self) -> usize {
+    let mut has_new_event = true;
+    let mut event_count = 0;
+
+    // We want to grind through ALL the events.
+    while has_new_event {
+      if let Some(event) = self.event_receiver.receive_timeout(Duration::new(0, 0)) {
+        match event {
+          StoredNodeEvent::Network(new_event) => {
+            self.event_reaction(new_event.clone());
+            event_count += 1;
+          }
+          // Nothing sends signals, the handler is only used for the network.
+          StoredNodeEvent::Signal(_) => (),
+        }
+      } else {
+        has_new_event = false;
+      }
+    }
+
+    self.release_held_messages();
+    self.send_heartbeats();
+    self.flush();
+
+    if let Some(recorder) = &self.recorder {
+      recorder.flush();
+    }
+
+    event_count
+  }
+}
+
+impl Drop for ServerConnection {
+  fn drop(&mut self) {
+    // ServerConnection must stop the handler entity or the Server
+    // will not shut down.
+    println!("ServerConnection: Shutting down network handler.");
+    NodeHandler::stop(&self.handler);
+    println!("ServerConnection dropped!");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs::remove_dir_all,
+    net::UdpSocket,
+    time::{Duration, Instant},
+  };
+
+  use glam::Vec3A;
+  use message_io::network::SendStatus;
+
+  use crate::{
+    file_utilities::{create_dir_all, test_dir, write_file_atomic},
+    game::{
+      game_config::GameConfig,
+      lua_engine::lua_file_helpers::ModDirectory,
+      network_message::{NetworkMessage, MEDIA_CHUNK_BYTES},
+      server::{
+        auth::PasswordAuth,
+        media_index::MediaIndex,
+        send_queue::{SATURATION_TIMEOUT, SENDS_PER_FLUSH, SEND_QUEUE_CAPACITY},
+        server_connection::{
+          ConnectionError, ServerConnection, FAILED_LOGINS_PER_WINDOW, HANDSHAKES_PER_WINDOW,
+          MEDIA_REQUESTS_PER_WINDOW,
+        },
+      },
+      test_client::TestClient,
+    },
+    media_hash::hash_bytes,
+  };
+
+  ///
+  /// Start a ServerConnection on a random localhost port.
+  ///
+  fn start_server(config: &GameConfig, world: &str) -> ServerConnection {
+    match ServerConnection::new("127.0.0.1".to_string(), 0, config, world) {
+      Ok(server) => server,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+  }
+
+  #[test]
+  fn test_server_connection_status_request() {
+    println!("--- BEGIN SERVER CONNECTION STATUS REQUEST TEST ---");
+    let config = GameConfig::parse(
+      "server_name = Test\nserver_description = A test server.\nmotd = Welcome!\nmax_users = 7",
+    );
+    let world = test_dir("server_connection_status_request");
+    let mut server = start_server(&config, &world);
+
+    // One real player.
+    let mut player = TestClient::new(server.get_real_address());
+    player.send_handshake("player");
+    assert_eq!(
+      player.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeConfirmed)
+    );
+
+    // And a server browser which never shakes hands.
+    let mut browser = TestClient::new(server.get_real_address());
+    browser.send(&NetworkMessage::StatusRequest);
+    assert_eq!(
+      browser.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::StatusResponse {
+        name: "Test".to_string(),
+        description: "A test server.".to_string(),
+        motd: "Welcome!".to_string(),
+        players: 1,
+        max_players: 7,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+      })
+    );
+
+    // The query did not take up a player slot.
+    assert_eq!(server.clients.len(), 1);
+
+    let _ = remove_dir_all(&world);
+  }
+
+  #[test]
+  fn test_server_connection_full() {
+    println!("--- BEGIN SERVER CONNECTION FULL TEST ---");
+    let world = test_dir("server_connection_full");
+    let mut server = start_server(&GameConfig::parse("max_users = 1"), &world);
+
+    let mut player = TestClient::new(server.get_real_address());
+    player.send_handshake("player");
+    assert_eq!(
+      player.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeConfirmed)
+    );
+
+    // max_players in the status is what gets enforced.
+    let mut latecomer = TestClient::new(server.get_real_address());
+    latecomer.send_handshake("latecomer");
+    assert_eq!(
+      latecomer.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeRejected {
+        reason: "The server is full.".to_string()
+      })
+    );
+    assert_eq!(server.get_player_count(), 1);
+
+    // A slot opens up when someone leaves.
+    assert!(server.kick("player", "Bye."));
+    latecomer.send_handshake("latecomer");
+    assert_eq!(
+      latecomer.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeConfirmed)
+    );
+
+    drop(server);
+    let _ = remove_dir_all(&world);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_server_connection_receive_buffer() {
+    println!("--- BEGIN SERVER CONNECTION RECEIVE BUFFER TEST ---");
+    let world = test_dir("server_connection_receive_buffer");
+    let server = start_server(&GameConfig::parse("udp_recv_buffer_bytes = 4096"), &world);
+
+    // Well under the OS default and any clamp, so it really got smaller.
+    // Linux hands back double what it was asked for.
+    match ServerConnection::set_receive_buffer(server.get_real_address(), 4096) {
+      Ok(actual) => {
+        assert!((4096..=8192).contains(&actual), "got [{}]", actual);
+        assert_eq!(ServerConnection::get_usable_receive_buffer(actual), 4096);
+      }
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+
+    // Asking for 300000 past a 212992 rmem_max is a clamp, not a bigger buffer.
+    #[cfg(target_os = "linux")]
+    assert_eq!(ServerConnection::get_usable_receive_buffer(425984), 212992);
+
+    // Nothing is listening there.
+    let nowhere = match "127.0.0.1:1".parse() {
+      Ok(nowhere) => nowhere,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    assert!(ServerConnection::set_receive_buffer(nowhere, 4096).is_err());
+
+    let _ = remove_dir_all(&world);
+  }
+
+  #[test]
+  fn test_server_connection_media_request() {
+    println!("--- BEGIN SERVER CONNECTION MEDIA REQUEST TEST ---");
+    let world = test_dir("server_connection_media_request");
+    let texture = b"not really a png".to_vec();
+    let hash = hash_bytes(&texture);
+    // Two and a bit chunks.
+    let model: Vec<u8> = (0..MEDIA_CHUNK_BYTES * 2 + 10)
+      .map(|index| index as u8)
+      .collect();
+    let model_hash = hash_bytes(&model);
+    for folder in ["textures", "models"] {
+      if let Err(e) = create_dir_all(&format!("{}/rocks/{}", world, folder)) {
+        panic!("Unit test is broken. {}", e);
+      }
+    }
+    if let Err(e) = write_file_atomic(&format!("{}/rocks/textures/rocks.png", world), &texture) {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = write_file_atomic(&format!("{}/rocks/models/rock.obj", world), &model) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let mut server = start_server(&GameConfig::new(), &world);
+    server.set_media_index(MediaIndex::from_mods(&[ModDirectory {
+      mod_name: "rocks".to_string(),
+      mod_path: format!("{}/rocks", world),
+      depends: vec![],
+    }]));
+
+    // Without a session it's ignored, the status reply is the first answer.
+    let mut browser = TestClient::new(server.get_real_address());
+    browser.send(&NetworkMessage::MediaRequest {
+      hash: hash.clone(),
+      chunk: 0,
+    });
+    browser.send(&NetworkMessage::StatusRequest);
+    assert!(matches!(
+      browser.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::StatusResponse { .. })
+    ));
+
+    let mut player = TestClient::new(server.get_real_address());
+    player.send_handshake("player");
+    assert_eq!(
+      player.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeConfirmed)
+    );
+
+    // Media no mod has gets no answer either.
+    player.send(&NetworkMessage::MediaRequest {
+      hash: hash_bytes(b"missing"),
+      chunk: 0,
+    });
+    player.send(&NetworkMessage::MediaRequest {
+      hash: hash.clone(),
+      chunk: 0,
+    });
+    assert_eq!(
+      player.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::Media {
+        hash,
+        chunk: 0,
+        chunk_count: 1,
+        data: texture,
+      })
+    );
+
+    // Bigger media comes a chunk at a time, past the end there's nothing.
+    player.send(&NetworkMessage::MediaRequest {
+      hash: model_hash.clone(),
+      chunk: 3,
+    });
+    player.send(&NetworkMessage::MediaRequest {
+      hash: model_hash.clone(),
+      chunk: 2,
+    });
+    assert_eq!(
+      player.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::Media {
+        hash: model_hash.clone(),
+        chunk: 2,
+        chunk_count: 3,
+        data: model[MEDIA_CHUNK_BYTES * 2..].to_vec(),
+      })
+    );
+
+    // Asking too fast gets some of them dropped.
+    for _ in 0..MEDIA_REQUESTS_PER_WINDOW * 2 {
+      player.send(&NetworkMessage::MediaRequest {
+        hash: model_hash.clone(),
+        chunk: 0,
+      });
+    }
+    let mut answers = 0;
+    while player
+      .wait_for_reply(|| {
+        server.receive();
+      })
+      .is_some()
+    {
+      answers += 1;
+    }
+    assert!(answers > 0);
+    assert!(answers <= MEDIA_REQUESTS_PER_WINDOW, "got [{}]", answers);
+
+    drop(server);
+    let _ = remove_dir_all(&world);
+  }
+
+  #[test]
+  fn test_server_connection_kick() {
+    println!("--- BEGIN SERVER CONNECTION KICK TEST ---");
+    let world = test_dir("server_connection_kick");
+    let mut server = start_server(&GameConfig::new(), &world);
+
+    let mut player = TestClient::new(server.get_real_address());
+    player.send_handshake("rude_player");
+    assert_eq!(
+      player.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeConfirmed)
+    );
+    assert_eq!(server.get_player_names(), vec!["rude_player".to_string()]);
+
+    assert!(!server.kick("nobody", "no reason"));
+    assert!(server.kick("rude_player", "Be nice."));
+
+    // They're told why, and their session is gone.
+    assert_eq!(
+      player.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::Disconnect {
+        reason: "Be nice.".to_string()
+      })
+    );
+    assert_eq!(server.get_player_count(), 0);
+  }
+
+  #[test]
+  fn test_server_connection_duplicate_name() {
+    println!("--- BEGIN SERVER CONNECTION DUPLICATE NAME TEST ---");
+    let world = test_dir("server_connection_duplicate_name");
+    let mut server = start_server(&GameConfig::new(), &world);
+
+    let mut first = TestClient::new(server.get_real_address());
+    first.send_handshake("twin");
+    assert_eq!(
+      first.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeConfirmed)
+    );
+
+    // Another endpoint can't join under the same name.
+    let mut second = TestClient::new(server.get_real_address());
+    second.send_handshake("twin");
+    assert_eq!(
+      second.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeRejected {
+        reason: "Already connected.".to_string()
+      })
+    );
+    assert_eq!(server.get_player_count(), 1);
+
+    // The first one is who gets kicked, then the name is free again.
+    assert!(server.kick("twin", "Bye."));
+    assert_eq!(
+      first.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::Disconnect {
+        reason: "Bye.".to_string()
+      })
+    );
+    assert_eq!(server.get_player_count(), 0);
+    second.send_handshake("twin");
+    assert_eq!(
+      second.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeConfirmed)
+    );
+  }
+
+  #[test]
+  fn test_server_connection_duplicate_login() {
+    println!("--- BEGIN SERVER CONNECTION DUPLICATE LOGIN TEST ---");
+    let world = test_dir("server_connection_duplicate_login");
+    let mut server = start_server(&GameConfig::new(), &world);
+    server.set_auth_provider(Box::new(PasswordAuth::load(&world, true)));
+
+    let mut first = TestClient::new(server.get_real_address());
+    first.send_login("alice", "hunter2");
+    assert_eq!(
+      first.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeConfirmed)
+    );
+    first.send(&NetworkMessage::PlayerMove {
+      position: Vec3A::new(4.0, 0.0, 0.0),
+      rotation: Vec3A::ZERO,
+    });
+    let start = Instant::now();
+    while server.get_player_position("alice") != Some((Vec3A::new(4.0, 0.0, 0.0), Vec3A::ZERO))
+      && start.elapsed() < Duration::from_secs(2)
+    {
+      server.receive();
+    }
+
+    // The right password still doesn't take over a live player.
+    let mut second = TestClient::new(server.get_real_address());
+    second.send_login("alice", "hunter2");
+    assert_eq!(
+      second.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeRejected {
+        reason: "Already connected.".to_string()
+      })
+    );
+    assert_eq!(server.get_player_count(), 1);
+    assert_eq!(
+      server.get_player_position("alice"),
+      Some((Vec3A::new(4.0, 0.0, 0.0), Vec3A::ZERO))
+    );
+
+    // The session is still the first client's.
+    assert!(server.kick("alice", "Bye."));
+    assert_eq!(
+      first.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::Disconnect {
+        reason: "Bye.".to_string()
+      })
+    );
+
+    drop(server);
+    let _ = remove_dir_all(&world);
+  }
+
+  #[test]
+  fn test_server_connection_shutdown_request() {
+    println!("--- BEGIN SERVER CONNECTION SHUTDOWN REQUEST TEST ---");
+    let world = test_dir("server_connection_shutdown_request");
+    let mut server = start_server(&GameConfig::new(), &world);
+
+    // A stranger's request never reaches the Server.
+    let stranger = TestClient::new(server.get_real_address());
+    stranger.send(&NetworkMessage::ShutDownRequest);
+    let start = Instant::now();
+    while server.receive() == 0 && start.elapsed() < Duration::from_secs(2) {}
+    assert!(server.shutdown_requests.is_empty());
+
+    // A player's does, by name.
+    let mut player = TestClient::new(server.get_real_address());
+    player.send_handshake("player");
+    assert_eq!(
+      player.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeConfirmed)
+    );
+    player.send(&NetworkMessage::ShutDownRequest);
+    let start = Instant::now();
+    while server.shutdown_requests.is_empty() && start.elapsed() < Duration::from_secs(2) {
+      server.receive();
+    }
+    assert_eq!(server.shutdown_requests, vec!["player".to_string()]);
+
+    drop(server);
+    let _ = remove_dir_all(&world);
+  }
+
+  #[test]
+  fn test_server_connection_banned_handshake() {
+    println!("--- BEGIN SERVER CONNECTION BANNED HANDSHAKE TEST ---");
+    let world = test_dir("server_connection_banned_handshake");
+    let mut server = start_server(&GameConfig::new(), &world);
+
+    if let Err(e) = server.ban("griefer", "Griefing spawn.") {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    // The ban list is reloaded on startup.
+    drop(server);
+    let mut server = start_server(&GameConfig::new(), &world);
+
+    let mut griefer = TestClient::new(server.get_real_address());
+    griefer.send_handshake("griefer");
+    assert_eq!(
+      griefer.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeRejected {
+        reason: "Griefing spawn.".to_string()
+      })
+    );
+
+    // No session was created.
+    assert_eq!(server.get_player_count(), 0);
+
+    // Nobody gets to join as the console, or with a name that would break the files.
+    for name in [
+      "console",
+      "",
+      "mallory|$argon2id$forged\nalice",
+      "way_too_long_of_a_name",
+      "space cadet",
+    ] {
+      let mut player = TestClient::new(server.get_real_address());
+      player.send_handshake(name);
+      assert!(matches!(
+        player.wait_for_reply(|| {
+          server.receive();
+        }),
+        Some(NetworkMessage::HandShakeRejected { .. })
+      ));
+    }
+    assert_eq!(server.get_player_count(), 0);
+
+    let _ = remove_dir_all(&world);
+  }
+
+  #[test]
+  fn test_server_connection_receive_count() {
+    println!("--- BEGIN SERVER CONNECTION RECEIVE COUNT TEST ---");
+    let world = test_dir("server_connection_receive_count");
+    let mut server = start_server(&GameConfig::new(), &world);
+
+    // Nothing has been sent yet.
+    assert_eq!(server.receive(), 0);
+
+    let client = TestClient::new(server.get_real_address());
+    client.send_handshake("counted");
+    client.send(&NetworkMessage::ChatMessage("one".to_string()));
+    client.send(&NetworkMessage::ChatMessage("two".to_string()));
+
+    // Loopback is fast, but not instant.
+    let mut drained = 0;
+    let start = Instant::now();
+    while drained < 3 && start.elapsed() < Duration::from_secs(2) {
+      drained += server.receive();
+    }
+    assert_eq!(drained, 3);
+    assert_eq!(server.chat_messages.len(), 2);
+
+    // Everything was drained.
+    assert_eq!(server.receive(), 0);
+
+    let _ = remove_dir_all(&world);
+  }
+
+  #[test]
+  fn test_server_connection_sequencing() {
+    println!("--- BEGIN SERVER CONNECTION SEQUENCING TEST ---");
+    let world = test_dir("server_connection_sequencing");
+    let mut server = start_server(&GameConfig::new(), &world);
+
+    let client = TestClient::new(server.get_real_address());
+    client.send_handshake("mover");
+
+    // The same chat packet shows up twice.
+    let chat = NetworkMessage::ChatMessage("hello".to_string());
+    client.send_sequenced(2, &chat);
+    client.send_sequenced(2, &chat);
+
+    // The newer movement update beats the older one there.
+    let player_move = |x: f32| NetworkMessage::PlayerMove {
+      position: Vec3A::new(x, 0.0, 0.0),
+      rotation: Vec3A::ZERO,
+    };
+    client.send_sequenced(4, &player_move(4.0));
+    client.send_sequenced(3, &player_move(3.0));
+
+    let mut drained = 0;
+    let start = Instant::now();
+    while drained < 5 && start.elapsed() < Duration::from_secs(2) {
+      drained += server.receive();
+    }
+    assert_eq!(drained, 5);
+
+    assert_eq!(
+      server.chat_messages,
+      vec![("mover".to_string(), "hello".to_string())]
+    );
+    assert_eq!(
+      server.get_player_position("mover"),
+      Some((Vec3A::new(4.0, 0.0, 0.0), Vec3A::ZERO))
+    );
+
+    let _ = remove_dir_all(&world);
+  }
+
+  #[test]
+  fn test_server_connection_login_rate_limit() {
+    println!("--- BEGIN SERVER CONNECTION LOGIN RATE LIMIT TEST ---");
+    let world = test_dir("server_connection_login_rate_limit");
+    let mut server = start_server(&GameConfig::new(), &world);
+    server.set_auth_provider(Box::new(PasswordAuth::load(&world, true)));
+
+    let mut login = |name: &str, password: &str| {
+      let mut player = TestClient::new(server.get_real_address());
+      player.send_login(name, password);
+      let reply = player.wait_for_reply(|| {
+        server.receive();
+      });
+      // Log straight back out, a connected name can't log in again.
+      server.kick(name, "Next.");
+      reply
+    };
+
+    assert_eq!(
+      login("alice", "hunter2"),
+      Some(NetworkMessage::HandShakeConfirmed)
+    );
+    for _ in 0..FAILED_LOGINS_PER_WINDOW {
+      assert!(matches!(
+        login("alice", "guess"),
+        Some(NetworkMessage::HandShakeRejected { reason }) if reason == "Wrong password."
+      ));
+    }
+
+    // Locked out, even with the right password.
+    assert!(matches!(
+      login("alice", "hunter2"),
+      Some(NetworkMessage::HandShakeRejected { reason }) if reason.starts_with("Too many")
+    ));
+
+    // Handshakes that check a password are limited too, right or wrong.
+    let mut server = start_server(&GameConfig::new(), &world);
+    let mut join = |name: &str| {
+      let mut player = TestClient::new(server.get_real_address());
+      player.send_handshake(name);
+      player.wait_for_reply(|| {
+        server.receive();
+      })
+    };
+    for index in 0..HANDSHAKES_PER_WINDOW {
+      assert_eq!(
+        join(&format!("player{}", index)),
+        Some(NetworkMessage::HandShakeConfirmed)
+      );
+    }
+    assert_eq!(join("one_too_many"), None);
+
+    let _ = remove_dir_all(&world);
+  }
+
+  #[test]
+  fn test_server_connection_bind_errors() {
+    println!("--- BEGIN SERVER CONNECTION BIND ERRORS TEST ---");
+    let world = test_dir("server_connection_bind_errors");
+
+    let unresolvable = ServerConnection::new(
+      "not a real address".to_string(),
+      30000,
+      &GameConfig::new(),
+      &world,
+    );
+    assert!(matches!(
+      unresolvable,
+      Err(ConnectionError::AddressResolution { socket, .. }) if socket == "not a real address:30000"
+    ));
+
+    // Something else got the port first.
+    let squatter = match UdpSocket::bind("127.0.0.1:0") {
+      Ok(squatter) => squatter,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let taken_address = match squatter.local_addr() {
+      Ok(taken_address) => taken_address,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    let port_in_use = ServerConnection::new(
+      "127.0.0.1".to_string(),
+      taken_address.port() as i32,
+      &GameConfig::new(),
+      &world,
+    );
+    assert!(
+      matches!(port_in_use, Err(ConnectionError::PortInUse(address)) if address == taken_address)
+    );
+
+    let _ = remove_dir_all(&world);
+  }
+
+  #[test]
+  fn test_server_connection_slow_session() {
+    println!("--- BEGIN SERVER CONNECTION SLOW SESSION TEST ---");
+    let world = test_dir("server_connection_slow_session");
+    let mut server = start_server(&GameConfig::new(), &world);
+
+    let mut player = TestClient::new(server.get_real_address());
+    player.send_handshake("slow");
+    assert_eq!(
+      player.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeConfirmed)
+    );
+    let end_point = match server.get_player_end_point("slow") {
+      Some(end_point) => end_point,
+      None => panic!("Unit test is broken. The player is not connected."),
+    };
+    let get_dropped = |server: &ServerConnection| match server.send_queues.get(&end_point) {
+      Some(send_queue) => send_queue.get_dropped(),
+      None => panic!("Unit test is broken. The session is gone."),
+    };
+
+    // Flood it without flushing. Movement gets dropped, chat pushes old movement out.
+    let player_move = NetworkMessage::PlayerMove {
+      position: Vec3A::ZERO,
+      rotation: Vec3A::ZERO,
+    };
+    for _ in 0..SEND_QUEUE_CAPACITY + 40 {
+      server.send_to_player("slow", &player_move);
+    }
+    assert_eq!(get_dropped(&server), 40);
+    for i in 0..20 {
+      server.send_to_player("slow", &NetworkMessage::ChatMessage(i.to_string()));
+    }
+    assert_eq!(get_dropped(&server), 60);
+    assert_eq!(server.get_player_count(), 1);
+
+    // Staying backed up for too long gets the session dropped.
+    let start = Instant::now();
+    let mut step = Duration::ZERO;
+    while step <= SATURATION_TIMEOUT {
+      for _ in 0..SENDS_PER_FLUSH * 2 {
+        server.send_to_player("slow", &player_move);
+      }
+      server.flush_at(start + step);
+      step += Duration::from_secs(1);
+    }
+    assert_eq!(server.get_player_count(), 0);
+    assert!(server.send_queues.is_empty());
+
+    let _ = remove_dir_all(&world);
+  }
+
+  #[test]
+  fn test_server_connection_transient_send_errors() {
+    println!("--- BEGIN SERVER CONNECTION TRANSIENT SEND ERRORS TEST ---");
+    let world = test_dir("server_connection_transient_send_errors");
+    let mut server = start_server(&GameConfig::new(), &world);
+
+    let mut player = TestClient::new(server.get_real_address());
+    player.send_handshake("flaky");
+    assert_eq!(
+      player.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeConfirmed)
+    );
+    let end_point = match server.get_player_end_point("flaky") {
+      Some(end_point) => end_point,
+      None => panic!("Unit test is broken. The player is not connected."),
+    };
+
+    // A busy socket, or one message that can't go, doesn't hurt the session.
+    server.handle_send_status(end_point, SendStatus::ResourceNotAvailable);
+    server.handle_send_status(end_point, SendStatus::MaxPacketSizeExceeded);
+    assert_eq!(server.get_player_count(), 1);
+    assert!(server.left_players.is_empty());
+
+    // And the loop keeps going.
+    player.send(&NetworkMessage::Hi);
+    assert_eq!(
+      player.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HiThere)
+    );
+
+    // A closed socket drops the session, once.
+    server.handle_send_status(end_point, SendStatus::ResourceNotFound);
+    server.handle_send_status(end_point, SendStatus::ResourceNotFound);
+    assert_eq!(server.get_player_count(), 0);
+    assert_eq!(server.left_players, vec!["flaky".to_string()]);
+    assert!(server.send_queues.is_empty());
+
+    let _ = remove_dir_all(&world);
+  }
+
+  #[test]
+  fn test_server_connection_spawn_and_border() {
+    println!("--- BEGIN SERVER CONNECTION SPAWN AND BORDER TEST ---");
+    let config = GameConfig::parse("static_spawnpoint = (10, 20, -30)\nmapgen_limit = 100");
+    let world = test_dir("server_connection_spawn_and_border");
+    let mut server = start_server(&config, &world);
+
+    let mut player = TestClient::new(server.get_real_address());
+    player.send_handshake("explorer");
+    assert_eq!(
+      player.wait_for_reply(|| {
+        server.receive();
+      }),
+      Some(NetworkMessage::HandShakeConfirmed)
+    );
+
+    // They start out at the spawn point.
+    let spawn_point = Vec3A::new(10.0, 20.0, -30.0);
+    assert_eq!(
+      server.get_player_position("explorer"),
+      Some((spawn_point, Vec3A::ZERO))
+    );
+
+    // Walking past the border, they get put back on the edge.
+    let rotation = Vec3A::new(0.0, 1.0, 0.0);
+    player.send(&NetworkMessage::PlayerMove {
+      position: Vec3A::new(50.0, 250.0, -1000.0),
+      rotation,
+    });
+    // Broken moves are ignored.
+    player.send(&NetworkMessage::PlayerMove {
+      position: Vec3A::new(f32::NAN, 0.0, 0.0),
+      rotation,
+    });
+    let start = Instant::now();
+    let clamped = Vec3A::new(50.0, 100.0, -100.0);
+    while server.get_player_position("explorer") != Some((clamped, rotation)) {
+      server.receive();
+      assert!(
+        start.elapsed() < Duration::from_secs(2),
+        "The move never got clamped. {:?}",
+        server.get_player_position("explorer")
+      );
+    }
+    for _ in 0..10 {
+      server.receive();
+    }
+    assert_eq!(
+      server.get_player_position("explorer"),
+      Some((clamped, rotation))
+    );
+
+    let _ = remove_dir_all(&world);
   }
 }
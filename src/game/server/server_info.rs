@@ -0,0 +1,51 @@
+use std::time::Instant;
+
+///
+/// What the Server tells Lua about itself, see lua_server_info.
+///
+/// The Server refreshes this every tick, so mods read it without
+/// having to borrow the whole Server.
+///
+pub struct ServerInfo {
+  start: Instant,
+  player_names: Vec<String>,
+  // Server::get_status, as of the last refresh.
+  status: String,
+}
+
+impl ServerInfo {
+  pub fn new(start: Instant) -> Self {
+    ServerInfo {
+      start,
+      player_names: vec![],
+      status: String::new(),
+    }
+  }
+
+  ///
+  /// Get how long the Server has been up. [seconds]
+  ///
+  pub fn get_uptime(&self) -> f64 {
+    self.start.elapsed().as_secs_f64()
+  }
+
+  pub fn set_player_names(&mut self, player_names: Vec<String>) {
+    self.player_names = player_names;
+  }
+
+  pub fn get_player_names(&self) -> &[String] {
+    &self.player_names
+  }
+
+  pub fn get_player_count(&self) -> u32 {
+    self.player_names.len() as u32
+  }
+
+  pub fn set_status(&mut self, status: String) {
+    self.status = status;
+  }
+
+  pub fn get_status(&self) -> &str {
+    &self.status
+  }
+}
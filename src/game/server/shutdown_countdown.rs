@@ -0,0 +1,132 @@
+///
+/// Seconds left when players get warned, under a minute.
+///
+/// Above a minute they get warned on every whole minute.
+///
+const WARNING_SECONDS: [u64; 7] = [30, 10, 5, 4, 3, 2, 1];
+
+///
+/// Counts down to a scheduled shutdown.
+///
+/// This gets driven by the server's tick delta, so it has no idea
+/// what time it really is. It just hands back the warnings that
+/// need to be broadcast to the players.
+///
+pub struct ShutdownCountdown {
+  remaining: f64,
+}
+
+impl ShutdownCountdown {
+  pub fn new(seconds: f64) -> Self {
+    ShutdownCountdown {
+      remaining: seconds.max(0.0),
+    }
+  }
+
+  ///
+  /// Check if players get warned when this many seconds are left.
+  ///
+  fn is_warning_second(seconds: u64) -> bool {
+    seconds > 0 && (seconds % 60 == 0 || WARNING_SECONDS.contains(&seconds))
+  }
+
+  ///
+  /// Get the warning text for this many seconds left.
+  ///
+  pub fn get_warning(seconds: u64) -> String {
+    format!("Server shutting down in {}s...", seconds)
+  }
+
+  ///
+  /// Get the warning for when the countdown first starts.
+  ///
+  pub fn get_announcement(&self) -> String {
+    Self::get_warning(self.remaining.ceil() as u64)
+  }
+
+  ///
+  /// Get how many seconds are left.
+  ///
+  pub fn get_remaining(&self) -> f64 {
+    self.remaining
+  }
+
+  ///
+  /// Check if the countdown hit 0.
+  ///
+  pub fn is_expired(&self) -> bool {
+    self.remaining <= 0.0
+  }
+
+  ///
+  /// Move the countdown forward by delta seconds.
+  ///
+  /// Returns the warning to broadcast if one was passed.
+  /// If a huge delta passes multiple, only the latest one is returned
+  /// so the chat doesn't get spammed.
+  ///
+  pub fn advance(&mut self, delta: f64) -> Option<String> {
+    if self.is_expired() {
+      return None;
+    }
+
+    let previous = self.remaining;
+    self.remaining = (self.remaining - delta).max(0.0);
+
+    if self.is_expired() {
+      return None;
+    }
+
+    // Every whole second in [remaining, previous) was just passed.
+    let lowest = self.remaining.ceil() as u64;
+    let highest = previous.ceil() as u64;
+    (lowest..highest)
+      .find(|seconds| Self::is_warning_second(*seconds))
+      .map(Self::get_warning)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::game::server::shutdown_countdown::ShutdownCountdown;
+
+  #[test]
+  fn test_shutdown_countdown_warnings() {
+    println!("--- BEGIN SHUTDOWN COUNTDOWN WARNINGS TEST ---");
+    let mut countdown = ShutdownCountdown::new(90.0);
+    assert_eq!(
+      countdown.get_announcement(),
+      "Server shutting down in 90s..."
+    );
+
+    // 20 TPS, collect every warning.
+    let mut warnings = vec![];
+    let mut ticks = 0;
+    while !countdown.is_expired() {
+      if let Some(warning) = countdown.advance(0.05) {
+        warnings.push(warning);
+      }
+      ticks += 1;
+    }
+
+    // Float drift can leave it a tick off.
+    assert!((1799..=1801).contains(&ticks));
+    assert_eq!(
+      warnings,
+      vec![60, 30, 10, 5, 4, 3, 2, 1]
+        .into_iter()
+        .map(ShutdownCountdown::get_warning)
+        .collect::<Vec<String>>()
+    );
+
+    // A lag spike skips straight to the latest warning.
+    let mut countdown = ShutdownCountdown::new(12.0);
+    assert_eq!(
+      countdown.advance(8.0),
+      Some(ShutdownCountdown::get_warning(4))
+    );
+    assert_eq!(countdown.advance(100.0), None);
+    assert!(countdown.is_expired());
+    assert_eq!(countdown.advance(1.0), None);
+  }
+}
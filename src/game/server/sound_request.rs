@@ -0,0 +1,26 @@
+use crate::game::network_message::NetworkMessage;
+
+///
+/// A sound a mod asked to play, waiting for the Server to send it out.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundRequest {
+  pub name: String,
+  pub gain: f32,
+  pub pitch: f32,
+  // Only this player hears it. Everyone does if it's None.
+  pub to_player: Option<String>,
+}
+
+impl SoundRequest {
+  ///
+  /// Get the message that tells a client to play it.
+  ///
+  pub fn to_message(&self) -> NetworkMessage {
+    NetworkMessage::PlaySound {
+      name: self.name.clone(),
+      gain: self.gain,
+      pitch: self.pitch,
+    }
+  }
+}
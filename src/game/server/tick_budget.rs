@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+///
+/// How many overrun ticks in a row are considered sustained lag.
+///
+/// 5 seconds at the default 20 TPS.
+///
+const SUSTAINED_OVERRUN_TICKS: u32 = 100;
+
+///
+/// The TPS goal used when the one given makes no sense.
+///
+const DEFAULT_TICKS_PER_SECOND: f64 = 20.0;
+
+///
+/// Keeps track of how long server ticks take compared to the target period.
+///
+/// Without this the server silently falls behind when it's overloaded.
+///
+pub struct TickBudget {
+  target_period: Duration,
+  lagged_ticks: u64,
+  consecutive_overruns: u32,
+  last_tick_duration: Duration,
+}
+
+impl TickBudget {
+  pub fn new(goal_ticks_per_second: f64) -> Self {
+    TickBudget {
+      target_period: Self::get_period(goal_ticks_per_second),
+      lagged_ticks: 0,
+      consecutive_overruns: 0,
+      last_tick_duration: Duration::ZERO,
+    }
+  }
+
+  ///
+  /// Change the target period to match a new TPS goal.
+  ///
+  pub fn set_goal_ticks_per_second(&mut self, goal_ticks_per_second: f64) {
+    self.target_period = Self::get_period(goal_ticks_per_second);
+  }
+
+  ///
+  /// Turn a TPS goal into a tick period.
+  ///
+  /// Zero, negative, non-finite, and absurdly small goals fall back to the
+  /// default, Duration::from_secs_f64 would panic on them.
+  ///
+  fn get_period(goal_ticks_per_second: f64) -> Duration {
+    if goal_ticks_per_second > 0.0 && goal_ticks_per_second.is_finite() {
+      if let Ok(period) = Duration::try_from_secs_f64(1.0 / goal_ticks_per_second) {
+        return period;
+      }
+    }
+    println!(
+      "TickBudget: [{}] is not a valid TPS goal, using [{}].",
+      goal_ticks_per_second, DEFAULT_TICKS_PER_SECOND
+    );
+    Duration::from_secs_f64(1.0 / DEFAULT_TICKS_PER_SECOND)
+  }
+
+  ///
+  /// Get how long a tick is allowed to take.
+  ///
+  pub fn get_target_period(&self) -> Duration {
+    self.target_period
+  }
+
+  ///
+  /// Get how many ticks took longer than the target period.
+  ///
+  pub fn get_lagged_ticks(&self) -> u64 {
+    self.lagged_ticks
+  }
+
+  ///
+  /// Get how long the last tick took.
+  ///
+  pub fn get_last_tick_duration(&self) -> Duration {
+    self.last_tick_duration
+  }
+
+  ///
+  /// Record how long a tick took.
+  ///
+  /// Returns how far over budget the tick was, if it was.
+  ///
+  pub fn record(&mut self, tick_duration: Duration) -> Option<Duration> {
+    self.last_tick_duration = tick_duration;
+
+    if tick_duration <= self.target_period {
+      self.consecutive_overruns = 0;
+      return None;
+    }
+
+    let overrun = tick_duration - self.target_period;
+
+    self.lagged_ticks += 1;
+    self.consecutive_overruns += 1;
+
+    println!(
+      "Server: tick took [{:.2}] ms, [{:.2}] ms over budget.",
+      tick_duration.as_secs_f64() * 1000.0,
+      overrun.as_secs_f64() * 1000.0
+    );
+
+    // todo: this would be a good place to trigger a profiling dump.
+    if self.consecutive_overruns == SUSTAINED_OVERRUN_TICKS {
+      println!(
+        "Server: has been lagging for [{}] ticks in a row! Is the server overloaded?",
+        SUSTAINED_OVERRUN_TICKS
+      );
+    }
+
+    Some(overrun)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+  };
+
+  use crate::game::server::tick_budget::TickBudget;
+
+  #[test]
+  fn test_tick_budget_slow_tick() {
+    println!("--- BEGIN TICK BUDGET SLOW TICK TEST ---");
+    // 50 ms budget.
+    let mut tick_budget = TickBudget::new(20.0);
+
+    assert_eq!(tick_budget.record(Duration::from_millis(10)), None);
+    assert_eq!(tick_budget.get_lagged_ticks(), 0);
+
+    // A deliberately slow tick.
+    let start = Instant::now();
+    sleep(Duration::from_millis(60));
+    assert!(tick_budget.record(start.elapsed()).is_some());
+    assert_eq!(tick_budget.get_lagged_ticks(), 1);
+
+    assert_eq!(
+      tick_budget.record(Duration::from_millis(75)),
+      Some(Duration::from_millis(25))
+    );
+    assert_eq!(tick_budget.get_lagged_ticks(), 2);
+  }
+
+  #[test]
+  fn test_tick_budget_bad_goal() {
+    println!("--- BEGIN TICK BUDGET BAD GOAL TEST ---");
+    let default_period = Duration::from_millis(50);
+    for goal in [0.0, -20.0, f64::NAN, f64::INFINITY, f64::MIN_POSITIVE] {
+      assert_eq!(TickBudget::new(goal).get_target_period(), default_period);
+    }
+
+    let mut tick_budget = TickBudget::new(10.0);
+    assert_eq!(tick_budget.get_target_period(), Duration::from_millis(100));
+    tick_budget.set_goal_ticks_per_second(0.0);
+    assert_eq!(tick_budget.get_target_period(), default_period);
+  }
+}
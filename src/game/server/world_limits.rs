@@ -0,0 +1,110 @@
+use glam::Vec3A;
+
+use crate::game::game_config::GameConfig;
+
+///
+/// How far from 0,0,0 the world goes on each axis, unless minetest.conf says mapgen_limit. [nodes]
+///
+/// This is also as far as it can go, like minetest C++.
+///
+pub const MAX_MAPGEN_LIMIT: f32 = 31007.0;
+
+///
+/// Where players show up, and the box they have to stay in.
+///
+/// static_spawnpoint = (x, y, z)
+/// mapgen_limit = 31007
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldLimits {
+  spawn_point: Vec3A,
+  mapgen_limit: f32,
+}
+
+impl WorldLimits {
+  pub fn new(spawn_point: Vec3A, mapgen_limit: f32) -> Self {
+    // NaN gets straight through a clamp, and then so would every position.
+    let mapgen_limit = match mapgen_limit.is_finite() {
+      true => mapgen_limit.clamp(0.0, MAX_MAPGEN_LIMIT),
+      false => {
+        println!(
+          "WorldLimits: ignoring mapgen_limit [{}], using [{}].",
+          mapgen_limit, MAX_MAPGEN_LIMIT
+        );
+        MAX_MAPGEN_LIMIT
+      }
+    };
+    let spawn_point = match spawn_point.is_finite() {
+      true => spawn_point,
+      false => {
+        println!("WorldLimits: ignoring static_spawnpoint [{}].", spawn_point);
+        Vec3A::ZERO
+      }
+    };
+    WorldLimits {
+      // A spawn outside of the border would get pulled back on the first move.
+      spawn_point: spawn_point.clamp(Vec3A::splat(-mapgen_limit), Vec3A::splat(mapgen_limit)),
+      mapgen_limit,
+    }
+  }
+
+  pub fn from_config(config: &GameConfig) -> Self {
+    Self::new(
+      config.get_pos("static_spawnpoint").unwrap_or(Vec3A::ZERO),
+      config.get_parsed("mapgen_limit", MAX_MAPGEN_LIMIT),
+    )
+  }
+
+  pub fn get_spawn_point(&self) -> Vec3A {
+    self.spawn_point
+  }
+
+  pub fn get_mapgen_limit(&self) -> f32 {
+    self.mapgen_limit
+  }
+
+  ///
+  /// Pull a position back inside the border, if it's outside.
+  ///
+  pub fn clamp(&self, position: Vec3A) -> Vec3A {
+    position.clamp(
+      Vec3A::splat(-self.mapgen_limit),
+      Vec3A::splat(self.mapgen_limit),
+    )
+  }
+
+  pub fn contains(&self, position: Vec3A) -> bool {
+    self.clamp(position) == position
+  }
+}
+
+impl Default for WorldLimits {
+  fn default() -> Self {
+    Self::new(Vec3A::ZERO, MAX_MAPGEN_LIMIT)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::Vec3A;
+
+  use crate::game::server::world_limits::{WorldLimits, MAX_MAPGEN_LIMIT};
+
+  #[test]
+  fn test_world_limits_not_finite() {
+    println!("--- BEGIN WORLD LIMITS NOT FINITE TEST ---");
+    for mapgen_limit in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+      let world_limits = WorldLimits::new(Vec3A::new(f32::NAN, 0.0, 0.0), mapgen_limit);
+      assert_eq!(world_limits.get_mapgen_limit(), MAX_MAPGEN_LIMIT);
+      assert_eq!(world_limits.get_spawn_point(), Vec3A::ZERO);
+
+      // The border still holds.
+      let far_away = Vec3A::splat(1.0e9);
+      assert!(!world_limits.contains(far_away));
+      assert_eq!(world_limits.clamp(far_away), Vec3A::splat(MAX_MAPGEN_LIMIT));
+    }
+
+    // Out of range but finite is still clamped like before.
+    assert_eq!(WorldLimits::new(Vec3A::ZERO, -5.0).get_mapgen_limit(), 0.0);
+  }
+}
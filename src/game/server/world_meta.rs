@@ -0,0 +1,503 @@
+use glam::IVec3;
+
+use crate::{
+  file_utilities::create_dir_all,
+  game::{
+    game_config::GameConfig, lua_engine::lua_file_helpers::ModDirectory,
+    server::map_backend::FILES_BACKEND,
+  },
+};
+
+///
+/// Area seeds are cut down to this many bits so they survive the trip
+/// through a Lua number. (f64)
+///
+const AREA_SEED_BITS: u32 = 53;
+
+///
+/// What the Lua PRNG asks get_derived_seed() for.
+///
+pub const PSEUDO_RANDOM_SUBSYSTEM: &str = "pseudo_random";
+
+///
+/// The map backend new worlds get.
+///
+/// The only one there is, see map_backend_from_world.
+///
+pub const DEFAULT_BACKEND: &str = FILES_BACKEND;
+
+///
+/// The world.mt key prefix that turns a mod on or off.
+///
+const LOAD_MOD_PREFIX: &str = "load_mod_";
+
+///
+/// The world's own settings.
+///
+/// Stored in world.mt in the world directory, like minetest C++:
+/// gameid = <game>
+/// backend = <map backend>
+/// seed = <number>
+/// load_mod_<name> = true/false
+///
+/// Mods without a line are enabled. Keys this doesn't know about are
+/// kept as is, so world.mt round trips.
+///
+pub struct WorldMeta {
+  world_path: String,
+  world_mt: GameConfig,
+  seed: u64,
+}
+
+impl WorldMeta {
+  ///
+  /// Load the settings of a world. A missing world.mt means everything
+  /// is enabled.
+  ///
+  /// Anything missing gets a default, saved right away so it never
+  /// changes. A world without a seed gets a random one.
+  ///
+  pub fn load(world_path: &str) -> Self {
+    Self::load_with_seed(world_path, None)
+  }
+
+  ///
+  /// Load the settings of a world, forcing the seed. (fixed_map_seed)
+  ///
+  /// A world without a seed takes the fixed one for good. A world that
+  /// already has one keeps it in world.mt, it's only overridden while
+  /// this runs.
+  ///
+  pub fn load_with_seed(world_path: &str, fixed_seed: Option<u64>) -> Self {
+    let mut new_world_meta = WorldMeta {
+      world_path: world_path.to_owned(),
+      world_mt: GameConfig::new(),
+      seed: 0,
+    };
+    new_world_meta.world_mt = GameConfig::load(&new_world_meta.get_path());
+    let mut changed = false;
+
+    match new_world_meta
+      .world_mt
+      .get("seed")
+      .map(|seed| seed.parse::<u64>())
+    {
+      Some(Ok(seed)) => {
+        new_world_meta.seed = seed;
+        if let Some(fixed_seed) = fixed_seed.filter(|fixed_seed| *fixed_seed != seed) {
+          println!(
+            "WorldMeta: fixed seed [{}] overrides world seed [{}] for this run.",
+            fixed_seed, seed
+          );
+          new_world_meta.seed = fixed_seed;
+        }
+      }
+      _ => {
+        new_world_meta.seed = match fixed_seed {
+          Some(fixed_seed) => fixed_seed,
+          None => rand::random::<u64>(),
+        };
+        println!("WorldMeta: generated world seed [{}].", new_world_meta.seed);
+        let seed = new_world_meta.seed.to_string();
+        new_world_meta.world_mt.set("seed", &seed);
+        changed = true;
+      }
+    }
+
+    if !new_world_meta.world_mt.has("backend") {
+      new_world_meta.world_mt.set("backend", DEFAULT_BACKEND);
+      changed = true;
+    }
+
+    if changed {
+      if let Err(e) = new_world_meta.save() {
+        println!("WorldMeta: Failed to save the world defaults. {}", e);
+      }
+    }
+
+    new_world_meta
+  }
+
+  ///
+  /// Work out which game this world runs.
+  ///
+  /// A world is tied to the game it was made with, so gameid wins over
+  /// the requested game. A world without one (new or old) takes the
+  /// requested game, and remembers it.
+  ///
+  pub fn reconcile_game(&mut self, requested_game: &str) -> Result<String, String> {
+    match self.world_mt.get("gameid") {
+      Some(game_id) => {
+        if game_id != requested_game {
+          println!(
+            "WorldMeta: world is for game [{}], ignoring requested game [{}].",
+            game_id, requested_game
+          );
+        }
+        Ok(game_id.clone())
+      }
+      None => {
+        self.world_mt.set("gameid", requested_game);
+        self.save()?;
+        println!("WorldMeta: world is now for game [{}].", requested_game);
+        Ok(requested_game.to_owned())
+      }
+    }
+  }
+
+  ///
+  /// Get the game this world runs, None if it hasn't been picked yet.
+  ///
+  pub fn get_game_id(&self) -> Option<&String> {
+    self.world_mt.get("gameid")
+  }
+
+  ///
+  /// Get the map backend.
+  ///
+  pub fn get_backend(&self) -> String {
+    self.world_mt.get_string("backend", DEFAULT_BACKEND)
+  }
+
+  ///
+  /// Write world.mt out to disk.
+  ///
+  fn save(&self) -> Result<(), String> {
+    create_dir_all(&self.world_path)?;
+    self.world_mt.save(&self.get_path())
+  }
+
+  ///
+  /// Get the world seed.
+  ///
+  pub fn get_seed(&self) -> u64 {
+    self.seed
+  }
+
+  ///
+  /// Get the seed for generating the area that starts at min.
+  ///
+  /// The same world seed and area always give the same seed, neighboring
+  /// areas give wildly different ones.
+  ///
+  pub fn get_area_seed(&self, min: IVec3) -> u64 {
+    Self::mix_area_seed(self.seed, min)
+  }
+
+  ///
+  /// Get the seed a subsystem should seed it's own randomness with.
+  ///
+  /// Everything random in the engine goes through here, so a fixed world
+  /// seed makes a whole run reproducible.
+  ///
+  pub fn get_derived_seed(&self, subsystem: &str) -> u64 {
+    Self::mix_derived_seed(self.seed, subsystem)
+  }
+
+  ///
+  /// The derived seed math, FNV-1a over the name then a splitmix64 finalizer.
+  ///
+  fn mix_derived_seed(world_seed: u64, subsystem: &str) -> u64 {
+    let mut name_hash: u64 = 0xCBF29CE484222325;
+    for byte in subsystem.bytes() {
+      name_hash ^= byte as u64;
+      name_hash = name_hash.wrapping_mul(0x100000001B3);
+    }
+
+    let mut hash = world_seed ^ name_hash;
+    hash = hash.wrapping_add(0x9E3779B97F4A7C15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D049BB133111EB);
+    hash ^ (hash >> 31)
+  }
+
+  ///
+  /// The actual area seed math. (splitmix64 finalizer)
+  ///
+  fn mix_area_seed(world_seed: u64, min: IVec3) -> u64 {
+    let mut hash = world_seed;
+    for component in [min.x, min.y, min.z] {
+      hash ^= component as u32 as u64;
+      hash = hash.wrapping_add(0x9E3779B97F4A7C15);
+      hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+      hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D049BB133111EB);
+      hash ^= hash >> 31;
+    }
+    hash & ((1 << AREA_SEED_BITS) - 1)
+  }
+
+  ///
+  /// Get the path to world.mt.
+  ///
+  fn get_path(&self) -> String {
+    let mut path = self.world_path.clone();
+    path.push_str("/world.mt");
+    path
+  }
+
+  ///
+  /// Get the world.mt key for a mod.
+  ///
+  fn get_key(mod_name: &str) -> String {
+    format!("{}{}", LOAD_MOD_PREFIX, mod_name)
+  }
+
+  ///
+  /// Check if a mod is turned on in this world.
+  ///
+  pub fn is_mod_enabled(&self, mod_name: &str) -> bool {
+    self.world_mt.get_bool(&Self::get_key(mod_name), true)
+  }
+
+  ///
+  /// Turn a mod on or off, and save world.mt.
+  ///
+  pub fn set_mod_enabled(&mut self, mod_name: &str, enabled: bool) -> Result<(), String> {
+    self
+      .world_mt
+      .set(&Self::get_key(mod_name), &enabled.to_string());
+
+    self.save()
+  }
+
+  ///
+  /// Get the names of the mods world.mt has a line for, that the game doesn't have.
+  ///
+  /// Their lines are kept, in case the mod comes back.
+  ///
+  pub fn get_unknown_mods(&self, mods: &[ModDirectory]) -> Vec<String> {
+    let mut unknown: Vec<String> = self
+      .world_mt
+      .iter()
+      .filter_map(|(key, _)| key.strip_prefix(LOAD_MOD_PREFIX))
+      .filter(|mod_name| !mods.iter().any(|other| other.mod_name == *mod_name))
+      .map(|mod_name| mod_name.to_owned())
+      .collect();
+    unknown.sort();
+    unknown
+  }
+
+  ///
+  /// Get the names of every enabled mod that depends on this one.
+  ///
+  pub fn get_enabled_dependents(&self, mod_name: &str, mods: &[ModDirectory]) -> Vec<String> {
+    let mut dependents: Vec<String> = mods
+      .iter()
+      .filter(|other| self.is_mod_enabled(&other.mod_name))
+      .filter(|other| other.depends.iter().any(|depend| depend == mod_name))
+      .map(|other| other.mod_name.clone())
+      .collect();
+    dependents.sort();
+    dependents
+  }
+
+  ///
+  /// Get the names of every dependency of this mod that isn't enabled.
+  ///
+  /// Dependencies the game doesn't have count as not enabled.
+  ///
+  pub fn get_missing_dependencies(&self, mod_name: &str, mods: &[ModDirectory]) -> Vec<String> {
+    let depends = match mods.iter().find(|other| other.mod_name == mod_name) {
+      Some(mod_directory) => &mod_directory.depends,
+      None => return vec![],
+    };
+
+    let mut missing: Vec<String> = depends
+      .iter()
+      .filter(|depend| {
+        !mods.iter().any(|other| &other.mod_name == *depend) || !self.is_mod_enabled(depend)
+      })
+      .cloned()
+      .collect();
+    missing.sort();
+    missing
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::remove_dir_all;
+
+  use glam::IVec3;
+
+  use crate::{
+    file_utilities::{read_file_to_string, test_dir, write_file_atomic},
+    game::{
+      lua_engine::lua_file_helpers::ModDirectory,
+      server::world_meta::{WorldMeta, DEFAULT_BACKEND, PSEUDO_RANDOM_SUBSYSTEM},
+    },
+  };
+
+  #[test]
+  fn test_world_meta_round_trip() {
+    println!("--- BEGIN WORLD META ROUND TRIP TEST ---");
+    let world_path = test_dir("world_meta_round_trip");
+
+    // A new world gets it's defaults, and takes the requested game.
+    let mut world_meta = WorldMeta::load(&world_path);
+    assert_eq!(world_meta.get_game_id(), None);
+    assert_eq!(world_meta.get_backend(), DEFAULT_BACKEND);
+    assert_eq!(
+      world_meta.reconcile_game("minetest"),
+      Ok("minetest".to_string())
+    );
+    if let Err(e) = world_meta.set_mod_enabled("doors", false) {
+      panic!("Unit test is broken. {}", e);
+    }
+    let seed = world_meta.get_seed();
+
+    // Something else wrote a key this doesn't know about.
+    let world_mt_path = format!("{}/world.mt", world_path);
+    let mut raw_world_mt = match read_file_to_string(&world_mt_path) {
+      Ok(raw_world_mt) => raw_world_mt,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    raw_world_mt.push_str("world_name = Test World\n");
+    if let Err(e) = write_file_atomic(&world_mt_path, raw_world_mt.as_bytes()) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    // Reloaded, everything matches and the world keeps it's game.
+    let mut world_meta = WorldMeta::load(&world_path);
+    assert_eq!(world_meta.get_game_id(), Some(&"minetest".to_string()));
+    assert_eq!(world_meta.get_backend(), DEFAULT_BACKEND);
+    assert_eq!(world_meta.get_seed(), seed);
+    assert!(!world_meta.is_mod_enabled("doors"));
+    assert_eq!(
+      world_meta.reconcile_game("other_game"),
+      Ok("minetest".to_string())
+    );
+
+    // Saving again keeps the unknown key.
+    if let Err(e) = world_meta.set_mod_enabled("doors", true) {
+      panic!("Unit test is broken. {}", e);
+    }
+    match read_file_to_string(&world_mt_path) {
+      Ok(raw_world_mt) => assert!(raw_world_mt.contains("world_name = Test World")),
+      Err(e) => panic!("Unit test is broken. {}", e),
+    }
+
+    // A mod the game doesn't have anymore.
+    let mods = vec![ModDirectory {
+      mod_name: "default".to_string(),
+      mod_path: String::new(),
+      depends: vec![],
+    }];
+    assert_eq!(world_meta.get_unknown_mods(&mods), vec!["doors"]);
+
+    let _ = remove_dir_all(&world_path);
+  }
+
+  #[test]
+  fn test_world_meta_mod_dependencies() {
+    println!("--- BEGIN WORLD META MOD DEPENDENCIES TEST ---");
+    let world_path = test_dir("world_meta_mod_dependencies");
+
+    let new_mod = |name: &str, depends: &[&str]| ModDirectory {
+      mod_name: name.to_string(),
+      mod_path: String::new(),
+      depends: depends.iter().map(|depend| depend.to_string()).collect(),
+    };
+    let mods = vec![
+      new_mod("default", &[]),
+      new_mod("doors", &["default"]),
+      new_mod("farming", &["default", "nonexistent"]),
+    ];
+
+    let mut world_meta = WorldMeta::load(&world_path);
+    assert!(world_meta.is_mod_enabled("default"));
+    assert_eq!(
+      world_meta.get_enabled_dependents("default", &mods),
+      vec!["doors", "farming"]
+    );
+    assert_eq!(
+      world_meta.get_missing_dependencies("farming", &mods),
+      vec!["nonexistent"]
+    );
+
+    if let Err(e) = world_meta.set_mod_enabled("doors", false) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    // Survives a reload, and doors being off means it doesn't count.
+    let world_meta = WorldMeta::load(&world_path);
+    assert!(!world_meta.is_mod_enabled("doors"));
+    assert_eq!(
+      world_meta.get_enabled_dependents("default", &mods),
+      vec!["farming"]
+    );
+
+    let _ = remove_dir_all(&world_path);
+  }
+
+  #[test]
+  fn test_world_meta_area_seed() {
+    println!("--- BEGIN WORLD META AREA SEED TEST ---");
+    let world_path = test_dir("world_meta_area_seed");
+
+    // The seed is made once, then it sticks.
+    let world_meta = WorldMeta::load(&world_path);
+    let reloaded = WorldMeta::load(&world_path);
+    assert_eq!(world_meta.get_seed(), reloaded.get_seed());
+
+    let area = IVec3::new(-80, 16, 32);
+    assert_eq!(world_meta.get_area_seed(area), reloaded.get_area_seed(area));
+    assert_eq!(
+      WorldMeta::mix_area_seed(1337, area),
+      WorldMeta::mix_area_seed(1337, area)
+    );
+
+    // Different areas and worlds diverge.
+    assert_ne!(
+      WorldMeta::mix_area_seed(1337, area),
+      WorldMeta::mix_area_seed(1337, IVec3::new(-80, 16, 48))
+    );
+    assert_ne!(
+      WorldMeta::mix_area_seed(1337, area),
+      WorldMeta::mix_area_seed(1338, area)
+    );
+
+    // Lua numbers can hold it exactly.
+    assert!(WorldMeta::mix_area_seed(u64::MAX, area) < (1 << 53));
+
+    let _ = remove_dir_all(&world_path);
+  }
+
+  #[test]
+  fn test_world_meta_fixed_seed() {
+    println!("--- BEGIN WORLD META FIXED SEED TEST ---");
+    let first_path = test_dir("world_meta_fixed_seed_first");
+    let second_path = test_dir("world_meta_fixed_seed_second");
+
+    // Two new worlds, one seed, the same everything.
+    let first = WorldMeta::load_with_seed(&first_path, Some(1234));
+    let second = WorldMeta::load_with_seed(&second_path, Some(1234));
+    assert_eq!(first.get_seed(), 1234);
+    assert_eq!(second.get_seed(), 1234);
+    let area = IVec3::new(-80, 16, 32);
+    assert_eq!(first.get_area_seed(area), second.get_area_seed(area));
+    for subsystem in [PSEUDO_RANDOM_SUBSYSTEM, "mapgen"] {
+      assert_eq!(
+        first.get_derived_seed(subsystem),
+        second.get_derived_seed(subsystem)
+      );
+    }
+
+    // Subsystems don't share a seed.
+    assert_ne!(
+      first.get_derived_seed(PSEUDO_RANDOM_SUBSYSTEM),
+      first.get_derived_seed("mapgen")
+    );
+
+    // The new world kept it, an override doesn't stick.
+    assert_eq!(WorldMeta::load(&first_path).get_seed(), 1234);
+    assert_eq!(
+      WorldMeta::load_with_seed(&first_path, Some(99)).get_seed(),
+      99
+    );
+    assert_eq!(WorldMeta::load(&first_path).get_seed(), 1234);
+
+    let _ = remove_dir_all(&first_path);
+    let _ = remove_dir_all(&second_path);
+  }
+}
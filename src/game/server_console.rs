@@ -0,0 +1,148 @@
+use std::{
+  io::{stdin, BufRead},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::Sender,
+    Arc,
+  },
+  thread::{self, JoinHandle},
+};
+
+use super::game_command::GameCommand;
+
+///
+/// The admin console for a dedicated server.
+///
+/// Lines typed into stdin are read on their own thread and sent to the
+/// Game as GameCommand::ConsoleCommand, so the tick loop stays single
+/// threaded. The Server runs them through the same dispatch as in-game
+/// chat commands.
+///
+/// Only main() starts one, through Game::start_console. A Game in a
+/// test doesn't touch stdin.
+///
+pub struct ServerConsole {
+  running: Arc<AtomicBool>,
+  thread: Option<JoinHandle<()>>,
+}
+
+impl ServerConsole {
+  pub fn new(command_sender: Sender<GameCommand>) -> Self {
+    let running = Arc::new(AtomicBool::new(true));
+    let thread_running = running.clone();
+
+    let spawn_result = thread::Builder::new()
+      .name("server_console".to_string())
+      .spawn(move || Self::read_commands(stdin().lock(), command_sender, thread_running));
+
+    let thread = match spawn_result {
+      Ok(thread) => {
+        println!("ServerConsole: ready for commands.");
+        Some(thread)
+      }
+      Err(e) => {
+        println!("ServerConsole: Failed to start console thread. {}", e);
+        None
+      }
+    };
+
+    ServerConsole { running, thread }
+  }
+
+  ///
+  /// Stop reading commands.
+  ///
+  /// A thread that already finished (stdin closed, or a line came in after
+  /// the stop) gets joined.
+  ///
+  /// ? One that's still blocked on stdin can't be woken up, it's left to
+  /// ? finish on the next line or when the process exits. It holds nothing
+  /// ? that needs cleaning up.
+  ///
+  pub fn shutdown(&mut self) {
+    self.running.store(false, Ordering::Relaxed);
+    let thread = match self.thread.take() {
+      Some(thread) => thread,
+      None => return,
+    };
+    if !thread.is_finished() {
+      println!("ServerConsole: still waiting on stdin, leaving it to the process exit.");
+      return;
+    }
+    if thread.join().is_err() {
+      println!("ServerConsole: the console thread panicked.");
+    }
+  }
+
+  ///
+  /// Forward every line from the reader to the Game until the reader runs
+  /// dry, the Game goes away, or the console is stopped.
+  ///
+  fn read_commands<R: BufRead>(
+    reader: R,
+    command_sender: Sender<GameCommand>,
+    running: Arc<AtomicBool>,
+  ) {
+    for line_result in reader.lines() {
+      if !running.load(Ordering::Relaxed) {
+        break;
+      }
+
+      let line = match line_result {
+        Ok(line) => line,
+        Err(e) => {
+          println!("ServerConsole: Failed to read from stdin. {}", e);
+          break;
+        }
+      };
+
+      if line.trim().is_empty() {
+        continue;
+      }
+
+      // The Game is gone, so are we.
+      if command_sender
+        .send(GameCommand::ConsoleCommand(line))
+        .is_err()
+      {
+        break;
+      }
+    }
+  }
+}
+
+impl Drop for ServerConsole {
+  fn drop(&mut self) {
+    self.shutdown();
+    println!("ServerConsole dropped!");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    io::Cursor,
+    sync::{atomic::AtomicBool, mpsc::channel, Arc},
+  };
+
+  use crate::game::{game_command::GameCommand, server_console::ServerConsole};
+
+  #[test]
+  fn test_server_console_forwards_lines() {
+    println!("--- BEGIN SERVER CONSOLE FORWARDS LINES TEST ---");
+    let (command_sender, command_receiver) = channel();
+    let input = Cursor::new("status\n\n   \nsay hello\nshutdown\n");
+
+    ServerConsole::read_commands(input, command_sender, Arc::new(AtomicBool::new(true)));
+
+    let received: Vec<GameCommand> = command_receiver.try_iter().collect();
+    assert_eq!(
+      received,
+      vec![
+        GameCommand::ConsoleCommand("status".to_string()),
+        GameCommand::ConsoleCommand("say hello".to_string()),
+        GameCommand::ConsoleCommand("shutdown".to_string()),
+      ]
+    );
+  }
+}
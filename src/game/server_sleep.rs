@@ -0,0 +1,192 @@
+use std::time::{Duration, Instant};
+
+use spin_sleep_util::Interval;
+
+use super::game_config::GameConfig;
+
+///
+/// How often the idle time report gets logged.
+///
+const REPORT_PERIOD: Duration = Duration::from_secs(60);
+
+///
+/// How the server waits for the next tick.
+///
+/// Precise always spin sleeps, which is accurate but burns CPU.
+/// PowerSaving only spin sleeps when the server is busy.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepMode {
+  Precise,
+  PowerSaving,
+}
+
+impl SleepMode {
+  ///
+  /// Read server_sleep_mode out of minetest.conf. Defaults to power_saving.
+  ///
+  pub fn from_config(config: &GameConfig) -> Self {
+    match config
+      .get_string("server_sleep_mode", "power_saving")
+      .as_str()
+    {
+      "precise" => SleepMode::Precise,
+      "power_saving" => SleepMode::PowerSaving,
+      unknown => {
+        println!(
+          "ServerSleep: unknown server_sleep_mode [{}]. Using power_saving.",
+          unknown
+        );
+        SleepMode::PowerSaving
+      }
+    }
+  }
+}
+
+///
+/// Decides how the server sleeps between ticks.
+///
+/// An idle dedicated server doesn't need spin_sleep's precision, so it
+/// sleeps natively and leaves the CPU alone. As soon as there's work to
+/// do it goes back to precise spin sleeping.
+///
+pub struct ServerSleep {
+  mode: SleepMode,
+
+  spin_ticks: u64,
+  no_spin_ticks: u64,
+
+  // Time spent sleeping vs time spent working in this report period.
+  idle_time: Duration,
+  busy_time: Duration,
+  last_wake: Instant,
+  report_start: Instant,
+}
+
+impl ServerSleep {
+  pub fn new(mode: SleepMode) -> Self {
+    ServerSleep {
+      mode,
+
+      spin_ticks: 0,
+      no_spin_ticks: 0,
+
+      idle_time: Duration::ZERO,
+      busy_time: Duration::ZERO,
+      last_wake: Instant::now(),
+      report_start: Instant::now(),
+    }
+  }
+
+  ///
+  /// Check if the next sleep needs to be precise.
+  ///
+  pub fn should_spin(&self, server_idle: bool) -> bool {
+    match self.mode {
+      SleepMode::Precise => true,
+      SleepMode::PowerSaving => !server_idle,
+    }
+  }
+
+  ///
+  /// Wait for the next tick of the interval.
+  ///
+  pub fn sleep(&mut self, interval: &mut Interval, server_idle: bool) {
+    let sleep_start = Instant::now();
+    self.busy_time += sleep_start - self.last_wake;
+
+    if self.should_spin(server_idle) {
+      interval.tick();
+      self.spin_ticks += 1;
+    } else {
+      interval.tick_no_spin();
+      self.no_spin_ticks += 1;
+    }
+
+    self.last_wake = Instant::now();
+    self.idle_time += self.last_wake - sleep_start;
+
+    if self.report_start.elapsed() >= REPORT_PERIOD {
+      self.report();
+    }
+  }
+
+  ///
+  /// Get the share of time spent sleeping in this report period. [0.0 - 1.0]
+  ///
+  pub fn get_idle_fraction(&self) -> f64 {
+    let total = self.idle_time + self.busy_time;
+    if total.is_zero() {
+      return 0.0;
+    }
+    self.idle_time.as_secs_f64() / total.as_secs_f64()
+  }
+
+  ///
+  /// Get how many ticks were spin slept.
+  ///
+  pub fn get_spin_ticks(&self) -> u64 {
+    self.spin_ticks
+  }
+
+  ///
+  /// Get how many ticks were natively slept.
+  ///
+  pub fn get_no_spin_ticks(&self) -> u64 {
+    self.no_spin_ticks
+  }
+
+  ///
+  /// Log how CPU friendly the server has been, then start a new report period.
+  ///
+  fn report(&mut self) {
+    println!(
+      "ServerSleep: [{:.1}]% idle. [{}] precise tick(s), [{}] power saving tick(s).",
+      self.get_idle_fraction() * 100.0,
+      self.spin_ticks,
+      self.no_spin_ticks
+    );
+
+    self.spin_ticks = 0;
+    self.no_spin_ticks = 0;
+    self.idle_time = Duration::ZERO;
+    self.busy_time = Duration::ZERO;
+    self.report_start = Instant::now();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use spin_sleep_util::interval;
+
+  use crate::game::server_sleep::{ServerSleep, SleepMode};
+
+  #[test]
+  fn test_server_sleep_reduces_spin_when_idle() {
+    println!("--- BEGIN SERVER SLEEP REDUCES SPIN WHEN IDLE TEST ---");
+    let mut ticker = interval(Duration::from_millis(2));
+
+    // An idle power saving server never spins.
+    let mut power_saving = ServerSleep::new(SleepMode::PowerSaving);
+    for _ in 0..10 {
+      power_saving.sleep(&mut ticker, true);
+    }
+    assert_eq!(power_saving.get_spin_ticks(), 0);
+    assert_eq!(power_saving.get_no_spin_ticks(), 10);
+    assert!(power_saving.get_idle_fraction() > 0.0);
+
+    // But it goes back to being precise when there's work.
+    power_saving.sleep(&mut ticker, false);
+    assert_eq!(power_saving.get_spin_ticks(), 1);
+
+    // Precise always spins.
+    let mut precise = ServerSleep::new(SleepMode::Precise);
+    for _ in 0..10 {
+      precise.sleep(&mut ticker, true);
+    }
+    assert_eq!(precise.get_spin_ticks(), 10);
+    assert_eq!(precise.get_no_spin_ticks(), 0);
+  }
+}
@@ -0,0 +1,293 @@
+mod config_deserializer;
+
+use std::{fmt, ops::RangeInclusive};
+
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use self::config_deserializer::ConfigDeserializer;
+
+use super::{game_config::GameConfig, VSyncMode};
+
+///
+/// The common settings out of minetest.conf, typed.
+///
+/// Use this instead of looking keys up in the GameConfig by hand.
+/// Anything missing gets the Default. Keys that aren't a field here
+/// are kept as is, see get_unknown.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+  // Frames per second goal of a client.
+  pub fps_max: f64,
+  // Ticks per second goal of a server.
+  pub tick_rate: f64,
+  // Seconds per server tick, like minetest C++. Wins over tick_rate if it's set.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub dedicated_server_step: Option<f64>,
+  // How often the server sends state to players. [hz] Every tick if it's not set.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub network_broadcast_rate: Option<f64>,
+  pub vsync: VSyncMode,
+  // How far the world gets drawn. [chunks]
+  pub render_distance: u32,
+  pub max_users: u32,
+  pub motd: String,
+
+  #[serde(skip)]
+  unknown: AHashMap<String, String>,
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Settings {
+      fps_max: 60.0,
+      tick_rate: 20.0,
+      dedicated_server_step: None,
+      network_broadcast_rate: None,
+      vsync: VSyncMode::Off,
+      render_distance: 8,
+      max_users: 15,
+      motd: String::new(),
+
+      unknown: AHashMap::new(),
+    }
+  }
+}
+
+///
+/// Why minetest.conf couldn't be turned into Settings.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingsError {
+  // The value couldn't be parsed into the field's type.
+  Malformed(String),
+  OutOfRange {
+    key: &'static str,
+    value: String,
+    range: String,
+  },
+}
+
+impl fmt::Display for SettingsError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SettingsError::Malformed(reason) => write!(f, "{}", reason),
+      SettingsError::OutOfRange { key, value, range } => {
+        write!(f, "[{}] must be within [{}], got [{}].", key, range, value)
+      }
+    }
+  }
+}
+
+///
+/// Push an error if the value isn't within the range.
+///
+fn check_range<T: PartialOrd + fmt::Display>(
+  errors: &mut Vec<SettingsError>,
+  key: &'static str,
+  value: T,
+  range: RangeInclusive<T>,
+) {
+  if !range.contains(&value) {
+    errors.push(SettingsError::OutOfRange {
+      key,
+      value: value.to_string(),
+      range: format!("{} - {}", range.start(), range.end()),
+    });
+  }
+}
+
+impl Settings {
+  ///
+  /// Pull the Settings out of a parsed minetest.conf and validate them.
+  ///
+  /// Every out of range value gets it's own error.
+  ///
+  pub fn from_config(config: &GameConfig) -> Result<Self, Vec<SettingsError>> {
+    let mut deserializer = ConfigDeserializer::new(config);
+    let mut settings = match Settings::deserialize(&mut deserializer) {
+      Ok(settings) => settings,
+      Err(e) => return Err(vec![SettingsError::Malformed(e.to_string())]),
+    };
+    settings.unknown = deserializer.take_unknown();
+
+    settings.validate()?;
+    Ok(settings)
+  }
+
+  ///
+  /// Check that every value makes sense.
+  ///
+  pub fn validate(&self) -> Result<(), Vec<SettingsError>> {
+    let mut errors = vec![];
+
+    check_range(&mut errors, "fps_max", self.fps_max, 1.0..=1000.0);
+    check_range(&mut errors, "tick_rate", self.tick_rate, 1.0..=1000.0);
+    if let Some(dedicated_server_step) = self.dedicated_server_step {
+      check_range(
+        &mut errors,
+        "dedicated_server_step",
+        dedicated_server_step,
+        0.001..=1.0,
+      );
+    }
+    if let Some(network_broadcast_rate) = self.network_broadcast_rate {
+      check_range(
+        &mut errors,
+        "network_broadcast_rate",
+        network_broadcast_rate,
+        0.1..=1000.0,
+      );
+    }
+    check_range(&mut errors, "render_distance", self.render_distance, 1..=64);
+    check_range(&mut errors, "max_users", self.max_users, 1..=65535);
+
+    match errors.is_empty() {
+      true => Ok(()),
+      false => Err(errors),
+    }
+  }
+
+  ///
+  /// Get the server's ticks per second goal.
+  ///
+  /// dedicated_server_step takes priority over tick_rate.
+  ///
+  pub fn get_ticks_per_second(&self) -> f64 {
+    match self.dedicated_server_step {
+      Some(dedicated_server_step) => 1.0 / dedicated_server_step,
+      None => self.tick_rate,
+    }
+  }
+
+  ///
+  /// Get the keys from minetest.conf that aren't typed Settings.
+  ///
+  /// They're passed through untouched by to_config.
+  ///
+  pub fn get_unknown(&self) -> &AHashMap<String, String> {
+    &self.unknown
+  }
+
+  ///
+  /// Turn the Settings back into a GameConfig, unknown keys included.
+  ///
+  pub fn to_config(&self) -> GameConfig {
+    let mut config = GameConfig::new();
+    for (key, value) in &self.unknown {
+      config.set(key, value);
+    }
+
+    let fields = match serde_json::to_value(self) {
+      Ok(Value::Object(fields)) => fields,
+      _ => panic!("Settings: failed to serialize. This is a bug."),
+    };
+    for (key, value) in fields {
+      match value {
+        Value::String(value) => config.set(&key, &value),
+        value => config.set(&key, &value.to_string()),
+      }
+    }
+
+    config
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::game::{
+    game_config::GameConfig,
+    settings::{Settings, SettingsError},
+    VSyncMode,
+  };
+
+  #[test]
+  fn test_settings_from_config() {
+    println!("--- BEGIN SETTINGS FROM CONFIG TEST ---");
+
+    // Nothing set, nothing wrong.
+    assert_eq!(
+      Settings::from_config(&GameConfig::new()),
+      Ok(Settings::default())
+    );
+
+    let config = GameConfig::parse(
+      "fps_max = 144
+      vsync = triple
+      motd = Hello = world
+      server_sleep_mode = off",
+    );
+    let settings = match Settings::from_config(&config) {
+      Ok(settings) => settings,
+      Err(e) => panic!("Unit test is broken. {:?}", e),
+    };
+    assert_eq!(settings.fps_max, 144.0);
+    assert_eq!(settings.vsync, VSyncMode::Triple);
+    assert_eq!(settings.motd, "Hello = world");
+    assert_eq!(settings.tick_rate, 20.0);
+    assert_eq!(settings.get_ticks_per_second(), 20.0);
+
+    // Keys that aren't Settings are kept, and survive a round trip.
+    assert_eq!(
+      settings.get_unknown().get("server_sleep_mode"),
+      Some(&"off".to_string())
+    );
+    assert_eq!(Settings::from_config(&settings.to_config()), Ok(settings));
+
+    // The simulation and the network can run at their own rates.
+    let config = GameConfig::parse(
+      "dedicated_server_step = 0.05
+      network_broadcast_rate = 10",
+    );
+    let settings = match Settings::from_config(&config) {
+      Ok(settings) => settings,
+      Err(e) => panic!("Unit test is broken. {:?}", e),
+    };
+    assert_eq!(settings.get_ticks_per_second(), 20.0);
+    assert_eq!(settings.network_broadcast_rate, Some(10.0));
+    assert_eq!(Settings::from_config(&settings.to_config()), Ok(settings));
+  }
+
+  #[test]
+  fn test_settings_rejects_bad_values() {
+    println!("--- BEGIN SETTINGS REJECTS BAD VALUES TEST ---");
+
+    // Every out of range field gets it's own error.
+    let config = GameConfig::parse(
+      "fps_max = 0
+      tick_rate = 20
+      render_distance = 500
+      max_users = 0",
+    );
+    let errors = match Settings::from_config(&config) {
+      Ok(_) => panic!("Unit test is broken. Out of range values were accepted."),
+      Err(errors) => errors,
+    };
+    let keys: Vec<&str> = errors
+      .iter()
+      .map(|error| match error {
+        SettingsError::OutOfRange { key, .. } => *key,
+        SettingsError::Malformed(_) => panic!("Unit test is broken. {}", error),
+      })
+      .collect();
+    assert_eq!(keys, vec!["fps_max", "render_distance", "max_users"]);
+    assert_eq!(
+      errors[1].to_string(),
+      "[render_distance] must be within [1 - 64], got [500]."
+    );
+
+    // Values that can't even be parsed say which key it was.
+    let malformed = |raw_config: &str| match Settings::from_config(&GameConfig::parse(raw_config)) {
+      Err(errors) => errors[0].to_string(),
+      Ok(_) => panic!("Unit test is broken. [{}] was accepted.", raw_config),
+    };
+    assert!(malformed("max_users = lots").contains("[max_users] is not a number"));
+    assert!(
+      malformed("vsync = sometimes").contains("[vsync] is not one of [off, on, double, triple]")
+    );
+    assert!(malformed("render_distance = -1").contains("[render_distance]"));
+  }
+}
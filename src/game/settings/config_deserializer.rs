@@ -0,0 +1,162 @@
+use ahash::AHashMap;
+use serde::{
+  de::{self, value::MapDeserializer, IntoDeserializer, Visitor},
+  forward_to_deserialize_any, Deserializer,
+};
+
+use crate::game::game_config::GameConfig;
+
+///
+/// Feeds a GameConfig into serde.
+///
+/// Every value in minetest.conf is a String, so each field gets parsed
+/// into whatever type the struct asks for. Keys the struct doesn't
+/// have are kept in unknown.
+///
+pub struct ConfigDeserializer<'a> {
+  config: &'a GameConfig,
+  unknown: AHashMap<String, String>,
+}
+
+impl<'a> ConfigDeserializer<'a> {
+  pub fn new(config: &'a GameConfig) -> Self {
+    ConfigDeserializer {
+      config,
+      unknown: AHashMap::new(),
+    }
+  }
+
+  ///
+  /// Hand over the keys the struct didn't know about.
+  ///
+  pub fn take_unknown(&mut self) -> AHashMap<String, String> {
+    std::mem::take(&mut self.unknown)
+  }
+}
+
+impl<'de, 'a> Deserializer<'de> for &mut ConfigDeserializer<'a> {
+  type Error = de::value::Error;
+
+  fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+    Err(de::Error::custom(
+      "A GameConfig can only be deserialized into a struct.",
+    ))
+  }
+
+  fn deserialize_struct<V: Visitor<'de>>(
+    self,
+    _name: &'static str,
+    fields: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    let mut known = vec![];
+    for (key, value) in self.config.iter() {
+      match fields.contains(&key.as_str()) {
+        true => known.push((key.as_str(), ConfigValue { key, value })),
+        false => {
+          self.unknown.insert(key.clone(), value.clone());
+        }
+      }
+    }
+    visitor.visit_map(MapDeserializer::new(known.into_iter()))
+  }
+
+  forward_to_deserialize_any! {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+    bytes byte_buf option unit unit_struct newtype_struct seq tuple
+    tuple_struct map enum identifier ignored_any
+  }
+}
+
+///
+/// One raw value out of minetest.conf. The key is kept for the errors.
+///
+struct ConfigValue<'a> {
+  key: &'a str,
+  value: &'a str,
+}
+
+impl<'a> ConfigValue<'a> {
+  fn malformed(&self, expected: &str) -> de::value::Error {
+    de::Error::custom(format!(
+      "[{}] is not {}, got [{}].",
+      self.key, expected, self.value
+    ))
+  }
+}
+
+impl<'de, 'a> IntoDeserializer<'de, de::value::Error> for ConfigValue<'a> {
+  type Deserializer = Self;
+
+  fn into_deserializer(self) -> Self::Deserializer {
+    self
+  }
+}
+
+///
+/// Parse the value as a number and hand it to the matching visit method.
+///
+macro_rules! deserialize_number {
+  ($($deserialize:ident => $visit:ident),* $(,)?) => {
+    $(
+      fn $deserialize<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value.parse() {
+          Ok(number) => visitor.$visit(number),
+          Err(_) => Err(self.malformed("a number")),
+        }
+      }
+    )*
+  };
+}
+
+impl<'de, 'a> Deserializer<'de> for ConfigValue<'a> {
+  type Error = de::value::Error;
+
+  fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+    visitor.visit_str(self.value)
+  }
+
+  fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+    // The same spellings GameConfig::get_bool takes.
+    match self.value {
+      "true" | "1" => visitor.visit_bool(true),
+      "false" | "0" => visitor.visit_bool(false),
+      _ => Err(self.malformed("a boolean")),
+    }
+  }
+
+  deserialize_number! {
+    deserialize_i8 => visit_i8,
+    deserialize_i16 => visit_i16,
+    deserialize_i32 => visit_i32,
+    deserialize_i64 => visit_i64,
+    deserialize_u8 => visit_u8,
+    deserialize_u16 => visit_u16,
+    deserialize_u32 => visit_u32,
+    deserialize_u64 => visit_u64,
+    deserialize_f32 => visit_f32,
+    deserialize_f64 => visit_f64,
+  }
+
+  fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+    visitor.visit_some(self)
+  }
+
+  fn deserialize_enum<V: Visitor<'de>>(
+    self,
+    _name: &'static str,
+    variants: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    // Checked here so the error can say which key it was.
+    if !variants.contains(&self.value) {
+      return Err(self.malformed(&format!("one of [{}]", variants.join(", "))));
+    }
+    visitor.visit_enum(self.value.into_deserializer())
+  }
+
+  forward_to_deserialize_any! {
+    i128 u128 char str string bytes byte_buf unit unit_struct newtype_struct
+    seq tuple tuple_struct map struct identifier ignored_any
+  }
+}
@@ -0,0 +1,182 @@
+use crate::file_utilities::file_exists;
+
+use super::{
+  client::render_engine::SHADER_FILES,
+  game_init_error::GameInitError,
+  server::{item_registry::ItemRegistry, node_registry::NodeRegistry},
+};
+
+///
+/// What to do about missing assets and broken mods at startup.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartupMode {
+  // Warn, then carry on with placeholders. Broken mods crash the server, unless skip_broken_mods.
+  Permissive,
+  // Refuse to start, --strict. For CI and checking packages.
+  Strict,
+}
+
+impl StartupMode {
+  pub fn from_strict(strict: bool) -> Self {
+    match strict {
+      true => StartupMode::Strict,
+      false => StartupMode::Permissive,
+    }
+  }
+}
+
+///
+/// Everything that's wrong with the assets, gathered up before the game starts.
+///
+/// In strict mode all of it gets reported at once, instead of one
+/// problem at a time halfway into a session.
+///
+pub struct StartupReport {
+  problems: Vec<String>,
+}
+
+impl StartupReport {
+  pub fn new() -> Self {
+    StartupReport { problems: vec![] }
+  }
+
+  pub fn add(&mut self, problem: String) {
+    self.problems.push(problem);
+  }
+
+  pub fn get_problems(&self) -> &[String] {
+    &self.problems
+  }
+
+  ///
+  /// Check that every shader the RenderEngine loads is there.
+  ///
+  pub fn check_shaders(&mut self) {
+    for shader in SHADER_FILES {
+      if !file_exists(shader) {
+        self.add(format!("Shader [{}] is missing.", shader));
+      }
+    }
+  }
+
+  ///
+  /// Check that every texture a node or craftitem uses is in some mod's textures folder.
+  ///
+  pub fn check_textures(&mut self, nodes: &NodeRegistry, items: &ItemRegistry) {
+    for (node, texture) in nodes.get_all_missing_textures() {
+      self.add(format!(
+        "Node [{}] uses texture [{}], no mod has it.",
+        node, texture
+      ));
+    }
+
+    for name in items.get_names() {
+      let inventory_image = match items.get(&name) {
+        Some(definition) if !definition.inventory_image.is_empty() => &definition.inventory_image,
+        _ => continue,
+      };
+      if !nodes.has_texture(inventory_image) {
+        self.add(format!(
+          "Item [{}] uses texture [{}], no mod has it.",
+          name, inventory_image
+        ));
+      }
+    }
+  }
+
+  ///
+  /// Add the mods that failed to load, see LuaEngine::get_mod_errors.
+  ///
+  pub fn add_mod_errors(&mut self, mod_errors: &[String]) {
+    for mod_error in mod_errors {
+      self.add(mod_error.clone());
+    }
+  }
+
+  ///
+  /// Strict mode fails on any problem, permissive mode just warns about them.
+  ///
+  pub fn finish(&self, mode: StartupMode) -> Result<(), GameInitError> {
+    if self.problems.is_empty() {
+      return Ok(());
+    }
+
+    match mode {
+      StartupMode::Strict => Err(GameInitError::Startup(self.problems.clone())),
+      StartupMode::Permissive => {
+        for problem in &self.problems {
+          println!("Minetest: warning, {}", problem);
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::{create_dir_all, remove_dir_all, write};
+
+  use crate::{
+    file_utilities::test_dir,
+    game::{
+      game_init_error::GameInitError,
+      server::{
+        item_registry::{ItemDefinition, ItemRegistry},
+        node_registry::{NodeDefinition, NodeRegistry},
+      },
+      startup_check::{StartupMode, StartupReport},
+    },
+  };
+
+  #[test]
+  fn test_startup_check_missing_texture() {
+    println!("--- BEGIN STARTUP CHECK MISSING TEXTURE TEST ---");
+    let texture_dir = test_dir("startup_check_textures");
+    if let Err(e) = create_dir_all(&texture_dir) {
+      panic!("Unit test is broken. {}", e);
+    }
+    if let Err(e) = write(format!("{}/stone.png", texture_dir), b"not really a png") {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    let mut nodes = NodeRegistry::new(vec![texture_dir.clone()]);
+    let stone = NodeDefinition {
+      textures: vec!["stone.png".to_string()],
+      ..NodeDefinition::new("rocks:stone")
+    };
+    if let Err(e) = nodes.register(stone) {
+      panic!("Unit test is broken. {}", e);
+    }
+    let mut items = ItemRegistry::new();
+    let apple = ItemDefinition {
+      inventory_image: "apple.png".to_string(),
+      ..ItemDefinition::new("food:apple")
+    };
+    if let Err(e) = items.register(apple) {
+      panic!("Unit test is broken. {}", e);
+    }
+
+    // Everything's there, both modes are happy.
+    let mut report = StartupReport::new();
+    report.check_textures(&nodes, &ItemRegistry::new());
+    assert!(report.finish(StartupMode::Strict).is_ok());
+
+    // The apple's texture is missing.
+    let mut report = StartupReport::new();
+    report.check_textures(&nodes, &items);
+    report.add_mod_errors(&["Mod [broken] failed to load. Oops.".to_string()]);
+    assert!(report.finish(StartupMode::Permissive).is_ok());
+    match report.finish(StartupMode::Strict) {
+      Err(GameInitError::Startup(problems)) => {
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("apple.png"));
+        assert!(problems[1].contains("broken"));
+      }
+      result => panic!("Strict mode let a missing texture through. {:?}", result),
+    }
+
+    let _ = remove_dir_all(&texture_dir);
+  }
+}
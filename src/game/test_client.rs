@@ -0,0 +1,410 @@
+use std::{
+  cell::Cell,
+  net::SocketAddr,
+  time::{Duration, Instant},
+};
+
+use message_io::{
+  events::EventReceiver,
+  network::{Endpoint, Transport},
+  node::{self, NodeHandler, NodeTask, StoredNetEvent, StoredNodeEvent},
+};
+
+use super::{
+  network_message::NetworkMessage,
+  serial::{deserialize, serialize},
+};
+
+///
+/// A bare bones client to poke a server with over loopback.
+///
+/// The real Client needs a window, this only speaks the protocol.
+///
+pub struct TestClient {
+  handler: NodeHandler<()>,
+  _task: NodeTask,
+  event_receiver: EventReceiver<StoredNodeEvent<()>>,
+  end_point: Endpoint,
+  next_sequence: Cell<u32>,
+}
+
+impl TestClient {
+  pub fn new(server_address: SocketAddr) -> Self {
+    let (handler, listener) = node::split::<()>();
+    let (task, event_receiver) = listener.enqueue();
+    // The socket must be ready before the first send or it gets dropped.
+    let end_point = match handler
+      .network()
+      .connect_sync(Transport::Udp, server_address)
+    {
+      Ok((end_point, _)) => end_point,
+      Err(e) => panic!("Unit test is broken. {}", e),
+    };
+    TestClient {
+      handler,
+      _task: task,
+      event_receiver,
+      end_point,
+      next_sequence: Cell::new(1),
+    }
+  }
+
+  pub fn send(&self, message: &NetworkMessage) {
+    let sequence = self.next_sequence.get();
+    self.send_sequenced(sequence, message);
+  }
+
+  ///
+  /// Send with a hand picked sequence number, to fake duplicates and reordering.
+  ///
+  /// Later sends carry on after the highest one used.
+  ///
+  pub fn send_sequenced(&self, sequence: u32, message: &NetworkMessage) {
+    self
+      .next_sequence
+      .set(self.next_sequence.get().max(sequence + 1));
+    self
+      .handler
+      .network()
+      .send(self.end_point, &serialize(sequence, message));
+  }
+
+  pub fn send_handshake(&self, name: &str) {
+    self.send_login(name, "");
+  }
+
+  pub fn send_login(&self, name: &str, password: &str) {
+    self.send(&NetworkMessage::HandShake {
+      name: name.to_string(),
+      password: password.to_string(),
+    });
+  }
+
+  ///
+  /// Keep pumping the server until this client gets a reply.
+  ///
+  /// Heartbeat pings, time of day updates, entity positions and being moved
+  /// by the server aren't replies, they're skipped.
+  /// Gives up after 2 seconds.
+  ///
+  pub fn wait_for_reply(&mut self, mut pump: impl FnMut()) -> Option<NetworkMessage> {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(2) {
+      pump();
+      if let Some(StoredNodeEvent::Network(StoredNetEvent::Message(_, data))) = self
+        .event_receiver
+        .receive_timeout(Duration::from_millis(5))
+      {
+        match deserialize(&data) {
+          Ok(packet)
+            if matches!(
+              packet.message,
+              NetworkMessage::PingRequest { .. }
+                | NetworkMessage::TimeOfDay { .. }
+                | NetworkMessage::MovePlayer { .. }
+                | NetworkMessage::EntityPositions { .. }
+            ) =>
+          {
+            continue
+          }
+          packet => return packet.ok().map(|packet| packet.message),
+        }
+      }
+    }
+    None
+  }
+
+  ///
+  /// Wait for a message that matches, skipping everything else.
+  ///
+  /// Gives up after 2 seconds.
+  ///
+  pub fn wait_for_message(
+    &mut self,
+    mut pump: impl FnMut(),
+    matches: impl Fn(&NetworkMessage) -> bool,
+  ) -> Option<NetworkMessage> {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(2) {
+      pump();
+      if let Some(StoredNodeEvent::Network(StoredNetEvent::Message(_, data))) = self
+        .event_receiver
+        .receive_timeout(Duration::from_millis(5))
+      {
+        if let Ok(packet) = deserialize(&data) {
+          if matches(&packet.message) {
+            return Some(packet.message);
+          }
+        }
+      }
+    }
+    None
+  }
+}
+
+impl Drop for TestClient {
+  fn drop(&mut self) {
+    self.handler.stop();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+  };
+
+  use glam::Vec3A;
+  use message_io::{
+    events::EventReceiver,
+    network::{Endpoint, NetEvent, Transport},
+    node::{self, NodeEvent, NodeHandler, NodeTask, StoredNetEvent, StoredNodeEvent},
+  };
+
+  use crate::game::{
+    network_message::NetworkMessage,
+    serial::{deserialize, serialize},
+  };
+
+  ///
+  /// Both ends of something that echoes back whatever it gets.
+  ///
+  trait EchoLink {
+    fn send(&mut self, data: Vec<u8>);
+    fn receive(&mut self, timeout: Duration) -> Option<Vec<u8>>;
+  }
+
+  ///
+  /// The real transport, message_io UDP over 127.0.0.1.
+  ///
+  struct UdpLink {
+    handler: NodeHandler<()>,
+    _task: NodeTask,
+    event_receiver: EventReceiver<StoredNodeEvent<()>>,
+    end_point: Endpoint,
+    echo_handler: NodeHandler<()>,
+    _echo_task: NodeTask,
+  }
+
+  impl UdpLink {
+    fn new() -> Self {
+      let (echo_handler, echo_listener) = node::split::<()>();
+      let address = match echo_handler.network().listen(Transport::Udp, "127.0.0.1:0") {
+        Ok((_, address)) => address,
+        Err(e) => panic!("Unit test is broken. {}", e),
+      };
+      let echo = echo_handler.clone();
+      let echo_task = echo_listener.for_each_async(move |event| {
+        if let NodeEvent::Network(NetEvent::Message(end_point, data)) = event {
+          echo.network().send(end_point, data);
+        }
+      });
+
+      let (handler, listener) = node::split::<()>();
+      let (task, event_receiver) = listener.enqueue();
+      let end_point = match handler.network().connect_sync(Transport::Udp, address) {
+        Ok((end_point, _)) => end_point,
+        Err(e) => panic!("Unit test is broken. {}", e),
+      };
+      UdpLink {
+        handler,
+        _task: task,
+        event_receiver,
+        end_point,
+        echo_handler,
+        _echo_task: echo_task,
+      }
+    }
+  }
+
+  impl EchoLink for UdpLink {
+    fn send(&mut self, data: Vec<u8>) {
+      self.handler.network().send(self.end_point, &data);
+    }
+
+    fn receive(&mut self, timeout: Duration) -> Option<Vec<u8>> {
+      let start = Instant::now();
+      while let Some(remaining) = timeout.checked_sub(start.elapsed()) {
+        if let Some(StoredNodeEvent::Network(StoredNetEvent::Message(_, data))) =
+          self.event_receiver.receive_timeout(remaining)
+        {
+          return Some(data);
+        }
+      }
+      None
+    }
+  }
+
+  impl Drop for UdpLink {
+    fn drop(&mut self) {
+      self.handler.stop();
+      self.echo_handler.stop();
+    }
+  }
+
+  ///
+  /// The same bytes through channels to another thread, instead of a socket.
+  ///
+  /// ! The engine has no transport like this, a client always talks to its server over UDP.
+  /// ! It's a baseline, what's left of a round trip without the socket.
+  ///
+  struct LoopbackLink {
+    to_echo: Option<Sender<Vec<u8>>>,
+    from_echo: Receiver<Vec<u8>>,
+    echo_thread: Option<thread::JoinHandle<()>>,
+  }
+
+  impl LoopbackLink {
+    fn new() -> Self {
+      let (to_echo, echo_receiver) = mpsc::channel::<Vec<u8>>();
+      let (echo_sender, from_echo) = mpsc::channel();
+      let echo_thread = thread::spawn(move || {
+        for data in echo_receiver {
+          if echo_sender.send(data).is_err() {
+            break;
+          }
+        }
+      });
+      LoopbackLink {
+        to_echo: Some(to_echo),
+        from_echo,
+        echo_thread: Some(echo_thread),
+      }
+    }
+  }
+
+  impl EchoLink for LoopbackLink {
+    fn send(&mut self, data: Vec<u8>) {
+      if let Some(to_echo) = &self.to_echo {
+        let _ = to_echo.send(data);
+      }
+    }
+
+    fn receive(&mut self, timeout: Duration) -> Option<Vec<u8>> {
+      self.from_echo.recv_timeout(timeout).ok()
+    }
+  }
+
+  impl Drop for LoopbackLink {
+    fn drop(&mut self) {
+      // Hanging up ends the echo thread.
+      self.to_echo = None;
+      if let Some(echo_thread) = self.echo_thread.take() {
+        let _ = echo_thread.join();
+      }
+    }
+  }
+
+  ///
+  /// What a client sends the most of, in the same order every run.
+  ///
+  fn get_bench_message(sequence: u32) -> NetworkMessage {
+    match sequence % 4 {
+      0 => NetworkMessage::PingRequest { id: sequence },
+      1 => NetworkMessage::ChatMessage("<singleplayer> hello there, anybody around?".to_string()),
+      _ => NetworkMessage::PlayerMove {
+        position: Vec3A::new(sequence as f32, 12.5, -40.25),
+        rotation: Vec3A::new(0.0, 1.5, 0.0),
+      },
+    }
+  }
+
+  struct BenchResult {
+    p50: Duration,
+    p99: Duration,
+    messages_per_second: f64,
+    lost: usize,
+  }
+
+  fn run_echo_bench(link: &mut impl EchoLink) -> BenchResult {
+    const WARM_UP: u32 = 1_000;
+    const ROUND_TRIPS: u32 = 5_000;
+    const THROUGHPUT_MESSAGES: u32 = 50_000;
+    // How many can be on the way at once, like a client that doesn't wait on replies.
+    const IN_FLIGHT: u32 = 32;
+    let timeout = Duration::from_millis(250);
+    let mut lost = 0;
+
+    let round_trip = |link: &mut dyn EchoLink, sequence: u32| -> bool {
+      link.send(serialize(sequence, &get_bench_message(sequence)));
+      match link.receive(timeout) {
+        Some(data) => std::hint::black_box(deserialize(&data)).is_ok(),
+        None => false,
+      }
+    };
+
+    for sequence in 1..=WARM_UP {
+      round_trip(link, sequence);
+    }
+
+    // Latency, one at a time.
+    let mut latencies = vec![];
+    for sequence in 1..=ROUND_TRIPS {
+      let start = Instant::now();
+      match round_trip(link, sequence) {
+        true => latencies.push(start.elapsed()),
+        false => lost += 1,
+      }
+    }
+    latencies.sort();
+    let percentile = |percent: usize| match latencies.len() {
+      0 => Duration::ZERO,
+      len => latencies[(len * percent / 100).min(len - 1)],
+    };
+
+    // Throughput, keeping it busy.
+    let start = Instant::now();
+    let mut sent = 0;
+    let mut received = 0;
+    let mut missing = 0;
+    while sent < THROUGHPUT_MESSAGES || received + missing < sent {
+      // A late one can come in after it was counted missing, so this can't just subtract.
+      while sent < THROUGHPUT_MESSAGES && sent.saturating_sub(received + missing) < IN_FLIGHT {
+        sent += 1;
+        link.send(serialize(sent, &get_bench_message(sent)));
+      }
+      match link.receive(timeout) {
+        Some(data) => {
+          let _ = std::hint::black_box(deserialize(&data));
+          received += 1;
+        }
+        // Whatever is still on the way isn't coming.
+        None => missing = sent.saturating_sub(received),
+      }
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    BenchResult {
+      p50: percentile(50),
+      p99: percentile(99),
+      messages_per_second: received as f64 / elapsed,
+      lost: lost + missing as usize,
+    }
+  }
+
+  ///
+  /// Run with: cargo test --release bench_udp_vs_loopback -- --ignored --nocapture
+  ///
+  #[test]
+  #[ignore]
+  fn bench_udp_vs_loopback_throughput() {
+    println!("--- BEGIN UDP VS LOOPBACK THROUGHPUT BENCHMARK ---");
+    let udp = run_echo_bench(&mut UdpLink::new());
+    let loopback = run_echo_bench(&mut LoopbackLink::new());
+
+    // The channels aren't something the engine can use, see LoopbackLink.
+    for (label, result) in [("udp", &udp), ("channel baseline", &loopback)] {
+      println!(
+        "{}: round trip p50 [{:.2?}] p99 [{:.2?}], [{:.0}] messages/s, [{}] lost",
+        label, result.p50, result.p99, result.messages_per_second, result.lost
+      );
+    }
+    println!(
+      "channel baseline: [{:.1}]x the throughput, [{:.1}]x faster at p50, that much is the socket",
+      loopback.messages_per_second / udp.messages_per_second.max(f64::EPSILON),
+      udp.p50.as_secs_f64() / loopback.p50.as_secs_f64().max(f64::EPSILON)
+    );
+  }
+}
@@ -0,0 +1,286 @@
+use crate::{
+  file_utilities::create_dir_all,
+  game::{game_config::GameConfig, time_step::TimeStep},
+};
+
+///
+/// How many times faster than real time the in-game day goes.
+///
+/// 72 is minetest C++'s default, a whole day takes 20 minutes.
+///
+pub const DEFAULT_TIME_SPEED: f64 = 72.0;
+
+///
+/// What a new world starts at. [0.0 - 1.0]
+///
+/// Just after sunrise, like minetest C++'s world_start_time.
+///
+pub const DEFAULT_START_TIME: f64 = 0.255;
+
+///
+/// Real seconds in a day, at a time_speed of 1.
+///
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+///
+/// env_meta.txt keeps the time in minetest C++'s units, 24000 to a day.
+///
+const TIME_OF_DAY_UNITS: f64 = 24_000.0;
+
+///
+/// A client further off the server than this jumps straight to it. [0.0 - 1.0]
+///
+/// That's about 15 in-game minutes, it's someone calling set_timeofday.
+///
+const SNAP_DISTANCE: f64 = 0.01;
+
+///
+/// How long a client takes to blend out a small difference with the server. [seconds]
+///
+const CORRECTION_TIME: f64 = 1.0;
+
+///
+/// The in-game time of day and how fast it goes. [0.0 - 1.0]
+///
+/// 0.0 is midnight, 0.5 is noon. It wraps back to 0.0 at 1.0.
+///
+/// The Server owns the real clock, and stores it in env_meta.txt in the
+/// world directory. Clients run their own copy between updates, so the
+/// sky moves smoothly, and nudge it toward every update that comes in.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeOfDay {
+  time: f64,
+  speed: f64,
+  // Set by set(), the Server sends the new time right away.
+  was_set: bool,
+  // What's left to blend in from the last sync().
+  correction: f64,
+}
+
+impl TimeOfDay {
+  pub fn new(time: f64, speed: f64) -> Self {
+    TimeOfDay {
+      time: Self::wrap(time),
+      speed: Self::check_speed(speed),
+      was_set: false,
+      correction: 0.0,
+    }
+  }
+
+  ///
+  /// Load the time out of the world's env_meta.txt, and time_speed from minetest.conf.
+  ///
+  /// A new world starts at DEFAULT_START_TIME.
+  ///
+  pub fn load(world_path: &str, config: &GameConfig) -> Self {
+    let env_meta = GameConfig::load(&Self::get_path(world_path));
+    TimeOfDay::new(
+      env_meta.get_parsed("time_of_day", DEFAULT_START_TIME * TIME_OF_DAY_UNITS)
+        / TIME_OF_DAY_UNITS,
+      config.get_parsed("time_speed", DEFAULT_TIME_SPEED),
+    )
+  }
+
+  ///
+  /// Write the time out to the world's env_meta.txt.
+  ///
+  /// Other keys in there are kept.
+  ///
+  pub fn save(&self, world_path: &str) -> Result<(), String> {
+    create_dir_all(world_path)?;
+    let path = Self::get_path(world_path);
+    let mut env_meta = GameConfig::load(&path);
+    env_meta.set(
+      "time_of_day",
+      &((self.time * TIME_OF_DAY_UNITS).round() as u32).to_string(),
+    );
+    env_meta.save(&path)
+  }
+
+  ///
+  /// Get the path to env_meta.txt.
+  ///
+  fn get_path(world_path: &str) -> String {
+    format!("{}/env_meta.txt", world_path)
+  }
+
+  ///
+  /// Put any time into [0.0 - 1.0).
+  ///
+  fn wrap(time: f64) -> f64 {
+    match time.is_finite() {
+      true => time.rem_euclid(1.0),
+      false => DEFAULT_START_TIME,
+    }
+  }
+
+  ///
+  /// The day can stop, but not run backwards.
+  ///
+  fn check_speed(speed: f64) -> f64 {
+    match speed.is_finite() {
+      true => speed.max(0.0),
+      false => DEFAULT_TIME_SPEED,
+    }
+  }
+
+  ///
+  /// Get the time of day. [0.0 - 1.0)
+  ///
+  pub fn get(&self) -> f64 {
+    self.time
+  }
+
+  ///
+  /// Jump to a time of day. It's wrapped into [0.0 - 1.0).
+  ///
+  pub fn set(&mut self, time: f64) {
+    self.time = Self::wrap(time);
+    self.correction = 0.0;
+    self.was_set = true;
+  }
+
+  ///
+  /// Get if set() was called since the last time this was asked, and reset it.
+  ///
+  pub fn take_was_set(&mut self) -> bool {
+    std::mem::take(&mut self.was_set)
+  }
+
+  pub fn get_speed(&self) -> f64 {
+    self.speed
+  }
+
+  pub fn set_speed(&mut self, speed: f64) {
+    self.speed = Self::check_speed(speed);
+  }
+
+  ///
+  /// Move the clock along by one tick.
+  ///
+  pub fn advance(&mut self, delta: TimeStep) {
+    let delta = delta.as_secs_f64().max(0.0);
+    let mut time = self.time + delta * self.speed / SECONDS_PER_DAY;
+
+    // Blend in a bit of what the server said.
+    if self.correction != 0.0 {
+      let step = self.correction * (delta / CORRECTION_TIME).min(1.0);
+      time += step;
+      self.correction -= step;
+    }
+
+    self.time = Self::wrap(time);
+  }
+
+  ///
+  /// Catch up with a time of day from the server.
+  ///
+  /// Small differences are blended in over CORRECTION_TIME, big ones
+  /// are jumped to.
+  ///
+  pub fn sync(&mut self, server_time: f64, server_speed: f64) {
+    self.set_speed(server_speed);
+
+    // The short way around, 0.99 to 0.01 is 0.02 forward.
+    let difference = (Self::wrap(server_time) - self.time + 0.5).rem_euclid(1.0) - 0.5;
+    match difference.abs() > SNAP_DISTANCE {
+      true => {
+        self.time = Self::wrap(server_time);
+        self.correction = 0.0;
+      }
+      false => self.correction = difference,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::remove_dir_all;
+
+  use crate::{
+    file_utilities::test_dir,
+    game::{
+      game_config::GameConfig,
+      time_of_day::{TimeOfDay, DEFAULT_START_TIME, DEFAULT_TIME_SPEED},
+      time_step::TimeStep,
+    },
+  };
+
+  #[test]
+  fn test_time_of_day_advance_and_wrap() {
+    println!("--- BEGIN TIME OF DAY ADVANCE AND WRAP TEST ---");
+    let tick = TimeStep::from_secs_f64(0.05);
+
+    // At 72x a day is 1200 seconds, that's 24000 ticks.
+    let mut time_of_day = TimeOfDay::new(0.0, DEFAULT_TIME_SPEED);
+    for _ in 0..6000 {
+      time_of_day.advance(tick);
+    }
+    assert!((time_of_day.get() - 0.25).abs() < 1e-9);
+
+    // Twice the speed, twice as far.
+    let mut fast = TimeOfDay::new(0.0, DEFAULT_TIME_SPEED * 2.0);
+    for _ in 0..6000 {
+      fast.advance(tick);
+    }
+    assert!((fast.get() - 0.5).abs() < 1e-9);
+
+    // Going past midnight wraps back around.
+    let mut time_of_day = TimeOfDay::new(0.9, DEFAULT_TIME_SPEED);
+    for _ in 0..4800 {
+      time_of_day.advance(tick);
+    }
+    assert!((time_of_day.get() - 0.1).abs() < 1e-9);
+
+    // set() wraps too, and a stopped day stays put.
+    time_of_day.set(1.25);
+    assert!(time_of_day.take_was_set());
+    assert!(!time_of_day.take_was_set());
+    assert_eq!(time_of_day.get(), 0.25);
+    time_of_day.set(-0.25);
+    assert_eq!(time_of_day.get(), 0.75);
+    time_of_day.set_speed(0.0);
+    time_of_day.advance(tick);
+    assert_eq!(time_of_day.get(), 0.75);
+  }
+
+  #[test]
+  fn test_time_of_day_client_sync() {
+    println!("--- BEGIN TIME OF DAY CLIENT SYNC TEST ---");
+    let mut client = TimeOfDay::new(0.998, 0.0);
+    let distance = |a: f64, b: f64| ((a - b + 0.5).rem_euclid(1.0) - 0.5).abs();
+
+    // Close by, across midnight. It blends in, it doesn't jump.
+    client.sync(0.002, 0.0);
+    assert_eq!(client.get(), 0.998);
+    client.advance(TimeStep::from_secs_f64(0.5));
+    assert!(distance(client.get(), 0.0) < 1e-9);
+    client.advance(TimeStep::from_secs_f64(1.0));
+    assert!(distance(client.get(), 0.002) < 1e-9);
+
+    // Far away, it jumps.
+    client.sync(0.5, DEFAULT_TIME_SPEED);
+    assert_eq!(client.get(), 0.5);
+    assert_eq!(client.get_speed(), DEFAULT_TIME_SPEED);
+  }
+
+  #[test]
+  fn test_time_of_day_persistence() {
+    println!("--- BEGIN TIME OF DAY PERSISTENCE TEST ---");
+    let world_path = test_dir("time_of_day_persistence");
+
+    let config = GameConfig::parse("time_speed = 10");
+    let mut time_of_day = TimeOfDay::load(&world_path, &config);
+    assert!((time_of_day.get() - DEFAULT_START_TIME).abs() < 1e-9);
+    assert_eq!(time_of_day.get_speed(), 10.0);
+
+    time_of_day.set(0.5);
+    if let Err(e) = time_of_day.save(&world_path) {
+      panic!("Unit test is broken. {}", e);
+    }
+    assert_eq!(TimeOfDay::load(&world_path, &config).get(), 0.5);
+
+    let _ = remove_dir_all(&world_path);
+  }
+}
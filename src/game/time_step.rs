@@ -0,0 +1,134 @@
+use std::{
+  fmt,
+  ops::{Add, AddAssign, Deref, Mul, Sub},
+  time::Duration,
+};
+
+///
+/// The time between two ticks, in seconds.
+///
+/// A bare f64 could just as well be milliseconds or a tick count.
+/// This makes it obvious at the type level which one it is.
+///
+/// It derefs to the f64 seconds, so existing math still works:
+/// let move_speed = *delta as f32 * 10.0;
+///
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct TimeStep(f64);
+
+impl TimeStep {
+  pub const ZERO: TimeStep = TimeStep(0.0);
+
+  pub fn from_secs_f64(seconds: f64) -> Self {
+    TimeStep(seconds)
+  }
+
+  pub fn from_millis(milliseconds: f64) -> Self {
+    TimeStep(milliseconds / 1000.0)
+  }
+
+  ///
+  /// Get the time step in seconds.
+  ///
+  pub fn as_secs_f64(&self) -> f64 {
+    self.0
+  }
+
+  ///
+  /// Get the time step in milliseconds.
+  ///
+  pub fn as_millis(&self) -> f64 {
+    self.0 * 1000.0
+  }
+
+  ///
+  /// Get the time step as a Duration.
+  ///
+  /// Negative time steps become a 0 Duration.
+  ///
+  pub fn as_duration(&self) -> Duration {
+    Duration::try_from_secs_f64(self.0).unwrap_or(Duration::ZERO)
+  }
+}
+
+impl From<Duration> for TimeStep {
+  fn from(duration: Duration) -> Self {
+    TimeStep(duration.as_secs_f64())
+  }
+}
+
+impl Deref for TimeStep {
+  type Target = f64;
+
+  fn deref(&self) -> &f64 {
+    &self.0
+  }
+}
+
+impl Add for TimeStep {
+  type Output = TimeStep;
+
+  fn add(self, other: TimeStep) -> TimeStep {
+    TimeStep(self.0 + other.0)
+  }
+}
+
+impl AddAssign for TimeStep {
+  fn add_assign(&mut self, other: TimeStep) {
+    self.0 += other.0;
+  }
+}
+
+impl Sub for TimeStep {
+  type Output = TimeStep;
+
+  fn sub(self, other: TimeStep) -> TimeStep {
+    TimeStep(self.0 - other.0)
+  }
+}
+
+impl Mul<f64> for TimeStep {
+  type Output = TimeStep;
+
+  fn mul(self, scale: f64) -> TimeStep {
+    TimeStep(self.0 * scale)
+  }
+}
+
+impl fmt::Display for TimeStep {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    // Forwarded so {:.3} and friends work.
+    fmt::Display::fmt(&self.0, f)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use crate::game::time_step::TimeStep;
+
+  #[test]
+  fn test_time_step_units() {
+    println!("--- BEGIN TIME STEP UNITS TEST ---");
+    let step = TimeStep::from(Duration::from_millis(50));
+    assert_eq!(step.as_secs_f64(), 0.05);
+    assert_eq!(step.as_millis(), 50.0);
+    assert_eq!(TimeStep::from_millis(50.0), step);
+    assert_eq!(step.as_duration(), Duration::from_millis(50));
+
+    // Arithmetic stays in seconds.
+    let mut total = TimeStep::ZERO;
+    for _ in 0..20 {
+      total += step;
+    }
+    assert!((total.as_secs_f64() - 1.0).abs() < 1e-9);
+    assert_eq!((step * 2.0).as_millis(), 100.0);
+    assert_eq!((step - step), TimeStep::ZERO);
+
+    // Deref for plain f64 math.
+    assert_eq!(*step * 10.0, 0.5);
+    assert_eq!(TimeStep::from_secs_f64(-1.0).as_duration(), Duration::ZERO);
+    assert_eq!(format!("{:.3}", step), "0.050");
+  }
+}
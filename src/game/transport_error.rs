@@ -0,0 +1,96 @@
+use std::{fmt, io};
+
+use message_io::network::SendStatus;
+
+///
+/// Something the network layer couldn't do, sorted by what to do about it.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportError {
+  // The socket is busy, like would-block or interrupted. Nothing is wrong, carry on.
+  Transient(String),
+  // Only this message can't go, like one too big for a packet.
+  Message(String),
+  // The connection is gone, the session goes with it.
+  Fatal(String),
+}
+
+impl fmt::Display for TransportError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TransportError::Transient(reason) => write!(f, "Busy, trying again later. {}", reason),
+      TransportError::Message(reason) => write!(f, "Message dropped. {}", reason),
+      TransportError::Fatal(reason) => write!(f, "Connection lost. {}", reason),
+    }
+  }
+}
+
+impl TransportError {
+  ///
+  /// Find out if a send went out.
+  ///
+  pub fn check_send(status: SendStatus) -> Result<(), TransportError> {
+    match status {
+      SendStatus::Sent => Ok(()),
+      // message_io's would-block, the socket isn't ready yet.
+      SendStatus::ResourceNotAvailable => Err(TransportError::Transient(
+        "The socket is not ready.".to_string(),
+      )),
+      SendStatus::MaxPacketSizeExceeded => Err(TransportError::Message(
+        "It's too big for a packet.".to_string(),
+      )),
+      SendStatus::ResourceNotFound => {
+        Err(TransportError::Fatal("The socket is closed.".to_string()))
+      }
+    }
+  }
+
+  ///
+  /// Sort an error from the OS.
+  ///
+  pub fn from_io_error(error: &io::Error) -> TransportError {
+    match error.kind() {
+      io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted | io::ErrorKind::TimedOut => {
+        TransportError::Transient(error.to_string())
+      }
+      _ => TransportError::Fatal(error.to_string()),
+    }
+  }
+
+  pub fn is_transient(&self) -> bool {
+    matches!(self, TransportError::Transient(_))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io;
+
+  use message_io::network::SendStatus;
+
+  use crate::game::transport_error::TransportError;
+
+  #[test]
+  fn test_transport_error_sorting() {
+    println!("--- BEGIN TRANSPORT ERROR SORTING TEST ---");
+    assert_eq!(TransportError::check_send(SendStatus::Sent), Ok(()));
+    let is_transient = |status| match TransportError::check_send(status) {
+      Ok(()) => panic!("Unit test is broken. {:?} was sent.", status),
+      Err(e) => e.is_transient(),
+    };
+    assert!(is_transient(SendStatus::ResourceNotAvailable));
+    assert!(!is_transient(SendStatus::MaxPacketSizeExceeded));
+    assert!(matches!(
+      TransportError::check_send(SendStatus::ResourceNotFound),
+      Err(TransportError::Fatal(_))
+    ));
+
+    for kind in [io::ErrorKind::WouldBlock, io::ErrorKind::Interrupted] {
+      assert!(TransportError::from_io_error(&io::Error::from(kind)).is_transient());
+    }
+    assert!(matches!(
+      TransportError::from_io_error(&io::Error::from(io::ErrorKind::ConnectionRefused)),
+      TransportError::Fatal(_)
+    ));
+  }
+}
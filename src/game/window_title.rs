@@ -0,0 +1,177 @@
+use super::game_config::GameConfig;
+
+///
+/// The window title format used when minetest.conf doesn't have one.
+///
+pub const DEFAULT_TITLE_FORMAT: &str = "minetest | {fps} FPS";
+
+///
+/// The window title when show_fps_in_title is off.
+///
+pub const STATIC_TITLE: &str = "minetest";
+
+///
+/// Keeps track of what the window title should be.
+///
+/// With show_fps_in_title on, the title gets rewritten every time a new
+/// FPS measurement comes in. With it off the title is set to STATIC_TITLE
+/// once and left alone after that.
+///
+pub struct WindowTitle {
+  format: String,
+  show_fps: bool,
+  // If STATIC_TITLE already went out since show_fps was turned off.
+  static_title_set: bool,
+}
+
+impl WindowTitle {
+  pub fn new(format: &str, show_fps: bool) -> Self {
+    WindowTitle {
+      format: format.to_string(),
+      show_fps,
+      static_title_set: false,
+    }
+  }
+
+  ///
+  /// Read window_title_format and show_fps_in_title out of minetest.conf.
+  ///
+  pub fn from_config(config: &GameConfig) -> Self {
+    WindowTitle::new(
+      &config.get_string("window_title_format", DEFAULT_TITLE_FORMAT),
+      config.get_bool("show_fps_in_title", true),
+    )
+  }
+
+  pub fn set_format(&mut self, format: &str) {
+    self.format = format.to_string();
+  }
+
+  pub fn set_show_fps(&mut self, show_fps: bool) {
+    if show_fps != self.show_fps {
+      self.static_title_set = false;
+    }
+    self.show_fps = show_fps;
+  }
+
+  pub fn get_show_fps(&self) -> bool {
+    self.show_fps
+  }
+
+  ///
+  /// Get the new title, if it needs to change. Run this every frame.
+  ///
+  /// fps is only there on frames where a new measurement came in.
+  ///
+  pub fn update(&mut self, fps: Option<f64>, tps: f64) -> Option<String> {
+    if self.show_fps {
+      return fps.map(|fps| format_window_title(&self.format, fps, tps));
+    }
+    if self.static_title_set {
+      return None;
+    }
+    self.static_title_set = true;
+    Some(STATIC_TITLE.to_string())
+  }
+}
+
+///
+/// Fill in a window title format.
+///
+/// Known placeholders:
+/// {fps} - The current frames per second.
+/// {tps} - The ticks per second goal.
+/// {version} - The engine version.
+///
+/// Anything else in braces is left as is.
+///
+pub fn format_window_title(format: &str, fps: f64, tps: f64) -> String {
+  let mut title = String::with_capacity(format.len());
+  let mut remaining = format;
+
+  while let Some(start) = remaining.find('{') {
+    title.push_str(&remaining[..start]);
+    let placeholder_and_rest = &remaining[start..];
+
+    let end = match placeholder_and_rest.find('}') {
+      Some(end) => end,
+      // Unclosed, nothing left to substitute.
+      None => {
+        remaining = placeholder_and_rest;
+        break;
+      }
+    };
+
+    match &placeholder_and_rest[1..end] {
+      "fps" => title.push_str(&format!("{:.1}", fps)),
+      "tps" => title.push_str(&format!("{:.1}", tps)),
+      "version" => title.push_str(env!("CARGO_PKG_VERSION")),
+      _ => title.push_str(&placeholder_and_rest[..=end]),
+    }
+
+    remaining = &placeholder_and_rest[end + 1..];
+  }
+
+  // Whatever is left has no placeholders in it.
+  title.push_str(remaining);
+  title
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::game::window_title::{
+    format_window_title, WindowTitle, DEFAULT_TITLE_FORMAT, STATIC_TITLE,
+  };
+
+  #[test]
+  fn test_window_title_format() {
+    println!("--- BEGIN WINDOW TITLE FORMAT TEST ---");
+    assert_eq!(
+      format_window_title(DEFAULT_TITLE_FORMAT, 59.94, 20.0),
+      "minetest | 59.9 FPS"
+    );
+    assert_eq!(
+      format_window_title("{fps}/{tps} v{version}", 144.0, 20.0),
+      format!("144.0/20.0 v{}", env!("CARGO_PKG_VERSION"))
+    );
+
+    // Unknown and broken placeholders stay literal.
+    assert_eq!(
+      format_window_title("{server} {fps} {", 30.0, 20.0),
+      "{server} 30.0 {"
+    );
+    assert_eq!(
+      format_window_title("no placeholders", 30.0, 20.0),
+      "no placeholders"
+    );
+  }
+
+  #[test]
+  fn test_window_title_show_fps_off() {
+    println!("--- BEGIN WINDOW TITLE SHOW FPS OFF TEST ---");
+    let mut title = WindowTitle::new(DEFAULT_TITLE_FORMAT, false);
+
+    // Set once on the first frame, then never again.
+    assert_eq!(title.update(None, 20.0), Some(STATIC_TITLE.to_string()));
+    for frame in 0..120 {
+      let fps = match frame % 60 {
+        0 => Some(60.0),
+        _ => None,
+      };
+      assert_eq!(title.update(fps, 20.0), None);
+    }
+
+    // Turning it back on goes back to the FPS, once a measurement is in.
+    title.set_show_fps(true);
+    assert_eq!(title.update(None, 20.0), None);
+    assert_eq!(
+      title.update(Some(59.94), 20.0),
+      Some("minetest | 59.9 FPS".to_string())
+    );
+
+    // And off again sets the static title again.
+    title.set_show_fps(false);
+    assert_eq!(title.update(None, 20.0), Some(STATIC_TITLE.to_string()));
+    assert_eq!(title.update(Some(60.0), 20.0), None);
+  }
+}
@@ -1,12 +1,13 @@
 pub mod command_line;
 pub mod file_utilities;
 pub mod game;
+pub mod media_hash;
 
 use std::{cell::RefCell, ops::Deref, rc::Rc};
 
 use clap::Parser;
 use command_line::CommandLineInterface;
-use game::*;
+use game::{minetest_error::MinetestError, *};
 
 ///
 /// ! main()'s sole purpose is to encapsulate and initialize
@@ -19,10 +20,27 @@ fn main() {
   // That's why this is written like this.
   // The entry point is literally borrowing the game struct
   // for the lifetime of the game.
-  Rc::new(RefCell::new(Game::new(CommandLineInterface::parse())))
-    .deref()
-    .borrow_mut()
-    .enter_main_loop();
+  // Native plugins get registered here, see native_plugin.rs.
+  let plugins = native_plugin::PluginRegistry::new();
+  let game = match Game::try_new(CommandLineInterface::parse(), plugins) {
+    Ok(game) => game,
+    Err(e) => {
+      println!("minetest: failed to start. {}", MinetestError::from(e));
+      std::process::exit(1);
+    }
+  };
 
-  println!("minetest: shutdown procedure completed.");
+  let game = Rc::new(RefCell::new(game));
+  game.deref().borrow_mut().start_console();
+  let result = game.deref().borrow_mut().enter_main_loop();
+  // Everything gets dropped before exiting, exit() wouldn't.
+  drop(game);
+
+  match result {
+    Ok(()) => println!("minetest: shutdown procedure completed."),
+    Err(e) => {
+      println!("minetest: shut down after an error. {}", e);
+      std::process::exit(1);
+    }
+  }
 }
@@ -0,0 +1,35 @@
+use sha1::{Digest, Sha1};
+
+///
+/// Get the SHA1 hash of some data as a lowercase hex string.
+///
+/// Media is addressed by this on both ends. The server's MediaIndex
+/// files it's media under it, and the client's MediaCache stores and
+/// checks what it downloads with it. They have to agree byte for byte.
+///
+pub fn hash_bytes(data: &[u8]) -> String {
+  let mut hasher = Sha1::new();
+  hasher.update(data);
+
+  let mut hash = String::with_capacity(40);
+  for byte in hasher.finalize() {
+    hash.push_str(&format!("{:02x}", byte));
+  }
+
+  hash
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::media_hash::hash_bytes;
+
+  #[test]
+  fn test_media_hash() {
+    println!("--- BEGIN MEDIA HASH TEST ---");
+    assert_eq!(
+      hash_bytes(b"abc"),
+      "a9993e364706816aba3e25717850c26c9cd0d89d"
+    );
+    assert_eq!(hash_bytes(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+  }
+}